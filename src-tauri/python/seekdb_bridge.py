@@ -37,12 +37,16 @@ except Exception as e:
     traceback.print_exc(file=sys.stderr)
     sys.exit(1)
 
+# Command protocol version; must match BRIDGE_PROTOCOL_VERSION in python_subprocess.rs
+PROTOCOL_VERSION = 3
+
 class SeekDBBridge:
     def __init__(self):
         self.conn = None
         self.cursor = None
         self.db_path = None
         self.db_name = None
+        self.rerankers = {}
         
     def log(self, msg: str):
         """Log to stderr (stdout is reserved for responses)"""
@@ -293,6 +297,63 @@ class SeekDBBridge:
         """Health check"""
         self.send_success({"message": "pong"})
     
+    def handle_version(self, params: Dict[str, Any]):
+        """Protocol handshake"""
+        client_version = (params or {}).get("protocol_version")
+        if client_version is not None and client_version != PROTOCOL_VERSION:
+            self.log(f"Protocol version mismatch: client={client_version}, bridge={PROTOCOL_VERSION}")
+        self.send_success({
+            "protocol_version": PROTOCOL_VERSION,
+            "seekdb_version": self.seekdb_version(),
+        })
+    
+    @staticmethod
+    def seekdb_version() -> Optional[str]:
+        """Installed seekdb package version, if it can be determined"""
+        version = getattr(seekdb, "__version__", None)
+        if version:
+            return str(version)
+        try:
+            from importlib.metadata import version as package_version
+            return package_version("seekdb")
+        except Exception:
+            return None
+    
+    def handle_rerank(self, params: Dict[str, Any]):
+        """Score (query, document) pairs with a local cross-encoder reranker.
+
+        Returns raw logits; the Rust side maps them to 0..1 with a sigmoid."""
+        try:
+            model_name = params["model"]
+            query = params["query"]
+            documents = params.get("documents") or []
+            if not documents:
+                self.send_success({"scores": []})
+                return
+
+            model = self.rerankers.get(model_name)
+            if model is None:
+                from sentence_transformers import CrossEncoder
+                self.log(f"Loading reranker model: {model_name}")
+                model = CrossEncoder(model_name)
+                self.rerankers[model_name] = model
+
+            import torch
+            pairs = [(query, doc) for doc in documents]
+            try:
+                scores = model.predict(pairs, activation_fct=torch.nn.Identity())
+            except TypeError:
+                # sentence-transformers >= 4 renamed the argument
+                scores = model.predict(pairs, activation_fn=torch.nn.Identity())
+            self.send_success({"scores": [float(score) for score in scores]})
+
+        except ImportError as e:
+            self.log(f"Rerank error: {e}")
+            self.send_error("RerankError", f"sentence-transformers is not installed: {e}")
+        except Exception as e:
+            self.log(f"Rerank error: {e}")
+            self.send_error("RerankError", str(e))
+    
     def handle_command(self, command: Dict[str, Any]):
         """Route command to appropriate handler"""
         cmd_type = command.get("command")
@@ -306,6 +367,8 @@ class SeekDBBridge:
             "commit": self.handle_commit,
             "rollback": self.handle_rollback,
             "ping": self.handle_ping,
+            "version": self.handle_version,
+            "rerank": self.handle_rerank,
         }
         
         handler = handlers.get(cmd_type)
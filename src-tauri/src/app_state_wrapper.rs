@@ -1,16 +1,68 @@
 use crate::services::app_state::AppState;
+use crate::utils::cancellation::CancellationToken;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// 启动后台初始化任务的回调：接收本次初始化的取消令牌和运行标志（任务结束时应置为 false）
+pub type InitLauncher = Arc<dyn Fn(CancellationToken, Arc<AtomicBool>) + Send + Sync>;
+
+/// 后台初始化控制器，支持取消和重新触发初始化
+#[derive(Default)]
+pub struct InitController {
+    launcher: Option<InitLauncher>,
+    token: Option<CancellationToken>,
+    running: Arc<AtomicBool>,
+}
+
+impl InitController {
+    pub fn set_launcher(&mut self, launcher: InitLauncher) {
+        self.launcher = Some(launcher);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// 启动一次新的初始化；已有初始化在运行时返回 false
+    pub fn start(&mut self) -> Result<bool, String> {
+        if self.is_running() {
+            return Ok(false);
+        }
+
+        let launcher = self.launcher.clone()
+            .ok_or_else(|| "初始化任务尚未就绪".to_string())?;
+
+        let token = CancellationToken::new();
+        self.token = Some(token.clone());
+        self.running.store(true, Ordering::SeqCst);
+        launcher(token, self.running.clone());
+        Ok(true)
+    }
+
+    /// 取消正在运行的初始化；没有运行中的初始化时返回 false
+    pub fn cancel(&self) -> bool {
+        match &self.token {
+            Some(token) if self.is_running() => {
+                token.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 /// 应用状态包装器，支持延迟初始化
 pub struct AppStateWrapper {
     pub state: Arc<Mutex<Option<AppState>>>,
+    pub init: std::sync::Mutex<InitController>,
 }
 
 impl AppStateWrapper {
     pub fn new() -> Self {
         Self {
             state: Arc::new(Mutex::new(None)),
+            init: std::sync::Mutex::new(InitController::default()),
         }
     }
     
@@ -23,6 +75,12 @@ impl AppStateWrapper {
                 document_service: state.document_service.clone(),
                 conversation_service: state.conversation_service.clone(),
                 llm_client: state.llm_client.clone(),
+                chat_config: state.chat_config.clone(),
+                ingestion_config: state.ingestion_config.clone(),
+                retrieval_config: state.retrieval_config.clone(),
+                task_registry: state.task_registry.clone(),
+                generation_registry: state.generation_registry.clone(),
+                health_cache: state.health_cache.clone(),
             }),
             None => Err("应用正在初始化，请稍候...".to_string()),
         }
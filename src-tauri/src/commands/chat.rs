@@ -1,6 +1,23 @@
 use serde::{Deserialize, Serialize};
-use tauri::command;
-use crate::models::conversation::MessageRole;
+use tauri::{command, AppHandle};
+use crate::commands::documents::{process_single_document, refresh_project_document_count};
+use crate::config::ScoreFooterMode;
+use crate::models::conversation::{ContextChunk, Message, MessageRole};
+use crate::services::conversation_export::{self, ExportFormat};
+use crate::services::conversation_service;
+use crate::services::document_service::{self, ProjectWeight};
+use crate::services::pasted_document;
+use crate::services::prompts;
+use crate::services::query_filter::{self, QueryPrecheck};
+use crate::services::reranker;
+use crate::services::response_filter;
+use crate::services::retrieval_log::{self, RetrievalLogRecord};
+use crate::services::sentence_buffer::SentenceBuffer;
+use crate::services::task_registry::TaskKind;
+use crate::services::usage_report::{self, UsageKind};
+use crate::utils::content_hash::{content_hash, HashAlgorithm};
+use crate::utils::heartbeat::{Heartbeat, HEARTBEAT_INTERVAL};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,14 +34,170 @@ pub struct ConversationResponse {
     pub created_at: String,
     pub updated_at: String,
     pub message_count: u32,
+    /// 对话级别的模型覆盖（None 表示使用默认模型）
+    pub model: Option<String>,
+    /// 最后一条消息的预览（空对话为 None）
+    pub last_message_preview: Option<String>,
+    pub last_message_role: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SendMessageRequest {
+    /// 目标对话；不指定时在 `project_id` 下自动创建新对话（需开启 chat.autoCreateConversation）
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// 自动创建对话时所属的项目
+    #[serde(default)]
+    pub project_id: Option<String>,
+    pub content: String,
+    /// 按句子发送 chat-stream-token 事件（不指定时使用配置 chat.sentenceFlush）
+    #[serde(default)]
+    pub sentence_flush: Option<bool>,
+    /// 检索完成后逐个发送 chat-stream-source 事件（不指定时使用配置 chat.incrementalSources）
+    #[serde(default)]
+    pub incremental_sources: Option<bool>,
+    /// 同时检索的其他项目及权重（分数乘数），对话所属项目默认权重为 1.0，也可在此覆盖
+    #[serde(default)]
+    pub extra_projects: Option<Vec<ProjectWeight>>,
+    /// 本次回复使用的 temperature（0.0-2.0，不指定时沿用配置）
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// 本次回复的最大 token 数（不超过模型的最大输出，不指定时沿用配置）
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// 只检索元数据匹配的文档块，如 {"filename": "report.pdf"} 只针对一个文档提问
+    #[serde(default)]
+    pub metadata_filters: Option<HashMap<String, String>>,
+}
+
+/// 参与检索的项目：对话所属项目（权重 1.0，可被覆盖）加上额外指定的项目
+fn retrieval_projects(project_id: &str, extra_projects: &[ProjectWeight]) -> Vec<ProjectWeight> {
+    let mut projects = Vec::with_capacity(extra_projects.len() + 1);
+    if !extra_projects.iter().any(|p| p.project_id == project_id) {
+        projects.push(ProjectWeight { project_id: project_id.to_string(), weight: 1.0 });
+    }
+    for project in extra_projects {
+        if !projects.iter().any(|p: &ProjectWeight| p.project_id == project.project_id) {
+            projects.push(project.clone());
+        }
+    }
+    projects
+}
+
+/// 项目权重是分数乘数，必须是有限的非负数（NaN 会让排序结果不确定，负数会把相关结果排到最后）
+fn validate_project_weights(projects: &[ProjectWeight]) -> Result<(), String> {
+    match projects.iter().find(|p| !p.weight.is_finite() || p.weight < 0.0) {
+        Some(project) => Err(format!("项目 {} 的权重无效: {}，应为非负数", project.project_id, project.weight)),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendMessageResponse {
     pub conversation_id: String,
+    /// 本次请求是否自动创建了对话
+    pub conversation_created: bool,
     pub content: String,
 }
 
+/// send_message 的目标对话
+#[derive(Debug, PartialEq)]
+enum ConversationTarget {
+    Existing(Uuid),
+    Create { project_id: Uuid },
+}
+
+/// 根据请求确定目标对话：指定了 conversation_id 时使用已有对话，否则（开启自动创建时）在 project_id 下新建
+fn resolve_conversation_target(
+    conversation_id: Option<&str>,
+    project_id: Option<&str>,
+    auto_create: bool,
+) -> Result<ConversationTarget, String> {
+    if let Some(conversation_id) = conversation_id.filter(|id| !id.trim().is_empty()) {
+        let conversation_uuid = Uuid::parse_str(conversation_id)
+            .map_err(|e| format!("无效的对话ID: {}", e))?;
+        return Ok(ConversationTarget::Existing(conversation_uuid));
+    }
+
+    if !auto_create {
+        return Err("缺少对话ID（未开启 chat.autoCreateConversation）".to_string());
+    }
+
+    let project_id = project_id.ok_or_else(|| "缺少对话ID时必须指定项目ID".to_string())?;
+    let project_uuid = Uuid::parse_str(project_id)
+        .map_err(|e| format!("无效的项目ID: {}", e))?;
+    Ok(ConversationTarget::Create { project_id: project_uuid })
+}
+
+/// 用户消息保存之前失败时删除本次请求自动创建的对话，不留下空的「新对话」
+async fn discard_created_conversation(
+    state: &crate::services::app_state::AppState,
+    conversation_id: Uuid,
+    created: bool,
+) {
+    if !created {
+        return;
+    }
+    let conversation_service = state.conversation_service();
+    let mut conversation_service_guard = conversation_service.lock().await;
+    match conversation_service_guard.delete_conversation(conversation_id).await {
+        Ok(_) => log::info!("🗑️  [CHAT] 已删除未保存消息的自动创建对话: {}", conversation_id),
+        Err(e) => log::warn!("⚠️  删除自动创建的对话失败 {}: {}", conversation_id, e),
+    }
+}
+
+/// 自动创建的对话以第一条消息的开头作为标题
+fn auto_conversation_title(first_message: &str) -> String {
+    const MAX_TITLE_CHARS: usize = 30;
+    let collapsed = first_message.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() <= MAX_TITLE_CHARS {
+        collapsed
+    } else {
+        format!("{}…", collapsed.chars().take(MAX_TITLE_CHARS).collect::<String>().trim_end())
+    }
+}
+
+/// 自动生成的标题最多包含的词数
+const MAX_TITLE_WORDS: usize = 8;
+
+/// 清理模型生成的标题：取第一行，去掉"标题："前缀、引号、Markdown 标记和结尾标点，最多保留 8 个词
+fn clean_generated_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = ["标题：", "标题:", "Title:"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .unwrap_or(line);
+    let is_wrapper = |c: char| c.is_whitespace() || "\"'“”‘’《》「」*#`".contains(c);
+    let line = line
+        .trim_start_matches(is_wrapper)
+        .trim_end_matches(|c: char| is_wrapper(c) || "。.!！?？".contains(c));
+
+    // 中文标题没有空格分词，另外限制字符数
+    const MAX_TITLE_CHARS: usize = 60;
+    let words: Vec<&str> = line.split_whitespace().take(MAX_TITLE_WORDS).collect();
+    let title: String = words.join(" ").chars().take(MAX_TITLE_CHARS).collect();
+    (!title.is_empty()).then_some(title)
+}
+
+/// 使用模型生成的标题；生成失败或结果为空时退回到截断的第一条用户消息，保证标题不为空
+fn title_or_fallback(generated: anyhow::Result<String>, first_user_message: &str) -> String {
+    match generated {
+        Ok(raw) => match clean_generated_title(&raw) {
+            Some(title) => return title,
+            None => log::warn!("⚠️  模型返回的标题为空，使用第一条消息作为标题"),
+        },
+        Err(e) => log::warn!("⚠️  生成标题失败，使用第一条消息作为标题: {}", e),
+    }
+    auto_conversation_title(first_user_message)
+}
+
+/// 第一轮对话：第一条用户消息及其后的第一条助手回复
+fn first_exchange(messages: &[crate::models::conversation::Message]) -> Option<(&crate::models::conversation::Message, Option<&crate::models::conversation::Message>)> {
+    let user_index = messages.iter().position(|m| m.role == MessageRole::User)?;
+    let reply = messages[user_index + 1..].iter().find(|m| m.role == MessageRole::Assistant);
+    Some((&messages[user_index], reply))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MessageResponse {
     pub id: String,
@@ -35,10 +208,30 @@ pub struct MessageResponse {
     pub sources: Option<Vec<SourceResponse>>,
 }
 
+/// get_conversation_history 默认返回的消息数
+const DEFAULT_HISTORY_PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversationHistoryResponse {
+    /// 本页消息，按时间升序
+    pub messages: Vec<MessageResponse>,
+    /// 对话的消息总数
+    pub total: usize,
+    /// 是否还有更早的消息
+    pub has_more: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SourceResponse {
     pub filename: String,
     pub relevance_score: f64,
+    /// 来源文档所在的项目（跨项目检索时用于区分）
+    pub project_id: Option<String>,
+    /// 来源块所属的章节（文档中最近的标题）
+    pub section: Option<String>,
+    /// 包含相同内容的其他文档（按内容去重后合并到该来源）
+    #[serde(default)]
+    pub other_filenames: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -57,6 +250,19 @@ pub struct ClearMessagesRequest {
     pub conversation_id: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrimConversationRequest {
+    pub conversation_id: String,
+    /// 保留最近的消息条数
+    pub keep_last_n: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchConversationMessagesRequest {
+    pub conversation_id: String,
+    pub query: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RenameConversationRequest {
     pub conversation_id: String,
@@ -114,6 +320,9 @@ pub async fn create_conversation(
         created_at: conversation.created_at.to_rfc3339(),
         updated_at: conversation.updated_at.to_rfc3339(),
         message_count: conversation.message_count,
+        model: conversation.model,
+        last_message_preview: None,
+        last_message_role: None,
     };
 
     log::info!("对话创建成功: {:?}", response);
@@ -143,13 +352,20 @@ pub async fn get_conversations(
         // 立即转换为 owned 数据，避免生命周期问题
         conversations
             .iter()
-            .map(|conv| ConversationResponse {
-                id: conv.id.to_string(),
-                project_id: conv.project_id.to_string(),
-                title: conv.title.clone(),
-                created_at: conv.created_at.to_rfc3339(),
-                updated_at: conv.updated_at.to_rfc3339(),
-                message_count: conv.message_count,
+            .map(|conv| {
+                let last_message = conversation_service_guard.get_last_message(conv.id);
+                ConversationResponse {
+                    id: conv.id.to_string(),
+                    project_id: conv.project_id.to_string(),
+                    title: conv.title.clone(),
+                    created_at: conv.created_at.to_rfc3339(),
+                    updated_at: conv.updated_at.to_rfc3339(),
+                    message_count: conv.message_count,
+                    model: conv.model.clone(),
+                    last_message_preview: last_message
+                        .map(|msg| msg.preview(crate::services::conversation_service::LAST_MESSAGE_PREVIEW_CHARS)),
+                    last_message_role: last_message.map(|msg| msg.role.to_string().to_lowercase()),
+                }
             })
             .collect::<Vec<ConversationResponse>>()
     };
@@ -158,12 +374,18 @@ pub async fn get_conversations(
     Ok(responses)
 }
 
+/// 获取对话历史：默认返回最新的 `DEFAULT_HISTORY_PAGE_SIZE` 条消息，
+/// `offset` 为已加载的（较新的）消息数，用于向前加载更早的消息
 #[command]
 pub async fn get_conversation_history(
     conversation_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<Vec<MessageResponse>, String> {
-    log::info!("获取对话历史: {}", conversation_id);
+) -> Result<ConversationHistoryResponse, String> {
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_PAGE_SIZE).max(1);
+    log::info!("获取对话历史: {}, offset={}, limit={}", conversation_id, offset, limit);
 
     // 获取应用状态
     let state = wrapper.get_state().await?;
@@ -173,11 +395,12 @@ pub async fn get_conversation_history(
         .map_err(|e| format!("无效的对话ID: {}", e))?;
 
     // 获取消息列表
-    let messages = {
+    let (messages, total) = {
         let conversation_service = state.conversation_service();
-        let conversation_service_guard = conversation_service.lock().await;
+        let mut conversation_service_guard = conversation_service.lock().await;
         conversation_service_guard
-            .get_conversation_messages(conversation_uuid)
+            .get_conversation_messages(conversation_uuid, Some(offset), Some(limit))
+            .await
             .map_err(|e| format!("获取对话历史失败: {}", e))?
     };
 
@@ -193,13 +416,55 @@ pub async fn get_conversation_history(
                 sources.iter().map(|s| SourceResponse {
                     filename: s.filename.clone(),
                     relevance_score: s.relevance_score,
+                    project_id: s.project_id.clone(),
+                    section: s.section.clone(),
+                    other_filenames: s.other_filenames.clone(),
                 }).collect()
             }),
         })
         .collect();
 
-    log::info!("找到 {} 条消息", responses.len());
-    Ok(responses)
+    log::info!("找到 {}/{} 条消息", responses.len(), total);
+    Ok(ConversationHistoryResponse {
+        has_more: offset + responses.len() < total,
+        messages: responses,
+        total,
+    })
+}
+
+/// 不调用 LLM，直接保存一条助手回复并按流式事件发送给前端
+async fn reply_without_llm(
+    state: &crate::services::app_state::AppState,
+    window: &tauri::Window,
+    conversation_uuid: Uuid,
+    conversation_created: bool,
+    content: String,
+) -> Result<SendMessageResponse, String> {
+    {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .add_message(conversation_uuid, MessageRole::Assistant, content.clone())
+            .await
+            .map_err(|e| format!("保存 AI 消息失败: {}", e))?;
+    }
+
+    let conversation_id = conversation_uuid.to_string();
+    let _ = window.emit("chat-stream-start", conversation_id.clone());
+    let _ = window.emit("chat-stream-token", serde_json::json!({
+        "conversation_id": conversation_id,
+        "token": content
+    }));
+    let _ = window.emit("chat-stream-end", serde_json::json!({
+        "conversation_id": conversation_id,
+        "content": content.clone()
+    }));
+
+    Ok(SendMessageResponse {
+        conversation_id,
+        conversation_created,
+        content,
+    })
 }
 
 #[command]
@@ -207,24 +472,67 @@ pub async fn send_message(
     request: SendMessageRequest,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
     window: tauri::Window,
-) -> Result<String, String> {
+    app_handle: AppHandle,
+) -> Result<SendMessageResponse, String> {
     log::info!("发送消息请求: {:?}", request);
 
+    validate_project_weights(request.extra_projects.as_deref().unwrap_or_default())?;
+
     // 获取应用状态
     let state = wrapper.get_state().await?;
 
-    // 验证 conversation_id
-    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
-        .map_err(|e| format!("无效的对话ID: {}", e))?;
+    // 确定目标对话（未指定时自动创建）
+    let target = resolve_conversation_target(
+        request.conversation_id.as_deref(),
+        request.project_id.as_deref(),
+        state.chat_config().auto_create_conversation,
+    )?;
+    let conversation_created = matches!(target, ConversationTarget::Create { .. });
+
+    // 自动创建对话前先完成能提前做的校验，避免请求被拒绝时留下空的对话
+    state
+        .llm_client()
+        .lock()
+        .await
+        .with_temperature(request.temperature)
+        .and_then(|client| client.with_max_tokens(request.max_tokens))
+        .map_err(|e| format!("无效的生成参数: {}", e))?;
+    if let ConversationTarget::Create { project_id } = target {
+        if state.project_service().lock().await.get_project(project_id).is_none() {
+            return Err(format!("项目不存在: {}", project_id));
+        }
+    }
+
+    let conversation_uuid = match target {
+        ConversationTarget::Existing(conversation_uuid) => conversation_uuid,
+        ConversationTarget::Create { project_id } => {
+            let conversation_service = state.conversation_service();
+            let mut conversation_service_guard = conversation_service.lock().await;
+            let conversation_uuid = conversation_service_guard
+                .create_conversation(project_id, Some(auto_conversation_title(&request.content)))
+                .await
+                .map_err(|e| format!("创建对话失败: {}", e))?;
+            log::info!("🆕 [CHAT] 自动创建对话: {}", conversation_uuid);
+            let _ = window.emit("chat-conversation-created", serde_json::json!({
+                "conversation_id": conversation_uuid.to_string(),
+                "project_id": project_id.to_string()
+            }));
+            conversation_uuid
+        }
+    };
+    let conversation_id = conversation_uuid.to_string();
+
+    // 登记本次生成，cancel_generation 可以在流式响应期间停止它
+    let generation = state.generation_registry().start(conversation_uuid);
 
-    // 获取对话信息和项目ID
-    let project_id = {
+    // 获取对话信息、项目ID和模型覆盖
+    let (project_id, conversation_model) = {
         let conversation_service = state.conversation_service();
         let conversation_service_guard = conversation_service.lock().await;
         let conversation = conversation_service_guard
             .get_conversation(conversation_uuid)
             .ok_or_else(|| "对话不存在".to_string())?;
-        conversation.project_id
+        (conversation.project_id, conversation.model.clone())
     };
 
     log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
@@ -235,26 +543,165 @@ pub async fn send_message(
     log::info!("💬 用户消息: {}", request.content);
     log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
+    // 超长消息（如粘贴的整篇文档）按配置导入为知识库文档，对话中只保留说明
+    let routed = {
+        let chat_config = state.chat_config();
+        let document_service = state.document_service();
+        let app_data_dir = app_handle.path_resolver().app_data_dir();
+        pasted_document::route_user_message(
+            &request.content,
+            chat_config.ingest_long_messages,
+            chat_config.long_message_threshold,
+            |content| async move {
+                let app_data_dir = app_data_dir.ok_or_else(|| anyhow::anyhow!("无法获取应用数据目录"))?;
+                let path = pasted_document::write_document(
+                    &pasted_document::pasted_documents_dir(&app_data_dir),
+                    &content,
+                    chrono::Utc::now(),
+                )?;
+                let (_, filename, ..) = process_single_document(
+                    project_id,
+                    path.to_string_lossy().to_string(),
+                    document_service,
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!(e))?;
+                Ok(filename)
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())
+    };
+    let routed = match routed {
+        Ok(routed) => routed,
+        Err(e) => {
+            discard_created_conversation(&state, conversation_uuid, conversation_created).await;
+            return Err(e);
+        }
+    };
+
     // 1. 保存用户消息
     log::info!("💾 [CHAT] 步骤 1/5: 保存用户消息到数据库");
-    {
+    let saved = {
         let conversation_service = state.conversation_service();
         let mut conversation_service_guard = conversation_service.lock().await;
         conversation_service_guard
-            .add_message(conversation_uuid, MessageRole::User, request.content.clone())
+            .add_message(conversation_uuid, MessageRole::User, routed.stored_content.clone())
             .await
-            .map_err(|e| format!("保存用户消息失败: {}", e))?;
+            .map_err(|e| format!("保存用户消息失败: {}", e))
+    };
+    if let Err(e) = saved {
+        discard_created_conversation(&state, conversation_uuid, conversation_created).await;
+        return Err(e);
     }
     log::info!("✅ [CHAT] 用户消息已保存");
 
+    if let Some(filename) = routed.ingested_document {
+        refresh_project_document_count(&state, project_id).await;
+        log::info!("📄 [CHAT] 粘贴的长文本已作为文档导入: {}", filename);
+        return reply_without_llm(
+            &state,
+            &window,
+            conversation_uuid,
+            conversation_created,
+            pasted_document::ingestion_reply(&filename),
+        )
+        .await;
+    }
+
+    // 查询预检：只包含停用词/标点的查询不值得调用 Embedding API
+    let precheck = query_filter::precheck_query(
+        &request.content,
+        state.chat_config().trivial_query_action,
+    );
+
+    if precheck == QueryPrecheck::AskRephrase {
+        log::info!("⚠️  [CHAT] 查询只包含停用词或标点，提示用户重新表述");
+        return reply_without_llm(
+            &state,
+            &window,
+            conversation_uuid,
+            conversation_created,
+            query_filter::get_rephrase_message().to_string(),
+        )
+        .await;
+    }
+
+    // AI 回复的生成耗时（含检索），随消息一起保存
+    let started = std::time::Instant::now();
+
+    // 在第一个 token 到达前定期发送心跳，避免检索较慢时界面没有反馈
+    let mut heartbeat = {
+        let window = window.clone();
+        let conversation_id = conversation_id.clone();
+        Heartbeat::start(HEARTBEAT_INTERVAL, move |beat, elapsed| {
+            let _ = window.emit("chat-heartbeat", serde_json::json!({
+                "conversation_id": conversation_id,
+                "beat": beat,
+                "elapsed_ms": elapsed.as_millis() as u64
+            }));
+        })
+    };
+
     // 2. 向量检索：从知识库检索相关文档块（使用SeekDB向量搜索）
     log::info!("🔍 [CHAT] 步骤 2/5: 执行SeekDB向量检索");
-    let context_chunks = {
+    // nothing_matched：检索成功但没有块达到相似度阈值，而项目中其实有文档
+    let (context_chunks, nothing_matched) = if precheck == QueryPrecheck::SkipRetrieval {
+        log::info!("⏭️  [CHAT] 查询只包含停用词或标点，跳过向量检索");
+        (Vec::new(), false)
+    } else {
         let document_service = state.document_service();
         let document_service_guard = document_service.lock().await;
 
-        match document_service_guard.search_similar_chunks(&project_id.to_string(), &request.content, 5).await {
+        let retrieval = state.retrieval_config();
+        // 开启重排序时先召回更多候选，重排序后再取 top_k
+        let candidates = document_service_guard.retrieval_candidates(retrieval.top_k);
+        let extra_projects = request.extra_projects.as_deref().unwrap_or_default();
+        let projects = retrieval_projects(&project_id.to_string(), extra_projects);
+        let metadata_filters = request.metadata_filters.as_ref().filter(|filters| !filters.is_empty());
+        let search = async {
+            match request.extra_projects.as_deref().filter(|extra| !extra.is_empty()) {
+                Some(_) => {
+                    log::info!("🔀 [CHAT] 跨 {} 个项目加权检索", projects.len());
+                    document_service_guard.search_similar_chunks_weighted(&projects, metadata_filters, &request.content, candidates).await
+                }
+                None if retrieval.use_hybrid => {
+                    document_service_guard
+                        .search_similar_chunks_hybrid(&project_id.to_string(), metadata_filters, &request.content, candidates, retrieval.semantic_weight)
+                        .await
+                }
+                None => document_service_guard.search_similar_chunks(&project_id.to_string(), metadata_filters, &request.content, candidates).await,
+            }
+        };
+        // 检索期间被取消时不再等待 embedding 和向量检索
+        let search = tokio::select! {
+            biased;
+            _ = generation.cancelled() => Err(anyhow::anyhow!("生成已取消")),
+            search = search => search,
+        };
+
+        // embedding 服务不可用时降级为关键词检索，对话仍能引用文档
+        let search = match search {
+            Err(e) if retrieval.keyword_fallback && document_service::is_embedding_unavailable(&e) => {
+                log::warn!("⚠️  [CHAT] {}，降级为关键词检索", e);
+                let _ = window.emit("chat-retrieval-degraded", serde_json::json!({
+                    "conversation_id": conversation_id,
+                    "mode": "keyword",
+                    "message": e.to_string()
+                }));
+                document_service_guard.search_keyword_chunks(&projects, metadata_filters, &request.content, candidates).await
+            }
+            other => other,
+        };
+
+        match search {
             Ok(chunks) => {
+                let nothing_matched = chunks.is_empty()
+                    && document_service_guard.project_has_chunks(&project_id.to_string()).await.unwrap_or(false);
+                // 重排序可能较慢，释放 DocumentService 锁之后再进行
+                let reranker = document_service_guard.reranker();
+                drop(document_service_guard);
+                let chunks = reranker::rerank_chunks(reranker.as_deref(), &request.content, chunks, retrieval.top_k).await;
                 log::info!("✅ [CHAT] SeekDB向量检索成功，找到 {} 个相关文档块", chunks.len());
                 
                 // 打印每个文档块的详细信息
@@ -268,36 +715,52 @@ pub async fn send_message(
                         chunk.content.chars().take(100).collect::<String>()
                     );
                 }
-                
-                chunks.into_iter().map(|chunk| {
-                    crate::models::conversation::ContextChunk {
-                        document_id: chunk.document_id,
-                        filename: chunk.filename.unwrap_or_else(|| "未知文档".to_string()),
-                        content: chunk.content,
-                        relevance_score: chunk.relevance_score,
-                    }
-                }).collect::<Vec<_>>()
+
+                (into_context_chunks(chunks), nothing_matched)
             }
             Err(e) => {
                 log::warn!("⚠️  [CHAT] 混合检索失败: {}，将不使用上下文", e);
-                Vec::new()
+                (Vec::new(), false)
             }
         }
     };
     
+    let mut context_chunks = if state.chat_config().dedup_sources_by_content {
+        dedup_sources_by_content(context_chunks)
+    } else {
+        context_chunks
+    };
+
+    if generation.is_cancelled() {
+        log::info!("⏹️  [CHAT] 检索期间生成已被取消");
+        return Ok(cancelled_before_reply(&window, conversation_id, conversation_created));
+    }
+
     if context_chunks.is_empty() {
         log::warn!("⚠️  [CHAT] 没有找到相关文档，AI 将基于通用知识回答");
     } else {
         log::info!("✅ [CHAT] 将使用 {} 个文档块作为上下文", context_chunks.len());
     }
 
+    let low_confidence = emit_low_confidence(
+        &conversation_id,
+        &context_chunks,
+        nothing_matched,
+        state.chat_config().low_confidence_floor,
+        |event, payload| {
+            let _ = window.emit(event, payload);
+        },
+    );
+
     // 3. 获取对话历史
     log::info!("📜 [CHAT] 步骤 3/5: 获取对话历史");
     let messages = {
         let conversation_service = state.conversation_service();
-        let conversation_service_guard = conversation_service.lock().await;
+        let mut conversation_service_guard = conversation_service.lock().await;
         conversation_service_guard
-            .get_conversation_messages(conversation_uuid)
+            .get_conversation_messages(conversation_uuid, None, None)
+            .await
+            .map(|(messages, _)| messages)
             .map_err(|e| format!("获取对话历史失败: {}", e))?
     };
     log::info!("✅ [CHAT] 获取到 {} 条历史消息", messages.len());
@@ -319,56 +782,109 @@ pub async fn send_message(
     use crate::services::llm_client::StreamEvent;
 
     let mut response_content = String::new();
+    let mut cancelled = false;
+    let mut chat_usage = None;
 
     {
         let llm_client = state.llm_client();
-        let llm_client_guard = llm_client.lock().await;
-
-        let mut stream = llm_client_guard
-            .generate_response(&messages, &context_chunks)
+        let llm_client_guard = llm_client
+            .lock()
             .await
-            .map_err(|e| {
+            .with_model(conversation_model.as_deref())
+            .map_err(|e| format!("无效的对话模型: {}", e))?
+            .with_temperature(request.temperature)
+            .and_then(|client| client.with_max_tokens(request.max_tokens))
+            .map_err(|e| format!("无效的生成参数: {}", e))?
+            .with_low_confidence_caveat(low_confidence && state.chat_config().low_confidence_caveat);
+        if let Some(model) = &conversation_model {
+            log::info!("   使用对话指定的模型: {}", model);
+        }
+        let chat_model = llm_client_guard.get_config().model.clone();
+
+        // 超出提示词预算时丢弃的上下文块不会发给模型，也不作为来源保存和展示
+        context_chunks = llm_client_guard.fit_context_to_budget(&messages, &context_chunks);
+
+        // 来源已确定，逐个发送给界面；LLM 开始响应后仍会发送完整的来源列表
+        if request.incremental_sources.unwrap_or(state.chat_config().incremental_sources) {
+            emit_incremental_sources(&conversation_id, &context_chunks, |event, payload| {
+                let _ = window.emit(event, payload);
+            });
+        }
+
+        // 建立连接前的临时错误由 LlmClient 按 chat.generationRetries 自动重试（用户消息已保存，不会重复保存）
+        let connect = llm_client_guard.generate_response(&messages, &context_chunks);
+        let mut stream = tokio::select! {
+            biased;
+            _ = generation.cancelled() => {
+                log::info!("⏹️  [CHAT] 连接 LLM 期间生成已被取消");
+                return Ok(cancelled_before_reply(&window, conversation_id, conversation_created));
+            }
+            stream = connect => stream.map_err(|e| {
                 log::error!("❌ [CHAT] LLM 调用失败: {}", e);
                 format!("LLM 调用失败: {}", e)
-            })?;
+            })?,
+        };
         
         log::info!("✅ [CHAT] LLM 流式响应已建立");
 
         // 发送流式开始事件
-        let _ = window.emit("chat-stream-start", request.conversation_id.clone());
+        let _ = window.emit("chat-stream-start", conversation_id.clone());
 
         // 发送来源文档信息
         if !context_chunks.is_empty() {
-            let sources: Vec<serde_json::Value> = context_chunks.iter().map(|chunk| {
-                serde_json::json!({
-                    "filename": chunk.filename,
-                    "relevance_score": chunk.relevance_score,
-                })
-            }).collect();
+            let sources: Vec<serde_json::Value> = context_chunks.iter().map(source_json).collect();
 
             let _ = window.emit("chat-stream-context", serde_json::json!({
-                "conversation_id": request.conversation_id,
+                "conversation_id": conversation_id,
                 "sources": sources
             }));
         }
 
+        // 开启句子缓冲时按句发送，否则每个 token 立即发送
+        let sentence_flush = request.sentence_flush.unwrap_or(state.chat_config().sentence_flush);
+        let mut sentence_buffer = sentence_flush
+            .then(|| SentenceBuffer::new(state.chat_config().sentence_flush_max_chars));
+        let emit_token = |token: &str| {
+            let _ = window.emit("chat-stream-token", serde_json::json!({
+                "conversation_id": conversation_id,
+                "token": token
+            }));
+        };
+
         // 流式处理响应
         let mut token_count = 0;
-        while let Some(event) = stream.next().await {
+        loop {
+            // 等待下一个 token 时也响应取消，不必等到 LLM 返回下一个事件
+            let event = tokio::select! {
+                biased;
+                _ = generation.cancelled() => {
+                    log::info!("⏹️  [CHAT] 生成已被取消，保留已生成的 {} 个 token", token_count);
+                    cancelled = true;
+                    break;
+                }
+                event = stream.next() => event,
+            };
+            let Some(event) = event else {
+                break;
+            };
             match event {
                 StreamEvent::Token(token) => {
+                    heartbeat.stop();
                     response_content.push_str(&token);
                     token_count += 1;
 
-                    // 立即发送 token 到前端
-                    let _ = window.emit("chat-stream-token", serde_json::json!({
-                        "conversation_id": request.conversation_id,
-                        "token": token
-                    }));
+                    match sentence_buffer.as_mut() {
+                        Some(buffer) => buffer.push(&token).iter().for_each(|piece| emit_token(piece)),
+                        None => emit_token(&token),
+                    }
                 }
                 StreamEvent::Context(_) => {
                     log::debug!("   收到上下文信息");
                 }
+                StreamEvent::Usage(usage) => {
+                    log::info!("   token 用量: 输入 {} / 输出 {}", usage.prompt_tokens, usage.completion_tokens);
+                    chat_usage = Some((usage, chat_model.clone()));
+                }
                 StreamEvent::Complete(response_id) => {
                     log::info!("✅ [CHAT] LLM 响应完成: {}", response_id);
                     log::info!("   总 token 数: {}", token_count);
@@ -377,7 +893,7 @@ pub async fn send_message(
                 StreamEvent::Error(error) => {
                     log::error!("❌ [CHAT] 流式响应错误: {}", error);
                     let _ = window.emit("chat-stream-error", serde_json::json!({
-                        "conversation_id": request.conversation_id,
+                        "conversation_id": conversation_id,
                         "error": error.clone()
                     }));
                     return Err(format!("LLM 响应错误: {}", error));
@@ -385,13 +901,41 @@ pub async fn send_message(
             }
         }
         
+        if let Some(rest) = sentence_buffer.as_mut().and_then(SentenceBuffer::finish) {
+            emit_token(&rest);
+        }
+
         log::info!("🎉 [CHAT] 流式传输完成，共收到 {} 个 token", token_count);
     }
+    heartbeat.stop();
+
+    // 记录本次回答消耗的 tokens（服务端未报告用量时不记录）
+    if let Some((usage, model)) = chat_usage {
+        let vector_db = state.document_service().lock().await.get_vector_db();
+        usage_report::record_usage(&vector_db, &project_id.to_string(), UsageKind::Chat, usage.total_tokens, &model)
+            .await;
+    }
+
+    if response_content.is_empty() && cancelled {
+        return Ok(cancelled_before_reply(&window, conversation_id, conversation_created));
+    }
 
     if response_content.is_empty() {
         log::error!("❌ [CHAT] LLM 未返回有效响应");
         return Err("LLM 未返回有效响应".to_string());
     }
+
+    // 后处理：剥离模型包装标签等残留（流式 token 已原样发送，这里只清理最终保存的内容）
+    let cleaned = response_filter::clean_response(&response_content, &state.chat_config().strip_wrapper_tags);
+    if cleaned != response_content {
+        log::info!("🧹 [CHAT] 已清理回复中的模型残留: {} -> {} 字符", response_content.len(), cleaned.len());
+        response_content = cleaned;
+    }
+
+    let score_footer_mode = state.chat_config().score_footer;
+    if let Some(footer) = score_footer(score_footer_mode, &context_chunks) {
+        response_content.push_str(&footer);
+    }
     
     log::info!("📝 [CHAT] AI 响应内容预览: {}...", 
         response_content.chars().take(100).collect::<String>()
@@ -413,15 +957,23 @@ pub async fn send_message(
     
     log::info!("✅ [CHAT] AI 消息已保存，消息ID: {}", message_id);
 
-    // 如果有 sources，更新消息并保存到数据库
-    if !context_chunks.is_empty() {
-        log::info!("📎 [CHAT] 附加来源文档信息（{} 个）", context_chunks.len());
+    // 记录生成耗时，有 sources 时一并附加，然后更新消息到数据库
+    let processing_time = started.elapsed().as_secs_f64();
+    {
+        if context_chunks.is_empty() {
+            log::info!("ℹ️  [CHAT] 没有来源文档信息需要附加");
+        } else {
+            log::info!("📎 [CHAT] 附加来源文档信息（{} 个）", context_chunks.len());
+        }
         let conversation_service = state.conversation_service();
         let mut conversation_service_guard = conversation_service.lock().await;
 
         if let Some(message) = conversation_service_guard.get_message_mut(conversation_uuid, message_id) {
+            message.set_processing_time(processing_time);
             // 设置 sources
-            message.set_sources(context_chunks.clone());
+            if !context_chunks.is_empty() {
+                message.set_sources(context_chunks.clone());
+            }
 
             // 保存到数据库
             let message_clone = message.clone();
@@ -437,15 +989,36 @@ pub async fn send_message(
                     format!("更新消息 sources 失败: {}", e)
                 })?;
             
-            log::info!("✅ [CHAT] 来源文档信息已附加");
+            log::info!("✅ [CHAT] 消息已更新（耗时 {:.2}s）", processing_time);
         }
-    } else {
-        log::info!("ℹ️  [CHAT] 没有来源文档信息需要附加");
     }
 
-    // 在所有保存操作完成后，才发送流式结束事件
-    let _ = window.emit("chat-stream-end", serde_json::json!({
-        "conversation_id": request.conversation_id,
+    // 可选：记录本轮检索详情，用于离线评测（失败不影响对话）
+    if state.chat_config().retrieval_log_enabled {
+        if let Some(app_data_dir) = app_handle.path_resolver().app_data_dir() {
+            let record = RetrievalLogRecord::new(
+                &conversation_id,
+                &project_id.to_string(),
+                &request.content,
+                &context_chunks,
+                &response_content,
+            );
+            if let Err(e) = retrieval_log::append_record(&retrieval_log::log_path(&app_data_dir), &record) {
+                log::warn!("⚠️  [CHAT] 写入检索日志失败: {}", e);
+            }
+        }
+    }
+
+    if score_footer_mode == ScoreFooterMode::EventOnly {
+        emit_source_scores(&conversation_id, &context_chunks, |event, payload| {
+            let _ = window.emit(event, payload);
+        });
+    }
+
+    // 在所有保存操作完成后，才发送流式结束事件（被取消时发送 chat-stream-cancelled，内容为已保存的部分回复）
+    let end_event = if cancelled { "chat-stream-cancelled" } else { "chat-stream-end" };
+    let _ = window.emit(end_event, serde_json::json!({
+        "conversation_id": conversation_id,
         "content": response_content.clone()
     }));
 
@@ -456,95 +1029,554 @@ pub async fn send_message(
     log::info!("   使用了 {} 个上下文文档块", context_chunks.len());
     log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
     
-    Ok(response_content)
+    Ok(SendMessageResponse {
+        conversation_id,
+        conversation_created,
+        content: response_content,
+    })
 }
 
-#[command]
-pub async fn delete_conversation(
-    request: DeleteConversationRequest,
-    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<bool, String> {
-    log::info!("删除对话请求: {:?}", request);
+/// 来源事件中单个来源的内容
+fn source_json(chunk: &ContextChunk) -> serde_json::Value {
+    serde_json::json!({
+        "filename": chunk.filename,
+        "relevance_score": chunk.relevance_score,
+        "project_id": chunk.project_id,
+        "section": chunk.section,
+        "other_filenames": chunk.other_filenames,
+    })
+}
 
-    // 获取应用状态
-    let state = wrapper.get_state().await?;
+/// 逐个发送来源事件（chat-stream-source），界面可以在 LLM 响应之前逐步填充来源面板
+fn emit_incremental_sources<F>(conversation_id: &str, chunks: &[ContextChunk], mut emit: F)
+where
+    F: FnMut(&str, serde_json::Value),
+{
+    for (index, chunk) in chunks.iter().enumerate() {
+        emit("chat-stream-source", serde_json::json!({
+            "conversation_id": conversation_id,
+            "index": index,
+            "total": chunks.len(),
+            "source": source_json(chunk),
+        }));
+    }
+}
 
-    // 验证 conversation_id
-    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
-        .map_err(|e| format!("无效的对话ID: {}", e))?;
+/// 还没有收到任何内容就被取消：没有需要保存的回复，只通知界面
+fn cancelled_before_reply(window: &tauri::Window, conversation_id: String, conversation_created: bool) -> SendMessageResponse {
+    let _ = window.emit("chat-stream-cancelled", serde_json::json!({
+        "conversation_id": conversation_id,
+        "content": ""
+    }));
+    SendMessageResponse {
+        conversation_id,
+        conversation_created,
+        content: String::new(),
+    }
+}
 
-    // 删除对话
-    {
-        let conversation_service = state.conversation_service();
-        let mut conversation_service_guard = conversation_service.lock().await;
-        conversation_service_guard
-            .delete_conversation(conversation_uuid)
-            .await
-            .map_err(|e| format!("删除对话失败: {}", e))?;
+/// 最相关的来源低于下限时发送 chat-low-confidence，返回是否为低置信度
+///
+/// 低分块通常在检索时就已被相似度阈值过滤，所以项目有文档却没有任何块通过阈值时也会发送（`top_score` 为 null）；
+/// 跳过检索、检索失败或项目为空时不发送
+fn emit_low_confidence<F>(conversation_id: &str, chunks: &[ContextChunk], nothing_matched: bool, floor: f64, mut emit: F) -> bool
+where
+    F: FnMut(&str, serde_json::Value),
+{
+    let top_score = chunks.iter().map(|chunk| chunk.relevance_score).reduce(f64::max);
+    match top_score {
+        // 所有块都被相似度阈值过滤掉了：最高分必然低于阈值
+        None if nothing_matched => {
+            log::warn!("⚠️  [CHAT] 项目中有文档但没有块达到相似度阈值，知识库可能没有涵盖该问题");
+        }
+        Some(top_score) if top_score < floor => {
+            log::warn!("⚠️  [CHAT] 最高相关度 {:.4} 低于 {:.4}，知识库可能没有涵盖该问题", top_score, floor);
+        }
+        _ => return false,
     }
+    emit("chat-low-confidence", serde_json::json!({
+        "conversation_id": conversation_id,
+        "top_score": top_score,
+        "floor": floor,
+    }));
+    true
+}
 
-    log::info!("对话删除成功: {}", conversation_uuid);
-    Ok(true)
+/// 把相关度线性缩放到 0~1：缩放区间为 [min(0, 最低分), max(1, 最高分)]，
+/// 因此都在 0~1 内的向量相似度保持不变，关键词等超出 0~1 的分数按比例缩放且保持相对顺序
+fn normalize_scores(scores: &[f64]) -> Vec<f64> {
+    let low = scores.iter().copied().fold(0.0, f64::min);
+    let high = scores.iter().copied().fold(1.0, f64::max);
+    scores.iter().map(|score| (score - low) / (high - low)).collect()
 }
 
-#[command]
-pub async fn delete_message(
-    request: DeleteMessageRequest,
-    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<bool, String> {
-    log::info!("删除消息请求: {:?}", request);
+/// 各来源的归一化相关度（保留三位小数），顺序与来源列表一致
+fn source_scores(chunks: &[ContextChunk]) -> serde_json::Value {
+    let scores: Vec<f64> = chunks.iter().map(|chunk| chunk.relevance_score).collect();
+    chunks
+        .iter()
+        .zip(normalize_scores(&scores))
+        .enumerate()
+        .map(|(index, (chunk, score))| {
+            serde_json::json!({
+                "index": index,
+                "filename": chunk.filename,
+                "document_id": chunk.document_id,
+                "score": (score * 1000.0).round() / 1000.0,
+            })
+        })
+        .collect()
+}
 
-    // 获取应用状态
-    let state = wrapper.get_state().await?;
+/// Inline 模式下追加到助手消息末尾的页脚；其他模式或没有来源时返回 None
+///
+/// 页脚只写入保存的消息，不作为 token 发送；界面加载消息时去掉页脚并用其中的相关度显示来源
+fn score_footer(mode: ScoreFooterMode, chunks: &[ContextChunk]) -> Option<String> {
+    if mode != ScoreFooterMode::Inline || chunks.is_empty() {
+        return None;
+    }
+    Some(format!("\n\n{}{} -->", response_filter::SCORE_FOOTER_PREFIX, source_scores(chunks)))
+}
 
-    // 验证 conversation_id 和 message_id
-    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
-        .map_err(|e| format!("无效的对话ID: {}", e))?;
-    let message_uuid = Uuid::parse_str(&request.message_id)
-        .map_err(|e| format!("无效的消息ID: {}", e))?;
+/// 发送各来源的相关度（chat-stream-scores），没有来源时不发送
+fn emit_source_scores<F>(conversation_id: &str, chunks: &[ContextChunk], mut emit: F)
+where
+    F: FnMut(&str, serde_json::Value),
+{
+    if chunks.is_empty() {
+        return;
+    }
+    emit("chat-stream-scores", serde_json::json!({
+        "conversation_id": conversation_id,
+        "scores": source_scores(chunks),
+    }));
+}
 
-    // 删除消息
-    {
-        let conversation_service = state.conversation_service();
-        let mut conversation_service_guard = conversation_service.lock().await;
-        conversation_service_guard
-            .delete_message(conversation_uuid, message_uuid)
-            .await
-            .map_err(|e| format!("删除消息失败: {}", e))?;
+/// 把检索结果转换为消息上下文块
+fn into_context_chunks(
+    chunks: Vec<crate::services::document_service::SimilarChunk>,
+) -> Vec<crate::models::conversation::ContextChunk> {
+    chunks.into_iter().map(|chunk| {
+        crate::models::conversation::ContextChunk {
+            document_id: chunk.document_id,
+            filename: chunk.filename.unwrap_or_else(|| "未知文档".to_string()),
+            content: chunk.content,
+            relevance_score: chunk.relevance_score,
+            project_id: Some(chunk.project_id),
+            section: chunk.section,
+            other_filenames: Vec::new(),
+        }
+    }).collect()
+}
+
+/// 按内容哈希合并来源：不同文档中内容完全相同的块只保留分数最高的一个，
+/// 其他文档的文件名记录在 `other_filenames` 中
+fn dedup_sources_by_content(chunks: Vec<ContextChunk>) -> Vec<ContextChunk> {
+    let mut deduped: Vec<ContextChunk> = Vec::new();
+    let mut index_by_hash: HashMap<String, usize> = HashMap::new();
+
+    for chunk in chunks {
+        let hash = content_hash(chunk.content.trim().as_bytes(), HashAlgorithm::Sha256);
+        match index_by_hash.get(&hash) {
+            Some(&index) => merge_duplicate_source(&mut deduped[index], chunk),
+            None => {
+                index_by_hash.insert(hash, deduped.len());
+                deduped.push(chunk);
+            }
+        }
     }
 
-    log::info!("消息删除成功: {}", message_uuid);
-    Ok(true)
+    deduped
 }
 
+fn merge_duplicate_source(kept: &mut ContextChunk, duplicate: ContextChunk) {
+    // 保留分数较高的块作为来源
+    let other = if duplicate.relevance_score > kept.relevance_score {
+        std::mem::replace(kept, duplicate)
+    } else {
+        duplicate
+    };
+
+    for filename in std::iter::once(other.filename).chain(other.other_filenames) {
+        if filename != kept.filename && !kept.other_filenames.contains(&filename) {
+            kept.other_filenames.push(filename);
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RegenerateOverrides {
+    /// 本次生成使用的 temperature（不指定则沿用配置）
+    pub temperature: Option<f32>,
+    /// 检索的文档块数量（不指定则使用 retrieval.topK）
+    pub top_k: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateMessageRequest {
+    pub conversation_id: String,
+    pub message_id: String,
+    #[serde(default)]
+    pub overrides: Option<RegenerateOverrides>,
+}
+
+/// 使用调整后的参数重新回答任意一条助手消息：对其前面的用户消息重新检索并调用 LLM，
+/// 原地替换该消息的内容和来源（消息 ID 不变）
 #[command]
-pub async fn clear_messages(
-    request: ClearMessagesRequest,
+pub async fn regenerate_message(
+    request: RegenerateMessageRequest,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<bool, String> {
-    log::info!("清空消息请求: {:?}", request);
+) -> Result<MessageResponse, String> {
+    log::info!("重新生成消息请求: {:?}", request);
 
     // 获取应用状态
     let state = wrapper.get_state().await?;
 
-    // 验证 conversation_id
     let conversation_uuid = Uuid::parse_str(&request.conversation_id)
         .map_err(|e| format!("无效的对话ID: {}", e))?;
+    let message_uuid = Uuid::parse_str(&request.message_id)
+        .map_err(|e| format!("无效的消息ID: {}", e))?;
+    let overrides = request.overrides.unwrap_or_default();
 
-    // 清空对话的所有消息
-    {
+    // 获取项目ID和截止到对应用户消息的历史
+    let (project_id, conversation_model, history) = {
         let conversation_service = state.conversation_service();
         let mut conversation_service_guard = conversation_service.lock().await;
-        conversation_service_guard
-            .clear_conversation_messages(conversation_uuid)
+        let conversation = conversation_service_guard
+            .get_conversation(conversation_uuid)
+            .ok_or_else(|| "对话不存在".to_string())?;
+        let (project_id, conversation_model) = (conversation.project_id, conversation.model.clone());
+        let history = conversation_service_guard
+            .get_regeneration_history(conversation_uuid, message_uuid)
             .await
-            .map_err(|e| format!("清空消息失败: {}", e))?;
-    }
+            .map_err(|e| format!("获取对话历史失败: {}", e))?;
+        (project_id, conversation_model, history)
+    };
 
-    log::info!("消息清空成功: {}", conversation_uuid);
+    let (response_content, context_chunks, processing_time) =
+        generate_reply(&state, project_id, conversation_model.as_deref(), &history, &overrides).await?;
+
+    // 原地替换目标消息
+    let message = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .replace_message(
+                conversation_uuid,
+                message_uuid,
+                response_content,
+                context_chunks,
+                Some(processing_time),
+            )
+            .await
+            .map_err(|e| format!("保存重新生成的消息失败: {}", e))?
+    };
+
+    log::info!("✅ 消息已重新生成: {}", message.id);
+    Ok(MessageResponse {
+        id: message.id.to_string(),
+        conversation_id: message.conversation_id.to_string(),
+        role: message.role.to_string().to_lowercase(),
+        content: message.content.clone(),
+        created_at: message.timestamp.to_rfc3339(),
+        sources: message.sources.as_ref().map(|sources| {
+            sources.iter().map(|s| SourceResponse {
+                filename: s.filename.clone(),
+                relevance_score: s.relevance_score,
+                project_id: s.project_id.clone(),
+                section: s.section.clone(),
+                other_filenames: s.other_filenames.clone(),
+            }).collect()
+        }),
+    })
+}
+
+/// 对历史中最后一条用户消息重新检索并调用 LLM（非流式），返回（回复内容, 来源, 生成耗时秒数）
+async fn generate_reply(
+    state: &crate::services::app_state::AppState,
+    project_id: Uuid,
+    conversation_model: Option<&str>,
+    history: &[crate::models::conversation::Message],
+    overrides: &RegenerateOverrides,
+) -> Result<(String, Vec<ContextChunk>, f64), String> {
+    let top_k = overrides.top_k.unwrap_or(state.retrieval_config().top_k);
+    let query = history.last().map(|m| m.content.clone()).unwrap_or_default();
+
+    // 重新检索
+    let precheck = query_filter::precheck_query(&query, state.chat_config().trivial_query_action);
+    let mut context_chunks = if precheck != QueryPrecheck::Retrieve {
+        log::info!("⏭️  查询只包含停用词或标点，跳过向量检索");
+        Vec::new()
+    } else {
+        let document_service = state.document_service();
+        let document_service_guard = document_service.lock().await;
+        match document_service_guard.search_similar_chunks(&project_id.to_string(), None, &query, top_k).await {
+            Ok(chunks) => into_context_chunks(chunks),
+            Err(e) => {
+                log::warn!("⚠️  检索失败: {}，将不使用上下文", e);
+                Vec::new()
+            }
+        }
+    };
+    log::info!("🔍 重新检索到 {} 个文档块 (top_k={})", context_chunks.len(), top_k);
+
+    // 使用覆盖参数调用 LLM
+    let started = std::time::Instant::now();
+    let response_content = {
+        let llm_client = state.llm_client();
+        let llm_client = llm_client
+            .lock()
+            .await
+            .with_model(conversation_model)
+            .and_then(|client| client.with_temperature(overrides.temperature))
+            .map_err(|e| format!("无效的生成参数: {}", e))?;
+
+        context_chunks = llm_client.fit_context_to_budget(history, &context_chunks);
+        llm_client
+            .generate_response_blocking(history, &context_chunks)
+            .await
+            .map_err(|e| format!("LLM 调用失败: {}", e))?
+    };
+
+    let response_content = response_filter::clean_response(&response_content, &state.chat_config().strip_wrapper_tags);
+    if response_content.is_empty() {
+        return Err("LLM 未返回有效响应".to_string());
+    }
+
+    Ok((response_content, context_chunks, started.elapsed().as_secs_f64()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditMessageRequest {
+    pub conversation_id: String,
+    pub message_id: String,
+    pub new_content: String,
+}
+
+/// 编辑已发送的用户消息：更新内容、删除其后的所有消息，然后重新检索并生成新的助手回复；
+/// 返回新助手消息的 ID
+#[command]
+pub async fn edit_message(
+    request: EditMessageRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<String, String> {
+    log::info!("编辑消息请求: conversation_id={}, message_id={}", request.conversation_id, request.message_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+    let message_uuid = Uuid::parse_str(&request.message_id)
+        .map_err(|e| format!("无效的消息ID: {}", e))?;
+
+    // 先用编辑后的历史生成回复，回复生成并保存前不删除原有消息
+    let (project_id, conversation_model, history) = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        let conversation = conversation_service_guard
+            .get_conversation(conversation_uuid)
+            .ok_or_else(|| "对话不存在".to_string())?;
+        let (project_id, conversation_model) = (conversation.project_id, conversation.model.clone());
+        let history = conversation_service_guard
+            .preview_user_message_edit(conversation_uuid, message_uuid, request.new_content.clone())
+            .await
+            .map_err(|e| format!("编辑消息失败: {}", e))?;
+        (project_id, conversation_model, history)
+    };
+
+    let (response_content, context_chunks, processing_time) = generate_reply(
+        &state,
+        project_id,
+        conversation_model.as_deref(),
+        &history,
+        &RegenerateOverrides::default(),
+    )
+    .await?;
+
+    // 一次写入编辑后的问题、删除其后的消息并保存新的助手回复（包含来源和生成耗时）
+    let mut reply = Message::new_assistant_message(conversation_uuid, response_content, Vec::new(), Some(processing_time))
+        .map_err(|e| format!("保存 AI 消息失败: {}", e))?;
+    if !context_chunks.is_empty() {
+        reply.set_sources(context_chunks);
+    }
+    let message_id = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .edit_user_message(conversation_uuid, message_uuid, request.new_content, reply)
+            .await
+            .map_err(|e| format!("编辑消息失败: {}", e))?
+    };
+
+    log::info!("✅ 消息已编辑并重新生成回复: {}", message_id);
+    Ok(message_id.to_string())
+}
+
+#[command]
+pub async fn delete_conversation(
+    request: DeleteConversationRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("删除对话请求: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    // 删除对话
+    {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .delete_conversation(conversation_uuid)
+            .await
+            .map_err(|e| format!("删除对话失败: {}", e))?;
+    }
+
+    log::info!("对话删除成功: {}", conversation_uuid);
     Ok(true)
 }
 
+#[command]
+pub async fn delete_message(
+    request: DeleteMessageRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("删除消息请求: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id 和 message_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+    let message_uuid = Uuid::parse_str(&request.message_id)
+        .map_err(|e| format!("无效的消息ID: {}", e))?;
+
+    // 删除消息
+    {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .delete_message(conversation_uuid, message_uuid)
+            .await
+            .map_err(|e| format!("删除消息失败: {}", e))?;
+    }
+
+    log::info!("消息删除成功: {}", message_uuid);
+    Ok(true)
+}
+
+#[command]
+pub async fn clear_messages(
+    request: ClearMessagesRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("清空消息请求: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    // 清空对话的所有消息
+    {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .clear_conversation_messages(conversation_uuid)
+            .await
+            .map_err(|e| format!("清空消息失败: {}", e))?;
+    }
+
+    log::info!("消息清空成功: {}", conversation_uuid);
+    Ok(true)
+}
+
+/// 停止对话正在生成的回复：已生成的部分会作为助手消息保存，并发送 chat-stream-cancelled 事件
+/// 返回是否有正在进行的生成
+#[command]
+pub async fn cancel_generation(
+    conversation_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("取消生成请求: {}", conversation_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let cancelled = state.generation_registry().cancel(conversation_uuid);
+    if !cancelled {
+        log::info!("对话 {} 没有正在进行的生成", conversation_uuid);
+    }
+    Ok(cancelled)
+}
+
+/// 只保留对话中最近的 N 条消息，返回删除的消息数
+#[command]
+pub async fn trim_conversation(
+    request: TrimConversationRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<usize, String> {
+    log::info!("裁剪对话请求: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let removed = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .trim_conversation(conversation_uuid, request.keep_last_n)
+            .await
+            .map_err(|e| format!("裁剪对话失败: {}", e))?
+    };
+
+    log::info!("对话裁剪成功: {}，删除 {} 条消息", conversation_uuid, removed);
+    Ok(removed)
+}
+
+/// 在单个对话的消息中搜索（忽略大小写的子串匹配），返回命中的消息、位置和高亮区间
+#[command]
+pub async fn search_conversation_messages(
+    request: SearchConversationMessagesRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<crate::services::conversation_service::MessageSearchHit>, String> {
+    log::info!("对话内搜索: {} - {}", request.conversation_id, request.query);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let hits = state
+        .conversation_service()
+        .lock()
+        .await
+        .search_conversation_messages(conversation_uuid, &request.query)
+        .await
+        .map_err(|e| format!("搜索对话消息失败: {}", e))?;
+
+    log::info!("对话内搜索命中 {} 条消息", hits.len());
+    Ok(hits)
+}
+
 #[command]
 pub async fn rename_conversation(
     request: RenameConversationRequest,
@@ -578,3 +1610,728 @@ pub async fn rename_conversation(
     log::info!("对话重命名成功: {}", conversation_uuid);
     Ok(true)
 }
+
+/// 修复对话的消息顺序：为已有消息按插入顺序分配序号（解决同一秒内的消息显示顺序错乱），返回更新的消息数
+#[command]
+pub async fn repair_message_ordering(
+    conversation_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<usize, String> {
+    log::info!("修复消息顺序: {}", conversation_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let conversation_service = state.conversation_service();
+    let mut conversation_service_guard = conversation_service.lock().await;
+    conversation_service_guard
+        .repair_message_ordering(conversation_uuid)
+        .await
+        .map_err(|e| format!("修复消息顺序失败: {}", e))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompressConversationResponse {
+    /// 被摘要替换的消息数（0 表示没有可压缩的消息）
+    pub compressed_messages: usize,
+    /// 压缩后对话中的消息数
+    pub remaining_messages: usize,
+}
+
+/// 压缩对话历史：把最近 `keep_turns` 轮之前的消息总结为一条系统摘要消息，并删除原消息
+#[command]
+pub async fn compress_conversation(
+    conversation_id: String,
+    keep_turns: Option<usize>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<CompressConversationResponse, String> {
+    let keep_turns = keep_turns.unwrap_or(conversation_service::DEFAULT_KEEP_RECENT_TURNS);
+    log::info!("压缩对话: {}, 保留最近 {} 轮", conversation_id, keep_turns);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let (model, messages, total) = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        let model = conversation_service_guard
+            .get_conversation(conversation_uuid)
+            .ok_or_else(|| "对话不存在".to_string())?
+            .model
+            .clone();
+        let (messages, total) = conversation_service_guard
+            .get_conversation_messages(conversation_uuid, None, None)
+            .await
+            .map_err(|e| format!("获取对话消息失败: {}", e))?;
+        (model, messages, total)
+    };
+
+    let to_compress = conversation_service::messages_to_compress(&messages, keep_turns);
+    if to_compress.is_empty() {
+        log::info!("没有需要压缩的消息");
+        return Ok(CompressConversationResponse {
+            compressed_messages: 0,
+            remaining_messages: total,
+        });
+    }
+
+    let _task = state.task_registry().start(TaskKind::Summarize, conversation_id.clone());
+    let transcript = conversation_service::compression_transcript(&to_compress);
+    let summary = {
+        let llm_client = state.llm_client();
+        let llm_client = llm_client
+            .lock()
+            .await
+            .with_model(model.as_deref())
+            .map_err(|e| format!("创建模型客户端失败: {}", e))?;
+        llm_client
+            .generate_text(prompts::get_compression_prompt(), &transcript)
+            .await
+            .map_err(|e| format!("生成对话摘要失败: {}", e))?
+    };
+
+    let compressed_ids: Vec<Uuid> = to_compress.iter().map(|m| m.id).collect();
+    let conversation_service = state.conversation_service();
+    let mut conversation_service_guard = conversation_service.lock().await;
+    let remaining_messages = conversation_service_guard
+        .compress_messages(
+            conversation_uuid,
+            &compressed_ids,
+            conversation_service::compressed_summary_content(&summary),
+        )
+        .await
+        .map_err(|e| format!("压缩对话失败: {}", e))?;
+
+    log::info!("✅ 对话已压缩: {} 条消息 → 1 条摘要", compressed_ids.len());
+    Ok(CompressConversationResponse {
+        compressed_messages: compressed_ids.len(),
+        remaining_messages,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetConversationModelRequest {
+    pub conversation_id: String,
+    /// 本对话使用的模型，None 表示恢复为默认模型
+    pub model: Option<String>,
+}
+
+/// 设置对话级别的模型覆盖（例如日常闲聊用便宜的模型，难题用更强的模型）
+#[command]
+pub async fn set_conversation_model(
+    request: SetConversationModelRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("设置对话模型请求: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    // 验证模型名称不为空
+    if request.model.as_deref().is_some_and(|model| model.trim().is_empty()) {
+        return Err("模型名称不能为空".to_string());
+    }
+
+    {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        conversation_service_guard
+            .set_conversation_model(conversation_uuid, request.model)
+            .await
+            .map_err(|e| format!("设置对话模型失败: {}", e))?;
+    }
+
+    log::info!("对话模型设置成功: {}", conversation_uuid);
+    Ok(true)
+}
+
+/// 读取检索日志（用于离线 RAG 评测）；`limit` 指定时只返回最近的 N 条
+#[command]
+pub async fn get_retrieval_log(
+    limit: Option<usize>,
+    app_handle: AppHandle,
+) -> Result<Vec<RetrievalLogRecord>, String> {
+    log::info!("读取检索日志: limit={:?}", limit);
+
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "无法获取应用数据目录".to_string())?;
+
+    retrieval_log::read_records(&retrieval_log::log_path(&app_data_dir), limit)
+        .map_err(|e| format!("读取检索日志失败: {}", e))
+}
+
+/// 获取对话统计：按角色的消息数、token 总数以及 AI 回复的平均耗时
+#[command]
+pub async fn get_conversation_stats(
+    conversation_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::conversation_service::ConversationStats, String> {
+    log::info!("获取对话统计: {}", conversation_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let conversation_service = state.conversation_service();
+    let mut conversation_service_guard = conversation_service.lock().await;
+    conversation_service_guard
+        .get_conversation_stats(conversation_uuid)
+        .await
+        .map_err(|e| format!("获取对话统计失败: {}", e))
+}
+
+/// 根据第一轮对话生成标题（仅当标题仍为默认的时间戳时），返回对话当前的标题
+#[command]
+pub async fn generate_conversation_title(
+    conversation_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<String, String> {
+    log::info!("生成对话标题: {}", conversation_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let (conversation, messages) = {
+        let conversation_service = state.conversation_service();
+        let mut conversation_service_guard = conversation_service.lock().await;
+        let conversation = conversation_service_guard
+            .get_conversation(conversation_uuid)
+            .cloned()
+            .ok_or_else(|| "对话不存在".to_string())?;
+        if !conversation.has_default_title() {
+            log::info!("对话已有标题，跳过: {}", conversation.title);
+            return Ok(conversation.title);
+        }
+        let (messages, _) = conversation_service_guard
+            .get_conversation_messages(conversation_uuid, None, None)
+            .await
+            .map_err(|e| format!("获取对话消息失败: {}", e))?;
+        (conversation, messages)
+    };
+
+    let Some((question, answer)) = first_exchange(&messages) else {
+        log::info!("对话还没有用户消息，保留默认标题");
+        return Ok(conversation.title);
+    };
+
+    const MAX_PROMPT_CHARS: usize = 500;
+    let excerpt = |content: &str| content.chars().take(MAX_PROMPT_CHARS).collect::<String>();
+    let prompt = format!(
+        "用户：{}\n\n助手：{}",
+        excerpt(&question.content),
+        answer.map(|m| excerpt(&m.content)).unwrap_or_default()
+    );
+
+    let generated = {
+        let llm_client = state.llm_client();
+        let llm_client = llm_client.lock().await.with_model(conversation.model.as_deref());
+        match llm_client {
+            Ok(client) => client.generate_text(prompts::get_title_prompt(), &prompt).await,
+            Err(e) => Err(e),
+        }
+    };
+    let title = title_or_fallback(generated, &question.content);
+
+    let conversation_service = state.conversation_service();
+    let mut conversation_service_guard = conversation_service.lock().await;
+    // 生成期间用户可能已手动重命名
+    if let Some(current) = conversation_service_guard.get_conversation(conversation_uuid) {
+        if !current.has_default_title() {
+            return Ok(current.title.clone());
+        }
+    }
+    conversation_service_guard
+        .update_conversation_title(conversation_uuid, title.clone())
+        .await
+        .map_err(|e| format!("更新对话标题失败: {}", e))?;
+
+    log::info!("✅ 对话标题已生成: {}", title);
+    Ok(title)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportConversationRequest {
+    pub conversation_id: String,
+    /// 导出文件路径
+    pub output_path: String,
+    /// markdown（默认）或 jsonl
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// 流式导出对话到文件：按页读取消息并逐页写入，发送 conversation-export-progress 事件，返回导出的消息数
+#[command]
+pub async fn export_conversation(
+    request: ExportConversationRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+    window: tauri::Window,
+) -> Result<usize, String> {
+    log::info!("导出对话: {} -> {} ({:?})", request.conversation_id, request.output_path, request.format);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 验证 conversation_id
+    let conversation_uuid = Uuid::parse_str(&request.conversation_id)
+        .map_err(|e| format!("无效的对话ID: {}", e))?;
+
+    let conversation_service = state.conversation_service();
+    let (conversation, total) = {
+        let mut guard = conversation_service.lock().await;
+        let conversation = guard
+            .get_conversation(conversation_uuid)
+            .cloned()
+            .ok_or_else(|| "对话不存在".to_string())?;
+        let (_, total) = guard
+            .get_message_page(conversation_uuid, 0, 0)
+            .await
+            .map_err(|e| format!("导出对话失败: {}", e))?;
+        (conversation, total)
+    };
+
+    let output_path = std::path::PathBuf::from(&request.output_path);
+    let file = std::fs::File::create(&output_path)
+        .map_err(|e| format!("创建导出文件失败: {}", e))?;
+    let mut writer = std::io::BufWriter::new(file);
+
+    // 每页单独加锁，导出期间不阻塞聊天
+    let result = conversation_export::export_paged(
+        &mut writer,
+        &conversation,
+        request.format,
+        total,
+        conversation_export::EXPORT_PAGE_SIZE,
+        |offset, limit| {
+            let conversation_service = conversation_service.clone();
+            async move {
+                let mut guard = conversation_service.lock().await;
+                guard.get_message_page(conversation_uuid, offset, limit).await.map(|(page, _)| page)
+            }
+        },
+        |exported, total| {
+            let _ = window.emit("conversation-export-progress", serde_json::json!({
+                "conversation_id": request.conversation_id,
+                "exported": exported,
+                "total": total,
+            }));
+        },
+    )
+    .await;
+
+    match result {
+        Ok(exported) => {
+            log::info!("✅ 对话导出完成: {} 条消息", exported);
+            Ok(exported)
+        }
+        Err(e) => {
+            drop(writer);
+            let _ = std::fs::remove_file(&output_path);
+            Err(format!("导出对话失败: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::conversation::{Conversation, Message};
+
+    #[test]
+    fn test_identical_chunks_from_different_documents_collapse_into_one_source() {
+        let chunk = |document_id: &str, filename: &str, content: &str, score: f64| ContextChunk {
+            document_id: document_id.to_string(),
+            filename: filename.to_string(),
+            content: content.to_string(),
+            relevance_score: score,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let sources = dedup_sources_by_content(vec![
+            chunk("doc-a", "a.md", "SeekDB 支持向量索引。", 0.7),
+            chunk("doc-c", "c.md", "完全不同的内容", 0.6),
+            chunk("doc-b", "b.md", "SeekDB 支持向量索引。\n", 0.9),
+        ]);
+
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].filename, "b.md");
+        assert_eq!(sources[0].relevance_score, 0.9);
+        assert_eq!(sources[0].other_filenames, vec!["a.md".to_string()]);
+        assert_eq!(sources[1].filename, "c.md");
+        assert!(sources[1].other_filenames.is_empty());
+    }
+
+    #[test]
+    fn test_incremental_sources_are_emitted_individually_before_first_token() {
+        let chunk = |filename: &str, score: f64| ContextChunk {
+            document_id: format!("doc-{}", filename),
+            filename: filename.to_string(),
+            content: format!("{} 的内容", filename),
+            relevance_score: score,
+            project_id: Some("project".to_string()),
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let chunks = vec![chunk("a.md", 0.9), chunk("b.md", 0.8)];
+
+        // 与 send_message 的顺序一致：来源确定后先逐个发送，之后才开始发送 token
+        let mut events: Vec<(String, serde_json::Value)> = Vec::new();
+        emit_incremental_sources("conv-1", &chunks, |event, payload| events.push((event.to_string(), payload)));
+        events.push(("chat-stream-token".to_string(), serde_json::json!({ "conversation_id": "conv-1", "token": "你好" })));
+
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["chat-stream-source", "chat-stream-source", "chat-stream-token"]);
+
+        let (_, first) = &events[0];
+        assert_eq!(first["conversation_id"], "conv-1");
+        assert_eq!(first["index"], 0);
+        assert_eq!(first["total"], 2);
+        assert_eq!(first["source"]["filename"], "a.md");
+        assert_eq!(events[1].1["source"]["filename"], "b.md");
+        assert_eq!(events[1].1["source"], source_json(&chunks[1]));
+    }
+
+    #[test]
+    fn test_event_only_score_mode_emits_per_source_scores() {
+        let chunk = |filename: &str, score: f64| ContextChunk {
+            document_id: format!("doc-{}", filename),
+            filename: filename.to_string(),
+            content: format!("{} 的内容", filename),
+            relevance_score: score,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let chunks = vec![chunk("a.md", 0.91234), chunk("b.md", 1.3)];
+
+        // event-only 模式不修改消息内容，只发送 chat-stream-scores
+        assert_eq!(score_footer(ScoreFooterMode::EventOnly, &chunks), None);
+        let mut events: Vec<(String, serde_json::Value)> = Vec::new();
+        emit_source_scores("conv-1", &chunks, |event, payload| events.push((event.to_string(), payload)));
+
+        assert_eq!(events.len(), 1);
+        let (name, payload) = &events[0];
+        assert_eq!(name, "chat-stream-scores");
+        assert_eq!(payload["conversation_id"], "conv-1");
+        assert_eq!(payload["scores"][0]["filename"], "a.md");
+        assert_eq!(payload["scores"][0]["document_id"], "doc-a.md");
+        // 超出 0~1 的分数按比例缩放，保持相对顺序
+        assert_eq!(payload["scores"][0]["score"], 0.702);
+        assert_eq!(payload["scores"][1]["score"], 1.0);
+
+        let footer = score_footer(ScoreFooterMode::Inline, &chunks).unwrap();
+        assert!(footer.starts_with("\n\n<!-- mine-kb-scores: [") && footer.ends_with(" -->"));
+        assert_eq!(response_filter::strip_score_footer(&format!("回答{}", footer)), "回答");
+        assert_eq!(score_footer(ScoreFooterMode::Hidden, &chunks), None);
+    }
+
+    #[test]
+    fn test_scores_are_normalized_without_clamping() {
+        // 都在 0~1 内的相似度保持不变
+        assert_eq!(normalize_scores(&[0.42, 0.9]), vec![0.42, 0.9]);
+        // 关键词分数按最高分缩放
+        assert_eq!(normalize_scores(&[2.0, 4.0]), vec![0.5, 1.0]);
+        // 负分映射到 0 以上，顺序不变
+        let scores = normalize_scores(&[-1.0, 0.0, 1.0]);
+        assert_eq!(scores, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_all_low_score_retrieval_emits_low_confidence_event() {
+        let chunk = |filename: &str, score: f64| ContextChunk {
+            document_id: format!("doc-{}", filename),
+            filename: filename.to_string(),
+            content: format!("{} 的内容", filename),
+            relevance_score: score,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let mut events: Vec<(String, serde_json::Value)> = Vec::new();
+
+        let low = vec![chunk("a.md", 0.12), chunk("b.md", 0.21)];
+        assert!(emit_low_confidence("conv-1", &low, false, 0.3, |event, payload| events.push((event.to_string(), payload))));
+        assert_eq!(events.len(), 1);
+        let (name, payload) = &events[0];
+        assert_eq!(name, "chat-low-confidence");
+        assert_eq!(payload["conversation_id"], "conv-1");
+        assert_eq!(payload["top_score"], 0.21);
+        assert_eq!(payload["floor"], 0.3);
+
+        // 有一个来源达到下限，或者没有来源且项目为空时不发送
+        let mixed = vec![chunk("a.md", 0.12), chunk("c.md", 0.8)];
+        assert!(!emit_low_confidence("conv-1", &mixed, false, 0.3, |event, payload| events.push((event.to_string(), payload))));
+        assert!(!emit_low_confidence("conv-1", &[], false, 0.3, |event, payload| events.push((event.to_string(), payload))));
+        assert_eq!(events.len(), 1);
+
+        // 项目有文档但所有块都被相似度阈值过滤掉时发送，最高分未知
+        assert!(emit_low_confidence("conv-1", &[], true, 0.3, |event, payload| events.push((event.to_string(), payload))));
+        assert_eq!(events.len(), 2);
+        assert!(events[1].1["top_score"].is_null());
+    }
+
+    /// 固定返回同一个向量的 embedding 服务
+    struct FixedEmbedding(Vec<f64>);
+
+    impl crate::services::embedding_provider::EmbeddingProvider for FixedEmbedding {
+        fn embed_text<'a>(&'a self, _text: &'a str) -> futures::future::BoxFuture<'a, anyhow::Result<Vec<f64>>> {
+            Box::pin(async move { Ok(self.0.clone()) })
+        }
+
+        fn embed_batch<'a>(&'a self, texts: &'a [String]) -> futures::future::BoxFuture<'a, anyhow::Result<Vec<Vec<f64>>>> {
+            Box::pin(async move { Ok(vec![self.0.clone(); texts.len()]) })
+        }
+
+        fn embed_text_uncached<'a>(&'a self, text: &'a str) -> futures::future::BoxFuture<'a, anyhow::Result<Vec<f64>>> {
+            self.embed_text(text)
+        }
+
+        fn embedding_dim(&self) -> usize {
+            self.0.len()
+        }
+
+        fn model(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_unrelated_question_against_indexed_project_is_low_confidence() {
+        use crate::services::document_service::DocumentService;
+        use crate::services::seekdb_adapter::{VectorDocument, SCHEMA_EMBEDDING_DIMENSION};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("low_confidence.db").display().to_string();
+        let mut service = DocumentService::with_full_config(&db_path, "test-key".to_string(), None, None, None).await.unwrap();
+
+        // 查询向量与文档块正交，相似度低于默认阈值 0.3
+        let axis = |i: usize| {
+            let mut embedding = vec![0.0; SCHEMA_EMBEDDING_DIMENSION];
+            embedding[i] = 1.0;
+            embedding
+        };
+        service.set_embedding_provider(std::sync::Arc::new(FixedEmbedding(axis(0))));
+        let project_id = Uuid::new_v4().to_string();
+        service.get_vector_db().lock().await.add_documents(vec![VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.clone(),
+            document_id: "doc".to_string(),
+            chunk_index: 0,
+            content: "SeekDB 是一个嵌入式数据库".to_string(),
+            embedding: axis(1),
+            metadata: HashMap::new(),
+        }]).unwrap();
+
+        let chunks = service.search_similar_chunks(&project_id, None, "今天天气怎么样", 5).await.unwrap();
+        assert!(chunks.is_empty());
+        let nothing_matched = service.project_has_chunks(&project_id).await.unwrap();
+        assert!(nothing_matched);
+
+        let mut events = Vec::new();
+        let floor = crate::config::ChatConfig::default().low_confidence_floor;
+        assert!(emit_low_confidence("conv-1", &into_context_chunks(chunks), nothing_matched, floor, |event, _| events.push(event.to_string())));
+        assert_eq!(events, vec!["chat-low-confidence"]);
+
+        // 空项目不算低置信度
+        assert!(!service.project_has_chunks(&Uuid::new_v4().to_string()).await.unwrap());
+    }
+
+    #[test]
+    fn test_generated_title_is_cleaned_and_falls_back_to_first_message() {
+        let conversation_id = Uuid::new_v4();
+        let messages = vec![
+            Message::new(conversation_id, MessageRole::System, "系统提示".to_string()).unwrap(),
+            Message::new(conversation_id, MessageRole::User, "如何在   SeekDB 中创建向量索引？".to_string()).unwrap(),
+            Message::new(conversation_id, MessageRole::Assistant, "使用 VECTOR INDEX 语法。".to_string()).unwrap(),
+        ];
+        let (question, answer) = first_exchange(&messages).unwrap();
+        assert_eq!(question.role, MessageRole::User);
+        assert_eq!(answer.unwrap().role, MessageRole::Assistant);
+
+        assert_eq!(
+            title_or_fallback(Ok("标题：“SeekDB 向量索引创建”。\n解释……".to_string()), &question.content),
+            "SeekDB 向量索引创建"
+        );
+        assert_eq!(
+            title_or_fallback(Ok("How to create a vector index in SeekDB quickly and easily".to_string()), ""),
+            "How to create a vector index in SeekDB"
+        );
+
+        // LLM 失败或返回空内容时使用第一条消息，标题不会为空
+        assert_eq!(
+            title_or_fallback(Err(anyhow::anyhow!("发送请求失败")), &question.content),
+            "如何在 SeekDB 中创建向量索引？"
+        );
+        assert_eq!(title_or_fallback(Ok("  \"\" ".to_string()), &question.content), "如何在 SeekDB 中创建向量索引？");
+
+        let mut conversation = Conversation::new(Uuid::new_v4(), None).unwrap();
+        assert!(conversation.has_default_title());
+        conversation.update_title("向量索引".to_string()).unwrap();
+        assert!(!conversation.has_default_title());
+    }
+
+    #[test]
+    fn test_send_without_conversation_id_creates_one() {
+        let project_id = Uuid::new_v4();
+        let target = resolve_conversation_target(None, Some(&project_id.to_string()), true).unwrap();
+        assert_eq!(target, ConversationTarget::Create { project_id });
+
+        // 新对话以第一条消息作为标题
+        let conversation = crate::models::conversation::Conversation::new(
+            project_id,
+            Some(auto_conversation_title("  如何配置\n SeekDB 的混合检索？ ")),
+        )
+        .unwrap();
+        assert_eq!(conversation.title, "如何配置 SeekDB 的混合检索？");
+        assert_eq!(auto_conversation_title(&"长".repeat(50)), format!("{}…", "长".repeat(30)));
+
+        let response = SendMessageResponse {
+            conversation_id: conversation.id.to_string(),
+            conversation_created: true,
+            content: "回答".to_string(),
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["conversation_id"], conversation.id.to_string());
+        assert_eq!(json["conversation_created"], true);
+    }
+
+    #[test]
+    fn test_retrieval_projects_include_conversation_project_once() {
+        let extra = vec![
+            ProjectWeight { project_id: "shared".to_string(), weight: 0.5 },
+            ProjectWeight { project_id: "client".to_string(), weight: 2.0 },
+        ];
+        let projects = retrieval_projects("client", &extra);
+        let weights: Vec<(&str, f64)> = projects.iter().map(|p| (p.project_id.as_str(), p.weight)).collect();
+        assert_eq!(weights, vec![("shared", 0.5), ("client", 2.0)]);
+
+        let projects = retrieval_projects("client", &extra[..1]);
+        let weights: Vec<(&str, f64)> = projects.iter().map(|p| (p.project_id.as_str(), p.weight)).collect();
+        assert_eq!(weights, vec![("client", 1.0), ("shared", 0.5)]);
+    }
+
+    #[test]
+    fn test_non_finite_or_negative_project_weights_are_rejected() {
+        let weight = |weight: f64| vec![ProjectWeight { project_id: "shared".to_string(), weight }];
+        assert!(validate_project_weights(&weight(0.0)).is_ok());
+        assert!(validate_project_weights(&weight(2.5)).is_ok());
+        assert!(validate_project_weights(&weight(-0.5)).is_err());
+        assert!(validate_project_weights(&weight(f64::NAN)).is_err());
+        assert!(validate_project_weights(&weight(f64::INFINITY)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transient_connect_error_is_retried_without_duplicating_user_message() {
+        use crate::services::generation_retry::RetryPolicy;
+        use crate::services::llm_client::{collect_stream_text, LlmClient, LlmConfig};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 本地模拟 LLM 服务：第一次连接读取请求后直接断开，第二次返回完整回答
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = tokio::spawn(async move {
+            let body = r#"{"id":"resp_1","object":"chat.completion","created":0,"model":"qwen-max","choices":[{"index":0,"message":{"role":"assistant","content":"SeekDB 是一个 AI 原生数据库"}}]}"#;
+            let mut received = Vec::new();
+            for attempt in 0..2 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = vec![0u8; 64 * 1024];
+                // 读完请求头和请求体，之后检查重试请求中的对话历史
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some(header_end) = text.find("\r\n\r\n") else { continue };
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if n == 0 || request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                received.push(String::from_utf8_lossy(&request).to_string());
+                if attempt == 1 {
+                    let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                    socket.shutdown().await.unwrap();
+                }
+            }
+            received
+        });
+
+        // 1. 与 send_message 相同：用户消息在生成之前保存一次
+        let conversation = Conversation::new(Uuid::new_v4(), None).unwrap();
+        let question = "什么是 SeekDB?";
+        let mut saved = vec![Message::new_user_message(conversation.id, question.to_string()).unwrap()];
+
+        // 4. 生成阶段：第一次连接被断开，LlmClient 按重试策略重新发送请求
+        let mut client = LlmClient::new(LlmConfig {
+            api_key: "test-key".to_string(),
+            model: "qwen-max".to_string(),
+            base_url,
+            stream: false,
+            ..Default::default()
+        })
+        .unwrap();
+        client.set_retry_policy(RetryPolicy {
+            max_retries: 2,
+            initial_backoff: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        });
+        let stream = client.generate_response(&saved, &[]).await.unwrap();
+        let answer = collect_stream_text(stream).await.unwrap();
+        assert_eq!(answer, "SeekDB 是一个 AI 原生数据库");
+
+        // 5. 保存 AI 回复
+        saved.push(Message::new_assistant_message(conversation.id, answer, Vec::new(), None).unwrap());
+
+        let received = server.await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        // 重试请求中用户消息只出现一次，保存的消息中也只有一条用户消息
+        assert_eq!(received[1].matches(question).count(), 1);
+        assert_eq!(saved.iter().filter(|m| m.role == MessageRole::User).count(), 1);
+        assert_eq!(saved.len(), 2);
+    }
+
+    #[test]
+    fn test_explicit_conversation_id_is_used_as_before() {
+        let conversation_id = Uuid::new_v4();
+        assert_eq!(
+            resolve_conversation_target(Some(&conversation_id.to_string()), None, true).unwrap(),
+            ConversationTarget::Existing(conversation_id)
+        );
+        assert!(resolve_conversation_target(Some("not-a-uuid"), None, true).is_err());
+
+        // 关闭自动创建或缺少项目ID时报错
+        assert!(resolve_conversation_target(None, Some(&Uuid::new_v4().to_string()), false).is_err());
+        assert!(resolve_conversation_target(None, None, true).is_err());
+    }
+}
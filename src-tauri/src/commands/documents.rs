@@ -1,13 +1,20 @@
 use serde::{Deserialize, Serialize};
 use tauri::command;
 use uuid::Uuid;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use futures::StreamExt;
+use crate::services::task_registry::TaskKind;
+use crate::utils::content_hash::content_hash;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UploadDocumentsRequest {
     pub project_id: String,
     pub file_paths: Vec<String>,
+    /// 严格模式：所有文件均失败时返回 `UploadError::AllFailed`，未指定时使用配置 `ingestion.failOnAllFailed`
+    #[serde(default)]
+    pub strict: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +48,92 @@ pub struct UploadSummary {
     pub failed: usize,
 }
 
+/// 上传进度事件名，每处理完一个文件发送一次，全部完成后再发送一次 `stage` 为 "done" 的事件
+pub const UPLOAD_PROGRESS_EVENT: &str = "document-upload-progress";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentUploadProgress {
+    pub project_id: String,
+    /// 刚处理完的文件，最终事件为 None
+    pub filename: Option<String>,
+    /// 已处理的文件数（从 1 开始）
+    pub index: usize,
+    pub total: usize,
+    /// 成功时为文档的处理状态（如 "Indexed"），失败时为错误阶段（如 "embedding"），全部完成时为 "done"
+    pub stage: String,
+}
+
+impl DocumentUploadProgress {
+    fn file(project_id: Uuid, filename: String, index: usize, total: usize, stage: String) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            filename: Some(filename),
+            index,
+            total,
+            stage,
+        }
+    }
+
+    fn finished(project_id: Uuid, total: usize) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            filename: None,
+            index: total,
+            total,
+            stage: "done".to_string(),
+        }
+    }
+}
+
+/// 上传文档命令的错误
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    /// 请求无效或应用状态不可用（项目不存在、未初始化等），与其他命令一样序列化为字符串
+    #[error("{0}")]
+    InvalidRequest(String),
+    /// 严格模式下本批次所有文件都处理失败，序列化为 `{ kind: "allFailed", message, failed }`；
+    /// 部分成功时不会返回该错误
+    #[error("{message}")]
+    AllFailed {
+        message: String,
+        failed: Vec<FailedDocumentInfo>,
+    },
+}
+
+impl From<String> for UploadError {
+    fn from(message: String) -> Self {
+        UploadError::InvalidRequest(message)
+    }
+}
+
+impl Serialize for UploadError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        match self {
+            UploadError::InvalidRequest(message) => serializer.serialize_str(message),
+            UploadError::AllFailed { message, failed } => {
+                let mut s = serializer.serialize_struct("UploadError", 3)?;
+                s.serialize_field("kind", "allFailed")?;
+                s.serialize_field("message", message)?;
+                s.serialize_field("failed", failed)?;
+                s.end()
+            }
+        }
+    }
+}
+
+/// 根据上传结果决定返回值：严格模式下全部失败返回错误，其余情况返回汇总
+fn finish_upload(response: UploadDocumentsResponse, strict: bool) -> Result<UploadDocumentsResponse, UploadError> {
+    if strict && response.successful.is_empty() && !response.failed.is_empty() {
+        return Err(UploadError::AllFailed {
+            message: format!("所有 {} 个文档均上传失败", response.failed.len()),
+            failed: response.failed,
+        });
+    }
+    Ok(response)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ValidateFilesRequest {
     pub file_paths: Vec<String>,
@@ -82,15 +175,17 @@ pub struct ValidationSummary {
 pub async fn upload_documents(
     request: UploadDocumentsRequest,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<UploadDocumentsResponse, String> {
+    window: tauri::Window,
+) -> Result<UploadDocumentsResponse, UploadError> {
     log::info!("📤 上传文档请求: {:?}", request);
 
     // 获取应用状态
     let state = wrapper.get_state().await?;
+    let strict = request.strict.unwrap_or(state.ingestion_config().fail_on_all_failed);
 
     // 验证输入
     if request.file_paths.is_empty() {
-        return Err("至少需要上传一个文档".to_string());
+        return Err("至少需要上传一个文档".to_string().into());
     }
 
     // 解析项目 ID
@@ -101,8 +196,11 @@ pub async fn upload_documents(
     {
         let project_service = state.project_service();
         let project_service_guard = project_service.lock().await;
-        if project_service_guard.get_project(project_id).is_none() {
-            return Err(format!("项目不存在: {}", project_id));
+        let project = project_service_guard
+            .get_project(project_id)
+            .ok_or_else(|| format!("项目不存在: {}", project_id))?;
+        if project.is_archived() {
+            return Err(format!("项目已归档，请先恢复后再上传文档: {}", project_id).into());
         }
     }
 
@@ -111,11 +209,35 @@ pub async fn upload_documents(
     let mut successful_docs = Vec::new();
     let mut failed_docs = Vec::new();
     let total_files = request.file_paths.len();
-
-    for file_path in request.file_paths {
-        log::info!("📄 处理文件: {}", file_path);
-
-        match process_single_document(project_id, file_path.clone(), document_service.clone()).await {
+    let files = request.file_paths.iter().map(std::path::PathBuf::from).collect();
+    let task = state.task_registry().start_with_files(TaskKind::Upload, project_id.to_string(), files);
+    let concurrency = state.ingestion_config().upload_concurrency.max(1);
+    log::info!("📄 并发处理 {} 个文件（并发数 {}）", total_files, concurrency);
+
+    let results = process_concurrently(
+        request.file_paths,
+        concurrency,
+        |file_path| {
+            let document_service = document_service.clone();
+            async move {
+                let result = process_single_document(project_id, file_path.clone(), document_service).await;
+                (file_path, result)
+            }
+        },
+        |completed, (file_path, result)| {
+            let progress = match result {
+                Ok((_, filename, _, status, _)) => DocumentUploadProgress::file(project_id, filename.clone(), completed, total_files, status.clone()),
+                Err(e) => DocumentUploadProgress::file(project_id, upload_filename(file_path), completed, total_files, parse_error_stage(e).0),
+            };
+            let _ = window.emit(UPLOAD_PROGRESS_EVENT, progress);
+            task.set_progress(completed, total_files);
+        },
+    )
+    .await;
+
+    // 结果按文件顺序汇总
+    for (file_path, result) in results {
+        match result {
             Ok((doc_id, filename, file_size, status, created_at)) => {
                 successful_docs.push(DocumentResponse {
                     id: doc_id.to_string(),
@@ -127,12 +249,7 @@ pub async fn upload_documents(
                 log::info!("✅ 文档上传成功: {} (ID: {})", filename, doc_id);
             }
             Err(e) => {
-                // 提取文件名
-                let filename = std::path::Path::new(&file_path)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("未知文件")
-                    .to_string();
+                let filename = upload_filename(&file_path);
 
                 // 解析错误阶段
                 let (error_stage, error_message) = parse_error_stage(&e);
@@ -149,28 +266,8 @@ pub async fn upload_documents(
     }
 
     // 更新项目的文档数量
-    {
-        // 先计算文档数量（从数据库查询，确保是累加的总数）
-        let doc_count = {
-            let doc_service = state.document_service();
-            let doc_service_guard = doc_service.lock().await;
-            doc_service_guard.count_documents(Some(project_id)).await
-        };
-
-        log::info!("📊 项目 {} 的文档总数: {}", project_id, doc_count);
-
-        // 然后更新项目
-        let project_service = state.project_service();
-        let mut project_service_guard = project_service.lock().await;
-        if let Some(project) = project_service_guard.get_project_mut(project_id) {
-            project.document_count = doc_count as u32;
-            project.updated_at = chrono::Utc::now();
-
-            // 保存更新到数据库
-            let project_clone = project.clone();
-            let _ = project_service_guard.save_project_to_db(&project_clone);
-        }
-    }
+    refresh_project_document_count(&state, project_id).await;
+    let _ = window.emit(UPLOAD_PROGRESS_EVENT, DocumentUploadProgress::finished(project_id, total_files));
 
     let summary = UploadSummary {
         total: total_files,
@@ -185,12 +282,51 @@ pub async fn upload_documents(
         summary.failed
     );
 
-    // 即使部分失败也返回成功，让前端处理失败列表
-    Ok(UploadDocumentsResponse {
-        successful: successful_docs,
-        failed: failed_docs,
-        summary,
-    })
+    // 部分失败时返回成功，让前端处理失败列表；严格模式下全部失败返回错误
+    finish_upload(
+        UploadDocumentsResponse {
+            successful: successful_docs,
+            failed: failed_docs,
+            summary,
+        },
+        strict,
+    )
+}
+
+/// 从文件路径提取文件名
+fn upload_filename(file_path: &str) -> String {
+    std::path::Path::new(file_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("未知文件")
+        .to_string()
+}
+
+/// 以最多 `concurrency` 个任务并发处理 `items`，每完成一个调用 `on_complete(已完成数, 结果)`，
+/// 返回按输入顺序排列的结果
+async fn process_concurrently<T, R, F, Fut, C>(items: Vec<T>, concurrency: usize, process: F, mut on_complete: C) -> Vec<R>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R>,
+    C: FnMut(usize, &R),
+{
+    let total = items.len();
+    let mut results: Vec<Option<R>> = (0..total).map(|_| None).collect();
+    let mut pending = futures::stream::iter(items.into_iter().enumerate())
+        .map(|(index, item)| {
+            let task = process(item);
+            async move { (index, task.await) }
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut completed = 0;
+    while let Some((index, result)) = pending.next().await {
+        completed += 1;
+        on_complete(completed, &result);
+        results[index] = Some(result);
+    }
+
+    results.into_iter().flatten().collect()
 }
 
 /// 解析错误信息，提取错误阶段和清晰的错误消息
@@ -222,14 +358,40 @@ fn extract_error_message(error: &str) -> String {
     }
 }
 
+/// 从数据库重新统计项目的文档数量并保存到项目
+pub(crate) async fn refresh_project_document_count(
+    state: &crate::services::app_state::AppState,
+    project_id: Uuid,
+) {
+    // 先计算文档数量（从数据库查询，确保是累加的总数）
+    let doc_count = {
+        let doc_service = state.document_service();
+        let doc_service_guard = doc_service.lock().await;
+        doc_service_guard.count_documents(Some(project_id)).await
+    };
+
+    log::info!("📊 项目 {} 的文档总数: {}", project_id, doc_count);
+
+    // 然后更新项目
+    let project_service = state.project_service();
+    let mut project_service_guard = project_service.lock().await;
+    if let Some(project) = project_service_guard.get_project_mut(project_id) {
+        project.document_count = doc_count as u32;
+        project.updated_at = chrono::Utc::now();
+
+        // 保存更新到数据库
+        let project_clone = project.clone();
+        let _ = project_service_guard.save_project_to_db(&project_clone);
+    }
+}
+
 /// 处理单个文档的上传和处理
-async fn process_single_document(
+pub(crate) async fn process_single_document(
     project_id: Uuid,
     file_path: String,
     document_service: Arc<Mutex<crate::services::document_service::DocumentService>>,
 ) -> Result<(Uuid, String, u64, String, chrono::DateTime<chrono::Utc>), String> {
     use std::path::Path;
-    use sha2::{Sha256, Digest};
 
     log::info!("📄 [阶段1/5] 开始处理文档: {}", file_path);
 
@@ -274,50 +436,61 @@ async fn process_single_document(
             error
         })?;
 
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let hash = format!("{:x}", hasher.finalize());
+    // 只在读取哈希算法和记录文档时短暂持有 DocumentService 的锁，哈希计算、文本提取和向量化都在锁外进行，
+    // 批量上传时多个文档可以并发处理
+    let (hash_algorithm, indexer) = {
+        let doc_service = document_service.lock().await;
+        (doc_service.hash_algorithm(), doc_service.indexer())
+    };
+    let hash = content_hash(&content, hash_algorithm);
 
     log::debug!("✅ 文件哈希: {}", hash);
 
     // 阶段4: 添加文档到服务（包含文本提取、分块、向量化）
     log::info!("📝 [阶段4/5] 处理文档内容（提取文本、分块、向量化）...");
-    let mut doc_service = document_service.lock().await;
-    let document_id = doc_service
-        .add_document(project_id, file_path.clone(), file_size, hash)
-        .await
-        .map_err(|e| {
-            let error_msg = e.to_string();
-
-            // 根据错误类型提供更详细的错误信息
-            let detailed_error = if error_msg.contains("Failed to extract") {
-                format!("[阶段4-文本提取] 无法提取文档内容: {} - 可能是文件损坏或格式不正确", filename)
-            } else if error_msg.contains("No valid chunks") {
-                format!("[阶段4-分块] 文档内容为空或无法分块: {} - 文档可能没有可提取的文本内容", filename)
-            } else if error_msg.contains("embedding") || error_msg.contains("API") {
-                format!("[阶段4-向量化] 向量化失败: {} - API调用错误或网络问题", filename)
-            } else if error_msg.contains("Unsupported file type") {
-                format!("[阶段4-格式] 不支持的文件格式: {} - {}", filename, error_msg)
-            } else {
-                format!("[阶段4-处理] 文档处理失败: {} - {}", filename, error_msg)
-            };
-
-            log::error!("❌ {}", detailed_error);
-            detailed_error
-        })?;
+    let describe_error = |e: anyhow::Error| {
+        let error_msg = e.to_string();
+
+        // 根据错误类型提供更详细的错误信息
+        let detailed_error = if error_msg.contains("Failed to extract") {
+            format!("[阶段4-文本提取] 无法提取文档内容: {} - 可能是文件损坏或格式不正确", filename)
+        } else if error_msg.contains("No valid chunks") {
+            format!("[阶段4-分块] 文档内容为空或无法分块: {} - 文档可能没有可提取的文本内容", filename)
+        } else if error_msg.contains("embedding") || error_msg.contains("API") {
+            format!("[阶段4-向量化] 向量化失败: {} - API调用错误或网络问题", filename)
+        } else if error_msg.contains("Unsupported file type") {
+            format!("[阶段4-格式] 不支持的文件格式: {} - {}", filename, error_msg)
+        } else {
+            format!("[阶段4-处理] 文档处理失败: {} - {}", filename, error_msg)
+        };
 
-    log::info!("✅ 文档处理成功，ID: {}", document_id);
+        log::error!("❌ {}", detailed_error);
+        detailed_error
+    };
 
-    // 阶段5: 获取文档信息
-    log::debug!("📊 [阶段5/5] 获取文档状态...");
-    let document = doc_service
-        .get_document(document_id)
-        .ok_or_else(|| {
-            let error = format!("[阶段5-查询] 文档添加后未找到: {}", filename);
+    let mut document = indexer
+        .new_document(project_id, file_path.clone(), file_size, hash)
+        .map_err(describe_error)?;
+    {
+        // 在同一次加锁中检查配额并记录文档，并发上传的文档会计入彼此的用量
+        let mut doc_service = document_service.lock().await;
+        doc_service.check_project_quota(project_id, file_size).map_err(|e| {
+            let error = format!("[阶段4-配额] {}: {}", filename, e);
             log::error!("❌ {}", error);
             error
         })?;
+        doc_service.upsert_document(document.clone());
+    }
 
+    let indexed = indexer.index(&mut document).await;
+    // 无论成功与否都记录处理后的文档状态
+    document_service.lock().await.upsert_document(document.clone());
+    indexed.map_err(describe_error)?;
+
+    log::info!("✅ 文档处理成功，ID: {}", document.id);
+
+    // 阶段5: 文档状态
+    log::debug!("📊 [阶段5/5] 获取文档状态...");
     log::info!(
         "🎉 文档处理完成: {} (状态: {}, chunks: {})",
         filename,
@@ -465,7 +638,7 @@ async fn validate_single_file(
         .and_then(|ext| ext.to_str())
         .unwrap_or("");
 
-    let supported_extensions = vec!["txt", "md", "markdown", "pdf", "doc", "docx", "rtf"];
+    let supported_extensions = vec!["txt", "md", "markdown", "pdf", "doc", "docx", "rtf", "html", "htm", "epub"];
     if !supported_extensions.contains(&extension.to_lowercase().as_str()) {
         return Err(FileValidationError {
             path: file_path.to_string(),
@@ -487,6 +660,8 @@ async fn validate_single_file(
         "doc" => "application/msword",
         "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
         "rtf" => "application/rtf",
+        "html" | "htm" => "text/html",
+        "epub" => "application/epub+zip",
         _ => "application/octet-stream",
     };
 
@@ -499,8 +674,486 @@ async fn validate_single_file(
     })
 }
 
+/// 获取文档内容：由文档块按顺序拼接并去掉重叠部分，用于查看引用来源
+#[command]
+pub async fn get_document_content(
+    document_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::document_service::DocumentContent, String> {
+    log::info!("获取文档内容: {}", document_id);
+
+    let state = wrapper.get_state().await?;
+
+    let document_uuid = Uuid::parse_str(&document_id)
+        .map_err(|e| format!("无效的文档ID: {}", e))?;
+
+    let document_service = state.document_service();
+    let document_service_guard = document_service.lock().await;
+    document_service_guard
+        .get_document_content(document_uuid)
+        .await
+        .map_err(|e| format!("获取文档内容失败: {}", e))
+}
+
+/// 按文档结构（编号章节、标题层级）重新分块并重新生成 embedding，适用于合同、手册等结构化文档
+#[command]
+pub async fn restructure_document(
+    document_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::document_service::RestructureReport, String> {
+    log::info!("按结构重新分块: {}", document_id);
+
+    let state = wrapper.get_state().await?;
+
+    let document_uuid = Uuid::parse_str(&document_id)
+        .map_err(|e| format!("无效的文档ID: {}", e))?;
+
+    let document_service = state.document_service();
+    let mut document_service_guard = document_service.lock().await;
+    document_service_guard
+        .restructure_document(document_uuid)
+        .await
+        .map_err(|e| format!("按结构重新分块失败: {}", e))
+}
+
+/// 删除文档：同时删除其在数据库中的文档块并更新所属项目的文档数量
+#[command]
+pub async fn delete_document(
+    document_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<(), String> {
+    log::info!("删除文档: {}", document_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let document_uuid = Uuid::parse_str(&document_id)
+        .map_err(|e| format!("无效的文档ID: {}", e))?;
+
+    let project_id = {
+        let document_service = state.document_service();
+        let mut document_service_guard = document_service.lock().await;
+        document_service_guard
+            .delete_document(document_uuid)
+            .await
+            .map_err(|e| format!("删除文档失败: {}", e))?
+    };
+
+    refresh_project_document_count(&state, project_id).await;
+    Ok(())
+}
+
+async fn set_project_status(
+    state: &crate::services::app_state::AppState,
+    project_id: Uuid,
+    status: crate::models::project::ProjectStatus,
+) -> Result<(), String> {
+    let project_service = state.project_service();
+    let mut project_service_guard = project_service.lock().await;
+    project_service_guard
+        .update_project_status(project_id, status)
+        .map_err(|e| format!("更新项目状态失败: {}", e))
+}
+
+/// 重新处理结束后的项目状态：所有文档都成功时为 Ready，否则为 Error
+fn reprocess_status(
+    result: &Result<crate::services::document_service::ReprocessReport, String>,
+) -> crate::models::project::ProjectStatus {
+    use crate::models::project::ProjectStatus;
+    match result {
+        Ok(report) if report.failed_documents.is_empty() => ProjectStatus::Ready,
+        _ => ProjectStatus::Error,
+    }
+}
+
+/// 重新处理项目内所有文档（更换 embedding 模型后重建向量），通过 `reprocess-progress` 事件报告进度
+///
+/// 处理期间项目状态为 Processing，结束后根据结果设为 Ready 或 Error
+#[command]
+pub async fn reprocess_project(
+    project_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+    window: tauri::Window,
+) -> Result<crate::services::document_service::ReprocessReport, String> {
+    use crate::models::project::ProjectStatus;
+
+    log::info!("重新处理项目文档: {}", project_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = Uuid::parse_str(&project_id)
+        .map_err(|e| format!("无效的项目ID: {}", e))?;
+
+    set_project_status(&state, project_uuid, ProjectStatus::Processing).await?;
+    let task = state.task_registry().start(TaskKind::Reprocess, project_id.clone());
+
+    // 只在收集文档和写回结果时持有 DocumentService 的锁，处理期间不阻塞对话和上传
+    let document_service = state.document_service();
+    let prepared = {
+        let mut document_service_guard = document_service.lock().await;
+        let indexer = document_service_guard.indexer();
+        document_service_guard
+            .documents_for_reprocess(project_uuid)
+            .await
+            .map(|documents| (indexer, documents))
+            .map_err(|e| format!("重新处理项目文档失败: {}", e))
+    };
+
+    let result = match prepared {
+        Ok((indexer, documents)) => {
+            let (report, reprocessed) = indexer
+                .reprocess_documents(documents, |current, total, filename| {
+                    task.set_progress(current, total);
+                    let _ = window.emit("reprocess-progress", serde_json::json!({
+                        "project_id": project_id,
+                        "current": current,
+                        "total": total,
+                        "filename": filename,
+                    }));
+                })
+                .await;
+
+            let mut document_service_guard = document_service.lock().await;
+            for document in reprocessed {
+                document_service_guard.upsert_document(document);
+            }
+            Ok(report)
+        }
+        Err(e) => Err(e),
+    };
+
+    refresh_project_document_count(&state, project_uuid).await;
+    set_project_status(&state, project_uuid, reprocess_status(&result)).await?;
+
+    if let Ok(report) = &result {
+        log::info!("项目文档重新处理完成: 成功 {}, 失败 {}", report.reprocessed, report.failed_documents.len());
+    }
+    result
+}
+
+/// 清理项目中处理失败的文档残留在数据库中的部分文档块，返回清理统计
+#[command]
+pub async fn cleanup_partial_documents(
+    project_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::document_service::PartialCleanupReport, String> {
+    log::info!("清理失败文档的残留文档块: {}", project_id);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = Uuid::parse_str(&project_id)
+        .map_err(|e| format!("无效的项目ID: {}", e))?;
+
+    let report = {
+        let document_service = state.document_service();
+        let mut document_service_guard = document_service.lock().await;
+        document_service_guard
+            .cleanup_partial_documents(project_uuid)
+            .await
+            .map_err(|e| format!("清理残留文档块失败: {}", e))?
+    };
+
+    if report.removed_chunks > 0 {
+        refresh_project_document_count(&state, project_uuid).await;
+    }
+    Ok(report)
+}
+
+/// 为项目中缺少向量的文档块重新生成向量（只处理这些块，不重建整个项目），返回补全统计
 #[command]
-pub async fn get_document_content(_document_id: String) -> Result<String, String> {
-    // TODO: Implement get document content
-    Err("Not implemented".to_string())
+pub async fn fill_missing_embeddings(
+    project_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::document_service::EmbeddingFillReport, String> {
+    log::info!("补全缺失的文档块向量: {}", project_id);
+
+    Uuid::parse_str(&project_id).map_err(|e| format!("无效的项目ID: {}", e))?;
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 批量生成 embedding 期间不持有 DocumentService 锁
+    let indexer = state.document_service().lock().await.indexer();
+    indexer
+        .fill_missing_embeddings(&project_id)
+        .await
+        .map_err(|e| format!("补全文档块向量失败: {}", e))
+}
+
+/// 查找与指定文档内容相近的同项目文档（默认返回 5 个）
+#[command]
+pub async fn find_similar_documents(
+    document_id: String,
+    top_k: Option<usize>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<crate::services::document_service::SimilarDocument>, String> {
+    let top_k = top_k.unwrap_or(5);
+    log::info!("查找相似文档: document_id={}, top_k={}", document_id, top_k);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let document_service_arc = state.document_service();
+    let document_service = document_service_arc.lock().await;
+
+    let similar = document_service
+        .find_similar_documents(&document_id, top_k)
+        .await
+        .map_err(|e| format!("查找相似文档失败: {}", e))?;
+
+    log::info!("找到 {} 个相似文档", similar.len());
+    Ok(similar)
+}
+
+/// 获取项目中某个元数据键的所有取值及数量（用于按元数据筛选的下拉框）
+#[command]
+pub async fn get_document_metadata_values(
+    project_id: String,
+    key: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<crate::services::document_service::MetadataValueCount>, String> {
+    log::info!("获取元数据取值: project_id={}, key={}", project_id, key);
+
+    // 验证 project_id
+    Uuid::parse_str(&project_id).map_err(|e| format!("无效的项目ID: {}", e))?;
+    if key.trim().is_empty() {
+        return Err("元数据键不能为空".to_string());
+    }
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let document_service_arc = state.document_service();
+    let document_service = document_service_arc.lock().await;
+
+    document_service
+        .get_metadata_values(&project_id, key.trim())
+        .await
+        .map_err(|e| format!("获取元数据取值失败: {}", e))
+}
+
+/// 检索延迟基准测试：对一组查询重复执行"生成向量 + 检索"，分别返回两个阶段的 p50/p95/p99
+#[command]
+pub async fn benchmark_retrieval(
+    project_id: String,
+    queries: Vec<String>,
+    iterations: Option<u32>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::retrieval_benchmark::RetrievalBenchmark, String> {
+    const MAX_QUERIES: usize = 20;
+    const MAX_ITERATIONS: u32 = 50;
+    // 与聊天检索使用相同的 top_k
+    const TOP_K: usize = 5;
+
+    let iterations = iterations.unwrap_or(5);
+    log::info!("检索基准测试: project_id={}, queries={}, iterations={}", project_id, queries.len(), iterations);
+
+    // 验证参数
+    Uuid::parse_str(&project_id).map_err(|e| format!("无效的项目ID: {}", e))?;
+    let queries: Vec<String> = queries
+        .into_iter()
+        .map(|q| q.trim().to_string())
+        .filter(|q| !q.is_empty())
+        .collect();
+    if queries.is_empty() || queries.len() > MAX_QUERIES {
+        return Err(format!("查询数量必须在 1 到 {} 之间", MAX_QUERIES));
+    }
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(format!("迭代次数必须在 1 到 {} 之间", MAX_ITERATIONS));
+    }
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 只在取得服务句柄时持有 DocumentService 锁，多轮 embedding 和检索期间不阻塞其他操作
+    let (indexer, score_threshold) = {
+        let document_service_arc = state.document_service();
+        let document_service = document_service_arc.lock().await;
+        (document_service.indexer(), document_service.score_threshold())
+    };
+
+    indexer
+        .benchmark_retrieval(&project_id, &queries, iterations, TOP_K, score_threshold)
+        .await
+        .map_err(|e| format!("检索基准测试失败: {}", e))
+}
+
+/// 分块大小调优：对每个候选分块大小分块样本并生成向量，用测试查询衡量检索效果，返回推荐的分块大小
+#[command]
+pub async fn optimize_chunk_size(
+    project_id: Option<String>,
+    file_path: Option<String>,
+    candidate_sizes: Vec<usize>,
+    test_queries: Vec<crate::services::chunk_size_tuning::ChunkSizeTestQuery>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::chunk_size_tuning::ChunkSizeRecommendation, String> {
+    const MAX_CANDIDATES: usize = 8;
+    const MAX_QUERIES: usize = 20;
+    // 与聊天检索使用相同的 top_k
+    const TOP_K: usize = 5;
+
+    log::info!(
+        "分块大小调优: project_id={:?}, file_path={:?}, candidates={:?}, queries={}",
+        project_id, file_path, candidate_sizes, test_queries.len()
+    );
+
+    // 验证参数
+    if let Some(project_id) = &project_id {
+        Uuid::parse_str(project_id).map_err(|e| format!("无效的项目ID: {}", e))?;
+    }
+    if candidate_sizes.is_empty() || candidate_sizes.len() > MAX_CANDIDATES {
+        return Err(format!("候选分块大小数量必须在 1 到 {} 之间", MAX_CANDIDATES));
+    }
+    if test_queries.is_empty() || test_queries.len() > MAX_QUERIES {
+        return Err(format!("测试查询数量必须在 1 到 {} 之间", MAX_QUERIES));
+    }
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    // 读取样本和批量生成 embedding 都在 DocumentService 锁外进行
+    let indexer = state.document_service().lock().await.indexer();
+
+    let sample = indexer
+        .load_tuning_sample(project_id.as_deref(), file_path.as_deref())
+        .await
+        .map_err(|e| format!("读取样本失败: {}", e))?;
+
+    indexer
+        .optimize_chunk_size(&sample, &candidate_sizes, &test_queries, TOP_K)
+        .await
+        .map_err(|e| format!("分块大小调优失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_uploads_run_concurrently_up_to_limit_and_keep_file_order() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let mut progress = Vec::new();
+
+        // 靠前的文件耗时更长，完成顺序与输入顺序不同
+        let results = process_concurrently(
+            vec![60u64, 50, 40, 30, 20, 10],
+            3,
+            |delay_ms| {
+                let in_flight = &in_flight;
+                let max_in_flight = &max_in_flight;
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    delay_ms
+                }
+            },
+            |completed, result| progress.push((completed, *result)),
+        )
+        .await;
+
+        assert_eq!(results, vec![60, 50, 40, 30, 20, 10]);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        let completed: Vec<usize> = progress.iter().map(|(completed, _)| *completed).collect();
+        assert_eq!(completed, vec![1, 2, 3, 4, 5, 6]);
+        assert_ne!(progress[0].1, 60);
+    }
+
+    fn upload_response(successful: usize, failed: usize) -> UploadDocumentsResponse {
+        UploadDocumentsResponse {
+            successful: (0..successful)
+                .map(|i| DocumentResponse {
+                    id: Uuid::new_v4().to_string(),
+                    filename: format!("ok-{}.md", i),
+                    file_size: 10,
+                    processing_status: "Completed".to_string(),
+                    created_at: chrono::Utc::now().to_rfc3339(),
+                })
+                .collect(),
+            failed: (0..failed)
+                .map(|i| FailedDocumentInfo {
+                    filename: format!("bad-{}.md", i),
+                    file_path: format!("/tmp/bad-{}.md", i),
+                    error: "文件不存在".to_string(),
+                    error_stage: "validation".to_string(),
+                })
+                .collect(),
+            summary: UploadSummary {
+                total: successful + failed,
+                successful,
+                failed,
+            },
+        }
+    }
+
+    #[test]
+    fn test_all_failed_batch_is_error_only_in_strict_mode() {
+        match finish_upload(upload_response(0, 2), true) {
+            Err(UploadError::AllFailed { failed, .. }) => assert_eq!(failed.len(), 2),
+            other => panic!("严格模式下全部失败应返回 AllFailed: {:?}", other),
+        }
+
+        let response = finish_upload(upload_response(0, 2), false).unwrap();
+        assert_eq!(response.summary.failed, 2);
+    }
+
+    #[test]
+    fn test_partial_success_is_ok_in_strict_mode() {
+        let response = finish_upload(upload_response(1, 2), true).unwrap();
+        assert_eq!(response.successful.len(), 1);
+        assert_eq!(response.failed.len(), 2);
+    }
+
+    #[test]
+    fn test_upload_error_serialization() {
+        let error = finish_upload(upload_response(0, 1), true).unwrap_err();
+        let value = serde_json::to_value(&error).unwrap();
+        assert_eq!(value["kind"], "allFailed");
+        assert_eq!(value["failed"][0]["filename"], "bad-0.md");
+        assert_eq!(value["message"], error.to_string());
+
+        let error = UploadError::from("项目不存在".to_string());
+        assert_eq!(serde_json::to_value(&error).unwrap(), "项目不存在");
+    }
+
+    #[test]
+    fn test_reprocess_sets_error_status_when_any_document_fails() {
+        use crate::models::project::ProjectStatus;
+        use crate::services::document_service::{ReprocessFailure, ReprocessReport};
+
+        let mut report = ReprocessReport { document_count: 2, reprocessed: 2, ..Default::default() };
+        assert_eq!(reprocess_status(&Ok(report.clone())), ProjectStatus::Ready);
+
+        report.reprocessed = 1;
+        report.failed_documents.push(ReprocessFailure {
+            document_id: Uuid::new_v4().to_string(),
+            filename: "moved.pdf".to_string(),
+            error: "源文件不存在: /tmp/moved.pdf".to_string(),
+        });
+        assert_eq!(reprocess_status(&Ok(report)), ProjectStatus::Error);
+        assert_eq!(reprocess_status(&Err("数据库错误".to_string())), ProjectStatus::Error);
+    }
+
+    #[test]
+    fn test_upload_progress_event_payload() {
+        let project_id = Uuid::new_v4();
+        let (stage, _) = parse_error_stage("[阶段1-验证] 文件不存在: /tmp/missing.pdf");
+        let failed = DocumentUploadProgress::file(project_id, "missing.pdf".to_string(), 2, 3, stage);
+        let value = serde_json::to_value(&failed).unwrap();
+        assert_eq!(value["project_id"], project_id.to_string());
+        assert_eq!(value["filename"], "missing.pdf");
+        assert_eq!((value["index"].as_u64(), value["total"].as_u64()), (Some(2), Some(3)));
+        assert_eq!(value["stage"], "validation");
+
+        let done = serde_json::to_value(DocumentUploadProgress::finished(project_id, 3)).unwrap();
+        assert_eq!(done["filename"], serde_json::Value::Null);
+        assert_eq!(done["index"], done["total"]);
+        assert_eq!(done["stage"], "done");
+    }
 }
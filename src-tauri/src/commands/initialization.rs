@@ -1,8 +1,18 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use tauri::{command, AppHandle, State};
-use crate::app_state_wrapper::AppStateWrapper;
+use crate::app_state_wrapper::{AppStateWrapper, InitController};
+use crate::config::AppConfig;
+
+#[derive(Debug, Serialize)]
+pub struct SaveConfigResponse {
+    pub config_path: String,
+    /// 是否已（重新）开始初始化；应用已初始化时新配置需重启后生效
+    pub initialization_started: bool,
+}
 
 /// 前端调用此命令以触发应用初始化
-/// 这样可以确保前端已经准备好接收启动事件
+/// 这样可以确保前端已经准备好接收启动事件；初始化被取消或失败后，可再次调用以重新初始化
 #[command]
 pub async fn trigger_initialization(
     _app_handle: AppHandle,
@@ -19,14 +29,36 @@ pub async fn trigger_initialization(
         }
     }
     
-    log::info!("开始后台初始化...");
-    
-    // 这里的逻辑已经在 setup 中的异步任务里执行
-    // 前端只需要等待事件即可
+    let mut init = wrapper.init.lock().map_err(|e| format!("获取初始化控制器失败: {}", e))?;
+    if init.start()? {
+        log::info!("开始后台初始化...");
+    } else {
+        // 初始化已在后台运行，前端只需要等待事件即可
+        log::info!("后台初始化正在进行中，等待完成");
+    }
     
     Ok(())
 }
 
+/// 取消正在进行的后台初始化（会终止正在运行的 SeekDB 安装进程）
+/// 返回是否确实取消了一个运行中的初始化
+#[command]
+pub async fn cancel_initialization(
+    wrapper: State<'_, AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("前端请求取消初始化");
+
+    let init = wrapper.init.lock().map_err(|e| format!("获取初始化控制器失败: {}", e))?;
+    let cancelled = init.cancel();
+    if cancelled {
+        log::info!("⛔ 已请求取消后台初始化");
+    } else {
+        log::info!("没有正在进行的初始化");
+    }
+
+    Ok(cancelled)
+}
+
 /// 检查初始化状态
 #[command]
 pub async fn check_initialization_status(
@@ -36,3 +68,110 @@ pub async fn check_initialization_status(
     Ok(state_guard.is_some())
 }
 
+
+/// 应用数据目录下的 config.json 路径（启动时优先读取该文件）
+pub(crate) fn app_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join("config.json"))
+        .ok_or_else(|| "无法获取应用数据目录".to_string())
+}
+
+/// 校验并写入配置，应用尚未初始化时触发初始化；返回是否已开始初始化
+///
+/// 首次运行缺少配置时，初始化会停在"配置文件缺失"，保存配置后无需重启即可继续。
+/// 如果初始化仍在运行（例如正在安装 SeekDB），它会在加载配置的步骤读取到新文件。
+pub(crate) fn write_config_and_initialize(
+    config_path: &Path,
+    config: &AppConfig,
+    init: &mut InitController,
+    initialized: bool,
+) -> Result<bool, String> {
+    config
+        .save_validated(config_path)
+        .map_err(|e| format!("保存配置失败: {}", e))?;
+    log::info!("✅ 配置已保存: {:?}", config_path);
+
+    if initialized {
+        log::info!("应用已经初始化，新配置将在重启后生效");
+        return Ok(false);
+    }
+
+    if init.start()? {
+        log::info!("开始后台初始化...");
+    } else {
+        log::info!("后台初始化正在进行中，将在加载配置时读取新配置");
+    }
+    Ok(true)
+}
+
+/// 在界面中提交完整配置：写入 config.json 并继续初始化
+#[command]
+pub async fn save_config(
+    app_handle: AppHandle,
+    wrapper: State<'_, AppStateWrapper>,
+    config: AppConfig,
+) -> Result<SaveConfigResponse, String> {
+    log::info!("前端提交配置");
+
+    let config_path = app_config_path(&app_handle)?;
+    let initialized = wrapper.state.lock().await.is_some();
+
+    let mut init = wrapper.init.lock().map_err(|e| format!("获取初始化控制器失败: {}", e))?;
+    let initialization_started = write_config_and_initialize(&config_path, &config, &mut init, initialized)?;
+
+    Ok(SaveConfigResponse {
+        config_path: config_path.to_string_lossy().to_string(),
+        initialization_started,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cancellation::CancellationToken;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// 模拟初始化任务：记录每次启动时能否加载配置
+    fn controller_recording_config(config_path: PathBuf) -> (InitController, Arc<Mutex<Vec<bool>>>) {
+        let loads = Arc::new(Mutex::new(Vec::new()));
+        let recorded = loads.clone();
+        let mut init = InitController::default();
+        init.set_launcher(Arc::new(move |_cancel: CancellationToken, running: Arc<AtomicBool>| {
+            recorded.lock().unwrap().push(AppConfig::load_from_file(&config_path).is_ok());
+            running.store(false, Ordering::SeqCst);
+        }));
+        (init, loads)
+    }
+
+    #[test]
+    fn test_saving_valid_config_lets_initialization_proceed() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("app-data").join("config.json");
+        let (mut init, loads) = controller_recording_config(config_path.clone());
+
+        // 首次启动：配置缺失，初始化停在加载配置
+        init.start().unwrap();
+        assert_eq!(*loads.lock().unwrap(), vec![false]);
+
+        // 占位 API Key 不会被写入，也不会重新初始化
+        let result = write_config_and_initialize(&config_path, &AppConfig::default_config(), &mut init, false);
+        assert!(result.is_err());
+        assert!(!config_path.exists());
+        assert_eq!(loads.lock().unwrap().len(), 1);
+
+        let mut config = AppConfig::default_config();
+        config.llm.api_key = "sk-test".to_string();
+        let started = write_config_and_initialize(&config_path, &config, &mut init, false).unwrap();
+        assert!(started);
+        assert_eq!(*loads.lock().unwrap(), vec![false, true]);
+        assert_eq!(AppConfig::load_from_file(&config_path).unwrap().llm.api_key, "sk-test");
+
+        // 已初始化时只保存配置
+        let started = write_config_and_initialize(&config_path, &config, &mut init, true).unwrap();
+        assert!(!started);
+        assert_eq!(loads.lock().unwrap().len(), 2);
+    }
+}
@@ -19,6 +19,19 @@ pub struct ProjectResponse {
     pub document_count: u32,
 }
 
+/// 项目详情：项目信息加上从数据库统计的数据
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectDetailsResponse {
+    #[serde(flatten)]
+    pub project: ProjectResponse,
+    pub conversation_count: usize,
+    pub total_chunks: usize,
+    /// 文档块内容的总字节数
+    pub storage_size: u64,
+    /// 当前用量（文档数、文件总字节数）与配额
+    pub quota: crate::services::project_quota::ProjectQuotaUsage,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateProjectResponse {
     pub project: ProjectResponse,
@@ -55,9 +68,15 @@ pub async fn create_project(
 
     log::info!("项目创建成功，ID: {}", project_id);
 
-    // 处理文档上传
+    // 处理文档上传，登记为后台任务以免上传期间临时副本被清理
     let mut document_count = 0;
     let document_service = state.document_service();
+    let files = request.file_paths.iter().map(std::path::PathBuf::from).collect();
+    let _task = state.task_registry().start_with_files(
+        crate::services::task_registry::TaskKind::Upload,
+        project_id.to_string(),
+        files,
+    );
 
     for file_path in request.file_paths {
         match process_document(project_id, file_path, document_service.clone()).await {
@@ -90,6 +109,14 @@ pub async fn create_project(
             .clone()
     };
 
+    // 为项目描述生成向量（失败不影响项目创建），embedding 请求期间不持有 DocumentService 锁
+    let indexer = document_service.lock().await.project_description_indexer();
+    if let Some(indexer) = indexer {
+        if let Err(e) = indexer.index_project_description(&project).await {
+            log::warn!("项目描述向量化失败: {}", e);
+        }
+    }
+
     let response = CreateProjectResponse {
         project: ProjectResponse {
             id: project.id.to_string(),
@@ -113,7 +140,6 @@ async fn process_document(
     document_service: std::sync::Arc<tokio::sync::Mutex<crate::services::document_service::DocumentService>>,
 ) -> Result<uuid::Uuid, String> {
     use std::path::Path;
-    use sha2::{Sha256, Digest};
 
     // 检查文件是否存在
     let path = Path::new(&file_path);
@@ -131,12 +157,9 @@ async fn process_document(
     let content = std::fs::read(&file_path)
         .map_err(|e| format!("无法读取文件内容: {}", e))?;
 
-    let mut hasher = Sha256::new();
-    hasher.update(&content);
-    let content_hash = format!("{:x}", hasher.finalize());
-
     // 添加文档到服务
     let mut doc_service = document_service.lock().await;
+    let content_hash = doc_service.hash_content(&content);
     let document_id = doc_service
         .add_document(project_id, file_path, file_size, content_hash)
         .await
@@ -145,8 +168,10 @@ async fn process_document(
     Ok(document_id)
 }
 
+/// 获取项目列表，默认不包含已归档的项目
 #[command]
 pub async fn get_projects(
+    include_archived: Option<bool>,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
 ) -> Result<Vec<ProjectResponse>, String> {
     log::info!("获取项目列表");
@@ -156,7 +181,7 @@ pub async fn get_projects(
 
     let project_service_arc = state.project_service();
     let project_service = project_service_arc.lock().await;
-    let projects = project_service.list_projects();
+    let projects = project_service.list_visible_projects(include_archived.unwrap_or(false));
 
     let response: Vec<ProjectResponse> = projects
         .into_iter()
@@ -175,11 +200,183 @@ pub async fn get_projects(
     Ok(response)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentProjectResponse {
+    #[serde(flatten)]
+    pub project: ProjectResponse,
+    /// 最近活动时间：项目更新时间与最新对话更新时间中的较大者
+    pub last_activity_at: String,
+}
+
+/// 获取最近使用的项目（按最近对话或上传活动排序），默认返回 5 个
+#[command]
+pub async fn get_recent_projects(
+    limit: Option<usize>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<RecentProjectResponse>, String> {
+    let limit = limit.unwrap_or(5);
+    log::info!("获取最近使用的项目: limit={}", limit);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let conversation_activity = {
+        let conversation_service_arc = state.conversation_service();
+        let conversation_service = conversation_service_arc.lock().await;
+        conversation_service.latest_activity_by_project()
+    };
+
+    let project_service_arc = state.project_service();
+    let project_service = project_service_arc.lock().await;
+
+    let response: Vec<RecentProjectResponse> = project_service
+        .list_recent_projects(&conversation_activity, limit)
+        .into_iter()
+        .map(|(project, last_activity)| RecentProjectResponse {
+            project: ProjectResponse {
+                id: project.id.to_string(),
+                name: project.name.clone(),
+                description: project.description.clone(),
+                status: project.status.to_string(),
+                created_at: project.created_at.to_rfc3339(),
+                updated_at: project.updated_at.to_rfc3339(),
+                document_count: project.document_count,
+            },
+            last_activity_at: last_activity.to_rfc3339(),
+        })
+        .collect();
+
+    log::info!("返回 {} 个最近项目", response.len());
+    Ok(response)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectRouteResponse {
+    #[serde(flatten)]
+    pub project: ProjectResponse,
+    /// 问题与项目名称/描述的向量相似度
+    pub score: f64,
+}
+
+/// 根据项目名称和描述的向量相似度，为问题推荐最相关的项目，默认返回 3 个
+#[command]
+pub async fn route_query_to_project(
+    query: String,
+    limit: Option<usize>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<ProjectRouteResponse>, String> {
+    let limit = limit.unwrap_or(3);
+    log::info!("问题路由: query={}, limit={}", query, limit);
+
+    if query.trim().is_empty() {
+        return Err("问题不能为空".to_string());
+    }
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let projects: Vec<crate::models::project::Project> = {
+        let project_service_arc = state.project_service();
+        let project_service = project_service_arc.lock().await;
+        project_service.list_projects().into_iter().cloned().collect()
+    };
+
+    // 补齐项目向量和生成问题向量期间不持有 DocumentService 锁
+    let indexer = state
+        .document_service()
+        .lock()
+        .await
+        .project_description_indexer()
+        .ok_or_else(|| "问题路由失败: 项目描述向量化未开启（ingestion.embedProjectDescriptions）".to_string())?;
+    let routes = indexer
+        .route_query_to_project(&query, &projects, limit)
+        .await
+        .map_err(|e| format!("问题路由失败: {}", e))?;
+
+    let response: Vec<ProjectRouteResponse> = routes
+        .into_iter()
+        .filter_map(|route| {
+            let project = projects.iter().find(|p| p.id == route.project_id)?;
+            Some(ProjectRouteResponse {
+                project: ProjectResponse {
+                    id: project.id.to_string(),
+                    name: project.name.clone(),
+                    description: project.description.clone(),
+                    status: project.status.to_string(),
+                    created_at: project.created_at.to_rfc3339(),
+                    updated_at: project.updated_at.to_rfc3339(),
+                    document_count: project.document_count,
+                },
+                score: route.score,
+            })
+        })
+        .collect();
+
+    log::info!("返回 {} 个候选项目", response.len());
+    Ok(response)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StaleProjectResponse {
+    #[serde(flatten)]
+    pub project: ProjectResponse,
+    pub last_activity_at: String,
+    pub reasons: Vec<crate::services::project_service::StaleReason>,
+}
+
+/// 找出没有文档或超过 `days` 天（默认 30）没有活动的项目，供前端提示清理；不会删除任何数据
+#[command]
+pub async fn find_stale_projects(
+    days: Option<i64>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<StaleProjectResponse>, String> {
+    let days = days.unwrap_or(30);
+    log::info!("查找闲置项目: days={}", days);
+
+    if days < 0 {
+        return Err("天数不能为负数".to_string());
+    }
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let conversation_activity = {
+        let conversation_service_arc = state.conversation_service();
+        let conversation_service = conversation_service_arc.lock().await;
+        conversation_service.latest_activity_by_project()
+    };
+
+    let project_service_arc = state.project_service();
+    let project_service = project_service_arc.lock().await;
+
+    let response: Vec<StaleProjectResponse> = project_service
+        .find_stale_projects(&conversation_activity, days)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|stale| StaleProjectResponse {
+            project: ProjectResponse {
+                id: stale.project.id.to_string(),
+                name: stale.project.name.clone(),
+                description: stale.project.description.clone(),
+                status: stale.project.status.to_string(),
+                created_at: stale.project.created_at.to_rfc3339(),
+                updated_at: stale.project.updated_at.to_rfc3339(),
+                document_count: stale.project.document_count,
+            },
+            last_activity_at: stale.last_activity.to_rfc3339(),
+            reasons: stale.reasons,
+        })
+        .collect();
+
+    log::info!("找到 {} 个闲置项目", response.len());
+    Ok(response)
+}
+
 #[command]
 pub async fn get_project_details(
     project_id: String,
     wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
-) -> Result<ProjectResponse, String> {
+) -> Result<ProjectDetailsResponse, String> {
     log::info!("获取项目详情: {}", project_id);
 
     // 获取应用状态
@@ -188,20 +385,37 @@ pub async fn get_project_details(
     let project_uuid = uuid::Uuid::parse_str(&project_id)
         .map_err(|_| "无效的项目ID格式".to_string())?;
 
+    let conversation_count = state
+        .conversation_service()
+        .lock()
+        .await
+        .count_conversations(Some(project_uuid));
+    let quota = state.document_service().lock().await.project_quota_usage(project_uuid);
+
     let project_service_arc = state.project_service();
     let project_service = project_service_arc.lock().await;
     let project = project_service
         .get_project(project_uuid)
         .ok_or_else(|| "项目未找到".to_string())?;
+    let stats = project_service
+        .get_project_stats(project_uuid, conversation_count, quota)
+        .await
+        .map_err(|e| format!("统计项目数据失败: {}", e))?;
 
-    let response = ProjectResponse {
-        id: project.id.to_string(),
-        name: project.name.clone(),
-        description: project.description.clone(),
-        status: project.status.to_string(),
-        created_at: project.created_at.to_rfc3339(),
-        updated_at: project.updated_at.to_rfc3339(),
-        document_count: project.document_count,
+    let response = ProjectDetailsResponse {
+        project: ProjectResponse {
+            id: project.id.to_string(),
+            name: project.name.clone(),
+            description: project.description.clone(),
+            status: project.status.to_string(),
+            created_at: project.created_at.to_rfc3339(),
+            updated_at: project.updated_at.to_rfc3339(),
+            document_count: stats.document_count as u32,
+        },
+        conversation_count: stats.conversation_count,
+        total_chunks: stats.total_chunks,
+        storage_size: stats.storage_size,
+        quota: stats.quota,
     };
 
     log::info!("返回项目详情: {}", project.name);
@@ -231,6 +445,72 @@ pub async fn delete_project(
     Ok(true)
 }
 
+/// 归档项目：从默认项目列表中隐藏，保留对话和向量数据
+#[command]
+pub async fn archive_project(
+    project_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("归档项目: {}", project_id);
+
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = uuid::Uuid::parse_str(&project_id)
+        .map_err(|_| "无效的项目ID格式".to_string())?;
+
+    let project_service_arc = state.project_service();
+    let mut project_service = project_service_arc.lock().await;
+    project_service
+        .archive_project(project_uuid)
+        .map_err(|e| format!("归档项目失败: {}", e))?;
+
+    log::info!("项目已归档: {}", project_id);
+    Ok(true)
+}
+
+/// 恢复已归档的项目
+#[command]
+pub async fn restore_project(
+    project_id: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<bool, String> {
+    log::info!("恢复项目: {}", project_id);
+
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = uuid::Uuid::parse_str(&project_id)
+        .map_err(|_| "无效的项目ID格式".to_string())?;
+
+    let project_service_arc = state.project_service();
+    let mut project_service = project_service_arc.lock().await;
+    project_service
+        .restore_project(project_uuid)
+        .map_err(|e| format!("恢复项目失败: {}", e))?;
+
+    log::info!("项目已恢复: {}", project_id);
+    Ok(true)
+}
+
+/// 按数据库重新计算所有项目的文档数，修正部分失败或崩溃后的偏差，返回被修正的项目
+#[command]
+pub async fn recompute_document_counts(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<crate::services::project_service::DocumentCountCorrection>, String> {
+    log::info!("重新计算项目文档数");
+
+    let state = wrapper.get_state().await?;
+
+    let project_service_arc = state.project_service();
+    let mut project_service = project_service_arc.lock().await;
+    let corrections = project_service
+        .recompute_document_counts()
+        .await
+        .map_err(|e| format!("重新计算文档数失败: {}", e))?;
+
+    log::info!("已修正 {} 个项目的文档数", corrections.len());
+    Ok(corrections)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RenameProjectRequest {
     pub project_id: String,
@@ -255,18 +535,26 @@ pub async fn rename_project(
     let project_uuid = uuid::Uuid::parse_str(&request.project_id)
         .map_err(|_| "无效的项目ID格式".to_string())?;
 
-    let project_service_arc = state.project_service();
-    let mut project_service = project_service_arc.lock().await;
-
-    // 更新项目名称
-    project_service
-        .update_project(project_uuid, Some(request.new_name.trim().to_string()), None)
-        .map_err(|e| format!("重命名项目失败: {}", e))?;
+    // 更新项目名称并获取更新后的项目信息
+    let project = {
+        let project_service_arc = state.project_service();
+        let mut project_service = project_service_arc.lock().await;
+        project_service
+            .update_project(project_uuid, Some(request.new_name.trim().to_string()), None)
+            .map_err(|e| format!("重命名项目失败: {}", e))?;
+        project_service
+            .get_project(project_uuid)
+            .ok_or_else(|| "项目未找到".to_string())?
+            .clone()
+    };
 
-    // 获取更新后的项目信息
-    let project = project_service
-        .get_project(project_uuid)
-        .ok_or_else(|| "项目未找到".to_string())?;
+    // 名称变化后更新项目描述向量（失败不影响重命名），embedding 请求期间不持有任何服务锁
+    let indexer = state.document_service().lock().await.project_description_indexer();
+    if let Some(indexer) = indexer {
+        if let Err(e) = indexer.index_project_description(&project).await {
+            log::warn!("项目描述向量化失败: {}", e);
+        }
+    }
 
     let response = ProjectResponse {
         id: project.id.to_string(),
@@ -281,3 +569,199 @@ pub async fn rename_project(
     log::info!("项目重命名成功: {}", project.name);
     Ok(response)
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RechunkProjectRequest {
+    pub project_id: String,
+    pub max_chunk_size: usize,
+    pub chunk_overlap: usize,
+}
+
+/// 使用新的分块参数重新切分项目内所有文档，并通过 `rechunk-progress` 事件报告进度
+#[command]
+pub async fn rechunk_project(
+    request: RechunkProjectRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+    window: tauri::Window,
+) -> Result<crate::services::document_service::RechunkReport, String> {
+    log::info!("重新分块项目: {:?}", request);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = uuid::Uuid::parse_str(&request.project_id)
+        .map_err(|_| "无效的项目ID格式".to_string())?;
+
+    {
+        let project_service_arc = state.project_service();
+        let project_service = project_service_arc.lock().await;
+        if project_service.get_project(project_uuid).is_none() {
+            return Err("项目未找到".to_string());
+        }
+    }
+
+    let task = state
+        .task_registry()
+        .start(crate::services::task_registry::TaskKind::Rechunk, request.project_id.clone());
+    // 重新分块和重新生成 embedding 在 DocumentService 锁外进行
+    let indexer = state.document_service().lock().await.indexer();
+    let report = indexer
+        .rechunk_project(
+            &request.project_id,
+            request.max_chunk_size,
+            request.chunk_overlap,
+            |current, total, filename| {
+                task.set_progress(current, total);
+                let _ = window.emit("rechunk-progress", serde_json::json!({
+                    "project_id": request.project_id,
+                    "current": current,
+                    "total": total,
+                    "filename": filename,
+                }));
+            },
+        )
+        .await
+        .map_err(|e| format!("重新分块失败: {}", e))?;
+
+    log::info!("项目重新分块完成: {:?}", report);
+    Ok(report)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportProjectRequest {
+    pub project_id: String,
+    /// 导出文件路径，缺少扩展名时补上 .mkb
+    pub output_path: String,
+}
+
+/// 将项目（项目信息、文档块及向量、对话和消息）导出为单个 .mkb 文件，返回实际写入的路径
+#[command]
+pub async fn export_project(
+    request: ExportProjectRequest,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<String, String> {
+    use crate::services::project_bundle;
+
+    log::info!("导出项目: {} -> {}", request.project_id, request.output_path);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let project_uuid = uuid::Uuid::parse_str(&request.project_id)
+        .map_err(|_| "无效的项目ID格式".to_string())?;
+
+    let project = {
+        let project_service_arc = state.project_service();
+        let project_service = project_service_arc.lock().await;
+        project_service
+            .get_project(project_uuid)
+            .cloned()
+            .ok_or_else(|| "项目未找到".to_string())?
+    };
+
+    let (db, embedding_model) = {
+        let document_service = state.document_service();
+        let document_service_guard = document_service.lock().await;
+        (document_service_guard.get_vector_db(), document_service_guard.embedding_model().to_string())
+    };
+    let bundle = {
+        let db_guard = db.lock().await;
+        project_bundle::collect_project_bundle(&db_guard, &project, &embedding_model)
+            .map_err(|e| format!("读取项目数据失败: {}", e))?
+    };
+
+    let mut output_path = std::path::PathBuf::from(&request.output_path);
+    if output_path.extension().is_none() {
+        output_path.set_extension(project_bundle::BUNDLE_EXTENSION);
+    }
+    let file = std::fs::File::create(&output_path)
+        .map_err(|e| format!("创建导出文件失败: {}", e))?;
+    project_bundle::write_bundle(std::io::BufWriter::new(file), &bundle)
+        .map_err(|e| format!("导出项目失败: {}", e))?;
+
+    log::info!(
+        "✅ 项目导出完成: {} 个文档块, {} 个对话, {} 条消息 -> {}",
+        bundle.chunks.len(),
+        bundle.conversations.len(),
+        bundle.messages.len(),
+        output_path.display()
+    );
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// 导入前检查 .mkb 文件：返回记录数量、解压后大小，并与数据库的向量维度比较，不导入任何数据
+#[command]
+pub async fn inspect_project_archive(
+    path: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::project_bundle::BundleInspection, String> {
+    use crate::services::project_bundle;
+
+    log::info!("检查导出文件: {}", path);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let db = state.document_service().lock().await.get_vector_db();
+    let database_dimension = db
+        .lock()
+        .await
+        .get_embedding_dimension()
+        .map_err(|e| format!("获取数据库向量维度失败: {}", e))?
+        .dimension;
+
+    let file = std::fs::File::open(&path)
+        .map_err(|e| format!("打开导出文件失败: {}", e))?;
+    project_bundle::inspect_bundle(std::io::BufReader::new(file), database_dimension)
+        .map_err(|e| format!("读取导出文件失败: {}", e))
+}
+
+/// 从 .mkb 文件导入项目，为所有记录分配新的 ID；向量维度与当前配置不一致时在结果中给出提示
+#[command]
+pub async fn import_project(
+    bundle_path: String,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<crate::services::project_bundle::ProjectImportReport, String> {
+    use crate::services::project_bundle;
+
+    log::info!("导入项目: {}", bundle_path);
+
+    // 获取应用状态
+    let state = wrapper.get_state().await?;
+
+    let file = std::fs::File::open(&bundle_path)
+        .map_err(|e| format!("打开导出文件失败: {}", e))?;
+    let bundle = project_bundle::read_bundle(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取导出文件失败: {}", e))?;
+    let bundle = project_bundle::with_fresh_ids(bundle);
+
+    let document_service = state.document_service();
+    let (db, column_dimension) = {
+        let document_service_guard = document_service.lock().await;
+        (document_service_guard.get_vector_db(), document_service_guard.embedding_dimension())
+    };
+    let report = {
+        let mut db_guard = db.lock().await;
+        project_bundle::restore_bundle(&mut db_guard, &bundle, column_dimension)
+            .map_err(|e| format!("导入项目失败: {}", e))?
+    };
+
+    // 数据直接写入了数据库，只把导入的项目、对话和文档同步到内存；
+    // 不重新加载全部数据，其他项目中上传中或处理失败的文档不受影响
+    let documents = crate::services::document_service::reconstruct_documents(&bundle.chunks, &std::collections::HashMap::new());
+    state.project_service().lock().await.insert_imported_project(bundle.project);
+    state
+        .conversation_service()
+        .lock()
+        .await
+        .insert_imported(bundle.conversations, bundle.messages);
+    {
+        let mut document_service_guard = document_service.lock().await;
+        for document in documents {
+            document_service_guard.upsert_document(document);
+        }
+    }
+
+    log::info!("✅ 项目导入完成: {:?}", report);
+    Ok(report)
+}
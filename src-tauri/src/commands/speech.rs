@@ -1,14 +1,16 @@
-use tauri::command;
+use tauri::{command, Window};
 use serde::{Deserialize, Serialize};
 use base64::{Engine as _, engine::general_purpose};
 use crate::config::AppConfig;
-use crate::services::speech_service::AliyunAsrService;
+use crate::services::speech_service::{AliyunAsrService, SpeechStreamRegistry, StreamingRecognition};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SpeechConfig {
     pub configured: bool,
     pub provider: Option<String>,
     pub message: Option<String>,
+    /// 服务是否原生支持流式识别；一句话识别接口不支持，start_streaming_recognition 只是分段重新识别
+    pub streaming_supported: bool,
 }
 
 /// 检查语音识别配置
@@ -17,6 +19,7 @@ pub async fn check_speech_config() -> Result<SpeechConfig, String> {
     match load_speech_config().await {
         Ok((provider, _)) => Ok(SpeechConfig {
             configured: true,
+            streaming_supported: false,
             provider: Some(provider),
             message: Some("语音识别服务已配置".to_string()),
         }),
@@ -24,6 +27,7 @@ pub async fn check_speech_config() -> Result<SpeechConfig, String> {
             configured: false,
             provider: None,
             message: Some(e),
+            streaming_supported: false,
         }),
     }
 }
@@ -49,19 +53,109 @@ pub async fn recognize_speech(
     let (provider, config) = load_speech_config().await
         .map_err(|e| format!("配置错误: {}", e))?;
 
-    match provider.as_str() {
+    let mut service = create_asr_service(&provider, config)?;
+    service.recognize_speech(&audio_bytes).await
+        .map_err(|e| format!("语音识别失败: {}", e))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamingRecognitionRequest {
+    /// 第一段音频不传，之后传入返回的 session_id
+    pub session_id: Option<String>,
+    /// Base64 编码的音频片段（16kHz 16bit 单声道 PCM），可以为空
+    pub audio_chunk: String,
+    /// 最后一段音频：识别全部音频、发送 speech-final 并结束会话
+    #[serde(default)]
+    pub is_final: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamingRecognitionResponse {
+    pub session_id: String,
+    /// 最终识别结果，只在 is_final 时返回
+    pub text: Option<String>,
+}
+
+/// 流式语音识别：重复调用并依次传入音频片段
+///
+/// 识别过程中发送 `speech-partial` 事件（中间结果），最后一段之后发送 `speech-final` 事件。
+/// 短录音可以直接使用 recognize_speech。
+#[command]
+pub async fn start_streaming_recognition(
+    request: StreamingRecognitionRequest,
+    window: Window,
+    sessions: tauri::State<'_, SpeechStreamRegistry>,
+) -> Result<StreamingRecognitionResponse, String> {
+    let audio_bytes = general_purpose::STANDARD
+        .decode(&request.audio_chunk)
+        .map_err(|e| format!("Base64解码失败: {}", e))?;
+
+    let (session_id, session) = match &request.session_id {
+        Some(session_id) => {
+            let session = sessions.get(session_id).ok_or("语音识别会话不存在或已结束")?;
+            (session_id.clone(), session)
+        }
+        None => {
+            let (provider, config) = load_speech_config().await
+                .map_err(|e| format!("配置错误: {}", e))?;
+            let service = create_asr_service(&provider, config)?;
+            let (session_id, session) = sessions.start(StreamingRecognition::new(service));
+            log::info!("🎙️  开始流式语音识别: {}", session_id);
+            (session_id, session)
+        }
+    };
+
+    let partial_audio = match session.push(&audio_bytes) {
+        Ok(partial_audio) => partial_audio,
+        Err(e) => {
+            sessions.remove(&session_id);
+            return Err(format!("语音识别失败: {}", e));
+        }
+    };
+
+    if request.is_final {
+        sessions.remove(&session_id);
+        let text = session.finish().await
+            .map_err(|e| format!("语音识别失败: {}", e))?;
+        log::info!("🎙️  流式语音识别完成: {}", session_id);
+        let _ = window.emit("speech-final", serde_json::json!({
+            "session_id": session_id,
+            "text": text
+        }));
+        return Ok(StreamingRecognitionResponse { session_id, text: Some(text) });
+    }
+
+    if let Some(audio) = partial_audio {
+        // 中间识别在后台进行，不阻塞后续音频片段；失败不影响继续录音，最终结果会重新识别全部音频
+        let session_id = session_id.clone();
+        tauri::async_runtime::spawn(async move {
+            match session.recognize_partial(&audio).await {
+                Some(Ok(text)) => {
+                    let _ = window.emit("speech-partial", serde_json::json!({
+                        "session_id": session_id,
+                        "text": text
+                    }));
+                }
+                Some(Err(e)) => log::warn!("⚠️  中间语音识别失败: {}", e),
+                None => log::debug!("上一次中间识别尚未完成，跳过本次中间识别"),
+            }
+        });
+    }
+
+    Ok(StreamingRecognitionResponse { session_id, text: None })
+}
+
+fn create_asr_service(provider: &str, config: AppConfig) -> Result<AliyunAsrService, String> {
+    match provider {
         "aliyun" => {
             let speech_config = config.speech.ok_or("语音配置不存在")?;
             let aliyun_config = speech_config.aliyun.ok_or("阿里云配置不存在")?;
 
-            let mut service = AliyunAsrService::new(
+            Ok(AliyunAsrService::new(
                 aliyun_config.access_key_id,
                 aliyun_config.access_key_secret,
                 aliyun_config.app_key,
-            );
-
-            service.recognize_speech(&audio_bytes).await
-                .map_err(|e| format!("语音识别失败: {}", e))
+            ))
         }
         _ => Err(format!("不支持的语音服务提供商: {}", provider)),
     }
@@ -1,21 +1,30 @@
 use serde::{Deserialize, Serialize};
-use tauri::command;
+use tauri::{command, AppHandle};
 use tauri::api::dialog::blocking::FileDialogBuilder;
 use std::path::Path;
 use std::fs;
+use crate::commands::initialization;
+use crate::config::AppConfig;
+use crate::services::seekdb_adapter::DimensionSource;
+use crate::utils::path_size;
+use crate::services::health_check::{self, SystemHealth};
+use crate::services::python_env::{PythonEnv, PythonEnvReport};
+use crate::services::temp_artifacts::{self, TempArtifact, TempCleanupReport, TempLocations};
+use crate::services::usage_report::{self, UsageReport};
 
+/// 各子系统的连通性，供设置页的诊断面板展示
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppStatusResponse {
-    pub status: String,
-    pub version: String,
-    pub database_status: String,
-    pub vector_db_status: String,
-    pub llm_service_status: String,
+    pub db_ok: bool,
+    pub python_ok: bool,
+    pub llm_ok: bool,
+    pub embedding_ok: bool,
+    /// 每个子系统一条说明，失败时包含错误信息
+    pub details: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConfigureLLMRequest {
-    pub provider: String,
     pub api_key: Option<String>,
     pub model: String,
     pub base_url: Option<String>,
@@ -28,16 +37,528 @@ pub struct FileInfo {
     pub size: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectStorageResponse {
+    pub project_id: String,
+    pub project_name: Option<String>,
+    pub chunk_count: i64,
+    pub content_bytes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageBreakdownResponse {
+    pub database_bytes: u64,
+    pub projects: Vec<ProjectStorageResponse>,
+    pub embedding_cache_bytes: u64,
+    pub model_cache_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DbEmbeddingDimensionResponse {
+    /// 数据库中向量的维度
+    pub dimension: usize,
+    /// 维度来源：stored_vector（已存储的向量）、table_schema（表定义）或 default（默认值）
+    pub source: DimensionSource,
+    /// 当前配置的 embedding 模型输出的维度
+    pub model_dimension: usize,
+    pub matches: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReloadFromDatabaseResponse {
+    pub projects: usize,
+    pub conversations: usize,
+    pub messages: usize,
+    pub documents: usize,
+}
+
+/// 汇总 Python 环境和健康检查结果；`health` 为 Err 时（应用尚未初始化）数据库、LLM、embedding 均视为不可用
+fn build_app_status(python_env: &PythonEnv, health: Result<&SystemHealth, &str>) -> AppStatusResponse {
+    let python_ok = python_env.venv_exists();
+    let mut details = vec![if python_ok {
+        format!("Python: 虚拟环境正常 ({})", python_env.get_python_executable().display())
+    } else {
+        format!("Python: 虚拟环境不存在 ({})", python_env.get_venv_dir().display())
+    }];
+
+    let health = match health {
+        Ok(health) => health,
+        Err(e) => {
+            details.push(format!("应用尚未初始化，无法检查数据库、LLM 和 embedding 服务: {}", e));
+            return AppStatusResponse { db_ok: false, python_ok, llm_ok: false, embedding_ok: false, details };
+        }
+    };
+
+    for (name, component) in [("SeekDB", &health.database), ("LLM", &health.llm), ("Embedding", &health.embedding)] {
+        details.push(match &component.error {
+            None => format!("{}: 正常 ({} ms)", name, component.latency_ms),
+            Some(error) => format!("{}: 不可用 - {}", name, error),
+        });
+    }
+
+    AppStatusResponse {
+        db_ok: health.database.healthy,
+        python_ok,
+        llm_ok: health.llm.healthy,
+        embedding_ok: health.embedding.healthy,
+        details,
+    }
+}
+
+/// 检查 SeekDB、Python 环境、LLM 和 embedding 服务的连通性；数据库、LLM、embedding 的结果
+/// 与 `get_system_health` 共用缓存，`force` 为 true 时重新检查
+#[command]
+pub async fn get_app_status(
+    force: Option<bool>,
+    app_handle: AppHandle,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<AppStatusResponse, String> {
+    log::info!("获取应用状态");
+
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "无法获取应用数据目录".to_string())?;
+    let python_env = PythonEnv::new(&app_data_dir)
+        .map_err(|e| format!("Python 环境初始化失败: {}", e))?;
+
+    let status = match wrapper.get_state().await {
+        Ok(state) => {
+            let health = state
+                .health_cache()
+                .get_or_check(force.unwrap_or(false), || health_check::run_health_checks(&state))
+                .await;
+            build_app_status(&python_env, Ok(&health))
+        }
+        Err(e) => build_app_status(&python_env, Err(&e)),
+    };
+
+    log::info!(
+        "应用状态: db={}, python={}, llm={}, embedding={}",
+        status.db_ok,
+        status.python_ok,
+        status.llm_ok,
+        status.embedding_ok
+    );
+    Ok(status)
+}
+
+/// 在界面中配置 LLM 服务：写入 config.json 的 llm 部分（保留其他配置）并继续初始化
+/// 返回是否已开始初始化；应用已初始化时新配置需重启后生效
+#[command]
+pub async fn configure_llm_service(
+    app_handle: AppHandle,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+    request: ConfigureLLMRequest,
+) -> Result<bool, String> {
+    log::info!("配置 LLM 服务: model={}", request.model);
+
+    let config_path = initialization::app_config_path(&app_handle)?;
+    // 只有配置文件不存在时才从默认配置开始，已有但无法解析的配置不能被默默覆盖
+    let mut config = if config_path.exists() {
+        AppConfig::load_from_file(&config_path).map_err(|e| format!("读取配置文件失败: {}", e))?
+    } else {
+        AppConfig::default_config()
+    };
+    config.llm.api_key = request.api_key.unwrap_or_default().trim().to_string();
+    config.llm.model = request.model.trim().to_string();
+    config.llm.base_url = request.base_url.filter(|url| !url.trim().is_empty());
+
+    let initialized = wrapper.state.lock().await.is_some();
+    let mut init = wrapper.init.lock().map_err(|e| format!("获取初始化控制器失败: {}", e))?;
+    initialization::write_config_and_initialize(&config_path, &config, &mut init, initialized)
+}
+
+/// 检测当前数据库使用的向量维度，便于为继承来的数据库配置匹配的 embedding 模型
+#[command]
+pub async fn get_db_embedding_dimension(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<DbEmbeddingDimensionResponse, String> {
+    log::info!("检测数据库向量维度");
+
+    let state = wrapper.get_state().await?;
+
+    let document_service = state.document_service();
+    let document_service_guard = document_service.lock().await;
+    let model_dimension = document_service_guard.embedding_dimension();
+
+    let db = document_service_guard.get_vector_db();
+    let db_guard = db.lock().await;
+    let detected = db_guard
+        .get_embedding_dimension()
+        .map_err(|e| format!("检测向量维度失败: {}", e))?;
+
+    if detected.dimension != model_dimension {
+        log::warn!("⚠️  数据库向量维度 ({}) 与当前模型维度 ({}) 不一致", detected.dimension, model_dimension);
+    }
+
+    Ok(DbEmbeddingDimensionResponse {
+        dimension: detected.dimension,
+        source: detected.source,
+        model_dimension,
+        matches: detected.dimension == model_dimension,
+    })
+}
+
+/// 重新从数据库加载项目、对话和文档到内存，用于数据库被外部修改（导入、修复、迁移）后同步
+#[command]
+pub async fn reload_from_database(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<ReloadFromDatabaseResponse, String> {
+    log::info!("🔄 重新从数据库加载内存数据");
+
+    let state = wrapper.get_state().await?;
+    if wrapper.init.lock().map_err(|e| format!("获取初始化控制器失败: {}", e))?.is_running() {
+        return Err("应用正在初始化，请稍后再试".to_string());
+    }
+
+    // 文档索引在 DocumentService 锁外进行，锁空闲不代表没有进行中的上传，先检查后台任务
+    let active_tasks = state.task_registry().list();
+    if !active_tasks.is_empty() {
+        return Err(format!("有 {} 个后台任务正在进行中，请稍后再试", active_tasks.len()));
+    }
+
+    // 保存消息等操作进行中时会持有对应服务的锁，此时不重新加载
+    let busy = |_| "有操作正在进行中，请稍后再试".to_string();
+    let project_service = state.project_service();
+    let conversation_service = state.conversation_service();
+    let document_service = state.document_service();
+    let mut project_service_guard = project_service.try_lock().map_err(busy)?;
+    let mut conversation_service_guard = conversation_service.try_lock().map_err(busy)?;
+    let mut document_service_guard = document_service.try_lock().map_err(busy)?;
+
+    let projects = project_service_guard
+        .reload_from_db()
+        .map_err(|e| format!("重新加载项目失败: {}", e))?;
+    let project_ids: Vec<uuid::Uuid> = project_service_guard.list_projects().iter().map(|p| p.id).collect();
+
+    let (conversations, messages) = conversation_service_guard
+        .reload_from_database()
+        .await
+        .map_err(|e| format!("重新加载对话失败: {}", e))?;
+
+    let documents = document_service_guard
+        .reload_from_database(&project_ids)
+        .await
+        .map_err(|e| format!("重新加载文档失败: {}", e))?;
+
+    log::info!(
+        "✅ 重新加载完成: {} 个项目, {} 个对话, {} 条消息, {} 个文档",
+        projects,
+        conversations,
+        messages,
+        documents
+    );
+    Ok(ReloadFromDatabaseResponse {
+        projects,
+        conversations,
+        messages,
+        documents,
+    })
+}
+
+/// 获取磁盘占用明细（数据库文件、各项目文档块、缓存目录）
+#[command]
+pub async fn get_storage_breakdown(
+    app_handle: AppHandle,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<StorageBreakdownResponse, String> {
+    log::info!("获取磁盘占用明细");
+
+    let state = wrapper.get_state().await?;
+
+    // 数据库文件路径与各项目的文档块统计
+    let (db_path, project_stats) = {
+        let document_service = state.document_service();
+        let document_service_guard = document_service.lock().await;
+        let db = document_service_guard.get_vector_db();
+        let db_guard = db.lock().await;
+
+        let project_stats = db_guard
+            .get_project_storage_stats()
+            .map_err(|e| format!("统计项目存储失败: {}", e))?;
+        (db_guard.get_db_path().to_string(), project_stats)
+    };
+
+    let projects: Vec<ProjectStorageResponse> = {
+        let project_service = state.project_service();
+        let project_service_guard = project_service.lock().await;
+        project_stats
+            .into_iter()
+            .map(|stat| {
+                let project_name = uuid::Uuid::parse_str(&stat.project_id)
+                    .ok()
+                    .and_then(|id| project_service_guard.get_project(id))
+                    .map(|p| p.name.clone());
+                ProjectStorageResponse {
+                    project_id: stat.project_id,
+                    project_name,
+                    chunk_count: stat.chunk_count,
+                    content_bytes: stat.content_bytes,
+                }
+            })
+            .collect()
+    };
+
+    let app_data_dir = app_handle.path_resolver().app_data_dir();
+    let breakdown = build_storage_breakdown(Path::new(&db_path), app_data_dir.as_deref(), projects);
+
+    log::info!(
+        "磁盘占用: 数据库={} 字节, 模型缓存={} 字节, 项目数={}",
+        breakdown.database_bytes, breakdown.model_cache_bytes, breakdown.projects.len()
+    );
+
+    Ok(breakdown)
+}
+
+/// 统计数据库文件和应用数据目录下各缓存的大小；应用数据目录未知时缓存大小按 0 计
+fn build_storage_breakdown(
+    db_path: &Path,
+    app_data_dir: Option<&Path>,
+    projects: Vec<ProjectStorageResponse>,
+) -> StorageBreakdownResponse {
+    let database_bytes = path_size(db_path);
+
+    // 模型缓存目录（与 main.rs 中创建的 models 目录一致）
+    let model_cache_bytes = app_data_dir
+        .map(|dir| path_size(&dir.join("models")))
+        .unwrap_or(0);
+
+    // 持久化 Embedding 缓存文件（未开启时不存在）
+    let embedding_cache_bytes = app_data_dir
+        .map(|dir| path_size(&dir.join(crate::services::embedding_cache::EMBEDDING_CACHE_FILE)))
+        .unwrap_or(0);
+
+    StorageBreakdownResponse {
+        database_bytes,
+        projects,
+        embedding_cache_bytes,
+        model_cache_bytes,
+        total_bytes: database_bytes + embedding_cache_bytes + model_cache_bytes,
+    }
+}
+
+/// 清空持久化 embedding 缓存（内存中的条目和缓存文件），返回删除的条目数
+///
+/// 之后索引的文档会重新调用 embedding API；`embedding.persistentCache` 设为 false 时没有缓存，返回 0
 #[command]
-pub async fn get_app_status() -> Result<AppStatusResponse, String> {
-    // TODO: Implement get app status
-    Err("Not implemented".to_string())
+pub async fn clear_embedding_cache(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<usize, String> {
+    log::info!("🗑️  清空 embedding 缓存");
+
+    let state = wrapper.get_state().await?;
+    let document_service = state.document_service();
+    let document_service_guard = document_service.lock().await;
+    document_service_guard
+        .clear_embedding_cache()
+        .await
+        .map_err(|e| format!("清空 embedding 缓存失败: {}", e))
 }
 
+/// 按项目和模型汇总日期范围内的 token 用量（`from`/`to` 为 YYYY-MM-DD，包含两端，默认最近 30 天）
 #[command]
-pub async fn configure_llm_service(_request: ConfigureLLMRequest) -> Result<bool, String> {
-    // TODO: Implement configure LLM service
-    Err("Not implemented".to_string())
+pub async fn get_usage_report(
+    from: Option<String>,
+    to: Option<String>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<UsageReport, String> {
+    let (from, to) = usage_report::usage_date_range(from.as_deref(), to.as_deref(), chrono::Utc::now().date_naive())
+        .map_err(|e| e.to_string())?;
+    log::info!("获取用量报告: {} ~ {}", from, to);
+
+    let state = wrapper.get_state().await?;
+
+    let totals = {
+        let vector_db = state.document_service().lock().await.get_vector_db();
+        let (start, end) = usage_report::usage_time_bounds(from, to).map_err(|e| e.to_string())?;
+        let totals = vector_db.lock().await.get_usage_totals(start, end);
+        totals.map_err(|e| format!("获取用量报告失败: {}", e))?
+    };
+
+    let project_service = state.project_service();
+    let project_service_guard = project_service.lock().await;
+    Ok(usage_report::build_usage_report(from, to, totals, |project_id| {
+        uuid::Uuid::parse_str(project_id)
+            .ok()
+            .and_then(|id| project_service_guard.get_project(id))
+            .map(|p| p.name.clone())
+    }))
+}
+
+/// 列出遗留的临时数据库、上传临时副本和缓存临时文件
+#[command]
+pub async fn list_temp_artifacts(app_handle: AppHandle) -> Result<Vec<TempArtifact>, String> {
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "无法获取应用数据目录".to_string())?;
+
+    Ok(temp_artifacts::list_temp_artifacts(&TempLocations::new(&app_data_dir)))
+}
+
+/// 删除超过 older_than_hours 小时未修改的临时文件
+#[command]
+pub async fn clean_temp_artifacts(
+    older_than_hours: u64,
+    app_handle: AppHandle,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<TempCleanupReport, String> {
+    log::info!("🗑️  清理 {} 小时前的临时文件", older_than_hours);
+
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "无法获取应用数据目录".to_string())?;
+
+    // 正在上传的文件可能就是待清理的临时副本
+    let in_use = wrapper
+        .state
+        .lock()
+        .await
+        .as_ref()
+        .map(|state| state.task_registry().active_files())
+        .unwrap_or_default();
+
+    let report = temp_artifacts::clean_temp_artifacts(
+        &TempLocations::new(&app_data_dir),
+        std::time::Duration::from_secs(older_than_hours.saturating_mul(3600)),
+        &in_use,
+    );
+    log::info!(
+        "临时文件清理完成: 删除 {} 个, 释放 {} 字节, 失败 {} 个",
+        report.removed, report.freed_bytes, report.failed.len()
+    );
+    Ok(report)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BridgeVersionResponse {
+    /// 当前程序使用的协议版本
+    pub expected_protocol_version: u32,
+    /// seekdb_bridge.py 报告的协议版本
+    pub bridge_protocol_version: u32,
+    pub compatible: bool,
+}
+
+/// 查看 Python 桥接脚本的协议版本
+#[command]
+pub async fn get_bridge_version(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<BridgeVersionResponse, String> {
+    use crate::services::python_subprocess::BRIDGE_PROTOCOL_VERSION;
+
+    let state = wrapper.get_state().await?;
+
+    let bridge_protocol_version = {
+        let document_service = state.document_service();
+        let document_service_guard = document_service.lock().await;
+        let db = document_service_guard.get_vector_db();
+        let db_guard = db.lock().await;
+        db_guard.bridge_protocol_version()
+    };
+
+    Ok(BridgeVersionResponse {
+        expected_protocol_version: BRIDGE_PROTOCOL_VERSION,
+        bridge_protocol_version,
+        compatible: bridge_protocol_version == BRIDGE_PROTOCOL_VERSION,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionInfoResponse {
+    /// 应用版本（Cargo.toml 中的 version）
+    pub app_version: String,
+    /// 当前程序使用的桥接协议版本
+    pub expected_protocol_version: u32,
+    /// seekdb_bridge.py 报告的协议版本
+    pub bridge_protocol_version: u32,
+    /// 已安装的 seekdb 包版本，无法获取时为 None
+    pub seekdb_version: Option<String>,
+    pub embedding_model: String,
+    pub llm_model: String,
+}
+
+/// 应用版本，编译时取自 Cargo.toml
+pub fn app_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+/// 查看应用版本、桥接协议版本、SeekDB 版本和使用中的模型，便于提交问题反馈
+#[command]
+pub async fn get_version_info(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<VersionInfoResponse, String> {
+    use crate::services::python_subprocess::BRIDGE_PROTOCOL_VERSION;
+
+    let state = wrapper.get_state().await?;
+
+    let (bridge_protocol_version, seekdb_version, embedding_model) = {
+        let document_service = state.document_service();
+        let document_service_guard = document_service.lock().await;
+        let db = document_service_guard.get_vector_db();
+        let db_guard = db.lock().await;
+        // 获取不到 SeekDB 版本时仍返回其他信息
+        let seekdb_version = db_guard.seekdb_version().unwrap_or_else(|e| {
+            log::warn!("获取 SeekDB 版本失败: {}", e);
+            None
+        });
+        (
+            db_guard.bridge_protocol_version(),
+            seekdb_version,
+            document_service_guard.embedding_model().to_string(),
+        )
+    };
+    let llm_model = state.llm_client().lock().await.get_config().model.clone();
+
+    Ok(VersionInfoResponse {
+        app_version: app_version().to_string(),
+        expected_protocol_version: BRIDGE_PROTOCOL_VERSION,
+        bridge_protocol_version,
+        seekdb_version,
+        embedding_model,
+        llm_model,
+    })
+}
+
+/// 列出正在运行的后台任务（文档上传、重新分块/重新处理、对话压缩），按开始时间排序
+#[command]
+pub async fn list_active_tasks(
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<Vec<crate::services::task_registry::TaskInfo>, String> {
+    let state = wrapper.get_state().await?;
+    Ok(state.task_registry().list())
+}
+
+/// 检查数据库、LLM 和 embedding 服务的健康状态；结果短时间内缓存，`force` 为 true 时重新检查
+#[command]
+pub async fn get_system_health(
+    force: Option<bool>,
+    wrapper: tauri::State<'_, crate::app_state_wrapper::AppStateWrapper>,
+) -> Result<SystemHealth, String> {
+    let state = wrapper.get_state().await?;
+    let health = state
+        .health_cache()
+        .get_or_check(force.unwrap_or(false), || health_check::run_health_checks(&state))
+        .await;
+    Ok(health)
+}
+
+/// 诊断 Python 环境（虚拟环境、解释器、seekdb 包），不依赖应用初始化完成
+#[command]
+pub async fn validate_python_env(app_handle: AppHandle) -> Result<PythonEnvReport, String> {
+    log::info!("诊断 Python 环境");
+
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| "无法获取应用数据目录".to_string())?;
+
+    let python_env = PythonEnv::new(&app_data_dir)
+        .map_err(|e| format!("Python 环境初始化失败: {}", e))?;
+
+    Ok(python_env.validate())
 }
 
 /// 打开目录选择对话框
@@ -74,7 +595,7 @@ pub async fn scan_directory(dir_path: String) -> Result<Vec<FileInfo>, String> {
         return Err(format!("路径不是目录: {}", dir_path));
     }
 
-    let allowed_extensions = vec!["txt", "md", "pdf", "doc", "docx", "rtf"];
+    let allowed_extensions = vec!["txt", "md", "pdf", "doc", "docx", "rtf", "html", "htm", "epub"];
     let mut files = Vec::new();
 
     match scan_directory_recursive(path, &allowed_extensions, &mut files) {
@@ -82,7 +603,7 @@ pub async fn scan_directory(dir_path: String) -> Result<Vec<FileInfo>, String> {
             log::info!("扫描完成，找到 {} 个文件", files.len());
 
             if files.is_empty() {
-                return Err("未找到支持的文档格式（.txt, .md, .pdf, .doc, .docx, .rtf）".to_string());
+                return Err("未找到支持的文档格式（.txt, .md, .pdf, .doc, .docx, .rtf, .html, .epub）".to_string());
             }
 
             // 如果文件数量很多，记录警告
@@ -168,3 +689,69 @@ fn scan_directory_recursive(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_version_matches_manifest() {
+        let manifest = include_str!("../../Cargo.toml");
+        let manifest_version = manifest
+            .lines()
+            .find_map(|line| line.strip_prefix("version = "))
+            .map(|version| version.trim_matches('"'))
+            .unwrap();
+        assert_eq!(app_version(), manifest_version);
+    }
+
+    #[test]
+    fn test_storage_breakdown_reports_database_and_cache_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("mine_kb.db");
+        fs::write(&db_path, vec![0u8; 300]).unwrap();
+        fs::write(dir.path().join(crate::services::embedding_cache::EMBEDDING_CACHE_FILE), vec![0u8; 40]).unwrap();
+        fs::create_dir_all(dir.path().join("models/bge")).unwrap();
+        fs::write(dir.path().join("models/bge/model.bin"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("models/config.json"), vec![0u8; 5]).unwrap();
+
+        let breakdown = build_storage_breakdown(&db_path, Some(dir.path()), Vec::new());
+        assert_eq!(breakdown.database_bytes, 300);
+        assert_eq!(breakdown.embedding_cache_bytes, 40);
+        assert_eq!(breakdown.model_cache_bytes, 105);
+        assert_eq!(breakdown.total_bytes, 445);
+
+        // 应用数据目录未知时只统计数据库
+        let breakdown = build_storage_breakdown(&db_path, None, Vec::new());
+        assert_eq!((breakdown.embedding_cache_bytes, breakdown.model_cache_bytes, breakdown.total_bytes), (0, 0, 300));
+    }
+
+    #[test]
+    fn test_app_status_reports_each_subsystem() {
+        use crate::services::health_check::ComponentHealth;
+
+        let dir = tempfile::tempdir().unwrap();
+        let python_env = PythonEnv::new(dir.path()).unwrap();
+        let ok = ComponentHealth { healthy: true, latency_ms: 3, error: None };
+        let health = SystemHealth {
+            database: ok.clone(),
+            llm: ComponentHealth { healthy: false, latency_ms: 10, error: Some("401 Unauthorized".to_string()) },
+            embedding: ok,
+            checked_at: chrono::Utc::now(),
+            cached: false,
+        };
+
+        let status = build_app_status(&python_env, Ok(&health));
+        assert_eq!(
+            (status.db_ok, status.python_ok, status.llm_ok, status.embedding_ok),
+            (true, false, false, true)
+        );
+        assert_eq!(status.details.len(), 4);
+        assert!(status.details.iter().any(|d| d.starts_with("LLM") && d.contains("401 Unauthorized")));
+
+        // 尚未初始化时只能报告 Python 环境
+        let status = build_app_status(&python_env, Err("应用未初始化"));
+        assert!(!status.db_ok && !status.llm_ok && !status.embedding_ok);
+        assert_eq!(status.details.len(), 2);
+    }
+}
@@ -1,13 +1,28 @@
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
+
+use crate::services::model_registry::ModelLimits;
+use crate::utils::content_hash::HashAlgorithm;
+
+/// 示例配置中的占位 API Key
+pub const PLACEHOLDER_API_KEY: &str = "your-api-key-here";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub llm: LlmConfig,
     pub embedding: Option<EmbeddingConfig>,
     pub speech: Option<SpeechConfig>,
+    pub chat: Option<ChatConfig>,
+    pub python: Option<PythonConfig>,
+    pub ingestion: Option<IngestionConfig>,
+    pub retrieval: Option<RetrievalConfig>,
+    pub health: Option<HealthConfig>,
+    pub reranker: Option<RerankerConfig>,
+    pub database: Option<DatabaseConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,12 +37,101 @@ pub struct LlmConfig {
     pub temperature: Option<f64>,
     #[serde(default = "default_stream")]
     pub stream: bool,
+    /// 自定义模型的上下文窗口和最大输出 token 数，优先于内置的模型登记表
+    #[serde(rename = "modelLimits", default, skip_serializing_if = "HashMap::is_empty")]
+    pub model_limits: HashMap<String, ModelLimits>,
+    /// 登记表中没有的模型使用的上下文窗口
+    #[serde(rename = "defaultContextWindow", skip_serializing_if = "Option::is_none")]
+    pub default_context_window: Option<u32>,
+    /// 流式请求是否要求返回 token 用量（stream_options.include_usage），不设置时只对 OpenAI 和阿里百炼开启
+    #[serde(rename = "requestUsage", default, skip_serializing_if = "Option::is_none")]
+    pub request_usage: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EmbeddingConfig {
+    /// Embedding 服务提供方（默认 dashscope）
+    #[serde(default)]
+    pub provider: EmbeddingProviderKind,
+    /// 模型名称，不设置时使用提供方的默认模型（DashScope: text-embedding-v2，OpenAI: text-embedding-3-small）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// OpenAI 的 API Key，不设置时读取环境变量 OPENAI_API_KEY（DashScope 沿用 llm.apiKey）
+    #[serde(rename = "apiKey", default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
     #[serde(rename = "baseUrl")]
     pub base_url: Option<String>,
+    /// 单次 Embedding 请求的最大文本字节数
+    #[serde(rename = "maxBatchBytes")]
+    pub max_batch_bytes: Option<usize>,
+    /// 查询向量微批处理窗口（毫秒），窗口内的单文本请求合并为一次 API 调用；不设置则关闭
+    #[serde(rename = "microBatchWindowMs")]
+    pub micro_batch_window_ms: Option<u64>,
+    /// 同时进行的文档摄取 embedding 请求数上限，为对话查询保留 embedding 服务的并发（默认 2）
+    #[serde(rename = "maxConcurrentIngestion", default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_ingestion: Option<usize>,
+    /// 是否按内容哈希（模型 + 文本）把生成的向量缓存到磁盘，重新导入相同内容时不再请求 API（默认开启）
+    #[serde(rename = "persistentCache")]
+    pub persistent_cache: Option<bool>,
+    /// 缓存有效期（小时），默认 7 天
+    #[serde(rename = "cacheTtlHours")]
+    pub cache_ttl_hours: Option<u64>,
+    /// 最多缓存的向量数量，超出后淘汰最久未使用的条目
+    #[serde(rename = "cacheMaxEntries")]
+    pub cache_max_entries: Option<usize>,
+    /// 向量维度，需与 embedding 模型的输出一致（默认 1536）；只影响新建的数据库，已有数据库沿用建表时的维度
+    pub dimension: Option<usize>,
+    /// 文档摄取（批量生成向量）的重试策略，可以容忍较长的重试
+    #[serde(rename = "ingestionRetry", default, skip_serializing_if = "Option::is_none")]
+    pub ingestion_retry: Option<EmbeddingRetryConfig>,
+    /// 对话查询向量的重试策略，应尽快失败而不是让用户长时间等待
+    #[serde(rename = "queryRetry", default, skip_serializing_if = "Option::is_none")]
+    pub query_retry: Option<EmbeddingRetryConfig>,
+    /// 跳过启动时的 embedding 预热请求（预热可降低首次查询延迟并尽早发现配置错误）
+    #[serde(rename = "skipWarmUp", default)]
+    pub skip_warm_up: bool,
+}
+
+/// Embedding 服务提供方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingProviderKind {
+    /// 阿里云百炼（默认）
+    #[default]
+    DashScope,
+    /// OpenAI（text-embedding-3-small 等）
+    OpenAI,
+}
+
+/// Embedding 请求的重试与超时设置，未设置的项使用对应路径的默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingRetryConfig {
+    /// 最多重试次数（不含第一次请求）
+    #[serde(rename = "maxRetries", skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+    /// 第一次重试前的等待时间（毫秒），之后每次翻倍
+    #[serde(rename = "initialBackoffMs", skip_serializing_if = "Option::is_none")]
+    pub initial_backoff_ms: Option<u64>,
+    /// 单次等待时间的上限（毫秒）
+    #[serde(rename = "maxBackoffMs", skip_serializing_if = "Option::is_none")]
+    pub max_backoff_ms: Option<u64>,
+    /// 单次请求的超时时间（秒）
+    #[serde(rename = "timeoutSecs", skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl EmbeddingConfig {
+    /// 是否使用持久化 embedding 缓存（默认开启，`embedding.persistentCache` 为 false 时关闭）
+    pub fn persistent_cache_enabled(&self) -> bool {
+        self.persistent_cache.unwrap_or(true)
+    }
+}
+
+impl AppConfig {
+    /// 启动时是否预热 embedding 服务（默认预热，`embedding.skipWarmUp` 为 true 时跳过）
+    pub fn embedding_warm_up_enabled(&self) -> bool {
+        !self.embedding.as_ref().is_some_and(|embedding| embedding.skip_warm_up)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,11 +147,442 @@ pub struct AliyunSpeechConfig {
     pub app_key: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// 只包含停用词/标点的查询的处理方式
+    #[serde(rename = "trivialQueryAction", default)]
+    pub trivial_query_action: TrivialQueryAction,
+    /// 构建提示词时是否去除内容重复的上下文块
+    #[serde(rename = "dedupContextChunks", default = "default_dedup_context_chunks")]
+    pub dedup_context_chunks: bool,
+    /// 是否把不同文档中内容完全相同的来源块合并为一个来源（按内容哈希）
+    #[serde(rename = "dedupSourcesByContent", default = "default_dedup_sources_by_content")]
+    pub dedup_sources_by_content: bool,
+    /// 是否把每轮对话的检索详情追加到检索日志（用于离线评测，默认关闭）
+    #[serde(rename = "retrievalLogEnabled", default)]
+    pub retrieval_log_enabled: bool,
+    /// 保存回复前要剥离的包装标签（如 answer 对应 <answer>...</answer>），为空则不剥离
+    #[serde(rename = "stripWrapperTags", default = "default_strip_wrapper_tags")]
+    pub strip_wrapper_tags: Vec<String>,
+    /// 检索结果的过滤方式：固定阈值或按最高分自适应
+    #[serde(rename = "thresholdMode", default)]
+    pub threshold_mode: ThresholdMode,
+    /// 固定阈值模式下的最低相似度
+    #[serde(rename = "similarityThreshold", default = "default_similarity_threshold")]
+    pub similarity_threshold: f64,
+    /// 自适应模式下，保留与最高分相差不超过该值的结果
+    #[serde(rename = "adaptiveScoreGap", default = "default_adaptive_score_gap")]
+    pub adaptive_score_gap: f64,
+    /// 是否按句子缓冲流式 token（遇到句末标点或超过上限时才发送）
+    #[serde(rename = "sentenceFlush", default)]
+    pub sentence_flush: bool,
+    /// 是否在检索完成后立即逐个发送来源（chat-stream-source），不必等到 LLM 开始响应
+    #[serde(rename = "incrementalSources", default)]
+    pub incremental_sources: bool,
+    /// 句子缓冲的最大字符数
+    #[serde(rename = "sentenceFlushMaxChars", default = "default_sentence_flush_max_chars")]
+    pub sentence_flush_max_chars: usize,
+    /// 生成阶段（第一个 token 之前）遇到临时错误时的最大重试次数，0 表示不重试
+    #[serde(rename = "generationRetries", default = "default_generation_retries")]
+    pub generation_retries: u32,
+    /// 第一次重试前的等待时间（毫秒），之后每次翻倍
+    #[serde(rename = "generationRetryBackoffMs", default = "default_generation_retry_backoff_ms")]
+    pub generation_retry_backoff_ms: u64,
+    /// send_message 未指定对话ID时是否在指定项目下自动创建对话
+    #[serde(rename = "autoCreateConversation", default = "default_auto_create_conversation")]
+    pub auto_create_conversation: bool,
+    /// 是否在日志中记录每次 LLM 请求的延迟明细（首 token 延迟、总耗时、token 数、模型）
+    #[serde(rename = "logLlmRequests", default)]
+    pub log_llm_requests: bool,
+    /// 是否把超过长度阈值的消息作为文档导入当前项目的知识库（默认关闭）
+    #[serde(rename = "ingestLongMessages", default)]
+    pub ingest_long_messages: bool,
+    /// 触发自动入库的消息长度（字符数）
+    #[serde(rename = "longMessageThreshold", default = "default_long_message_threshold")]
+    pub long_message_threshold: usize,
+    /// 回复完成后如何输出各来源的归一化相关度
+    #[serde(rename = "scoreFooter", default)]
+    pub score_footer: ScoreFooterMode,
+    /// 最相关的来源低于该相关度时发送 chat-low-confidence，提醒知识库可能没有涵盖该问题
+    #[serde(rename = "lowConfidenceFloor", default = "default_low_confidence_floor")]
+    pub low_confidence_floor: f64,
+    /// 低置信度时是否在提示词中提醒模型不要根据无关片段推测答案
+    #[serde(rename = "lowConfidenceCaveat", default)]
+    pub low_confidence_caveat: bool,
+    /// 发送给 LLM 的提示词（系统提示词、上下文块和对话历史）的 token 上限，不设置则只受模型上下文窗口限制
+    #[serde(rename = "maxContextTokens", default, skip_serializing_if = "Option::is_none")]
+    pub max_context_tokens: Option<usize>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            trivial_query_action: TrivialQueryAction::default(),
+            dedup_context_chunks: true,
+            dedup_sources_by_content: default_dedup_sources_by_content(),
+            retrieval_log_enabled: false,
+            strip_wrapper_tags: default_strip_wrapper_tags(),
+            threshold_mode: ThresholdMode::default(),
+            similarity_threshold: default_similarity_threshold(),
+            adaptive_score_gap: default_adaptive_score_gap(),
+            sentence_flush: false,
+            incremental_sources: false,
+            sentence_flush_max_chars: default_sentence_flush_max_chars(),
+            generation_retries: default_generation_retries(),
+            generation_retry_backoff_ms: default_generation_retry_backoff_ms(),
+            auto_create_conversation: default_auto_create_conversation(),
+            log_llm_requests: false,
+            ingest_long_messages: false,
+            long_message_threshold: default_long_message_threshold(),
+            score_footer: ScoreFooterMode::default(),
+            low_confidence_floor: default_low_confidence_floor(),
+            low_confidence_caveat: false,
+            max_context_tokens: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestionConfig {
+    /// 是否提取图片说明（图注、alt 文本）并作为单独的块建立索引
+    #[serde(rename = "extractCaptions", default)]
+    pub extract_captions: bool,
+    /// 是否在创建/更新项目时为项目名称和描述生成向量（用于把问题路由到相关项目）
+    #[serde(rename = "embedProjectDescriptions", default = "default_embed_project_descriptions")]
+    pub embed_project_descriptions: bool,
+    /// 文档去重使用的内容哈希算法（sha256 / blake3）
+    #[serde(rename = "hashAlgorithm", default)]
+    pub hash_algorithm: HashAlgorithm,
+    /// 批量上传时所有文件均失败是否返回错误（部分成功仍返回成功及失败列表）
+    #[serde(rename = "failOnAllFailed", default)]
+    pub fail_on_all_failed: bool,
+    /// 是否在块 metadata 中记录所属章节（块之前最近的 Markdown/HTML 标题）
+    #[serde(rename = "sectionMetadata", default = "default_section_metadata")]
+    pub section_metadata: bool,
+    /// 批量上传时同时处理（提取、向量化、写入）的文档数
+    #[serde(rename = "uploadConcurrency", default = "default_upload_concurrency")]
+    pub upload_concurrency: usize,
+    /// 每个项目最多的文档数，不设置则不限制
+    #[serde(rename = "maxDocumentsPerProject", default, skip_serializing_if = "Option::is_none")]
+    pub max_documents_per_project: Option<usize>,
+    /// 每个项目文档文件的总字节数上限，不设置则不限制
+    #[serde(rename = "maxTotalBytesPerProject", default, skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes_per_project: Option<u64>,
+    /// 按项目 ID 覆盖上面两项配额
+    #[serde(rename = "projectQuotas", default, skip_serializing_if = "HashMap::is_empty")]
+    pub project_quotas: HashMap<String, ProjectQuotaConfig>,
+    /// 未配置数据库路径时使用的临时数据库的最大保留时间（小时），超过后启动时清理
+    #[serde(rename = "tempDbMaxAgeHours", default = "default_temp_db_max_age_hours")]
+    pub temp_db_max_age_hours: u64,
+}
+
+/// 单个项目的配额覆盖，未设置的项使用全局配额
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectQuotaConfig {
+    #[serde(rename = "maxDocuments", default, skip_serializing_if = "Option::is_none")]
+    pub max_documents: Option<usize>,
+    #[serde(rename = "maxTotalBytes", default, skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes: Option<u64>,
+}
+
+impl Default for IngestionConfig {
+    fn default() -> Self {
+        Self {
+            extract_captions: false,
+            embed_project_descriptions: true,
+            hash_algorithm: HashAlgorithm::default(),
+            fail_on_all_failed: false,
+            section_metadata: default_section_metadata(),
+            upload_concurrency: default_upload_concurrency(),
+            max_documents_per_project: None,
+            max_total_bytes_per_project: None,
+            project_quotas: HashMap::new(),
+            temp_db_max_age_hours: default_temp_db_max_age_hours(),
+        }
+    }
+}
+
+impl IngestionConfig {
+    /// 临时数据库的最大保留时间，超大的小时数按上限饱和而不溢出
+    pub fn temp_db_max_age(&self) -> Duration {
+        Duration::from_secs(self.temp_db_max_age_hours.saturating_mul(3600))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalConfig {
+    /// 每次对话检索的文档块数量
+    #[serde(rename = "topK", default = "default_retrieval_top_k")]
+    pub top_k: usize,
+    /// 混合检索中向量检索所占权重（0.0 为纯全文，1.0 为纯向量）
+    #[serde(rename = "semanticWeight", default = "default_semantic_weight")]
+    pub semantic_weight: f64,
+    /// 向量检索的最低相似度，设置后覆盖 chat.thresholdMode / chat.similarityThreshold
+    #[serde(rename = "minScore", default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f64>,
+    /// 是否使用混合检索（向量 + 全文），默认只用向量检索
+    #[serde(rename = "useHybrid", default)]
+    pub use_hybrid: bool,
+    /// embedding 服务不可用时是否改用关键词检索（只用全文索引），保证对话仍能引用文档
+    #[serde(rename = "keywordFallback", default = "default_keyword_fallback")]
+    pub keyword_fallback: bool,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            top_k: default_retrieval_top_k(),
+            semantic_weight: default_semantic_weight(),
+            min_score: None,
+            use_hybrid: false,
+            keyword_fallback: default_keyword_fallback(),
+        }
+    }
+}
+
+impl ChatConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_context_tokens == Some(0) {
+            return Err(anyhow!("chat.maxContextTokens 必须大于 0"));
+        }
+        Ok(())
+    }
+}
+
+impl RetrievalConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.top_k == 0 {
+            return Err(anyhow!("retrieval.topK 必须大于 0"));
+        }
+        if !(0.0..=1.0).contains(&self.semantic_weight) {
+            return Err(anyhow!("retrieval.semanticWeight 必须在 0.0 到 1.0 之间，当前为 {}", self.semantic_weight));
+        }
+        if self.min_score.is_some_and(|min_score| !min_score.is_finite()) {
+            return Err(anyhow!("retrieval.minScore 必须是有效数字"));
+        }
+        Ok(())
+    }
+}
+
+/// 检索结果重排序的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RerankerProvider {
+    /// 不重排序（默认）
+    #[default]
+    None,
+    /// 调用 DashScope 重排序 API
+    Api,
+    /// 通过 Python 子进程在本地运行 cross-encoder 模型（需要安装 sentence-transformers）
+    Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerankerConfig {
+    #[serde(default)]
+    pub provider: RerankerProvider,
+    /// 重排序模型，未指定时 API 使用 gte-rerank，本地使用 BAAI/bge-reranker-base
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 重排序前召回的候选块数量（不少于 retrieval.topK）
+    #[serde(default = "default_rerank_candidates")]
+    pub candidates: usize,
+    /// API 重排序的 base URL，未指定时使用 DashScope 默认地址
+    #[serde(rename = "baseUrl", default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+impl Default for RerankerConfig {
+    fn default() -> Self {
+        Self {
+            provider: RerankerProvider::default(),
+            model: None,
+            candidates: default_rerank_candidates(),
+            base_url: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// 健康检查结果的缓存时间（秒），0 表示不缓存
+    #[serde(rename = "cacheTtlSecs", default = "default_health_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: default_health_cache_ttl_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    /// 初始化表结构时等待其他实例释放 schema 锁的最长时间（秒）
+    #[serde(rename = "schemaLockTimeoutSecs", default = "default_schema_lock_timeout_secs")]
+    pub schema_lock_timeout_secs: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            schema_lock_timeout_secs: default_schema_lock_timeout_secs(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn schema_lock_timeout(&self) -> Duration {
+        Duration::from_secs(self.schema_lock_timeout_secs)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PythonConfig {
+    /// 安装 SeekDB 时使用的 pip 镜像地址（为空则使用内置默认镜像）
+    #[serde(rename = "pipIndexUrl")]
+    pub pip_index_url: Option<String>,
+}
+
+/// 无意义查询的处理策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrivialQueryAction {
+    /// 跳过向量检索，直接让 LLM 回答（默认）
+    #[default]
+    SkipRetrieval,
+    /// 不调用 LLM，直接提示用户重新表述
+    AskRephrase,
+    /// 不做预检，照常检索
+    Disabled,
+}
+
+/// 检索结果的阈值模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThresholdMode {
+    /// 丢弃低于固定相似度的结果（默认）
+    #[default]
+    Fixed,
+    /// 保留与最高分差距在 adaptiveScoreGap 以内的结果
+    Adaptive,
+}
+
+/// 来源相关度的输出方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoreFooterMode {
+    /// 不输出（默认）
+    #[default]
+    Hidden,
+    /// 在保存的助手消息末尾追加一个 HTML 注释形式的机器可读页脚（不随流式 token 发送，
+    /// 界面显示、发送给 LLM 的历史和导出时都会去掉）
+    Inline,
+    /// 只发送 chat-stream-scores 事件，不修改消息内容
+    EventOnly,
+}
+
 /// 默认启用流式输出
 fn default_stream() -> bool {
     true
 }
 
+/// 默认开启上下文块去重
+fn default_dedup_context_chunks() -> bool {
+    true
+}
+
+fn default_dedup_sources_by_content() -> bool {
+    true
+}
+
+/// 默认为项目描述生成向量
+fn default_embed_project_descriptions() -> bool {
+    true
+}
+
+fn default_section_metadata() -> bool {
+    true
+}
+
+fn default_upload_concurrency() -> usize {
+    4
+}
+
+fn default_temp_db_max_age_hours() -> u64 {
+    24
+}
+
+/// 默认固定相似度阈值（DashScope embedding: 0.3=宽泛, 0.4=中等, 0.5+=严格）
+fn default_similarity_threshold() -> f64 {
+    0.3
+}
+
+fn default_rerank_candidates() -> usize {
+    20
+}
+
+fn default_health_cache_ttl_secs() -> u64 {
+    crate::services::health_check::DEFAULT_HEALTH_CACHE_TTL.as_secs()
+}
+
+fn default_schema_lock_timeout_secs() -> u64 {
+    crate::services::seekdb_adapter::DEFAULT_SCHEMA_LOCK_TIMEOUT.as_secs()
+}
+
+fn default_retrieval_top_k() -> usize {
+    5
+}
+
+/// 默认语义权重：向量检索占 70%
+fn default_semantic_weight() -> f64 {
+    0.7
+}
+
+fn default_keyword_fallback() -> bool {
+    true
+}
+
+/// 默认自适应分数差
+fn default_adaptive_score_gap() -> f64 {
+    0.1
+}
+
+/// 默认句子缓冲上限
+fn default_sentence_flush_max_chars() -> usize {
+    crate::services::sentence_buffer::DEFAULT_MAX_BUFFER_CHARS
+}
+
+fn default_long_message_threshold() -> usize {
+    crate::services::pasted_document::DEFAULT_LONG_MESSAGE_THRESHOLD
+}
+
+fn default_low_confidence_floor() -> f64 {
+    0.3
+}
+
+fn default_auto_create_conversation() -> bool {
+    true
+}
+
+fn default_generation_retries() -> u32 {
+    2
+}
+
+fn default_generation_retry_backoff_ms() -> u64 {
+    500
+}
+
+/// 默认剥离的回复包装标签
+fn default_strip_wrapper_tags() -> Vec<String> {
+    crate::services::response_filter::DEFAULT_WRAPPER_TAGS
+        .iter()
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
 impl AppConfig {
     /// 从文件加载配置
     pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -69,6 +604,12 @@ impl AppConfig {
         if self.llm.model.is_empty() {
             return Err(anyhow!("模型名称不能为空"));
         }
+        if let Some(retrieval) = &self.retrieval {
+            retrieval.validate()?;
+        }
+        if let Some(chat) = &self.chat {
+            chat.validate()?;
+        }
         Ok(())
     }
 
@@ -76,16 +617,40 @@ impl AppConfig {
     pub fn default_config() -> Self {
         Self {
             llm: LlmConfig {
-                api_key: "your-api-key-here".to_string(),
+                api_key: PLACEHOLDER_API_KEY.to_string(),
                 model: "qwen-max".to_string(),
                 base_url: None,
                 max_tokens: Some(4000),
                 temperature: Some(0.7),
                 stream: true,
+                model_limits: HashMap::new(),
+                default_context_window: None,
+                request_usage: None,
             },
             embedding: None,
             speech: None,
+            chat: None,
+            python: None,
+            ingestion: None,
+            retrieval: None,
+            health: None,
+            reranker: None,
+            database: None,
+        }
+    }
+
+    /// 校验后保存配置（用于在界面中填写配置），校验失败时不会写入文件
+    pub fn save_validated<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.validate()?;
+        if self.llm.api_key.trim() == PLACEHOLDER_API_KEY {
+            return Err(anyhow!("请填写有效的 API Key"));
+        }
+
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| anyhow!("无法创建配置目录: {}", e))?;
         }
+        self.save_to_file(path)
     }
 
     /// 保存配置到文件
@@ -96,3 +661,51 @@ impl AppConfig {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_db_max_age_defaults_and_saturates() {
+        let config: IngestionConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.temp_db_max_age(), Duration::from_secs(24 * 3600));
+
+        let config: IngestionConfig = serde_json::from_str(r#"{"tempDbMaxAgeHours": 18446744073709551615}"#).unwrap();
+        assert_eq!(config.temp_db_max_age(), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn test_retrieval_config_defaults_and_semantic_weight_validation() {
+        let config: RetrievalConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.top_k, 5);
+        assert_eq!(config.semantic_weight, 0.7);
+        assert!(config.min_score.is_none());
+        assert!(!config.use_hybrid);
+        assert!(config.validate().is_ok());
+
+        for weight in [0.0, 1.0] {
+            assert!(RetrievalConfig { semantic_weight: weight, ..Default::default() }.validate().is_ok());
+        }
+        for weight in [-0.1, 1.5, f64::NAN] {
+            assert!(RetrievalConfig { semantic_weight: weight, ..Default::default() }.validate().is_err());
+        }
+        assert!(RetrievalConfig { top_k: 0, ..Default::default() }.validate().is_err());
+
+        // AppConfig validation covers the retrieval section
+        let mut app_config = AppConfig::default_config();
+        app_config.retrieval = Some(RetrievalConfig { semantic_weight: 2.0, ..Default::default() });
+        assert!(app_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_zero_max_context_tokens_is_rejected() {
+        assert!(ChatConfig::default().validate().is_ok());
+        assert!(ChatConfig { max_context_tokens: Some(12_000), ..Default::default() }.validate().is_ok());
+        assert!(ChatConfig { max_context_tokens: Some(0), ..Default::default() }.validate().is_err());
+
+        let mut app_config = AppConfig::default_config();
+        app_config.chat = Some(ChatConfig { max_context_tokens: Some(0), ..Default::default() });
+        assert!(app_config.validate().is_err());
+    }
+}
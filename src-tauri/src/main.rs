@@ -4,12 +4,16 @@
 
 use mine_kb::commands::{chat, documents, projects, system, speech, initialization};
 use mine_kb::services::app_state::AppState;
+use mine_kb::services::document_service::spawn_embedding_warm_up;
 use mine_kb::services::python_env::PythonEnv;
 use mine_kb::services::seekdb_package::SeekDbPackage;
+use mine_kb::services::speech_service::SpeechStreamRegistry;
 use mine_kb::config::AppConfig;
-use mine_kb::app_state_wrapper::AppStateWrapper;
+use mine_kb::app_state_wrapper::{AppStateWrapper, InitController};
+use mine_kb::utils::cancellation::CancellationToken;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tauri::{Manager, AppHandle};
 use tokio::sync::Mutex;
@@ -72,6 +76,27 @@ impl StartupEvent {
             error: Some(error.into()),
         }
     }
+    
+    fn cancelled(step: u32) -> Self {
+        Self {
+            step,
+            total_steps: 3,
+            message: "初始化已取消".to_string(),
+            status: "cancelled".to_string(),
+            details: Some("可以修改配置后重新触发初始化".to_string()),
+            error: None,
+        }
+    }
+}
+
+/// 若初始化已被取消，发送取消事件并返回 true
+fn abort_if_cancelled(app_handle: &AppHandle, cancel: &CancellationToken, step: u32) -> bool {
+    if !cancel.is_cancelled() {
+        return false;
+    }
+    log::warn!("⛔ 初始化已在步骤 {} 被取消", step);
+    let _ = app_handle.emit_all("startup-progress", StartupEvent::cancelled(step));
+    true
 }
 
 /// 后台初始化任务
@@ -81,9 +106,13 @@ async fn initialize_app_async(
     db_path_str: String,
     model_cache_dir_str: Option<String>,
     state_wrapper: Arc<Mutex<Option<AppState>>>,
+    cancel: CancellationToken,
 ) {
     // 等待窗口显示
     tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+    if abort_if_cancelled(&app_handle, &cancel, 0) {
+        return;
+    }
     
     // 发送初始事件
     let _ = app_handle.emit_all("startup-progress", StartupEvent::progress(0, "正在启动应用..."));
@@ -121,11 +150,29 @@ async fn initialize_app_async(
         ));
         return;
     }
+    if abort_if_cancelled(&app_handle, &cancel, 1) {
+        return;
+    }
     
     let _ = app_handle.emit_all("startup-progress", StartupEvent::progress(1, "检查 SeekDB 包"));
     
-    // 检查并安装 SeekDB
-    let seekdb_pkg = SeekDbPackage::new(&python_env);
+    // 检查并安装 SeekDB（配置文件可指定 pip 镜像，此时配置尚未正式加载，仅读取镜像地址）
+    let pip_index_url = load_app_config(&app_data_dir)
+        .and_then(|c| c.python)
+        .and_then(|p| p.pip_index_url)
+        .filter(|url| !url.trim().is_empty());
+
+    let seekdb_pkg = match pip_index_url {
+        Some(url) => match SeekDbPackage::with_index_url(&python_env, &url) {
+            Ok(pkg) => pkg,
+            Err(e) => {
+                log::warn!("⚠️  配置的 pip 镜像无效，使用默认镜像: {}", e);
+                SeekDbPackage::new(&python_env)
+            }
+        },
+        None => SeekDbPackage::new(&python_env),
+    };
+    log::info!("📦 SeekDB 安装镜像: {}", seekdb_pkg.get_index_url());
     
     match seekdb_pkg.is_installed() {
         Ok(false) => {
@@ -136,7 +183,10 @@ async fn initialize_app_async(
                 "首次运行需要下载并安装 SeekDB（约3GB），可能需要几分钟..."
             ));
             
-            if let Err(e) = seekdb_pkg.install() {
+            if let Err(e) = seekdb_pkg.install_cancellable(&cancel) {
+                if abort_if_cancelled(&app_handle, &cancel, 1) {
+                    return;
+                }
                 log::error!("SeekDB 安装失败: {}", e);
                 let _ = app_handle.emit_all("startup-progress", StartupEvent::error(
                     "SeekDB 安装失败",
@@ -150,7 +200,10 @@ async fn initialize_app_async(
         }
         Err(e) => {
             log::warn!("⚠️  检查 SeekDB 安装状态失败，尝试安装: {}", e);
-            if let Err(e) = seekdb_pkg.install() {
+            if let Err(e) = seekdb_pkg.install_cancellable(&cancel) {
+                if abort_if_cancelled(&app_handle, &cancel, 1) {
+                    return;
+                }
                 log::error!("SeekDB 安装失败: {}", e);
                 let _ = app_handle.emit_all("startup-progress", StartupEvent::error(
                     "SeekDB 安装失败",
@@ -161,6 +214,10 @@ async fn initialize_app_async(
         }
     }
     
+    if abort_if_cancelled(&app_handle, &cancel, 1) {
+        return;
+    }
+    
     if let Err(e) = seekdb_pkg.verify() {
         log::error!("SeekDB 验证失败: {}", e);
         let _ = app_handle.emit_all("startup-progress", StartupEvent::error(
@@ -175,6 +232,9 @@ async fn initialize_app_async(
     log::info!("✅ Python 可执行文件: {}", python_path_str);
     
     let _ = app_handle.emit_all("startup-progress", StartupEvent::success(1, "Python 环境和 SeekDB 准备完成"));
+    if abort_if_cancelled(&app_handle, &cancel, 2) {
+        return;
+    }
 
     // ============================================================
     // 2. 配置文件加载
@@ -197,7 +257,7 @@ async fn initialize_app_async(
         }
 
         let error_msg = format!(
-            "配置文件缺失\n\n请按照以下步骤配置：\n1. 打开文件夹: {}\n2. 编辑 config.example.json\n3. 将文件重命名为 config.json\n4. 重新启动应用",
+            "配置文件缺失\n\n请在界面中填写 API Key 和模型，保存后将自动继续初始化。\n也可以手动配置：\n1. 打开文件夹: {}\n2. 编辑 config.example.json\n3. 将文件重命名为 config.json\n4. 重新启动应用",
             app_data_dir.display()
         );
         let _ = app_handle.emit_all("startup-progress", StartupEvent::error("配置文件缺失", error_msg));
@@ -205,6 +265,9 @@ async fn initialize_app_async(
     }
     
     let _ = app_handle.emit_all("startup-progress", StartupEvent::success(2, "配置文件加载完成"));
+    if abort_if_cancelled(&app_handle, &cancel, 3) {
+        return;
+    }
 
     // ============================================================
     // 3. 初始化应用状态
@@ -220,6 +283,8 @@ async fn initialize_app_async(
     ));
     
     log::info!("开始初始化应用状态...");
+
+    let warm_up_config = app_config.clone();
     
     let app_state_result = AppState::new_with_full_config(
         &db_path_str, 
@@ -230,7 +295,14 @@ async fn initialize_app_async(
     .await;
 
     match app_state_result {
+        Ok(_) if abort_if_cancelled(&app_handle, &cancel, 3) => {
+            // 取消发生在创建应用状态期间，丢弃已创建的状态
+        }
         Ok(app_state) => {
+            // 预热 embedding 服务：后台发送一次请求，不阻塞启动，失败只记录警告
+            let embedding_service = app_state.document_service().lock().await.embedding_service();
+            spawn_embedding_warm_up(warm_up_config.as_ref(), embedding_service);
+
             // 保存到状态包装器
             let mut state_guard = state_wrapper.lock().await;
             *state_guard = Some(app_state);
@@ -258,6 +330,7 @@ fn main() {
     env_logger::init();
 
     tauri::Builder::default()
+        .manage(SpeechStreamRegistry::default())
         .setup(|app| {
             log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
             log::info!("  Setup: 快速准备（非阻塞）");
@@ -324,24 +397,38 @@ fn main() {
 
             // 创建状态包装器
             let state_wrapper = Arc::new(Mutex::new(None));
-            let wrapper = AppStateWrapper {
-                state: state_wrapper.clone(),
-            };
-            app.manage(wrapper);
 
             // 克隆 app_handle 用于后台任务
             let app_handle = app.handle();
             
-            // 在后台异步初始化（不阻塞 setup）
-            tauri::async_runtime::spawn(async move {
-                initialize_app_async(
-                    app_handle,
-                    app_data_dir,
-                    db_path_str,
-                    model_cache_dir_str,
-                    state_wrapper,
-                ).await;
-            });
+            // 初始化任务启动器：首次启动以及取消后重新触发都通过它在后台异步初始化（不阻塞 setup）
+            let mut init_controller = InitController::default();
+            let launcher_state = state_wrapper.clone();
+            init_controller.set_launcher(Arc::new(move |cancel: CancellationToken, running: Arc<AtomicBool>| {
+                let app_handle = app_handle.clone();
+                let app_data_dir = app_data_dir.clone();
+                let db_path_str = db_path_str.clone();
+                let model_cache_dir_str = model_cache_dir_str.clone();
+                let state_wrapper = launcher_state.clone();
+                tauri::async_runtime::spawn(async move {
+                    initialize_app_async(
+                        app_handle,
+                        app_data_dir,
+                        db_path_str,
+                        model_cache_dir_str,
+                        state_wrapper,
+                        cancel,
+                    ).await;
+                    running.store(false, Ordering::SeqCst);
+                });
+            }));
+            init_controller.start()?;
+
+            let wrapper = AppStateWrapper {
+                state: state_wrapper,
+                init: std::sync::Mutex::new(init_controller),
+            };
+            app.manage(wrapper);
 
             log::info!("✅ Setup 完成，窗口即将显示");
             log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
@@ -352,32 +439,78 @@ fn main() {
             // Initialization commands
             initialization::trigger_initialization,
             initialization::check_initialization_status,
+            initialization::cancel_initialization,
+            initialization::save_config,
             // Project management commands
             projects::create_project,
             projects::get_projects,
+            projects::get_recent_projects,
+            projects::route_query_to_project,
+            projects::find_stale_projects,
             projects::get_project_details,
             projects::delete_project,
+            projects::archive_project,
+            projects::restore_project,
+            projects::recompute_document_counts,
             projects::rename_project,
+            projects::rechunk_project,
+            projects::export_project,
+            projects::import_project,
+            projects::inspect_project_archive,
             // Document management commands
             documents::validate_files,
             documents::upload_documents,
             documents::get_document_content,
+            documents::restructure_document,
+            documents::delete_document,
+            documents::cleanup_partial_documents,
+            documents::fill_missing_embeddings,
+            documents::reprocess_project,
+            documents::find_similar_documents,
+            documents::get_document_metadata_values,
+            documents::benchmark_retrieval,
+            documents::optimize_chunk_size,
             // Chat/conversation commands
             chat::create_conversation,
             chat::send_message,
+            chat::regenerate_message,
+            chat::edit_message,
             chat::get_conversations,
             chat::get_conversation_history,
             chat::delete_conversation,
             chat::delete_message,
             chat::clear_messages,
+            chat::trim_conversation,
+            chat::cancel_generation,
+            chat::search_conversation_messages,
             chat::rename_conversation,
+            chat::set_conversation_model,
+            chat::repair_message_ordering,
+            chat::compress_conversation,
+            chat::get_retrieval_log,
+            chat::get_conversation_stats,
+            chat::export_conversation,
+            chat::generate_conversation_title,
             // System commands
             system::get_app_status,
             system::configure_llm_service,
             system::select_directory,
             system::scan_directory,
+            system::get_storage_breakdown,
+            system::clear_embedding_cache,
+            system::get_usage_report,
+            system::list_temp_artifacts,
+            system::clean_temp_artifacts,
+            system::get_db_embedding_dimension,
+            system::reload_from_database,
+            system::list_active_tasks,
+            system::get_system_health,
+            system::get_bridge_version,
+            system::get_version_info,
+            system::validate_python_env,
             // Speech recognition commands
             speech::recognize_speech,
+            speech::start_streaming_recognition,
             speech::check_speech_config,
         ])
         .run(tauri::generate_context!())
@@ -27,6 +27,9 @@ pub struct Conversation {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub message_count: u32,
+    /// 本对话使用的模型（覆盖全局 LLM 配置），None 表示使用默认模型
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
 impl Conversation {
@@ -42,9 +45,27 @@ impl Conversation {
             created_at: now,
             updated_at: now,
             message_count: 0,
+            model: None,
         })
     }
 
+    /// 设置或清除（None）本对话的模型覆盖
+    pub fn set_model(&mut self, model: Option<String>) -> Result<(), ConversationValidationError> {
+        let model = match model {
+            Some(model) if model.trim().is_empty() => return Err(ConversationValidationError::EmptyModel),
+            Some(model) => Some(model.trim().to_string()),
+            None => None,
+        };
+        self.model = model;
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+
+    /// 标题是否仍为创建时生成的默认时间戳标题
+    pub fn has_default_title(&self) -> bool {
+        chrono::NaiveDateTime::parse_from_str(self.title.trim(), "%Y-%m-%d %H:%M:%S").is_ok()
+    }
+
     pub fn update_title(&mut self, title: String) -> Result<(), ConversationValidationError> {
         Self::validate_title(&title)?;
         self.title = title;
@@ -89,6 +110,8 @@ pub struct Message {
     pub context_chunks: Vec<Uuid>, // References to DocumentChunk IDs
     pub processing_time: Option<f64>, // Time taken to generate response (for Assistant messages)
     pub sources: Option<Vec<ContextChunk>>, // Source documents with filename and relevance
+    #[serde(default)]
+    pub sequence: u64, // Per-conversation insertion order (0 = not yet assigned)
 }
 
 impl Message {
@@ -111,9 +134,16 @@ impl Message {
             context_chunks: Vec::new(),
             processing_time: None,
             sources: None,
+            sequence: 0,
         })
     }
 
+    /// Sort key for displaying messages: stored timestamps may only have second
+    /// resolution, so messages within the same second are ordered by sequence number
+    pub fn order_key(&self) -> (i64, u64, DateTime<Utc>) {
+        (self.timestamp.timestamp(), self.sequence, self.timestamp)
+    }
+
     pub fn new_user_message(
         conversation_id: Uuid,
         content: String,
@@ -154,7 +184,33 @@ impl Message {
         self.processing_time = Some(time);
     }
 
-    fn validate_content(content: &str, role: &MessageRole) -> Result<(), ConversationValidationError> {
+    /// Replace the content in place (regeneration), keeping id and timestamp
+    pub fn replace_content(
+        &mut self,
+        content: String,
+        sources: Vec<ContextChunk>,
+        processing_time: Option<f64>,
+    ) -> Result<(), ConversationValidationError> {
+        Self::validate_content(&content, &self.role)?;
+
+        self.token_count = Self::estimate_token_count(&content);
+        self.content = content;
+        self.sources = if sources.is_empty() { None } else { Some(sources) };
+        self.processing_time = processing_time;
+        Ok(())
+    }
+
+    /// Short single-line preview of the content, truncated to `max_chars` characters
+    pub fn preview(&self, max_chars: usize) -> String {
+        let collapsed = self.content.split_whitespace().collect::<Vec<_>>().join(" ");
+        if collapsed.chars().count() <= max_chars {
+            return collapsed;
+        }
+        let truncated: String = collapsed.chars().take(max_chars).collect();
+        format!("{}…", truncated.trim_end())
+    }
+
+    pub fn validate_content(content: &str, role: &MessageRole) -> Result<(), ConversationValidationError> {
         match role {
             MessageRole::User | MessageRole::Assistant => {
                 if content.trim().is_empty() {
@@ -173,7 +229,7 @@ impl Message {
         Ok(())
     }
 
-    fn estimate_token_count(content: &str) -> u32 {
+    pub(crate) fn estimate_token_count(content: &str) -> u32 {
         // Simple token estimation: roughly 4 characters per token
         (content.len() as f32 / 4.0).ceil() as u32
     }
@@ -187,6 +243,7 @@ pub struct ConversationResponse {
     pub created_at: String,
     pub updated_at: String,
     pub message_count: u32,
+    pub model: Option<String>,
 }
 
 impl From<Conversation> for ConversationResponse {
@@ -198,6 +255,7 @@ impl From<Conversation> for ConversationResponse {
             created_at: conversation.created_at.to_rfc3339(),
             updated_at: conversation.updated_at.to_rfc3339(),
             message_count: conversation.message_count,
+            model: conversation.model,
         }
     }
 }
@@ -233,6 +291,15 @@ pub struct ContextChunk {
     pub filename: String,
     pub content: String,
     pub relevance_score: f64,
+    /// Project the chunk was retrieved from (None for sources saved before this was recorded)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// Nearest heading preceding the chunk in its document, e.g. "Installation"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    /// Other documents containing identical chunk content (collapsed into this source)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_filenames: Vec<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -245,6 +312,8 @@ pub enum ConversationValidationError {
     EmptyMessageContent,
     #[error("Message content cannot exceed 10000 characters")]
     MessageTooLong,
+    #[error("Conversation model cannot be empty")]
+    EmptyModel,
 }
 
 #[cfg(test)]
@@ -263,6 +332,21 @@ mod tests {
         assert_eq!(conversation.message_count, 0);
     }
 
+    #[test]
+    fn test_conversation_model_override() {
+        let mut conversation = Conversation::new(Uuid::new_v4(), Some("Test".to_string())).unwrap();
+        assert_eq!(conversation.model, None);
+
+        conversation.set_model(Some(" qwen-max ".to_string())).unwrap();
+        assert_eq!(conversation.model.as_deref(), Some("qwen-max"));
+
+        assert!(conversation.set_model(Some("  ".to_string())).is_err());
+        assert_eq!(conversation.model.as_deref(), Some("qwen-max"));
+
+        conversation.set_model(None).unwrap();
+        assert_eq!(conversation.model, None);
+    }
+
     #[test]
     fn test_conversation_default_title() {
         let project_id = Uuid::new_v4();
@@ -359,6 +443,18 @@ mod tests {
         assert_eq!(message.context_chunks.len(), 1);
     }
 
+    #[test]
+    fn test_message_preview() {
+        let conversation_id = Uuid::new_v4();
+        let short = Message::new_user_message(conversation_id, "你好\n  世界".to_string()).unwrap();
+        assert_eq!(short.preview(60), "你好 世界");
+
+        let long = Message::new_user_message(conversation_id, "很".repeat(100)).unwrap();
+        let preview = long.preview(60);
+        assert_eq!(preview.chars().count(), 61);
+        assert!(preview.ends_with('…'));
+    }
+
     #[test]
     fn test_response_conversion() {
         let project_id = Uuid::new_v4();
@@ -118,6 +118,8 @@ impl Document {
             "txt" => Ok("text/plain".to_string()),
             "md" | "markdown" => Ok("text/markdown".to_string()),
             "pdf" => Ok("application/pdf".to_string()),
+            "html" | "htm" => Ok("text/html".to_string()),
+            "epub" => Ok("application/epub+zip".to_string()),
             _ => Err(DocumentValidationError::UnsupportedFileType(extension)),
         }
     }
@@ -134,6 +136,9 @@ pub struct DocumentChunk {
     pub end_offset: u64,
     pub embedding_id: String,
     pub created_at: DateTime<Utc>,
+    /// 块之前最近的 Markdown/HTML 标题（开启章节元数据时填充）
+    #[serde(default)]
+    pub section: Option<String>,
 }
 
 impl DocumentChunk {
@@ -160,6 +165,37 @@ impl DocumentChunk {
             end_offset,
             embedding_id: String::new(), // Will be set when stored in vector DB
             created_at: Utc::now(),
+            section: None,
+        })
+    }
+
+    /// 创建图片说明块：说明文字通常很短，因此不受最小 token 数限制
+    pub fn new_caption(
+        document_id: Uuid,
+        chunk_index: u32,
+        content: String,
+        start_offset: u64,
+        end_offset: u64,
+    ) -> Result<Self, DocumentValidationError> {
+        Self::validate_content(&content)?;
+        Self::validate_offsets(start_offset, end_offset)?;
+
+        let token_count = Self::estimate_token_count(&content);
+        if token_count > 1000 {
+            return Err(DocumentValidationError::InvalidTokenCount);
+        }
+
+        Ok(DocumentChunk {
+            id: Uuid::new_v4(),
+            document_id,
+            chunk_index,
+            content,
+            token_count,
+            start_offset,
+            end_offset,
+            embedding_id: String::new(),
+            created_at: Utc::now(),
+            section: None,
         })
     }
 
@@ -188,12 +224,32 @@ impl DocumentChunk {
         Ok(())
     }
 
-    fn estimate_token_count(content: &str) -> u32 {
-        // Simple token estimation: roughly 4 characters per token
-        (content.len() as f32 / 4.0).ceil() as u32
+    /// Rough token estimate: ~1.5 CJK characters per token, ~4 bytes per token for everything else
+    pub fn estimate_token_count(content: &str) -> u32 {
+        let (cjk_chars, other_bytes) = content.chars().fold((0usize, 0usize), |(cjk, other), c| {
+            if is_cjk(c) {
+                (cjk + 1, other)
+            } else {
+                (cjk, other + c.len_utf8())
+            }
+        });
+        (cjk_chars as f32 / 1.5 + other_bytes as f32 / 4.0).ceil() as u32
     }
 }
 
+/// CJK ideographs, kana, hangul, and CJK/full-width punctuation
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'   // CJK symbols and punctuation
+        | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Half-width and full-width forms
+    )
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentResponse {
     pub id: String,
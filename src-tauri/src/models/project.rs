@@ -8,6 +8,8 @@ pub enum ProjectStatus {
     Processing,
     Ready,
     Error,
+    /// 已归档：默认不在项目列表中显示，对话和向量数据保留以便恢复
+    Archived,
 }
 
 impl std::fmt::Display for ProjectStatus {
@@ -17,6 +19,20 @@ impl std::fmt::Display for ProjectStatus {
             ProjectStatus::Processing => write!(f, "Processing"),
             ProjectStatus::Ready => write!(f, "Ready"),
             ProjectStatus::Error => write!(f, "Error"),
+            ProjectStatus::Archived => write!(f, "Archived"),
+        }
+    }
+}
+
+impl ProjectStatus {
+    /// 解析数据库中保存的状态字符串（`Display` 的输出），未知值视为 Created
+    pub fn from_status_str(status: &str) -> Self {
+        match status {
+            "Processing" => ProjectStatus::Processing,
+            "Ready" => ProjectStatus::Ready,
+            "Error" => ProjectStatus::Error,
+            "Archived" => ProjectStatus::Archived,
+            _ => ProjectStatus::Created,
         }
     }
 }
@@ -49,6 +65,10 @@ impl Project {
         })
     }
 
+    pub fn is_archived(&self) -> bool {
+        self.status == ProjectStatus::Archived
+    }
+
     pub fn update_status(&mut self, status: ProjectStatus) {
         self.status = status;
         self.updated_at = Utc::now();
@@ -179,6 +199,20 @@ mod tests {
         assert!(project.updated_at > original_updated_at);
     }
 
+    #[test]
+    fn test_status_string_round_trips_including_archived() {
+        for status in [
+            ProjectStatus::Created,
+            ProjectStatus::Processing,
+            ProjectStatus::Ready,
+            ProjectStatus::Error,
+            ProjectStatus::Archived,
+        ] {
+            assert_eq!(ProjectStatus::from_status_str(&status.to_string()), status);
+        }
+        assert_eq!(ProjectStatus::from_status_str("Unknown"), ProjectStatus::Created);
+    }
+
     #[test]
     fn test_project_response_conversion() {
         let project = Project::new("Test Project".to_string(), None).unwrap();
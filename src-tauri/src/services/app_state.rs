@@ -1,10 +1,17 @@
 use crate::services::{
     project_service::ProjectService,
-    document_service::DocumentService,
+    document_service::{DocumentService, ScoreThreshold},
     conversation_service::ConversationService,
+    generation_registry::GenerationRegistry,
+    generation_retry::RetryPolicy,
+    health_check::HealthCache,
     llm_client::{LlmClient, LlmConfig as LlmClientConfig, LlmProvider},
+    model_registry::ModelRegistry,
+    project_quota::ProjectQuotas,
+    reranker::Reranker,
+    task_registry::TaskRegistry,
 };
-use crate::config::{AppConfig, LlmConfig};
+use crate::config::{AppConfig, ChatConfig, IngestionConfig, LlmConfig, RetrievalConfig};
 use anyhow::{Result, anyhow};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -15,12 +22,19 @@ pub struct AppState {
     pub document_service: Arc<Mutex<DocumentService>>,
     pub conversation_service: Arc<Mutex<ConversationService>>,
     pub llm_client: Arc<Mutex<LlmClient>>,
+    pub chat_config: ChatConfig,
+    pub ingestion_config: IngestionConfig,
+    pub retrieval_config: RetrievalConfig,
+    pub task_registry: TaskRegistry,
+    pub generation_registry: GenerationRegistry,
+    pub health_cache: HealthCache,
 }
 
 impl AppState {
     pub async fn new() -> Result<Self> {
         // 初始化各个服务
-        let document_service = Arc::new(Mutex::new(DocumentService::new().await?));
+        let ingestion_config = IngestionConfig::default();
+        let document_service = Arc::new(Mutex::new(DocumentService::new(ingestion_config.temp_db_max_age()).await?));
 
         // 获取 document_service 中的 vector_db 引用
         let vector_db = {
@@ -39,6 +53,12 @@ impl AppState {
             document_service,
             conversation_service,
             llm_client,
+            chat_config: ChatConfig::default(),
+            ingestion_config,
+            retrieval_config: RetrievalConfig::default(),
+            task_registry: TaskRegistry::new(),
+            generation_registry: GenerationRegistry::new(),
+            health_cache: HealthCache::default(),
         })
     }
 
@@ -70,15 +90,50 @@ impl AppState {
                 .map_err(|_| anyhow!("未找到 DASHSCOPE_API_KEY，请在 config.json 配置或设置环境变量"))?
         };
 
-        // 获取 embedding base URL（优先使用 embedding 配置，而不是 LLM 配置）
-        let embedding_base_url = app_config.as_ref()
-            .and_then(|c| c.embedding.as_ref())
-            .and_then(|e| e.base_url.clone());
+        // 获取 embedding 配置（base URL 优先使用 embedding 配置，而不是 LLM 配置）
+        let embedding_config = app_config.as_ref()
+            .and_then(|c| c.embedding.clone());
 
         // 初始化各个服务，使用指定的数据库路径和 API 配置
-        let document_service = Arc::new(Mutex::new(
-            DocumentService::with_full_config(db_path, api_key, embedding_base_url, python_path).await?
-        ));
+        let schema_lock_timeout = app_config.as_ref()
+            .and_then(|c| c.database.clone())
+            .map(|database| database.schema_lock_timeout());
+        let mut document_service = DocumentService::with_full_config(
+            db_path,
+            api_key.clone(),
+            embedding_config,
+            python_path,
+            schema_lock_timeout,
+        ).await?;
+        let ingestion_config = app_config.as_ref()
+            .and_then(|c| c.ingestion.clone())
+            .unwrap_or_default();
+        document_service.set_caption_extraction(ingestion_config.extract_captions);
+        document_service.set_section_metadata(ingestion_config.section_metadata);
+        document_service.set_project_description_embedding(ingestion_config.embed_project_descriptions);
+        document_service.set_hash_algorithm(ingestion_config.hash_algorithm);
+        document_service.set_project_quotas(ProjectQuotas::from_config(&ingestion_config));
+
+        let chat_config = app_config.as_ref()
+            .and_then(|c| c.chat.clone())
+            .unwrap_or_default();
+        let retrieval_config = app_config.as_ref()
+            .and_then(|c| c.retrieval.clone())
+            .unwrap_or_default();
+        let score_threshold = match retrieval_config.min_score {
+            Some(min_score) => ScoreThreshold::Fixed(min_score),
+            None => ScoreThreshold::from_config(&chat_config),
+        };
+        document_service.set_score_threshold(score_threshold);
+        let reranker_config = app_config.as_ref()
+            .and_then(|c| c.reranker.clone())
+            .unwrap_or_default();
+        let reranker = Reranker::from_config(&reranker_config, &api_key, &*document_service.get_vector_db().lock().await)?;
+        if let Some(reranker) = &reranker {
+            reranker.preload();
+        }
+        document_service.set_reranker(reranker);
+        let document_service = Arc::new(Mutex::new(document_service));
 
         // 获取 document_service 中的 vector_db 引用
         let vector_db = {
@@ -91,7 +146,19 @@ impl AppState {
 
         // 初始化 LLM 客户端（使用配置文件的配置）
         let llm_config = app_config.as_ref().map(|c| c.llm.clone());
-        let llm_client = Arc::new(Mutex::new(Self::create_llm_client(llm_config)?));
+        let request_usage = llm_config.as_ref().and_then(|c| c.request_usage);
+        let mut llm_client = Self::create_llm_client(llm_config)?;
+        llm_client.set_request_usage(request_usage);
+        llm_client.set_context_dedup(chat_config.dedup_context_chunks);
+        llm_client.set_request_logging(chat_config.log_llm_requests);
+        llm_client.set_retry_policy(RetryPolicy::from_config(&chat_config));
+        llm_client.set_max_context_tokens(chat_config.max_context_tokens);
+        let llm_client = Arc::new(Mutex::new(llm_client));
+
+        let health_config = app_config.as_ref()
+            .and_then(|c| c.health.clone())
+            .unwrap_or_default();
+        let health_cache = HealthCache::new(std::time::Duration::from_secs(health_config.cache_ttl_secs));
 
         log::info!("✅ 应用状态初始化完成");
 
@@ -100,6 +167,12 @@ impl AppState {
             document_service,
             conversation_service,
             llm_client,
+            chat_config,
+            ingestion_config,
+            retrieval_config,
+            task_registry: TaskRegistry::new(),
+            generation_registry: GenerationRegistry::new(),
+            health_cache,
         })
     }
 
@@ -123,8 +196,43 @@ impl AppState {
         self.llm_client.clone()
     }
 
+    /// 获取对话配置
+    pub fn chat_config(&self) -> &ChatConfig {
+        &self.chat_config
+    }
+
+    /// 获取文档导入配置
+    pub fn ingestion_config(&self) -> &IngestionConfig {
+        &self.ingestion_config
+    }
+
+    /// 获取检索配置
+    pub fn retrieval_config(&self) -> &RetrievalConfig {
+        &self.retrieval_config
+    }
+
+    /// 获取后台任务登记表
+    pub fn task_registry(&self) -> &TaskRegistry {
+        &self.task_registry
+    }
+
+    /// 获取正在生成的对话回复登记表
+    pub fn generation_registry(&self) -> &GenerationRegistry {
+        &self.generation_registry
+    }
+
+    /// 获取健康检查结果缓存
+    pub fn health_cache(&self) -> &HealthCache {
+        &self.health_cache
+    }
+
     /// 创建 LLM 客户端，配置阿里百炼
     fn create_llm_client(llm_config: Option<LlmConfig>) -> Result<LlmClient> {
+        let model_registry = llm_config
+            .as_ref()
+            .map(|config| ModelRegistry::new(config.model_limits.clone(), config.default_context_window))
+            .unwrap_or_default();
+
         let (api_key, model, base_url_opt, max_tokens, temperature, stream) = if let Some(config) = llm_config {
             // 使用配置文件
             if config.api_key.is_empty() {
@@ -193,7 +301,7 @@ impl AppState {
             stream,
         };
 
-        LlmClient::new(config)
+        LlmClient::with_model_registry(config, model_registry)
     }
 
     /// 获取阿里百炼 Base URL（根据 IP 判断国内或海外）
@@ -0,0 +1,233 @@
+//! 分块大小调优
+//!
+//! 对每个候选分块大小，把样本文本分块并生成向量，用测试查询在内存中做向量检索，
+//! 统计预期文本是否出现在检索结果里，据此推荐分块大小。
+//! 命中率相同时比较 MRR，仍相同时选较小的分块（发给 LLM 的上下文更少）。
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use uuid::Uuid;
+
+use crate::services::document_processor::DocumentProcessor;
+
+/// 候选分块大小的范围（token），与 DocumentChunk 的 token 数校验一致
+pub const MIN_CANDIDATE_CHUNK_SIZE: usize = 10;
+pub const MAX_CANDIDATE_CHUNK_SIZE: usize = 1000;
+
+/// 测试查询：检索 `query` 时，期望 `expected_text` 出现在检索到的块中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkSizeTestQuery {
+    pub query: String,
+    pub expected_text: String,
+}
+
+/// 单个分块大小的检索质量
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ChunkSizeScore {
+    pub chunk_size: usize,
+    pub chunk_overlap: usize,
+    pub chunk_count: usize,
+    /// 预期文本出现在前 top_k 个结果中的查询数
+    pub hits: usize,
+    pub hit_rate: f64,
+    /// 平均倒数排名（未命中记 0）
+    pub mrr: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkSizeRecommendation {
+    pub recommended_chunk_size: usize,
+    pub top_k: usize,
+    /// 按候选分块大小从小到大排列
+    pub scores: Vec<ChunkSizeScore>,
+}
+
+/// 与默认配置（1000 / 100）保持相同的重叠比例
+pub fn overlap_for(chunk_size: usize) -> usize {
+    chunk_size / 10
+}
+
+/// 去掉空白并统一大小写后再比较：分块时句子之间的空格可能被合并或丢失，不应影响是否命中
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase()
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// 预期文本在前 `top_k` 个检索结果中首次出现的排名（从 1 开始）
+fn hit_rank(query_embedding: &[f64], chunks: &[String], embeddings: &[Vec<f64>], expected: &str, top_k: usize) -> Option<usize> {
+    let mut ranked: Vec<(usize, f64)> = embeddings
+        .iter()
+        .enumerate()
+        .map(|(i, embedding)| (i, cosine_similarity(query_embedding, embedding)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranked
+        .iter()
+        .take(top_k)
+        .position(|(i, _)| normalize(&chunks[*i]).contains(expected))
+        .map(|position| position + 1)
+}
+
+/// 评估各候选分块大小并给出推荐
+///
+/// `embed` 对一批文本生成向量，返回顺序与输入一致；查询向量只生成一次，所有候选共用
+pub async fn evaluate_chunk_sizes<E, Fut>(
+    sample: &str,
+    candidate_sizes: &[usize],
+    queries: &[ChunkSizeTestQuery],
+    top_k: usize,
+    mut embed: E,
+) -> Result<ChunkSizeRecommendation>
+where
+    E: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<Vec<f64>>>>,
+{
+    if sample.trim().is_empty() {
+        return Err(anyhow!("样本文本不能为空"));
+    }
+    if queries.is_empty() {
+        return Err(anyhow!("测试查询不能为空"));
+    }
+    if top_k == 0 {
+        return Err(anyhow!("top_k 必须大于 0"));
+    }
+    let mut sizes: Vec<usize> = candidate_sizes.to_vec();
+    sizes.sort_unstable();
+    sizes.dedup();
+    if sizes.is_empty() || sizes.iter().any(|size| !(MIN_CANDIDATE_CHUNK_SIZE..=MAX_CANDIDATE_CHUNK_SIZE).contains(size)) {
+        return Err(anyhow!(
+            "候选分块大小必须在 {} 到 {} 之间",
+            MIN_CANDIDATE_CHUNK_SIZE,
+            MAX_CANDIDATE_CHUNK_SIZE
+        ));
+    }
+
+    let query_embeddings = embed(queries.iter().map(|q| q.query.clone()).collect()).await?;
+    if query_embeddings.len() != queries.len() {
+        return Err(anyhow!("查询向量数量与查询数量不一致"));
+    }
+    let expected: Vec<String> = queries.iter().map(|q| normalize(&q.expected_text)).collect();
+
+    let mut scores = Vec::with_capacity(sizes.len());
+    for chunk_size in sizes {
+        let chunk_overlap = overlap_for(chunk_size);
+        let processor = DocumentProcessor::with_chunk_settings(chunk_size, chunk_overlap);
+        let chunks: Vec<String> = processor
+            .chunk_text(Uuid::nil(), sample)?
+            .into_iter()
+            .map(|chunk| chunk.content)
+            .collect();
+
+        let embeddings = embed(chunks.clone()).await?;
+        if embeddings.len() != chunks.len() {
+            return Err(anyhow!("分块向量数量与分块数量不一致"));
+        }
+
+        let ranks: Vec<Option<usize>> = query_embeddings
+            .iter()
+            .zip(&expected)
+            .map(|(query_embedding, expected)| hit_rank(query_embedding, &chunks, &embeddings, expected, top_k))
+            .collect();
+        let hits = ranks.iter().flatten().count();
+        let mrr = ranks.iter().flatten().map(|rank| 1.0 / *rank as f64).sum::<f64>() / queries.len() as f64;
+
+        log::info!("📐 分块大小 {}: {} 个块, 命中 {}/{}, MRR={:.3}", chunk_size, chunks.len(), hits, queries.len(), mrr);
+        scores.push(ChunkSizeScore {
+            chunk_size,
+            chunk_overlap,
+            chunk_count: chunks.len(),
+            hits,
+            hit_rate: hits as f64 / queries.len() as f64,
+            mrr,
+        });
+    }
+
+    // scores 按分块大小升序，max_by 遇到相等时取后者，所以反向遍历以便平局时选较小的分块
+    let best = scores
+        .iter()
+        .rev()
+        .max_by(|a, b| a.hit_rate.total_cmp(&b.hit_rate).then(a.mrr.total_cmp(&b.mrr)))
+        .map(|score| score.chunk_size)
+        .ok_or_else(|| anyhow!("没有可评估的分块大小"))?;
+    log::info!("📐 推荐分块大小: {}", best);
+
+    Ok(ChunkSizeRecommendation {
+        recommended_chunk_size: best,
+        top_k,
+        scores,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSION: usize = 64;
+
+    /// 词袋向量：每个词按字节和落到一个维度上
+    fn bag_of_words(text: &str) -> Vec<f64> {
+        let mut vector = vec![0.0; DIMENSION];
+        for word in text.split_whitespace() {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+            if !word.is_empty() {
+                let slot = word.bytes().map(usize::from).sum::<usize>() % DIMENSION;
+                vector[slot] += 1.0;
+            }
+        }
+        vector
+    }
+
+    fn test_query(query: &str, expected_text: &str) -> ChunkSizeTestQuery {
+        ChunkSizeTestQuery {
+            query: query.to_string(),
+            expected_text: expected_text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunk_size_that_keeps_answers_together_is_recommended() {
+        // 每个主题的答案跨两句：分块太小会把两句拆开；分块很大时同样能命中，但平局时选较小的分块
+        let pairs = [
+            ("volcanoes lava basalt", "Volcanoes on the island erupt with molten lava.", "The lava slowly cools and hardens into basalt."),
+            ("bees nectar honey", "Bees in the meadow gather nectar from flowers.", "The hive turns that nectar into golden honey."),
+            ("rivers canyons strata", "Rivers in the desert carve very deep canyons.", "The canyon walls expose many ancient strata."),
+            ("comets ice sunlight", "Comets from the outer system carry frozen ice.", "Their ice sublimates when comets near sunlight."),
+        ];
+        let sample = pairs.iter().map(|(_, a, b)| format!("{} {}", a, b)).collect::<Vec<_>>().join(" ");
+        let queries: Vec<ChunkSizeTestQuery> = pairs.iter().map(|(q, a, b)| test_query(q, &format!("{} {}", a, b))).collect();
+
+        let recommendation = evaluate_chunk_sizes(&sample, &[1000, 12, 30], &queries, 1, |texts| async move {
+            Ok(texts.iter().map(|text| bag_of_words(text)).collect())
+        })
+        .await
+        .unwrap();
+
+        let sizes: Vec<usize> = recommendation.scores.iter().map(|s| s.chunk_size).collect();
+        assert_eq!(sizes, vec![12, 30, 1000]);
+        assert_eq!(recommendation.scores[0].hits, 0);
+        assert_eq!(recommendation.scores[1].hits, 4);
+        assert_eq!(recommendation.scores[1].mrr, 1.0);
+        assert_eq!(recommendation.scores[2].chunk_count, 1);
+        assert_eq!(recommendation.recommended_chunk_size, 30);
+
+        let no_queries = evaluate_chunk_sizes(&sample, &[30], &[], 1, |_| async { Ok(vec![]) }).await;
+        assert!(no_queries.is_err());
+        let too_small = evaluate_chunk_sizes(&sample, &[5], &queries, 1, |_| async { Ok(vec![]) }).await;
+        assert!(too_small.is_err());
+    }
+}
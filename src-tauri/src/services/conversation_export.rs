@@ -0,0 +1,210 @@
+//! 对话流式导出
+//!
+//! 按页读取消息并逐页写入输出文件，导出过程中只持有一页消息，
+//! 即使是上万条消息的对话，内存占用也保持在一页的大小。
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io::Write;
+
+use crate::models::conversation::{Conversation, Message, MessageRole};
+use crate::services::response_filter;
+
+/// 每页读取的消息数
+pub const EXPORT_PAGE_SIZE: usize = 200;
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// 便于阅读的 Markdown
+    #[default]
+    Markdown,
+    /// 每行一条消息的 JSON Lines，便于程序处理
+    Jsonl,
+}
+
+fn role_label(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "用户",
+        MessageRole::Assistant => "助手",
+        MessageRole::System => "系统",
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, conversation: &Conversation, format: ExportFormat) -> Result<()> {
+    if format == ExportFormat::Markdown {
+        writeln!(writer, "# {}", conversation.title)?;
+        writeln!(writer)?;
+        writeln!(writer, "- 创建时间: {}", conversation.created_at.format("%Y-%m-%d %H:%M:%S"))?;
+        writeln!(writer, "- 消息数: {}", conversation.message_count)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_message<W: Write>(writer: &mut W, message: &Message, format: ExportFormat) -> Result<()> {
+    match format {
+        ExportFormat::Markdown => {
+            writeln!(
+                writer,
+                "## {} · {}",
+                role_label(&message.role),
+                message.timestamp.format("%Y-%m-%d %H:%M:%S")
+            )?;
+            writeln!(writer)?;
+            writeln!(writer, "{}", response_filter::strip_score_footer(&message.content))?;
+            if let Some(sources) = message.sources.as_ref().filter(|s| !s.is_empty()) {
+                let filenames: Vec<&str> = sources.iter().map(|s| s.filename.as_str()).collect();
+                writeln!(writer)?;
+                writeln!(writer, "> 来源: {}", filenames.join(", "))?;
+            }
+            writeln!(writer)?;
+        }
+        ExportFormat::Jsonl => {
+            let content = response_filter::strip_score_footer(&message.content);
+            if content.len() == message.content.len() {
+                serde_json::to_writer(&mut *writer, message)?;
+            } else {
+                serde_json::to_writer(&mut *writer, &Message { content: content.to_string(), ..message.clone() })?;
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// 分页导出对话：`fetch_page(offset, limit)` 返回按顺序排列的一页消息，
+/// 每写完一页调用 `on_progress(已导出数, 总数)`；返回导出的消息数
+pub async fn export_paged<W, F, Fut, P>(
+    writer: &mut W,
+    conversation: &Conversation,
+    format: ExportFormat,
+    total: usize,
+    page_size: usize,
+    mut fetch_page: F,
+    mut on_progress: P,
+) -> Result<usize>
+where
+    W: Write,
+    F: FnMut(usize, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<Message>>>,
+    P: FnMut(usize, usize),
+{
+    let page_size = page_size.max(1);
+    write_header(writer, conversation, format)?;
+
+    let mut exported = 0;
+    loop {
+        let page = fetch_page(exported, page_size).await?;
+        if page.is_empty() {
+            break;
+        }
+
+        for message in &page {
+            write_message(writer, message, format)?;
+        }
+        exported += page.len();
+        on_progress(exported, total);
+
+        if page.len() < page_size {
+            break;
+        }
+    }
+
+    writer.flush()?;
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::conversation_service::message_page;
+    use std::cell::RefCell;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_large_conversation_exports_completely_via_paged_writes() {
+        let conversation = Conversation::new(Uuid::new_v4(), Some("大对话".to_string())).unwrap();
+        let messages: Vec<Message> = (1..=2500u64)
+            .map(|i| {
+                let role = if i % 2 == 1 { MessageRole::User } else { MessageRole::Assistant };
+                let mut message = Message::new(conversation.id, role, format!("第 {} 条消息", i)).unwrap();
+                message.sequence = i;
+                message
+            })
+            .collect();
+
+        let largest_page = RefCell::new(0);
+        let progress = RefCell::new(Vec::new());
+        let mut output = Vec::new();
+
+        let exported = export_paged(
+            &mut output,
+            &conversation,
+            ExportFormat::Jsonl,
+            messages.len(),
+            100,
+            |offset, limit| {
+                let page = message_page(&messages, offset, limit);
+                let len = page.len();
+                largest_page.replace_with(|max| (*max).max(len));
+                async move { Ok(page) }
+            },
+            |done, total| progress.borrow_mut().push((done, total)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(exported, 2500);
+        assert_eq!(*largest_page.borrow(), 100);
+        assert_eq!(progress.borrow().len(), 25);
+        assert_eq!(progress.borrow().last(), Some(&(2500, 2500)));
+
+        let lines: Vec<Message> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let sequences: Vec<u64> = lines.iter().map(|m| m.sequence).collect();
+        assert_eq!(sequences, (1..=2500).collect::<Vec<_>>());
+
+        // Markdown 导出同样包含所有消息
+        let mut markdown = Vec::new();
+        let exported = export_paged(
+            &mut markdown,
+            &conversation,
+            ExportFormat::Markdown,
+            messages.len(),
+            EXPORT_PAGE_SIZE,
+            |offset, limit| {
+                let page = message_page(&messages, offset, limit);
+                async move { Ok(page) }
+            },
+            |_, _| {},
+        )
+        .await
+        .unwrap();
+        let markdown = String::from_utf8(markdown).unwrap();
+        assert_eq!(exported, 2500);
+        assert!(markdown.starts_with("# 大对话"));
+        assert_eq!(markdown.matches("\n## ").count(), 2500);
+        assert!(markdown.contains("第 2500 条消息"));
+    }
+
+    #[test]
+    fn test_score_footer_is_not_exported() {
+        let conversation_id = Uuid::new_v4();
+        let content = "SeekDB 支持混合检索。\n\n<!-- mine-kb-scores: [{\"index\":0,\"score\":0.9}] -->";
+        let message = Message::new(conversation_id, MessageRole::Assistant, content.to_string()).unwrap();
+
+        for format in [ExportFormat::Markdown, ExportFormat::Jsonl] {
+            let mut output = Vec::new();
+            write_message(&mut output, &message, format).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("SeekDB 支持混合检索。"));
+            assert!(!output.contains("mine-kb-scores"));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::conversation::{Conversation, Message, MessageRole};
+use crate::models::conversation::{ContextChunk, Conversation, Message, MessageRole};
 use crate::services::seekdb_adapter::SeekDbAdapter;
 use anyhow::{anyhow, Result};
 use uuid::Uuid;
@@ -6,10 +6,305 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// 对话列表中最后一条消息预览的最大字符数
+pub const LAST_MESSAGE_PREVIEW_CHARS: usize = 60;
+
+/// 压缩对话时默认原样保留的最近轮数（一轮从一条用户消息开始）
+pub const DEFAULT_KEEP_RECENT_TURNS: usize = 3;
+
+/// 压缩后摘要消息的开头
+pub const COMPRESSED_SUMMARY_PREFIX: &str = "[早期对话摘要]";
+
+/// 对话统计信息
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConversationStats {
+    pub total_messages: usize,
+    pub user_messages: usize,
+    pub assistant_messages: usize,
+    pub system_messages: usize,
+    pub total_tokens: u64,
+    pub user_tokens: u64,
+    pub assistant_tokens: u64,
+    /// 有记录处理时间的 AI 回复的平均耗时（秒），没有记录时为 None
+    pub average_response_time: Option<f64>,
+    pub total_response_time: f64,
+}
+
+/// 消息内容中的一处匹配，按字符（而非字节）计的 [start, end) 区间，便于前端高亮
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 对话内搜索命中的消息
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MessageSearchHit {
+    pub message_id: String,
+    /// 消息在对话中的位置（按显示顺序，从 0 开始），用于跳转
+    pub position: usize,
+    pub role: String,
+    pub content: String,
+    pub timestamp: String,
+    pub highlights: Vec<HighlightRange>,
+}
+
+/// 根据消息列表汇总对话统计（空对话返回全零统计）
+fn compute_conversation_stats(messages: &[Message]) -> ConversationStats {
+    let mut stats = ConversationStats {
+        total_messages: messages.len(),
+        ..Default::default()
+    };
+    let mut timed_responses = 0usize;
+
+    for message in messages {
+        let tokens = message.token_count as u64;
+        stats.total_tokens += tokens;
+        match message.role {
+            MessageRole::User => {
+                stats.user_messages += 1;
+                stats.user_tokens += tokens;
+            }
+            MessageRole::Assistant => {
+                stats.assistant_messages += 1;
+                stats.assistant_tokens += tokens;
+                if let Some(time) = message.processing_time {
+                    stats.total_response_time += time;
+                    timed_responses += 1;
+                }
+            }
+            MessageRole::System => stats.system_messages += 1,
+        }
+    }
+
+    if timed_responses > 0 {
+        stats.average_response_time = Some(stats.total_response_time / timed_responses as f64);
+    }
+
+    stats
+}
+
+/// 返回时间上最新的一条消息
+fn latest_message(messages: &[Message]) -> Option<&Message> {
+    messages.iter().max_by_key(|m| m.order_key())
+}
+
+/// 下一条消息的序号（对话内单调递增）
+fn next_sequence(messages: &[Message]) -> u64 {
+    messages.iter().map(|m| m.sequence).max().unwrap_or(0) + 1
+}
+
+/// 按当前顺序（时间戳，同一秒内按插入顺序）重新编号，返回序号发生变化的消息
+fn assign_sequences(messages: &mut [Message]) -> Vec<Uuid> {
+    // 稳定排序：同一秒且都没有序号的消息保持原有（插入/加载）顺序
+    messages.sort_by_key(|m| m.order_key());
+
+    let mut changed = Vec::new();
+    for (i, message) in messages.iter_mut().enumerate() {
+        let sequence = i as u64 + 1;
+        if message.sequence != sequence {
+            message.sequence = sequence;
+            changed.push(message.id);
+        }
+    }
+    changed
+}
+
+/// 从已按显示顺序排列的消息中取一页（只克隆本页，用于分页读取大对话）
+pub fn message_page(messages: &[Message], offset: usize, limit: usize) -> Vec<Message> {
+    messages.iter().skip(offset).take(limit).cloned().collect()
+}
+
+/// 从最新的消息往前分页：跳过最新的 `offset` 条后取 `limit` 条，返回这一页在升序列表中的范围
+pub fn latest_page_range(total: usize, offset: usize, limit: usize) -> std::ops::Range<usize> {
+    let end = total.saturating_sub(offset);
+    end.saturating_sub(limit)..end
+}
+
+/// 找出压缩时要被摘要替换的消息：最近 `keep_turns` 轮（从倒数第 `keep_turns` 条用户消息开始）原样保留，
+/// 更早的消息按显示顺序返回；不足 2 条时不值得压缩，返回空
+pub fn messages_to_compress(messages: &[Message], keep_turns: usize) -> Vec<Message> {
+    let mut sorted = messages.to_vec();
+    sorted.sort_by_key(|m| m.order_key());
+
+    let keep_from = match keep_turns {
+        0 => sorted.len(),
+        n => sorted
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, m)| m.role == MessageRole::User)
+            .nth(n - 1)
+            .map_or(0, |(i, _)| i),
+    };
+    sorted.truncate(keep_from);
+    if sorted.len() < 2 {
+        sorted.clear();
+    }
+    sorted
+}
+
+/// 把要压缩的消息整理成交给模型摘要的文本
+pub fn compression_transcript(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .map(|m| {
+            let speaker = match m.role {
+                MessageRole::User => "用户",
+                MessageRole::Assistant => "助手",
+                MessageRole::System => "系统",
+            };
+            format!("{}：{}", speaker, m.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 摘要消息的内容：带上前缀，并截断到消息长度上限（10000 字节）以内
+pub fn compressed_summary_content(summary: &str) -> String {
+    const MAX_MESSAGE_BYTES: usize = 10000;
+    let mut content = format!("{}\n{}", COMPRESSED_SUMMARY_PREFIX, summary.trim());
+    if content.len() > MAX_MESSAGE_BYTES {
+        let mut end = MAX_MESSAGE_BYTES;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        content.truncate(end);
+    }
+    content
+}
+
+/// 编辑用户消息：替换内容并删除其后的所有消息，返回被删除的消息 ID；
+/// 编辑后 `messages` 只保留到该消息（含），按显示顺序排列
+fn apply_user_message_edit(messages: &mut Vec<Message>, message_id: Uuid, content: String) -> Result<Vec<Uuid>> {
+    Message::validate_content(&content, &MessageRole::User)?;
+    messages.sort_by_key(|m| m.order_key());
+
+    let index = messages
+        .iter()
+        .position(|m| m.id == message_id)
+        .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+    if messages[index].role != MessageRole::User {
+        return Err(anyhow!("只能编辑用户消息"));
+    }
+
+    let removed = messages.split_off(index + 1).into_iter().map(|m| m.id).collect();
+    let edited = &mut messages[index];
+    edited.token_count = Message::estimate_token_count(&content);
+    edited.content = content;
+    Ok(removed)
+}
+
+/// 把消息追加到已按显示顺序排列的 `messages` 末尾并分配下一个序号，返回追加后的消息
+fn append_message(messages: &mut Vec<Message>, mut message: Message) -> Message {
+    message.sequence = next_sequence(messages);
+    messages.push(message.clone());
+    message
+}
+
+/// 只保留最近的 `keep_last_n` 条消息（按显示顺序），返回被删除的消息 ID
+fn trim_to_last(messages: &mut Vec<Message>, keep_last_n: usize) -> Vec<Uuid> {
+    messages.sort_by_key(|m| m.order_key());
+    let cut = messages.len().saturating_sub(keep_last_n);
+    messages.drain(..cut).map(|m| m.id).collect()
+}
+
+/// 在对话消息中查找包含 `query` 的消息（忽略大小写的子串匹配），按显示顺序返回命中的消息及所有匹配位置
+pub fn search_messages(messages: &[Message], query: &str) -> Vec<MessageSearchHit> {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let needle: Vec<char> = query.trim().chars().map(fold).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut order: Vec<usize> = (0..messages.len()).collect();
+    order.sort_by_key(|&i| messages[i].order_key());
+
+    order
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, i)| {
+            let message = &messages[i];
+            let haystack: Vec<char> = message.content.chars().map(fold).collect();
+
+            let mut highlights = Vec::new();
+            let mut start = 0;
+            while start + needle.len() <= haystack.len() {
+                if haystack[start..start + needle.len()] == needle[..] {
+                    highlights.push(HighlightRange { start, end: start + needle.len() });
+                    start += needle.len();
+                } else {
+                    start += 1;
+                }
+            }
+            if highlights.is_empty() {
+                return None;
+            }
+
+            Some(MessageSearchHit {
+                message_id: message.id.to_string(),
+                position,
+                role: message.role.to_string(),
+                content: message.content.clone(),
+                timestamp: message.timestamp.to_rfc3339(),
+                highlights,
+            })
+        })
+        .collect()
+}
+
+/// 用摘要消息替换被压缩的消息：摘要占据被压缩消息的位置（对话最前面），然后重新编号；
+/// 返回需要保存的消息（摘要和序号变化的消息）ID
+fn apply_compression(messages: &mut Vec<Message>, compressed: &[Uuid], mut summary: Message) -> Vec<Uuid> {
+    if let Some(earliest) = messages
+        .iter()
+        .filter(|m| compressed.contains(&m.id))
+        .map(|m| m.timestamp)
+        .min()
+    {
+        summary.timestamp = earliest;
+    }
+    summary.sequence = 0;
+
+    messages.retain(|m| !compressed.contains(&m.id));
+    messages.push(summary);
+    assign_sequences(messages)
+}
+
+/// 重新生成某条助手消息时使用的对话历史：目标消息之前、截止到最近一条用户消息（含）
+fn regeneration_history(messages: &[Message], message_id: Uuid) -> Result<Vec<Message>> {
+    let mut sorted = messages.to_vec();
+    sorted.sort_by_key(|m| m.order_key());
+
+    let target_index = sorted
+        .iter()
+        .position(|m| m.id == message_id)
+        .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+
+    if sorted[target_index].role != MessageRole::Assistant {
+        return Err(anyhow!("只能重新生成助手消息"));
+    }
+
+    let user_index = sorted[..target_index]
+        .iter()
+        .rposition(|m| m.role == MessageRole::User)
+        .ok_or_else(|| anyhow!("目标消息之前没有用户消息"))?;
+
+    sorted.truncate(user_index + 1);
+    Ok(sorted)
+}
+
+/// 对话和消息的内存缓存
+///
+/// 启动时只加载对话及每个对话的最新一条消息（列表预览），对话的全部消息在第一次需要时才加载；
+/// 只查看最新一页历史时直接按 LIMIT 从数据库读取，不加载整段对话。
 #[derive(Debug)]
 pub struct ConversationService {
     conversations: HashMap<Uuid, Conversation>,
-    messages: HashMap<Uuid, Vec<Message>>, // conversation_id -> messages
+    /// 已加载全部消息的对话：conversation_id -> messages（始终按显示顺序排列）
+    messages: HashMap<Uuid, Vec<Message>>,
+    /// 尚未加载消息的对话的最新一条消息
+    latest_messages: HashMap<Uuid, Message>,
     db: Arc<Mutex<SeekDbAdapter>>,
 }
 
@@ -20,6 +315,7 @@ impl ConversationService {
         let mut service = Self {
             conversations: HashMap::new(),
             messages: HashMap::new(),
+            latest_messages: HashMap::new(),
             db: db.clone(),
         };
 
@@ -29,7 +325,7 @@ impl ConversationService {
             Ok(_) => {
                 log::info!("ConversationService 初始化完成: {} 个对话，{} 条消息",
                     service.conversations.len(),
-                    service.messages.values().map(|v| v.len()).sum::<usize>()
+                    service.total_message_count()
                 );
             }
             Err(e) => {
@@ -41,7 +337,7 @@ impl ConversationService {
         service
     }
 
-    /// 从数据库加载所有对话和消息
+    /// 从数据库加载所有对话及各自的最新一条消息（替换内存中已有的数据）
     async fn load_from_database(&mut self) -> Result<()> {
         log::info!("load_from_database: 开始执行");
 
@@ -52,31 +348,65 @@ impl ConversationService {
         let conversations = db.load_all_conversations()?;
         log::info!("✅ 从数据库加载了 {} 个对话", conversations.len());
 
+        let mut loaded_conversations = HashMap::with_capacity(conversations.len());
+        let mut latest_messages = HashMap::with_capacity(conversations.len());
         for conv in conversations {
             let conv_id = conv.id;
             log::info!("处理对话: id={}, title={}", conv_id, conv.title);
 
-            // 加载该对话的所有消息
-            match db.load_messages_by_conversation(&conv_id.to_string()) {
-                Ok(messages) => {
-                    log::info!("✅ 对话 {} 加载了 {} 条消息", conv_id, messages.len());
-                    self.conversations.insert(conv_id, conv);
-                    self.messages.insert(conv_id, messages);
+            // 只读取最新一条消息用于列表预览，全部消息在打开对话时再加载
+            match db.load_latest_messages(&conv_id.to_string(), 1) {
+                Ok(mut messages) => {
+                    if let Some(latest) = messages.pop() {
+                        latest_messages.insert(conv_id, latest);
+                    }
                 }
                 Err(e) => {
-                    log::error!("❌ 对话 {} 加载消息失败: {}", conv_id, e);
-                    log::error!("错误详情: {:?}", e);
                     // 即使某个对话加载失败，也继续加载其他对话
-                    self.conversations.insert(conv_id, conv);
-                    self.messages.insert(conv_id, Vec::new());
+                    log::error!("❌ 对话 {} 加载最新消息失败: {}", conv_id, e);
                 }
             }
+            loaded_conversations.insert(conv_id, conv);
         }
 
+        self.conversations = loaded_conversations;
+        self.messages = HashMap::new();
+        self.latest_messages = latest_messages;
+
         log::info!("load_from_database: 完成");
         Ok(())
     }
 
+    /// 重新从数据库加载对话和消息，返回 (对话数, 消息数)
+    pub async fn reload_from_database(&mut self) -> Result<(usize, usize)> {
+        self.load_from_database().await?;
+        Ok((self.conversations.len(), self.total_message_count()))
+    }
+
+    /// 所有对话的消息总数（按对话记录的消息数）
+    fn total_message_count(&self) -> usize {
+        self.conversations.values().map(|conv| conv.message_count as usize).sum()
+    }
+
+    /// 确保对话的全部消息已加载到内存（第一次访问时从数据库读取）
+    pub async fn load_messages(&mut self, conversation_id: Uuid) -> Result<()> {
+        self.conversations
+            .get(&conversation_id)
+            .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
+        if self.messages.contains_key(&conversation_id) {
+            return Ok(());
+        }
+
+        let messages = {
+            let db = self.db.lock().await;
+            db.load_messages_by_conversation(&conversation_id.to_string())?
+        };
+        log::info!("✅ 对话 {} 加载了 {} 条消息", conversation_id, messages.len());
+        self.messages.insert(conversation_id, messages);
+        self.latest_messages.remove(&conversation_id);
+        Ok(())
+    }
+
     pub async fn create_conversation(&mut self, project_id: Uuid, title: Option<String>) -> Result<Uuid> {
         let conversation = Conversation::new(project_id, title)?;
         let conversation_id = conversation.id;
@@ -92,6 +422,22 @@ impl ConversationService {
         Ok(conversation_id)
     }
 
+    /// 记录已直接写入数据库的对话及其消息（如导入），其他对话保持不变
+    pub fn insert_imported(&mut self, conversations: Vec<Conversation>, messages: Vec<Message>) {
+        let mut by_conversation: HashMap<Uuid, Vec<Message>> = HashMap::new();
+        for message in messages {
+            by_conversation.entry(message.conversation_id).or_default().push(message);
+        }
+
+        for conversation in conversations {
+            let mut messages = by_conversation.remove(&conversation.id).unwrap_or_default();
+            messages.sort_by_key(Message::order_key);
+            self.latest_messages.remove(&conversation.id);
+            self.messages.insert(conversation.id, messages);
+            self.conversations.insert(conversation.id, conversation);
+        }
+    }
+
     pub fn get_conversation(&self, conversation_id: Uuid) -> Option<&Conversation> {
         self.conversations.get(&conversation_id)
     }
@@ -118,14 +464,28 @@ impl ConversationService {
         conversations
     }
 
+    /// 每个项目最新对话的更新时间（项目ID -> updated_at）
+    pub fn latest_activity_by_project(&self) -> HashMap<Uuid, chrono::DateTime<chrono::Utc>> {
+        let mut activity: HashMap<Uuid, chrono::DateTime<chrono::Utc>> = HashMap::new();
+        for conv in self.conversations.values() {
+            activity
+                .entry(conv.project_id)
+                .and_modify(|latest| *latest = (*latest).max(conv.updated_at))
+                .or_insert(conv.updated_at);
+        }
+        activity
+    }
+
     pub async fn add_message(&mut self, conversation_id: Uuid, role: MessageRole, content: String) -> Result<Uuid> {
         log::info!("add_message 开始: conversation_id={}, role={:?}", conversation_id, role);
+        self.load_messages(conversation_id).await?;
 
         let conversation = self.conversations
             .get_mut(&conversation_id)
             .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
 
-        let message = Message::new(conversation_id, role, content)?;
+        let mut message = Message::new(conversation_id, role, content)?;
+        message.sequence = next_sequence(self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or(&[]));
         let message_id = message.id;
         log::info!("创建消息对象成功: message_id={}", message_id);
 
@@ -157,9 +517,10 @@ impl ConversationService {
             log::warn!("🔍 [AFTER-LOCK-RELEASE] 释放锁后，messages总数: {}", count);
         }
 
-        // Add message to messages collection
+        // Add message to messages collection（按显示顺序插入，通常在末尾）
         let messages = self.messages.entry(conversation_id).or_insert_with(Vec::new);
-        messages.push(message);
+        let index = messages.partition_point(|m| m.order_key() <= message.order_key());
+        messages.insert(index, message);
         log::info!("消息添加到内存集合成功");
 
         // Update conversation
@@ -205,6 +566,23 @@ impl ConversationService {
         Ok(())
     }
 
+    /// 设置或清除（None）对话的模型覆盖
+    pub async fn set_conversation_model(&mut self, conversation_id: Uuid, model: Option<String>) -> Result<()> {
+        let conversation = self.conversations
+            .get_mut(&conversation_id)
+            .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
+
+        conversation.set_model(model)?;
+
+        // 保存到数据库
+        {
+            let mut db = self.db.lock().await;
+            db.save_conversation(conversation)?;
+        }
+
+        Ok(())
+    }
+
     pub async fn delete_conversation(&mut self, conversation_id: Uuid) -> Result<()> {
         // 从数据库删除
         {
@@ -216,10 +594,13 @@ impl ConversationService {
             .remove(&conversation_id)
             .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
         self.messages.remove(&conversation_id);
+        self.latest_messages.remove(&conversation_id);
         Ok(())
     }
 
     pub async fn delete_message(&mut self, conversation_id: Uuid, message_id: Uuid) -> Result<()> {
+        self.load_messages(conversation_id).await?;
+
         // 验证对话是否存在
         let conversation = self.conversations
             .get_mut(&conversation_id)
@@ -264,7 +645,8 @@ impl ConversationService {
         }
 
         // 清空内存中的消息
-        self.messages.entry(conversation_id).or_insert_with(Vec::new).clear();
+        self.messages.insert(conversation_id, Vec::new());
+        self.latest_messages.remove(&conversation_id);
         conversation.update_message_count(0);
 
         // 更新对话到数据库
@@ -276,23 +658,261 @@ impl ConversationService {
         Ok(())
     }
 
-    pub fn get_conversation_messages(&self, conversation_id: Uuid) -> Result<Vec<Message>> {
-        log::info!("get_conversation_messages: conversation_id={}", conversation_id);
+    /// 获取对话消息，返回 (消息, 消息总数)
+    ///
+    /// 从最新的消息往前分页：跳过最新的 `offset` 条后取 `limit` 条（None 表示取到最早的消息），
+    /// 页内按时间升序排列。消息尚未加载时，指定了 `limit` 的请求直接按 LIMIT 从数据库读取。
+    pub async fn get_conversation_messages(
+        &mut self,
+        conversation_id: Uuid,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Message>, usize)> {
+        log::info!(
+            "get_conversation_messages: conversation_id={}, offset={:?}, limit={:?}",
+            conversation_id,
+            offset,
+            limit
+        );
 
-        self.conversations
+        let conversation = self.conversations
             .get(&conversation_id)
             .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
 
-        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
-        
-        // 确保消息按创建时间升序排序（从旧到新）
-        messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        if let (false, Some(limit)) = (self.messages.contains_key(&conversation_id), limit) {
+            let offset = offset.unwrap_or(0);
+            let mut messages = {
+                let db = self.db.lock().await;
+                db.load_latest_messages(&conversation_id.to_string(), offset.saturating_add(limit))?
+            };
+            let total = (conversation.message_count as usize).max(messages.len());
+            let range = latest_page_range(messages.len(), offset, limit);
+            let messages = messages.drain(range).collect::<Vec<_>>();
+            log::info!("get_conversation_messages: 从数据库返回 {}/{} 条消息", messages.len(), total);
+            return Ok((messages, total));
+        }
+        self.load_messages(conversation_id).await?;
+
+        // 内存中的消息已按创建时间升序排列（从旧到新），同一秒内按序号；只克隆本页
+        let messages = self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or_default();
+        let total = messages.len();
+        let range = latest_page_range(total, offset.unwrap_or(0), limit.unwrap_or(total));
+        let messages = messages[range].to_vec();
         
-        log::info!("get_conversation_messages: 从内存返回 {} 条消息（已按时间排序）", messages.len());
+        log::info!("get_conversation_messages: 从内存返回 {}/{} 条消息（已按时间排序）", messages.len(), total);
 
+        Ok((messages, total))
+    }
+
+    /// 分页获取对话消息（按显示顺序），返回本页消息和消息总数
+    pub async fn get_message_page(&mut self, conversation_id: Uuid, offset: usize, limit: usize) -> Result<(Vec<Message>, usize)> {
+        self.load_messages(conversation_id).await?;
+
+        let messages = self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or_default();
+        Ok((message_page(messages, offset, limit), messages.len()))
+    }
+
+    /// 获取对话统计（按角色的消息数、token 总数、平均响应时间）
+    pub async fn get_conversation_stats(&mut self, conversation_id: Uuid) -> Result<ConversationStats> {
+        self.load_messages(conversation_id).await?;
+
+        let messages = self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or(&[]);
+        Ok(compute_conversation_stats(messages))
+    }
+
+    /// 获取对话中最新的一条消息（空对话返回 None）
+    pub fn get_last_message(&self, conversation_id: Uuid) -> Option<&Message> {
+        match self.messages.get(&conversation_id) {
+            Some(messages) => latest_message(messages),
+            None => self.latest_messages.get(&conversation_id),
+        }
+    }
+
+    /// 获取重新生成指定助手消息所需的对话历史（最后一条为对应的用户消息）
+    pub async fn get_regeneration_history(&mut self, conversation_id: Uuid, message_id: Uuid) -> Result<Vec<Message>> {
+        self.load_messages(conversation_id).await?;
+
+        let messages = self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or(&[]);
+        regeneration_history(messages, message_id)
+    }
+
+    /// 返回编辑用户消息后截止到该消息（含）的对话历史，不修改内存和数据库，用于先生成新回复
+    pub async fn preview_user_message_edit(&mut self, conversation_id: Uuid, message_id: Uuid, content: String) -> Result<Vec<Message>> {
+        self.load_messages(conversation_id).await?;
+        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
+        apply_user_message_edit(&mut messages, message_id, content)?;
         Ok(messages)
     }
 
+    /// 编辑用户消息并追加新的助手回复：替换内容、删除其后的消息并保存回复，
+    /// 全部写入数据库成功后才更新内存，返回新回复的 ID
+    pub async fn edit_user_message(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        content: String,
+        reply: Message,
+    ) -> Result<Uuid> {
+        self.load_messages(conversation_id).await?;
+        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
+        let removed = apply_user_message_edit(&mut messages, message_id, content)?;
+        let edited = messages.last().cloned().ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+        let reply = append_message(&mut messages, reply);
+
+        self.commit_messages(conversation_id, messages, |db, _| {
+            db.save_message(&edited)?;
+            for id in &removed {
+                db.delete_message_by_id(&id.to_string())?;
+            }
+            db.save_message(&reply)
+        })
+        .await?;
+
+        log::info!("✏️  已编辑消息 {}，删除其后的 {} 条消息", message_id, removed.len());
+        Ok(reply.id)
+    }
+
+    /// 在单个对话的消息中搜索，返回命中的消息及匹配位置
+    pub async fn search_conversation_messages(&mut self, conversation_id: Uuid, query: &str) -> Result<Vec<MessageSearchHit>> {
+        self.load_messages(conversation_id).await?;
+
+        let messages = self.messages.get(&conversation_id).map(Vec::as_slice).unwrap_or(&[]);
+        Ok(search_messages(messages, query))
+    }
+
+    /// 删除最近 `keep_last_n` 条之外的所有消息（内存和数据库），返回删除的消息数
+    pub async fn trim_conversation(&mut self, conversation_id: Uuid, keep_last_n: usize) -> Result<usize> {
+        self.load_messages(conversation_id).await?;
+        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
+        let removed = trim_to_last(&mut messages, keep_last_n);
+        let kept = messages.len();
+
+        self.commit_messages(conversation_id, messages, |db, _| {
+            for id in &removed {
+                db.delete_message_by_id(&id.to_string())?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        log::info!("✂️  对话 {} 已裁剪，删除 {} 条消息，保留 {} 条", conversation_id, removed.len(), kept);
+        Ok(removed.len())
+    }
+
+    /// 原地替换消息内容和来源（保持消息 ID 不变），写入数据库成功后才更新内存
+    pub async fn replace_message(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        content: String,
+        sources: Vec<ContextChunk>,
+        processing_time: Option<f64>,
+    ) -> Result<Message> {
+        self.load_messages(conversation_id).await?;
+        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
+        let message = messages
+            .iter_mut()
+            .find(|msg| msg.id == message_id)
+            .ok_or_else(|| anyhow!("Message not found: {}", message_id))?;
+        message.replace_content(content, sources, processing_time)?;
+        let message = message.clone();
+
+        self.commit_messages(conversation_id, messages, |db, _| db.save_message(&message))
+            .await?;
+
+        Ok(message)
+    }
+
+    /// 修复消息顺序：按时间戳（同一秒内按插入顺序）为对话中的消息重新分配序号并保存，返回更新的消息数
+    pub async fn repair_message_ordering(&mut self, conversation_id: Uuid) -> Result<usize> {
+        self.load_messages(conversation_id).await?;
+
+        let messages = self.messages.entry(conversation_id).or_default();
+        let changed = assign_sequences(messages);
+
+        if !changed.is_empty() {
+            let mut db = self.db.lock().await;
+            for message in messages.iter().filter(|m| changed.contains(&m.id)) {
+                db.save_message(message)?;
+            }
+        }
+
+        log::info!("🔧 对话 {} 的消息顺序已修复，更新了 {} 条消息", conversation_id, changed.len());
+        Ok(changed.len())
+    }
+
+    /// 压缩对话：用一条系统摘要消息替换 `compressed` 中的消息（内存和数据库），返回剩余消息数
+    pub async fn compress_messages(
+        &mut self,
+        conversation_id: Uuid,
+        compressed: &[Uuid],
+        summary: String,
+    ) -> Result<usize> {
+        self.load_messages(conversation_id).await?;
+        let summary = Message::new_system_message(conversation_id, summary)?;
+
+        let mut messages = self.messages.get(&conversation_id).cloned().unwrap_or_default();
+        // 生成摘要期间消息可能已被删除，此时放弃本次压缩
+        if let Some(missing) = compressed.iter().find(|id| !messages.iter().any(|m| m.id == **id)) {
+            return Err(anyhow!("Message not found: {}", missing));
+        }
+
+        let changed = apply_compression(&mut messages, compressed, summary);
+        let remaining = messages.len();
+
+        self.commit_messages(conversation_id, messages, |db, messages| {
+            for message_id in compressed {
+                db.delete_message_by_id(&message_id.to_string())?;
+            }
+            for message in messages.iter().filter(|m| changed.contains(&m.id)) {
+                db.save_message(message)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        log::info!(
+            "🗜️ 对话 {} 已压缩：{} 条消息替换为摘要，剩余 {} 条",
+            conversation_id,
+            compressed.len(),
+            remaining
+        );
+        Ok(remaining)
+    }
+
+    /// 把修改后的消息写入数据库，全部写入成功后才替换内存中的消息和对话的消息数
+    ///
+    /// 数据库写入不是事务性的，失败时可能只写入了一部分：此时重新从数据库加载该对话的消息，
+    /// 使内存与数据库保持一致
+    async fn commit_messages<F>(&mut self, conversation_id: Uuid, messages: Vec<Message>, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut SeekDbAdapter, &[Message]) -> Result<()>,
+    {
+        let mut conversation = self.conversations
+            .get(&conversation_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Conversation not found: {}", conversation_id))?;
+        conversation.update_message_count(messages.len() as u32);
+
+        let result = {
+            let mut db = self.db.lock().await;
+            write(&mut db, &messages).and_then(|_| db.save_conversation(&conversation))
+        };
+        if let Err(e) = result {
+            log::error!("❌ 对话 {} 写入数据库失败，重新加载消息: {}", conversation_id, e);
+            self.messages.remove(&conversation_id);
+            if let Err(reload_error) = self.load_messages(conversation_id).await {
+                log::warn!("⚠️  重新加载对话 {} 的消息失败: {}", conversation_id, reload_error);
+            }
+            return Err(e);
+        }
+
+        self.conversations.insert(conversation_id, conversation);
+        self.messages.insert(conversation_id, messages);
+        Ok(())
+    }
+
+    /// 获取已加载对话中的消息（对话消息未加载时返回 None，见 `load_messages`）
     pub fn get_message_mut(&mut self, conversation_id: Uuid, message_id: Uuid) -> Option<&mut Message> {
         self.messages
             .get_mut(&conversation_id)?
@@ -318,6 +938,322 @@ impl ConversationService {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_conversation_stats_reflect_known_messages() {
+        let conversation_id = Uuid::new_v4();
+        assert_eq!(compute_conversation_stats(&[]), ConversationStats::default());
+
+        let messages = vec![
+            Message::new_user_message(conversation_id, "a".repeat(40)).unwrap(),
+            Message::new_assistant_message(conversation_id, "b".repeat(80), vec![], Some(1.5)).unwrap(),
+            Message::new_user_message(conversation_id, "c".repeat(8)).unwrap(),
+            Message::new_assistant_message(conversation_id, "d".repeat(20), vec![], Some(2.5)).unwrap(),
+            Message::new_assistant_message(conversation_id, "e".repeat(4), vec![], None).unwrap(),
+        ];
+
+        let stats = compute_conversation_stats(&messages);
+        assert_eq!(stats.total_messages, 5);
+        assert_eq!(stats.user_messages, 2);
+        assert_eq!(stats.assistant_messages, 3);
+        assert_eq!(stats.user_tokens, 10 + 2);
+        assert_eq!(stats.assistant_tokens, 20 + 5 + 1);
+        assert_eq!(stats.total_tokens, 38);
+        assert_eq!(stats.total_response_time, 4.0);
+        assert_eq!(stats.average_response_time, Some(2.0));
+    }
+
+    #[test]
+    fn test_regeneration_replaces_message_keeping_its_id() {
+        use crate::services::llm_client::{LlmClient, LlmConfig};
+
+        let conversation_id = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let mut messages = vec![
+            Message::new_user_message(conversation_id, "第一个问题".to_string()).unwrap(),
+            Message::new_assistant_message(conversation_id, "第一个回答".to_string(), vec![], Some(1.0)).unwrap(),
+            Message::new_user_message(conversation_id, "第二个问题".to_string()).unwrap(),
+            Message::new_assistant_message(conversation_id, "第二个回答".to_string(), vec![], Some(1.0)).unwrap(),
+        ];
+        for (i, message) in messages.iter_mut().enumerate() {
+            message.timestamp = now + chrono::Duration::seconds(i as i64);
+        }
+
+        // 重新生成第一个回答：历史截止到第一个问题
+        let target_id = messages[1].id;
+        let history = regeneration_history(&messages, target_id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "第一个问题");
+        assert!(regeneration_history(&messages, messages[0].id).is_err());
+
+        // 使用不同的 temperature 重新生成
+        let client = LlmClient::new(LlmConfig { api_key: "test_key".into(), ..LlmConfig::default() }).unwrap();
+        let tweaked = client.with_temperature(Some(0.1)).unwrap();
+        assert_eq!(tweaked.get_config().temperature, Some(0.1));
+        assert_ne!(client.get_config().temperature, tweaked.get_config().temperature);
+
+        let sources = vec![ContextChunk {
+            document_id: "doc-1".to_string(),
+            filename: "guide.md".to_string(),
+            content: "资料".to_string(),
+            relevance_score: 0.8,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        }];
+        let target = &mut messages[1];
+        let timestamp = target.timestamp;
+        target.replace_content("更保守的第一个回答".to_string(), sources, Some(2.0)).unwrap();
+
+        assert_eq!(target.id, target_id);
+        assert_eq!(target.timestamp, timestamp);
+        assert_eq!(target.content, "更保守的第一个回答");
+        assert_eq!(target.sources.as_ref().unwrap()[0].filename, "guide.md");
+        assert_eq!(target.processing_time, Some(2.0));
+    }
+
+    #[test]
+    fn test_editing_user_message_drops_everything_after_it() {
+        let conversation_id = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let mut messages = vec![
+            Message::new_user_message(conversation_id, "第一个问题".to_string()).unwrap(),
+            Message::new_assistant_message(conversation_id, "第一个回答".to_string(), vec![], Some(1.0)).unwrap(),
+            Message::new_user_message(conversation_id, "第二个问提".to_string()).unwrap(),
+            Message::new_assistant_message(conversation_id, "第二个回答".to_string(), vec![], Some(1.0)).unwrap(),
+        ];
+        for (i, message) in messages.iter_mut().enumerate() {
+            message.timestamp = now + chrono::Duration::seconds(i as i64);
+        }
+        let ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+
+        // 只能编辑用户消息，内容不能为空；失败时不修改消息
+        assert!(apply_user_message_edit(&mut messages, ids[1], "改".to_string()).is_err());
+        assert!(apply_user_message_edit(&mut messages, ids[2], "  ".to_string()).is_err());
+        assert_eq!(messages.len(), 4);
+
+        let removed = apply_user_message_edit(&mut messages, ids[2], "第二个问题".to_string()).unwrap();
+        assert_eq!(removed, vec![ids[3]]);
+        assert_eq!(messages.iter().map(|m| m.id).collect::<Vec<_>>(), ids[..3]);
+        assert_eq!(messages[2].content, "第二个问题");
+
+        // 编辑第一个问题会删除其后的所有消息
+        let removed = apply_user_message_edit(&mut messages, ids[0], "新的问题".to_string()).unwrap();
+        assert_eq!(removed, ids[1..3]);
+        assert_eq!(messages.len(), 1);
+
+        // 新回复排在编辑后的问题之后
+        let reply = Message::new_assistant_message(conversation_id, "新的回答".to_string(), vec![], Some(1.0)).unwrap();
+        let reply = append_message(&mut messages, reply);
+        assert!(reply.sequence > messages[0].sequence);
+        assert_eq!(messages.last().unwrap().id, reply.id);
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_edit_keeps_downstream_messages_until_reply_is_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Mutex::new(SeekDbAdapter::new(dir.path().join("edit_message.db")).unwrap()));
+        let mut service = ConversationService::new(db.clone()).await;
+        let conversation_id = service.create_conversation(Uuid::new_v4(), None).await.unwrap();
+        let question = service.add_message(conversation_id, MessageRole::User, "第一个问提".to_string()).await.unwrap();
+        service.add_message(conversation_id, MessageRole::Assistant, "第一个回答".to_string()).await.unwrap();
+
+        // 生成回复前只预览编辑结果，原对话不变
+        let history = service.preview_user_message_edit(conversation_id, question, "第一个问题".to_string()).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(service.messages[&conversation_id].len(), 2);
+
+        let reply = Message::new_assistant_message(conversation_id, "新的回答".to_string(), vec![], Some(0.5)).unwrap();
+        let reply_id = service.edit_user_message(conversation_id, question, "第一个问题".to_string(), reply).await.unwrap();
+
+        // 重启后从数据库读到编辑后的问题和新回复
+        let mut service = ConversationService::new(db).await;
+        let (messages, total) = service.get_conversation_messages(conversation_id, None, None).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(messages[0].content, "第一个问题");
+        assert_eq!(messages[1].id, reply_id);
+    }
+
+    #[test]
+    fn test_trimming_keeps_most_recent_messages_in_order() {
+        let conversation_id = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let mut messages: Vec<Message> = (0..20)
+            .map(|i| {
+                let mut message = Message::new_user_message(conversation_id, format!("消息 {}", i)).unwrap();
+                message.timestamp = now + chrono::Duration::seconds(i);
+                message
+            })
+            .collect();
+        let ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+        // 内存中的顺序不一定是显示顺序
+        messages.reverse();
+
+        let removed = trim_to_last(&mut messages, 5);
+        assert_eq!(removed, ids[..15]);
+        assert_eq!(messages.iter().map(|m| m.id).collect::<Vec<_>>(), ids[15..]);
+        assert_eq!(messages[0].content, "消息 15");
+
+        // 保留数量不少于消息数时不删除
+        assert!(trim_to_last(&mut messages, 10).is_empty());
+        assert_eq!(messages.len(), 5);
+    }
+
+    #[test]
+    fn test_search_finds_matching_message_within_conversation() {
+        let conversation_id = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let contents = ["如何配置 SeekDB？", "在 config.json 中设置路径即可", "谢谢", "seekdb 支持全文索引吗"];
+        let messages: Vec<Message> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| {
+                let mut message = Message::new_user_message(conversation_id, content.to_string()).unwrap();
+                message.timestamp = now + chrono::Duration::seconds(i as i64);
+                message
+            })
+            .collect();
+
+        let hits = search_messages(&messages, "SeekDB");
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].message_id, messages[0].id.to_string());
+        assert_eq!(hits[0].position, 0);
+        // 高亮按字符计算，不受前面中文字符的字节长度影响
+        assert_eq!(hits[0].highlights, vec![HighlightRange { start: 5, end: 11 }]);
+        assert_eq!((hits[1].message_id.clone(), hits[1].position), (messages[3].id.to_string(), 3));
+
+        assert_eq!(search_messages(&messages, "config")[0].message_id, messages[1].id.to_string());
+        assert!(search_messages(&messages, "不存在").is_empty());
+        assert!(search_messages(&messages, "  ").is_empty());
+    }
+
+    #[test]
+    fn test_last_message_preview_reflects_latest_message() {
+        let conversation_id = Uuid::new_v4();
+        assert!(latest_message(&[]).is_none());
+
+        let mut first = Message::new_user_message(conversation_id, "第一个问题".to_string()).unwrap();
+        let mut latest = Message::new_assistant_message(conversation_id, "这是最新的回答".to_string(), vec![], None).unwrap();
+        first.timestamp = chrono::Utc::now() - chrono::Duration::seconds(10);
+        latest.timestamp = chrono::Utc::now();
+
+        // 存储顺序不代表时间顺序
+        let messages = vec![latest.clone(), first];
+        let last = latest_message(&messages).unwrap();
+        assert_eq!(last.id, latest.id);
+        assert_eq!(last.preview(LAST_MESSAGE_PREVIEW_CHARS), "这是最新的回答");
+        assert_eq!(last.role, MessageRole::Assistant);
+    }
+
+    #[test]
+    fn test_same_second_messages_keep_insertion_order_after_repair() {
+        let conversation_id = Uuid::new_v4();
+        // 旧数据：时间戳只精确到秒，且没有序号
+        let same_second = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut question = Message::new_user_message(conversation_id, "问题".to_string()).unwrap();
+        let mut answer = Message::new_assistant_message(conversation_id, "回答".to_string(), vec![], None).unwrap();
+        question.timestamp = same_second;
+        answer.timestamp = same_second;
+
+        let mut messages = vec![question.clone(), answer.clone()];
+        let changed = assign_sequences(&mut messages);
+        assert_eq!(changed, vec![question.id, answer.id]);
+
+        // 无论加载顺序如何，排序后都保持插入顺序
+        messages.reverse();
+        messages.sort_by_key(|m| m.order_key());
+        let ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![question.id, answer.id]);
+        assert_eq!(latest_message(&messages).unwrap().id, answer.id);
+        assert_eq!(next_sequence(&messages), 3);
+
+        // 再次修复不会产生变化
+        assert!(assign_sequences(&mut messages).is_empty());
+    }
+
+    #[test]
+    fn test_history_pages_walk_back_from_newest() {
+        // 默认取最新的一页，再按已加载数量向前翻页，最后一页不足一页
+        assert_eq!(latest_page_range(120, 0, 50), 70..120);
+        assert_eq!(latest_page_range(120, 50, 50), 20..70);
+        assert_eq!(latest_page_range(120, 100, 50), 0..20);
+        assert!(latest_page_range(120, 120, 50).is_empty());
+        assert!(latest_page_range(120, 500, 50).is_empty());
+        assert_eq!(latest_page_range(3, 0, 50), 0..3);
+    }
+
+    #[test]
+    fn test_compression_replaces_old_turns_with_summary() {
+        let conversation_id = Uuid::new_v4();
+        let base = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // 5 轮问答，共 10 条消息
+        let mut messages: Vec<Message> = (0..10)
+            .map(|i| {
+                let mut message = if i % 2 == 0 {
+                    Message::new_user_message(conversation_id, format!("问题{}", i / 2)).unwrap()
+                } else {
+                    Message::new_assistant_message(conversation_id, format!("回答{}", i / 2), vec![], None).unwrap()
+                };
+                message.timestamp = base + chrono::Duration::seconds(i);
+                message
+            })
+            .collect();
+        assign_sequences(&mut messages);
+
+        let to_compress = messages_to_compress(&messages, 2);
+        assert_eq!(to_compress.len(), 6);
+        assert!(compression_transcript(&to_compress).starts_with("用户：问题0\n\n助手：回答0"));
+        // 保留轮数不少于总轮数时没有可压缩的消息
+        assert!(messages_to_compress(&messages, 5).is_empty());
+
+        let compressed: Vec<Uuid> = to_compress.iter().map(|m| m.id).collect();
+        let summary = Message::new_system_message(
+            conversation_id,
+            compressed_summary_content(&"用户询问了部署步骤。".repeat(1000)),
+        )
+        .unwrap();
+        let changed = apply_compression(&mut messages, &compressed, summary.clone());
+
+        assert_eq!(messages.len(), 5);
+        assert!(changed.contains(&summary.id));
+        let first = &messages[0];
+        assert_eq!(first.id, summary.id);
+        assert_eq!(first.role, MessageRole::System);
+        assert!(first.content.starts_with(COMPRESSED_SUMMARY_PREFIX));
+        assert!(first.content.len() <= 10000);
+        // 最近 2 轮原样保留，顺序不变
+        let kept: Vec<&str> = messages[1..].iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(kept, vec!["问题3", "回答3", "问题4", "回答4"]);
+        assert_eq!(messages.iter().map(|m| m.sequence).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_latest_page_is_read_without_loading_the_whole_conversation() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Mutex::new(SeekDbAdapter::new(dir.path().join("lazy_messages.db")).unwrap()));
+        let mut service = ConversationService::new(db.clone()).await;
+        let conversation_id = service.create_conversation(Uuid::new_v4(), None).await.unwrap();
+        for i in 0..30 {
+            service.add_message(conversation_id, MessageRole::User, format!("消息 {}", i)).await.unwrap();
+        }
+
+        // 重启后只有最新一条消息在内存中
+        let mut service = ConversationService::new(db).await;
+        assert!(service.messages.is_empty());
+        assert_eq!(service.get_last_message(conversation_id).unwrap().content, "消息 29");
+
+        let (page, total) = service.get_conversation_messages(conversation_id, Some(10), Some(5)).await.unwrap();
+        let contents: Vec<&str> = page.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["消息 15", "消息 16", "消息 17", "消息 18", "消息 19"]);
+        assert_eq!(total, 30);
+        assert!(service.messages.is_empty());
+
+        // 需要全部消息的操作在第一次访问时加载
+        assert_eq!(service.get_conversation_stats(conversation_id).await.unwrap().total_messages, 30);
+        assert_eq!(service.messages[&conversation_id].len(), 30);
+    }
+
     #[test]
     fn test_conversation_service_creation() {
         let service = ConversationService::new();
@@ -1,8 +1,27 @@
+use crate::services::embedding_batcher::MicroBatcher;
+use crate::services::embedding_cache::PersistentEmbeddingCache;
+use crate::services::embedding_provider::{EmbeddingBatch, EmbeddingProvider};
+use crate::services::generation_retry::RetryPolicy;
 use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::ops::Range;
 use std::time::Duration;
 
+/// DashScope 的默认 embedding 模型
+pub const DEFAULT_DASHSCOPE_EMBEDDING_MODEL: &str = "text-embedding-v2";
+
+/// DashScope API 单次请求最多支持的文本数量
+const MAX_BATCH_TEXTS: usize = 25;
+
+/// 单次请求的默认最大文本字节数（所有文本 UTF-8 字节之和）
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// text-embedding-v2 输出的向量维度，也是未配置 `embedding.dimension` 时的默认维度
+pub const DEFAULT_EMBEDDING_DIMENSION: usize = 1536;
+
 /// 阿里云百炼 Embedding 服务
 /// 文档：https://help.aliyun.com/zh/dashscope/developer-reference/text-embedding-api-details
 pub struct DashScopeEmbeddingService {
@@ -10,12 +29,26 @@ pub struct DashScopeEmbeddingService {
     api_key: String,
     base_url: String,
     model: String,
+    dimension: usize,
+    max_batch_bytes: usize,
+    micro_batcher: Option<MicroBatcher>,
+    cache: Option<std::sync::Mutex<PersistentEmbeddingCache>>,
+    ingestion_retry: RetryPolicy,
+    query_retry: RetryPolicy,
 }
 
 #[derive(Debug, Serialize)]
 struct EmbeddingRequest {
     model: String,
     input: EmbeddingInput,
+    /// 只在使用非默认维度时发送（text-embedding-v3 支持 1024/768/512 等维度）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<EmbeddingParameters>,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingParameters {
+    dimension: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,9 +68,9 @@ struct EmbeddingOutput {
 }
 
 #[derive(Debug, Deserialize)]
-struct EmbeddingItem {
-    text_index: usize,
-    embedding: Vec<f64>,
+pub(crate) struct EmbeddingItem {
+    pub(crate) text_index: usize,
+    pub(crate) embedding: Vec<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,42 +97,176 @@ impl DashScopeEmbeddingService {
         });
 
         log::info!("  - Base URL: {}", base_url);
-        log::info!("  - 模型: text-embedding-v2");
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()?;
+        // 超时按请求设置，见 RetryPolicy
+        let client = Client::builder().build()?;
 
         Ok(Self {
             client,
             api_key,
             base_url,
-            model: "text-embedding-v2".to_string(),
+            model: DEFAULT_DASHSCOPE_EMBEDDING_MODEL.to_string(),
+            dimension: DEFAULT_EMBEDDING_DIMENSION,
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+            micro_batcher: None,
+            cache: None,
+            ingestion_retry: RetryPolicy::embedding_ingestion(),
+            query_retry: RetryPolicy::embedding_query(),
         })
     }
 
-    /// 生成单个文本的 embedding
+    /// 设置 embedding 模型（默认 text-embedding-v2）
+    pub fn with_model(mut self, model: String) -> Self {
+        log::info!("  - 模型: {}", model);
+        self.model = model;
+        self
+    }
+
+    /// 设置输出向量的维度（需与数据库 vector 列的维度一致）
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        log::info!("  - 向量维度: {}", dimension);
+        self.dimension = dimension;
+        self
+    }
+
+    /// 设置单次请求的最大文本字节数
+    pub fn with_max_batch_bytes(mut self, max_batch_bytes: usize) -> Self {
+        log::info!("  - 单次请求最大字节数: {}", max_batch_bytes);
+        self.max_batch_bytes = max_batch_bytes.max(1);
+        self
+    }
+
+    /// 分别设置文档摄取（embed_batch）和对话查询（embed_text）的重试策略
+    pub fn with_retry_policies(mut self, ingestion: RetryPolicy, query: RetryPolicy) -> Self {
+        log::info!("  - 摄取重试策略: {:?}", ingestion);
+        log::info!("  - 查询重试策略: {:?}", query);
+        self.ingestion_retry = ingestion;
+        self.query_retry = query;
+        self
+    }
+
+    /// 开启单文本请求的微批处理：窗口内到达的 embed_text 请求合并为一次 API 调用
+    pub fn with_micro_batch_window(mut self, window: Duration) -> Self {
+        log::info!("  - 微批处理窗口: {:?}", window);
+        self.micro_batcher = Some(MicroBatcher::new(window));
+        self
+    }
+
+    /// 开启持久化缓存：已计算过的文本直接从缓存文件读取，重启后仍然有效
+    pub fn with_persistent_cache(mut self, cache: PersistentEmbeddingCache) -> Self {
+        log::info!("  - 持久化 Embedding 缓存: {} 条", cache.len());
+        self.cache = Some(std::sync::Mutex::new(cache));
+        self
+    }
+
+    /// 清空持久化缓存，返回删除的条目数；未开启缓存时返回 0
+    pub fn clear_cache(&self) -> Result<usize> {
+        match &self.cache {
+            Some(cache) => cache.lock().unwrap().clear(),
+            None => Ok(0),
+        }
+    }
+
+    /// 生成单个文本的 embedding（查询路径，使用查询重试策略）
     pub async fn embed_text(&self, text: &str) -> Result<Vec<f64>> {
-        let embeddings = self.embed_batch(&[text.to_string()]).await?;
-        embeddings.into_iter().next()
+        if let Some(batcher) = &self.micro_batcher {
+            return batcher
+                .submit(text.to_string(), |texts| async move {
+                    Ok(self.embed_with_policy(&texts, &self.query_retry).await?.embeddings)
+                })
+                .await;
+        }
+
+        let batch = self.embed_with_policy(&[text.to_string()], &self.query_retry).await?;
+        batch.embeddings.into_iter().next()
             .ok_or_else(|| anyhow!("生成 embedding 失败"))
     }
 
     /// 批量生成 embeddings（推荐，效率更高）
-    /// 注意：DashScope API 每次最多支持 25 个文本
-    /// 自动重试：遇到临时错误会自动重试最多3次，使用指数退避策略
+    /// 注意：DashScope API 每次最多支持 25 个文本，且单次请求总大小有限制，
+    /// 超出任一限制时会自动拆分为多次请求
+    /// 自动重试：遇到临时错误时按摄取重试策略（默认最多 3 次）指数退避重试
     pub async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+        Ok(self.embed_batch_with_usage(texts).await?.embeddings)
+    }
+
+    /// 批量生成 embeddings，同时返回 API 报告的 token 消耗
+    pub async fn embed_batch_with_usage(&self, texts: &[String]) -> Result<EmbeddingBatch> {
+        self.embed_with_policy(texts, &self.ingestion_retry).await
+    }
+
+    async fn embed_with_policy(&self, texts: &[String], policy: &RetryPolicy) -> Result<EmbeddingBatch> {
+        let Some(cache) = &self.cache else {
+            return self.embed_batch_uncached(texts, policy).await;
+        };
+
+        let keys: Vec<String> = texts.iter().map(|t| PersistentEmbeddingCache::key(&self.model, t)).collect();
+        let mut results: Vec<Option<Vec<f64>>> = {
+            let mut cache = cache.lock().unwrap();
+            // 维度变化前缓存的向量不再可用
+            keys.iter()
+                .map(|key| cache.get(key).filter(|embedding| embedding.len() == self.dimension))
+                .collect()
+        };
+
+        // 未命中的文本按缓存键去重，相同内容只请求一次
+        let mut missing: Vec<usize> = Vec::new();
+        for i in (0..texts.len()).filter(|&i| results[i].is_none()) {
+            if !missing.iter().any(|&j| keys[j] == keys[i]) {
+                missing.push(i);
+            }
+        }
+        let mut total_tokens = 0;
+        if !missing.is_empty() {
+            log::debug!("💾 Embedding 缓存命中 {}/{}", results.iter().filter(|r| r.is_some()).count(), texts.len());
+            let missing_texts: Vec<String> = missing.iter().map(|&i| texts[i].clone()).collect();
+            let batch = self.embed_batch_uncached(&missing_texts, policy).await?;
+            total_tokens = batch.total_tokens;
+
+            let mut cache = cache.lock().unwrap();
+            for (&i, embedding) in missing.iter().zip(batch.embeddings) {
+                // 缓存写入失败不影响本次结果
+                if let Err(e) = cache.insert(keys[i].clone(), embedding.clone()) {
+                    log::warn!("⚠️  写入 Embedding 缓存失败: {}", e);
+                }
+                for (result, key) in results.iter_mut().zip(&keys) {
+                    if result.is_none() && *key == keys[i] {
+                        *result = Some(embedding.clone());
+                    }
+                }
+            }
+        }
+
+        let embeddings = results
+            .into_iter()
+            .map(|embedding| embedding.ok_or_else(|| anyhow!("生成 embedding 失败")))
+            .collect::<Result<_>>()?;
+        Ok(EmbeddingBatch { embeddings, total_tokens })
+    }
+
+    /// 绕过持久化缓存和微批处理直接调用 API（用于测量 API 延迟）
+    pub async fn embed_text_uncached(&self, text: &str) -> Result<Vec<f64>> {
+        self.embed_batch_uncached(&[text.to_string()], &self.query_retry).await?
+            .embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("生成 embedding 失败"))
+    }
+
+    async fn embed_batch_uncached(&self, texts: &[String], policy: &RetryPolicy) -> Result<EmbeddingBatch> {
         if texts.is_empty() {
-            return Ok(Vec::new());
+            return Ok(EmbeddingBatch::default());
         }
 
-        // 如果文本数量超过 25 个，分批处理
-        if texts.len() > 25 {
-            return self.embed_batch_chunked(texts, 25).await;
+        let batches = plan_batches(texts, MAX_BATCH_TEXTS, self.max_batch_bytes);
+
+        // 需要拆分时分批处理
+        if batches.len() > 1 {
+            return self.embed_batch_chunked(texts, &batches, policy).await;
         }
 
         // 使用重试机制调用 API
-        self.embed_batch_with_retry(texts, 3).await
+        self.embed_batch_with_retry(texts, policy).await
     }
 
     /// 带重试机制的批量生成 embeddings
@@ -107,64 +274,15 @@ impl DashScopeEmbeddingService {
     async fn embed_batch_with_retry(
         &self,
         texts: &[String],
-        max_retries: u32,
-    ) -> Result<Vec<Vec<f64>>> {
-        let mut retries = 0;
-        let mut delay = Duration::from_millis(1000); // 初始延迟 1 秒
-
-        loop {
-            log::debug!(
-                "🔄 调用 DashScope API 生成 {} 个 embeddings (尝试 {}/{})",
-                texts.len(),
-                retries + 1,
-                max_retries + 1
-            );
-
-            match self.embed_batch_internal(texts).await {
-                Ok(result) => {
-                    if retries > 0 {
-                        log::info!("✅ 重试成功！第 {} 次尝试成功", retries + 1);
-                    }
-                    return Ok(result);
-                }
-                Err(e) => {
-                    let is_retryable = Self::is_retryable_error(&e);
-
-                    if retries < max_retries && is_retryable {
-                        log::warn!(
-                            "⚠️  Embedding API 调用失败 (第 {}/{} 次)，{}ms 后重试: {}",
-                            retries + 1,
-                            max_retries,
-                            delay.as_millis(),
-                            e
-                        );
-
-                        tokio::time::sleep(delay).await;
-
-                        // 指数退避：每次延迟翻倍，最大 30 秒
-                        delay = std::cmp::min(delay * 2, Duration::from_secs(30));
-                        retries += 1;
-                    } else {
-                        if !is_retryable {
-                            log::error!("❌ 不可重试的错误: {}", e);
-                        } else {
-                            log::error!("❌ 达到最大重试次数 ({}次)，放弃重试", max_retries);
-                        }
-                        return Err(e);
-                    }
-                }
-            }
-        }
+        policy: &RetryPolicy,
+    ) -> Result<EmbeddingBatch> {
+        log::debug!("🔄 调用 DashScope API 生成 {} 个 embeddings", texts.len());
+        call_with_retry(policy, || self.embed_batch_internal(texts, policy.timeout)).await
     }
 
     /// 内部方法：实际调用 API（不包含重试逻辑）
-    async fn embed_batch_internal(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
-        let request_body = EmbeddingRequest {
-            model: self.model.clone(),
-            input: EmbeddingInput {
-                texts: texts.to_vec(),
-            },
-        };
+    async fn embed_batch_internal(&self, texts: &[String], timeout: Duration) -> Result<EmbeddingBatch> {
+        let request_body = self.build_request(texts);
 
         let url = format!("{}/services/embeddings/text-embedding/text-embedding", self.base_url);
 
@@ -172,6 +290,7 @@ impl DashScopeEmbeddingService {
             .post(&url)
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
+            .timeout(timeout)
             .json(&request_body)
             .send()
             .await?;
@@ -180,7 +299,7 @@ impl DashScopeEmbeddingService {
 
         if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!("DashScope API 调用失败 [{}]: {}", status, error_text));
+            return Err(anyhow!("DashScope API 调用失败 [{}]: {}", status.as_u16(), error_text));
         }
 
         let result: EmbeddingResponse = response.json().await?;
@@ -190,67 +309,67 @@ impl DashScopeEmbeddingService {
             result.usage.total_tokens
         );
 
-        // 按 text_index 排序并提取 embedding
-        let mut embeddings: Vec<_> = result.output.embeddings;
-        embeddings.sort_by_key(|e| e.text_index);
-
-        Ok(embeddings.into_iter().map(|e| e.embedding).collect())
-    }
-
-    /// 判断错误是否可重试
-    /// 可重试的错误包括：网络超时、429限流、5xx服务器错误
-    fn is_retryable_error(error: &anyhow::Error) -> bool {
-        let error_str = error.to_string().to_lowercase();
-
-        // 网络相关错误
-        if error_str.contains("timeout")
-            || error_str.contains("connection")
-            || error_str.contains("network") {
-            return true;
+        let embeddings = align_embeddings(result.output.embeddings, texts.len())?;
+        if let Some(embedding) = embeddings.iter().find(|e| e.len() != self.dimension) {
+            return Err(anyhow!(
+                "Embedding 维度不一致: 模型 {} 返回 {} 维向量，但配置的 embedding.dimension 为 {}",
+                self.model,
+                embedding.len(),
+                self.dimension
+            ));
         }
+        Ok(EmbeddingBatch { embeddings, total_tokens: result.usage.total_tokens })
+    }
 
-        // HTTP 状态码相关
-        if error_str.contains("[429]")  // 限流
-            || error_str.contains("[500]")  // 服务器内部错误
-            || error_str.contains("[502]")  // 网关错误
-            || error_str.contains("[503]")  // 服务不可用
-            || error_str.contains("[504]") {  // 网关超时
-            return true;
+    fn build_request(&self, texts: &[String]) -> EmbeddingRequest {
+        EmbeddingRequest {
+            model: self.model.clone(),
+            input: EmbeddingInput {
+                texts: texts.to_vec(),
+            },
+            parameters: (self.dimension != DEFAULT_EMBEDDING_DIMENSION)
+                .then_some(EmbeddingParameters { dimension: self.dimension }),
         }
-
-        false
     }
 
-    /// 分块批量处理（当文本数量超过 API 限制时）
+    /// 分块批量处理（当文本数量或大小超过 API 限制时）
     /// 每个分块都会使用重试机制
-    async fn embed_batch_chunked(&self, texts: &[String], chunk_size: usize) -> Result<Vec<Vec<f64>>> {
-        log::debug!("📦 分 {} 批处理 {} 个文本",
-            (texts.len() + chunk_size - 1) / chunk_size,
-            texts.len()
-        );
+    async fn embed_batch_chunked(
+        &self,
+        texts: &[String],
+        batches: &[Range<usize>],
+        policy: &RetryPolicy,
+    ) -> Result<EmbeddingBatch> {
+        log::debug!("📦 分 {} 批处理 {} 个文本", batches.len(), texts.len());
 
-        let mut all_embeddings = Vec::new();
+        let mut all = EmbeddingBatch::default();
 
-        for (i, chunk) in texts.chunks(chunk_size).enumerate() {
+        for (i, range) in batches.iter().enumerate() {
+            let chunk = &texts[range.clone()];
             log::debug!("处理第 {}/{} 批 ({} 个文本)",
                 i + 1,
-                (texts.len() + chunk_size - 1) / chunk_size,
+                batches.len(),
                 chunk.len()
             );
 
             // 每个分块都使用重试机制
-            let chunk_embeddings = self.embed_batch_with_retry(chunk, 3).await?;
-            all_embeddings.extend(chunk_embeddings);
+            let batch = self.embed_batch_with_retry(chunk, policy).await?;
+            all.embeddings.extend(batch.embeddings);
+            all.total_tokens += batch.total_tokens;
         }
 
-        Ok(all_embeddings)
+        Ok(all)
+    }
+
+    /// 使用的 embedding 模型
+    pub fn model(&self) -> &str {
+        &self.model
     }
 
     /// 获取 embedding 维度
-    /// text-embedding-v2: 1536 维
-    /// text-embedding-v1: 1536 维
+    /// text-embedding-v2 / v1: 1536 维，其他维度通过 `embedding.dimension` 配置
     pub fn embedding_dim(&self) -> usize {
-        1536
+        self.dimension
     }
 
     /// 获取 base URL（自动检测国内/国际）
@@ -261,9 +380,303 @@ impl DashScopeEmbeddingService {
     }
 }
 
+impl EmbeddingProvider for DashScopeEmbeddingService {
+    fn embed_text<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(DashScopeEmbeddingService::embed_text(self, text))
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f64>>>> {
+        Box::pin(DashScopeEmbeddingService::embed_batch(self, texts))
+    }
+
+    fn embed_batch_with_usage<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<EmbeddingBatch>> {
+        Box::pin(DashScopeEmbeddingService::embed_batch_with_usage(self, texts))
+    }
+
+    fn embed_text_uncached<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(DashScopeEmbeddingService::embed_text_uncached(self, text))
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.dimension
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn clear_cache(&self) -> Result<usize> {
+        DashScopeEmbeddingService::clear_cache(self)
+    }
+}
+
+/// 按重试策略调用 embedding API：遇到临时错误时指数退避重试，不可重试的错误立即返回
+pub(crate) async fn call_with_retry<T, F, Fut>(policy: &RetryPolicy, mut call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let max_retries = policy.max_retries;
+    let mut retries = 0;
+
+    loop {
+        match call().await {
+            Ok(result) => {
+                if retries > 0 {
+                    log::info!("✅ 重试成功！第 {} 次尝试成功", retries + 1);
+                }
+                return Ok(result);
+            }
+            Err(e) => {
+                let is_retryable = is_retryable_error(&e);
+
+                if retries < max_retries && is_retryable {
+                    let delay = policy.backoff(retries);
+                    log::warn!(
+                        "⚠️  Embedding API 调用失败 (第 {}/{} 次)，{}ms 后重试: {}",
+                        retries + 1,
+                        max_retries,
+                        delay.as_millis(),
+                        e
+                    );
+
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                } else {
+                    if !is_retryable {
+                        log::error!("❌ 不可重试的错误: {}", e);
+                    } else {
+                        log::error!("❌ 达到最大重试次数 ({}次)，放弃重试", max_retries);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// 判断错误是否可重试
+/// 可重试的错误包括：网络超时、429限流、5xx服务器错误
+pub(crate) fn is_retryable_error(error: &anyhow::Error) -> bool {
+    let error_str = error.to_string().to_lowercase();
+
+    // 网络相关错误
+    if error_str.contains("timeout")
+        || error_str.contains("connection")
+        || error_str.contains("network") {
+        return true;
+    }
+
+    // 返回的 embeddings 与输入不对应（部分响应）
+    if error_str.contains(INCOMPLETE_RESPONSE_ERROR) {
+        return true;
+    }
+
+    // HTTP 状态码相关
+    if error_str.contains("[429]")  // 限流
+        || error_str.contains("[500]")  // 服务器内部错误
+        || error_str.contains("[502]")  // 网关错误
+        || error_str.contains("[503]")  // 服务不可用
+        || error_str.contains("[504]") {  // 网关超时
+        return true;
+    }
+
+    false
+}
+
+/// 返回的 embeddings 与输入文本不一一对应时的错误信息前缀（可重试）
+const INCOMPLETE_RESPONSE_ERROR: &str = "embedding 响应不完整";
+
+/// 按 text_index 把 embeddings 与输入文本对齐
+///
+/// 要求每个输入文本恰好有一个 embedding：数量不符、索引越界或重复都视为部分响应并报错，
+/// 避免按顺序拼接时丢失分块或把向量对应到错误的内容上。
+pub(crate) fn align_embeddings(items: Vec<EmbeddingItem>, expected: usize) -> Result<Vec<Vec<f64>>> {
+    if items.len() != expected {
+        return Err(anyhow!(
+            "{}: 发送 {} 个文本，返回 {} 个 embedding",
+            INCOMPLETE_RESPONSE_ERROR,
+            expected,
+            items.len()
+        ));
+    }
+
+    let mut aligned: Vec<Option<Vec<f64>>> = vec![None; expected];
+    for item in items {
+        match aligned.get_mut(item.text_index) {
+            Some(slot @ None) => *slot = Some(item.embedding),
+            Some(Some(_)) => {
+                return Err(anyhow!("{}: text_index {} 重复", INCOMPLETE_RESPONSE_ERROR, item.text_index));
+            }
+            None => {
+                return Err(anyhow!(
+                    "{}: text_index {} 超出输入范围 (共 {} 个文本)",
+                    INCOMPLETE_RESPONSE_ERROR,
+                    item.text_index,
+                    expected
+                ));
+            }
+        }
+    }
+
+    // 数量相同且没有重复/越界时每个位置都已填充
+    Ok(aligned.into_iter().flatten().collect())
+}
+
+/// 将文本按顺序打包为多个请求批次，每批同时满足数量上限和字节上限
+///
+/// 单个文本本身超过字节上限时单独成批（由 API 自行截断或报错）。
+pub(crate) fn plan_batches(texts: &[String], max_count: usize, max_bytes: usize) -> Vec<Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+
+    for (i, text) in texts.iter().enumerate() {
+        let len = text.len();
+        let count = i - start;
+        if count > 0 && (count >= max_count || bytes + len > max_bytes) {
+            batches.push(start..i);
+            start = i;
+            bytes = 0;
+        }
+        bytes += len;
+    }
+
+    if start < texts.len() {
+        batches.push(start..texts.len());
+    }
+
+    batches
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::EmbeddingRetryConfig;
+
+    #[tokio::test]
+    async fn test_cached_embedding_is_reused_after_restart() {
+        use crate::services::embedding_cache::EMBEDDING_CACHE_FILE;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EMBEDDING_CACHE_FILE);
+        let ttl = Duration::from_secs(3600);
+        let text = "SeekDB 支持混合检索".to_string();
+
+        // 上一次运行中计算过的向量
+        {
+            let mut cache = PersistentEmbeddingCache::open(&path, ttl, 100).unwrap();
+            cache.insert(PersistentEmbeddingCache::key("text-embedding-v2", &text), vec![0.5, 0.25]).unwrap();
+        }
+
+        // 重启后：API 地址不可达，命中缓存时不会发起请求
+        let service = DashScopeEmbeddingService::new("test-key".to_string(), Some("http://127.0.0.1:9".to_string()))
+            .unwrap()
+            .with_dimension(2)
+            .with_persistent_cache(PersistentEmbeddingCache::open(&path, ttl, 100).unwrap());
+
+        assert_eq!(service.embed_text(&text).await.unwrap(), vec![0.5, 0.25]);
+
+        // 清空缓存同时清空缓存文件
+        assert_eq!(EmbeddingProvider::clear_cache(&service).unwrap(), 1);
+        assert!(PersistentEmbeddingCache::open(&path, ttl, 100).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_query_path_fails_faster_than_ingestion_path() {
+        let ingestion = RetryPolicy::embedding_ingestion();
+        let query = RetryPolicy::embedding_query();
+        let total_backoff = |policy: &RetryPolicy| (0..policy.max_retries).map(|retry| policy.backoff(retry)).sum::<Duration>();
+        assert!(query.max_retries < ingestion.max_retries);
+        assert!(total_backoff(&query) < total_backoff(&ingestion));
+        assert!(query.timeout < ingestion.timeout);
+        assert_eq!(ingestion.backoff(10), ingestion.max_backoff);
+
+        // 未配置的项保留默认值
+        let overridden = RetryPolicy::embedding_query().with_overrides(Some(&EmbeddingRetryConfig {
+            max_retries: Some(0),
+            ..Default::default()
+        }));
+        assert_eq!(overridden, RetryPolicy { max_retries: 0, ..RetryPolicy::embedding_query() });
+
+        // 本地模拟服务收到请求后直接断开连接（可重试）：查询路径不重试，摄取路径重试两次
+        let (base_url, requests) = dropping_server().await;
+        let service = DashScopeEmbeddingService::new("test-key".to_string(), Some(base_url))
+            .unwrap()
+            .with_retry_policies(
+                RetryPolicy { max_retries: 2, initial_backoff: Duration::from_millis(1), ..RetryPolicy::embedding_ingestion() },
+                overridden,
+            );
+
+        assert!(service.embed_text("查询").await.is_err());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        assert!(service.embed_batch(&["文档".to_string()]).await.is_err());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1 + 3);
+    }
+
+    /// 读取请求后不回复、直接断开连接的本地服务，返回地址和已收到的请求数
+    async fn dropping_server() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 64 * 1024];
+                let _ = socket.read(&mut buf).await.unwrap();
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+        (base_url, requests)
+    }
+
+    #[test]
+    fn test_large_texts_are_split_by_byte_limit() {
+        // 10 个 30KB 的文本：数量远低于 25，但总大小超过 64KB
+        let texts: Vec<String> = (0..10).map(|_| "x".repeat(30 * 1024)).collect();
+        let batches = plan_batches(&texts, MAX_BATCH_TEXTS, DEFAULT_MAX_BATCH_BYTES);
+
+        assert!(batches.len() > 1);
+        for range in &batches {
+            let bytes: usize = texts[range.clone()].iter().map(|t| t.len()).sum();
+            assert!(bytes <= DEFAULT_MAX_BATCH_BYTES);
+        }
+        // 所有文本按顺序覆盖且不重复
+        let flattened: Vec<usize> = batches.into_iter().flatten().collect();
+        assert_eq!(flattened, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_partial_embedding_response_is_rejected() {
+        let item = |text_index: usize| EmbeddingItem { text_index, embedding: vec![text_index as f64] };
+
+        // 乱序返回时按 text_index 对齐
+        let aligned = align_embeddings(vec![item(2), item(0), item(1)], 3).unwrap();
+        assert_eq!(aligned, vec![vec![0.0], vec![1.0], vec![2.0]]);
+
+        // 少返回一个 embedding：报错而不是截断，并且会触发重试
+        let error = align_embeddings(vec![item(0), item(2)], 3).unwrap_err();
+        assert!(error.to_string().contains("发送 3 个文本，返回 2 个"));
+        assert!(is_retryable_error(&error));
+
+        // 数量相同但索引不覆盖所有输入
+        assert!(align_embeddings(vec![item(0), item(0), item(1)], 3).is_err());
+        assert!(align_embeddings(vec![item(0), item(1), item(3)], 3).is_err());
+    }
+
+    #[test]
+    fn test_batches_respect_count_cap_and_oversized_texts() {
+        let small: Vec<String> = (0..60).map(|i| format!("text {}", i)).collect();
+        let batches = plan_batches(&small, MAX_BATCH_TEXTS, DEFAULT_MAX_BATCH_BYTES);
+        assert_eq!(batches, vec![0..25, 25..50, 50..60]);
+
+        let oversized = vec!["a".repeat(100), "b".repeat(10), "c".repeat(10)];
+        assert_eq!(plan_batches(&oversized, MAX_BATCH_TEXTS, 50), vec![0..1, 1..3]);
+    }
 
     #[tokio::test]
     #[ignore] // 需要 API Key
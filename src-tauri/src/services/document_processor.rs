@@ -1,18 +1,37 @@
 use crate::models::document::{Document, DocumentChunk};
 use anyhow::{anyhow, Result};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::OnceLock;
 use uuid::Uuid;
 
+/// 块 metadata 中表示块类型的键
+pub const CHUNK_TYPE_KEY: &str = "type";
+/// 图片说明块的类型值
+pub const CHUNK_TYPE_CAPTION: &str = "caption";
+/// 块 metadata 中表示所属章节（最近的标题）的键
+pub const CHUNK_SECTION_KEY: &str = "section";
+/// 块 metadata 中表示章节路径（从顶层到所属章节的标题，按结构分块时填充）的键
+pub const CHUNK_SECTION_PATH_KEY: &str = "section_path";
+/// 块 metadata 中记录源文件大小（字节）的键，重启后按它计算项目的存储配额
+pub const CHUNK_FILE_SIZE_KEY: &str = "file_size";
+/// 块 metadata 中记录文档总块数的键；数据库中的块数少于它说明摄取中途失败
+pub const CHUNK_TOTAL_KEY: &str = "chunk_total";
+
 #[derive(Debug, Clone)]
 pub struct DocumentProcessor {
     max_chunk_size: usize,
     chunk_overlap: usize,
+    extract_captions: bool,
+    section_metadata: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct ProcessingResult {
     pub chunks: Vec<DocumentChunk>,
+    /// 图片说明块（仅在开启说明提取时生成），chunk_index 接在正文块之后
+    pub caption_chunks: Vec<DocumentChunk>,
     pub total_tokens: u32,
     pub processing_time: f64,
 }
@@ -22,6 +41,8 @@ impl DocumentProcessor {
         Self {
             max_chunk_size: 1000, // tokens
             chunk_overlap: 100,   // tokens
+            extract_captions: false,
+            section_metadata: false,
         }
     }
 
@@ -29,9 +50,50 @@ impl DocumentProcessor {
         Self {
             max_chunk_size,
             chunk_overlap,
+            extract_captions: false,
+            section_metadata: false,
         }
     }
 
+    /// 开启/关闭图片说明（图注、alt 文本）提取
+    pub fn with_caption_extraction(mut self, enabled: bool) -> Self {
+        self.extract_captions = enabled;
+        self
+    }
+
+    /// 开启/关闭章节元数据：记录每个块之前最近的标题
+    pub fn with_section_metadata(mut self, enabled: bool) -> Self {
+        self.section_metadata = enabled;
+        self
+    }
+
+    pub fn section_metadata(&self) -> bool {
+        self.section_metadata
+    }
+
+    /// 解析单行 Markdown（`## Setup`）或 HTML（`<h2>Setup</h2>`）标题，返回标题文字
+    pub fn parse_heading(line: &str) -> Option<String> {
+        use regex::Regex;
+
+        static MARKDOWN: OnceLock<Regex> = OnceLock::new();
+        static HTML: OnceLock<Regex> = OnceLock::new();
+        static HTML_TAG: OnceLock<Regex> = OnceLock::new();
+        let markdown = MARKDOWN.get_or_init(|| Regex::new(r"^\s{0,3}#{1,6}\s+(.+?)(\s+#+)?\s*$").unwrap());
+        let html = HTML.get_or_init(|| Regex::new(r"(?i)^\s*<h[1-6][^>]*>(.*?)</h[1-6]>").unwrap());
+        let html_tag = HTML_TAG.get_or_init(|| Regex::new(r"<[^>]+>").unwrap());
+
+        let heading = if let Some(cap) = markdown.captures(line) {
+            cap[1].to_string()
+        } else if let Some(cap) = html.captures(line) {
+            html_tag.replace_all(&cap[1], "").to_string()
+        } else {
+            return None;
+        };
+
+        let heading = heading.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!heading.is_empty()).then_some(heading)
+    }
+
     pub async fn process_document(&self, document: &Document) -> Result<ProcessingResult> {
         let start_time = std::time::Instant::now();
 
@@ -41,16 +103,80 @@ impl DocumentProcessor {
         // Create chunks
         let chunks = self.create_chunks(document.id, &content)?;
 
-        let total_tokens: u32 = chunks.iter().map(|chunk| chunk.token_count).sum();
+        // Index figure captions as separate chunks so figures are searchable by caption
+        let caption_chunks = if self.extract_captions {
+            self.create_caption_chunks(document.id, &content, chunks.len() as u32)
+        } else {
+            Vec::new()
+        };
+
+        let total_tokens: u32 = chunks.iter().chain(caption_chunks.iter()).map(|chunk| chunk.token_count).sum();
         let processing_time = start_time.elapsed().as_secs_f64();
 
         Ok(ProcessingResult {
             chunks,
+            caption_chunks,
             total_tokens,
             processing_time,
         })
     }
 
+    /// 读取文档的纯文本内容（不分块）
+    pub async fn extract_text(&self, document: &Document) -> Result<String> {
+        self.read_file_content(&document.file_path, &document.mime_type).await
+    }
+
+    /// 从文本中提取图片说明：
+    /// - 以 "Figure 1" / "Fig. 2" / "图 3" 开头的图注行（PDF、DOCX 中的题注）
+    /// - Markdown 图片的 alt 文本 `![说明](path)`
+    /// - HTML 的 `<figcaption>` 与 `<img alt="...">`
+    pub fn extract_captions(text: &str) -> Vec<String> {
+        use regex::Regex;
+
+        let figure_line = Regex::new(r"(?i)^\s*(figure|fig\.|图)\s*\d+([.\-]\d+)*\s*[.:：、]?\s*\S").unwrap();
+        let markdown_image = Regex::new(r"!\[([^\]]+)\]\([^)]*\)").unwrap();
+        let figcaption = Regex::new(r"(?is)<figcaption[^>]*>(.*?)</figcaption>").unwrap();
+        let img_alt = Regex::new(r#"(?i)<img[^>]*\balt\s*=\s*["']([^"']+)["']"#).unwrap();
+        let html_tag = Regex::new(r"<[^>]+>").unwrap();
+
+        let mut captions: Vec<String> = Vec::new();
+        let mut push = |caption: &str| {
+            let caption = caption.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !caption.is_empty() && !captions.contains(&caption) {
+                captions.push(caption);
+            }
+        };
+
+        for line in text.lines() {
+            if figure_line.is_match(line) {
+                push(line);
+            }
+        }
+        for cap in markdown_image.captures_iter(text) {
+            push(&cap[1]);
+        }
+        for cap in figcaption.captures_iter(text) {
+            push(&html_tag.replace_all(&cap[1], ""));
+        }
+        for cap in img_alt.captures_iter(text) {
+            push(&cap[1]);
+        }
+
+        captions
+    }
+
+    fn create_caption_chunks(&self, document_id: Uuid, content: &str, first_index: u32) -> Vec<DocumentChunk> {
+        Self::extract_captions(content)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, caption)| {
+                let start = content.find(&caption).unwrap_or(0);
+                let end = start + caption.len();
+                DocumentChunk::new_caption(document_id, first_index + i as u32, caption, start as u64, end as u64).ok()
+            })
+            .collect()
+    }
+
     async fn read_file_content(&self, file_path: &str, mime_type: &str) -> Result<String> {
         let path = Path::new(file_path);
 
@@ -72,6 +198,13 @@ impl DocumentProcessor {
             "application/rtf" => {
                 self.extract_rtf_text(path).await
             }
+            "text/html" => {
+                let content = Self::decode_markup(&fs::read(path)?);
+                Ok(self.clean_text(&Self::strip_html_tags(&content)))
+            }
+            "application/epub+zip" => {
+                self.extract_epub_text(path).await
+            }
             _ => Err(anyhow!("Unsupported file type: {}", mime_type)),
         }
     }
@@ -130,6 +263,149 @@ impl DocumentProcessor {
         text.to_string()
     }
 
+    async fn extract_epub_text(&self, path: &Path) -> Result<String> {
+        // EPUB 是 zip 包：container.xml 指向 OPF，OPF 的 spine 按阅读顺序列出章节
+        let file = fs::File::open(path)?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| anyhow!("Failed to open EPUB: {}", e))?;
+
+        let container = Self::read_zip_entry(&mut archive, "META-INF/container.xml")?;
+        let opf_path = Self::find_xml_tags(&container, "rootfile")
+            .into_iter()
+            .find_map(|tag| Self::xml_attr(&tag, "full-path"))
+            .ok_or_else(|| anyhow!("EPUB container.xml is missing rootfile"))?;
+        let opf = Self::read_zip_entry(&mut archive, &opf_path)?;
+        let opf_dir = opf_path.rsplit_once('/').map(|(dir, _)| format!("{}/", dir)).unwrap_or_default();
+
+        let manifest: HashMap<String, String> = Self::find_xml_tags(&opf, "item")
+            .into_iter()
+            .filter_map(|tag| Some((Self::xml_attr(&tag, "id")?, Self::xml_attr(&tag, "href")?)))
+            .collect();
+
+        let mut chapters = Vec::new();
+        for tag in Self::find_xml_tags(&opf, "itemref") {
+            let Some(href) = Self::xml_attr(&tag, "idref").and_then(|idref| manifest.get(&idref)) else {
+                continue;
+            };
+            let href = urlencoding::decode(href).map(|h| h.into_owned()).unwrap_or_else(|_| href.clone());
+            match Self::read_zip_entry(&mut archive, &Self::resolve_zip_path(&opf_dir, &href)) {
+                Ok(chapter) => chapters.push(Self::strip_html_tags(&chapter)),
+                Err(e) => log::warn!("⚠️  跳过无法读取的 EPUB 章节 {}: {}", href, e),
+            }
+        }
+
+        if chapters.is_empty() {
+            return Err(anyhow!("No readable chapters found in EPUB"));
+        }
+        Ok(self.clean_text(&chapters.join("\n\n")))
+    }
+
+    fn read_zip_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Result<String> {
+        use std::io::Read;
+        let mut entry = archive.by_name(name).map_err(|e| anyhow!("EPUB entry {} not found: {}", name, e))?;
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        Ok(Self::decode_markup(&content))
+    }
+
+    /// 把 OPF 中的相对 href 解析为 zip 内的路径：去掉 `#片段`，处理 `.` 和 `..`（不会越过包的根目录）
+    fn resolve_zip_path(base_dir: &str, href: &str) -> String {
+        let href = href.split('#').next().unwrap_or_default();
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in base_dir.split('/').chain(href.split('/')) {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        segments.join("/")
+    }
+
+    /// 把 HTML/XHTML 字节解码为文本：识别 BOM（UTF-8/UTF-16），声明为 Latin-1/Windows-1252 的非 UTF-8 内容按单字节解码，
+    /// 其余按 UTF-8 解码并替换无效字节，避免编码不是 UTF-8 的文件整体读取失败
+    fn decode_markup(bytes: &[u8]) -> String {
+        let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        };
+        match bytes {
+            [0xEF, 0xBB, 0xBF, rest @ ..] => return String::from_utf8_lossy(rest).into_owned(),
+            [0xFF, 0xFE, rest @ ..] => return utf16(rest, u16::from_le_bytes),
+            [0xFE, 0xFF, rest @ ..] => return utf16(rest, u16::from_be_bytes),
+            _ => {}
+        }
+
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return text.to_string();
+        }
+
+        // 在文件开头查找 <meta charset=...> 或 XML 声明中的 encoding
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+        let single_byte = ["iso-8859-1", "latin1", "windows-1252"]
+            .iter()
+            .any(|charset| head.contains(&format!("charset={}", charset))
+                || head.contains(&format!("charset=\"{}", charset))
+                || head.contains(&format!("encoding=\"{}", charset)));
+        if single_byte {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+
+    /// 找出所有名为 `name` 的开始标签（含属性），如 `<item id="c1" href="c1.xhtml"/>`
+    fn find_xml_tags(xml: &str, name: &str) -> Vec<String> {
+        use regex::Regex;
+        let re = Regex::new(&format!(r"<(?:[\w-]+:)?{}\b[^>]*>", regex::escape(name))).unwrap();
+        re.find_iter(xml).map(|m| m.as_str().to_string()).collect()
+    }
+
+    fn xml_attr(tag: &str, name: &str) -> Option<String> {
+        use regex::Regex;
+        let re = Regex::new(&format!(r#"\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(name))).unwrap();
+        let caps = re.captures(tag)?;
+        caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str().to_string())
+    }
+
+    /// 简单的 HTML 标签移除：去掉 script/style 块和注释，块级标签换行，解码常见实体
+    fn strip_html_tags(html: &str) -> String {
+        use regex::Regex;
+
+        let re = Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->").unwrap();
+        let text = re.replace_all(html, "");
+
+        // 块级元素的边界换行，保留段落结构
+        let re = Regex::new(r"(?i)<br\s*/?>|</?(?:p|div|h[1-6]|li|tr|section|article|blockquote|pre|title)\b[^>]*>").unwrap();
+        let text = re.replace_all(&text, "\n");
+
+        let re = Regex::new(r"<[^>]*>").unwrap();
+        let text = re.replace_all(&text, "");
+
+        let re = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+        re.replace_all(&text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = match entity {
+                "nbsp" => Some(' '),
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            decoded.map(String::from).unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+    }
+
     fn clean_text(&self, text: &str) -> String {
         // 清理文本：保留换行符结构，移除每行内的多余空白
         use regex::Regex;
@@ -159,6 +435,10 @@ impl DocumentProcessor {
         let mut current_chunk = String::new();
         let mut current_chunk_start = 0;
 
+        // 章节跟踪：已处理文本中最近的标题，以及当前块所属的章节
+        let mut last_heading: Option<String> = None;
+        let mut chunk_section: Option<String> = None;
+
         for sentence in sentences {
             let sentence_tokens = self.estimate_token_count(&sentence);
             let current_tokens = self.estimate_token_count(&current_chunk);
@@ -167,13 +447,14 @@ impl DocumentProcessor {
             if current_tokens + sentence_tokens > self.max_chunk_size && !current_chunk.is_empty() {
                 let chunk_end = current_offset;
 
-                if let Ok(chunk) = DocumentChunk::new(
+                if let Ok(mut chunk) = DocumentChunk::new(
                     document_id,
                     chunk_index,
                     current_chunk.trim().to_string(),
                     current_chunk_start as u64,
                     chunk_end as u64,
                 ) {
+                    chunk.section = chunk_section.clone();
                     chunks.push(chunk);
                     chunk_index += 1;
                 }
@@ -181,26 +462,35 @@ impl DocumentProcessor {
                 // Start new chunk with overlap
                 current_chunk = self.create_overlap_content(&current_chunk, &sentence);
                 current_chunk_start = self.calculate_overlap_start(current_offset, &current_chunk);
+                chunk_section = self.section_for_new_chunk(&sentence, &last_heading);
             } else {
                 if current_chunk.is_empty() {
                     current_chunk_start = current_offset;
+                    chunk_section = self.section_for_new_chunk(&sentence, &last_heading);
                 }
                 current_chunk.push_str(&sentence);
                 current_chunk.push(' ');
             }
 
+            if self.section_metadata {
+                if let Some(heading) = sentence.lines().rev().find_map(Self::parse_heading) {
+                    last_heading = Some(heading);
+                }
+            }
+
             current_offset += sentence.len() + 1; // +1 for space
         }
 
         // Create final chunk if there's remaining content
         if !current_chunk.trim().is_empty() {
-            if let Ok(chunk) = DocumentChunk::new(
+            if let Ok(mut chunk) = DocumentChunk::new(
                 document_id,
                 chunk_index,
                 current_chunk.trim().to_string(),
                 current_chunk_start as u64,
                 current_offset as u64,
             ) {
+                chunk.section = chunk_section;
                 chunks.push(chunk);
             }
         }
@@ -212,6 +502,68 @@ impl DocumentProcessor {
         Ok(chunks)
     }
 
+    /// 新块的章节：以标题开头的句子属于该标题，否则沿用之前最近的标题
+    fn section_for_new_chunk(&self, first_sentence: &str, last_heading: &Option<String>) -> Option<String> {
+        if !self.section_metadata {
+            return None;
+        }
+        first_sentence
+            .lines()
+            .next()
+            .and_then(Self::parse_heading)
+            .or_else(|| last_heading.clone())
+    }
+
+    /// 对已有文本按当前分块参数重新切分（用于调整分块参数后重新分块）
+    pub fn chunk_text(&self, document_id: Uuid, content: &str) -> Result<Vec<DocumentChunk>> {
+        self.create_chunks(document_id, content)
+    }
+
+    /// 将按 chunk_index 排好序的块内容拼接回完整文本
+    ///
+    /// 相邻块之间存在重叠（后一块以前一块末尾的若干个词开头），
+    /// 这里按词比较找出最长的重叠部分并去掉，保证拼接后的文本可读。
+    pub fn reassemble_chunks<S: AsRef<str>>(chunks: &[S]) -> String {
+        let mut result = String::new();
+        let mut previous_words: Vec<&str> = Vec::new();
+
+        for chunk in chunks {
+            let content = chunk.as_ref().trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            let words: Vec<&str> = content.split_whitespace().collect();
+            let max_overlap = previous_words.len().min(words.len());
+            let overlap = (1..=max_overlap)
+                .rev()
+                .find(|&k| previous_words[previous_words.len() - k..] == words[..k])
+                .unwrap_or(0);
+
+            let remainder = Self::skip_words(content, overlap);
+            if !remainder.is_empty() {
+                if !result.is_empty() {
+                    result.push(' ');
+                }
+                result.push_str(remainder);
+            }
+
+            previous_words = words;
+        }
+
+        result
+    }
+
+    /// 跳过文本开头的 n 个空白分隔的词，保留剩余部分的原始空白
+    fn skip_words(text: &str, n: usize) -> &str {
+        let mut rest = text.trim_start();
+        for _ in 0..n {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        }
+        rest
+    }
+
     fn split_into_sentences(&self, text: &str) -> Vec<String> {
         // Simple sentence splitting - in a real implementation, you might use
         // a more sophisticated NLP library for better sentence boundary detection
@@ -221,8 +573,8 @@ impl DocumentProcessor {
         for char in text.chars() {
             current_sentence.push(char);
 
-            // Simple sentence boundary detection
-            if matches!(char, '.' | '!' | '?') {
+            // Simple sentence boundary detection (ASCII and CJK/full-width punctuation)
+            if Self::is_sentence_boundary(char) {
                 let trimmed = current_sentence.trim();
                 if !trimmed.is_empty() && trimmed.len() > 3 {
                     sentences.push(trimmed.to_string());
@@ -305,11 +657,15 @@ impl DocumentProcessor {
         }
     }
 
+    /// 句子结束符：ASCII 的 `.!?`，中文的 `。！？；`，以及全角的 `．；`
+    fn is_sentence_boundary(c: char) -> bool {
+        matches!(c, '.' | '!' | '?' | '。' | '！' | '？' | '；' | '．' | '｡')
+    }
+
     fn estimate_token_count(&self, text: &str) -> usize {
-        // Simple token estimation: roughly 4 characters per token
-        // This is a rough approximation - for production use, you'd want
-        // to use a proper tokenizer like tiktoken
-        (text.len() as f32 / 4.0).ceil() as usize
+        // Rough approximation (CJK ~1.5 chars per token, otherwise ~4 bytes per token);
+        // shares the estimate with DocumentChunk so chunks sized here pass its validation
+        DocumentChunk::estimate_token_count(text) as usize
     }
 
     pub fn validate_file(&self, file_path: &str) -> Result<()> {
@@ -340,7 +696,7 @@ impl DocumentProcessor {
     }
 
     pub fn get_supported_extensions() -> Vec<&'static str> {
-        vec!["txt", "md", "markdown", "pdf", "doc", "docx", "rtf"]
+        vec!["txt", "md", "markdown", "pdf", "doc", "docx", "rtf", "html", "htm", "epub"]
     }
 
     pub fn is_supported_file(&self, file_path: &str) -> bool {
@@ -399,6 +755,27 @@ mod tests {
         assert!(tokens >= 3 && tokens <= 5);
     }
 
+    #[test]
+    fn test_chinese_paragraph_is_split_into_multiple_chunks() {
+        let processor = DocumentProcessor::with_chunk_settings(50, 5);
+        let content = "知识库把上传的文档切分成较小的文本块，并为每个块生成向量。\
+            用户提问时，系统先把问题转换成向量，再检索最相关的文本块！\
+            检索到的文本块会作为上下文发送给大语言模型，用来生成回答。\
+            分块太大时上下文里混入无关内容；分块太小时又会丢失完整的语义？\
+            因此需要根据文档的特点选择合适的分块大小。";
+
+        let sentences = processor.split_into_sentences(content);
+        assert_eq!(sentences.len(), 6);
+        assert!(sentences[3].ends_with('；'));
+
+        // 每个中文字符约 0.67 个 token，而不是按 UTF-8 字节数的 3/4 计算
+        assert_eq!(processor.estimate_token_count("知识库检索"), 4);
+
+        let chunks = processor.chunk_text(Uuid::new_v4(), content).unwrap();
+        assert!(chunks.len() > 1, "expected multiple chunks, got {}", chunks.len());
+        assert!(chunks.iter().all(|chunk| chunk.token_count <= 50));
+    }
+
     #[test]
     fn test_supported_extensions() {
         let extensions = DocumentProcessor::get_supported_extensions();
@@ -454,6 +831,148 @@ mod tests {
         assert!(processing_result.processing_time >= 0.0);
     }
 
+    #[test]
+    fn test_html_strips_tags_scripts_and_styles() {
+        let html = r#"<html><head><title>Guide</title><style>p { color: red; }</style>
+            <script>var secret = "tracking";</script></head>
+            <body><h1>SeekDB &amp; vectors</h1><!-- nav --><p>Create an <b>index</b>&nbsp;first.</p></body></html>"#;
+
+        let processor = DocumentProcessor::new();
+        let text = processor.clean_text(&DocumentProcessor::strip_html_tags(html));
+        assert_eq!(text, "Guide\nSeekDB & vectors\nCreate an index first.");
+    }
+
+    #[test]
+    fn test_epub_hrefs_are_normalized_and_markup_is_decoded_leniently() {
+        assert_eq!(DocumentProcessor::resolve_zip_path("OEBPS/text/", "../images/../text/ch1.xhtml#sec2"), "OEBPS/text/ch1.xhtml");
+        assert_eq!(DocumentProcessor::resolve_zip_path("", "./ch1.xhtml"), "ch1.xhtml");
+        assert_eq!(DocumentProcessor::resolve_zip_path("OEBPS/", "../../ch1.xhtml"), "ch1.xhtml");
+
+        assert_eq!(DocumentProcessor::decode_markup(b"\xEF\xBB\xBF<p>caf\xC3\xA9</p>"), "<p>café</p>");
+        assert_eq!(DocumentProcessor::decode_markup(b"\xFF\xFE<\x00p\x00>\x00"), "<p>");
+        assert_eq!(
+            DocumentProcessor::decode_markup(b"<meta charset=\"iso-8859-1\"><p>caf\xE9</p>"),
+            "<meta charset=\"iso-8859-1\"><p>café</p>"
+        );
+        // 未声明编码的无效字节被替换，而不是整个文件读取失败
+        assert_eq!(DocumentProcessor::decode_markup(b"<p>ok\xFF</p>"), "<p>ok\u{FFFD}</p>");
+    }
+
+    #[tokio::test]
+    async fn test_epub_chapters_are_read_in_spine_order() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("book.epub");
+        {
+            let options = zip::write::FileOptions::default();
+            let mut zip = zip::ZipWriter::new(File::create(&file_path).unwrap());
+            let mut add = |name: &str, content: &str| {
+                zip.start_file(name, options).unwrap();
+                zip.write_all(content.as_bytes()).unwrap();
+            };
+            add("mimetype", "application/epub+zip");
+            add(
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#,
+            );
+            // manifest 顺序与 spine 相反，章节应按 spine 顺序拼接
+            add(
+                "OEBPS/content.opf",
+                r#"<package><manifest>
+                    <item id="ch2" href="styles/../text/chapter%202.xhtml" media-type="application/xhtml+xml"/>
+                    <item href="text/ch1.xhtml" id="ch1" media-type="application/xhtml+xml"/>
+                </manifest><spine><itemref idref="ch1"/><itemref idref="ch2"/></spine></package>"#,
+            );
+            add("OEBPS/text/ch1.xhtml", "<html><body><h1>Chapter One</h1><p>The first chapter.</p></body></html>");
+            add("OEBPS/text/chapter 2.xhtml", "<html><body><h1>Chapter Two</h1><p>The second chapter.</p></body></html>");
+            zip.finish().unwrap();
+        }
+
+        let document = Document::new(Uuid::new_v4(), file_path.to_string_lossy().to_string(), 100, "hash".to_string()).unwrap();
+        assert_eq!(document.mime_type, "application/epub+zip");
+
+        let text = DocumentProcessor::new().extract_text(&document).await.unwrap();
+        assert_eq!(text, "Chapter One\nThe first chapter.\nChapter Two\nThe second chapter.");
+    }
+
+    #[tokio::test]
+    async fn test_figure_caption_produces_caption_chunk() {
+        let processor = DocumentProcessor::new().with_caption_extraction(true);
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("architecture.md");
+
+        let mut file = File::create(&file_path).unwrap();
+        writeln!(file, "# Architecture").unwrap();
+        writeln!(file, "The knowledge base stores every document chunk together with its vector embedding in SeekDB.").unwrap();
+        writeln!(file, "![Data flow between the desktop app and SeekDB](images/flow.png)").unwrap();
+        writeln!(file, "Figure 2: Hybrid retrieval pipeline overview").unwrap();
+
+        let document = Document::new(
+            Uuid::new_v4(),
+            file_path.to_string_lossy().to_string(),
+            200,
+            "test_hash".to_string(),
+        ).unwrap();
+
+        let result = processor.process_document(&document).await.unwrap();
+        assert_eq!(result.caption_chunks.len(), 2);
+
+        // 说明块可按说明文字检索到，且编号接在正文块之后
+        let found = result.caption_chunks
+            .iter()
+            .find(|c| c.content.contains("Hybrid retrieval pipeline"))
+            .expect("应生成图注说明块");
+        assert_eq!(found.content, "Figure 2: Hybrid retrieval pipeline overview");
+        assert!(found.chunk_index as usize >= result.chunks.len());
+        assert!(result.caption_chunks.iter().any(|c| c.content == "Data flow between the desktop app and SeekDB"));
+
+        // 默认不提取
+        let plain = DocumentProcessor::new().process_document(&document).await.unwrap();
+        assert!(plain.caption_chunks.is_empty());
+    }
+
+    #[test]
+    fn test_extract_captions_from_html_and_chinese_figures() {
+        let text = "图 1：系统架构\n<figure><img src=\"a.png\" alt=\"登录界面截图\"><figcaption>登录 <b>流程</b></figcaption></figure>\n图书馆开放时间";
+        let captions = DocumentProcessor::extract_captions(text);
+        assert_eq!(captions, vec!["图 1：系统架构", "登录 流程", "登录界面截图"]);
+    }
+
+    #[test]
+    fn test_reassemble_chunks_strips_overlap() {
+        let chunks = vec![
+            "The quick brown fox jumps over the lazy dog.",
+            "the lazy dog. Then it runs into the forest.",
+            "into the forest. The end.",
+        ];
+
+        let text = DocumentProcessor::reassemble_chunks(&chunks);
+        assert_eq!(
+            text,
+            "The quick brown fox jumps over the lazy dog. Then it runs into the forest. The end."
+        );
+    }
+
+    #[test]
+    fn test_rechunk_with_smaller_size_increases_chunk_count() {
+        let document_id = Uuid::new_v4();
+        let content = (1..=20)
+            .map(|i| format!("Sentence number {} talks about the knowledge base in some detail.", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let large = DocumentProcessor::with_chunk_settings(1000, 100);
+        let original_chunks = large.chunk_text(document_id, &content).unwrap();
+
+        // 从已有块重建文本，再用更小的分块参数重新切分
+        let contents: Vec<&str> = original_chunks.iter().map(|c| c.content.as_str()).collect();
+        let rebuilt = DocumentProcessor::reassemble_chunks(&contents);
+
+        let small = DocumentProcessor::with_chunk_settings(50, 5);
+        let rechunked = small.chunk_text(document_id, &rebuilt).unwrap();
+
+        assert!(rechunked.len() > original_chunks.len());
+    }
+
     #[test]
     fn test_chunk_creation() {
         let processor = DocumentProcessor::with_chunk_settings(50, 10); // Small chunks for testing
@@ -474,4 +993,25 @@ mod tests {
             assert!(chunk.end_offset > chunk.start_offset);
         }
     }
+
+    #[test]
+    fn test_chunk_after_heading_carries_section() {
+        let processor = DocumentProcessor::with_chunk_settings(30, 0).with_section_metadata(true);
+        let document_id = Uuid::new_v4();
+        let content = "# Guide\nThis guide explains how the knowledge base works in practice.\n\
+            ## Setup\nDownload the installer and run it on your machine first.\n\
+            Then open the app and create your first project from the sidebar.\n\
+            <h2>Usage <em>basics</em></h2>\nAsk questions in the chat panel and read the cited sources.";
+
+        let chunks = processor.chunk_text(document_id, content).unwrap();
+        let sections: Vec<Option<&str>> = chunks.iter().map(|c| c.section.as_deref()).collect();
+        assert_eq!(sections, vec![Some("Guide"), Some("Setup"), Some("Setup"), Some("Usage basics")]);
+
+        // 默认不记录章节
+        let plain = DocumentProcessor::with_chunk_settings(30, 0).chunk_text(document_id, content).unwrap();
+        assert!(plain.iter().all(|c| c.section.is_none()));
+
+        assert_eq!(DocumentProcessor::parse_heading("### Install ###").as_deref(), Some("Install"));
+        assert_eq!(DocumentProcessor::parse_heading("#hashtag"), None);
+    }
 }
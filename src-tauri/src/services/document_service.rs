@@ -1,36 +1,735 @@
+use crate::config::{AppConfig, ChatConfig, EmbeddingConfig, ThresholdMode};
 use crate::models::document::{Document, ProcessingStatus};
+use crate::models::project::Project;
+use crate::utils::content_hash::{self, HashAlgorithm};
 use crate::services::{
-    dashscope_embedding_service::DashScopeEmbeddingService,
-    document_processor::DocumentProcessor,
-    seekdb_adapter::{SeekDbAdapter, VectorDocument},
+    dashscope_embedding_service::{is_retryable_error, DashScopeEmbeddingService, DEFAULT_EMBEDDING_DIMENSION},
+    chunk_size_tuning::{self, ChunkSizeRecommendation, ChunkSizeTestQuery},
+    embedding_provider::{self, EmbeddingBatch, EmbeddingProvider},
+    project_quota::{ProjectQuotaUsage, ProjectQuotas, ProjectUsage},
+    reranker::Reranker,
+    retrieval_benchmark::{self, RetrievalBenchmark},
+    document_processor::{
+        DocumentProcessor, CHUNK_FILE_SIZE_KEY, CHUNK_SECTION_KEY, CHUNK_SECTION_PATH_KEY, CHUNK_TOTAL_KEY, CHUNK_TYPE_CAPTION,
+        CHUNK_TYPE_KEY,
+    },
+    seekdb_adapter::{SearchResult, SeekDbAdapter, VectorDocument, DEFAULT_SCHEMA_LOCK_TIMEOUT, INDEXED_DISTANCE_METRIC},
+    structure_chunker::{self, StructuredChunk},
+    usage_report::{self, UsageKind},
 };
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
+/// 临时数据库文件名前缀
+pub(crate) const TEMP_DB_PREFIX: &str = "mine_kb_temp";
+/// 临时数据库默认保留时长（小时）
+/// 分块大小调优时最多使用的样本字符数，避免为调优生成过多向量
+const MAX_TUNING_SAMPLE_CHARS: usize = 20_000;
+
 /// 相似文档块结构（用于聊天上下文）
 #[derive(Debug, Clone)]
 pub struct SimilarChunk {
+    pub project_id: String,
     pub document_id: String,
     pub filename: Option<String>,
     pub content: String,
     pub relevance_score: f64,
+    /// 块所属的章节（开启章节元数据时记录的最近标题）
+    pub section: Option<String>,
+}
+
+impl SimilarChunk {
+    /// 从检索结果构造，filename 取自 metadata
+    fn from_search_result(result: &SearchResult, relevance_score: f64) -> Self {
+        SimilarChunk {
+            project_id: result.document.project_id.clone(),
+            document_id: result.document.document_id.clone(),
+            filename: result.document.metadata.get("filename").cloned(),
+            content: result.document.content.clone(),
+            relevance_score,
+            section: result.document.metadata.get(CHUNK_SECTION_KEY).cloned(),
+        }
+    }
+}
+
+/// 生成查询向量失败（embedding 服务不可用），检索可以降级为关键词检索
+#[derive(Debug, thiserror::Error)]
+#[error("Embedding 服务不可用: {0}")]
+pub struct EmbeddingUnavailable(pub String);
+
+/// 检索失败是否因为无法生成查询向量
+pub fn is_embedding_unavailable(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<EmbeddingUnavailable>().is_some()
+}
+
+/// 生成查询向量的错误是否说明服务暂时无法访问（网络错误、超时、限流、5xx）；
+/// 鉴权失败、请求参数错误等配置问题不算，降级为关键词检索只会把它们掩盖掉
+fn is_embedding_service_unreachable(error: &anyhow::Error) -> bool {
+    let network_error = error.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request())
+    });
+    network_error || is_retryable_error(error)
+}
+
+/// 多项目检索中的项目及其权重（分数乘数）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectWeight {
+    pub project_id: String,
+    pub weight: f64,
+}
+
+/// 项目重新分块的结果统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RechunkReport {
+    pub document_count: usize,
+    pub chunks_before: usize,
+    pub chunks_after: usize,
+    pub failed_documents: Vec<String>,
+}
+
+/// 按结构重新分块的结果统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestructureReport {
+    pub document_id: String,
+    /// 识别出的章节数（第一个标题之前的内容也算一个）
+    pub sections: usize,
+    pub chunks_before: usize,
+    pub chunks_after: usize,
+}
+
+/// 项目重新处理（重新读取源文件、分块、生成 embedding）的结果统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReprocessReport {
+    pub document_count: usize,
+    /// 被新块替换的旧文档块数（失败的文档保留旧块，不计入）
+    pub deleted_chunks: usize,
+    pub reprocessed: usize,
+    pub failed_documents: Vec<ReprocessFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReprocessFailure {
+    pub document_id: String,
+    pub filename: String,
+    pub error: String,
+}
+
+/// 清理摄取失败残留文档块的结果统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartialCleanupReport {
+    /// 项目中摄取失败的文档数（数据库中块不完整，或本次运行中标记为 Failed）
+    pub failed_documents: usize,
+    /// 其中有残留文档块并已清理的文档数
+    pub cleaned_documents: usize,
+    /// 删除的文档块数
+    pub removed_chunks: usize,
+}
+
+/// 补全缺失向量的结果统计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingFillReport {
+    /// 缺少向量的文档块数
+    pub missing_chunks: usize,
+    /// 已补全向量的文档块数
+    pub filled_chunks: usize,
+}
+
+/// 由文档块拼接还原的文档内容（用于查看引用来源）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentContent {
+    pub document_id: String,
+    pub filename: Option<String>,
+    pub content: String,
+    pub chunk_count: usize,
+}
+
+impl DocumentContent {
+    /// 按 chunk_index 拼接正文块并去掉相邻块的重叠部分；图片说明块不属于正文，不参与拼接
+    pub fn from_chunks(document_id: &str, chunks: &[VectorDocument]) -> Option<Self> {
+        let mut text_chunks: Vec<&VectorDocument> = chunks
+            .iter()
+            .filter(|c| c.metadata.get(CHUNK_TYPE_KEY).map(String::as_str) != Some(CHUNK_TYPE_CAPTION))
+            .collect();
+        if text_chunks.is_empty() {
+            return None;
+        }
+        text_chunks.sort_by_key(|c| c.chunk_index);
+
+        let contents: Vec<&str> = text_chunks.iter().map(|c| c.content.as_str()).collect();
+        Some(Self {
+            document_id: document_id.to_string(),
+            filename: text_chunks.iter().find_map(|c| c.metadata.get("filename").cloned()),
+            content: DocumentProcessor::reassemble_chunks(&contents),
+            chunk_count: text_chunks.len(),
+        })
+    }
+}
+
+/// 检索结果的分数过滤策略
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreThreshold {
+    /// 丢弃低于固定分数的结果
+    Fixed(f64),
+    /// 保留与最高分相差不超过 gap 的结果
+    Adaptive { gap: f64 },
+}
+
+impl Default for ScoreThreshold {
+    fn default() -> Self {
+        ScoreThreshold::Fixed(0.3)
+    }
+}
+
+impl ScoreThreshold {
+    pub fn from_config(config: &ChatConfig) -> Self {
+        match config.threshold_mode {
+            ThresholdMode::Fixed => ScoreThreshold::Fixed(config.similarity_threshold),
+            ThresholdMode::Adaptive => ScoreThreshold::Adaptive { gap: config.adaptive_score_gap },
+        }
+    }
+
+    /// 传给数据库的绝对下限；自适应模式不做绝对过滤
+    fn absolute_cutoff(&self) -> f64 {
+        match self {
+            ScoreThreshold::Fixed(min_score) => *min_score,
+            ScoreThreshold::Adaptive { .. } => f64::MIN,
+        }
+    }
+}
+
+/// 问题路由结果：项目及其描述与问题的相似度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectRoute {
+    pub project_id: Uuid,
+    pub score: f64,
+}
+
+/// 相关文档：按文档向量中心的余弦相似度排序
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarDocument {
+    pub document_id: String,
+    pub filename: Option<String>,
+    pub score: f64,
+}
+
+/// 元数据某个键的取值及其出现次数（用于筛选下拉框）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetadataValueCount {
+    pub value: String,
+    /// 包含该取值的文档数
+    pub document_count: usize,
+    /// 包含该取值的文档块数
+    pub chunk_count: usize,
+}
+
+/// 文档索引：提取文本、分块、生成 embedding 并写入数据库
+///
+/// 不持有 DocumentService 的锁，批量上传时可以并发处理多个文档；
+/// 数据库锁只在写入向量时短暂持有。重新分块、项目描述向量化、检索基准测试、分块大小调优等耗时较长的操作也通过它执行
+#[derive(Clone)]
+pub struct DocumentIndexer {
+    document_processor: DocumentProcessor,
+    embedding_service: Arc<dyn EmbeddingProvider>,
+    vector_db: Arc<Mutex<SeekDbAdapter>>,
+}
+
+impl DocumentIndexer {
+    /// 校验文件并创建文档记录（尚未处理）
+    pub fn new_document(&self, project_id: Uuid, file_path: String, file_size: u64, content_hash: String) -> Result<Document> {
+        self.document_processor.validate_file(&file_path)?;
+        Ok(Document::new(project_id, file_path, file_size, content_hash)?)
+    }
+
+    /// 处理文档并写入向量，成功或失败都会更新文档状态
+    pub async fn index(&self, document: &mut Document) -> Result<()> {
+        self.index_chunks(document, false).await.map(|_| ())
+    }
+
+    /// 重新处理已索引的文档：新块生成后以单个事务替换旧块，返回被替换的旧块数
+    ///
+    /// 读取或生成 embedding 失败时旧块保持不变
+    pub async fn reindex(&self, document: &mut Document) -> Result<usize> {
+        self.index_chunks(document, true).await
+    }
+
+    async fn index_chunks(&self, document: &mut Document, replace_existing: bool) -> Result<usize> {
+        // Update status to processing
+        document.processing_status = ProcessingStatus::Processing;
+
+        // Process the document
+        match self.document_processor.process_document(document).await {
+            Ok(processing_result) => {
+                log::info!("Document processed successfully: {} chunks", processing_result.chunks.len());
+
+                if !processing_result.caption_chunks.is_empty() {
+                    log::info!("Extracted {} figure captions", processing_result.caption_chunks.len());
+                }
+
+                // Create vector documents for each chunk (正文块在前，图片说明块在后)
+                let mut vector_docs = Vec::new();
+                let all_chunks: Vec<(&crate::models::document::DocumentChunk, bool)> = processing_result.chunks
+                    .iter()
+                    .map(|c| (c, false))
+                    .chain(processing_result.caption_chunks.iter().map(|c| (c, true)))
+                    .collect();
+                let chunk_count = all_chunks.len();
+
+                // 批量生成 embeddings（更高效）
+                let chunk_texts: Vec<String> = all_chunks
+                    .iter()
+                    .map(|(c, _)| c.content.clone())
+                    .collect();
+
+                // 已缓存的文本（`embedding.persistentCache`，默认开启）不会再次请求 API
+                let EmbeddingBatch { embeddings, total_tokens } =
+                    self.embedding_service.embed_batch_with_usage(&chunk_texts).await?;
+                usage_report::record_usage(
+                    &self.vector_db,
+                    &document.project_id.to_string(),
+                    UsageKind::Embedding,
+                    total_tokens as u64,
+                    self.embedding_service.model(),
+                )
+                .await;
+
+                for ((chunk, is_caption), embedding) in all_chunks.iter().zip(embeddings.iter()) {
+
+                        let vector_doc = VectorDocument {
+                            id: Uuid::new_v4().to_string(),
+                            project_id: document.project_id.to_string(),
+                            document_id: document.id.to_string(),
+                            chunk_index: chunk.chunk_index as i32,
+                            content: chunk.content.clone(),
+                            embedding: embedding.clone(),
+                            metadata: {
+                                let mut meta = HashMap::new();
+                                meta.insert("filename".to_string(), document.filename.clone());
+                                meta.insert("mime_type".to_string(), document.mime_type.clone());
+                                meta.insert(CHUNK_FILE_SIZE_KEY.to_string(), document.file_size.to_string());
+                                meta.insert(CHUNK_TOTAL_KEY.to_string(), chunk_count.to_string());
+                                meta.insert("start_offset".to_string(), chunk.start_offset.to_string());
+                                meta.insert("end_offset".to_string(), chunk.end_offset.to_string());
+                                if *is_caption {
+                                    meta.insert(CHUNK_TYPE_KEY.to_string(), CHUNK_TYPE_CAPTION.to_string());
+                                }
+                                if let Some(section) = &chunk.section {
+                                    meta.insert(CHUNK_SECTION_KEY.to_string(), section.clone());
+                                }
+                                meta
+                            },
+                        };
+                        vector_docs.push(vector_doc);
+                    }
+
+                // Store vectors in database
+                let replaced = {
+                    let mut db = self.vector_db.lock().await;
+                    if replace_existing {
+                        db.replace_document_chunks(&document.id.to_string(), vector_docs)?.0
+                    } else {
+                        db.add_documents(vector_docs)?;
+                        0
+                    }
+                };
+
+                // Update document status
+                document.processing_status = ProcessingStatus::Indexed;
+                document.chunk_count = chunk_count as u32;
+                document.processed_at = Some(chrono::Utc::now());
+
+                log::info!("Document indexed successfully: {}", document.filename);
+                Ok(replaced)
+            }
+            Err(e) => {
+                log::error!("Document processing failed: {}", e);
+                document.processing_status = ProcessingStatus::Failed;
+                document.error_message = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// 逐个重新读取源文件、分块、生成 embedding 并替换文档的旧块
+    ///
+    /// 源文件不存在或处理失败的文档记为失败，数据库中的旧块保持不变。
+    /// 返回统计结果和重新处理成功的文档（供调用方写回 DocumentService）。
+    /// `on_progress(已完成数, 总数, 文件名)` 在每个文档处理完成后调用。
+    pub async fn reprocess_documents<F>(&self, documents: Vec<Document>, on_progress: F) -> (ReprocessReport, Vec<Document>)
+    where
+        F: Fn(usize, usize, &str),
+    {
+        let total = documents.len();
+        let mut report = ReprocessReport { document_count: total, ..Default::default() };
+        let mut reprocessed = Vec::new();
+
+        for (index, mut document) in documents.into_iter().enumerate() {
+            let filename = document.filename.clone();
+            let result = match missing_source_error(&document.file_path) {
+                Some(error) => Err(anyhow!(error)),
+                None => {
+                    document.update_processing_status(ProcessingStatus::Processing, None);
+                    self.reindex(&mut document).await
+                }
+            };
+
+            match result {
+                Ok(replaced) => {
+                    log::info!("   ✅ {} (替换 {} 个旧文档块)", filename, replaced);
+                    report.deleted_chunks += replaced;
+                    report.reprocessed += 1;
+                    reprocessed.push(document);
+                }
+                Err(e) => {
+                    log::error!("   ❌ {} 重新处理失败，保留原有文档块: {}", filename, e);
+                    report.failed_documents.push(ReprocessFailure {
+                        document_id: document.id.to_string(),
+                        filename: filename.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+
+            on_progress(index + 1, total, &filename);
+        }
+
+        log::info!("✅ [REPROCESS] 完成: {} 个文档, 成功 {} 个, 失败 {} 个",
+            report.document_count, report.reprocessed, report.failed_documents.len());
+        (report, reprocessed)
+    }
+
+    /// 检索延迟基准测试：分别统计生成查询向量（不经过缓存）和向量检索的耗时
+    pub async fn benchmark_retrieval(
+        &self,
+        project_id: &str,
+        queries: &[String],
+        iterations: u32,
+        top_k: usize,
+        score_threshold: ScoreThreshold,
+    ) -> Result<RetrievalBenchmark> {
+        log::info!("⏱️  检索基准测试: project_id={}, {} 个查询 × {} 轮", project_id, queries.len(), iterations);
+
+        let threshold = score_threshold.absolute_cutoff();
+        retrieval_benchmark::run_benchmark(
+            queries,
+            iterations,
+            |query| async move { self.embedding_service.embed_text_uncached(&query).await },
+            |embedding| async move {
+                let db = self.vector_db.lock().await;
+                Ok(db.similarity_search(&embedding, Some(project_id), None, top_k, threshold, INDEXED_DISTANCE_METRIC)?.len())
+            },
+        )
+        .await
+    }
+
+    /// 读取分块大小调优的样本文本：指定文件时读取文件内容，否则拼接项目中已有文档的内容
+    pub async fn load_tuning_sample(&self, project_id: Option<&str>, file_path: Option<&str>) -> Result<String> {
+        let sample = match (file_path, project_id) {
+            (Some(file_path), _) => {
+                let file_size = std::fs::metadata(file_path)?.len();
+                let document = Document::new(Uuid::nil(), file_path.to_string(), file_size, String::new())
+                    .map_err(|e| anyhow!("无法读取样本文件: {}", e))?;
+                self.document_processor.extract_text(&document).await?
+            }
+            (None, Some(project_id)) => {
+                let existing = self.vector_db.lock().await.get_project_documents(project_id)?;
+
+                // 按文档分组（get_project_documents 已按 document_id、chunk_index 排序）
+                let mut grouped: Vec<(String, Vec<VectorDocument>)> = Vec::new();
+                for chunk in existing {
+                    match grouped.last_mut() {
+                        Some((doc_id, chunks)) if *doc_id == chunk.document_id => chunks.push(chunk),
+                        _ => grouped.push((chunk.document_id.clone(), vec![chunk])),
+                    }
+                }
+
+                grouped
+                    .iter()
+                    .filter_map(|(document_id, chunks)| DocumentContent::from_chunks(document_id, chunks))
+                    .map(|content| content.content)
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            }
+            (None, None) => return Err(anyhow!("必须指定项目或样本文件")),
+        };
+
+        Ok(sample.chars().take(MAX_TUNING_SAMPLE_CHARS).collect())
+    }
+
+    /// 用样本文本试验各候选分块大小的检索效果，推荐分块大小（结果只在内存中计算，不写入数据库）
+    pub async fn optimize_chunk_size(
+        &self,
+        sample: &str,
+        candidate_sizes: &[usize],
+        queries: &[ChunkSizeTestQuery],
+        top_k: usize,
+    ) -> Result<ChunkSizeRecommendation> {
+        log::info!("📐 分块大小调优: 样本 {} 字符, 候选 {:?}, {} 个测试查询", sample.chars().count(), candidate_sizes, queries.len());
+
+        chunk_size_tuning::evaluate_chunk_sizes(sample, candidate_sizes, queries, top_k, |texts| async move {
+            self.embedding_service.embed_batch(&texts).await
+        })
+        .await
+    }
+
+    /// 使用新的分块参数重新切分整个项目的文档
+    ///
+    /// 文本从已有的块重建（去除块间重叠），重新分块、重新生成 embedding 后
+    /// 以单个事务替换该文档的旧块。单个文档失败不会中断其他文档。
+    /// `on_progress(已完成数, 总数, 文件名)` 在每个文档处理完成后调用。
+    pub async fn rechunk_project<F>(
+        &self,
+        project_id: &str,
+        max_chunk_size: usize,
+        chunk_overlap: usize,
+        on_progress: F,
+    ) -> Result<RechunkReport>
+    where
+        F: Fn(usize, usize, &str),
+    {
+        if max_chunk_size == 0 {
+            return Err(anyhow!("分块大小必须大于 0"));
+        }
+        if chunk_overlap >= max_chunk_size {
+            return Err(anyhow!("分块重叠 ({}) 必须小于分块大小 ({})", chunk_overlap, max_chunk_size));
+        }
+
+        log::info!("✂️  [RECHUNK] 项目 {} 重新分块: max_chunk_size={}, chunk_overlap={}",
+            project_id, max_chunk_size, chunk_overlap);
+
+        let existing = {
+            let db = self.vector_db.lock().await;
+            db.get_project_documents(project_id)?
+        };
+
+        // 按文档分组（get_project_documents 已按 document_id、chunk_index 排序）
+        let mut grouped: Vec<(String, Vec<VectorDocument>)> = Vec::new();
+        for chunk in existing {
+            match grouped.last_mut() {
+                Some((doc_id, chunks)) if *doc_id == chunk.document_id => chunks.push(chunk),
+                _ => grouped.push((chunk.document_id.clone(), vec![chunk])),
+            }
+        }
+
+        let processor = DocumentProcessor::with_chunk_settings(max_chunk_size, chunk_overlap)
+            .with_section_metadata(self.document_processor.section_metadata());
+        let total = grouped.len();
+        let mut report = RechunkReport {
+            document_count: total,
+            chunks_before: 0,
+            chunks_after: 0,
+            failed_documents: Vec::new(),
+        };
+
+        for (index, (document_id, old_chunks)) in grouped.into_iter().enumerate() {
+            let filename = old_chunks[0].metadata.get("filename").cloned().unwrap_or_default();
+            report.chunks_before += old_chunks.len();
+
+            match self.rechunk_document(&processor, &document_id, &old_chunks).await {
+                Ok(new_count) => {
+                    log::info!("   ✅ {} : {} -> {} 块", filename, old_chunks.len(), new_count);
+                    report.chunks_after += new_count;
+                }
+                Err(e) => {
+                    log::error!("   ❌ {} 重新分块失败: {}", filename, e);
+                    // 失败的文档保留原有块
+                    report.chunks_after += old_chunks.len();
+                    report.failed_documents.push(document_id.clone());
+                }
+            }
+
+            on_progress(index + 1, total, &filename);
+        }
+
+        log::info!("✅ [RECHUNK] 完成: {} 个文档, {} -> {} 块, 失败 {} 个",
+            report.document_count, report.chunks_before, report.chunks_after, report.failed_documents.len());
+
+        Ok(report)
+    }
+
+    /// 重新切分单个文档并替换旧块，返回新块数量
+    ///
+    /// 图片说明块不参与文本重建，内容原样保留并排在新正文块之后
+    async fn rechunk_document(
+        &self,
+        processor: &DocumentProcessor,
+        document_id: &str,
+        old_chunks: &[VectorDocument],
+    ) -> Result<usize> {
+        let (caption_chunks, text_chunks): (Vec<&VectorDocument>, Vec<&VectorDocument>) = old_chunks
+            .iter()
+            .partition(|c| c.metadata.get(CHUNK_TYPE_KEY).map(String::as_str) == Some(CHUNK_TYPE_CAPTION));
+        if text_chunks.is_empty() {
+            return Ok(old_chunks.len());
+        }
+
+        let contents: Vec<&str> = text_chunks.iter().map(|c| c.content.as_str()).collect();
+        let text = DocumentProcessor::reassemble_chunks(&contents);
+
+        let document_uuid = Uuid::parse_str(document_id).unwrap_or_else(|_| Uuid::nil());
+        let new_chunks = processor
+            .chunk_text(document_uuid, &text)?
+            .into_iter()
+            .map(|chunk| StructuredChunk { chunk, section_path: Vec::new() })
+            .collect();
+
+        self.replace_text_chunks(document_id, text_chunks[0], &caption_chunks, new_chunks).await
+    }
+
+    /// 用新的正文块替换文档的旧块，返回新块数量
+    ///
+    /// `template` 提供文件名等文档级 metadata；说明块原样保留并排在新正文块之后
+    async fn replace_text_chunks(
+        &self,
+        document_id: &str,
+        template: &VectorDocument,
+        caption_chunks: &[&VectorDocument],
+        new_chunks: Vec<StructuredChunk>,
+    ) -> Result<usize> {
+        // get_project_documents 不返回向量，因此保留的说明块也需要重新生成 embedding
+        let chunk_texts: Vec<String> = new_chunks
+            .iter()
+            .map(|c| c.chunk.content.clone())
+            .chain(caption_chunks.iter().map(|c| c.content.clone()))
+            .collect();
+        let mut embeddings = self.embedding_service.embed_batch(&chunk_texts).await?;
+        if embeddings.len() != chunk_texts.len() {
+            return Err(anyhow!("embedding 数量 ({}) 与块数量 ({}) 不一致", embeddings.len(), chunk_texts.len()));
+        }
+        let caption_embeddings = embeddings.split_off(new_chunks.len());
+
+        let mut vector_docs: Vec<VectorDocument> = new_chunks
+            .iter()
+            .zip(embeddings)
+            .map(|(structured, embedding)| {
+                let chunk = &structured.chunk;
+                let mut metadata = template.metadata.clone();
+                metadata.insert("start_offset".to_string(), chunk.start_offset.to_string());
+                metadata.insert("end_offset".to_string(), chunk.end_offset.to_string());
+                metadata.remove(CHUNK_SECTION_KEY);
+                metadata.remove(CHUNK_SECTION_PATH_KEY);
+                if let Some(section) = &chunk.section {
+                    metadata.insert(CHUNK_SECTION_KEY.to_string(), section.clone());
+                }
+                if let Some(section_path) = structured.section_path_string() {
+                    metadata.insert(CHUNK_SECTION_PATH_KEY.to_string(), section_path);
+                }
+                VectorDocument {
+                    id: Uuid::new_v4().to_string(),
+                    project_id: template.project_id.clone(),
+                    document_id: document_id.to_string(),
+                    chunk_index: chunk.chunk_index as i32,
+                    content: chunk.content.clone(),
+                    embedding,
+                    metadata,
+                }
+            })
+            .collect();
+
+        let first_caption_index = vector_docs.len() as i32;
+        for (i, (caption, embedding)) in caption_chunks.iter().zip(caption_embeddings).enumerate() {
+            let mut caption = (*caption).clone();
+            caption.id = Uuid::new_v4().to_string();
+            caption.chunk_index = first_caption_index + i as i32;
+            caption.embedding = embedding;
+            vector_docs.push(caption);
+        }
+
+        let total = vector_docs.len().to_string();
+        for doc in &mut vector_docs {
+            doc.metadata.insert(CHUNK_TOTAL_KEY.to_string(), total.clone());
+        }
+
+        let mut db = self.vector_db.lock().await;
+        let (_, inserted) = db.replace_document_chunks(document_id, vector_docs)?;
+        Ok(inserted)
+    }
+
+    /// 为项目中缺少向量的文档块（摄取中途失败留下的）重新生成向量，只处理这些块，比整体重建便宜
+    pub async fn fill_missing_embeddings(&self, project_id: &str) -> Result<EmbeddingFillReport> {
+        fill_missing_embeddings_with(&self.vector_db, project_id, |texts| async move {
+            self.embedding_service.embed_batch(&texts).await
+        })
+        .await
+    }
+
+    /// 为项目的名称和描述生成向量并保存，用于问题路由
+    pub async fn index_project_description(&self, project: &Project) -> Result<()> {
+        let source_text = project_description_text(project);
+        let embedding = self.embedding_service.embed_text(&source_text).await?;
+
+        let mut db = self.vector_db.lock().await;
+        db.save_project_embedding(&project.id.to_string(), &source_text, &embedding)?;
+        log::info!("🧭 已为项目 {} 的描述生成向量", project.name);
+        Ok(())
+    }
+
+    /// 按描述相似度对项目排序，返回最相关的 `limit` 个项目。
+    /// 缺少向量或名称/描述已变化的项目会先补齐向量
+    pub async fn route_query_to_project(
+        &self,
+        query: &str,
+        projects: &[Project],
+        limit: usize,
+    ) -> Result<Vec<ProjectRoute>> {
+        let stored: HashMap<String, (String, Vec<f64>)> = {
+            let db = self.vector_db.lock().await;
+            db.load_project_embeddings()?
+                .into_iter()
+                .map(|e| (e.project_id, (e.source_text, e.embedding)))
+                .collect()
+        };
+
+        let mut candidates = Vec::with_capacity(projects.len());
+        for project in routable_projects(projects) {
+            let source_text = project_description_text(project);
+            let embedding = match stored.get(&project.id.to_string()) {
+                Some((text, embedding)) if *text == source_text => embedding.clone(),
+                _ => {
+                    log::info!("🔄 项目 {} 的描述向量缺失或已过期，重新生成", project.name);
+                    let embedding = self.embedding_service.embed_text(&source_text).await?;
+                    let mut db = self.vector_db.lock().await;
+                    db.save_project_embedding(&project.id.to_string(), &source_text, &embedding)?;
+                    embedding
+                }
+            };
+            candidates.push((project.id, embedding));
+        }
+
+        let query_embedding = self.embedding_service.embed_text(query).await?;
+        Ok(rank_projects_by_similarity(&query_embedding, &candidates, limit))
+    }
 }
 
 pub struct DocumentService {
     documents: HashMap<Uuid, Document>,
     document_processor: DocumentProcessor,
     vector_db: Arc<Mutex<SeekDbAdapter>>,
-    embedding_service: Arc<DashScopeEmbeddingService>,
+    embedding_service: Arc<dyn EmbeddingProvider>,
+    embed_project_descriptions: bool,
+    hash_algorithm: HashAlgorithm,
+    score_threshold: ScoreThreshold,
+    reranker: Option<Arc<Reranker>>,
+    quotas: ProjectQuotas,
 }
 
 impl DocumentService {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(max_age: Duration) -> Result<Self> {
         // Use in-memory path for testing/temporary usage
         let temp_dir = std::env::temp_dir();
-        let db_path = temp_dir.join("mine_kb_temp.db");
+        let removed = Self::cleanup_stale_temp_dbs(&temp_dir, max_age);
+        if removed > 0 {
+            log::info!("🧹 已清理 {} 个过期的临时数据库", removed);
+        }
+
+        let db_path = Self::temp_db_path(&temp_dir);
         let vector_db = Arc::new(Mutex::new(SeekDbAdapter::new(db_path)?));
 
         // 从环境变量读取 API Key
@@ -43,6 +742,11 @@ impl DocumentService {
             document_processor: DocumentProcessor::new(),
             vector_db,
             embedding_service,
+            embed_project_descriptions: true,
+            hash_algorithm: HashAlgorithm::default(),
+            score_threshold: ScoreThreshold::default(),
+            reranker: None,
+            quotas: ProjectQuotas::default(),
         })
     }
 
@@ -58,6 +762,11 @@ impl DocumentService {
             document_processor: DocumentProcessor::new(),
             vector_db,
             embedding_service,
+            embed_project_descriptions: true,
+            hash_algorithm: HashAlgorithm::default(),
+            score_threshold: ScoreThreshold::default(),
+            reranker: None,
+            quotas: ProjectQuotas::default(),
         })
     }
 
@@ -66,32 +775,203 @@ impl DocumentService {
         api_key: String,
         base_url: Option<String>
     ) -> Result<Self> {
-        Self::with_full_config(db_path, api_key, base_url, None).await
+        let embedding_config = EmbeddingConfig { base_url, ..Default::default() };
+        Self::with_full_config(db_path, api_key, Some(embedding_config), None, None).await
     }
 
     pub async fn with_full_config(
         db_path: &str,
         api_key: String,
-        base_url: Option<String>,
-        python_path: Option<&str>
+        embedding_config: Option<EmbeddingConfig>,
+        python_path: Option<&str>,
+        schema_lock_timeout: Option<Duration>,
     ) -> Result<Self> {
         log::info!("🏗️  [DOC-SERVICE] 初始化DocumentService, db_path: {}", db_path);
-        let vector_db = Arc::new(Mutex::new(
-            SeekDbAdapter::new_with_python(db_path, python_path.unwrap_or("python3"))?
-        ));
+        let embedding_config = embedding_config.unwrap_or_default();
+        let dimension = embedding_config.dimension.unwrap_or(DEFAULT_EMBEDDING_DIMENSION);
+        // 启动 Python 桥接和等待 schema 锁都是阻塞操作，放到阻塞线程池中执行，不占用异步运行时
+        let adapter_path = db_path.to_string();
+        let python = python_path.unwrap_or("python3").to_string();
+        let lock_timeout = schema_lock_timeout.unwrap_or(DEFAULT_SCHEMA_LOCK_TIMEOUT);
+        let adapter = tokio::task::spawn_blocking(move || {
+            SeekDbAdapter::new_with_dimension(adapter_path, &python, dimension, lock_timeout)
+        })
+        .await
+        .map_err(|e| anyhow!("初始化数据库任务失败: {}", e))??;
+        let vector_db = Arc::new(Mutex::new(adapter));
         log::info!("🏗️  [DOC-SERVICE] 数据库实例已创建");
 
-        log::info!("🎯 使用阿里云百炼 Embedding API (text-embedding-v2)");
-        let embedding_service = Arc::new(DashScopeEmbeddingService::new(api_key, base_url)?);
+        let embedding_service = embedding_provider::create_embedding_provider(api_key, &embedding_config, db_path)?;
 
         Ok(Self {
             documents: HashMap::new(),
             document_processor: DocumentProcessor::new(),
             vector_db,
             embedding_service,
+            embed_project_descriptions: true,
+            hash_algorithm: HashAlgorithm::default(),
+            score_threshold: ScoreThreshold::default(),
+            reranker: None,
+            quotas: ProjectQuotas::default(),
         })
     }
 
+    /// 当前进程专用的临时数据库路径，避免多个实例互相冲突
+    pub(crate) fn temp_db_path(temp_dir: &Path) -> PathBuf {
+        temp_dir.join(format!("{}_{}.db", TEMP_DB_PREFIX, std::process::id()))
+    }
+
+    /// 删除临时目录中超过 max_age 未修改的临时数据库（文件或目录），返回删除数量
+    fn cleanup_stale_temp_dbs(temp_dir: &Path, max_age: Duration) -> usize {
+        let entries = match std::fs::read_dir(temp_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("无法读取临时目录 {}: {}", temp_dir.display(), e);
+                return 0;
+            }
+        };
+
+        let own_path = Self::temp_db_path(temp_dir);
+        let mut removed = 0;
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let is_temp_db = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with(TEMP_DB_PREFIX))
+                .unwrap_or(false);
+            if !is_temp_db || path == own_path || temp_db_in_use(&path) {
+                continue;
+            }
+
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if !matches!(age, Some(age) if age > max_age) {
+                continue;
+            }
+
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            match result {
+                Ok(_) => {
+                    log::debug!("删除过期临时数据库: {}", path.display());
+                    removed += 1;
+                }
+                Err(e) => log::warn!("删除临时数据库失败 {}: {}", path.display(), e),
+            }
+        }
+
+        removed
+    }
+
+    /// 开启/关闭上传文档时的图片说明提取
+    pub fn set_caption_extraction(&mut self, enabled: bool) {
+        self.document_processor = self.document_processor.clone().with_caption_extraction(enabled);
+    }
+
+    /// 开启/关闭章节元数据（块之前最近的标题）
+    pub fn set_section_metadata(&mut self, enabled: bool) {
+        self.document_processor = self.document_processor.clone().with_section_metadata(enabled);
+    }
+
+    /// 开启/关闭项目描述向量化（关闭后 route_query_to_project 不可用）
+    pub fn set_project_description_embedding(&mut self, enabled: bool) {
+        self.embed_project_descriptions = enabled;
+    }
+
+    /// 设置向量检索结果的分数过滤策略
+    pub fn set_score_threshold(&mut self, threshold: ScoreThreshold) {
+        self.score_threshold = threshold;
+    }
+
+    /// 向量检索结果的分数过滤策略
+    pub fn score_threshold(&self) -> ScoreThreshold {
+        self.score_threshold
+    }
+
+    /// 设置每个项目的文档数和存储配额
+    pub fn set_project_quotas(&mut self, quotas: ProjectQuotas) {
+        self.quotas = quotas;
+    }
+
+    /// 项目当前占用配额的文档数和文件总大小（处理失败的文档不计入）
+    pub fn project_usage(&self, project_id: Uuid) -> ProjectUsage {
+        self.documents
+            .values()
+            .filter(|doc| doc.project_id == project_id && doc.processing_status != ProcessingStatus::Failed)
+            .fold(ProjectUsage::default(), |usage, doc| ProjectUsage {
+                document_count: usage.document_count + 1,
+                total_bytes: usage.total_bytes + doc.file_size,
+            })
+    }
+
+    /// 项目用量与生效的配额
+    pub fn project_quota_usage(&self, project_id: Uuid) -> ProjectQuotaUsage {
+        ProjectQuotaUsage {
+            usage: self.project_usage(project_id),
+            quota: self.quotas.quota_for(project_id),
+        }
+    }
+
+    /// 检查项目能否再添加一个大小为 `file_size` 的文档
+    ///
+    /// 调用方应在持有 DocumentService 锁期间检查并记录文档，并发上传时才不会超出配额
+    pub fn check_project_quota(&self, project_id: Uuid, file_size: u64) -> Result<()> {
+        Ok(self.quotas.check(project_id, self.project_usage(project_id), file_size)?)
+    }
+
+    /// 替换生成向量使用的 embedding 服务
+    pub fn set_embedding_provider(&mut self, provider: Arc<dyn EmbeddingProvider>) {
+        self.embedding_service = provider;
+    }
+
+    /// 项目中是否已有索引的文档块（用于区分"检索无结果"和"项目为空"）
+    pub async fn project_has_chunks(&self, project_id: &str) -> Result<bool> {
+        Ok(self.vector_db.lock().await.count_project_chunks(project_id)? > 0)
+    }
+
+    /// 设置检索结果的重排序器（None 表示不重排序）
+    pub fn set_reranker(&mut self, reranker: Option<Reranker>) {
+        self.reranker = reranker.map(Arc::new);
+    }
+
+    /// 检索时应召回的候选数量：开启重排序时多召回一些，由重排序取前 top_k
+    pub fn retrieval_candidates(&self, top_k: usize) -> usize {
+        self.reranker.as_ref().map_or(top_k, |reranker| reranker.candidates(top_k))
+    }
+
+    /// 当前的重排序器；重排序可能较慢，调用方应在释放 DocumentService 锁之后再使用
+    pub fn reranker(&self) -> Option<Arc<Reranker>> {
+        self.reranker.clone()
+    }
+
+    /// 设置文档去重使用的内容哈希算法
+    pub fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) {
+        self.hash_algorithm = algorithm;
+    }
+
+    /// 文档去重使用的内容哈希算法；调用方可以在锁外自行计算大文件的哈希
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// 使用配置的算法计算文件内容哈希
+    pub fn hash_content(&self, content: &[u8]) -> String {
+        content_hash::content_hash(content, self.hash_algorithm)
+    }
+
+    /// 当前 embedding 模型输出的向量维度
+    pub fn embedding_dimension(&self) -> usize {
+        self.embedding_service.embedding_dim()
+    }
+
     /// 获取向量数据库的引用
     pub fn get_vector_db(&self) -> Arc<Mutex<SeekDbAdapter>> {
         self.vector_db.clone()
@@ -106,6 +986,7 @@ impl DocumentService {
     ) -> Result<Uuid> {
         // Validate file before processing
         self.document_processor.validate_file(&file_path)?;
+        self.check_project_quota(project_id, file_size)?;
 
         // Create document
         let document = Document::new(project_id, file_path, file_size, content_hash)?;
@@ -121,72 +1002,105 @@ impl DocumentService {
     }
 
     async fn process_document_async(&mut self, document_id: Uuid) -> Result<()> {
+        let indexer = self.indexer();
         let document = self.documents.get_mut(&document_id)
             .ok_or_else(|| anyhow!("Document not found: {}", document_id))?;
 
-        // Update status to processing
-        document.processing_status = ProcessingStatus::Processing;
+        indexer.index(document).await
+    }
 
-        // Process the document
-        match self.document_processor.process_document(document).await {
-            Ok(processing_result) => {
-                log::info!("Document processed successfully: {} chunks", processing_result.chunks.len());
+    /// 获取文档索引组件（与本服务共享 embedding 服务和数据库连接）
+    pub fn indexer(&self) -> DocumentIndexer {
+        DocumentIndexer {
+            document_processor: self.document_processor.clone(),
+            embedding_service: self.embedding_service.clone(),
+            vector_db: self.vector_db.clone(),
+        }
+    }
 
-                // Create vector documents for each chunk
-                let mut vector_docs = Vec::new();
-                let chunk_count = processing_result.chunks.len();
+    /// 项目描述向量化使用的句柄，未开启 `ingestion.embedProjectDescriptions` 时为 None
+    pub fn project_description_indexer(&self) -> Option<DocumentIndexer> {
+        self.embed_project_descriptions.then(|| self.indexer())
+    }
 
-                // 批量生成 embeddings（更高效）
-                let chunk_texts: Vec<String> = processing_result.chunks
-                    .iter()
-                    .map(|c| c.content.clone())
-                    .collect();
+    /// 记录（或更新）文档，用于在锁外完成索引的文档
+    pub fn upsert_document(&mut self, document: Document) {
+        self.documents.insert(document.id, document);
+    }
 
-                let embeddings = self.embedding_service.embed_batch(&chunk_texts).await?;
+    /// 按文档结构（编号章节、标题层级）重新分块并重新生成 embedding
+    ///
+    /// 优先从源文件读取文本（保留换行，标题识别更准确），源文件不存在时从已有的块重建。
+    /// 每个块只包含一个章节，章节路径写入 metadata 的 `section_path`。
+    /// 没有识别到结构时返回错误，原有分块保持不变。
+    pub async fn restructure_document(&mut self, document_id: Uuid) -> Result<RestructureReport> {
+        let document_id_str = document_id.to_string();
+        let old_chunks = self.vector_db.lock().await.get_document_chunks(&document_id_str)?;
+        let (caption_chunks, text_chunks): (Vec<&VectorDocument>, Vec<&VectorDocument>) = old_chunks
+            .iter()
+            .partition(|c| c.metadata.get(CHUNK_TYPE_KEY).map(String::as_str) == Some(CHUNK_TYPE_CAPTION));
+        if text_chunks.is_empty() {
+            return Err(anyhow!("Document not found: {}", document_id));
+        }
 
-                for (chunk, embedding) in processing_result.chunks.iter().zip(embeddings.iter()) {
+        let source = self
+            .documents
+            .get(&document_id)
+            .filter(|document| missing_source_error(&document.file_path).is_none());
+        let text = match source {
+            Some(document) => self.document_processor.extract_text(document).await?,
+            None => {
+                let contents: Vec<&str> = text_chunks.iter().map(|c| c.content.as_str()).collect();
+                DocumentProcessor::reassemble_chunks(&contents)
+            }
+        };
 
-                        let vector_doc = VectorDocument {
-                            id: Uuid::new_v4().to_string(),
-                            project_id: document.project_id.to_string(),
-                            document_id: document.id.to_string(),
-                            chunk_index: chunk.chunk_index as i32,
-                            content: chunk.content.clone(),
-                            embedding: embedding.clone(),
-                            metadata: {
-                                let mut meta = HashMap::new();
-                                meta.insert("filename".to_string(), document.filename.clone());
-                                meta.insert("mime_type".to_string(), document.mime_type.clone());
-                                meta.insert("start_offset".to_string(), chunk.start_offset.to_string());
-                                meta.insert("end_offset".to_string(), chunk.end_offset.to_string());
-                                meta
-                            },
-                        };
-                        vector_docs.push(vector_doc);
-                    }
+        let new_chunks = structure_chunker::chunk_by_structure(&self.document_processor, document_id, &text)?
+            .ok_or_else(|| anyhow!("未识别到编号章节或标题结构，保留原有分块"))?;
+        let sections = new_chunks
+            .iter()
+            .map(|c| &c.section_path)
+            .collect::<std::collections::HashSet<_>>()
+            .len();
+
+        let chunks_after = self
+            .indexer()
+            .replace_text_chunks(&document_id_str, text_chunks[0], &caption_chunks, new_chunks)
+            .await?;
+        if let Some(document) = self.documents.get_mut(&document_id) {
+            document.update_chunk_count(chunks_after as u32);
+        }
 
-                // Store vectors in database
-                {
-                    let mut db = self.vector_db.lock().await;
-                    db.add_documents(vector_docs)?;
-                }
+        log::info!("🧩 [RESTRUCTURE] 文档 {}: {} 个章节, {} -> {} 块",
+            document_id, sections, old_chunks.len(), chunks_after);
 
-                // Update document status
-                document.processing_status = ProcessingStatus::Indexed;
-                document.chunk_count = chunk_count as u32;
-                document.processed_at = Some(chrono::Utc::now());
+        Ok(RestructureReport {
+            document_id: document_id_str,
+            sections,
+            chunks_before: old_chunks.len(),
+            chunks_after,
+        })
+    }
 
-                log::info!("Document indexed successfully: {}", document.filename);
-            }
-            Err(e) => {
-                log::error!("Document processing failed: {}", e);
-                document.processing_status = ProcessingStatus::Failed;
-                document.error_message = Some(e.to_string());
-                return Err(e);
-            }
-        }
+    /// 查找与指定文档内容相近的同项目文档：用各文档所有块向量的平均值（中心）比较相似度
+    pub async fn find_similar_documents(&self, document_id: &str, top_k: usize) -> Result<Vec<SimilarDocument>> {
+        let db = self.vector_db.lock().await;
+        let project_id = db
+            .get_document_project_id(document_id)?
+            .ok_or_else(|| anyhow!("文档不存在或尚未建立索引: {}", document_id))?;
+        let chunks = db.get_project_chunk_embeddings(&project_id)?;
+        drop(db);
 
-        Ok(())
+        rank_similar_documents(&chunks, document_id, top_k)
+    }
+
+    /// 统计项目中某个元数据键（如 category）的所有取值及数量
+    pub async fn get_metadata_values(&self, project_id: &str, key: &str) -> Result<Vec<MetadataValueCount>> {
+        let chunks = {
+            let db = self.vector_db.lock().await;
+            db.get_project_documents(project_id)?
+        };
+        Ok(count_metadata_values(&chunks, key))
     }
 
     pub fn get_document(&self, document_id: Uuid) -> Option<&Document> {
@@ -212,19 +1126,26 @@ impl DocumentService {
         let results = db.similarity_search(
             &query_embedding,
             project_id_str.as_deref(),
+            None,
             limit,
             0.5, // DashScope embedding 质量高，可以设置较高阈值
+            INDEXED_DISTANCE_METRIC,
         )?;
 
         Ok(results)
     }
 
     /// 使用混合检索搜索相关文档块（向量+全文，用于聊天上下文）
+    ///
+    /// `semantic_weight` 为向量检索所占权重（0.0..=1.0）；`metadata_filters` 只检索元数据匹配的块
+    /// （如 `{"filename": "report.pdf"}` 只针对一个文档提问）
     pub async fn search_similar_chunks_hybrid(
         &self,
         project_id: &str,
+        metadata_filters: Option<&HashMap<String, String>>,
         query: &str,
         top_k: usize,
+        semantic_weight: f64,
     ) -> Result<Vec<SimilarChunk>> {
         log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         log::info!("🔍 [HYBRID-SEARCH] 开始混合检索文档块");
@@ -235,21 +1156,21 @@ impl DocumentService {
 
         // 使用 DashScope API 生成查询向量
         log::info!("🌐 调用 DashScope Embedding API...");
-        let query_embedding = self.embedding_service.embed_text(query).await?;
+        let query_embedding = self.embed_query(query).await?;
         log::info!("✅ 生成查询向量成功，维度: {}", query_embedding.len());
 
         // 从向量数据库执行混合搜索
         let db = self.vector_db.lock().await;
 
-        log::info!("🔄 执行混合检索（语义权重=0.7）...");
+        log::info!("🔄 执行混合检索（语义权重={}）...", semantic_weight);
 
-        // 使用混合检索 (语义权重 0.7 表示更偏重向量相似度)
         let results = db.hybrid_search(
             query,
             &query_embedding,
             Some(project_id),
+            metadata_filters,
             top_k,
-            0.7, // semantic boost: 0.7 表示向量检索占 70% 权重
+            semantic_weight,
         )?;
 
         log::info!("✅ 混合检索完成，找到 {} 个结果", results.len());
@@ -269,19 +1190,9 @@ impl DocumentService {
         let chunks: Vec<SimilarChunk> = results
             .iter()
             .map(|result| {
-                // 从 metadata 中获取 filename
-                let filename = result.document.metadata
-                    .get("filename")
-                    .cloned();
-
-                log::debug!("文档 {} 的 filename: {:?}", result.document.document_id, filename);
-
-                SimilarChunk {
-                    document_id: result.document.document_id.clone(),
-                    filename,
-                    content: result.document.content.clone(),
-                    relevance_score: result.similarity,
-                }
+                let chunk = SimilarChunk::from_search_result(result, result.similarity);
+                log::debug!("文档 {} 的 filename: {:?}", chunk.document_id, chunk.filename);
+                chunk
             })
             .collect();
 
@@ -292,34 +1203,91 @@ impl DocumentService {
         Ok(chunks)
     }
 
+    /// 生成查询向量；服务无法访问时返回 `EmbeddingUnavailable`，调用方可据此改用关键词检索，
+    /// 其他错误（如 API Key 无效）原样返回
+    async fn embed_query(&self, query: &str) -> Result<Vec<f64>> {
+        self.embedding_service.embed_text(query).await.map_err(|e| {
+            if is_embedding_service_unreachable(&e) {
+                EmbeddingUnavailable(format!("{:#}", e)).into()
+            } else {
+                e
+            }
+        })
+    }
+
+    /// 只用全文索引检索（不需要查询向量），用于 embedding 服务不可用时的降级
+    ///
+    /// 关键词相关度没有上限，与向量相似度不可比：除以本次查询的最高分归一化到 0~1，不应用相似度阈值；
+    /// 多个项目时归一化后的分数乘以项目权重再合并
+    pub async fn search_keyword_chunks(
+        &self,
+        projects: &[ProjectWeight],
+        metadata_filters: Option<&HashMap<String, String>>,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<SimilarChunk>> {
+        log::info!("🔤 关键词检索: {} 个项目, query={}, top_k={}", projects.len(), query, top_k);
+
+        let mut per_project = Vec::with_capacity(projects.len());
+        {
+            let db = self.vector_db.lock().await;
+            for project in projects {
+                let results = db.fulltext_search(query, Some(&project.project_id), metadata_filters, top_k)?;
+                per_project.push((project.weight, results));
+            }
+        }
+
+        let max_score = per_project
+            .iter()
+            .flat_map(|(_, results)| results.iter().map(|result| result.similarity))
+            .fold(0.0, f64::max);
+        let mut chunks: Vec<SimilarChunk> = per_project
+            .iter()
+            .flat_map(|(weight, results)| {
+                results.iter().map(move |result| {
+                    let score = if max_score > 0.0 { result.similarity / max_score } else { 0.0 };
+                    SimilarChunk::from_search_result(result, score * weight)
+                })
+            })
+            .collect();
+        chunks.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+        chunks.truncate(top_k);
+
+        log::info!("✅ 关键词检索完成，找到 {} 个结果", chunks.len());
+        Ok(chunks)
+    }
+
     // 搜索相关文档块（用于聊天上下文）- 保留纯向量搜索作为备选
     pub async fn search_similar_chunks(
         &self,
         project_id: &str,
+        metadata_filters: Option<&HashMap<String, String>>,
         query: &str,
         top_k: usize,
     ) -> Result<Vec<SimilarChunk>> {
         log::info!("🔍 开始搜索相关文档块: project_id={}, query={}, top_k={}", project_id, query, top_k);
 
         // 使用 DashScope API 生成查询向量
-        let query_embedding = self.embedding_service.embed_text(query).await?;
+        let query_embedding = self.embed_query(query).await?;
         log::info!("✅ 生成查询向量成功，维度: {}", query_embedding.len());
 
         // 从向量数据库搜索
         let db = self.vector_db.lock().await;
 
-        log::info!("🔍 使用SeekDB向量检索，阈值=0.3");
+        log::info!("🔍 使用SeekDB向量检索，阈值策略={:?}", self.score_threshold);
 
         // 使用 DashScope embedding，相似度通常在 0.3-0.9 之间
-        // 0.3 作为阈值可以获得较宽泛但相关的结果
         let results = db.similarity_search(
             &query_embedding,
             Some(project_id),
+            metadata_filters,
             top_k,
-            0.3, // DashScope embedding: 0.3=宽泛, 0.4=中等, 0.5+=严格
+            self.score_threshold.absolute_cutoff(),
+            INDEXED_DISTANCE_METRIC,
         )?;
+        let results = select_by_score(results, |r| r.similarity, self.score_threshold, top_k);
 
-        log::info!("✅ 向量搜索完成（阈值=0.3），找到 {} 个结果", results.len());
+        log::info!("✅ 向量搜索完成，找到 {} 个结果", results.len());
 
         // 打印前几个结果的相似度分数
         for (i, result) in results.iter().take(3).enumerate() {
@@ -334,47 +1302,177 @@ impl DocumentService {
         let chunks: Vec<SimilarChunk> = results
             .iter()
             .map(|result| {
-                // 从 metadata 中获取 filename
-                let filename = result.document.metadata
-                    .get("filename")
-                    .cloned();
-
-                log::debug!("文档 {} 的 filename: {:?}", result.document.document_id, filename);
-
-                SimilarChunk {
-                    document_id: result.document.document_id.clone(),
-                    filename,
-                    content: result.document.content.clone(),
-                    relevance_score: result.similarity,
-                }
+                let chunk = SimilarChunk::from_search_result(result, result.similarity);
+                log::debug!("文档 {} 的 filename: {:?}", chunk.document_id, chunk.filename);
+                chunk
             })
             .collect();
 
-        Ok(chunks)
+        Ok(chunks)
+    }
+
+    /// 跨多个项目检索：每个项目分别做向量检索，分数乘以项目权重后合并，再统一筛选 top_k
+    pub async fn search_similar_chunks_weighted(
+        &self,
+        projects: &[ProjectWeight],
+        metadata_filters: Option<&HashMap<String, String>>,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<SimilarChunk>> {
+        log::info!("🔍 多项目检索: {} 个项目, query={}, top_k={}", projects.len(), query, top_k);
+
+        let query_embedding = self.embed_query(query).await?;
+
+        let mut per_project = Vec::with_capacity(projects.len());
+        {
+            let db = self.vector_db.lock().await;
+            for project in projects {
+                let results = db.similarity_search(
+                    &query_embedding,
+                    Some(&project.project_id),
+                    metadata_filters,
+                    top_k,
+                    self.score_threshold.absolute_cutoff(),
+                    INDEXED_DISTANCE_METRIC,
+                )?;
+                log::info!("  📁 项目 {} (权重 {}): {} 个结果", project.project_id, project.weight, results.len());
+                per_project.push((project.weight, results));
+            }
+        }
+
+        Ok(merge_weighted_results(per_project, self.score_threshold, top_k))
+    }
+
+    pub fn list_documents(&self, project_id: Option<Uuid>) -> Vec<&Document> {
+        self.documents
+            .values()
+            .filter(|doc| {
+                if let Some(pid) = project_id {
+                    doc.project_id == pid
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
+    /// 删除文档及其在数据库中的所有文档块，删除后不会再被检索到；返回文档所属的项目ID
+    ///
+    /// 不在内存中的文档（如重启后未重新加载）按数据库中的块确定所属项目
+    pub async fn delete_document(&mut self, document_id: Uuid) -> Result<Uuid> {
+        let document_id_str = document_id.to_string();
+        let mut db = self.vector_db.lock().await;
+
+        let project_id = match self.documents.get(&document_id) {
+            Some(document) => Some(document.project_id),
+            None => db
+                .get_document_project_id(&document_id_str)?
+                .and_then(|project_id| Uuid::parse_str(&project_id).ok()),
+        }
+        .ok_or_else(|| anyhow!("Document not found: {}", document_id))?;
+
+        let deleted_chunks = db.delete_document(&document_id_str)?;
+        drop(db);
+
+        self.documents.remove(&document_id);
+        log::info!("🗑️  已删除文档 {}（{} 个文档块）", document_id, deleted_chunks);
+        Ok(project_id)
+    }
+
+    /// 当前使用的 embedding 模型
+    pub fn embedding_model(&self) -> &str {
+        self.embedding_service.model()
+    }
+
+    /// embedding 服务的句柄，用于不持有 DocumentService 锁的后台请求（如启动预热）
+    pub fn embedding_service(&self) -> Arc<dyn EmbeddingProvider> {
+        self.embedding_service.clone()
+    }
+
+    /// 从数据库中的文档块还原文档内容
+    pub async fn get_document_content(&self, document_id: Uuid) -> Result<DocumentContent> {
+        let document_id = document_id.to_string();
+        let chunks = self.vector_db.lock().await.get_document_chunks(&document_id)?;
+
+        DocumentContent::from_chunks(&document_id, &chunks)
+            .ok_or_else(|| anyhow!("Document not found: {}", document_id))
     }
 
-    pub fn list_documents(&self, project_id: Option<Uuid>) -> Vec<&Document> {
-        self.documents
-            .values()
-            .filter(|doc| {
-                if let Some(pid) = project_id {
-                    doc.project_id == pid
-                } else {
-                    true
+    /// 清理项目中摄取失败的文档在数据库中残留的部分文档块
+    ///
+    /// 摄取过程中出错时，已提交的块会留在数据库中并参与检索。失败的文档从数据库中识别
+    /// （块数少于块 metadata 记录的总块数），因此重启后也能找到；本次运行中标记为 Failed 的文档同样会清理。
+    /// 清理后文档为 Failed 状态，可以重新上传或重新处理
+    pub async fn cleanup_partial_documents(&mut self, project_id: Uuid) -> Result<PartialCleanupReport> {
+        let mut report = PartialCleanupReport::default();
+        let mut db = self.vector_db.lock().await;
+
+        let chunks = db.get_project_documents(&project_id.to_string())?;
+        let mut document_ids = partial_document_ids(&chunks);
+        for document in self.documents.values().filter(|doc| {
+            doc.project_id == project_id && doc.processing_status == ProcessingStatus::Failed
+        }) {
+            let document_id = document.id.to_string();
+            if !document_ids.contains(&document_id) {
+                document_ids.push(document_id);
+            }
+        }
+
+        for document_id in document_ids {
+            report.failed_documents += 1;
+            let removed = db.delete_document(&document_id)?;
+            if removed > 0 {
+                log::info!("🧹 清理失败文档 {} 的 {} 个残留文档块", document_id, removed);
+                report.cleaned_documents += 1;
+                report.removed_chunks += removed;
+            }
+            if let Some(document) = Uuid::parse_str(&document_id).ok().and_then(|id| self.documents.get_mut(&id)) {
+                document.chunk_count = 0;
+                if document.processing_status != ProcessingStatus::Failed {
+                    document.update_processing_status(ProcessingStatus::Failed, Some("摄取未完成，残留的文档块已清理".to_string()));
                 }
-            })
-            .collect()
+            }
+        }
+
+        log::info!(
+            "🧹 项目 {} 清理完成: 失败文档 {} 个，清理 {} 个，删除文档块 {} 个",
+            project_id,
+            report.failed_documents,
+            report.cleaned_documents,
+            report.removed_chunks
+        );
+        Ok(report)
     }
 
-    pub fn delete_document(&mut self, document_id: Uuid) -> Result<()> {
-        let _document = self.documents
-            .remove(&document_id)
-            .ok_or_else(|| anyhow!("Document not found: {}", document_id))?;
+    /// 清空 embedding 服务的持久化缓存（`embedding.persistentCache`），返回删除的条目数
+    pub async fn clear_embedding_cache(&self) -> Result<usize> {
+        let removed = self.embedding_service.clear_cache()?;
+        log::info!("🗑️  已清空 embedding 缓存: {} 条", removed);
+        Ok(removed)
+    }
 
-        // TODO: Delete from vector database
-        // self.vector_db.delete_documents(&collection_name, &[document_id.to_string()]).await?;
+    /// 收集项目内需要重新处理的文档（包括重启后只存在于数据库中的文档），按文件名排序
+    ///
+    /// 用于更换 embedding 模型后重建向量；实际处理由 `DocumentIndexer::reprocess_documents`
+    /// 在锁外完成
+    pub async fn documents_for_reprocess(&mut self, project_id: Uuid) -> Result<Vec<Document>> {
+        let chunks = {
+            let db = self.vector_db.lock().await;
+            db.get_project_documents(&project_id.to_string())?
+        };
+        for document in reconstruct_documents(&chunks, &self.documents) {
+            self.documents.entry(document.id).or_insert(document);
+        }
 
-        Ok(())
+        let mut documents: Vec<Document> = self
+            .documents
+            .values()
+            .filter(|doc| doc.project_id == project_id)
+            .cloned()
+            .collect();
+        documents.sort_by(|a, b| a.filename.cmp(&b.filename));
+        log::info!("🔁 [REPROCESS] 项目 {}: 重新处理 {} 个文档", project_id, documents.len());
+        Ok(documents)
     }
 
     pub fn get_documents_by_status(&self, status: ProcessingStatus) -> Vec<&Document> {
@@ -433,6 +1531,24 @@ impl DocumentService {
         }
     }
 
+    /// 根据数据库中的文档块重建内存中的文档列表，返回文档数
+    ///
+    /// 用于数据库被外部修改后同步内存；已在内存中的文档保留原有信息，只更新块数，
+    /// 还没有块的上传中和处理失败的文档保持不变
+    pub async fn reload_from_database(&mut self, project_ids: &[Uuid]) -> Result<usize> {
+        let mut chunks = Vec::new();
+        {
+            let db = self.vector_db.lock().await;
+            for project_id in project_ids {
+                chunks.extend(db.get_project_documents(&project_id.to_string())?);
+            }
+        }
+
+        self.documents = reloaded_documents(&chunks, &self.documents, project_ids);
+        log::info!("📚 从数据库重建了 {} 个文档", self.documents.len());
+        Ok(self.documents.len())
+    }
+
     pub fn get_processing_stats(&self, project_id: Option<Uuid>) -> HashMap<ProcessingStatus, usize> {
         let mut stats = HashMap::new();
 
@@ -458,6 +1574,367 @@ impl DocumentService {
     }
 }
 
+/// 按分数策略筛选检索结果，并最多保留 `top_k` 个（结果按分数降序返回）
+pub fn select_by_score<T, F>(mut items: Vec<T>, score: F, threshold: ScoreThreshold, top_k: usize) -> Vec<T>
+where
+    F: Fn(&T) -> f64,
+{
+    items.sort_by(|a, b| score(b).partial_cmp(&score(a)).unwrap_or(std::cmp::Ordering::Equal));
+
+    let cutoff = match threshold {
+        ScoreThreshold::Fixed(min_score) => min_score,
+        ScoreThreshold::Adaptive { gap } => match items.first() {
+            Some(top) => score(top) - gap,
+            None => return items,
+        },
+    };
+
+    items.retain(|item| score(item) >= cutoff);
+    items.truncate(top_k);
+    items
+}
+
+/// 临时数据库（或其 schema 锁文件）是否仍被其他实例使用：
+/// 文件名中的进程仍在运行，或者有实例持有未过期的 schema 锁
+pub(crate) fn temp_db_in_use(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let db_path = path.to_string_lossy();
+    let db_path = db_path.strip_suffix(".schema.lock").unwrap_or(&db_path);
+    if crate::services::seekdb_adapter::schema_lock_held(db_path) {
+        return true;
+    }
+
+    name.strip_prefix(TEMP_DB_PREFIX)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .and_then(|rest| rest.split('.').next())
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(process_alive)
+}
+
+/// 进程是否仍在运行；无法判断的平台返回 false（Windows 上被打开的文件本身也无法删除）
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let proc_dir = Path::new("/proc");
+    if proc_dir.is_dir() {
+        return proc_dir.join(pid.to_string()).exists();
+    }
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+/// 用于生成项目向量的文本：名称 + 描述
+pub fn project_description_text(project: &Project) -> String {
+    match project.description.as_deref().map(str::trim) {
+        Some(description) if !description.is_empty() => format!("{}\n{}", project.name, description),
+        _ => project.name.clone(),
+    }
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// 启动预热时发送的文本
+pub const WARM_UP_TEXT: &str = "warm-up";
+
+/// 启动时预热 embedding 服务：发送一次请求建立连接，同时尽早发现服务配置错误
+///
+/// 未启用时不发送请求并返回 None，否则返回预热耗时或请求错误
+pub async fn warm_up_embedding_with<E, Fut>(enabled: bool, embed: E) -> Option<Result<Duration>>
+where
+    E: FnOnce(&'static str) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<f64>>>,
+{
+    if !enabled {
+        return None;
+    }
+    let started = std::time::Instant::now();
+    Some(embed(WARM_UP_TEXT).await.map(|_| started.elapsed()))
+}
+
+/// 启动完成后在后台预热 embedding 服务，不阻塞启动，失败只记录警告
+///
+/// 没有配置文件或配置了 `embedding.skipWarmUp` 时跳过预热
+pub fn spawn_embedding_warm_up(
+    config: Option<&AppConfig>,
+    embedding_service: Arc<dyn EmbeddingProvider>,
+) -> tokio::task::JoinHandle<()> {
+    let enabled = config.is_some_and(|config| config.embedding_warm_up_enabled());
+    tokio::spawn(async move {
+        let outcome = warm_up_embedding_with(enabled, |text| async move {
+            embedding_service.embed_text_uncached(text).await
+        })
+        .await;
+        match outcome {
+            Some(Ok(elapsed)) => log::info!("🔥 Embedding 服务预热完成，耗时 {:?}", elapsed),
+            Some(Err(e)) => log::warn!("⚠️  Embedding 服务预热失败，请检查 embedding 配置: {}", e),
+            None => log::info!("已跳过 embedding 服务预热"),
+        }
+    })
+}
+
+/// 查询缺少向量的块、用 `embed` 按内容生成向量并写回；生成向量期间不持有数据库锁
+pub async fn fill_missing_embeddings_with<E, Fut>(
+    vector_db: &Arc<Mutex<SeekDbAdapter>>,
+    project_id: &str,
+    embed: E,
+) -> Result<EmbeddingFillReport>
+where
+    E: FnOnce(Vec<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<Vec<f64>>>>,
+{
+    let mut chunks = vector_db.lock().await.get_chunks_missing_embeddings(project_id)?;
+    let mut report = EmbeddingFillReport {
+        missing_chunks: chunks.len(),
+        filled_chunks: 0,
+    };
+    if chunks.is_empty() {
+        log::info!("✅ 项目 {} 没有缺少向量的文档块", project_id);
+        return Ok(report);
+    }
+
+    log::info!("🧩 项目 {} 有 {} 个文档块缺少向量，重新生成...", project_id, chunks.len());
+    let embeddings = embed(chunks.iter().map(|chunk| chunk.content.clone()).collect()).await?;
+    if embeddings.len() != chunks.len() {
+        return Err(anyhow!("生成的向量数量 ({}) 与文档块数量 ({}) 不一致", embeddings.len(), chunks.len()));
+    }
+    for (chunk, embedding) in chunks.iter_mut().zip(embeddings) {
+        chunk.embedding = embedding;
+    }
+
+    report.filled_chunks = vector_db.lock().await.fill_chunk_embeddings(&chunks)?;
+    log::info!("✅ 已补全 {}/{} 个文档块的向量", report.filled_chunks, report.missing_chunks);
+    Ok(report)
+}
+
+/// 把各项目的检索结果按权重调整分数后合并，按调整后的分数筛选 top_k
+pub fn merge_weighted_results(
+    per_project: Vec<(f64, Vec<SearchResult>)>,
+    threshold: ScoreThreshold,
+    top_k: usize,
+) -> Vec<SimilarChunk> {
+    let weighted: Vec<SimilarChunk> = per_project
+        .iter()
+        .flat_map(|(weight, results)| {
+            results
+                .iter()
+                .map(move |result| SimilarChunk::from_search_result(result, result.similarity * weight))
+        })
+        .collect();
+
+    select_by_score(weighted, |chunk| chunk.relevance_score, threshold, top_k)
+}
+
+/// 统计文档块中元数据 `key` 的不同取值，按文档数降序、取值升序排列；空值不计入
+pub fn count_metadata_values(chunks: &[VectorDocument], key: &str) -> Vec<MetadataValueCount> {
+    let mut counts: HashMap<&str, (std::collections::HashSet<&str>, usize)> = HashMap::new();
+    for chunk in chunks {
+        let Some(value) = chunk.metadata.get(key).map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let (documents, chunk_count) = counts.entry(value).or_default();
+        documents.insert(chunk.document_id.as_str());
+        *chunk_count += 1;
+    }
+
+    let mut values: Vec<MetadataValueCount> = counts
+        .into_iter()
+        .map(|(value, (documents, chunk_count))| MetadataValueCount {
+            value: value.to_string(),
+            document_count: documents.len(),
+            chunk_count,
+        })
+        .collect();
+    values.sort_by(|a, b| b.document_count.cmp(&a.document_count).then_with(|| a.value.cmp(&b.value)));
+    values
+}
+
+/// 块数少于块 metadata 中记录的总块数（摄取中途失败）的文档，按 document_id 排序
+///
+/// 没有记录总块数的旧文档块无法判断，视为完整
+pub fn partial_document_ids(chunks: &[VectorDocument]) -> Vec<String> {
+    let mut stored: HashMap<&str, (usize, Option<usize>)> = HashMap::new();
+    for chunk in chunks {
+        let entry = stored.entry(chunk.document_id.as_str()).or_default();
+        entry.0 += 1;
+        if let Some(total) = chunk.metadata.get(CHUNK_TOTAL_KEY).and_then(|total| total.parse().ok()) {
+            entry.1 = Some(entry.1.map_or(total, |current: usize| current.max(total)));
+        }
+    }
+
+    let mut partial: Vec<String> = stored
+        .into_iter()
+        .filter(|(_, (count, total))| total.is_some_and(|total| *count < total))
+        .map(|(document_id, _)| document_id.to_string())
+        .collect();
+    partial.sort();
+    partial
+}
+
+/// 源文件不存在时返回失败原因（无法重新处理）
+fn missing_source_error(file_path: &str) -> Option<String> {
+    (!Path::new(file_path).is_file()).then(|| format!("源文件不存在: {}", file_path))
+}
+
+/// 按 document_id 把文档块归并为文档：`existing` 中已有的文档沿用原有信息，
+/// 其余文档的文件名、类型和文件大小取自块元数据；没有记录文件大小的旧文档块按块内容估算
+pub fn reconstruct_documents(chunks: &[VectorDocument], existing: &HashMap<Uuid, Document>) -> Vec<Document> {
+    let mut grouped: HashMap<Uuid, Vec<&VectorDocument>> = HashMap::new();
+    for chunk in chunks {
+        match Uuid::parse_str(&chunk.document_id) {
+            Ok(document_id) => grouped.entry(document_id).or_default().push(chunk),
+            Err(_) => log::warn!("⚠️  跳过文档ID无效的块: {}", chunk.id),
+        }
+    }
+
+    let mut documents: Vec<Document> = grouped
+        .into_iter()
+        .filter_map(|(document_id, chunks)| {
+            let chunk_count = chunks.len() as u32;
+            if let Some(document) = existing.get(&document_id) {
+                let mut document = document.clone();
+                document.chunk_count = chunk_count;
+                return Some(document);
+            }
+
+            let first = chunks[0];
+            let project_id = Uuid::parse_str(&first.project_id).ok()?;
+            let filename = first.metadata.get("filename").cloned().unwrap_or_else(|| document_id.to_string());
+            Some(Document {
+                id: document_id,
+                project_id,
+                file_path: filename.clone(),
+                mime_type: first.metadata.get("mime_type").cloned().unwrap_or_else(|| "text/plain".to_string()),
+                filename,
+                file_size: first
+                    .metadata
+                    .get(CHUNK_FILE_SIZE_KEY)
+                    .and_then(|size| size.parse().ok())
+                    .unwrap_or_else(|| chunks.iter().map(|c| c.content.len() as u64).sum()),
+                content_hash: String::new(),
+                chunk_count,
+                processing_status: ProcessingStatus::Indexed,
+                error_message: None,
+                created_at: chrono::Utc::now(),
+                processed_at: None,
+            })
+        })
+        .collect();
+    documents.sort_by(|a, b| a.filename.cmp(&b.filename));
+    documents
+}
+
+/// 重新加载后的内存文档：从数据库中的块重建，再保留这些项目中还没有块的未完成文档
+///
+/// 上传中（Uploaded/Processing）和处理失败的文档在数据库中没有块（或只有部分块），
+/// `reconstruct_documents` 无法重建它们，直接丢弃会使进行中的上传和失败记录从内存中消失
+fn reloaded_documents(chunks: &[VectorDocument], existing: &HashMap<Uuid, Document>, project_ids: &[Uuid]) -> HashMap<Uuid, Document> {
+    let mut documents: HashMap<Uuid, Document> = reconstruct_documents(chunks, existing)
+        .into_iter()
+        .map(|document| (document.id, document))
+        .collect();
+    for document in existing.values() {
+        if document.processing_status != ProcessingStatus::Indexed && project_ids.contains(&document.project_id) {
+            documents.entry(document.id).or_insert_with(|| document.clone());
+        }
+    }
+    documents
+}
+
+/// 多个向量的平均值；维度不一致的向量会被忽略
+fn centroid<'a>(embeddings: impl IntoIterator<Item = &'a [f64]>) -> Option<Vec<f64>> {
+    let mut sum: Vec<f64> = Vec::new();
+    let mut count = 0usize;
+    for embedding in embeddings {
+        if sum.is_empty() {
+            sum = vec![0.0; embedding.len()];
+        } else if embedding.len() != sum.len() {
+            continue;
+        }
+        sum.iter_mut().zip(embedding).for_each(|(s, v)| *s += v);
+        count += 1;
+    }
+
+    (count > 0).then(|| sum.into_iter().map(|s| s / count as f64).collect())
+}
+
+/// 计算各文档的向量中心，返回与 `document_id` 最相似的其他文档（降序，最多 `top_k` 个）
+pub fn rank_similar_documents(
+    chunks: &[VectorDocument],
+    document_id: &str,
+    top_k: usize,
+) -> Result<Vec<SimilarDocument>> {
+    let mut by_document: HashMap<&str, Vec<&VectorDocument>> = HashMap::new();
+    for chunk in chunks {
+        by_document.entry(chunk.document_id.as_str()).or_default().push(chunk);
+    }
+
+    let target = by_document
+        .get(document_id)
+        .and_then(|chunks| centroid(chunks.iter().map(|c| c.embedding.as_slice())))
+        .ok_or_else(|| anyhow!("文档没有可用的向量: {}", document_id))?;
+
+    let mut similar: Vec<SimilarDocument> = by_document
+        .iter()
+        .filter(|(id, _)| **id != document_id)
+        .filter_map(|(id, chunks)| {
+            let center = centroid(chunks.iter().map(|c| c.embedding.as_slice()))?;
+            Some(SimilarDocument {
+                document_id: id.to_string(),
+                filename: chunks[0].metadata.get("filename").cloned(),
+                score: cosine_similarity(&target, &center),
+            })
+        })
+        .collect();
+
+    similar.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    similar.truncate(top_k);
+    Ok(similar)
+}
+
+/// 可作为问题路由目标的项目：已归档的项目不参与路由
+fn routable_projects(projects: &[Project]) -> impl Iterator<Item = &Project> {
+    projects.iter().filter(|project| !project.is_archived())
+}
+
+/// 按与查询向量的余弦相似度降序排列项目，返回前 `limit` 个
+pub fn rank_projects_by_similarity(
+    query_embedding: &[f64],
+    candidates: &[(Uuid, Vec<f64>)],
+    limit: usize,
+) -> Vec<ProjectRoute> {
+    let mut routes: Vec<ProjectRoute> = candidates
+        .iter()
+        .map(|(project_id, embedding)| ProjectRoute {
+            project_id: *project_id,
+            score: cosine_similarity(query_embedding, embedding),
+        })
+        .collect();
+
+    routes.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    routes.truncate(limit);
+    routes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -473,6 +1950,71 @@ mod tests {
         assert_eq!(service.documents.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_warm_up_sends_one_embedding_request_only_when_enabled() {
+        let calls = std::sync::Mutex::new(Vec::new());
+        let embed = |text: &'static str| {
+            calls.lock().unwrap().push(text);
+            async { Ok(vec![0.1; 4]) }
+        };
+
+        let outcome = warm_up_embedding_with(true, embed).await;
+        assert!(matches!(outcome, Some(Ok(_))));
+        assert_eq!(*calls.lock().unwrap(), vec![WARM_UP_TEXT]);
+
+        // 跳过预热时不发送请求
+        assert!(warm_up_embedding_with(false, embed).await.is_none());
+        assert_eq!(calls.lock().unwrap().len(), 1);
+
+        // 预热失败时把错误交给调用方记录
+        let failed = warm_up_embedding_with(true, |_| async { Err(anyhow!("401 Unauthorized")) }).await;
+        assert!(matches!(failed, Some(Err(_))));
+    }
+
+    /// 记录 `embed_text_uncached` 收到的文本
+    #[derive(Default)]
+    struct RecordingProvider {
+        uncached_calls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl EmbeddingProvider for RecordingProvider {
+        fn embed_text<'a>(&'a self, _text: &'a str) -> futures::future::BoxFuture<'a, Result<Vec<f64>>> {
+            Box::pin(async { Ok(vec![0.1; 4]) })
+        }
+
+        fn embed_batch<'a>(&'a self, texts: &'a [String]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f64>>>> {
+            Box::pin(async move { Ok(vec![vec![0.1; 4]; texts.len()]) })
+        }
+
+        fn embed_text_uncached<'a>(&'a self, text: &'a str) -> futures::future::BoxFuture<'a, Result<Vec<f64>>> {
+            self.uncached_calls.lock().unwrap().push(text.to_string());
+            self.embed_text(text)
+        }
+
+        fn embedding_dim(&self) -> usize {
+            4
+        }
+
+        fn model(&self) -> &str {
+            "recording"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_startup_warm_up_calls_the_embedding_service() {
+        let provider = Arc::new(RecordingProvider::default());
+        let config = AppConfig::default_config();
+        spawn_embedding_warm_up(Some(&config), provider.clone()).await.unwrap();
+        assert_eq!(*provider.uncached_calls.lock().unwrap(), vec![WARM_UP_TEXT.to_string()]);
+
+        // 配置跳过预热或没有配置文件时不调用 embedding 服务
+        let mut skipped = AppConfig::default_config();
+        skipped.embedding = Some(EmbeddingConfig { skip_warm_up: true, ..Default::default() });
+        spawn_embedding_warm_up(Some(&skipped), provider.clone()).await.unwrap();
+        spawn_embedding_warm_up(None, provider.clone()).await.unwrap();
+        assert_eq!(provider.uncached_calls.lock().unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_add_document() {
         let mut service = create_test_service();
@@ -520,6 +2062,505 @@ mod tests {
         assert!(stats.is_empty());
     }
 
+    #[test]
+    fn test_cleanup_stale_temp_dbs() {
+        // 使用不可能存在的进程号，避免被当作仍在运行的实例
+        let dir = tempfile::tempdir().unwrap();
+        let stale = dir.path().join("mine_kb_temp_4000000001.db");
+        let fresh = dir.path().join("mine_kb_temp_4000000002.db");
+        let unrelated = dir.path().join("other.db");
+        for path in [&stale, &fresh, &unrelated] {
+            std::fs::write(path, b"data").unwrap();
+        }
+
+        let two_days_ago = std::time::SystemTime::now() - Duration::from_secs(48 * 3600);
+        for path in [&stale, &unrelated] {
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(two_days_ago)
+                .unwrap();
+        }
+
+        let removed = DocumentService::cleanup_stale_temp_dbs(dir.path(), Duration::from_secs(24 * 3600));
+        assert_eq!(removed, 1);
+        assert!(!stale.exists());
+        assert!(fresh.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[test]
+    fn test_only_unreachable_embedding_service_falls_back_to_keywords() {
+        assert!(is_embedding_service_unreachable(&anyhow!("error sending request: connection refused")));
+        assert!(is_embedding_service_unreachable(&anyhow!("DashScope API 调用失败 [429]: Throttling")));
+        assert!(is_embedding_service_unreachable(&anyhow!("DashScope API 调用失败 [502]: Bad Gateway")));
+        assert!(!is_embedding_service_unreachable(&anyhow!("DashScope API 调用失败 [401]: Invalid API-key provided")));
+        assert!(!is_embedding_service_unreachable(&anyhow!("OpenAI Embedding API 调用失败 [400]: input is too long")));
+    }
+
+    #[test]
+    fn test_temp_db_of_live_process_or_with_held_lock_is_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let own = DocumentService::temp_db_path(dir.path());
+        assert!(temp_db_in_use(&own));
+
+        let orphan = dir.path().join("mine_kb_temp_4000000001.db");
+        assert!(!temp_db_in_use(&orphan));
+        std::fs::write(dir.path().join("mine_kb_temp_4000000001.db.schema.lock"), b"").unwrap();
+        assert!(temp_db_in_use(&orphan));
+        assert!(temp_db_in_use(&dir.path().join("mine_kb_temp_4000000001.db.schema.lock")));
+    }
+
+    #[test]
+    fn test_temp_db_path_is_unique_per_process() {
+        let path = DocumentService::temp_db_path(Path::new("/tmp"));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(TEMP_DB_PREFIX));
+        assert!(name.contains(&std::process::id().to_string()));
+    }
+
+    #[test]
+    fn test_query_routes_to_project_with_matching_description() {
+        use crate::services::simple_embeddings::SimpleEmbeddingService;
+
+        let projects = vec![
+            Project::new("Cooking".to_string(), Some("Recipes for pasta, sauces and baking bread".to_string())).unwrap(),
+            Project::new("Rust".to_string(), Some("Notes about rust ownership, borrowing and lifetimes".to_string())).unwrap(),
+            Project::new("Travel".to_string(), None).unwrap(),
+        ];
+
+        let texts: Vec<String> = projects.iter().map(project_description_text).collect();
+        let mut embedder = SimpleEmbeddingService::new(256);
+        embedder.train(&texts).unwrap();
+
+        let candidates: Vec<(Uuid, Vec<f64>)> = projects
+            .iter()
+            .zip(&texts)
+            .map(|(project, text)| (project.id, embedder.embed_text(text).unwrap()))
+            .collect();
+        let query = embedder.embed_text("How does borrowing interact with lifetimes?").unwrap();
+
+        let routes = rank_projects_by_similarity(&query, &candidates, 2);
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].project_id, projects[1].id);
+        assert!(routes[0].score > routes[1].score);
+    }
+
+    #[test]
+    fn test_archived_projects_are_not_routed() {
+        let mut archived = Project::new("Rust".to_string(), Some("Old rust notes".to_string())).unwrap();
+        archived.update_status(crate::models::project::ProjectStatus::Archived);
+        let active = Project::new("Cooking".to_string(), None).unwrap();
+        let projects = vec![archived, active.clone()];
+
+        let routable: Vec<Uuid> = routable_projects(&projects).map(|project| project.id).collect();
+        assert_eq!(routable, vec![active.id]);
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 API Key 和 SeekDB
+    async fn test_deleted_document_chunks_are_no_longer_searchable() {
+        let api_key = std::env::var("DASHSCOPE_API_KEY")
+            .expect("需要设置 DASHSCOPE_API_KEY 环境变量");
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("delete_document.db").display().to_string();
+        let mut service = DocumentService::with_config(&db_path, api_key, None).await.unwrap();
+
+        let content = "SeekDB 是一个同时支持向量检索和全文检索的嵌入式数据库。";
+        let file_path = dir.path().join("seekdb.md");
+        std::fs::write(&file_path, content).unwrap();
+
+        let project_id = Uuid::new_v4();
+        let hash = service.hash_content(content.as_bytes());
+        let document_id = service
+            .add_document(project_id, file_path.display().to_string(), content.len() as u64, hash)
+            .await
+            .unwrap();
+
+        let query = "SeekDB 支持哪些检索方式";
+        let returns_document = |chunks: &[SimilarChunk]| chunks.iter().any(|c| c.document_id == document_id.to_string());
+
+        let before = service.search_similar_chunks(&project_id.to_string(), None, query, 5).await.unwrap();
+        assert!(returns_document(&before));
+
+        assert_eq!(service.delete_document(document_id).await.unwrap(), project_id);
+        assert!(service.get_document(document_id).is_none());
+
+        let after = service.search_similar_chunks(&project_id.to_string(), None, query, 5).await.unwrap();
+        assert!(!returns_document(&after));
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_cleanup_removes_chunks_of_partially_ingested_failed_document() {
+        use crate::services::seekdb_adapter::SCHEMA_EMBEDDING_DIMENSION;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("cleanup_partial.db").display().to_string();
+        let mut service = DocumentService::with_config(&db_path, "test-key".to_string(), None).await.unwrap();
+
+        // 模拟摄取中途失败：文档已标记为 Failed，但前两个块已经提交
+        let project_id = Uuid::new_v4();
+        let mut document = Document::new(project_id, "partial.md".to_string(), 100, "hash".to_string()).unwrap();
+        document.processing_status = ProcessingStatus::Failed;
+        let chunk = |document_id: &str, index: i32, total: Option<usize>| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: index,
+            content: format!("残留块 {}", index),
+            embedding: vec![0.1; SCHEMA_EMBEDDING_DIMENSION],
+            metadata: total.map(|total| HashMap::from([(CHUNK_TOTAL_KEY.to_string(), total.to_string())])).unwrap_or_default(),
+        };
+        // 重启前失败的文档只存在于数据库中：应有 3 块，只提交了 2 块
+        let (crashed, complete) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        let document_id = document.id.to_string();
+        service.get_vector_db().lock().await.add_documents(vec![
+            chunk(&document_id, 0, None),
+            chunk(&document_id, 1, None),
+            chunk(&crashed, 0, Some(3)),
+            chunk(&crashed, 1, Some(3)),
+            chunk(&complete, 0, Some(1)),
+        ]).unwrap();
+        service.documents.insert(document.id, document);
+
+        let report = service.cleanup_partial_documents(project_id).await.unwrap();
+        assert_eq!(
+            report,
+            PartialCleanupReport { failed_documents: 2, cleaned_documents: 2, removed_chunks: 4 }
+        );
+        let remaining = service.get_vector_db().lock().await.get_project_documents(&project_id.to_string()).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].document_id, complete);
+
+        // 再次清理没有可删除的块
+        let again = service.cleanup_partial_documents(project_id).await.unwrap();
+        assert_eq!((again.failed_documents, again.removed_chunks), (1, 0));
+    }
+
+    #[test]
+    fn test_documents_with_fewer_chunks_than_recorded_are_partial() {
+        let chunk = |document_id: &str, total: Option<&str>| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: "p".to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: 0,
+            content: String::new(),
+            embedding: Vec::new(),
+            metadata: total.map(|total| HashMap::from([(CHUNK_TOTAL_KEY.to_string(), total.to_string())])).unwrap_or_default(),
+        };
+        let chunks = vec![
+            chunk("complete", Some("2")),
+            chunk("complete", Some("2")),
+            chunk("partial", Some("3")),
+            chunk("partial", Some("3")),
+            // 旧文档块没有记录总块数，无法判断
+            chunk("legacy", None),
+        ];
+
+        assert_eq!(partial_document_ids(&chunks), vec!["partial".to_string()]);
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_chunk_with_null_embedding_is_filled_and_embedded_chunks_untouched() {
+        use crate::services::seekdb_adapter::SCHEMA_EMBEDDING_DIMENSION;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Mutex::new(SeekDbAdapter::new(dir.path().join("fill_missing.db")).unwrap()));
+        let project_id = Uuid::new_v4().to_string();
+        let chunk = |index: i32, embedding: Vec<f64>| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.clone(),
+            document_id: "doc".to_string(),
+            chunk_index: index,
+            content: format!("文档块 {}", index),
+            embedding,
+            metadata: HashMap::new(),
+        };
+
+        let embedded = chunk(0, vec![0.1; SCHEMA_EMBEDDING_DIMENSION]);
+        let missing = chunk(1, Vec::new());
+        db.lock().await.add_documents(vec![embedded.clone()]).unwrap();
+        db.lock().await.insert_chunk_without_embedding(&missing).unwrap();
+
+        let embedded_texts = std::sync::Mutex::new(Vec::new());
+        let report = fill_missing_embeddings_with(&db, &project_id, |texts| {
+            let embeddings = texts.iter().map(|_| vec![0.9; SCHEMA_EMBEDDING_DIMENSION]).collect();
+            embedded_texts.lock().unwrap().extend(texts);
+            async { Ok(embeddings) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(report, EmbeddingFillReport { missing_chunks: 1, filled_chunks: 1 });
+        // 只为缺少向量的块生成向量
+        assert_eq!(*embedded_texts.lock().unwrap(), vec![missing.content.clone()]);
+
+        let embeddings: HashMap<String, Vec<f64>> = db.lock().await
+            .get_project_chunk_embeddings(&project_id)
+            .unwrap()
+            .into_iter()
+            .map(|chunk| (chunk.id, chunk.embedding))
+            .collect();
+        assert_eq!(embeddings[&missing.id][0], 0.9);
+        assert_eq!(embeddings[&embedded.id][0], 0.1);
+
+        // 再次执行没有需要补全的块
+        let again = fill_missing_embeddings_with(&db, &project_id, |_| async { Ok(Vec::new()) }).await.unwrap();
+        assert_eq!(again, EmbeddingFillReport::default());
+    }
+
+    #[test]
+    fn test_reconstructed_documents_keep_the_source_file_size() {
+        let project_id = Uuid::new_v4();
+        let chunk = |document_id: Uuid, index: i32, file_size: Option<&str>| {
+            let mut metadata = HashMap::from([("filename".to_string(), format!("{}.md", document_id))]);
+            if let Some(file_size) = file_size {
+                metadata.insert(CHUNK_FILE_SIZE_KEY.to_string(), file_size.to_string());
+            }
+            VectorDocument {
+                id: Uuid::new_v4().to_string(),
+                project_id: project_id.to_string(),
+                document_id: document_id.to_string(),
+                chunk_index: index,
+                content: "0123456789".to_string(),
+                embedding: Vec::new(),
+                metadata,
+            }
+        };
+        let (sized, legacy) = (Uuid::new_v4(), Uuid::new_v4());
+        let chunks = vec![
+            chunk(sized, 0, Some("4096")),
+            chunk(sized, 1, Some("4096")),
+            chunk(legacy, 0, None),
+            chunk(legacy, 1, None),
+        ];
+
+        let documents = reconstruct_documents(&chunks, &HashMap::new());
+        let file_size = |id: Uuid| documents.iter().find(|doc| doc.id == id).unwrap().file_size;
+        assert_eq!(file_size(sized), 4096);
+        // 旧文档块没有记录文件大小，按块内容估算
+        assert_eq!(file_size(legacy), 20);
+    }
+
+    #[test]
+    fn test_reload_keeps_in_flight_and_failed_documents_without_chunks() {
+        let project_id = Uuid::new_v4();
+        let document = |status: ProcessingStatus| {
+            let mut document = Document::new(project_id, "/tmp/doc.md".to_string(), 10, "hash".to_string()).unwrap();
+            document.processing_status = status;
+            document
+        };
+        let processing = document(ProcessingStatus::Processing);
+        let failed = document(ProcessingStatus::Failed);
+        // 已索引但块已在外部被删除的文档不再保留
+        let removed = document(ProcessingStatus::Indexed);
+        let mut other_project = document(ProcessingStatus::Processing);
+        other_project.project_id = Uuid::new_v4();
+
+        let indexed_id = Uuid::new_v4();
+        let chunks = vec![VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            document_id: indexed_id.to_string(),
+            chunk_index: 0,
+            content: "内容".to_string(),
+            embedding: Vec::new(),
+            metadata: HashMap::new(),
+        }];
+        let existing: HashMap<Uuid, Document> = [&processing, &failed, &removed, &other_project]
+            .into_iter()
+            .map(|document| (document.id, document.clone()))
+            .collect();
+
+        let documents = reloaded_documents(&chunks, &existing, &[project_id]);
+        let mut ids: Vec<Uuid> = documents.keys().copied().collect();
+        ids.sort();
+        let mut expected = vec![indexed_id, processing.id, failed.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert_eq!(documents[&processing.id].processing_status, ProcessingStatus::Processing);
+    }
+
+    #[test]
+    fn test_missing_source_file_is_reported_for_reprocessing() {
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("notes.md");
+        std::fs::write(&existing, "内容").unwrap();
+
+        assert_eq!(missing_source_error(&existing.display().to_string()), None);
+        let missing = dir.path().join("moved.md").display().to_string();
+        assert_eq!(missing_source_error(&missing), Some(format!("源文件不存在: {}", missing)));
+        // 重启后重建的文档只有文件名，没有可读取的路径
+        assert!(missing_source_error("notes.md").is_some());
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_reprocess_keeps_chunks_of_documents_without_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("reprocess.db").display().to_string();
+        let mut service = DocumentService::with_full_config(&db_path, "test-key".to_string(), None, None, None).await.unwrap();
+
+        // 重启后的文档只存在于数据库中，file_path 只剩文件名
+        let project_id = Uuid::new_v4();
+        let document_id = Uuid::new_v4().to_string();
+        let chunk = |index: i32| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            document_id: document_id.clone(),
+            chunk_index: index,
+            content: format!("第 {} 块", index),
+            embedding: vec![0.1; service.embedding_dimension()],
+            metadata: HashMap::from([("filename".to_string(), "notes.md".to_string())]),
+        };
+        service.get_vector_db().lock().await.add_documents(vec![chunk(0), chunk(1)]).unwrap();
+
+        let documents = service.documents_for_reprocess(project_id).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        let (report, reprocessed) = service.indexer().reprocess_documents(documents, |_, _, _| {}).await;
+
+        assert!(reprocessed.is_empty());
+        assert_eq!(report.reprocessed, 0);
+        assert_eq!(report.deleted_chunks, 0);
+        assert_eq!(report.failed_documents.len(), 1);
+        assert_eq!(service.get_vector_db().lock().await.get_document_chunks(&document_id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_thematically_similar_document_ranks_first() {
+        use crate::services::simple_embeddings::SimpleEmbeddingService;
+
+        let documents = [
+            ("rust-ownership", vec!["Rust ownership moves values between variables", "Borrowing lets code use values without ownership"]),
+            ("rust-lifetimes", vec!["Lifetimes describe how long borrowing references stay valid", "The borrow checker enforces ownership rules"]),
+            ("pasta", vec!["Boil pasta in salted water", "Simmer tomato sauce with garlic and basil"]),
+        ];
+
+        let texts: Vec<String> = documents.iter().flat_map(|(_, chunks)| chunks.iter().map(|c| c.to_string())).collect();
+        let mut embedder = SimpleEmbeddingService::new(256);
+        embedder.train(&texts).unwrap();
+
+        let chunks: Vec<VectorDocument> = documents
+            .iter()
+            .flat_map(|(document_id, contents)| {
+                let embedder = &embedder;
+                contents.iter().enumerate().map(move |(i, content)| VectorDocument {
+                    id: format!("{}-{}", document_id, i),
+                    project_id: "project".to_string(),
+                    document_id: document_id.to_string(),
+                    chunk_index: i as i32,
+                    content: content.to_string(),
+                    embedding: embedder.embed_text(content).unwrap(),
+                    metadata: HashMap::from([("filename".to_string(), format!("{}.md", document_id))]),
+                })
+            })
+            .collect();
+
+        let similar = rank_similar_documents(&chunks, "rust-ownership", 5).unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].document_id, "rust-lifetimes");
+        assert_eq!(similar[0].filename.as_deref(), Some("rust-lifetimes.md"));
+        assert_eq!(similar[1].document_id, "pasta");
+        assert!(similar[0].score > similar[1].score);
+
+        assert!(rank_similar_documents(&chunks, "missing", 5).is_err());
+    }
+
+    #[test]
+    fn test_distinct_metadata_values_with_counts() {
+        let chunk = |document_id: &str, index: i32, category: Option<&str>| VectorDocument {
+            id: format!("{}-{}", document_id, index),
+            project_id: "project".to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: index,
+            content: String::new(),
+            embedding: vec![],
+            metadata: category
+                .map(|c| HashMap::from([("category".to_string(), c.to_string())]))
+                .unwrap_or_default(),
+        };
+
+        let chunks = vec![
+            chunk("doc-1", 0, Some("手册")),
+            chunk("doc-1", 1, Some("手册")),
+            chunk("doc-2", 0, Some("手册")),
+            chunk("doc-3", 0, Some("FAQ")),
+            chunk("doc-4", 0, Some(" ")),
+            chunk("doc-5", 0, None),
+        ];
+
+        let values = count_metadata_values(&chunks, "category");
+        assert_eq!(
+            values,
+            vec![
+                MetadataValueCount { value: "手册".to_string(), document_count: 2, chunk_count: 3 },
+                MetadataValueCount { value: "FAQ".to_string(), document_count: 1, chunk_count: 1 },
+            ]
+        );
+        assert!(count_metadata_values(&chunks, "author").is_empty());
+    }
+
+    #[test]
+    fn test_project_weight_promotes_its_chunks() {
+        let result = |project_id: &str, document_id: &str, similarity: f64| SearchResult {
+            document: VectorDocument {
+                id: format!("{}-0", document_id),
+                project_id: project_id.to_string(),
+                document_id: document_id.to_string(),
+                chunk_index: 0,
+                content: String::new(),
+                embedding: vec![],
+                metadata: HashMap::from([("filename".to_string(), format!("{}.md", document_id))]),
+            },
+            similarity,
+        };
+        let primary = || vec![result("client", "contract", 0.8), result("client", "meeting", 0.7)];
+        let reference = || vec![result("shared", "handbook", 0.6), result("shared", "glossary", 0.5)];
+
+        // 权重相同：按原始分数排序
+        let merged = merge_weighted_results(vec![(1.0, primary()), (1.0, reference())], ScoreThreshold::Fixed(0.0), 3);
+        let ids: Vec<&str> = merged.iter().map(|c| c.document_id.as_str()).collect();
+        assert_eq!(ids, vec!["contract", "meeting", "handbook"]);
+
+        // 提高参考项目的权重后，其文档块排到前面
+        let merged = merge_weighted_results(vec![(1.0, primary()), (1.5, reference())], ScoreThreshold::Fixed(0.0), 3);
+        let ids: Vec<&str> = merged.iter().map(|c| c.document_id.as_str()).collect();
+        assert_eq!(ids, vec!["handbook", "contract", "glossary"]);
+        assert_eq!(merged[0].project_id, "shared");
+        assert_eq!(merged[0].filename.as_deref(), Some("handbook.md"));
+        assert!((merged[0].relevance_score - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fixed_vs_adaptive_score_selection() {
+        // 难查询：所有分数都低于 0.3
+        let hard = vec![0.28, 0.25, 0.12, 0.22, 0.05];
+        assert!(select_by_score(hard.clone(), |s| *s, ScoreThreshold::Fixed(0.3), 5).is_empty());
+        assert_eq!(
+            select_by_score(hard, |s| *s, ScoreThreshold::Adaptive { gap: 0.1 }, 5),
+            vec![0.28, 0.25, 0.22]
+        );
+
+        // 简单查询：固定阈值会放进大量低质量结果
+        let easy = vec![0.91, 0.45, 0.88, 0.35, 0.4, 0.84];
+        assert_eq!(
+            select_by_score(easy.clone(), |s| *s, ScoreThreshold::Fixed(0.3), 5),
+            vec![0.91, 0.88, 0.84, 0.45, 0.4]
+        );
+        assert_eq!(
+            select_by_score(easy.clone(), |s| *s, ScoreThreshold::Adaptive { gap: 0.1 }, 5),
+            vec![0.91, 0.88, 0.84]
+        );
+        assert_eq!(
+            select_by_score(easy, |s| *s, ScoreThreshold::Adaptive { gap: 0.1 }, 2),
+            vec![0.91, 0.88]
+        );
+    }
+
     #[test]
     fn test_get_supported_extensions() {
         let extensions = DocumentService::get_supported_extensions();
@@ -527,4 +2568,86 @@ mod tests {
         assert!(extensions.contains(&"md"));
         assert!(extensions.contains(&"pdf"));
     }
+
+    #[test]
+    fn test_document_content_is_reassembled_from_ordered_chunks() {
+        let chunk = |chunk_index: i32, content: &str, kind: Option<&str>| {
+            let mut metadata = HashMap::from([("filename".to_string(), "story.md".to_string())]);
+            if let Some(kind) = kind {
+                metadata.insert(CHUNK_TYPE_KEY.to_string(), kind.to_string());
+            }
+            VectorDocument {
+                id: format!("chunk-{}", chunk_index),
+                project_id: "project".to_string(),
+                document_id: "doc".to_string(),
+                chunk_index,
+                content: content.to_string(),
+                embedding: Vec::new(),
+                metadata,
+            }
+        };
+        // 数据库返回的顺序不保证与 chunk_index 一致
+        let chunks = vec![
+            chunk(2, "into the forest. The end.", None),
+            chunk(0, "The quick brown fox jumps over the lazy dog.", None),
+            chunk(3, "图片说明：狐狸", Some(CHUNK_TYPE_CAPTION)),
+            chunk(1, "the lazy dog. Then it runs into the forest.", None),
+        ];
+
+        let document = DocumentContent::from_chunks("doc", &chunks).unwrap();
+        assert_eq!(document.filename.as_deref(), Some("story.md"));
+        assert_eq!(document.chunk_count, 3);
+        assert_eq!(
+            document.content,
+            "The quick brown fox jumps over the lazy dog. Then it runs into the forest. The end."
+        );
+
+        assert!(DocumentContent::from_chunks("missing", &[]).is_none());
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 SeekDB
+    async fn test_keyword_fallback_returns_matches_when_embedding_fails() {
+        use crate::config::EmbeddingRetryConfig;
+        use crate::services::seekdb_adapter::SCHEMA_EMBEDDING_DIMENSION;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("keyword_fallback.db").display().to_string();
+        // embedding API 不可达，查询路径不重试
+        let embedding_config = EmbeddingConfig {
+            base_url: Some("http://127.0.0.1:9".to_string()),
+            query_retry: Some(EmbeddingRetryConfig { max_retries: Some(0), ..Default::default() }),
+            ..Default::default()
+        };
+        let service = DocumentService::with_full_config(&db_path, "test-key".to_string(), Some(embedding_config), None, None)
+            .await
+            .unwrap();
+
+        let project_id = Uuid::new_v4().to_string();
+        let chunk = |index: i32, content: &str| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.clone(),
+            document_id: "doc".to_string(),
+            chunk_index: index,
+            content: content.to_string(),
+            embedding: vec![0.1; SCHEMA_EMBEDDING_DIMENSION],
+            metadata: HashMap::new(),
+        };
+        service.get_vector_db().lock().await.add_documents(vec![
+            chunk(0, "SeekDB supports hybrid search over vectors and full text"),
+            chunk(1, "The weather today is sunny with a light breeze"),
+        ]).unwrap();
+
+        let error = service.search_similar_chunks(&project_id, None, "hybrid search", 5).await.unwrap_err();
+        assert!(is_embedding_unavailable(&error));
+
+        let projects = [ProjectWeight { project_id: project_id.clone(), weight: 1.0 }];
+        let chunks = service.search_keyword_chunks(&projects, None, "hybrid search", 5).await.unwrap();
+        assert!(!chunks.is_empty());
+        assert!(chunks[0].content.contains("hybrid search"));
+        assert!(chunks.iter().all(|chunk| !chunk.content.contains("weather")));
+        // 关键词分数按本次查询的最高分归一化
+        assert_eq!(chunks[0].relevance_score, 1.0);
+        assert!(chunks.iter().all(|chunk| (0.0..=1.0).contains(&chunk.relevance_score)));
+    }
 }
@@ -0,0 +1,160 @@
+//! Embedding 请求微批处理
+//!
+//! 边输入边搜索时，短时间内会有大量单文本的查询向量请求，每个请求都单独调用一次 API。
+//! 开启微批处理后，第一个到达的请求等待一个很短的窗口（如 20ms），把窗口内到达的所有文本
+//! 合并为一次 `embed_batch` 调用，再把结果按顺序分发给各自的调用方。
+
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+type PendingRequest = (String, oneshot::Sender<std::result::Result<Vec<f64>, String>>);
+
+/// 负责执行批次的调用方在窗口期间被取消时，清空队列，让其余调用方收到错误而不是一直等待
+struct LeaderGuard<'a> {
+    pending: &'a Mutex<Vec<PendingRequest>>,
+    armed: bool,
+}
+
+impl Drop for LeaderGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            // 发送端随队列一起被丢弃，等待方会收到“请求被取消”
+            self.pending.lock().unwrap().clear();
+        }
+    }
+}
+
+/// 把时间窗口内的单文本请求合并为一次批量请求
+#[derive(Debug, Clone)]
+pub struct MicroBatcher {
+    window: Duration,
+    pending: Arc<Mutex<Vec<PendingRequest>>>,
+}
+
+impl MicroBatcher {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 提交一个文本，返回它自己的向量。
+    /// 队列为空时当前调用方负责等待窗口结束并用 `run_batch` 执行整批请求，
+    /// 其余调用方只等待结果（它们传入的 `run_batch` 不会被调用）
+    pub async fn submit<F, Fut>(&self, text: String, run_batch: F) -> Result<Vec<f64>>
+    where
+        F: FnOnce(Vec<String>) -> Fut,
+        Fut: Future<Output = Result<Vec<Vec<f64>>>>,
+    {
+        let (tx, rx) = oneshot::channel();
+        let is_leader = {
+            let mut pending = self.pending.lock().unwrap();
+            let is_leader = pending.is_empty();
+            pending.push((text, tx));
+            is_leader
+        };
+
+        if is_leader {
+            let mut guard = LeaderGuard { pending: &self.pending, armed: true };
+            tokio::time::sleep(self.window).await;
+            let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+            guard.armed = false;
+            let (texts, senders): (Vec<String>, Vec<_>) = batch.into_iter().unzip();
+            log::debug!("📦 微批处理: 合并 {} 个 embedding 请求", texts.len());
+
+            match run_batch(texts).await {
+                Ok(embeddings) if embeddings.len() == senders.len() => {
+                    for (sender, embedding) in senders.into_iter().zip(embeddings) {
+                        let _ = sender.send(Ok(embedding));
+                    }
+                }
+                Ok(embeddings) => {
+                    let error = format!("批量 embedding 返回数量不匹配: 期望 {}, 实际 {}", senders.len(), embeddings.len());
+                    for sender in senders {
+                        let _ = sender.send(Err(error.clone()));
+                    }
+                }
+                Err(e) => {
+                    // 保留完整的错误链（如连接失败的原因），调用方据此判断是否可以降级
+                    let error = format!("{:#}", e);
+                    for sender in senders {
+                        let _ = sender.send(Err(error.clone()));
+                    }
+                }
+            }
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("微批处理请求被取消"))?
+            .map_err(|e| anyhow!(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 伪造的批量 embedding：向量为 [文本长度]
+    async fn fake_embed_batch(calls: &AtomicUsize, texts: Vec<String>) -> Result<Vec<Vec<f64>>> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Ok(texts.iter().map(|t| vec![t.chars().count() as f64]).collect())
+    }
+
+    #[tokio::test]
+    async fn test_near_simultaneous_requests_share_one_batch() {
+        let batcher = MicroBatcher::new(Duration::from_millis(20));
+        let calls = AtomicUsize::new(0);
+
+        let (a, b) = tokio::join!(
+            batcher.submit("a".to_string(), |texts| fake_embed_batch(&calls, texts)),
+            batcher.submit("bbb".to_string(), |texts| fake_embed_batch(&calls, texts)),
+        );
+
+        assert_eq!(a.unwrap(), vec![1.0]);
+        assert_eq!(b.unwrap(), vec![3.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // 窗口结束后的请求进入新的批次
+        let c = batcher.submit("cc".to_string(), |texts| fake_embed_batch(&calls, texts)).await;
+        assert_eq!(c.unwrap(), vec![2.0]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_error_is_returned_to_every_caller() {
+        let batcher = MicroBatcher::new(Duration::from_millis(10));
+        let failing = |_texts: Vec<String>| async { Err::<Vec<Vec<f64>>, _>(anyhow!("API 错误")) };
+
+        let (a, b) = tokio::join!(
+            batcher.submit("a".to_string(), failing),
+            batcher.submit("b".to_string(), failing),
+        );
+        assert!(a.unwrap_err().to_string().contains("API 错误"));
+        assert!(b.unwrap_err().to_string().contains("API 错误"));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_does_not_block_followers() {
+        let batcher = MicroBatcher::new(Duration::from_millis(50));
+        let calls = AtomicUsize::new(0);
+
+        let leader = batcher.submit("a".to_string(), |texts| fake_embed_batch(&calls, texts));
+        let follower = batcher.submit("b".to_string(), |texts| fake_embed_batch(&calls, texts));
+        let (_, follower) = tokio::join!(
+            tokio::time::timeout(Duration::from_millis(10), leader),
+            tokio::time::timeout(Duration::from_secs(1), follower),
+        );
+
+        assert!(follower.expect("跟随者不应一直等待").is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // 队列已清空，后续请求正常
+        let c = batcher.submit("cc".to_string(), |texts| fake_embed_batch(&calls, texts)).await;
+        assert_eq!(c.unwrap(), vec![2.0]);
+    }
+}
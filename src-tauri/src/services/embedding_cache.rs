@@ -0,0 +1,282 @@
+//! 持久化的 Embedding 缓存
+//!
+//! 反复导入同一批文档时，相同文本的向量会被重复计算。`embedding.persistentCache`（默认开启）
+//! 按模型和文本内容的哈希把生成的向量保存在数据库旁的 `embedding_cache.jsonl` 中，重启后仍可复用。
+//!
+//! 文件为追加写入的 JSON Lines：新向量直接追加一行。运行期间超过容量时按 LRU 淘汰，
+//! 文件行数明显超过容量时按最近使用顺序重写；打开时丢弃过期条目，并按文件中的顺序
+//! （即上次压缩时的使用顺序 + 之后的写入顺序）只保留最新的 `max_entries` 条。
+
+use crate::utils::content_hash::{content_hash, HashAlgorithm};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 缓存文件名（位于数据库文件所在目录）
+pub const EMBEDDING_CACHE_FILE: &str = "embedding_cache.jsonl";
+/// 默认有效期：7 天
+pub const DEFAULT_CACHE_TTL_HOURS: u64 = 7 * 24;
+/// 默认最多缓存的向量数量
+pub const DEFAULT_CACHE_MAX_ENTRIES: usize = 5000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheLine {
+    key: String,
+    embedding: Vec<f64>,
+    /// 写入时间（Unix 秒）
+    created_at: i64,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    embedding: Vec<f64>,
+    created_at: i64,
+    /// 最近使用的逻辑时钟，越大越新
+    last_used: u64,
+}
+
+#[derive(Debug)]
+pub struct PersistentEmbeddingCache {
+    path: PathBuf,
+    ttl: Duration,
+    max_entries: usize,
+    entries: HashMap<String, CacheEntry>,
+    clock: u64,
+    /// 文件中的行数（含已淘汰、被覆盖的旧行）
+    file_lines: usize,
+}
+
+impl PersistentEmbeddingCache {
+    /// 打开（或创建）缓存文件，丢弃过期条目并压缩文件
+    pub fn open(path: impl Into<PathBuf>, ttl: Duration, max_entries: usize) -> Result<Self> {
+        let mut cache = Self {
+            path: path.into(),
+            ttl,
+            max_entries: max_entries.max(1),
+            entries: HashMap::new(),
+            clock: 0,
+            file_lines: 0,
+        };
+
+        if cache.path.exists() {
+            let reader = BufReader::new(File::open(&cache.path)?);
+            for line in reader.lines() {
+                let line = line?;
+                // 跳过损坏的行（例如写入过程中进程退出）
+                let Ok(line) = serde_json::from_str::<CacheLine>(&line) else {
+                    continue;
+                };
+                cache.clock += 1;
+                cache.entries.insert(
+                    line.key,
+                    CacheEntry { embedding: line.embedding, created_at: line.created_at, last_used: cache.clock },
+                );
+            }
+        }
+
+        cache.evict_expired(chrono::Utc::now().timestamp());
+        cache.evict_lru();
+        cache.compact()?;
+
+        log::info!("💾 Embedding 缓存已加载: {} 条 ({:?})", cache.entries.len(), cache.path);
+        Ok(cache)
+    }
+
+    /// 缓存键：模型 + 文本
+    pub fn key(model: &str, text: &str) -> String {
+        content_hash(format!("{}\n{}", model, text).as_bytes(), HashAlgorithm::Blake3)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 读取未过期的向量，并标记为最近使用
+    pub fn get(&mut self, key: &str) -> Option<Vec<f64>> {
+        let now = chrono::Utc::now().timestamp();
+        let expired = self.entries.get(key).map(|entry| self.is_expired(entry, now))?;
+        if expired {
+            self.entries.remove(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(entry.embedding.clone())
+    }
+
+    /// 写入向量并追加到缓存文件
+    pub fn insert(&mut self, key: String, embedding: Vec<f64>) -> Result<()> {
+        let created_at = chrono::Utc::now().timestamp();
+        let line = serde_json::to_string(&CacheLine { key: key.clone(), embedding: embedding.clone(), created_at })?;
+
+        self.clock += 1;
+        self.entries.insert(key, CacheEntry { embedding, created_at, last_used: self.clock });
+        self.evict_lru();
+
+        if self.file_lines >= self.max_entries * 2 {
+            self.compact()
+        } else {
+            let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            writeln!(file, "{}", line)?;
+            self.file_lines += 1;
+            Ok(())
+        }
+    }
+
+    /// 删除所有条目并清空缓存文件，返回删除的条目数
+    pub fn clear(&mut self) -> Result<usize> {
+        let removed = self.entries.len();
+        self.entries.clear();
+        self.compact()?;
+        Ok(removed)
+    }
+
+    fn is_expired(&self, entry: &CacheEntry, now: i64) -> bool {
+        now.saturating_sub(entry.created_at) > self.ttl_secs()
+    }
+
+    fn evict_expired(&mut self, now: i64) {
+        let ttl = self.ttl_secs();
+        self.entries.retain(|_, entry| now.saturating_sub(entry.created_at) <= ttl);
+    }
+
+    /// 有效期（秒），超出 i64 范围时视为永不过期
+    fn ttl_secs(&self) -> i64 {
+        i64::try_from(self.ttl.as_secs()).unwrap_or(i64::MAX)
+    }
+
+    /// 超出容量时淘汰最久未使用的条目
+    fn evict_lru(&mut self) {
+        if self.entries.len() <= self.max_entries {
+            return;
+        }
+
+        let mut by_use: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        by_use.sort_unstable();
+
+        let excess = self.entries.len() - self.max_entries;
+        for (_, key) in by_use.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// 按最近使用顺序（旧 → 新）重写缓存文件，下次加载时保留 LRU 顺序
+    fn compact(&mut self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut entries: Vec<(&String, &CacheEntry)> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| entry.last_used);
+
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            for (key, entry) in &entries {
+                let line = CacheLine { key: (*key).clone(), embedding: entry.embedding.clone(), created_at: entry.created_at };
+                writeln!(writer, "{}", serde_json::to_string(&line)?)?;
+            }
+            writer.flush()?;
+        }
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file_lines = entries.len();
+        Ok(())
+    }
+}
+
+/// 缓存文件路径：与数据库文件位于同一目录
+pub fn cache_path_for_db(db_path: &str) -> PathBuf {
+    Path::new(db_path)
+        .parent()
+        .map(|dir| dir.join(EMBEDDING_CACHE_FILE))
+        .unwrap_or_else(|| PathBuf::from(EMBEDDING_CACHE_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_cached_embedding_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EMBEDDING_CACHE_FILE);
+        let key = PersistentEmbeddingCache::key("text-embedding-v2", "SeekDB 支持混合检索");
+
+        {
+            let mut cache = PersistentEmbeddingCache::open(&path, TTL, 10).unwrap();
+            assert_eq!(cache.get(&key), None);
+            cache.insert(key.clone(), vec![0.1, 0.2, 0.3]).unwrap();
+        }
+
+        // 模拟重启：重新打开同一个文件
+        let mut cache = PersistentEmbeddingCache::open(&path, TTL, 10).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&key), Some(vec![0.1, 0.2, 0.3]));
+
+        // 不同模型的同一文本不会命中
+        assert_eq!(cache.get(&PersistentEmbeddingCache::key("other-model", "SeekDB 支持混合检索")), None);
+
+        // TTL 为 0 时重启后条目已过期
+        drop(cache);
+        std::thread::sleep(Duration::from_millis(1100));
+        let cache = PersistentEmbeddingCache::open(&path, Duration::ZERO, 10).unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_huge_ttl_never_expires_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EMBEDDING_CACHE_FILE);
+        let ttl = Duration::from_secs(u64::MAX.saturating_mul(3600));
+
+        let mut cache = PersistentEmbeddingCache::open(&path, ttl, 10).unwrap();
+        cache.insert("a".to_string(), vec![1.0]).unwrap();
+        assert_eq!(cache.get("a"), Some(vec![1.0]));
+        drop(cache);
+        assert_eq!(PersistentEmbeddingCache::open(&path, ttl, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(EMBEDDING_CACHE_FILE);
+
+        let mut cache = PersistentEmbeddingCache::open(&path, TTL, 2).unwrap();
+        cache.insert("a".to_string(), vec![1.0]).unwrap();
+        cache.insert("b".to_string(), vec![2.0]).unwrap();
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_string(), vec![3.0]).unwrap();
+
+        assert_eq!(cache.get("b"), None);
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+
+        // 重启后容量限制仍然生效（文件中仍有被淘汰的旧行），最新写入的条目保留
+        drop(cache);
+        let mut cache = PersistentEmbeddingCache::open(&path, TTL, 2).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("c").is_some());
+
+        // 清空后重启也不会再读到旧条目
+        assert_eq!(cache.clear().unwrap(), 2);
+        drop(cache);
+        assert!(PersistentEmbeddingCache::open(&path, TTL, 2).unwrap().is_empty());
+    }
+}
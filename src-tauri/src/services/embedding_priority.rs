@@ -0,0 +1,183 @@
+//! Embedding 请求优先级
+//!
+//! 批量导入文档时，多个文档的 `embed_batch` 会同时占满 embedding 服务的并发，
+//! 此时对话的查询向量要排在大批量请求之后，输入问题后要等很久才开始检索。
+//! 这里限制同时进行的摄取请求数，并把摄取批次切成小段：查询请求不受限制直接发送，
+//! 有查询正在进行时，摄取在开始下一段之前先等待查询完成。
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use tokio::sync::{watch, Semaphore};
+
+use crate::services::embedding_provider::{EmbeddingBatch, EmbeddingProvider};
+
+/// 默认同时进行的摄取请求数
+pub const DEFAULT_MAX_CONCURRENT_INGESTION: usize = 2;
+
+/// 摄取批次按这个数量切段，查询请求可以在段与段之间插入
+const INGESTION_SLICE_TEXTS: usize = 25;
+
+/// 查询请求优先于摄取请求的 embedding 服务
+pub struct PrioritizedEmbeddingProvider {
+    inner: Arc<dyn EmbeddingProvider>,
+    ingestion_permits: Semaphore,
+    active_queries: watch::Sender<usize>,
+}
+
+/// 查询结束（包括被取消）时减少计数，唤醒等待的摄取请求
+struct QueryGuard<'a>(&'a watch::Sender<usize>);
+
+impl Drop for QueryGuard<'_> {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
+
+impl PrioritizedEmbeddingProvider {
+    pub fn new(inner: Arc<dyn EmbeddingProvider>, max_concurrent_ingestion: usize) -> Self {
+        log::info!("  - 最大并发摄取请求数: {}", max_concurrent_ingestion.max(1));
+        Self {
+            inner,
+            ingestion_permits: Semaphore::new(max_concurrent_ingestion.max(1)),
+            active_queries: watch::Sender::new(0),
+        }
+    }
+
+    fn begin_query(&self) -> QueryGuard<'_> {
+        self.active_queries.send_modify(|count| *count += 1);
+        QueryGuard(&self.active_queries)
+    }
+
+    async fn embed_query(&self, text: &str, uncached: bool) -> Result<Vec<f64>> {
+        let _guard = self.begin_query();
+        if uncached {
+            self.inner.embed_text_uncached(text).await
+        } else {
+            self.inner.embed_text(text).await
+        }
+    }
+
+    async fn embed_ingestion(&self, texts: &[String]) -> Result<EmbeddingBatch> {
+        let mut all = EmbeddingBatch { embeddings: Vec::with_capacity(texts.len()), total_tokens: 0 };
+        let mut active_queries = self.active_queries.subscribe();
+        for slice in texts.chunks(INGESTION_SLICE_TEXTS) {
+            // 有查询进行中时让出 embedding 服务
+            active_queries.wait_for(|count| *count == 0).await?;
+            let _permit = self.ingestion_permits.acquire().await?;
+            let batch = self.inner.embed_batch_with_usage(slice).await?;
+            all.embeddings.extend(batch.embeddings);
+            all.total_tokens += batch.total_tokens;
+        }
+        Ok(all)
+    }
+}
+
+impl EmbeddingProvider for PrioritizedEmbeddingProvider {
+    fn embed_text<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(self.embed_query(text, false))
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f64>>>> {
+        Box::pin(async move { Ok(self.embed_ingestion(texts).await?.embeddings) })
+    }
+
+    fn embed_batch_with_usage<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<EmbeddingBatch>> {
+        Box::pin(self.embed_ingestion(texts))
+    }
+
+    fn embed_text_uncached<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(self.embed_query(text, true))
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.inner.embedding_dim()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn clear_cache(&self) -> Result<usize> {
+        self.inner.clear_cache()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 模拟并发上限为 2 的 embedding 服务：每个请求占用一个并发槽，摄取 200ms、查询 10ms，按完成顺序记录请求
+    struct SlowProvider {
+        slots: Semaphore,
+        completed: std::sync::Mutex<Vec<&'static str>>,
+    }
+
+    impl SlowProvider {
+        fn new(slots: usize) -> Self {
+            Self { slots: Semaphore::new(slots), completed: std::sync::Mutex::new(Vec::new()) }
+        }
+
+        async fn call(&self, kind: &'static str, delay: Duration) {
+            let _slot = self.slots.acquire().await.unwrap();
+            tokio::time::sleep(delay).await;
+            self.completed.lock().unwrap().push(kind);
+        }
+    }
+
+    impl EmbeddingProvider for SlowProvider {
+        fn embed_text<'a>(&'a self, _text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+            Box::pin(async move {
+                self.call("query", Duration::from_millis(10)).await;
+                Ok(vec![1.0])
+            })
+        }
+
+        fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f64>>>> {
+            Box::pin(async move {
+                self.call("batch", Duration::from_millis(200)).await;
+                Ok(vec![vec![0.0]; texts.len()])
+            })
+        }
+
+        fn embed_text_uncached<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+            self.embed_text(text)
+        }
+
+        fn embedding_dim(&self) -> usize {
+            1
+        }
+
+        fn model(&self) -> &str {
+            "slow"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_embedding_is_not_stuck_behind_ingestion_batches() {
+        let inner = Arc::new(SlowProvider::new(2));
+        let provider = Arc::new(PrioritizedEmbeddingProvider::new(inner.clone(), 1));
+
+        // 三个文档同时导入，每个 100 个文本（4 段）
+        let ingestion: Vec<_> = (0..3)
+            .map(|_| {
+                let provider = provider.clone();
+                tokio::spawn(async move { provider.embed_batch(&vec!["文本".to_string(); 100]).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(provider.embed_text("问题").await.unwrap(), vec![1.0]);
+
+        for task in ingestion {
+            assert_eq!(task.await.unwrap().unwrap().len(), 100);
+        }
+
+        // 查询使用预留的并发槽，最多等待正在进行的那一个批次，不排在剩余的 12 个批次之后
+        let completed = inner.completed.lock().unwrap();
+        assert_eq!(completed.len(), 13);
+        let query_position = completed.iter().position(|kind| *kind == "query").unwrap();
+        assert!(query_position <= 1, "查询排在第 {} 位: {:?}", query_position, completed);
+    }
+}
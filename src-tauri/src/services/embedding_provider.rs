@@ -0,0 +1,152 @@
+//! Embedding 服务提供方
+//!
+//! DocumentService 通过 `EmbeddingProvider` 生成向量，不依赖具体的 API。
+//! 根据 `embedding.provider` 选择阿里云百炼（DashScope，默认）或 OpenAI。
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{EmbeddingConfig, EmbeddingProviderKind};
+use crate::services::dashscope_embedding_service::{DashScopeEmbeddingService, DEFAULT_EMBEDDING_DIMENSION};
+use crate::services::embedding_cache::{self, PersistentEmbeddingCache};
+use crate::services::embedding_priority::{PrioritizedEmbeddingProvider, DEFAULT_MAX_CONCURRENT_INGESTION};
+use crate::services::generation_retry::RetryPolicy;
+use crate::services::openai_embedding_service::OpenAIEmbeddingService;
+
+/// 批量生成的向量及 API 报告的 token 消耗（缓存命中的文本不消耗 token）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingBatch {
+    pub embeddings: Vec<Vec<f64>>,
+    pub total_tokens: usize,
+}
+
+/// 生成文本向量的服务
+pub trait EmbeddingProvider: Send + Sync {
+    /// 生成单个文本的 embedding（对话查询路径）
+    fn embed_text<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>>;
+
+    /// 批量生成 embeddings（文档摄取路径），返回顺序与输入一致
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f64>>>>;
+
+    /// 与 `embed_batch` 相同，同时返回消耗的 tokens；不报告用量的实现返回 0
+    fn embed_batch_with_usage<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<EmbeddingBatch>> {
+        Box::pin(async move { Ok(EmbeddingBatch { embeddings: self.embed_batch(texts).await?, total_tokens: 0 }) })
+    }
+
+    /// 绕过缓存和微批处理直接调用 API（用于健康检查、预热和测量 API 延迟）
+    fn embed_text_uncached<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>>;
+
+    /// 输出向量的维度
+    fn embedding_dim(&self) -> usize;
+
+    /// 使用的 embedding 模型
+    fn model(&self) -> &str;
+
+    /// 清空持久化缓存，返回删除的条目数；没有缓存的实现返回 0
+    fn clear_cache(&self) -> Result<usize> {
+        Ok(0)
+    }
+}
+
+/// 按 `embedding.provider` 创建 embedding 服务
+///
+/// `llm_api_key` 用于 DashScope（与 LLM 共用 API Key）；OpenAI 使用 `embedding.apiKey`
+/// 或环境变量 OPENAI_API_KEY。`db_path` 用于确定持久化缓存文件的位置。
+/// 返回的服务中查询请求优先于摄取请求，见 `PrioritizedEmbeddingProvider`。
+pub fn create_embedding_provider(
+    llm_api_key: String,
+    config: &EmbeddingConfig,
+    db_path: &str,
+) -> Result<Arc<dyn EmbeddingProvider>> {
+    let dimension = config.dimension.unwrap_or(DEFAULT_EMBEDDING_DIMENSION);
+    let ingestion_retry = RetryPolicy::embedding_ingestion().with_overrides(config.ingestion_retry.as_ref());
+    let query_retry = RetryPolicy::embedding_query().with_overrides(config.query_retry.as_ref());
+
+    let provider: Arc<dyn EmbeddingProvider> = match config.provider {
+        EmbeddingProviderKind::DashScope => {
+            log::info!("🎯 使用阿里云百炼 Embedding API");
+            let mut service = DashScopeEmbeddingService::new(llm_api_key, config.base_url.clone())?
+                .with_dimension(dimension)
+                .with_retry_policies(ingestion_retry, query_retry);
+            if let Some(model) = &config.model {
+                service = service.with_model(model.clone());
+            }
+            if let Some(max_batch_bytes) = config.max_batch_bytes {
+                service = service.with_max_batch_bytes(max_batch_bytes);
+            }
+            if let Some(window_ms) = config.micro_batch_window_ms.filter(|ms| *ms > 0) {
+                service = service.with_micro_batch_window(Duration::from_millis(window_ms));
+            }
+            if config.persistent_cache_enabled() {
+                let ttl_hours = config.cache_ttl_hours.unwrap_or(embedding_cache::DEFAULT_CACHE_TTL_HOURS);
+                let max_entries = config.cache_max_entries.unwrap_or(embedding_cache::DEFAULT_CACHE_MAX_ENTRIES);
+                match PersistentEmbeddingCache::open(
+                    embedding_cache::cache_path_for_db(db_path),
+                    Duration::from_secs(ttl_hours.saturating_mul(3600)),
+                    max_entries,
+                ) {
+                    Ok(cache) => service = service.with_persistent_cache(cache),
+                    Err(e) => log::warn!("⚠️  无法打开 Embedding 缓存，将不使用缓存: {}", e),
+                }
+            }
+            Arc::new(service)
+        }
+        EmbeddingProviderKind::OpenAI => {
+            log::info!("🎯 使用 OpenAI Embedding API");
+            let api_key = config
+                .api_key
+                .clone()
+                .filter(|key| !key.trim().is_empty())
+                .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+                .ok_or_else(|| anyhow!("未找到 OpenAI API Key，请在 config.json 的 embedding.apiKey 配置或设置环境变量 OPENAI_API_KEY"))?;
+            if config.persistent_cache == Some(true) || config.micro_batch_window_ms.is_some_and(|ms| ms > 0) {
+                log::warn!("⚠️  持久化缓存和微批处理目前只支持 DashScope，OpenAI 将忽略这些设置");
+            }
+
+            let mut service = OpenAIEmbeddingService::new(api_key, config.base_url.clone())?
+                .with_dimension(dimension)
+                .with_retry_policies(ingestion_retry, query_retry);
+            if let Some(model) = &config.model {
+                service = service.with_model(model.clone());
+            }
+            if let Some(max_batch_bytes) = config.max_batch_bytes {
+                service = service.with_max_batch_bytes(max_batch_bytes);
+            }
+            Arc::new(service)
+        }
+    };
+
+    let max_concurrent_ingestion = config.max_concurrent_ingestion.unwrap_or(DEFAULT_MAX_CONCURRENT_INGESTION);
+    Ok(Arc::new(PrioritizedEmbeddingProvider::new(provider, max_concurrent_ingestion)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_is_selected_from_embedding_config() {
+        // 默认开启持久化缓存，缓存文件写在数据库所在目录
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("mine_kb.db").display().to_string();
+        let dashscope = create_embedding_provider("sk-dashscope".to_string(), &EmbeddingConfig::default(), &db_path).unwrap();
+        assert!(dir.path().join(embedding_cache::EMBEDDING_CACHE_FILE).exists());
+        assert_eq!(dashscope.model(), "text-embedding-v2");
+        assert_eq!(dashscope.embedding_dim(), DEFAULT_EMBEDDING_DIMENSION);
+
+        let config: EmbeddingConfig = serde_json::from_value(serde_json::json!({
+            "provider": "openai",
+            "apiKey": "sk-openai",
+            "dimension": 512
+        }))
+        .unwrap();
+        let openai = create_embedding_provider("sk-dashscope".to_string(), &config, "db").unwrap();
+        assert_eq!(openai.model(), "text-embedding-3-small");
+        assert_eq!(openai.embedding_dim(), 512);
+
+        let config = EmbeddingConfig { model: Some("text-embedding-3-large".to_string()), ..config };
+        assert_eq!(create_embedding_provider(String::new(), &config, "db").unwrap().model(), "text-embedding-3-large");
+    }
+}
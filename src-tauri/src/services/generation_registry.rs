@@ -0,0 +1,123 @@
+//! 正在生成的对话回复登记表
+//!
+//! `send_message` 开始生成回复时按对话 ID 登记一个取消令牌，`cancel_generation` 通过它
+//! 请求停止；流式读取循环在每个 token 之间检查令牌并提前结束。返回的 `GenerationGuard`
+//! 被丢弃时自动注销，出错提前返回的生成也不会残留。
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::utils::cancellation::CancellationToken;
+
+/// 可克隆的登记表，所有克隆共享同一份状态
+#[derive(Debug, Clone, Default)]
+pub struct GenerationRegistry {
+    /// 对话 ID -> (本次生成的 ID, 取消令牌)
+    active: Arc<Mutex<HashMap<Uuid, (Uuid, CancellationToken)>>>,
+}
+
+impl GenerationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记对话的一次生成；同一对话已有生成在进行时，旧的登记被替换（旧生成不再能被取消）
+    pub fn start(&self, conversation_id: Uuid) -> GenerationGuard {
+        let generation_id = Uuid::new_v4();
+        let token = CancellationToken::new();
+        self.active
+            .lock()
+            .unwrap()
+            .insert(conversation_id, (generation_id, token.clone()));
+
+        GenerationGuard {
+            registry: self.clone(),
+            conversation_id,
+            generation_id,
+            token,
+        }
+    }
+
+    /// 请求取消对话正在进行的生成，返回是否有生成在进行
+    pub fn cancel(&self, conversation_id: Uuid) -> bool {
+        match self.active.lock().unwrap().get(&conversation_id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_generating(&self, conversation_id: Uuid) -> bool {
+        self.active.lock().unwrap().contains_key(&conversation_id)
+    }
+}
+
+/// 一次正在进行的生成；丢弃时从登记表中移除
+#[derive(Debug)]
+pub struct GenerationGuard {
+    registry: GenerationRegistry,
+    conversation_id: Uuid,
+    generation_id: Uuid,
+    token: CancellationToken,
+}
+
+impl GenerationGuard {
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// 等待本次生成被取消
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+}
+
+impl Drop for GenerationGuard {
+    fn drop(&mut self) {
+        let mut active = self.registry.active.lock().unwrap();
+        // 只移除自己的登记，不影响同一对话中更新的生成
+        if active.get(&self.conversation_id).is_some_and(|(id, _)| *id == self.generation_id) {
+            active.remove(&self.conversation_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_reaches_the_running_generation_of_that_conversation_only() {
+        let registry = GenerationRegistry::new();
+        let conversation_id = Uuid::new_v4();
+        let other_conversation = Uuid::new_v4();
+
+        let generation = registry.start(conversation_id);
+        let other = registry.start(other_conversation);
+        assert!(registry.cancel(conversation_id));
+        assert!(generation.is_cancelled());
+        assert!(!other.is_cancelled());
+
+        // 模拟流式读取循环：取消后不再读取剩余 token，已收到的部分保留
+        let mut partial = String::new();
+        for token in ["部分", "回复", "剩余"] {
+            if generation.is_cancelled() {
+                break;
+            }
+            partial.push_str(token);
+        }
+        assert!(partial.is_empty());
+
+        // 新的生成替换旧登记，旧 guard 被丢弃时不会注销新的生成
+        let next = registry.start(conversation_id);
+        drop(generation);
+        assert!(registry.is_generating(conversation_id));
+        assert!(!next.is_cancelled());
+
+        drop(next);
+        assert!(!registry.cancel(conversation_id));
+    }
+}
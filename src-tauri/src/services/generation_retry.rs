@@ -0,0 +1,177 @@
+//! 回答生成阶段的自动重试
+//!
+//! 用户消息保存后，如果建立 LLM 连接时遇到临时错误（连接失败、超时、429/5xx），
+//! `LlmClient` 会按退避策略重试请求，而不是直接报错留下一条没有回答的消息。
+//! 只重试收到响应头之前的失败；流式读取中途的错误不重试，用户消息也不会被重复保存。
+//!
+//! `RetryPolicy` 也用于 embedding 请求：文档摄取和对话查询分别使用不同的策略。
+
+use anyhow::Result;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::config::{ChatConfig, EmbeddingRetryConfig};
+
+/// 请求的重试策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 首次失败后最多重试的次数（0 表示不重试）
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 单次等待时间的上限
+    pub max_backoff: Duration,
+    /// 单次请求的超时时间（LLM 请求只限制等待响应头的时间，不限制流式读取）
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    /// 生成阶段的策略：按对话配置重试，退避最长 10 秒，等待响应头最长 60 秒
+    pub fn from_config(config: &ChatConfig) -> Self {
+        Self {
+            max_retries: config.generation_retries,
+            initial_backoff: Duration::from_millis(config.generation_retry_backoff_ms),
+            max_backoff: Duration::from_secs(10),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// 文档摄取 embedding 的默认策略：后台进行，最多重试 3 次，1 秒起指数退避，最长等待 30 秒
+    pub fn embedding_ingestion() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1000),
+            max_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// 对话查询 embedding 的默认策略：用户在等待回复，最多重试 1 次，等待 200 毫秒，单次请求 10 秒超时
+    pub fn embedding_query() -> Self {
+        Self {
+            max_retries: 1,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// 用配置覆盖策略中已设置的项
+    pub fn with_overrides(mut self, config: Option<&EmbeddingRetryConfig>) -> Self {
+        let Some(config) = config else {
+            return self;
+        };
+        if let Some(max_retries) = config.max_retries {
+            self.max_retries = max_retries;
+        }
+        if let Some(ms) = config.initial_backoff_ms {
+            self.initial_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = config.max_backoff_ms {
+            self.max_backoff = Duration::from_millis(ms);
+        }
+        if let Some(secs) = config.timeout_secs {
+            self.timeout = Duration::from_secs(secs.max(1));
+        }
+        self
+    }
+
+    /// 第 `retry` 次重试（从 0 开始）前的等待时间：每次翻倍，不超过 `max_backoff`
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::from_config(&ChatConfig::default())
+    }
+}
+
+/// 判断生成阶段的错误是否为临时错误（网络、超时、限流、服务端错误）
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    let error_str = error.to_string().to_lowercase();
+
+    // 请求未能发出（连接被拒绝、DNS、TLS 等）
+    if error_str.contains("发送请求失败")
+        || error_str.contains("timeout")
+        || error_str.contains("timed out")
+        || error_str.contains("connection")
+        || error_str.contains("connect")
+    {
+        return true;
+    }
+
+    // LLM API 返回的 HTTP 状态码，如 "LLM API 错误 (503 Service Unavailable)"
+    ["(429", "(500", "(502", "(503", "(504"]
+        .iter()
+        .any(|code| error_str.contains(code))
+}
+
+/// 执行 `attempt`，遇到临时错误时按指数退避重试；`attempt` 的参数为当前尝试序号（从 1 开始）
+pub async fn retry_transient<T, F, Fut>(policy: RetryPolicy, mut attempt: F) -> Result<T>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut retries = 0;
+
+    loop {
+        match attempt(retries + 1).await {
+            Ok(value) => {
+                if retries > 0 {
+                    log::info!("✅ [CHAT] 第 {} 次尝试生成成功", retries + 1);
+                }
+                return Ok(value);
+            }
+            Err(e) if retries < policy.max_retries && is_transient_error(&e) => {
+                let delay = policy.backoff(retries);
+                log::warn!(
+                    "⚠️  [CHAT] 生成失败 (第 {}/{} 次重试前)，{}ms 后重试: {}",
+                    retries + 1,
+                    policy.max_retries,
+                    delay.as_millis(),
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                retries += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::Mutex;
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_non_transient_error_and_exhausted_retries_surface() {
+        let attempts = Mutex::new(0);
+        let result: Result<()> = retry_transient(policy(3), |_| async {
+            *attempts.lock().unwrap() += 1;
+            Err(anyhow!("LLM API 错误 (401 Unauthorized): invalid api key"))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(*attempts.lock().unwrap(), 1);
+
+        let result: Result<()> = retry_transient(policy(2), |n| async move {
+            Err(anyhow!("LLM API 错误 (503 Service Unavailable): 第 {} 次", n))
+        })
+        .await;
+        assert!(result.unwrap_err().to_string().contains("第 3 次"));
+    }
+}
@@ -0,0 +1,182 @@
+//! 系统健康检查
+//!
+//! 同时检查数据库、LLM 和 embedding 服务，结果在 `health.cacheTtlSecs` 内缓存，
+//! 界面频繁轮询时直接返回缓存结果，避免每次都向 LLM/embedding 接口发送请求。
+//! 传入 `force` 时忽略缓存重新检查。
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::services::app_state::AppState;
+
+/// 默认缓存时间
+pub const DEFAULT_HEALTH_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// 单个组件的检查结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub healthy: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemHealth {
+    pub database: ComponentHealth,
+    pub llm: ComponentHealth,
+    pub embedding: ComponentHealth,
+    pub checked_at: DateTime<Utc>,
+    /// 是否为缓存的结果
+    pub cached: bool,
+}
+
+impl SystemHealth {
+    pub fn healthy(&self) -> bool {
+        self.database.healthy && self.llm.healthy && self.embedding.healthy
+    }
+}
+
+/// 执行一项检查并记录耗时
+async fn check_component<F>(check: F) -> ComponentHealth
+where
+    F: Future<Output = Result<()>>,
+{
+    let started = Instant::now();
+    let result = check.await;
+    ComponentHealth {
+        healthy: result.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+/// 并发检查数据库、LLM 和 embedding 服务
+pub async fn run_health_checks(state: &AppState) -> SystemHealth {
+    log::info!("🏥 执行系统健康检查...");
+
+    // 取得服务句柄后立即释放锁，检查期间不阻塞文档操作
+    let (vector_db, embedding_service) = {
+        let document_service = state.document_service();
+        let document_service = document_service.lock().await;
+        (document_service.get_vector_db(), document_service.embedding_service())
+    };
+    let llm_client = state.llm_client().lock().await.clone();
+
+    let (database, llm, embedding) = tokio::join!(
+        check_component(async { vector_db.lock().await.health_check() }),
+        check_component(async {
+            match llm_client.test_connection().await? {
+                true => Ok(()),
+                false => Err(anyhow!("LLM 服务不可用")),
+            }
+        }),
+        // 绕过缓存发送一次请求
+        check_component(async { embedding_service.embed_text_uncached("health check").await.map(|_| ()) }),
+    );
+
+    let health = SystemHealth {
+        database,
+        llm,
+        embedding,
+        checked_at: Utc::now(),
+        cached: false,
+    };
+    log::info!("🏥 健康检查完成: healthy={}", health.healthy());
+    health
+}
+
+/// 健康检查结果缓存，所有克隆共享同一份结果
+#[derive(Debug, Clone)]
+pub struct HealthCache {
+    ttl: Duration,
+    last: Arc<Mutex<Option<(Instant, SystemHealth)>>>,
+}
+
+impl Default for HealthCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEALTH_CACHE_TTL)
+    }
+}
+
+impl HealthCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 缓存未过期时返回缓存结果，否则（或 `force` 时）运行 `check` 并缓存
+    ///
+    /// 检查期间持有锁，并发的轮询会等待同一次检查的结果而不是各自再检查一次
+    pub async fn get_or_check<F, Fut>(&self, force: bool, check: F) -> SystemHealth
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = SystemHealth>,
+    {
+        let mut last = self.last.lock().await;
+        if !force {
+            if let Some((checked, health)) = last.as_ref() {
+                if checked.elapsed() < self.ttl {
+                    return SystemHealth { cached: true, ..health.clone() };
+                }
+            }
+        }
+
+        let health = check().await;
+        *last = Some((Instant::now(), health.clone()));
+        health
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn healthy_component() -> ComponentHealth {
+        ComponentHealth { healthy: true, latency_ms: 1, error: None }
+    }
+
+    #[tokio::test]
+    async fn test_health_is_cached_within_ttl_unless_forced() {
+        let cache = HealthCache::new(Duration::from_secs(60));
+        let runs = AtomicUsize::new(0);
+        let check = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            SystemHealth {
+                database: healthy_component(),
+                llm: healthy_component(),
+                embedding: healthy_component(),
+                checked_at: Utc::now(),
+                cached: false,
+            }
+        };
+
+        let first = cache.get_or_check(false, check).await;
+        assert!(!first.cached);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // TTL 内再次调用返回缓存结果，不重新检查
+        let second = cache.get_or_check(false, check).await;
+        assert!(second.cached);
+        assert_eq!(second.checked_at, first.checked_at);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // force 忽略缓存
+        let forced = cache.get_or_check(true, check).await;
+        assert!(!forced.cached);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        // 缓存过期后重新检查
+        let expired = HealthCache::new(Duration::ZERO);
+        expired.get_or_check(false, check).await;
+        expired.get_or_check(false, check).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+}
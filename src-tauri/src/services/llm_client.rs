@@ -1,5 +1,9 @@
 use crate::models::conversation::{ContextChunk, Message};
+use crate::models::document::DocumentChunk;
+use crate::services::generation_retry::{self, RetryPolicy};
+use crate::services::model_registry::ModelRegistry;
 use crate::services::prompts;
+use crate::services::response_filter;
 use anyhow::{anyhow, Result};
 use async_stream::stream;
 use futures::Stream;
@@ -7,12 +11,19 @@ use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 pub struct LlmClient {
     client: Client,
     config: LlmConfig,
+    dedup_context: bool,
+    log_requests: bool,
+    model_registry: ModelRegistry,
+    retry_policy: RetryPolicy,
+    low_confidence_caveat: bool,
+    max_context_tokens: Option<usize>,
+    request_usage: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +69,14 @@ pub struct ChatRequest {
     pub max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
+    /// 流式请求时要求在最后一个分块中返回 token 用量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,6 +86,19 @@ pub struct ChatResponse {
     pub created: u64,
     pub model: String,
     pub choices: Vec<ChatChoice>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// API 报告的 token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,20 +126,207 @@ pub enum StreamEvent {
     Context(Vec<ContextChunk>),
     Complete(String), // response_id
     Error(String),
+    /// 服务端报告的 token 用量（不报告用量的服务不会发送）
+    Usage(TokenUsage),
 }
 
 pub type StreamResponse = Pin<Box<dyn Stream<Item = StreamEvent> + Send>>;
 
+/// SSE 行缓冲区
+///
+/// 网络分块可能把一个多字节 UTF-8 字符（常见于中文输出）切成两半，
+/// 因此在字节层面缓冲，只有遇到完整的一行（`\n` 不会出现在多字节序列内部）才解码，
+/// 不完整的尾部保留到下一个分块。
+#[derive(Debug, Default)]
+struct SseLineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl SseLineBuffer {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个字节分块，返回其中所有完整的行
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(line_end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes: Vec<u8> = self.buffer.drain(..=line_end).collect();
+            let line_bytes = &line_bytes[..line_end];
+            match std::str::from_utf8(line_bytes) {
+                Ok(line) => lines.push(line.to_string()),
+                Err(e) => {
+                    log::warn!("SSE 行不是合法的 UTF-8: {}", e);
+                    lines.push(String::from_utf8_lossy(line_bytes).into_owned());
+                }
+            }
+        }
+
+        lines
+    }
+}
+
+/// 无法识别的 SSE 数据在日志中保留的最大字符数
+const SSE_SAMPLE_CHARS: usize = 200;
+
+/// SSE 事件解析
+///
+/// 兼容非标准的 OpenAI 兼容网关：`data:` 后可以没有空格；`event:` 行指定后续数据的事件类型
+/// （`event: error` 的数据作为错误返回）；内容可以在 `delta.content`、`message.content` 或 `text` 中。
+/// 无法识别的数据以 debug 级别记录样本，而不是静默丢弃。
+#[derive(Debug, Default)]
+struct SseParser {
+    /// 当前事件的类型，遇到空行（事件结束）时重置
+    event: Option<String>,
+    done: bool,
+}
+
+impl SseParser {
+    /// 处理一行，返回解析出的事件
+    fn handle_line(&mut self, line: &str) -> Vec<StreamEvent> {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            self.event = None;
+            return Vec::new();
+        }
+        // 以冒号开头的是注释（常用作心跳）
+        if line.starts_with(':') || self.done {
+            return Vec::new();
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        // 按 SSE 规范去掉冒号后的一个空格，这里兼容任意空白
+        let value = value.trim();
+        match field.trim() {
+            "event" => {
+                self.event = Some(value.to_string());
+                Vec::new()
+            }
+            "data" => self.handle_data(value),
+            other => {
+                log::debug!("忽略 SSE 字段: {}", other);
+                Vec::new()
+            }
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn handle_data(&mut self, data: &str) -> Vec<StreamEvent> {
+        if data == "[DONE]" || matches!(self.event.as_deref(), Some("done")) {
+            self.done = true;
+            return Vec::new();
+        }
+
+        let value: serde_json::Value = match serde_json::from_str(data) {
+            Ok(value) => value,
+            Err(_) if self.event.as_deref() == Some("error") => return vec![StreamEvent::Error(data.to_string())],
+            Err(e) => {
+                log::warn!("解析 SSE 数据失败: {} - 原始数据: {}", e, sse_sample(data));
+                return Vec::new();
+            }
+        };
+
+        let mut events = Vec::new();
+        if let Some(error) = value.get("error").filter(|e| !e.is_null()) {
+            let message = error.get("message").and_then(|m| m.as_str()).map(str::to_string)
+                .unwrap_or_else(|| error.as_str().map(str::to_string).unwrap_or_else(|| error.to_string()));
+            events.push(StreamEvent::Error(message));
+        } else if self.event.as_deref() == Some("error") {
+            events.push(StreamEvent::Error(data.to_string()));
+        }
+
+        let choice = value.get("choices").and_then(|c| c.get(0));
+        let content = choice
+            .and_then(|c| {
+                c.pointer("/delta/content")
+                    .or_else(|| c.pointer("/message/content"))
+                    .or_else(|| c.get("text"))
+            })
+            .or_else(|| value.pointer("/delta/content"))
+            .or_else(|| value.get("content"))
+            .and_then(|content| content.as_str());
+        if let Some(content) = content.filter(|c| !c.is_empty()) {
+            log::debug!("收到 token: {}", content);
+            events.push(StreamEvent::Token(content.to_string()));
+        }
+
+        if let Some(reason) = choice.and_then(|c| c.get("finish_reason")).and_then(|r| r.as_str()) {
+            // 用量在结束之后的最后一个分块中返回，继续读取到 [DONE]
+            log::info!("流式响应完成: {}", reason);
+        }
+
+        if let Some(usage) = value.get("usage").filter(|u| !u.is_null()) {
+            match serde_json::from_value::<TokenUsage>(usage.clone()) {
+                Ok(usage) => events.push(StreamEvent::Usage(usage)),
+                Err(e) => log::debug!("无法解析 token 用量: {}", e),
+            }
+        }
+
+        // 只有角色信息或空内容的分块是正常的，其余没有任何可识别字段的数据记录样本便于排查
+        if events.is_empty() && choice.is_none() && value.get("usage").is_none() {
+            log::debug!("无法识别的 SSE 数据格式: {}", sse_sample(data));
+        }
+        events
+    }
+}
+
+fn sse_sample(data: &str) -> String {
+    data.chars().take(SSE_SAMPLE_CHARS).collect()
+}
+
 impl LlmClient {
     pub fn new(config: LlmConfig) -> Result<Self> {
+        Self::with_model_registry(config, ModelRegistry::default())
+    }
+
+    /// 使用指定的模型登记表（含配置中的自定义模型）创建客户端，max_tokens 按模型的最大输出校验
+    pub fn with_model_registry(config: LlmConfig, model_registry: ModelRegistry) -> Result<Self> {
         Self::validate_config(&config)?;
+        Self::validate_model_limits(&config, &model_registry)?;
 
         Ok(Self {
             client: Client::new(),
             config,
+            dedup_context: true,
+            log_requests: false,
+            model_registry,
+            retry_policy: RetryPolicy::default(),
+            low_confidence_caveat: false,
+            max_context_tokens: None,
+            request_usage: None,
         })
     }
 
+    /// 是否在构建系统提示词时去除内容重复的上下文块（默认开启）
+    pub fn set_context_dedup(&mut self, enabled: bool) {
+        self.dedup_context = enabled;
+    }
+
+    /// 是否为每次请求记录延迟明细（首 token 延迟、总耗时、token 数）
+    pub fn set_request_logging(&mut self, enabled: bool) {
+        self.log_requests = enabled;
+    }
+
+    /// 提示词（系统消息 + 对话历史）的 token 上限，与模型上下文窗口取较小值；None 表示只受上下文窗口限制
+    pub fn set_max_context_tokens(&mut self, max_context_tokens: Option<usize>) {
+        self.max_context_tokens = max_context_tokens;
+    }
+
+    /// 流式请求是否携带 `stream_options.include_usage`；None 表示只对已知支持该参数的服务（OpenAI、阿里百炼）开启
+    pub fn set_request_usage(&mut self, enabled: Option<bool>) {
+        self.request_usage = enabled;
+    }
+
+    /// 建立连接阶段（收到响应头之前）遇到临时错误时的重试策略；流式读取中途的错误不重试
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
     pub async fn test_connection(&self) -> Result<bool> {
         match self.config.provider {
             LlmProvider::OpenAI => self.test_openai_connection().await,
@@ -122,27 +341,108 @@ impl LlmClient {
         context_chunks: &[ContextChunk],
     ) -> Result<StreamResponse> {
         let start_time = Instant::now();
+        let (chat_messages, context_chunks) = self.build_prompt_within_budget(messages, context_chunks);
+        self.dispatch(chat_messages, &context_chunks, start_time).await
+    }
+
+    /// 按提示词预算裁剪上下文块，返回实际会发送给模型的块（用于保存和展示来源）
+    pub fn fit_context_to_budget(&self, messages: &[Message], context_chunks: &[ContextChunk]) -> Vec<ContextChunk> {
+        self.build_prompt_within_budget(messages, context_chunks).1
+    }
+
+    /// 提示词可用的 token 数：模型上下文窗口减去输出预留，再受 `max_context_tokens` 限制
+    fn prompt_budget(&self) -> usize {
+        let budget = self.model_registry.prompt_budget(&self.config.model, self.config.max_tokens);
+        self.max_context_tokens.map_or(budget, |max| budget.min(max))
+    }
 
-        // Build the conversation context
-        let system_message = self.build_system_message(context_chunks);
+    /// 构建系统消息和对话历史，返回请求消息及实际使用的上下文块
+    ///
+    /// 超出预算时先丢弃最早的历史消息，仍然超出再依次丢弃相关度最低的上下文块；
+    /// 系统提示词和最后一条消息始终保留。
+    fn build_prompt_within_budget(
+        &self,
+        messages: &[Message],
+        context_chunks: &[ContextChunk],
+    ) -> (Vec<ChatMessage>, Vec<ContextChunk>) {
+        let mut context_chunks = context_chunks.to_vec();
         let mut chat_messages = vec![ChatMessage {
             role: "system".to_string(),
-            content: system_message,
+            content: self.build_system_message(&context_chunks),
         }];
 
         // Add conversation history
         for message in messages {
             chat_messages.push(ChatMessage {
                 role: message.role.to_string().to_lowercase(),
-                content: message.content.clone(),
+                content: response_filter::strip_score_footer(&message.content).to_string(),
             });
         }
 
-        match self.config.provider {
+        let budget = self.prompt_budget();
+        let dropped = trim_to_budget(&mut chat_messages, budget);
+        if dropped > 0 {
+            log::info!("✂️  提示词超出模型 {} 的上下文预算 ({} tokens)，丢弃了 {} 条最早的历史消息", self.config.model, budget, dropped);
+        }
+
+        let mut dropped_chunks = 0;
+        while prompt_tokens(&chat_messages) > budget {
+            let Some(lowest) = context_chunks
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.relevance_score.total_cmp(&b.relevance_score))
+                .map(|(index, _)| index)
+            else {
+                break;
+            };
+            context_chunks.remove(lowest);
+            chat_messages[0].content = self.build_system_message(&context_chunks);
+            dropped_chunks += 1;
+        }
+        if dropped_chunks > 0 {
+            log::info!("✂️  提示词仍超出上下文预算 ({} tokens)，丢弃了 {} 个相关度最低的上下文块", budget, dropped_chunks);
+        }
+
+        (chat_messages, context_chunks)
+    }
+
+    /// 使用自定义系统提示词（不附带知识库上下文）生成一段完整文本，用于生成标题等辅助任务
+    pub async fn generate_text(&self, system_prompt: &str, user_prompt: &str) -> Result<String> {
+        let chat_messages = vec![
+            ChatMessage { role: "system".to_string(), content: system_prompt.to_string() },
+            ChatMessage { role: "user".to_string(), content: user_prompt.to_string() },
+        ];
+        let stream = self.dispatch(chat_messages, &[], Instant::now()).await?;
+        collect_stream_text(stream).await
+    }
+
+    /// 按提供方发送请求，开启请求日志时包装响应流
+    async fn dispatch(
+        &self,
+        chat_messages: Vec<ChatMessage>,
+        context_chunks: &[ContextChunk],
+        start_time: Instant,
+    ) -> Result<StreamResponse> {
+        let stream = match self.config.provider {
             LlmProvider::OpenAI => self.generate_openai_response(chat_messages, context_chunks, start_time).await,
             LlmProvider::Anthropic => self.generate_anthropic_response(chat_messages, context_chunks, start_time).await,
             LlmProvider::Local => self.generate_local_response(chat_messages, context_chunks, start_time).await,
+        }?;
+
+        if !self.log_requests {
+            return Ok(stream);
         }
+        Ok(instrument_stream(stream, self.config.model.clone(), start_time, |timings| timings.log()))
+    }
+
+    /// 非流式调用：驱动流式响应直到结束，返回拼接后的完整回答
+    pub async fn generate_response_blocking(
+        &self,
+        messages: &[Message],
+        context_chunks: &[ContextChunk],
+    ) -> Result<String> {
+        let stream = self.generate_response(messages, context_chunks).await?;
+        collect_stream_text(stream).await
     }
 
     async fn generate_openai_response(
@@ -152,14 +452,17 @@ impl LlmClient {
         _start_time: Instant,
     ) -> Result<StreamResponse> {
         let url = format!("{}/chat/completions", self.config.base_url);
+        self.send_chat_completion(&url, messages, context_chunks).await
+    }
 
-        let request = ChatRequest {
-            model: self.config.model.clone(),
-            messages,
-            stream: self.config.stream,
-            max_tokens: self.config.max_tokens,
-            temperature: self.config.temperature,
-        };
+    /// 发送 OpenAI 兼容的 chat/completions 请求并解析响应（OpenAI 与本地服务共用）
+    async fn send_chat_completion(
+        &self,
+        url: &str,
+        messages: Vec<ChatMessage>,
+        context_chunks: &[ContextChunk],
+    ) -> Result<StreamResponse> {
+        let request = self.build_chat_request(messages);
 
         log::info!(
             "发送 LLM 请求: model={}, stream={}, base_url={}",
@@ -168,13 +471,35 @@ impl LlmClient {
             self.config.base_url
         );
 
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
+        // 只重试建立连接阶段，连接建立后流中的错误以 StreamEvent::Error 返回
+        let response = generation_retry::retry_transient(self.retry_policy, |_| {
+            self.post_chat_request(url, &request)
+        })
+        .await?;
+
+        if self.config.stream {
+            // 流式响应
+            log::info!("LLM 响应成功，开始流式读取");
+            self.handle_streaming_response(response, context_chunks).await
+        } else {
+            // 非流式响应
+            log::info!("LLM 响应成功，等待完整响应");
+            self.handle_non_streaming_response(response, context_chunks).await
+        }
+    }
+
+    /// 发送一次请求并检查状态码，超时和非 2xx 响应都作为错误返回
+    async fn post_chat_request(&self, url: &str, request: &ChatRequest) -> Result<reqwest::Response> {
+        let send = self.with_auth(self.client.post(url))
             .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
+            .json(request)
+            .send();
+
+        // 只限制等待响应头的时间，不限制流式读取
+        let connect_timeout = self.retry_policy.timeout;
+        let response = tokio::time::timeout(connect_timeout, send)
             .await
+            .map_err(|_| anyhow!("发送请求失败: 等待响应超时 ({}s)", connect_timeout.as_secs()))?
             .map_err(|e| anyhow!("发送请求失败: {}", e))?;
 
         if !response.status().is_success() {
@@ -184,15 +509,7 @@ impl LlmClient {
             return Err(anyhow!("LLM API 错误 ({}): {}", status, error_text));
         }
 
-        if self.config.stream {
-            // 流式响应
-            log::info!("LLM 响应成功，开始流式读取");
-            self.handle_streaming_response(response, context_chunks).await
-        } else {
-            // 非流式响应
-            log::info!("LLM 响应成功，等待完整响应");
-            self.handle_non_streaming_response(response, context_chunks).await
-        }
+        Ok(response)
     }
 
     async fn handle_streaming_response(
@@ -210,60 +527,21 @@ impl LlmClient {
             }
 
             let response_id = format!("resp_{}", uuid::Uuid::new_v4());
-            let mut buffer = String::new();
+            let mut buffer = SseLineBuffer::new();
+
+            let mut parser = SseParser::default();
 
             // Parse SSE stream
-            while let Some(chunk_result) = byte_stream.next().await {
+            'read: while let Some(chunk_result) = byte_stream.next().await {
                 match chunk_result {
                     Ok(chunk) => {
-                        let chunk_str = String::from_utf8_lossy(&chunk);
-                        buffer.push_str(&chunk_str);
-
-                        // Process complete lines
-                        while let Some(line_end) = buffer.find('\n') {
-                            let line = buffer[..line_end].trim().to_string();
-                            buffer = buffer[line_end + 1..].to_string();
-
-                            if line.is_empty() {
-                                continue;
+                        for line in buffer.push(&chunk) {
+                            for event in parser.handle_line(&line) {
+                                yield event;
                             }
-
-                            // SSE format: "data: {...}"
-                            if line.starts_with("data: ") {
-                                let json_str = &line[6..];
-
-                                // Check for [DONE] signal
-                                if json_str.trim() == "[DONE]" {
-                                    log::debug!("收到流式结束信号");
-                                    break;
-                                }
-
-                                // Parse JSON response
-                                match serde_json::from_str::<ChatResponse>(json_str) {
-                                    Ok(response) => {
-                                        if let Some(choice) = response.choices.first() {
-                                            if let Some(delta) = &choice.delta {
-                                                if let Some(content) = &delta.content {
-                                                    if !content.is_empty() {
-                                                        log::debug!("收到 token: {}", content);
-                                                        yield StreamEvent::Token(content.clone());
-                                                    }
-                                                }
-                                            }
-
-                                            // Check for finish
-                                            if let Some(reason) = &choice.finish_reason {
-                                                if reason == "stop" || reason == "length" {
-                                                    log::info!("流式响应完成: {}", reason);
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        log::warn!("解析 SSE 数据失败: {} - 原始数据: {}", e, json_str);
-                                    }
-                                }
+                            if parser.is_done() {
+                                log::debug!("收到流式结束信号");
+                                break 'read;
                             }
                         }
                     }
@@ -309,6 +587,9 @@ impl LlmClient {
                     yield StreamEvent::Token(message.content.clone());
                 }
             }
+            if let Some(usage) = chat_response.usage {
+                yield StreamEvent::Usage(usage);
+            }
 
             yield StreamEvent::Complete(chat_response.id);
         };
@@ -335,23 +616,43 @@ impl LlmClient {
         Ok(Box::pin(stream))
     }
 
+    /// 本地服务（Ollama、llama.cpp server）提供与 OpenAI 相同的 /v1/chat/completions 接口
     async fn generate_local_response(
         &self,
-        _messages: Vec<ChatMessage>,
+        messages: Vec<ChatMessage>,
         context_chunks: &[ContextChunk],
         _start_time: Instant,
     ) -> Result<StreamResponse> {
-        // Placeholder for local LLM implementation
-        let context_chunks = context_chunks.to_vec();
-        let stream = stream! {
-            if !context_chunks.is_empty() {
-                yield StreamEvent::Context(context_chunks);
-            }
-            yield StreamEvent::Token("Local LLM integration not implemented yet.".to_string());
-            yield StreamEvent::Complete(format!("resp_{}", uuid::Uuid::new_v4()));
-        };
+        let url = local_api_url(&self.config.base_url, "chat/completions");
+        self.send_chat_completion(&url, messages, context_chunks).await
+    }
 
-        Ok(Box::pin(stream))
+    /// 添加 Authorization 头；API Key 为空时（本地服务通常不需要）不发送
+    fn with_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if self.config.api_key.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("Bearer {}", self.config.api_key))
+        }
+    }
+
+    fn build_chat_request(&self, messages: Vec<ChatMessage>) -> ChatRequest {
+        // 对话级别的模型覆盖可能换成输出上限更小的模型
+        let max_output_tokens = self.model_registry.resolve(&self.config.model).max_output_tokens;
+        ChatRequest {
+            model: self.config.model.clone(),
+            messages,
+            stream: self.config.stream,
+            max_tokens: self.config.max_tokens.map(|tokens| tokens.min(max_output_tokens)),
+            temperature: self.config.temperature,
+            stream_options: (self.config.stream && self.requests_usage()).then_some(StreamOptions { include_usage: true }),
+        }
+    }
+
+    /// 部分 OpenAI 兼容服务会以 400 拒绝不认识的 `stream_options`，未配置时只对已知服务请求用量
+    fn requests_usage(&self) -> bool {
+        self.request_usage
+            .unwrap_or_else(|| supports_stream_usage(&self.config.base_url))
     }
 
     fn build_system_message(&self, context_chunks: &[ContextChunk]) -> String {
@@ -362,7 +663,15 @@ impl LlmClient {
         } else {
             system_message.push_str(prompts::get_context_header());
 
-            for (i, chunk) in context_chunks.iter().enumerate() {
+            let deduped;
+            let chunks: &[ContextChunk] = if self.dedup_context {
+                deduped = dedup_context_chunks(context_chunks);
+                &deduped
+            } else {
+                context_chunks
+            };
+
+            for (i, chunk) in chunks.iter().enumerate() {
                 system_message.push_str(&format!(
                     "---\n文档 {} (文件名: {}，相关度: {:.2})\n{}\n\n",
                     i + 1,
@@ -373,6 +682,9 @@ impl LlmClient {
             }
 
             system_message.push_str(prompts::get_context_footer());
+            if self.low_confidence_caveat {
+                system_message.push_str(prompts::get_low_confidence_prompt());
+            }
         }
 
         system_message
@@ -381,9 +693,7 @@ impl LlmClient {
     async fn test_openai_connection(&self) -> Result<bool> {
         let url = format!("{}/models", self.config.base_url);
 
-        let response = self.client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
+        let response = self.with_auth(self.client.get(&url))
             .send()
             .await?;
 
@@ -396,8 +706,8 @@ impl LlmClient {
     }
 
     async fn test_local_connection(&self) -> Result<bool> {
-        let response = self.client
-            .get(&self.config.base_url)
+        let url = local_api_url(&self.config.base_url, "models");
+        let response = self.with_auth(self.client.get(&url))
             .send()
             .await?;
 
@@ -430,17 +740,24 @@ impl LlmClient {
             }
         }
 
-        if let Some(max_tokens) = config.max_tokens {
-            if max_tokens == 0 || max_tokens > 32000 {
-                return Err(anyhow!("Max tokens must be between 1 and 32000"));
-            }
+        if config.max_tokens == Some(0) {
+            return Err(anyhow!("Max tokens must be greater than 0"));
         }
 
         Ok(())
     }
 
+    /// 校验 max_tokens 不超过模型的最大输出（按模型登记表）
+    fn validate_model_limits(config: &LlmConfig, model_registry: &ModelRegistry) -> Result<()> {
+        match config.max_tokens {
+            Some(max_tokens) => model_registry.validate_max_tokens(&config.model, max_tokens),
+            None => Ok(()),
+        }
+    }
+
     pub fn update_config(&mut self, config: LlmConfig) -> Result<()> {
         Self::validate_config(&config)?;
+        Self::validate_model_limits(&config, &self.model_registry)?;
         self.config = config;
         Ok(())
     }
@@ -448,6 +765,46 @@ impl LlmClient {
     pub fn get_config(&self) -> &LlmConfig {
         &self.config
     }
+
+    /// 复制一个使用不同 temperature 的客户端（None 表示沿用当前配置），用于重新生成回答
+    pub fn with_temperature(&self, temperature: Option<f32>) -> Result<Self> {
+        let mut client = self.clone();
+        if temperature.is_some() {
+            client.config.temperature = temperature;
+            Self::validate_config(&client.config)?;
+        }
+        Ok(client)
+    }
+
+    /// 复制一个使用不同 max_tokens 的客户端（None 表示沿用当前配置），用于单次请求的参数覆盖
+    pub fn with_max_tokens(&self, max_tokens: Option<u32>) -> Result<Self> {
+        let mut client = self.clone();
+        if max_tokens.is_some() {
+            client.config.max_tokens = max_tokens;
+            Self::validate_config(&client.config)?;
+            Self::validate_model_limits(&client.config, &client.model_registry)?;
+        }
+        Ok(client)
+    }
+
+    /// 复制一个在上下文之后附加低置信度提醒的客户端（检索结果的相关度都很低时使用）
+    pub fn with_low_confidence_caveat(&self, enabled: bool) -> Self {
+        let mut client = self.clone();
+        client.low_confidence_caveat = enabled;
+        client
+    }
+
+    /// 复制一个使用不同模型的客户端（None 表示沿用当前配置），用于对话级别的模型覆盖
+    pub fn with_model(&self, model: Option<&str>) -> Result<Self> {
+        let mut client = self.clone();
+        if let Some(model) = model {
+            if model.trim().is_empty() {
+                return Err(anyhow!("Model name cannot be empty"));
+            }
+            client.config.model = model.trim().to_string();
+        }
+        Ok(client)
+    }
 }
 
 impl Default for LlmConfig {
@@ -464,9 +821,178 @@ impl Default for LlmConfig {
     }
 }
 
+/// 请求消息的估算 token 总数
+fn prompt_tokens(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(|m| DocumentChunk::estimate_token_count(&m.content) as usize).sum()
+}
+
+/// 提示词超出预算时从最早的历史消息开始丢弃（保留系统消息和最后一条消息），返回丢弃的消息数
+fn trim_to_budget(messages: &mut Vec<ChatMessage>, budget: usize) -> usize {
+    let mut total = prompt_tokens(messages);
+    let mut dropped = 0;
+    while total > budget && messages.len() > 2 {
+        let removed = messages.remove(1);
+        total -= DocumentChunk::estimate_token_count(&removed.content) as usize;
+        dropped += 1;
+    }
+    dropped
+}
+
+/// 一次 LLM 请求的延迟明细（从发出请求开始计时）
+#[derive(Debug, Clone)]
+pub struct LlmRequestTimings {
+    pub request_id: Option<String>,
+    pub model: String,
+    /// 首个 token 到达的时间；没有收到 token 时为 None
+    pub time_to_first_token: Option<Duration>,
+    /// 流结束（完成或出错）的时间
+    pub total_duration: Duration,
+    /// 收到的 token 事件数
+    pub token_count: usize,
+    pub error: Option<String>,
+}
+
+impl LlmRequestTimings {
+    fn log(&self) {
+        log::info!(
+            "📊 [LLM] request_id={} model={} ttft_ms={} total_ms={} tokens={} status={}",
+            self.request_id.as_deref().unwrap_or("-"),
+            self.model,
+            self.time_to_first_token
+                .map(|d| d.as_millis().to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            self.total_duration.as_millis(),
+            self.token_count,
+            if self.error.is_some() { "error" } else { "ok" }
+        );
+    }
+}
+
+/// 包装响应流以记录延迟：首个 Token 事件打点得到 TTFT，Complete/Error 或流结束时调用 `on_finish`
+///
+/// 调用方在收到 Error 后通常直接丢弃流，因此在转发 Complete/Error 之前就完成记录
+pub fn instrument_stream<F>(mut inner: StreamResponse, model: String, started: Instant, on_finish: F) -> StreamResponse
+where
+    F: FnOnce(LlmRequestTimings) + Send + 'static,
+{
+    Box::pin(stream! {
+        let mut on_finish = Some(on_finish);
+        let mut timings = LlmRequestTimings {
+            request_id: None,
+            model,
+            time_to_first_token: None,
+            total_duration: Duration::ZERO,
+            token_count: 0,
+            error: None,
+        };
+
+        while let Some(event) = inner.next().await {
+            let finished = match &event {
+                StreamEvent::Token(_) => {
+                    timings.time_to_first_token.get_or_insert_with(|| started.elapsed());
+                    timings.token_count += 1;
+                    false
+                }
+                StreamEvent::Context(_) | StreamEvent::Usage(_) => false,
+                StreamEvent::Complete(response_id) => {
+                    timings.request_id = Some(response_id.clone());
+                    true
+                }
+                StreamEvent::Error(error) => {
+                    timings.error = Some(error.clone());
+                    true
+                }
+            };
+            if finished {
+                if let Some(on_finish) = on_finish.take() {
+                    timings.total_duration = started.elapsed();
+                    on_finish(timings.clone());
+                }
+            }
+            yield event;
+        }
+
+        if let Some(on_finish) = on_finish.take() {
+            timings.total_duration = started.elapsed();
+            on_finish(timings);
+        }
+    })
+}
+
+/// 读取整个流，拼接所有 Token；遇到 Error 事件时返回错误
+pub async fn collect_stream_text(mut stream: StreamResponse) -> Result<String> {
+    let mut text = String::new();
+    while let Some(event) = stream.next().await {
+        match event {
+            StreamEvent::Token(token) => text.push_str(&token),
+            StreamEvent::Error(error) => return Err(anyhow!("LLM 响应错误: {}", error)),
+            StreamEvent::Context(_) | StreamEvent::Complete(_) | StreamEvent::Usage(_) => {}
+        }
+    }
+    Ok(text)
+}
+
+/// 已知支持 `stream_options.include_usage` 的服务地址
+const STREAM_USAGE_HOSTS: &[&str] = &[
+    "api.openai.com",
+    "dashscope.aliyuncs.com",
+    "dashscope-intl.aliyuncs.com",
+];
+
+fn supports_stream_usage(base_url: &str) -> bool {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| STREAM_USAGE_HOSTS.contains(&host)))
+        .unwrap_or(false)
+}
+
+/// 本地服务的 OpenAI 兼容接口地址：`{base_url}/v1/{path}`（base_url 已带 /v1 时不重复添加）
+fn local_api_url(base_url: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let base = base.strip_suffix("/v1").unwrap_or(base);
+    format!("{}/v1/{}", base, path)
+}
+
+/// 归一化块内容用于比较：合并空白并转小写
+fn normalize_for_dedup(content: &str) -> String {
+    content.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// 去除内容重复的上下文块
+///
+/// 按相关度从高到低处理，若某块内容（归一化后）是已保留块的子串，或包含已保留块，
+/// 则视为重复并跳过，从而保留相关度最高的那一份。输出保持原有顺序。
+fn dedup_context_chunks(chunks: &[ContextChunk]) -> Vec<ContextChunk> {
+    let normalized: Vec<String> = chunks.iter().map(|c| normalize_for_dedup(&c.content)).collect();
+
+    let mut order: Vec<usize> = (0..chunks.len()).collect();
+    order.sort_by(|&a, &b| {
+        chunks[b].relevance_score
+            .partial_cmp(&chunks[a].relevance_score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut kept: Vec<usize> = Vec::new();
+    for i in order {
+        let is_duplicate = kept.iter().any(|&k| {
+            normalized[k].contains(normalized[i].as_str()) || normalized[i].contains(normalized[k].as_str())
+        });
+        if is_duplicate {
+            log::debug!("跳过重复的上下文块: {} (相关度 {:.2})", chunks[i].filename, chunks[i].relevance_score);
+        } else {
+            kept.push(i);
+        }
+    }
+
+    kept.sort_unstable();
+    kept.into_iter().map(|i| chunks[i].clone()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::conversation::MessageRole;
+    use uuid::Uuid;
 
     #[test]
     fn test_llm_config_validation() {
@@ -490,6 +1016,84 @@ mod tests {
         assert!(LlmClient::validate_config(&config).is_err());
     }
 
+    #[test]
+    fn test_conversation_model_override_reaches_request() {
+        let mut config = LlmConfig::default();
+        config.api_key = "test_key".to_string();
+        config.model = "qwen-turbo".to_string();
+        let client = LlmClient::new(config).unwrap();
+
+        let message = || vec![ChatMessage { role: "user".to_string(), content: "你好".to_string() }];
+
+        // 设置了模型覆盖的对话
+        let overridden = client.with_model(Some("qwen-max")).unwrap();
+        assert_eq!(overridden.build_chat_request(message()).model, "qwen-max");
+
+        // 其他对话仍使用默认模型
+        let default = client.with_model(None).unwrap();
+        assert_eq!(default.build_chat_request(message()).model, "qwen-turbo");
+        assert_eq!(client.build_chat_request(message()).model, "qwen-turbo");
+
+        assert!(client.with_model(Some("  ")).is_err());
+    }
+
+    #[test]
+    fn test_per_request_generation_overrides_reach_request() {
+        let mut config = LlmConfig::default();
+        config.api_key = "test_key".to_string();
+        config.model = "gpt-4o".to_string();
+        config.max_tokens = Some(2000);
+        config.temperature = Some(0.7);
+        let client = LlmClient::new(config).unwrap();
+
+        let message = || vec![ChatMessage { role: "user".to_string(), content: "你好".to_string() }];
+
+        // 指定的参数只影响本次请求
+        let overridden = client
+            .with_temperature(Some(0.2))
+            .and_then(|c| c.with_max_tokens(Some(512)))
+            .unwrap();
+        let request = overridden.build_chat_request(message());
+        assert_eq!((request.temperature, request.max_tokens), (Some(0.2), Some(512)));
+
+        // 未指定的参数使用配置
+        let defaults = client.with_temperature(None).and_then(|c| c.with_max_tokens(None)).unwrap();
+        let request = defaults.build_chat_request(message());
+        assert_eq!((request.temperature, request.max_tokens), (Some(0.7), Some(2000)));
+        let partial = client.with_max_tokens(Some(100)).unwrap().build_chat_request(message());
+        assert_eq!((partial.temperature, partial.max_tokens), (Some(0.7), Some(100)));
+
+        // 与配置相同的范围校验
+        assert!(client.with_temperature(Some(2.5)).is_err());
+        assert!(client.with_max_tokens(Some(0)).is_err());
+        assert!(client.with_max_tokens(Some(100_000)).is_err());
+    }
+
+    #[test]
+    fn test_stream_usage_is_requested_only_from_known_providers_unless_configured() {
+        let message = || vec![ChatMessage { role: "user".to_string(), content: "你好".to_string() }];
+        let client_for = |base_url: &str| {
+            LlmClient::new(LlmConfig {
+                api_key: "test_key".to_string(),
+                base_url: base_url.to_string(),
+                ..LlmConfig::default()
+            })
+            .unwrap()
+        };
+
+        let dashscope = client_for("https://dashscope.aliyuncs.com/compatible-mode/v1");
+        assert!(dashscope.build_chat_request(message()).stream_options.is_some());
+
+        let mut proxy = client_for("https://llm-proxy.example.com/v1");
+        assert!(proxy.build_chat_request(message()).stream_options.is_none());
+        proxy.set_request_usage(Some(true));
+        assert!(proxy.build_chat_request(message()).stream_options.is_some());
+
+        let mut openai = client_for("https://api.openai.com/v1");
+        openai.set_request_usage(Some(false));
+        assert!(openai.build_chat_request(message()).stream_options.is_none());
+    }
+
     #[test]
     fn test_llm_provider_display() {
         assert_eq!(LlmProvider::OpenAI.to_string(), "OpenAI");
@@ -514,6 +1118,9 @@ mod tests {
                 filename: "test.txt".to_string(),
                 content: "This is test content".to_string(),
                 relevance_score: 0.9,
+                project_id: None,
+                section: None,
+                other_filenames: Vec::new(),
             }
         ];
 
@@ -524,6 +1131,164 @@ mod tests {
         assert!(message.contains("This is test content"));
     }
 
+    #[test]
+    fn test_long_history_and_low_score_chunks_are_trimmed_to_context_budget() {
+        let mut config = LlmConfig::default();
+        config.api_key = "test_key".to_string();
+        let mut client = LlmClient::new(config).unwrap();
+        client.set_max_context_tokens(Some(3000));
+
+        let conversation_id = Uuid::new_v4();
+        let messages: Vec<Message> = (0..100)
+            .map(|i| {
+                let role = if i % 2 == 0 { MessageRole::User } else { MessageRole::Assistant };
+                Message::new(conversation_id, role, format!("第 {} 条消息：{}", i, "关于知识库的问题和回答".repeat(10))).unwrap()
+            })
+            .collect();
+        let chunk = |filename: &str, score: f64| ContextChunk {
+            document_id: format!("doc-{}", filename),
+            filename: filename.to_string(),
+            content: format!("{} 的内容", filename).repeat(20),
+            relevance_score: score,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let chunks = vec![chunk("a.md", 0.9), chunk("b.md", 0.5), chunk("c.md", 0.7)];
+
+        let (prompt, kept) = client.build_prompt_within_budget(&messages, &chunks);
+        assert!(prompt_tokens(&prompt) <= 3000);
+        assert!(prompt.len() < 101);
+        assert_eq!(prompt[0].role, "system");
+        assert_eq!(prompt.last().unwrap().content, messages[99].content);
+        // 保留的是最近的消息，上下文块不受影响
+        assert_eq!(prompt[1].content, messages[101 - prompt.len()].content);
+        assert_eq!(kept.len(), 3);
+
+        // 只剩最后一条消息仍超出预算时，从相关度最低的块开始丢弃
+        let full_tokens = prompt_tokens(&[
+            ChatMessage { role: "system".to_string(), content: client.build_system_message(&chunks) },
+            ChatMessage { role: "user".to_string(), content: messages[99].content.clone() },
+        ]);
+        client.set_max_context_tokens(Some(full_tokens - 1));
+        let (prompt, kept) = client.build_prompt_within_budget(&messages, &chunks);
+        assert_eq!(prompt.len(), 2);
+        let kept: Vec<&str> = kept.iter().map(|c| c.filename.as_str()).collect();
+        assert_eq!(kept, vec!["a.md", "c.md"]);
+        assert!(prompt[0].content.contains("a.md") && !prompt[0].content.contains("b.md"));
+        // 保存和展示的来源与实际发送给模型的上下文块一致
+        let fitted: Vec<String> = client.fit_context_to_budget(&messages, &chunks).into_iter().map(|c| c.filename).collect();
+        assert_eq!(fitted, vec!["a.md", "c.md"]);
+    }
+
+    #[test]
+    fn test_overlapping_chunks_are_deduplicated() {
+        let chunk = |content: &str, score: f64| ContextChunk {
+            document_id: "doc1".to_string(),
+            filename: "guide.md".to_string(),
+            content: content.to_string(),
+            relevance_score: score,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        };
+        let context_chunks = vec![
+            chunk("SeekDB supports  hybrid search.", 0.6),
+            chunk("Intro. SeekDB supports hybrid search. More details follow.", 0.8),
+            chunk("Unrelated paragraph about pricing.", 0.5),
+        ];
+
+        let deduped = dedup_context_chunks(&context_chunks);
+        assert_eq!(deduped.len(), 2);
+        assert_eq!(deduped[0].relevance_score, 0.8);
+
+        let config = LlmConfig {
+            api_key: "test_key".to_string(),
+            ..LlmConfig::default()
+        };
+        let mut client = LlmClient::new(config).unwrap();
+        let message = client.build_system_message(&context_chunks);
+        assert!(message.contains("文档 2"));
+        assert!(!message.contains("文档 3"));
+        assert!(!message.contains("SeekDB supports  hybrid search."));
+
+        client.set_context_dedup(false);
+        let message = client.build_system_message(&context_chunks);
+        assert!(message.contains("文档 3"));
+    }
+
+    #[test]
+    fn test_sse_buffer_reassembles_split_multibyte_char() {
+        let mut buffer = SseLineBuffer::new();
+        let line = "data: {\"content\":\"你好\"}\n".as_bytes();
+
+        // 在"你"（3 字节）的中间切开
+        let split = line.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        assert!(buffer.push(&line[..split]).is_empty());
+
+        let lines = buffer.push(&line[split..]);
+        assert_eq!(lines, vec!["data: {\"content\":\"你好\"}".to_string()]);
+        assert!(!lines[0].contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn test_sse_buffer_keeps_incomplete_tail() {
+        let mut buffer = SseLineBuffer::new();
+        let lines = buffer.push("data: a\ndata: 中".as_bytes());
+        assert_eq!(lines, vec!["data: a".to_string()]);
+
+        let lines = buffer.push("文\n".as_bytes());
+        assert_eq!(lines, vec!["data: 中文".to_string()]);
+    }
+
+    #[test]
+    fn test_sse_variants_from_nonstandard_gateways_still_yield_tokens() {
+        fn tokens(body: &str) -> (Vec<String>, Vec<String>, bool) {
+            let mut buffer = SseLineBuffer::new();
+            let mut parser = SseParser::default();
+            let (mut tokens, mut errors) = (Vec::new(), Vec::new());
+            for line in buffer.push(body.as_bytes()) {
+                for event in parser.handle_line(&line) {
+                    match event {
+                        StreamEvent::Token(token) => tokens.push(token),
+                        StreamEvent::Error(error) => errors.push(error),
+                        _ => {}
+                    }
+                }
+            }
+            (tokens, errors, parser.is_done())
+        }
+
+        // 标准 OpenAI 格式，缺少 id/created 等字段也能解析
+        let (standard, _, done) = tokens(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+             data: {\"choices\":[{\"delta\":{\"content\":\"你好\"}}]}\n\n\
+             data: [DONE]\n\n",
+        );
+        assert_eq!(standard, vec!["你好"]);
+        assert!(done);
+
+        // data: 后没有空格、CRLF 换行、心跳注释和 event: message
+        let (compact, _, done) = tokens(
+            ": keep-alive\r\n\r\n\
+             event: message\r\ndata:{\"choices\":[{\"delta\":{\"content\":\"世界\"}}]}\r\n\r\n\
+             data:[DONE]\r\n",
+        );
+        assert_eq!(compact, vec!["世界"]);
+        assert!(done);
+
+        // 内容放在 message.content 或 text 中
+        let (wrapped, _, _) = tokens(
+            "data: {\"choices\":[{\"message\":{\"content\":\"甲\"}}]}\n\
+             data: {\"choices\":[{\"text\":\"乙\"}]}\n",
+        );
+        assert_eq!(wrapped, vec!["甲", "乙"]);
+
+        // event: error 的数据作为错误返回
+        let (_, errors, _) = tokens("event: error\ndata: {\"error\":{\"message\":\"quota exceeded\"}}\n\n");
+        assert_eq!(errors, vec!["quota exceeded"]);
+    }
+
     #[test]
     fn test_chat_message_serialization() {
         let message = ChatMessage {
@@ -552,6 +1317,130 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[test]
+    fn test_local_provider_uses_v1_endpoints_without_empty_auth() {
+        assert_eq!(local_api_url("http://localhost:8080", "models"), "http://localhost:8080/v1/models");
+        assert_eq!(local_api_url("http://localhost:11434/v1/", "chat/completions"), "http://localhost:11434/v1/chat/completions");
+
+        let config = LlmConfig {
+            provider: LlmProvider::Local,
+            api_key: String::new(),
+            model: "llama3".to_string(),
+            base_url: "http://localhost:8080".to_string(),
+            ..LlmConfig::default()
+        };
+        let client = LlmClient::new(config.clone()).unwrap();
+        let request = client.with_auth(client.client.get("http://localhost:8080/v1/models")).build().unwrap();
+        assert!(request.headers().get("Authorization").is_none());
+
+        let client = LlmClient::new(LlmConfig { api_key: "local-secret".to_string(), ..config }).unwrap();
+        let request = client.with_auth(client.client.get("http://localhost:8080/v1/models")).build().unwrap();
+        assert_eq!(request.headers()["Authorization"], "Bearer local-secret");
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_text_concatenates_tokens_and_surfaces_errors() {
+        let events = vec![
+            StreamEvent::Context(Vec::new()),
+            StreamEvent::Token("SeekDB 是".to_string()),
+            StreamEvent::Token("一个数据库".to_string()),
+            StreamEvent::Complete("resp_1".to_string()),
+        ];
+        let text = collect_stream_text(Box::pin(futures::stream::iter(events))).await.unwrap();
+        assert_eq!(text, "SeekDB 是一个数据库");
+
+        let events = vec![
+            StreamEvent::Token("部分".to_string()),
+            StreamEvent::Error("连接中断".to_string()),
+            StreamEvent::Token("不会被读取".to_string()),
+        ];
+        let error = collect_stream_text(Box::pin(futures::stream::iter(events))).await.unwrap_err();
+        assert!(error.to_string().contains("连接中断"));
+    }
+
+    #[tokio::test]
+    async fn test_transient_503_on_initial_request_is_retried() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 本地模拟服务：第一次请求返回 503，第二次返回完整回答
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let body = r#"{"id":"resp_1","object":"chat.completion","created":0,"model":"qwen-max","choices":[{"index":0,"message":{"role":"assistant","content":"重试成功"}}]}"#;
+            let responses = [
+                "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbusy".to_string(),
+                format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body),
+            ];
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 64 * 1024];
+                let _ = socket.read(&mut buf).await.unwrap();
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            }
+        });
+
+        let config = LlmConfig {
+            api_key: "test-key".to_string(),
+            model: "qwen-max".to_string(),
+            base_url,
+            stream: false,
+            ..Default::default()
+        };
+        let mut client = LlmClient::new(config).unwrap();
+        client.set_retry_policy(RetryPolicy { max_retries: 2, initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() });
+
+        let text = client.generate_text("系统", "你好").await.unwrap();
+        assert_eq!(text, "重试成功");
+        server.await.unwrap();
+
+        // 不重试时 503 直接返回错误
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 64 * 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbusy")
+                .await
+                .unwrap();
+        });
+        client.update_config(LlmConfig { base_url, ..client.get_config().clone() }).unwrap();
+        client.set_retry_policy(RetryPolicy { max_retries: 0, initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() });
+        let error = client.generate_text("系统", "你好").await.unwrap_err();
+        assert!(error.to_string().contains("503"));
+    }
+
+    #[tokio::test]
+    async fn test_request_timings_record_ttft_and_total_duration() {
+        let inner: StreamResponse = Box::pin(stream! {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            yield StreamEvent::Token("你好".to_string());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            yield StreamEvent::Token("，世界".to_string());
+            yield StreamEvent::Complete("resp_42".to_string());
+        });
+
+        let recorded = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let sink = recorded.clone();
+        let stream = instrument_stream(inner, "qwen-max".to_string(), Instant::now(), move |timings| {
+            *sink.lock().unwrap() = Some(timings);
+        });
+
+        // 包装后的流原样转发事件
+        assert_eq!(collect_stream_text(stream).await.unwrap(), "你好，世界");
+
+        let timings = recorded.lock().unwrap().clone().expect("timings should be recorded");
+        assert_eq!(timings.request_id.as_deref(), Some("resp_42"));
+        assert_eq!(timings.model, "qwen-max");
+        assert_eq!(timings.token_count, 2);
+        let ttft = timings.time_to_first_token.unwrap();
+        assert!(ttft >= Duration::from_millis(20));
+        assert!(timings.total_duration >= ttft + Duration::from_millis(20));
+        assert!(timings.error.is_none());
+    }
+
     #[test]
     fn test_config_update() {
         let mut config = LlmConfig::default();
@@ -1,16 +1,40 @@
 pub mod app_state;
+pub mod chunk_size_tuning;
+pub mod conversation_export;
 pub mod conversation_service;
 pub mod dashscope_embedding_service;
 pub mod document_processor;
 pub mod document_service;
+pub mod embedding_batcher;
+pub mod embedding_cache;
+pub mod embedding_priority;
+pub mod embedding_provider;
+pub mod generation_registry;
+pub mod generation_retry;
+pub mod health_check;
 // pub mod embedded_vector_db; // Removed - replaced by seekdb_adapter
 pub mod llm_client;
+pub mod model_registry;
+pub mod openai_embedding_service;
+pub mod pasted_document;
+pub mod project_bundle;
+pub mod project_quota;
 pub mod project_service;
 pub mod prompts;
 pub mod python_env;
 pub mod python_subprocess;
+pub mod query_filter;
+pub mod reranker;
+pub mod response_filter;
+pub mod retrieval_benchmark;
+pub mod retrieval_log;
 pub mod seekdb_adapter;
 pub mod seekdb_package;
+pub mod sentence_buffer;
 pub mod simple_embeddings;
 pub mod speech_service;
+pub mod structure_chunker;
+pub mod task_registry;
+pub mod temp_artifacts;
+pub mod usage_report;
 pub mod vector_db;
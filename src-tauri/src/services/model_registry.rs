@@ -0,0 +1,169 @@
+//! 模型上下文窗口登记表
+//!
+//! 记录常见模型的上下文窗口和默认最大输出 token 数，用于校验 max_tokens 和控制提示词长度。
+//! 未登记的自定义模型可以在配置 `llm.modelLimits` 中补充，或通过 `llm.defaultContextWindow` 兜底。
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 模型的上下文窗口和最大输出 token 数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelLimits {
+    #[serde(rename = "contextWindow")]
+    pub context_window: u32,
+    #[serde(rename = "maxOutputTokens")]
+    pub max_output_tokens: u32,
+}
+
+const fn limits(context_window: u32, max_output_tokens: u32) -> ModelLimits {
+    ModelLimits { context_window, max_output_tokens }
+}
+
+/// 内置登记表：模型名（或带日期/版本后缀的模型名前缀）→ 限制
+const BUILTIN_MODELS: &[(&str, ModelLimits)] = &[
+    ("gpt-4o", limits(128_000, 16_384)),
+    ("gpt-4o-mini", limits(128_000, 16_384)),
+    ("gpt-4-turbo", limits(128_000, 4_096)),
+    ("gpt-4", limits(8_192, 4_096)),
+    ("gpt-3.5-turbo", limits(16_385, 4_096)),
+    ("qwen-max", limits(32_768, 8_192)),
+    ("qwen-plus", limits(131_072, 8_192)),
+    ("qwen-turbo", limits(131_072, 8_192)),
+    ("qwen-long", limits(1_000_000, 8_192)),
+    ("claude-3-5-sonnet", limits(200_000, 8_192)),
+    ("claude-3-5-haiku", limits(200_000, 8_192)),
+    ("claude-3-opus", limits(200_000, 4_096)),
+    ("claude-3-haiku", limits(200_000, 4_096)),
+    ("deepseek-chat", limits(65_536, 8_192)),
+    ("glm-4", limits(128_000, 4_096)),
+];
+
+/// 未登记模型的默认限制
+pub const DEFAULT_MODEL_LIMITS: ModelLimits = limits(8_192, 4_096);
+
+/// 按最长前缀匹配内置登记表（忽略大小写），如 gpt-4o-2024-08-06 匹配 gpt-4o 而不是 gpt-4
+fn builtin_limits(model: &str) -> Option<ModelLimits> {
+    let model = model.trim().to_lowercase();
+    BUILTIN_MODELS
+        .iter()
+        .filter(|(name, _)| model.starts_with(name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, limits)| *limits)
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelRegistry {
+    overrides: HashMap<String, ModelLimits>,
+    fallback: ModelLimits,
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            fallback: DEFAULT_MODEL_LIMITS,
+        }
+    }
+}
+
+impl ModelRegistry {
+    /// `overrides` 优先于内置登记表；`default_context_window` 用于两者都没有登记的模型
+    pub fn new(overrides: HashMap<String, ModelLimits>, default_context_window: Option<u32>) -> Self {
+        let fallback = match default_context_window {
+            Some(context_window) => limits(
+                context_window,
+                DEFAULT_MODEL_LIMITS.max_output_tokens.min(context_window / 2),
+            ),
+            None => DEFAULT_MODEL_LIMITS,
+        };
+        Self {
+            overrides: overrides
+                .into_iter()
+                .map(|(model, limits)| (model.trim().to_lowercase(), limits))
+                .collect(),
+            fallback,
+        }
+    }
+
+    pub fn resolve(&self, model: &str) -> ModelLimits {
+        self.overrides
+            .get(&model.trim().to_lowercase())
+            .copied()
+            .or_else(|| builtin_limits(model))
+            .unwrap_or(self.fallback)
+    }
+
+    /// 模型是否已登记（配置覆盖或内置登记表），未登记的模型使用默认限制
+    pub fn is_registered(&self, model: &str) -> bool {
+        self.overrides.contains_key(&model.trim().to_lowercase()) || builtin_limits(model).is_some()
+    }
+
+    /// 校验 max_tokens 不为 0 且不超过模型的最大输出
+    ///
+    /// 未登记的模型只知道默认限制，超出时不报错，请求时按默认上限发送
+    pub fn validate_max_tokens(&self, model: &str, max_tokens: u32) -> Result<()> {
+        let limits = self.resolve(model);
+        if max_tokens > limits.max_output_tokens && !self.is_registered(model) {
+            log::warn!(
+                "⚠️  模型 {} 未登记，max_tokens {} 超过默认上限，请求时按 {} 发送（可在 llm.modelLimits 中登记该模型）",
+                model,
+                max_tokens,
+                limits.max_output_tokens
+            );
+            return Ok(());
+        }
+        if max_tokens == 0 || max_tokens > limits.max_output_tokens {
+            return Err(anyhow!(
+                "Max tokens must be between 1 and {} for model {}",
+                limits.max_output_tokens,
+                model
+            ));
+        }
+        Ok(())
+    }
+
+    /// 提示词（系统消息 + 对话历史）可用的 token 数：上下文窗口减去为输出预留的部分
+    pub fn prompt_budget(&self, model: &str, max_tokens: Option<u32>) -> usize {
+        let limits = self.resolve(model);
+        let reserved = max_tokens.unwrap_or(limits.max_output_tokens).min(limits.max_output_tokens);
+        limits.context_window.saturating_sub(reserved) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_model_uses_registry_and_unknown_uses_configured_default() {
+        let registry = ModelRegistry::new(
+            HashMap::from([("my-finetune".to_string(), limits(16_000, 2_000))]),
+            Some(32_000),
+        );
+
+        assert_eq!(registry.resolve("gpt-4o"), limits(128_000, 16_384));
+        assert_eq!(registry.resolve("qwen-max").context_window, 32_768);
+        assert_eq!(registry.resolve("claude-3-5-sonnet-20241022").context_window, 200_000);
+        // 带后缀的模型名匹配最长的登记前缀
+        assert_eq!(registry.resolve("gpt-4o-2024-08-06").context_window, 128_000);
+        assert_eq!(registry.resolve("GPT-4").context_window, 8_192);
+
+        // 配置覆盖的自定义模型
+        assert_eq!(registry.resolve("My-Finetune"), limits(16_000, 2_000));
+        // 未登记的模型使用配置的默认上下文窗口
+        assert_eq!(registry.resolve("llama3:8b").context_window, 32_000);
+        assert_eq!(ModelRegistry::default().resolve("llama3:8b"), DEFAULT_MODEL_LIMITS);
+
+        assert!(registry.validate_max_tokens("gpt-4o", 16_000).is_ok());
+        assert!(registry.validate_max_tokens("gpt-4", 16_000).is_err());
+        assert!(registry.validate_max_tokens("my-finetune", 0).is_err());
+        // 未登记的模型超出默认上限时不报错（请求时截断），以兼容已有配置
+        assert!(!registry.is_registered("llama3:8b"));
+        assert!(ModelRegistry::default().validate_max_tokens("llama3:8b", 32_000).is_ok());
+        assert!(ModelRegistry::default().validate_max_tokens("llama3:8b", 0).is_err());
+
+        assert_eq!(registry.prompt_budget("gpt-4", Some(2_000)), 6_192);
+        assert_eq!(registry.prompt_budget("gpt-4", None), 4_096);
+    }
+}
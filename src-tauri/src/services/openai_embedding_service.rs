@@ -0,0 +1,272 @@
+//! OpenAI Embedding 服务
+//!
+//! 供无法使用 DashScope 的用户选择（`embedding.provider = "openai"`），默认模型 text-embedding-3-small。
+//! 与 DashScope 共用重试策略、批次拆分和响应对齐逻辑。
+
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::services::dashscope_embedding_service::{
+    align_embeddings, call_with_retry, plan_batches, EmbeddingItem, DEFAULT_EMBEDDING_DIMENSION, DEFAULT_MAX_BATCH_BYTES,
+};
+use crate::services::embedding_provider::{EmbeddingBatch, EmbeddingProvider};
+use crate::services::generation_retry::RetryPolicy;
+
+/// OpenAI 的默认 embedding 模型
+pub const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// OpenAI API 单次请求最多支持的文本数量
+const MAX_BATCH_TEXTS: usize = 2048;
+
+pub struct OpenAIEmbeddingService {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+    dimension: usize,
+    max_batch_bytes: usize,
+    ingestion_retry: RetryPolicy,
+    query_retry: RetryPolicy,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    /// 只有 text-embedding-3 系列支持指定输出维度
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    total_tokens: usize,
+}
+
+impl OpenAIEmbeddingService {
+    /// 创建 OpenAI Embedding 服务，`base_url` 不设置时使用 https://api.openai.com/v1
+    pub fn new(api_key: String, base_url: Option<String>) -> Result<Self> {
+        log::info!("🚀 初始化 OpenAI Embedding 服务...");
+
+        if api_key.is_empty() {
+            return Err(anyhow!("API Key 不能为空"));
+        }
+
+        let base_url = base_url.unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string());
+        log::info!("  - Base URL: {}", base_url);
+
+        // 超时按请求设置，见 RetryPolicy
+        let client = Client::builder().build()?;
+
+        Ok(Self {
+            client,
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: DEFAULT_OPENAI_EMBEDDING_MODEL.to_string(),
+            dimension: DEFAULT_EMBEDDING_DIMENSION,
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+            ingestion_retry: RetryPolicy::embedding_ingestion(),
+            query_retry: RetryPolicy::embedding_query(),
+        })
+    }
+
+    /// 设置 embedding 模型（默认 text-embedding-3-small）
+    pub fn with_model(mut self, model: String) -> Self {
+        log::info!("  - 模型: {}", model);
+        self.model = model;
+        self
+    }
+
+    /// 设置输出向量的维度（需与数据库 vector 列的维度一致）
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        log::info!("  - 向量维度: {}", dimension);
+        self.dimension = dimension;
+        self
+    }
+
+    /// 设置单次请求的最大文本字节数
+    pub fn with_max_batch_bytes(mut self, max_batch_bytes: usize) -> Self {
+        log::info!("  - 单次请求最大字节数: {}", max_batch_bytes);
+        self.max_batch_bytes = max_batch_bytes.max(1);
+        self
+    }
+
+    /// 分别设置文档摄取（embed_batch）和对话查询（embed_text）的重试策略
+    pub fn with_retry_policies(mut self, ingestion: RetryPolicy, query: RetryPolicy) -> Self {
+        self.ingestion_retry = ingestion;
+        self.query_retry = query;
+        self
+    }
+
+    /// 生成单个文本的 embedding（查询路径，使用查询重试策略）
+    pub async fn embed_text(&self, text: &str) -> Result<Vec<f64>> {
+        self.embed_with_policy(&[text.to_string()], &self.query_retry)
+            .await?
+            .embeddings
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("生成 embedding 失败"))
+    }
+
+    /// 批量生成 embeddings，超过数量或大小限制时自动拆分为多次请求
+    pub async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f64>>> {
+        Ok(self.embed_batch_with_usage(texts).await?.embeddings)
+    }
+
+    /// 批量生成 embeddings，同时返回 API 报告的 token 消耗
+    pub async fn embed_batch_with_usage(&self, texts: &[String]) -> Result<EmbeddingBatch> {
+        self.embed_with_policy(texts, &self.ingestion_retry).await
+    }
+
+    async fn embed_with_policy(&self, texts: &[String], policy: &RetryPolicy) -> Result<EmbeddingBatch> {
+        let mut all = EmbeddingBatch { embeddings: Vec::with_capacity(texts.len()), total_tokens: 0 };
+        for range in plan_batches(texts, MAX_BATCH_TEXTS, self.max_batch_bytes) {
+            let texts = &texts[range];
+            log::debug!("🔄 调用 OpenAI API 生成 {} 个 embeddings", texts.len());
+            let batch = call_with_retry(policy, || self.request_embeddings(texts, policy.timeout)).await?;
+            all.embeddings.extend(batch.embeddings);
+            all.total_tokens += batch.total_tokens;
+        }
+        Ok(all)
+    }
+
+    /// 实际调用 API（不包含重试逻辑）
+    async fn request_embeddings(&self, texts: &[String], timeout: Duration) -> Result<EmbeddingBatch> {
+        let response = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .bearer_auth(&self.api_key)
+            .timeout(timeout)
+            .json(&self.build_request(texts))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("OpenAI Embedding API 调用失败 [{}]: {}", status.as_u16(), error_text));
+        }
+
+        let result: EmbeddingResponse = response.json().await?;
+        self.parse_response(result, texts.len())
+    }
+
+    fn build_request<'a>(&'a self, texts: &'a [String]) -> EmbeddingRequest<'a> {
+        EmbeddingRequest {
+            model: &self.model,
+            input: texts,
+            dimensions: self.model.starts_with("text-embedding-3").then_some(self.dimension),
+        }
+    }
+
+    fn parse_response(&self, response: EmbeddingResponse, expected: usize) -> Result<EmbeddingBatch> {
+        let total_tokens = response.usage.map_or(0, |usage| usage.total_tokens);
+        log::debug!("✅ 成功生成 {} 个 embeddings，消耗 tokens: {}", response.data.len(), total_tokens);
+
+        let items = response
+            .data
+            .into_iter()
+            .map(|data| EmbeddingItem { text_index: data.index, embedding: data.embedding })
+            .collect();
+        let embeddings = align_embeddings(items, expected)?;
+        if let Some(embedding) = embeddings.iter().find(|e| e.len() != self.dimension) {
+            return Err(anyhow!(
+                "Embedding 维度不一致: 模型 {} 返回 {} 维向量，但配置的 embedding.dimension 为 {}",
+                self.model,
+                embedding.len(),
+                self.dimension
+            ));
+        }
+        Ok(EmbeddingBatch { embeddings, total_tokens })
+    }
+}
+
+impl EmbeddingProvider for OpenAIEmbeddingService {
+    fn embed_text<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(OpenAIEmbeddingService::embed_text(self, text))
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f64>>>> {
+        Box::pin(OpenAIEmbeddingService::embed_batch(self, texts))
+    }
+
+    fn embed_batch_with_usage<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<EmbeddingBatch>> {
+        Box::pin(OpenAIEmbeddingService::embed_batch_with_usage(self, texts))
+    }
+
+    /// 没有缓存和微批处理，与 embed_text 相同
+    fn embed_text_uncached<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<Vec<f64>>> {
+        Box::pin(OpenAIEmbeddingService::embed_text(self, text))
+    }
+
+    fn embedding_dim(&self) -> usize {
+        self.dimension
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_and_response_follow_the_openai_format() {
+        let service = OpenAIEmbeddingService::new("sk-test".to_string(), Some("http://localhost:8080/v1/".to_string()))
+            .unwrap()
+            .with_dimension(2);
+        assert_eq!(service.base_url, "http://localhost:8080/v1");
+
+        let texts = vec!["你好".to_string(), "hello".to_string()];
+        let request = serde_json::to_value(service.build_request(&texts)).unwrap();
+        assert_eq!(
+            request,
+            serde_json::json!({ "model": "text-embedding-3-small", "input": ["你好", "hello"], "dimensions": 2 })
+        );
+
+        // 旧模型不支持 dimensions 参数
+        let ada = OpenAIEmbeddingService::new("sk-test".to_string(), None)
+            .unwrap()
+            .with_model("text-embedding-ada-002".to_string());
+        assert!(serde_json::to_value(ada.build_request(&texts)).unwrap().get("dimensions").is_none());
+
+        // 按 index 对齐，维度不符时报错
+        let response: EmbeddingResponse = serde_json::from_value(serde_json::json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.3, 0.4] },
+                { "object": "embedding", "index": 0, "embedding": [0.1, 0.2] }
+            ],
+            "model": "text-embedding-3-small",
+            "usage": { "prompt_tokens": 4, "total_tokens": 4 }
+        }))
+        .unwrap();
+        let batch = service.parse_response(response, 2).unwrap();
+        assert_eq!(batch.embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+        assert_eq!(batch.total_tokens, 4);
+
+        let wrong_dimension: EmbeddingResponse =
+            serde_json::from_value(serde_json::json!({ "data": [{ "index": 0, "embedding": [0.1] }] })).unwrap();
+        assert!(service.parse_response(wrong_dimension, 1).unwrap_err().to_string().contains("维度不一致"));
+    }
+}
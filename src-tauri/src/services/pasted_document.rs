@@ -0,0 +1,181 @@
+//! 长消息自动入库
+//!
+//! 用户有时会把整篇文档直接粘贴到聊天框里。开启 `chat.ingestLongMessages` 后，
+//! 超过长度阈值的消息会被保存为应用数据目录下的 Markdown 文件，作为文档导入当前项目的知识库
+//! （分块 + 向量化），对话中只保留一条简短的说明。
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+/// 粘贴文档在应用数据目录下的存放目录
+pub const PASTED_DOCUMENTS_DIR: &str = "pasted_documents";
+
+/// 默认的长消息阈值（字符数）
+pub const DEFAULT_LONG_MESSAGE_THRESHOLD: usize = 4000;
+
+/// 文件名中标题部分的最大字符数
+const MAX_FILENAME_TITLE_CHARS: usize = 24;
+
+/// 用户消息的处理结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutedMessage {
+    /// 要作为用户消息保存到对话中的内容
+    pub stored_content: String,
+    /// 消息被导入为文档时的文件名
+    pub ingested_document: Option<String>,
+}
+
+/// 开启自动入库且消息超过阈值（按字符计）时，应导入为文档
+pub fn should_ingest(content: &str, enabled: bool, threshold: usize) -> bool {
+    enabled && threshold > 0 && content.chars().count() > threshold
+}
+
+pub fn pasted_documents_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(PASTED_DOCUMENTS_DIR)
+}
+
+/// 生成文档文件名：pasted-时间戳（精确到毫秒）-首行标题.md
+pub fn document_filename(content: &str, now: DateTime<Utc>) -> String {
+    let title: String = content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .take(MAX_FILENAME_TITLE_CHARS)
+        .collect();
+    let title = title
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    let timestamp = now.format("%Y%m%d-%H%M%S-%3f");
+    if title.is_empty() {
+        format!("pasted-{}.md", timestamp)
+    } else {
+        format!("pasted-{}-{}.md", timestamp, title)
+    }
+}
+
+/// 把粘贴的内容写入目录，返回文件路径；同名文件已存在时加上短 uuid 后缀，不覆盖之前的文档
+pub fn write_document(dir: &Path, content: &str, now: DateTime<Utc>) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let filename = document_filename(content, now);
+    let path = dir.join(&filename);
+    match create_new(&path, content) {
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let stem = filename.trim_end_matches(".md");
+            let suffix = &uuid::Uuid::new_v4().simple().to_string()[..8];
+            let path = dir.join(format!("{}-{}.md", stem, suffix));
+            create_new(&path, content)?;
+            Ok(path)
+        }
+        result => result.map(|_| path).map_err(Into::into),
+    }
+}
+
+fn create_new(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    fs::OpenOptions::new().write(true).create_new(true).open(path)?.write_all(content.as_bytes())
+}
+
+/// 代替原消息保存到对话中的说明
+pub fn ingestion_note(filename: &str, char_count: usize) -> String {
+    format!("📄 已将粘贴的长文本（{} 字符）作为文档「{}」添加到知识库", char_count, filename)
+}
+
+/// 导入完成后助手的回复
+pub fn ingestion_reply(filename: &str) -> String {
+    format!("文档「{}」已添加到当前项目的知识库，现在可以直接针对它提问了。", filename)
+}
+
+/// 决定用户消息的去向：需要入库时调用 `ingest(完整内容)`（返回文档文件名），
+/// 并用说明代替原消息；否则原样保存
+pub async fn route_user_message<F, Fut>(
+    content: &str,
+    enabled: bool,
+    threshold: usize,
+    ingest: F,
+) -> Result<RoutedMessage>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    if !should_ingest(content, enabled, threshold) {
+        return Ok(RoutedMessage {
+            stored_content: content.to_string(),
+            ingested_document: None,
+        });
+    }
+
+    let char_count = content.chars().count();
+    log::info!("📄 消息长度 {} 超过阈值 {}，作为文档导入知识库", char_count, threshold);
+    let filename = ingest(content.to_string())
+        .await
+        .map_err(|e| anyhow!("导入粘贴文本失败: {}", e))?;
+
+    Ok(RoutedMessage {
+        stored_content: ingestion_note(&filename, char_count),
+        ingested_document: Some(filename),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::conversation::{Message, MessageRole};
+    use chrono::TimeZone;
+    use std::cell::RefCell;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_over_threshold_message_is_ingested_as_document() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 17, 9, 30, 0).unwrap();
+        let dir = std::env::temp_dir().join(format!("pasted-doc-test-{}", Uuid::new_v4()));
+        let pasted = format!("# 部署手册\n\n{}", "第一步：安装依赖。".repeat(2000));
+        let ingested = RefCell::new(Vec::new());
+
+        let routed = route_user_message(&pasted, true, 4000, |content| {
+            let result = write_document(&dir, &content, now).map(|path| {
+                ingested.borrow_mut().push(path.clone());
+                path.file_name().unwrap().to_string_lossy().to_string()
+            });
+            async move { result }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(routed.ingested_document.as_deref(), Some("pasted-20261017-093000-000-部署手册.md"));
+        let path = ingested.borrow()[0].clone();
+        assert_eq!(fs::read_to_string(&path).unwrap(), pasted);
+
+        // 同一毫秒内再次粘贴同标题的内容不会覆盖之前的文档
+        let again = write_document(&dir, "# 部署手册\n\n第二版", now).unwrap();
+        assert_ne!(again, path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), pasted);
+        assert_eq!(fs::read_to_string(&again).unwrap(), "# 部署手册\n\n第二版");
+
+        // 对话中保存的是简短说明而不是整段文本
+        assert_ne!(routed.stored_content, pasted);
+        assert!(routed.stored_content.contains("部署手册"));
+        assert!(Message::new(Uuid::new_v4(), MessageRole::User, routed.stored_content.clone()).is_ok());
+
+        // 关闭时或未超过阈值时原样保存，不导入
+        for (content, enabled) in [(pasted.as_str(), false), ("短消息", true)] {
+            let routed = route_user_message(content, enabled, 4000, |_| async {
+                Err(anyhow!("不应导入"))
+            })
+            .await
+            .unwrap();
+            assert_eq!(routed.stored_content, content);
+            assert!(routed.ingested_document.is_none());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,485 @@
+//! 项目完整导出/导入（.mkb 包）
+//!
+//! `.mkb` 是一个 zip 包，以 JSON 保存项目信息、全部文档块（内容、向量、元数据）、对话和消息，
+//! 不依赖 SeekDB 的文件格式，可用于备份或在不同机器、不同版本之间迁移项目。
+//! 导入时为项目、文档、文档块、对话和消息分配新的 UUID，并同步改写它们之间的引用，
+//! 因此同一个包可以重复导入而不会与已有数据冲突。
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
+use uuid::Uuid;
+
+use crate::models::conversation::{Conversation, Message};
+use crate::models::project::Project;
+use crate::services::seekdb_adapter::{SeekDbAdapter, VectorDocument};
+
+/// 导出文件扩展名
+pub const BUNDLE_EXTENSION: &str = "mkb";
+
+/// 包格式版本，格式不兼容地变化时递增
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PROJECT_ENTRY: &str = "project.json";
+const CHUNKS_ENTRY: &str = "chunks.json";
+const CONVERSATIONS_ENTRY: &str = "conversations.json";
+const MESSAGES_ENTRY: &str = "messages.json";
+
+/// 包的描述信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub embedding_model: String,
+    /// 文档块向量的维度，项目没有带向量的文档块时为 None
+    pub embedding_dimension: Option<usize>,
+    /// 包中各类记录的数量，用于导入前预览；较早导出的包没有此项
+    #[serde(default)]
+    pub counts: Option<BundleCounts>,
+}
+
+/// 包中各类记录的数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleCounts {
+    pub documents: usize,
+    pub chunks: usize,
+    pub conversations: usize,
+    pub messages: usize,
+}
+
+impl BundleCounts {
+    fn of(chunks: &[VectorDocument], conversations: &[Conversation], messages: &[Message]) -> Self {
+        let documents: std::collections::HashSet<&str> = chunks.iter().map(|chunk| chunk.document_id.as_str()).collect();
+        Self {
+            documents: documents.len(),
+            chunks: chunks.len(),
+            conversations: conversations.len(),
+            messages: messages.len(),
+        }
+    }
+}
+
+/// 导入前对包的检查结果（不写入数据库）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleInspection {
+    pub format_version: u32,
+    pub app_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub project_name: String,
+    pub embedding_model: String,
+    pub embedding_dimension: Option<usize>,
+    /// 当前数据库向量列的维度
+    pub database_dimension: usize,
+    pub counts: BundleCounts,
+    /// 包内数据解压后的字节数，用于估算导入后增加的存储
+    pub uncompressed_bytes: u64,
+    /// 向量维度与数据库不一致时的提示，此时文档块会不带向量导入
+    pub warning: Option<String>,
+}
+
+/// 一个项目的全部数据
+#[derive(Debug, Clone)]
+pub struct ProjectBundle {
+    pub manifest: BundleManifest,
+    pub project: Project,
+    pub chunks: Vec<VectorDocument>,
+    pub conversations: Vec<Conversation>,
+    pub messages: Vec<Message>,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectImportReport {
+    pub project_id: String,
+    pub project_name: String,
+    pub chunks: usize,
+    pub conversations: usize,
+    pub messages: usize,
+    /// 向量维度与当前配置不一致时的提示；此时文档块不带向量导入，需要补全向量后才能语义检索
+    pub warning: Option<String>,
+}
+
+/// 从数据库收集一个项目的全部数据
+pub fn collect_project_bundle(db: &SeekDbAdapter, project: &Project, embedding_model: &str) -> Result<ProjectBundle> {
+    let project_key = project.id.to_string();
+    let chunks = db.get_project_chunks_with_embeddings(&project_key)?;
+    let conversations = db.load_conversations_by_project(&project_key)?;
+
+    let mut messages = Vec::new();
+    for conversation in &conversations {
+        messages.extend(db.load_messages_by_conversation(&conversation.id.to_string())?);
+    }
+
+    let embedding_dimension = chunks.iter().map(|chunk| chunk.embedding.len()).find(|len| *len > 0);
+    Ok(ProjectBundle {
+        manifest: BundleManifest {
+            format_version: BUNDLE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            embedding_model: embedding_model.to_string(),
+            embedding_dimension,
+            counts: Some(BundleCounts::of(&chunks, &conversations, &messages)),
+        },
+        project: project.clone(),
+        chunks,
+        conversations,
+        messages,
+    })
+}
+
+fn write_entry<W: Write + Seek, T: Serialize + ?Sized>(
+    zip: &mut zip::ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> Result<()> {
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(name, options)?;
+    serde_json::to_writer(&mut *zip, value)?;
+    Ok(())
+}
+
+fn read_entry<R: Read + Seek, T: DeserializeOwned>(archive: &mut zip::ZipArchive<R>, name: &str) -> Result<T> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|e| anyhow!("导出包缺少 {}: {}", name, e))?;
+    serde_json::from_reader(entry).with_context(|| format!("解析 {} 失败", name))
+}
+
+/// 将项目数据写为 .mkb 包
+pub fn write_bundle<W: Write + Seek>(writer: W, bundle: &ProjectBundle) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(writer);
+    write_entry(&mut zip, MANIFEST_ENTRY, &bundle.manifest)?;
+    write_entry(&mut zip, PROJECT_ENTRY, &bundle.project)?;
+    write_entry(&mut zip, CHUNKS_ENTRY, &bundle.chunks)?;
+    write_entry(&mut zip, CONVERSATIONS_ENTRY, &bundle.conversations)?;
+    write_entry(&mut zip, MESSAGES_ENTRY, &bundle.messages)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// 打开 .mkb 包并读取、校验 manifest
+fn open_bundle<R: Read + Seek>(reader: R) -> Result<(zip::ZipArchive<R>, BundleManifest)> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| anyhow!("无法打开导出包: {}", e))?;
+
+    let manifest: BundleManifest = read_entry(&mut archive, MANIFEST_ENTRY)?;
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(anyhow!(
+            "导出包格式版本 {} 高于当前支持的版本 {}，请升级应用后再导入",
+            manifest.format_version,
+            BUNDLE_FORMAT_VERSION
+        ));
+    }
+    Ok((archive, manifest))
+}
+
+/// 读取 .mkb 包
+pub fn read_bundle<R: Read + Seek>(reader: R) -> Result<ProjectBundle> {
+    let (mut archive, manifest) = open_bundle(reader)?;
+
+    Ok(ProjectBundle {
+        manifest,
+        project: read_entry(&mut archive, PROJECT_ENTRY)?,
+        chunks: read_entry(&mut archive, CHUNKS_ENTRY)?,
+        conversations: read_entry(&mut archive, CONVERSATIONS_ENTRY)?,
+        messages: read_entry(&mut archive, MESSAGES_ENTRY)?,
+    })
+}
+
+/// 只读取 manifest 和项目信息检查 .mkb 包，并与数据库向量列的维度比较，不导入任何数据
+///
+/// 较早导出的包 manifest 中没有记录数量，此时读取文档块、对话和消息来统计
+pub fn inspect_bundle<R: Read + Seek>(reader: R, database_dimension: usize) -> Result<BundleInspection> {
+    let (mut archive, manifest) = open_bundle(reader)?;
+    let project: Project = read_entry(&mut archive, PROJECT_ENTRY)?;
+
+    let counts = match manifest.counts {
+        Some(counts) => counts,
+        None => {
+            let chunks: Vec<VectorDocument> = read_entry(&mut archive, CHUNKS_ENTRY)?;
+            let conversations: Vec<Conversation> = read_entry(&mut archive, CONVERSATIONS_ENTRY)?;
+            let messages: Vec<Message> = read_entry(&mut archive, MESSAGES_ENTRY)?;
+            BundleCounts::of(&chunks, &conversations, &messages)
+        }
+    };
+
+    let mut uncompressed_bytes = 0;
+    for index in 0..archive.len() {
+        uncompressed_bytes += archive.by_index(index)?.size();
+    }
+
+    let warning = match manifest.embedding_dimension {
+        Some(dimension) if dimension != database_dimension => Some(format!(
+            "导出包的向量维度 ({}, 模型 {}) 与数据库的向量维度 ({}) 不一致，导入后文档块不带向量，需要补全向量后才能进行语义检索",
+            dimension, manifest.embedding_model, database_dimension
+        )),
+        _ => None,
+    };
+
+    Ok(BundleInspection {
+        warning,
+        format_version: manifest.format_version,
+        app_version: manifest.app_version,
+        exported_at: manifest.exported_at,
+        project_name: project.name,
+        embedding_model: manifest.embedding_model,
+        embedding_dimension: manifest.embedding_dimension,
+        database_dimension,
+        counts,
+        uncompressed_bytes,
+    })
+}
+
+/// 为包中的所有记录分配新的 UUID，并改写项目、文档、文档块、对话之间的引用
+pub fn with_fresh_ids(mut bundle: ProjectBundle) -> ProjectBundle {
+    let old_project_id = bundle.project.id.to_string();
+    let new_project_id = Uuid::new_v4();
+    bundle.project.id = new_project_id;
+
+    let mut document_ids: HashMap<String, String> = HashMap::new();
+    let mut chunk_ids: HashMap<String, Uuid> = HashMap::new();
+    for chunk in &mut bundle.chunks {
+        let document_id = document_ids
+            .entry(chunk.document_id.clone())
+            .or_insert_with(|| Uuid::new_v4().to_string())
+            .clone();
+        let chunk_id = Uuid::new_v4();
+        chunk_ids.insert(chunk.id.clone(), chunk_id);
+
+        chunk.id = chunk_id.to_string();
+        chunk.project_id = new_project_id.to_string();
+        chunk.document_id = document_id;
+    }
+
+    let mut conversation_ids: HashMap<Uuid, Uuid> = HashMap::new();
+    for conversation in &mut bundle.conversations {
+        let conversation_id = Uuid::new_v4();
+        conversation_ids.insert(conversation.id, conversation_id);
+        conversation.id = conversation_id;
+        conversation.project_id = new_project_id;
+    }
+
+    // 不属于包内对话的消息无法挂接，直接丢弃
+    bundle.messages.retain(|message| conversation_ids.contains_key(&message.conversation_id));
+    for message in &mut bundle.messages {
+        message.id = Uuid::new_v4();
+        message.conversation_id = conversation_ids[&message.conversation_id];
+        message.context_chunks = message
+            .context_chunks
+            .iter()
+            .filter_map(|chunk_id| chunk_ids.get(&chunk_id.to_string()).copied())
+            .collect();
+
+        // 来源中引用本项目文档的部分指向新的文档；引用其他项目（多项目检索）的保持不变
+        for source in message.sources.iter_mut().flatten() {
+            if let Some(document_id) = document_ids.get(&source.document_id) {
+                source.document_id = document_id.clone();
+            }
+            if source.project_id.as_deref() == Some(old_project_id.as_str()) {
+                source.project_id = Some(new_project_id.to_string());
+            }
+        }
+    }
+
+    bundle
+}
+
+/// 包中向量维度与当前数据库向量列维度不一致时返回提示
+pub fn dimension_warning(manifest: &BundleManifest, column_dimension: usize) -> Option<String> {
+    match manifest.embedding_dimension {
+        Some(dimension) if dimension != column_dimension => Some(format!(
+            "导出包的向量维度 ({}, 模型 {}) 与当前配置的维度 ({}) 不一致，文档块已不带向量导入，请补全向量后再进行语义检索",
+            dimension, manifest.embedding_model, column_dimension
+        )),
+        _ => None,
+    }
+}
+
+/// 将包写入数据库（调用方应先通过 `with_fresh_ids` 分配新 ID），返回导入结果
+///
+/// 向量维度与 `column_dimension` 不一致或缺少向量的文档块不带向量写入，之后可通过补全向量重新生成。
+/// 任何一步失败时删除已写入的项目、文档块、对话和消息，不会留下导入到一半的项目
+pub fn restore_bundle(db: &mut SeekDbAdapter, bundle: &ProjectBundle, column_dimension: usize) -> Result<ProjectImportReport> {
+    let warning = dimension_warning(&bundle.manifest, column_dimension);
+    if let Some(warning) = &warning {
+        log::warn!("⚠️  {}", warning);
+    }
+
+    if let Err(e) = write_bundle_records(db, bundle, column_dimension) {
+        log::error!("❌ 导入项目失败，清理已写入的数据: {}", e);
+        if let Err(cleanup_err) = discard_bundle_records(db, bundle) {
+            log::error!("❌ 清理导入失败的项目数据失败: {}", cleanup_err);
+        }
+        return Err(e);
+    }
+
+    Ok(ProjectImportReport {
+        project_id: bundle.project.id.to_string(),
+        project_name: bundle.project.name.clone(),
+        chunks: bundle.chunks.len(),
+        conversations: bundle.conversations.len(),
+        messages: bundle.messages.len(),
+        warning,
+    })
+}
+
+fn write_bundle_records(db: &mut SeekDbAdapter, bundle: &ProjectBundle, column_dimension: usize) -> Result<()> {
+    db.save_project(&bundle.project)?;
+
+    let (embedded, missing): (Vec<VectorDocument>, Vec<VectorDocument>) = bundle
+        .chunks
+        .iter()
+        .cloned()
+        .partition(|chunk| chunk.embedding.len() == column_dimension);
+    if !embedded.is_empty() {
+        db.add_documents(embedded)?;
+    }
+    for chunk in &missing {
+        db.insert_chunk_without_embedding(chunk)?;
+    }
+
+    for conversation in &bundle.conversations {
+        db.save_conversation(conversation)?;
+    }
+    for message in &bundle.messages {
+        db.save_message(message)?;
+    }
+    Ok(())
+}
+
+/// 删除导入到一半的项目；包中的 ID 都是新分配的，只会删除本次导入写入的记录
+fn discard_bundle_records(db: &mut SeekDbAdapter, bundle: &ProjectBundle) -> Result<()> {
+    let project_id = bundle.project.id.to_string();
+    for conversation in &bundle.conversations {
+        let conversation_id = conversation.id.to_string();
+        db.delete_messages_by_conversation(&conversation_id)?;
+        db.delete_conversation_by_id(&conversation_id)?;
+    }
+    db.delete_project_documents(&project_id)?;
+    db.delete_project_by_id(&project_id)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::conversation::MessageRole;
+
+    fn sample_bundle() -> ProjectBundle {
+        let project = Project::new("备份项目".to_string(), None).unwrap();
+        let chunk = |index: i32| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project.id.to_string(),
+            document_id: "doc-1".to_string(),
+            chunk_index: index,
+            content: format!("文档块 {}", index),
+            embedding: vec![0.5; 4],
+            metadata: HashMap::from([("filename".to_string(), "a.md".to_string())]),
+        };
+        let chunks = vec![chunk(0), chunk(1)];
+        let conversation = Conversation::new(project.id, Some("对话".to_string())).unwrap();
+        let mut message = Message::new(conversation.id, MessageRole::User, "问题".to_string()).unwrap();
+        message.context_chunks = vec![Uuid::parse_str(&chunks[1].id).unwrap()];
+
+        ProjectBundle {
+            manifest: BundleManifest {
+                format_version: BUNDLE_FORMAT_VERSION,
+                app_version: "0.0.0".to_string(),
+                exported_at: Utc::now(),
+                embedding_model: "test-model".to_string(),
+                embedding_dimension: Some(4),
+                counts: None,
+            },
+            project,
+            chunks,
+            conversations: vec![conversation],
+            messages: vec![message],
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trips_and_import_remaps_ids() {
+        let original = sample_bundle();
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        write_bundle(&mut buffer, &original).unwrap();
+        buffer.set_position(0);
+
+        let restored = read_bundle(buffer).unwrap();
+        assert_eq!(restored.manifest, original.manifest);
+        assert_eq!(restored.chunks[1].content, "文档块 1");
+        assert_eq!(restored.chunks[1].embedding, vec![0.5; 4]);
+
+        let imported = with_fresh_ids(restored);
+        let project_id = imported.project.id;
+        assert_ne!(project_id, original.project.id);
+
+        // 同一文档的块仍属于同一个（新的）文档，且都指向新项目
+        assert_ne!(imported.chunks[0].document_id, "doc-1");
+        assert_eq!(imported.chunks[0].document_id, imported.chunks[1].document_id);
+        assert!(imported.chunks.iter().all(|c| c.project_id == project_id.to_string()));
+
+        let conversation = &imported.conversations[0];
+        assert_ne!(conversation.id, original.conversations[0].id);
+        assert_eq!(conversation.project_id, project_id);
+
+        let message = &imported.messages[0];
+        assert_ne!(message.id, original.messages[0].id);
+        assert_eq!(message.conversation_id, conversation.id);
+        assert_eq!(message.context_chunks, vec![Uuid::parse_str(&imported.chunks[1].id).unwrap()]);
+
+        assert!(dimension_warning(&imported.manifest, 4).is_none());
+        assert!(dimension_warning(&imported.manifest, 1024).is_some());
+    }
+
+    #[test]
+    fn test_inspecting_a_bundle_reports_counts_and_dimension_mismatch() {
+        let mut bundle = sample_bundle();
+        let write = |bundle: &ProjectBundle| {
+            let mut buffer = std::io::Cursor::new(Vec::new());
+            write_bundle(&mut buffer, bundle).unwrap();
+            buffer.set_position(0);
+            buffer
+        };
+
+        // 没有记录数量的旧包：从数据中统计
+        let inspection = inspect_bundle(write(&bundle), 4).unwrap();
+        let expected = BundleCounts { documents: 1, chunks: 2, conversations: 1, messages: 1 };
+        assert_eq!(inspection.counts, expected);
+        assert_eq!(inspection.project_name, "备份项目");
+        assert_eq!(inspection.database_dimension, 4);
+        assert!(inspection.uncompressed_bytes > 0);
+        assert!(inspection.warning.is_none());
+
+        // manifest 中的数量优先，维度不一致时给出提示
+        bundle.manifest.counts = Some(BundleCounts { documents: 3, ..expected });
+        let inspection = inspect_bundle(write(&bundle), 1024).unwrap();
+        assert_eq!(inspection.counts.documents, 3);
+        assert!(inspection.warning.unwrap().contains("1024"));
+
+        // 不是 .mkb 包
+        assert!(inspect_bundle(std::io::Cursor::new(b"not a zip".to_vec()), 4).is_err());
+        bundle.manifest.format_version = BUNDLE_FORMAT_VERSION + 1;
+        assert!(inspect_bundle(write(&bundle), 4).is_err());
+    }
+
+    #[test]
+    #[ignore] // 需要 SeekDB
+    fn test_failed_import_leaves_no_partial_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = SeekDbAdapter::new(dir.path().join("import.db")).unwrap();
+        let bundle = with_fresh_ids(sample_bundle());
+        let project_id = bundle.project.id.to_string();
+
+        // 文档块按 4 维写入，但数据库的向量列不是 4 维：写入项目之后失败
+        assert!(restore_bundle(&mut db, &bundle, 4).is_err());
+
+        assert!(db.load_all_projects().unwrap().iter().all(|project| project.id != bundle.project.id));
+        assert_eq!(db.count_project_chunks(&project_id).unwrap(), 0);
+        assert!(db.load_conversations_by_project(&project_id).unwrap().is_empty());
+        assert!(db.load_messages_by_conversation(&bundle.conversations[0].id.to_string()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! 项目配额
+//!
+//! 共享部署中限制每个项目的文档数和文档文件总大小。全局配额来自 `ingestion.maxDocumentsPerProject`
+//! 和 `ingestion.maxTotalBytesPerProject`，可以在 `ingestion.projectQuotas` 中按项目 ID 覆盖。
+//! 处理失败的文档不占用配额。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::config::{IngestionConfig, ProjectQuotaConfig};
+
+/// 某个项目生效的配额，None 表示不限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectQuota {
+    pub max_documents: Option<usize>,
+    pub max_total_bytes: Option<u64>,
+}
+
+/// 项目当前用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectUsage {
+    pub document_count: usize,
+    pub total_bytes: u64,
+}
+
+/// 项目用量与配额，用于在项目统计中展示
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectQuotaUsage {
+    pub usage: ProjectUsage,
+    pub quota: ProjectQuota,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum QuotaExceeded {
+    #[error("超出项目文档数配额: 项目已有 {current} 个文档，上限为 {max} 个")]
+    Documents { current: usize, max: usize },
+    #[error("超出项目存储配额: 已使用 {current} 字节，加上该文件（{file_size} 字节）将超过上限 {max} 字节")]
+    TotalBytes { current: u64, file_size: u64, max: u64 },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProjectQuotas {
+    default: ProjectQuota,
+    overrides: HashMap<String, ProjectQuotaConfig>,
+}
+
+impl ProjectQuotas {
+    pub fn from_config(config: &IngestionConfig) -> Self {
+        Self {
+            default: ProjectQuota {
+                max_documents: config.max_documents_per_project,
+                max_total_bytes: config.max_total_bytes_per_project,
+            },
+            overrides: config.project_quotas.clone(),
+        }
+    }
+
+    /// 项目生效的配额：项目覆盖中设置的项优先
+    pub fn quota_for(&self, project_id: Uuid) -> ProjectQuota {
+        let Some(project) = self.overrides.get(&project_id.to_string()) else {
+            return self.default;
+        };
+        ProjectQuota {
+            max_documents: project.max_documents.or(self.default.max_documents),
+            max_total_bytes: project.max_total_bytes.or(self.default.max_total_bytes),
+        }
+    }
+
+    /// 检查项目在当前用量下能否再添加一个大小为 `file_size` 的文档
+    pub fn check(&self, project_id: Uuid, usage: ProjectUsage, file_size: u64) -> Result<(), QuotaExceeded> {
+        let quota = self.quota_for(project_id);
+        if let Some(max) = quota.max_documents {
+            if usage.document_count >= max {
+                return Err(QuotaExceeded::Documents { current: usage.document_count, max });
+            }
+        }
+        if let Some(max) = quota.max_total_bytes {
+            if usage.total_bytes.saturating_add(file_size) > max {
+                return Err(QuotaExceeded::TotalBytes { current: usage.total_bytes, file_size, max });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeding_document_count_quota_is_rejected() {
+        let limited = Uuid::new_v4();
+        let raised = Uuid::new_v4();
+        let config = IngestionConfig {
+            max_documents_per_project: Some(2),
+            max_total_bytes_per_project: Some(1000),
+            project_quotas: HashMap::from([(
+                raised.to_string(),
+                ProjectQuotaConfig { max_documents: Some(5), max_total_bytes: None },
+            )]),
+            ..Default::default()
+        };
+        let quotas = ProjectQuotas::from_config(&config);
+        let usage = |document_count, total_bytes| ProjectUsage { document_count, total_bytes };
+
+        assert!(quotas.check(limited, usage(1, 100), 100).is_ok());
+        assert_eq!(
+            quotas.check(limited, usage(2, 100), 100),
+            Err(QuotaExceeded::Documents { current: 2, max: 2 })
+        );
+        assert!(quotas.check(limited, usage(1, 900), 200).is_err());
+
+        // 项目覆盖只替换设置了的项
+        assert_eq!(quotas.quota_for(raised), ProjectQuota { max_documents: Some(5), max_total_bytes: Some(1000) });
+        assert!(quotas.check(raised, usage(2, 100), 100).is_ok());
+
+        // 未配置时不限制
+        assert!(ProjectQuotas::default().check(limited, usage(10_000, u64::MAX / 2), 1).is_ok());
+    }
+}
@@ -1,7 +1,9 @@
-use crate::models::project::Project;
+use crate::models::project::{Project, ProjectStatus};
+use crate::services::project_quota::ProjectQuotaUsage;
 use crate::services::seekdb_adapter::SeekDbAdapter;
 use anyhow::{anyhow, Result};
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -28,8 +30,8 @@ impl ProjectService {
         service
     }
 
-    /// 从数据库加载项目到内存
-    fn load_projects_from_db(&mut self) -> Result<()> {
+    /// 从数据库加载项目到内存（替换内存中已有的项目），返回项目数
+    fn load_projects_from_db(&mut self) -> Result<usize> {
         let db = self.db.clone();
         let db_guard = tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
@@ -40,11 +42,12 @@ impl ProjectService {
         let projects = db_guard.load_all_projects()?;
         log::info!("从数据库加载了 {} 个项目", projects.len());
 
-        for project in projects {
-            self.projects.insert(project.id, project);
-        }
+        Ok(replace_projects(&mut self.projects, projects))
+    }
 
-        Ok(())
+    /// 重新从数据库加载项目，用于数据库被外部修改（导入、修复、迁移）后同步内存
+    pub fn reload_from_db(&mut self) -> Result<usize> {
+        self.load_projects_from_db()
     }
 
     /// 保存项目到数据库
@@ -83,6 +86,11 @@ impl ProjectService {
         Ok(project_id)
     }
 
+    /// 记录已直接写入数据库的项目（如导入），其他项目保持不变
+    pub fn insert_imported_project(&mut self, project: Project) {
+        self.projects.insert(project.id, project);
+    }
+
     pub fn get_project(&self, project_id: Uuid) -> Option<&Project> {
         self.projects.get(&project_id)
     }
@@ -95,6 +103,49 @@ impl ProjectService {
         self.projects.values().collect()
     }
 
+    /// 列出项目，`include_archived` 为 false 时排除已归档的项目
+    pub fn list_visible_projects(&self, include_archived: bool) -> Vec<&Project> {
+        visible_projects(self.projects.values(), include_archived)
+    }
+
+    /// 归档项目：只修改状态，对话和向量数据保持不变，可通过 `restore_project` 恢复
+    pub fn archive_project(&mut self, project_id: Uuid) -> Result<()> {
+        self.write_project_status(project_id, ProjectStatus::Archived)
+    }
+
+    /// 恢复已归档的项目：有文档的项目恢复为 Ready，否则为 Created
+    pub fn restore_project(&mut self, project_id: Uuid) -> Result<()> {
+        let project = self.projects
+            .get(&project_id)
+            .ok_or_else(|| anyhow!("Project not found: {}", project_id))?;
+        if !project.is_archived() {
+            return Err(anyhow!("Project is not archived: {}", project_id));
+        }
+
+        self.write_project_status(project_id, restored_status(project))
+    }
+
+    /// 按最近活动时间（项目自身更新时间与其最新对话更新时间中的较大者）降序列出项目
+    ///
+    /// `conversation_activity` 为项目ID到该项目最新对话 `updated_at` 的映射
+    pub fn list_recent_projects(
+        &self,
+        conversation_activity: &HashMap<Uuid, chrono::DateTime<Utc>>,
+        limit: usize,
+    ) -> Vec<(&Project, chrono::DateTime<Utc>)> {
+        rank_recent_projects(self.projects.values(), conversation_activity, limit)
+    }
+
+    /// 找出没有文档或超过 `days` 天没有活动的项目（只报告，不删除），最久未活动的排在前面
+    pub fn find_stale_projects(
+        &self,
+        conversation_activity: &HashMap<Uuid, chrono::DateTime<Utc>>,
+        days: i64,
+    ) -> Result<Vec<StaleProject<'_>>> {
+        let cutoff = stale_cutoff(Utc::now(), days)?;
+        Ok(find_stale(self.projects.values(), conversation_activity, cutoff))
+    }
+
     pub fn update_project(
         &mut self,
         project_id: Uuid,
@@ -150,24 +201,75 @@ impl ProjectService {
         self.projects.len()
     }
 
-    pub fn get_project_stats(&self, project_id: Uuid) -> Result<ProjectStats> {
+    /// 从数据库统计项目的文档数、文档块数和内容大小
+    ///
+    /// 对话保存在 ConversationService 中，由调用方通过 `count_conversations` 统计后传入；
+    /// 配额用量同样由 DocumentService 提供
+    pub async fn get_project_stats(
+        &self,
+        project_id: Uuid,
+        conversation_count: usize,
+        quota: ProjectQuotaUsage,
+    ) -> Result<ProjectStats> {
         let project = self.projects
             .get(&project_id)
             .ok_or_else(|| anyhow!("Project not found: {}", project_id))?;
 
-        // In a real implementation, these would be calculated from actual data
+        let db = self.db.lock().await;
+        let project_key = project_id.to_string();
         Ok(ProjectStats {
             project_id,
-            document_count: 0,
-            conversation_count: 0,
-            total_chunks: 0,
-            storage_size: 0,
+            document_count: db.count_project_documents(&project_key)?,
+            conversation_count,
+            total_chunks: db.count_project_chunks(&project_key)?,
+            storage_size: db.project_content_bytes(&project_key)?,
+            quota,
             created_at: project.created_at,
             updated_at: project.updated_at,
         })
     }
 
+    /// 按数据库中实际的文档数重新计算所有项目的 document_count，返回被修正的项目
+    ///
+    /// 部分文档处理失败或应用崩溃后，保存的文档数可能与实际不符
+    pub async fn recompute_document_counts(&mut self) -> Result<Vec<DocumentCountCorrection>> {
+        let db = self.db.clone();
+        let mut db = db.lock().await;
+
+        let mut corrections = Vec::new();
+        for project in self.projects.values_mut() {
+            let project_key = project.id.to_string();
+            let actual_count = db.count_project_documents(&project_key)? as u32;
+            if actual_count == project.document_count {
+                continue;
+            }
+
+            db.update_project_document_count(&project_key, actual_count)?;
+            log::info!("🔢 修正项目 {} 的文档数: {} -> {}", project.name, project.document_count, actual_count);
+            corrections.push(DocumentCountCorrection {
+                project_id: project.id,
+                project_name: project.name.clone(),
+                previous_count: project.document_count,
+                actual_count,
+            });
+            project.update_document_count(actual_count);
+        }
+
+        corrections.sort_by(|a, b| a.project_name.cmp(&b.project_name));
+        Ok(corrections)
+    }
+
+    /// 更新项目状态，已归档的项目需要先通过 `restore_project` 恢复
     pub fn update_project_status(&mut self, project_id: Uuid, status: crate::models::project::ProjectStatus) -> Result<()> {
+        let project = self.projects
+            .get(&project_id)
+            .ok_or_else(|| anyhow!("Project not found: {}", project_id))?;
+        ensure_not_archived(project)?;
+
+        self.write_project_status(project_id, status)
+    }
+
+    fn write_project_status(&mut self, project_id: Uuid, status: ProjectStatus) -> Result<()> {
         {
             let project = self.projects
                 .get_mut(&project_id)
@@ -192,14 +294,125 @@ impl ProjectService {
     }
 }
 
+fn visible_projects<'a>(projects: impl Iterator<Item = &'a Project>, include_archived: bool) -> Vec<&'a Project> {
+    projects
+        .filter(|project| include_archived || !project.is_archived())
+        .collect()
+}
+
+/// 已归档的项目不接受上传、重新处理和状态修改
+fn ensure_not_archived(project: &Project) -> Result<()> {
+    if project.is_archived() {
+        return Err(anyhow!("Project is archived, restore it first: {}", project.id));
+    }
+    Ok(())
+}
+
+fn restored_status(project: &Project) -> ProjectStatus {
+    if project.document_count > 0 {
+        ProjectStatus::Ready
+    } else {
+        ProjectStatus::Created
+    }
+}
+
+/// 用从数据库加载的项目替换内存中的项目，返回项目数
+fn replace_projects(projects: &mut HashMap<Uuid, Project>, loaded: Vec<Project>) -> usize {
+    *projects = loaded.into_iter().map(|project| (project.id, project)).collect();
+    projects.len()
+}
+
+/// 计算每个项目的最近活动时间并降序排序，取前 `limit` 个
+fn rank_recent_projects<'a>(
+    projects: impl Iterator<Item = &'a Project>,
+    conversation_activity: &HashMap<Uuid, chrono::DateTime<Utc>>,
+    limit: usize,
+) -> Vec<(&'a Project, chrono::DateTime<Utc>)> {
+    let mut ranked: Vec<(&Project, chrono::DateTime<Utc>)> = projects
+        .map(|project| {
+            let last_activity = conversation_activity
+                .get(&project.id)
+                .map_or(project.updated_at, |&chat| chat.max(project.updated_at));
+            (project, last_activity)
+        })
+        .collect();
+
+    ranked.sort_by_key(|&(_, last_activity)| std::cmp::Reverse(last_activity));
+    ranked.truncate(limit);
+    ranked
+}
+
+/// 项目被判定为闲置的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StaleReason {
+    /// 项目中没有任何文档
+    NoDocuments,
+    /// 超过指定天数没有更新或对话
+    Inactive,
+}
 
 #[derive(Debug, Clone)]
+pub struct StaleProject<'a> {
+    pub project: &'a Project,
+    pub last_activity: chrono::DateTime<Utc>,
+    pub reasons: Vec<StaleReason>,
+}
+
+/// `now` 之前 `days` 天的时间点；天数为负或超出时间范围时返回错误
+fn stale_cutoff(now: chrono::DateTime<Utc>, days: i64) -> Result<chrono::DateTime<Utc>> {
+    if days < 0 {
+        return Err(anyhow!("无效的天数"));
+    }
+    chrono::Duration::try_days(days)
+        .and_then(|duration| now.checked_sub_signed(duration))
+        .ok_or_else(|| anyhow!("无效的天数"))
+}
+
+/// 按文档数量和最近活动时间筛选闲置项目，`cutoff` 之前没有活动视为不活跃
+fn find_stale<'a>(
+    projects: impl Iterator<Item = &'a Project>,
+    conversation_activity: &HashMap<Uuid, chrono::DateTime<Utc>>,
+    cutoff: chrono::DateTime<Utc>,
+) -> Vec<StaleProject<'a>> {
+    let mut stale: Vec<StaleProject> = rank_recent_projects(projects, conversation_activity, usize::MAX)
+        .into_iter()
+        .filter_map(|(project, last_activity)| {
+            let mut reasons = Vec::new();
+            if project.document_count == 0 {
+                reasons.push(StaleReason::NoDocuments);
+            }
+            if last_activity < cutoff {
+                reasons.push(StaleReason::Inactive);
+            }
+            (!reasons.is_empty()).then_some(StaleProject { project, last_activity, reasons })
+        })
+        .collect();
+
+    stale.reverse();
+    stale
+}
+
+/// 一个项目的文档数修正
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DocumentCountCorrection {
+    pub project_id: Uuid,
+    pub project_name: String,
+    /// 修正前保存的文档数
+    pub previous_count: u32,
+    /// 数据库中实际的文档数（不同 document_id 的数量）
+    pub actual_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ProjectStats {
     pub project_id: Uuid,
     pub document_count: usize,
     pub conversation_count: usize,
     pub total_chunks: usize,
     pub storage_size: u64,
+    /// 当前用量与配额
+    pub quota: ProjectQuotaUsage,
     pub created_at: chrono::DateTime<Utc>,
     pub updated_at: chrono::DateTime<Utc>,
 }
@@ -208,6 +421,160 @@ pub struct ProjectStats {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_archived_project_is_hidden_by_default_and_restorable() {
+        let mut archived = Project::new("旧项目".to_string(), None).unwrap();
+        archived.document_count = 2;
+        archived.update_status(ProjectStatus::Archived);
+        let active = Project::new("当前项目".to_string(), None).unwrap();
+        let projects = vec![archived.clone(), active.clone()];
+
+        let visible = visible_projects(projects.iter(), false);
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].id, active.id);
+        assert_eq!(visible_projects(projects.iter(), true).len(), 2);
+
+        // 归档只改变状态，恢复时按文档数量回到 Ready/Created
+        assert_eq!(restored_status(&archived), ProjectStatus::Ready);
+        assert_eq!(restored_status(&active), ProjectStatus::Created);
+    }
+
+    #[test]
+    fn test_archived_project_rejects_changes_until_restored() {
+        let mut project = Project::new("旧项目".to_string(), None).unwrap();
+        assert!(ensure_not_archived(&project).is_ok());
+
+        project.update_status(ProjectStatus::Archived);
+        let err = ensure_not_archived(&project).unwrap_err();
+        assert!(err.to_string().contains("archived"));
+
+        project.update_status(restored_status(&project));
+        assert!(ensure_not_archived(&project).is_ok());
+    }
+
+    #[test]
+    fn test_recent_chat_activity_ranks_project_higher() {
+        let now = Utc::now();
+        let mut active = Project::new("活跃项目".to_string(), None).unwrap();
+        let mut inactive = Project::new("闲置项目".to_string(), None).unwrap();
+
+        // 活跃项目本身创建得更早，但最近有对话
+        active.updated_at = now - chrono::Duration::days(30);
+        inactive.updated_at = now - chrono::Duration::days(2);
+
+        let mut conversation_activity = HashMap::new();
+        conversation_activity.insert(active.id, now - chrono::Duration::minutes(5));
+
+        let projects = vec![inactive.clone(), active.clone()];
+        let ranked = rank_recent_projects(projects.iter(), &conversation_activity, 10);
+        assert_eq!(ranked[0].0.id, active.id);
+        assert_eq!(ranked[0].1, now - chrono::Duration::minutes(5));
+        assert_eq!(ranked[1].0.id, inactive.id);
+
+        let limited = rank_recent_projects(projects.iter(), &conversation_activity, 1);
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_old_empty_project_is_flagged_but_active_one_is_not() {
+        let now = Utc::now();
+        let mut old_empty = Project::new("误建项目".to_string(), None).unwrap();
+        old_empty.updated_at = now - chrono::Duration::days(90);
+
+        let mut active = Project::new("常用项目".to_string(), None).unwrap();
+        active.document_count = 3;
+        active.updated_at = now - chrono::Duration::days(60);
+
+        let mut quiet = Project::new("很久没打开".to_string(), None).unwrap();
+        quiet.document_count = 2;
+        quiet.updated_at = now - chrono::Duration::days(45);
+
+        // 常用项目本身很久没更新，但最近有对话
+        let mut conversation_activity = HashMap::new();
+        conversation_activity.insert(active.id, now - chrono::Duration::days(1));
+
+        let projects = vec![active.clone(), old_empty.clone(), quiet.clone()];
+        let stale = find_stale(projects.iter(), &conversation_activity, now - chrono::Duration::days(30));
+
+        let ids: Vec<Uuid> = stale.iter().map(|s| s.project.id).collect();
+        assert_eq!(ids, vec![old_empty.id, quiet.id]);
+        assert_eq!(stale[0].reasons, vec![StaleReason::NoDocuments, StaleReason::Inactive]);
+        assert_eq!(stale[1].reasons, vec![StaleReason::Inactive]);
+    }
+
+    #[test]
+    fn test_stale_cutoff_rejects_negative_and_overflowing_days() {
+        let now = Utc::now();
+        assert_eq!(stale_cutoff(now, 30).unwrap(), now - chrono::Duration::days(30));
+        assert!(stale_cutoff(now, -1).is_err());
+        assert!(stale_cutoff(now, i64::MAX).is_err());
+        assert!(stale_cutoff(now, 200_000_000).is_err());
+    }
+
+    #[test]
+    fn test_reload_picks_up_projects_inserted_directly_into_db() {
+        let existing = Project::new("已有项目".to_string(), None).unwrap();
+        let mut projects = HashMap::new();
+        replace_projects(&mut projects, vec![existing.clone()]);
+
+        // 外部工具直接写入数据库的项目，以及在数据库中被删除的项目
+        let imported = Project::new("导入的项目".to_string(), None).unwrap();
+        let deleted = Project::new("已删除".to_string(), None).unwrap();
+        projects.insert(deleted.id, deleted.clone());
+
+        let count = replace_projects(&mut projects, vec![existing.clone(), imported.clone()]);
+
+        assert_eq!(count, 2);
+        let listed: Vec<Uuid> = projects.values().map(|p| p.id).collect();
+        assert!(listed.contains(&imported.id));
+        assert!(listed.contains(&existing.id));
+        assert!(!listed.contains(&deleted.id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore] // 需要 SeekDB
+    async fn test_drifted_document_count_is_fixed_to_actual_count() {
+        use crate::services::seekdb_adapter::{VectorDocument, SCHEMA_EMBEDDING_DIMENSION};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Mutex::new(SeekDbAdapter::new(dir.path().join("document_counts.db")).unwrap()));
+        let mut service = ProjectService::new(db.clone());
+        let drifted = service.create_project("计数错误".to_string(), None).unwrap();
+        let correct = service.create_project("计数正确".to_string(), None).unwrap();
+
+        // 两个文档共三个块；保存的文档数是崩溃前累加的 5
+        let chunk = |document_id: &str, index: i32| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: drifted.to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: index,
+            content: format!("文档块 {}", index),
+            embedding: vec![0.1; SCHEMA_EMBEDDING_DIMENSION],
+            metadata: HashMap::new(),
+        };
+        db.lock().await.add_documents(vec![chunk("doc-a", 0), chunk("doc-a", 1), chunk("doc-b", 0)]).unwrap();
+        service.get_project_mut(drifted).unwrap().document_count = 5;
+        db.lock().await.update_project_document_count(&drifted.to_string(), 5).unwrap();
+
+        let corrections = service.recompute_document_counts().await.unwrap();
+        assert_eq!(
+            corrections,
+            vec![DocumentCountCorrection {
+                project_id: drifted,
+                project_name: "计数错误".to_string(),
+                previous_count: 5,
+                actual_count: 2,
+            }]
+        );
+        assert_eq!(service.get_project(drifted).unwrap().document_count, 2);
+        assert_eq!(service.get_project(correct).unwrap().document_count, 0);
+
+        // 保存到数据库，重新加载后仍是正确的数量
+        service.reload_from_db().unwrap();
+        assert_eq!(service.get_project(drifted).unwrap().document_count, 2);
+        assert!(service.recompute_document_counts().await.unwrap().is_empty());
+    }
+
     #[test]
     fn test_project_service_creation() {
         let service = ProjectService::new();
@@ -315,4 +682,40 @@ mod tests {
         let non_existent_id = Uuid::new_v4();
         assert!(!service.project_exists(non_existent_id));
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore] // 需要 SeekDB
+    async fn test_project_stats_count_documents_chunks_and_content() {
+        use crate::services::seekdb_adapter::{VectorDocument, SCHEMA_EMBEDDING_DIMENSION};
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Mutex::new(SeekDbAdapter::new(dir.path().join("project_stats.db")).unwrap()));
+        let mut service = ProjectService::new(db.clone());
+        let project_id = service.create_project("统计".to_string(), None).unwrap();
+
+        let chunk = |document_id: &str, index: i32, content: &str| VectorDocument {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            document_id: document_id.to_string(),
+            chunk_index: index,
+            content: content.to_string(),
+            embedding: vec![0.1; SCHEMA_EMBEDDING_DIMENSION],
+            metadata: HashMap::new(),
+        };
+        db.lock().await.add_documents(vec![
+            chunk("doc-a", 0, "abcd"),
+            chunk("doc-a", 1, "efgh"),
+            chunk("doc-b", 0, "ijklmn"),
+        ]).unwrap();
+
+        let stats = service.get_project_stats(project_id, 2, ProjectQuotaUsage::default()).await.unwrap();
+        assert_eq!(stats.document_count, 2);
+        assert_eq!(stats.total_chunks, 3);
+        assert_eq!(stats.storage_size, 14);
+        assert_eq!(stats.conversation_count, 2);
+
+        let empty_project = service.create_project("空项目".to_string(), None).unwrap();
+        let empty = service.get_project_stats(empty_project, 0, ProjectQuotaUsage::default()).await.unwrap();
+        assert_eq!((empty.document_count, empty.total_chunks, empty.storage_size), (0, 0, 0));
+    }
 }
@@ -43,7 +43,29 @@ pub fn get_context_header() -> &'static str {
     "\n\n[上下文信息]\n以下是从知识库中检索到的相关文档片段：\n\n"
 }
 
+/// 获取根据首轮对话生成标题的提示词
+pub fn get_title_prompt() -> &'static str {
+    "你是对话标题生成器。请根据用户的问题和助手的回答，为这段对话生成一个简短的标题：\n\
+     - 不超过 8 个词（中文不超过 15 个字）\n\
+     - 使用与用户问题相同的语言\n\
+     - 只输出标题本身，不要引号、标点结尾或任何解释"
+}
+
+/// 获取压缩早期对话时生成摘要的提示词
+pub fn get_compression_prompt() -> &'static str {
+    "你是对话摘要助手。请把下面的早期对话压缩成一段摘要，供后续对话作为背景参考：\n\
+     - 保留用户的目标、关键问题、已得出的结论和重要细节（名称、数字、决定）\n\
+     - 省略寒暄和重复内容，不要编造对话中没有的信息\n\
+     - 使用与对话相同的语言，只输出摘要本身"
+}
+
 /// 获取上下文信息的结尾文本
 pub fn get_context_footer() -> &'static str {
     "---\n\n请严格基于以上[上下文信息]回答用户问题。"
 }
+
+/// 获取检索结果相关度都很低时附加的提醒
+pub fn get_low_confidence_prompt() -> &'static str {
+    "\n注意：以上文档片段与问题的相关度都很低，知识库可能没有涵盖这个问题。\
+     如果片段中没有直接回答问题的内容，请明确告知用户，不要根据片段推测答案。"
+}
@@ -1,7 +1,46 @@
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// 运行时必须能够导入的 Python 包
+const REQUIRED_PACKAGES: &[&str] = &["seekdb"];
+
+/// 探测包导入情况的脚本：逐个 import，输出 {包名: 错误信息或 null} 的 JSON
+const PACKAGE_PROBE_SCRIPT: &str = r#"
+import importlib, json, sys
+result = {}
+for name in sys.argv[1:]:
+    try:
+        importlib.import_module(name)
+        result[name] = None
+    except Exception as e:
+        result[name] = "%s: %s" % (type(e).__name__, e)
+print(json.dumps(result))
+"#;
+
+/// 单个 Python 包的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageCheck {
+    pub name: String,
+    pub importable: bool,
+    pub error: Option<String>,
+}
+
+/// Python 环境诊断报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PythonEnvReport {
+    pub venv_dir: String,
+    pub venv_exists: bool,
+    pub python_executable: String,
+    pub python_runs: bool,
+    pub python_version: Option<String>,
+    pub packages: Vec<PackageCheck>,
+    pub issues: Vec<String>,
+    pub ok: bool,
+}
+
 /// Python 虚拟环境管理器
 pub struct PythonEnv {
     venv_dir: PathBuf,
@@ -187,6 +226,116 @@ impl PythonEnv {
         }
     }
     
+    /// 诊断 Python 环境：虚拟环境、解释器以及必需包的导入情况
+    pub fn validate(&self) -> PythonEnvReport {
+        log::info!("🔍 诊断 Python 环境: {:?}", self.venv_dir);
+
+        let venv_exists = self.venv_exists();
+        let mut issues = Vec::new();
+        if !venv_exists {
+            issues.push(format!("虚拟环境不存在: {}", self.venv_dir.display()));
+        }
+
+        let (python_version, packages) = if venv_exists {
+            Self::probe_python(&self.python_executable, REQUIRED_PACKAGES, &mut issues)
+        } else {
+            (None, Vec::new())
+        };
+
+        let python_runs = python_version.is_some();
+        let ok = issues.is_empty();
+        if ok {
+            log::info!("✅ Python 环境正常");
+        } else {
+            log::warn!("⚠️  Python 环境存在 {} 个问题", issues.len());
+        }
+
+        PythonEnvReport {
+            venv_dir: self.venv_dir.display().to_string(),
+            venv_exists,
+            python_executable: self.python_executable.display().to_string(),
+            python_runs,
+            python_version,
+            packages,
+            issues,
+            ok,
+        }
+    }
+
+    /// 运行解释器获取版本，并用探测脚本检查各包能否导入
+    fn probe_python(
+        python_executable: &Path,
+        packages: &[&str],
+        issues: &mut Vec<String>,
+    ) -> (Option<String>, Vec<PackageCheck>) {
+        let version = match Command::new(python_executable).arg("--version").output() {
+            Ok(output) if output.status.success() => {
+                // Python 2 / 部分发行版会把版本输出到 stderr
+                let text = if output.stdout.is_empty() { output.stderr } else { output.stdout };
+                String::from_utf8_lossy(&text).trim().to_string()
+            }
+            Ok(output) => {
+                issues.push(format!(
+                    "Python 可执行文件无法运行（退出码: {:?}）: {}",
+                    output.status.code(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+                return (None, Vec::new());
+            }
+            Err(e) => {
+                issues.push(format!("无法启动 Python 可执行文件 {}: {}", python_executable.display(), e));
+                return (None, Vec::new());
+            }
+        };
+
+        let output = Command::new(python_executable)
+            .arg("-c")
+            .arg(PACKAGE_PROBE_SCRIPT)
+            .args(packages)
+            .output();
+
+        let results: HashMap<String, Option<String>> = match output {
+            Ok(output) if output.status.success() => {
+                serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+                    issues.push(format!("无法解析包探测结果: {}", e));
+                    HashMap::new()
+                })
+            }
+            Ok(output) => {
+                issues.push(format!(
+                    "包探测脚本执行失败: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+                HashMap::new()
+            }
+            Err(e) => {
+                issues.push(format!("包探测脚本启动失败: {}", e));
+                HashMap::new()
+            }
+        };
+
+        let checks = packages
+            .iter()
+            .map(|name| {
+                let error = match results.get(*name) {
+                    Some(None) => None,
+                    Some(Some(err)) => Some(err.clone()),
+                    None => Some("未返回探测结果".to_string()),
+                };
+                if let Some(ref err) = error {
+                    issues.push(format!("无法导入 Python 包 {}: {}", name, err));
+                }
+                PackageCheck {
+                    name: name.to_string(),
+                    importable: error.is_none(),
+                    error,
+                }
+            })
+            .collect();
+
+        (Some(version), checks)
+    }
+
     /// 获取虚拟环境的 Python 可执行文件路径
     pub fn get_python_executable(&self) -> &Path {
         &self.python_executable
@@ -222,5 +371,45 @@ mod tests {
         assert!(python_env.get_venv_dir().to_string_lossy().contains("venv"));
         assert!(python_env.get_python_executable().to_string_lossy().contains("python"));
     }
+
+    #[test]
+    fn test_validate_reports_missing_venv() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python_env = PythonEnv::new(temp_dir.path()).unwrap();
+
+        let report = python_env.validate();
+        assert!(!report.venv_exists);
+        assert!(!report.ok);
+        assert!(report.issues[0].contains("虚拟环境不存在"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_reports_missing_package() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // 用 shell 脚本模拟 Python 解释器：版本正常，但 seekdb 无法导入
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python_env = PythonEnv::new(temp_dir.path()).unwrap();
+        let fake_python = python_env.get_python_executable().to_path_buf();
+        std::fs::create_dir_all(fake_python.parent().unwrap()).unwrap();
+        std::fs::write(
+            &fake_python,
+            "#!/bin/sh\n\
+             if [ \"$1\" = \"--version\" ]; then echo 'Python 3.11.4'; exit 0; fi\n\
+             echo '{\"seekdb\": \"ModuleNotFoundError: No module named seekdb\"}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_python, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let report = python_env.validate();
+        assert!(report.venv_exists);
+        assert!(report.python_runs);
+        assert_eq!(report.python_version.as_deref(), Some("Python 3.11.4"));
+        assert_eq!(report.packages.len(), 1);
+        assert!(!report.packages[0].importable);
+        assert!(report.packages[0].error.as_ref().unwrap().contains("No module named"));
+        assert!(!report.ok);
+    }
 }
 
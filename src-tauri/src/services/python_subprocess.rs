@@ -7,6 +7,9 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+/// Command protocol version spoken by this build; must match `PROTOCOL_VERSION` in seekdb_bridge.py
+pub const BRIDGE_PROTOCOL_VERSION: u32 = 3;
+
 /// Request sent to Python subprocess
 #[derive(Debug, Serialize)]
 struct Request {
@@ -34,6 +37,7 @@ pub struct PythonSubprocess {
     stdout: Arc<Mutex<Option<BufReader<ChildStdout>>>>,
     script_path: String,
     python_executable: String,
+    protocol_version: u32,
 }
 
 impl PythonSubprocess {
@@ -64,13 +68,49 @@ impl PythonSubprocess {
         
         log::info!("✅ Python subprocess started successfully");
         
-        Ok(Self {
+        let mut subprocess = Self {
             child: Arc::new(Mutex::new(Some(child))),
             stdin: Arc::new(Mutex::new(Some(stdin))),
             stdout: Arc::new(Mutex::new(Some(stdout))),
             script_path: script_path.to_string(),
             python_executable: python_executable.to_string(),
-        })
+            protocol_version: 0,
+        };
+        
+        // Fail fast if the bundled bridge script speaks a different protocol
+        subprocess.protocol_version = subprocess.handshake()?;
+        check_protocol_version(subprocess.protocol_version)?;
+        log::info!("🤝 Bridge protocol version: {}", subprocess.protocol_version);
+        
+        Ok(subprocess)
+    }
+    
+    /// Exchange protocol versions with the bridge. Bridges that predate the
+    /// handshake reject the `version` command and are reported as version 0.
+    fn handshake(&self) -> Result<u32> {
+        let params = serde_json::json!({ "protocol_version": BRIDGE_PROTOCOL_VERSION });
+        match self.send_command("version", params) {
+            Ok(data) => data
+                .get("protocol_version")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32)
+                .ok_or_else(|| anyhow!("Invalid version response from bridge")),
+            Err(e) if e.to_string().contains("UnknownCommand") => Ok(0),
+            Err(e) => Err(anyhow!("Bridge handshake failed: {}", e)),
+        }
+    }
+    
+    /// Protocol version reported by the bridge script
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+    
+    /// Installed seekdb package version as reported by the bridge, if known.
+    /// Older bridges don't report it and yield `None`.
+    pub fn seekdb_version(&self) -> Result<Option<String>> {
+        let params = serde_json::json!({ "protocol_version": BRIDGE_PROTOCOL_VERSION });
+        let data = self.send_command("version", params)?;
+        Ok(data.get("seekdb_version").and_then(|v| v.as_str()).map(str::to_string))
     }
     
     /// Send a command and wait for response
@@ -201,6 +241,35 @@ impl PythonSubprocess {
         Ok(())
     }
     
+    /// Start another bridge process from the same script and interpreter, for work
+    /// that shouldn't queue behind database commands (e.g. local reranking)
+    pub fn spawn_sibling(&self) -> Result<Self> {
+        Self::new_with_python(&self.script_path, &self.python_executable)
+    }
+
+    /// Score (query, document) pairs with a local cross-encoder model; the scores are raw logits
+    pub fn rerank(&self, model: &str, query: &str, documents: &[String]) -> Result<Vec<f64>> {
+        let params = serde_json::json!({
+            "model": model,
+            "query": query,
+            "documents": documents
+        });
+        
+        let response = self.send_command("rerank", params)?;
+        let scores = response
+            .get("scores")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| anyhow!("Invalid rerank response"))?
+            .iter()
+            .map(|score| score.as_f64().ok_or_else(|| anyhow!("Invalid rerank score: {}", score)))
+            .collect::<Result<Vec<f64>>>()?;
+        
+        if scores.len() != documents.len() {
+            return Err(anyhow!("Reranker returned {} scores for {} documents", scores.len(), documents.len()));
+        }
+        Ok(scores)
+    }
+    
     /// Ping to check if subprocess is alive
     pub fn ping(&self) -> Result<()> {
         self.send_command("ping", Value::Null)?;
@@ -271,6 +340,19 @@ impl PythonSubprocess {
     }
 }
 
+/// Check that the bridge speaks the same command protocol as this build
+pub fn check_protocol_version(bridge_version: u32) -> Result<()> {
+    if bridge_version != BRIDGE_PROTOCOL_VERSION {
+        return Err(anyhow!(
+            "bridge version mismatch: expected protocol v{}, seekdb_bridge.py reports v{}. \
+             Please reinstall the application so the bundled bridge script matches",
+            BRIDGE_PROTOCOL_VERSION,
+            bridge_version
+        ));
+    }
+    Ok(())
+}
+
 impl Drop for PythonSubprocess {
     fn drop(&mut self) {
         self.shutdown();
@@ -286,5 +368,42 @@ mod tests {
         // This test would require the actual Python script to exist
         // Skipping in unit tests, should be tested in integration tests
     }
+    
+    /// Fake "python" that answers every request with the given JSON line
+    #[cfg(unix)]
+    fn fake_bridge(dir: &std::path::Path, response: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let path = dir.join("fake-python");
+        let script = format!("#!/bin/sh\nwhile read line; do echo '{}'; done\n", response);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_version_mismatch_detected_at_init() {
+        let dir = tempfile::tempdir().unwrap();
+        
+        let python = fake_bridge(dir.path(), r#"{"status":"success","data":{"protocol_version":99}}"#);
+        let err = PythonSubprocess::new_with_python("seekdb_bridge.py", &python).unwrap_err();
+        assert!(err.to_string().contains("bridge version mismatch"), "{}", err);
+        
+        let python = fake_bridge(dir.path(), r#"{"status":"error","error":"UnknownCommand","details":"Unknown command: version"}"#);
+        let err = PythonSubprocess::new_with_python("seekdb_bridge.py", &python).unwrap_err();
+        assert!(err.to_string().contains("reports v0"), "{}", err);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_matching_version_handshake_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let response = format!(r#"{{"status":"success","data":{{"protocol_version":{}}}}}"#, BRIDGE_PROTOCOL_VERSION);
+        let python = fake_bridge(dir.path(), &response);
+        
+        let subprocess = PythonSubprocess::new_with_python("seekdb_bridge.py", &python).unwrap();
+        assert_eq!(subprocess.protocol_version(), BRIDGE_PROTOCOL_VERSION);
+    }
 }
 
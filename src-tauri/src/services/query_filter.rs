@@ -0,0 +1,146 @@
+//! 查询预检模块
+//!
+//! 在调用 Embedding API 之前识别"无意义"的查询（只有停用词、标点或空白），
+//! 避免浪费 API 调用以及返回毫无意义的检索结果。
+//! 中文没有空格分词，因此 CJK 字符按单字处理，其他文字按空白/标点切词。
+
+use crate::config::TrivialQueryAction;
+
+/// 英文停用词
+const EN_STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "or", "but", "of", "to", "in", "on", "at", "by", "for",
+    "with", "from", "as", "is", "are", "was", "were", "be", "been", "am", "it", "its",
+    "this", "that", "these", "those", "i", "me", "my", "you", "your", "he", "she",
+    "we", "they", "them", "do", "does", "did", "so", "if", "then", "than", "too",
+    "very", "just", "not", "no", "yes", "ok", "okay", "oh", "uh", "um", "hmm",
+];
+
+/// 中文停用字（语气词、助词、代词等单字）
+const ZH_STOP_CHARS: &[char] = &[
+    '的', '了', '吗', '呢', '吧', '啊', '呀', '哦', '噢', '嗯', '哈', '嘛', '么', '啦',
+    '是', '在', '和', '与', '及', '就', '都', '也', '还', '又', '这', '那', '个', '着',
+    '过', '把', '被', '我', '你', '他', '她', '它', '们', '之', '而', '或', '其', '哎', '唉',
+];
+
+/// 查询预检结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryPrecheck {
+    /// 正常执行检索
+    Retrieve,
+    /// 跳过检索（不调用 Embedding），直接让 LLM 回答
+    SkipRetrieval,
+    /// 不调用 LLM，提示用户换个说法
+    AskRephrase,
+}
+
+/// 判断字符是否为 CJK 表意文字
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF     // CJK 统一表意文字
+        | 0x3400..=0x4DBF   // 扩展 A
+        | 0x20000..=0x2A6DF // 扩展 B
+        | 0xF900..=0xFAFF   // 兼容表意文字
+    )
+}
+
+/// 将查询归一化为有效词元：CJK 按单字切分，其余文字按非字母数字字符切分并转小写
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+
+    for c in query.chars() {
+        if is_cjk(c) {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            current.extend(c.to_lowercase());
+        } else if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn is_stop_token(token: &str) -> bool {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if is_cjk(c) => ZH_STOP_CHARS.contains(&c),
+        _ => EN_STOP_WORDS.contains(&token),
+    }
+}
+
+/// 判断查询在去掉空白、标点和停用词之后是否为空
+pub fn is_trivial_query(query: &str) -> bool {
+    tokenize(query).iter().all(|t| is_stop_token(t))
+}
+
+/// 根据配置决定如何处理该查询
+pub fn precheck_query(query: &str, action: TrivialQueryAction) -> QueryPrecheck {
+    if action == TrivialQueryAction::Disabled || !is_trivial_query(query) {
+        return QueryPrecheck::Retrieve;
+    }
+
+    match action {
+        TrivialQueryAction::AskRephrase => QueryPrecheck::AskRephrase,
+        _ => QueryPrecheck::SkipRetrieval,
+    }
+}
+
+/// 提示用户重新表述问题的回复文本
+pub fn get_rephrase_message() -> &'static str {
+    "您的问题似乎只包含标点或语气词，我无法据此检索知识库。请换一种更具体的说法重新提问。"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace_and_punctuation_skip_embedding() {
+        for query in ["", "   ", "??!!", " ？？。。！ ", "...\n\t---"] {
+            assert!(is_trivial_query(query), "应判定为无意义查询: {:?}", query);
+            assert_ne!(
+                precheck_query(query, TrivialQueryAction::SkipRetrieval),
+                QueryPrecheck::Retrieve,
+                "无意义查询不应触发 Embedding 调用: {:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_stop_word_only_queries() {
+        assert!(is_trivial_query("the the ??"));
+        assert!(is_trivial_query("是吗？"));
+        assert!(is_trivial_query("嗯嗯，是吧 ok"));
+        assert_eq!(
+            precheck_query("the the ??", TrivialQueryAction::AskRephrase),
+            QueryPrecheck::AskRephrase
+        );
+    }
+
+    #[test]
+    fn test_meaningful_queries_are_retrieved() {
+        assert!(!is_trivial_query("What is the pricing policy?"));
+        assert!(!is_trivial_query("定价策略是什么？"));
+        assert!(!is_trivial_query("SeekDB"));
+        assert_eq!(
+            precheck_query("定价策略", TrivialQueryAction::SkipRetrieval),
+            QueryPrecheck::Retrieve
+        );
+    }
+
+    #[test]
+    fn test_disabled_always_retrieves() {
+        assert_eq!(
+            precheck_query("??", TrivialQueryAction::Disabled),
+            QueryPrecheck::Retrieve
+        );
+    }
+}
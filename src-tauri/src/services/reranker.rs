@@ -0,0 +1,306 @@
+//! 检索结果重排序
+//!
+//! 向量检索先召回 `reranker.candidates` 个候选块，再由 cross-encoder 对（问题，候选块）重新打分，
+//! 按新分数取前 top_k 个作为上下文。可以调用 DashScope 重排序 API，也可以通过 Python 子进程
+//! 在本地运行 bge-reranker 等模型，适合完全离线的环境。本地重排序使用单独的 Python 进程，
+//! 启动时预加载模型，推理期间不占用数据库连接。
+
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::{RerankerConfig, RerankerProvider};
+use crate::services::document_service::SimilarChunk;
+use crate::services::python_subprocess::PythonSubprocess;
+use crate::services::seekdb_adapter::SeekDbAdapter;
+
+/// API 重排序的默认模型
+pub const DEFAULT_API_RERANK_MODEL: &str = "gte-rerank";
+/// 本地重排序的默认模型
+pub const DEFAULT_LOCAL_RERANK_MODEL: &str = "BAAI/bge-reranker-base";
+
+const DEFAULT_API_BASE_URL: &str = "https://dashscope.aliyuncs.com/api/v1";
+
+/// 本地重排序单次打分的超时；超时后按原检索顺序截取
+const LOCAL_RERANK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    input: RerankInput<'a>,
+    parameters: RerankParameters,
+}
+
+#[derive(Debug, Serialize)]
+struct RerankInput<'a> {
+    query: &'a str,
+    documents: &'a [String],
+}
+
+#[derive(Debug, Serialize)]
+struct RerankParameters {
+    return_documents: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankResponse {
+    output: RerankOutput,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankOutput {
+    results: Vec<RerankResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f64,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Api {
+        client: Client,
+        api_key: String,
+        base_url: String,
+    },
+    Local {
+        bridge: Arc<Mutex<PythonSubprocess>>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Reranker {
+    backend: Backend,
+    model: String,
+    candidates: usize,
+}
+
+impl Reranker {
+    /// 按配置创建重排序器，`provider` 为 none 时返回 None
+    ///
+    /// 本地重排序从数据库使用的 bridge 脚本另外启动一个 Python 进程
+    pub fn from_config(config: &RerankerConfig, api_key: &str, db: &SeekDbAdapter) -> Result<Option<Self>> {
+        let (backend, default_model) = match config.provider {
+            RerankerProvider::None => return Ok(None),
+            RerankerProvider::Api => {
+                let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+                let backend = Backend::Api {
+                    client,
+                    api_key: api_key.to_string(),
+                    base_url: config.base_url.clone().unwrap_or_else(|| DEFAULT_API_BASE_URL.to_string()),
+                };
+                (backend, DEFAULT_API_RERANK_MODEL)
+            }
+            RerankerProvider::Local => {
+                let bridge = Arc::new(Mutex::new(db.spawn_bridge()?));
+                (Backend::Local { bridge }, DEFAULT_LOCAL_RERANK_MODEL)
+            }
+        };
+
+        let model = config.model.clone().unwrap_or_else(|| default_model.to_string());
+        log::info!("🔀 启用重排序: provider={:?}, model={}, candidates={}", config.provider, model, config.candidates);
+        Ok(Some(Self {
+            backend,
+            model,
+            candidates: config.candidates,
+        }))
+    }
+
+    /// 在后台加载本地重排序模型，避免第一次检索时等待模型加载；API 重排序无需预加载
+    pub fn preload(&self) {
+        let Backend::Local { bridge } = &self.backend else {
+            return;
+        };
+        let bridge = bridge.clone();
+        let model = self.model.clone();
+        tokio::task::spawn_blocking(move || {
+            log::info!("🔀 预加载本地重排序模型: {}", model);
+            match bridge.lock().unwrap().rerank(&model, "warm up", &["warm up".to_string()]) {
+                Ok(_) => log::info!("✅ 本地重排序模型已加载: {}", model),
+                Err(e) => log::warn!("⚠️  预加载本地重排序模型失败: {}", e),
+            }
+        });
+    }
+
+    /// 重排序前召回的候选数量
+    pub fn candidates(&self, top_k: usize) -> usize {
+        self.candidates.max(top_k)
+    }
+
+    /// 为每个候选文本打分，分数顺序与 `documents` 一致
+    pub async fn score(&self, query: &str, documents: &[String]) -> Result<Vec<f64>> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        match &self.backend {
+            Backend::Api { client, api_key, base_url } => {
+                let url = format!("{}/services/rerank/text-rerank/text-rerank", base_url);
+                let request = RerankRequest {
+                    model: &self.model,
+                    input: RerankInput { query, documents },
+                    parameters: RerankParameters { return_documents: false },
+                };
+
+                let response = client
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .json(&request)
+                    .send()
+                    .await?;
+                let status = response.status();
+                if !status.is_success() {
+                    let error_text = response.text().await.unwrap_or_default();
+                    return Err(anyhow!("重排序 API 调用失败 [{}]: {}", status, error_text));
+                }
+
+                let result: RerankResponse = response.json().await?;
+                scores_by_index(result.output.results, documents.len())
+            }
+            Backend::Local { bridge } => {
+                let bridge = bridge.clone();
+                let (model, query, documents) = (self.model.clone(), query.to_string(), documents.to_vec());
+                let inference = tokio::task::spawn_blocking(move || bridge.lock().unwrap().rerank(&model, &query, &documents));
+                let logits = tokio::time::timeout(LOCAL_RERANK_TIMEOUT, inference)
+                    .await
+                    .map_err(|_| anyhow!("本地重排序超过 {} 秒未完成", LOCAL_RERANK_TIMEOUT.as_secs()))???;
+                Ok(logits.into_iter().map(sigmoid).collect())
+            }
+        }
+    }
+}
+
+/// cross-encoder 输出的是 logit，用 sigmoid 映射到 0~1，与向量相似度处于同一范围
+fn sigmoid(logit: f64) -> f64 {
+    1.0 / (1.0 + (-logit).exp())
+}
+
+/// 对召回的候选块重排序并取前 top_k；未开启重排序或重排序失败时按原顺序截取
+pub async fn rerank_chunks(reranker: Option<&Reranker>, query: &str, chunks: Vec<SimilarChunk>, top_k: usize) -> Vec<SimilarChunk> {
+    let Some(reranker) = reranker else {
+        return chunks.into_iter().take(top_k).collect();
+    };
+
+    let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+    let reranked = reranker
+        .score(query, &texts)
+        .await
+        .and_then(|scores| apply_scores(chunks.clone(), &scores, top_k));
+    match reranked {
+        Ok(reranked) => {
+            log::info!("🔀 重排序完成: {} 个候选 -> {} 个", texts.len(), reranked.len());
+            reranked
+        }
+        Err(e) => {
+            log::warn!("⚠️  重排序失败，使用原检索顺序: {}", e);
+            chunks.into_iter().take(top_k).collect()
+        }
+    }
+}
+
+/// 把 API 返回的（index, 分数）还原为与输入顺序一致的分数列表
+fn scores_by_index(results: Vec<RerankResult>, expected: usize) -> Result<Vec<f64>> {
+    let mut scores = vec![None; expected];
+    for result in results {
+        let slot = scores
+            .get_mut(result.index)
+            .ok_or_else(|| anyhow!("重排序结果的 index {} 超出范围", result.index))?;
+        *slot = Some(result.relevance_score);
+    }
+    scores
+        .into_iter()
+        .enumerate()
+        .map(|(index, score)| score.ok_or_else(|| anyhow!("重排序结果缺少第 {} 个候选的分数", index)))
+        .collect()
+}
+
+/// 用重排序分数替换相关度，按分数降序保留前 `top_k` 个
+pub fn apply_scores(chunks: Vec<SimilarChunk>, scores: &[f64], top_k: usize) -> Result<Vec<SimilarChunk>> {
+    if scores.len() != chunks.len() {
+        return Err(anyhow!("重排序分数数量 ({}) 与候选数量 ({}) 不一致", scores.len(), chunks.len()));
+    }
+
+    let mut reranked: Vec<SimilarChunk> = chunks
+        .into_iter()
+        .zip(scores)
+        .map(|(chunk, &score)| SimilarChunk { relevance_score: score, ..chunk })
+        .collect();
+    reranked.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+    reranked.truncate(top_k);
+    Ok(reranked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(content: &str, relevance_score: f64) -> SimilarChunk {
+        SimilarChunk {
+            project_id: "project".to_string(),
+            document_id: "doc".to_string(),
+            filename: Some("notes.md".to_string()),
+            content: content.to_string(),
+            relevance_score,
+            section: None,
+        }
+    }
+
+    #[test]
+    fn test_rerank_scores_reorder_candidates_and_keep_top_k() {
+        let candidates = vec![chunk("天气", 0.9), chunk("SeekDB 向量索引", 0.6), chunk("其他", 0.5)];
+        let reranked = apply_scores(candidates, &[0.1, 0.95, 0.3], 2).unwrap();
+        let contents: Vec<&str> = reranked.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(contents, ["SeekDB 向量索引", "其他"]);
+        assert_eq!(reranked[0].relevance_score, 0.95);
+
+        assert!(apply_scores(vec![chunk("a", 0.5)], &[], 1).is_err());
+
+        let api_results = vec![
+            RerankResult { index: 1, relevance_score: 0.8 },
+            RerankResult { index: 0, relevance_score: 0.2 },
+        ];
+        assert_eq!(scores_by_index(api_results, 2).unwrap(), vec![0.2, 0.8]);
+        assert!(scores_by_index(vec![RerankResult { index: 0, relevance_score: 0.2 }], 2).is_err());
+    }
+
+    #[test]
+    fn test_local_logits_are_mapped_to_unit_interval_preserving_order() {
+        assert_eq!(sigmoid(0.0), 0.5);
+        let scores: Vec<f64> = [-8.2, -0.4, 3.1].into_iter().map(sigmoid).collect();
+        assert!(scores.iter().all(|score| (0.0..1.0).contains(score)));
+        assert!(scores.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    /// Requires python3 with seekdb and sentence-transformers installed, plus the model download.
+    /// Run with `cargo test --features local-reranker-tests`.
+    #[cfg(feature = "local-reranker-tests")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_local_reranker_reorders_candidates_by_score() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("rerank.db");
+        let db = SeekDbAdapter::new(&db_path).unwrap();
+
+        let config = RerankerConfig {
+            provider: RerankerProvider::Local,
+            ..Default::default()
+        };
+        let reranker = Reranker::from_config(&config, "", &db).unwrap().unwrap();
+
+        let query = "How do I create a vector index in SeekDB?";
+        let candidates = vec![
+            chunk("The weather today is sunny with a light breeze.", 0.9),
+            chunk("Create a vector index in SeekDB with CREATE VECTOR INDEX on the embedding column.", 0.4),
+        ];
+        let texts: Vec<String> = candidates.iter().map(|c| c.content.clone()).collect();
+        let scores = reranker.score(query, &texts).await.unwrap();
+        assert_eq!(scores.len(), 2);
+        assert!(scores.iter().all(|score| (0.0..=1.0).contains(score)));
+
+        let reranked = apply_scores(candidates, &scores, 2).unwrap();
+        assert!(reranked[0].content.contains("vector index"));
+    }
+}
@@ -0,0 +1,115 @@
+//! 回复后处理模块
+//!
+//! 部分模型会把回答包在 `<answer>...</answer>` 之类的标签中，或者在回答开头复述系统提示词。
+//! 这里在流式输出结束后对完整回复做一次清理，再保存到数据库；流式 token 保持原样发送。
+
+use crate::services::prompts;
+
+/// 默认剥离的包装标签
+pub const DEFAULT_WRAPPER_TAGS: &[&str] = &["answer", "response", "final_answer"];
+
+/// 相关度页脚的前缀：chat.scoreFooter 为 inline 时追加到保存的助手消息末尾，界面据此识别并隐藏页脚
+pub const SCORE_FOOTER_PREFIX: &str = "<!-- mine-kb-scores: ";
+
+/// 去掉消息末尾的相关度页脚；发送给 LLM 的对话历史和导出的内容不包含页脚
+pub fn strip_score_footer(content: &str) -> &str {
+    match content.rfind(SCORE_FOOTER_PREFIX) {
+        Some(start) if content.trim_end().ends_with("-->") => content[..start].trim_end(),
+        _ => content,
+    }
+}
+
+/// 删除所有出现的 `<tag>` / `</tag>`（忽略大小写，允许标签带属性）
+fn strip_tag(content: &str, tag: &str) -> String {
+    let lower = content.to_ascii_lowercase();
+    let open = format!("<{}", tag.to_ascii_lowercase());
+    let close = format!("</{}>", tag.to_ascii_lowercase());
+
+    let mut result = String::with_capacity(content.len());
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let rest = &lower[pos..];
+        if rest.starts_with(&close) {
+            pos += close.len();
+            continue;
+        }
+        if rest.starts_with(&open) {
+            let after = &rest[open.len()..];
+            if after.starts_with('>') || after.starts_with(char::is_whitespace) {
+                if let Some(end) = after.find('>') {
+                    pos += open.len() + end + 1;
+                    continue;
+                }
+            }
+        }
+        let c = content[pos..].chars().next().unwrap();
+        result.push(c);
+        pos += c.len_utf8();
+    }
+
+    result
+}
+
+/// 清理模型回复：剥离配置的包装标签、去掉复述的系统提示词并修剪首尾空白。
+/// 清理后为空时返回原始内容（修剪后），避免丢失回答。
+pub fn clean_response<S: AsRef<str>>(content: &str, wrapper_tags: &[S]) -> String {
+    let mut cleaned = content.to_string();
+
+    for tag in wrapper_tags {
+        let tag = tag.as_ref().trim();
+        if !tag.is_empty() {
+            cleaned = strip_tag(&cleaned, tag);
+        }
+    }
+
+    let mut cleaned = cleaned.trim();
+    let leaked_prompt = prompts::get_base_system_prompt().trim();
+    if let Some(rest) = cleaned.strip_prefix(leaked_prompt) {
+        log::warn!("⚠️ 回复开头复述了系统提示词，已移除");
+        cleaned = rest.trim_start();
+    }
+
+    if cleaned.is_empty() {
+        content.trim().to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_footer_is_stripped() {
+        let content = "SeekDB 支持混合检索。\n\n<!-- mine-kb-scores: [{\"index\":0,\"score\":0.9}] -->";
+        assert_eq!(strip_score_footer(content), "SeekDB 支持混合检索。");
+        assert_eq!(strip_score_footer("没有页脚的回答"), "没有页脚的回答");
+        // 正文中提到前缀但不在末尾时保持原样
+        let quoted = "页脚以 <!-- mine-kb-scores: 开头，后面还有正文";
+        assert_eq!(strip_score_footer(quoted), quoted);
+    }
+
+    #[test]
+    fn test_wrapped_response_is_cleaned() {
+        let raw = "\n<answer>\n根据资料，SeekDB 支持混合检索。\n</answer>\n";
+        assert_eq!(clean_response(raw, DEFAULT_WRAPPER_TAGS), "根据资料，SeekDB 支持混合检索。");
+
+        let raw = "<Final_Answer type=\"text\">结论</FINAL_ANSWER>";
+        assert_eq!(clean_response(raw, DEFAULT_WRAPPER_TAGS), "结论");
+    }
+
+    #[test]
+    fn test_unconfigured_tags_and_plain_text_are_kept() {
+        let raw = "使用 <code>x</code> 和 <answers> 标签";
+        assert_eq!(clean_response(raw, DEFAULT_WRAPPER_TAGS), raw);
+        assert_eq!(clean_response("<answer>保留</answer>", &[] as &[&str]), "<answer>保留</answer>");
+    }
+
+    #[test]
+    fn test_leaked_system_prompt_is_removed() {
+        let raw = format!("{}\n\n真正的回答", prompts::get_base_system_prompt());
+        assert_eq!(clean_response(&raw, DEFAULT_WRAPPER_TAGS), "真正的回答");
+    }
+}
@@ -0,0 +1,155 @@
+//! 检索延迟基准测试
+//!
+//! 把一组查询反复执行"生成查询向量 → 向量检索"，分别统计两个阶段的 p50/p95/p99 延迟，
+//! 用于判断检索慢是 Embedding API 的问题还是数据库的问题。
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// 单个阶段的延迟统计（毫秒）
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// 基准测试结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RetrievalBenchmark {
+    pub query_count: usize,
+    pub iterations: u32,
+    pub embedding: LatencyStats,
+    pub search: LatencyStats,
+    /// 最后一轮每个查询检索到的结果数
+    pub result_counts: Vec<usize>,
+}
+
+/// 按最近秩法计算百分位
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn latency_stats(samples: &[Duration]) -> LatencyStats {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    LatencyStats {
+        samples: sorted.len(),
+        p50_ms: ms(percentile(&sorted, 50.0)),
+        p95_ms: ms(percentile(&sorted, 95.0)),
+        p99_ms: ms(percentile(&sorted, 99.0)),
+        max_ms: ms(sorted.last().copied().unwrap_or_default()),
+    }
+}
+
+/// 执行基准测试：每轮依次对每个查询调用 `embed` 和 `search`（返回检索到的结果数），分别计时
+pub async fn run_benchmark<E, EFut, S, SFut>(
+    queries: &[String],
+    iterations: u32,
+    mut embed: E,
+    mut search: S,
+) -> Result<RetrievalBenchmark>
+where
+    E: FnMut(String) -> EFut,
+    EFut: Future<Output = Result<Vec<f64>>>,
+    S: FnMut(Vec<f64>) -> SFut,
+    SFut: Future<Output = Result<usize>>,
+{
+    if queries.is_empty() {
+        return Err(anyhow!("查询列表不能为空"));
+    }
+    if iterations == 0 {
+        return Err(anyhow!("迭代次数必须大于 0"));
+    }
+
+    let mut embedding_samples = Vec::with_capacity(queries.len() * iterations as usize);
+    let mut search_samples = Vec::with_capacity(queries.len() * iterations as usize);
+    let mut result_counts = Vec::new();
+
+    for iteration in 1..=iterations {
+        result_counts.clear();
+        for query in queries {
+            let started = Instant::now();
+            let embedding = embed(query.clone()).await?;
+            embedding_samples.push(started.elapsed());
+
+            let started = Instant::now();
+            let count = search(embedding).await?;
+            search_samples.push(started.elapsed());
+
+            result_counts.push(count);
+        }
+        log::debug!("⏱️  检索基准测试第 {}/{} 轮完成", iteration, iterations);
+    }
+
+    let benchmark = RetrievalBenchmark {
+        query_count: queries.len(),
+        iterations,
+        embedding: latency_stats(&embedding_samples),
+        search: latency_stats(&search_samples),
+        result_counts,
+    };
+    log::info!(
+        "⏱️  检索基准测试完成: embedding p50={:.1}ms p95={:.1}ms, search p50={:.1}ms p95={:.1}ms",
+        benchmark.embedding.p50_ms,
+        benchmark.embedding.p95_ms,
+        benchmark.search.p50_ms,
+        benchmark.search.p95_ms
+    );
+    Ok(benchmark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_use_nearest_rank() {
+        let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = latency_stats(&samples);
+        assert_eq!(stats.samples, 100);
+        assert_eq!(stats.p50_ms, 50.0);
+        assert_eq!(stats.p95_ms, 95.0);
+        assert_eq!(stats.p99_ms, 99.0);
+        assert_eq!(stats.max_ms, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_reports_stage_percentiles_over_iterations() {
+        let queries = vec!["什么是 SeekDB?".to_string(), "如何导入文档?".to_string()];
+
+        let benchmark = run_benchmark(
+            &queries,
+            3,
+            |query| async move {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                Ok(vec![query.chars().count() as f64; 4])
+            },
+            |embedding| async move { Ok(embedding.len()) },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(benchmark.query_count, 2);
+        assert_eq!(benchmark.embedding.samples, 6);
+        assert_eq!(benchmark.search.samples, 6);
+        assert!(benchmark.embedding.p50_ms >= 5.0);
+        assert!(benchmark.embedding.p99_ms >= benchmark.embedding.p95_ms);
+        assert!(benchmark.embedding.p95_ms >= benchmark.embedding.p50_ms);
+        assert!(benchmark.search.p99_ms < benchmark.embedding.p50_ms);
+        assert_eq!(benchmark.result_counts, vec![4, 4]);
+
+        let empty = run_benchmark(&[], 3, |_| async { Ok(vec![]) }, |_| async { Ok(0) }).await;
+        assert!(empty.is_err());
+    }
+}
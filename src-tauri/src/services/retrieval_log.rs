@@ -0,0 +1,163 @@
+//! 检索日志模块
+//!
+//! 可选地把每一轮对话的检索详情（查询、检索到的文档块与分数、最终回答）
+//! 追加到应用数据目录下的 JSONL 文件中，用于离线构建 RAG 评测集。
+//! 默认关闭，通过配置 `chat.retrievalLogEnabled` 开启。
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::models::conversation::ContextChunk;
+
+/// 检索日志文件名
+pub const RETRIEVAL_LOG_FILE: &str = "retrieval_log.jsonl";
+
+/// 单个检索到的文档块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievedChunkRecord {
+    pub document_id: String,
+    pub filename: String,
+    pub relevance_score: f64,
+    pub content: String,
+}
+
+/// 一轮对话的检索记录（JSONL 中的一行）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievalLogRecord {
+    pub timestamp: DateTime<Utc>,
+    pub conversation_id: String,
+    pub project_id: String,
+    pub query: String,
+    pub chunks: Vec<RetrievedChunkRecord>,
+    pub answer: String,
+}
+
+impl RetrievalLogRecord {
+    pub fn new(
+        conversation_id: &str,
+        project_id: &str,
+        query: &str,
+        context_chunks: &[ContextChunk],
+        answer: &str,
+    ) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            conversation_id: conversation_id.to_string(),
+            project_id: project_id.to_string(),
+            query: query.to_string(),
+            chunks: context_chunks
+                .iter()
+                .map(|chunk| RetrievedChunkRecord {
+                    document_id: chunk.document_id.clone(),
+                    filename: chunk.filename.clone(),
+                    relevance_score: chunk.relevance_score,
+                    content: chunk.content.clone(),
+                })
+                .collect(),
+            answer: answer.to_string(),
+        }
+    }
+}
+
+/// 检索日志文件路径
+pub fn log_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(RETRIEVAL_LOG_FILE)
+}
+
+/// 追加一条记录（一行 JSON）
+pub fn append_record(path: &Path, record: &RetrievalLogRecord) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+/// 读取日志记录；`limit` 指定时只返回最近的 N 条。文件不存在时返回空列表，损坏的行会被跳过
+pub fn read_records(path: &Path, limit: Option<usize>) -> Result<Vec<RetrievalLogRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let mut records: Vec<RetrievalLogRecord> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::warn!("⚠️ 跳过无法解析的检索日志行: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    if let Some(limit) = limit {
+        let skip = records.len().saturating_sub(limit);
+        records.drain(..skip);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn sample_chunks() -> Vec<ContextChunk> {
+        vec![ContextChunk {
+            document_id: "doc-1".to_string(),
+            filename: "guide.md".to_string(),
+            content: "SeekDB 支持混合检索".to_string(),
+            relevance_score: 0.87,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
+        }]
+    }
+
+    #[test]
+    fn test_chat_turn_appends_one_jsonl_record() {
+        let dir = tempdir().unwrap();
+        let path = log_path(dir.path());
+
+        let record = RetrievalLogRecord::new("conv-1", "proj-1", "SeekDB 支持什么检索？", &sample_chunks(), "支持混合检索。");
+        append_record(&path, &record).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(value["query"], "SeekDB 支持什么检索？");
+        assert_eq!(value["chunks"][0]["filename"], "guide.md");
+        assert_eq!(value["chunks"][0]["relevance_score"], 0.87);
+        assert_eq!(value["answer"], "支持混合检索。");
+    }
+
+    #[test]
+    fn test_read_records_returns_latest_and_skips_malformed() {
+        let dir = tempdir().unwrap();
+        let path = log_path(dir.path());
+        assert!(read_records(&path, None).unwrap().is_empty());
+
+        for query in ["q1", "q2", "q3"] {
+            let record = RetrievalLogRecord::new("conv-1", "proj-1", query, &[], "a");
+            append_record(&path, &record).unwrap();
+        }
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"not json\n").unwrap();
+
+        let records = read_records(&path, Some(2)).unwrap();
+        assert_eq!(records.iter().map(|r| r.query.as_str()).collect::<Vec<_>>(), vec!["q2", "q3"]);
+    }
+}
@@ -2,11 +2,424 @@ use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use super::python_subprocess::PythonSubprocess;
 
+/// Default time to wait for another initializer to release the schema lock
+pub const DEFAULT_SCHEMA_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A lock file older than this is assumed to belong to a crashed process
+const SCHEMA_LOCK_STALE_SECS: u64 = 120;
+
+/// Cross-instance lock guarding schema initialization.
+///
+/// Implemented as an exclusively-created lock file next to the database, so it
+/// serializes initializers across adapters, threads and processes. The lock file
+/// is removed when the guard is dropped.
+struct SchemaInitLock {
+    path: PathBuf,
+}
+
+impl SchemaInitLock {
+    fn lock_path(db_path: &str) -> PathBuf {
+        PathBuf::from(format!("{}.schema.lock", db_path))
+    }
+
+    fn acquire(path: PathBuf, timeout: Duration) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let started = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if Self::is_stale(&path) {
+                        log::warn!("⚠️ 发现过期的 schema 锁文件，移除: {:?}", path);
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() >= timeout {
+                        return Err(anyhow!(
+                            "等待 schema 初始化锁超时 ({}s): {:?}",
+                            timeout.as_secs(),
+                            path
+                        ));
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(e) => return Err(anyhow!("无法创建 schema 锁文件 {:?}: {}", path, e)),
+            }
+        }
+    }
+
+    fn is_stale(path: &Path) -> bool {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map(|age| age > Duration::from_secs(SCHEMA_LOCK_STALE_SECS))
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for SchemaInitLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether another initializer currently holds a (non-stale) schema lock for `db_path`
+pub(crate) fn schema_lock_held(db_path: &str) -> bool {
+    let path = SchemaInitLock::lock_path(db_path);
+    path.exists() && !SchemaInitLock::is_stale(&path)
+}
+
+/// Run `f` while holding the schema init lock for the given database path
+fn with_schema_lock<T>(db_path: &str, timeout: Duration, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let _lock = SchemaInitLock::acquire(SchemaInitLock::lock_path(db_path), timeout)?;
+    f()
+}
+
+/// Parse a vector column value, which the bridge returns either as a JSON array
+/// or as its string form (e.g. "[0.1,0.2]")
+fn parse_embedding_value(value: &Value) -> Option<Vec<f64>> {
+    match value {
+        Value::Array(values) => values.iter().map(|v| v.as_f64()).collect(),
+        Value::String(s) => serde_json::from_str(s).ok(),
+        _ => None,
+    }
+}
+
+/// Bind value for `messages.processing_time` (NULL when unknown or not a finite number)
+fn processing_time_value(processing_time: Option<f64>) -> Value {
+    processing_time
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+/// `ORDER BY`/`LIMIT` suffix for a query, or an empty string when SeekDB can't run it
+/// (callers then sort and truncate in memory)
+fn order_limit_clause(supported: bool, order_by: &str, limit: Option<usize>) -> String {
+    if !supported {
+        return String::new();
+    }
+    match limit {
+        Some(limit) => format!(" ORDER BY {} LIMIT {}", order_by, limit),
+        None => format!(" ORDER BY {}", order_by),
+    }
+}
+
+/// The `limit` most recent messages in display order (in-memory fallback for `LIMIT`)
+fn latest_messages(
+    mut messages: Vec<crate::models::conversation::Message>,
+    limit: usize,
+) -> Vec<crate::models::conversation::Message> {
+    messages.sort_by_key(|m| m.order_key());
+    let skip = messages.len().saturating_sub(limit);
+    messages.drain(..skip);
+    messages
+}
+
+/// Read `token_count` (column 7) and `processing_time` (column 8) from a messages row.
+/// Rows saved before these columns existed have NULLs: the token count is re-estimated
+/// from the content and the processing time stays unknown.
+fn message_usage_from_row(row: &[Value], content: &str) -> (u32, Option<f64>) {
+    let number = |value: &Value| value.as_f64().or_else(|| value.as_str()?.parse().ok());
+    let token_count = row
+        .get(7)
+        .and_then(number)
+        .map(|n| n.max(0.0) as u32)
+        .unwrap_or_else(|| crate::models::conversation::Message::estimate_token_count(content));
+    let processing_time = row.get(8).and_then(number);
+    (token_count, processing_time)
+}
+
+/// Default vector dimension declared for `vector_documents.embedding` when the table is created
+/// (overridden by `embedding.dimension` in the config)
+pub const SCHEMA_EMBEDDING_DIMENSION: usize = 1536;
+
+/// Distance metric used to rank vectors in `similarity_search`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    /// Euclidean distance, similarity = 1 / (1 + distance)
+    #[default]
+    L2,
+    /// Cosine distance (1 - cosine similarity), similarity = 1 - distance
+    Cosine,
+    /// Negative inner product, similarity = inner product
+    InnerProduct,
+}
+
+/// The metric `idx_embedding` is built with.
+///
+/// The HNSW index only serves queries ranked by this metric. Searching with any other
+/// metric is an exact scan over every row in scope, which gets slow on large projects.
+pub const INDEXED_DISTANCE_METRIC: DistanceMetric = DistanceMetric::L2;
+
+impl DistanceMetric {
+    /// Value of the `distance=` option in the vector index definition
+    fn index_option(self) -> &'static str {
+        match self {
+            DistanceMetric::L2 => "l2",
+            DistanceMetric::Cosine => "cosine",
+            DistanceMetric::InnerProduct => "inner_product",
+        }
+    }
+
+    /// SQL function returning a distance where smaller means more similar
+    fn sql_function(self) -> &'static str {
+        match self {
+            DistanceMetric::L2 => "l2_distance",
+            DistanceMetric::Cosine => "cosine_distance",
+            DistanceMetric::InnerProduct => "negative_inner_product",
+        }
+    }
+
+    /// Whether the stored HNSW index can answer queries ranked by this metric
+    pub fn is_indexed(self) -> bool {
+        self == INDEXED_DISTANCE_METRIC
+    }
+
+    /// Map a distance returned by `sql_function` to a similarity (higher is better)
+    pub fn to_similarity(self, distance: f64) -> f64 {
+        match self {
+            DistanceMetric::L2 => 1.0 / (1.0 + distance.max(0.0)),
+            DistanceMetric::Cosine => 1.0 - distance,
+            DistanceMetric::InnerProduct => -distance,
+        }
+    }
+}
+
+/// Equality predicate on one key of the JSON `metadata` column.
+/// Binds the JSON path, then the expected value.
+const METADATA_FILTER_PREDICATE: &str = "JSON_UNQUOTE(JSON_EXTRACT(metadata, ?)) = ?";
+
+/// Bound values for metadata equality filters: a (path, value) pair per key, in key order.
+/// Keys end up in a JSON path, so only ASCII letters, digits and `_` are accepted.
+fn metadata_filter_values(filters: Option<&HashMap<String, String>>) -> Result<Vec<Value>> {
+    let Some(filters) = filters else {
+        return Ok(Vec::new());
+    };
+
+    let mut keys: Vec<&String> = filters.keys().collect();
+    keys.sort();
+
+    let mut values = Vec::with_capacity(keys.len() * 2);
+    for key in keys {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("Invalid metadata filter key: {:?}", key));
+        }
+        values.push(Value::String(format!("$.{}", key)));
+        values.push(Value::String(filters[key].clone()));
+    }
+    Ok(values)
+}
+
+/// Optional `project_id = ?` followed by one metadata predicate per filter, in bind order.
+fn filter_conditions(filter_by_project: bool, metadata_filter_count: usize) -> Vec<&'static str> {
+    let mut conditions = Vec::with_capacity(metadata_filter_count + 1);
+    if filter_by_project {
+        conditions.push("project_id = ?");
+    }
+    conditions.extend(std::iter::repeat_n(METADATA_FILTER_PREDICATE, metadata_filter_count));
+    conditions
+}
+
+/// Build the vector search query. Only the indexed metric gets `APPROXIMATE`;
+/// other metrics are ranked exactly. Filters are applied in the WHERE clause so
+/// `LIMIT` counts only matching chunks.
+fn similarity_search_sql(
+    embedding_str: &str,
+    metric: DistanceMetric,
+    filter_by_project: bool,
+    metadata_filter_count: usize,
+    limit: usize,
+) -> String {
+    let distance = format!("{}(embedding, '{}')", metric.sql_function(), embedding_str);
+    let conditions = filter_conditions(filter_by_project, metadata_filter_count);
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("\n                 WHERE {}", conditions.join(" AND "))
+    };
+    let approximate = if metric.is_indexed() { " APPROXIMATE" } else { "" };
+    format!(
+        "SELECT id, project_id, document_id, chunk_index, content, metadata,
+                        {distance} as distance
+                 FROM vector_documents{where_clause}
+                 ORDER BY {distance}{approximate}
+                 LIMIT {limit}"
+    )
+}
+
+/// Build the keyword-only query against the `idx_content` full-text index.
+/// The query text is bound twice (score and filter), followed by the optional
+/// project id and the metadata filter values. Without `ORDER BY ... LIMIT` support
+/// all matches are returned and the caller sorts and truncates them.
+fn fulltext_search_sql(supports_order_by: bool, filter_by_project: bool, metadata_filter_count: usize, limit: usize) -> String {
+    let project_clause: String = filter_conditions(filter_by_project, metadata_filter_count)
+        .iter()
+        .map(|condition| format!("\n                   AND {}", condition))
+        .collect();
+    let order_limit = order_limit_clause(supports_order_by, "score DESC", Some(limit));
+    format!(
+        "SELECT id, project_id, document_id, chunk_index, content, metadata,
+                        MATCH(content) AGAINST(? IN NATURAL LANGUAGE MODE) as score
+                 FROM vector_documents
+                 WHERE MATCH(content) AGAINST(? IN NATURAL LANGUAGE MODE){project_clause}{order_limit}"
+    )
+}
+
+/// Query for the ids of chunks matching the project and metadata filters.
+/// Used to scope hybrid search, whose knn filter can't reach into the metadata JSON.
+fn filtered_chunk_ids_sql(filter_by_project: bool, metadata_filter_count: usize) -> String {
+    format!(
+        "SELECT id FROM vector_documents WHERE {}",
+        filter_conditions(filter_by_project, metadata_filter_count).join(" AND ")
+    )
+}
+
+/// Build the `dbms_hybrid_search.search()` parameter (see docs/seekdb.md section 3.3).
+/// `chunk_ids` restricts the search to the given chunks through the knn filter.
+fn hybrid_search_param(
+    query_text: &str,
+    query_embedding: &[f64],
+    project_id: Option<&str>,
+    chunk_ids: Option<&[String]>,
+    limit: usize,
+    semantic_boost: f64,
+) -> String {
+    let mut filters = Vec::new();
+    if let Some(pid) = project_id {
+        filters.push(serde_json::json!({ "term": { "project_id": pid } }));
+    }
+    if let Some(ids) = chunk_ids {
+        filters.push(serde_json::json!({ "terms": { "id": ids } }));
+    }
+
+    let mut param = serde_json::json!({
+        "query": {
+            "bool": {
+                "must": [
+                    { "match": { "content": query_text } }
+                ]
+            }
+        },
+        "knn": {
+            "field": "embedding",
+            "k": limit,
+            "num_candidates": limit * 2,
+            "query_vector": query_embedding,
+            "boost": semantic_boost
+        },
+        "_source": ["id", "project_id", "document_id", "chunk_index", "content", "metadata", "_keyword_score", "_semantic_score"]
+    });
+    match filters.len() {
+        0 => {}
+        1 => param["filter"] = filters.remove(0),
+        _ => param["filter"] = serde_json::Value::Array(filters),
+    }
+    param.to_string()
+}
+
+/// Parse an `id, project_id, document_id, chunk_index, content, metadata` row.
+/// The embedding is left empty since these queries don't select the vector column.
+fn chunk_from_row(row: &[Value]) -> Option<VectorDocument> {
+    if row.len() < 6 {
+        return None;
+    }
+
+    let metadata_str = row[5].as_str().unwrap_or("{}");
+    let metadata: HashMap<String, String> = serde_json::from_str(metadata_str).unwrap_or_default();
+
+    Some(VectorDocument {
+        id: row[0].as_str().unwrap_or_default().to_string(),
+        project_id: row[1].as_str().unwrap_or_default().to_string(),
+        document_id: row[2].as_str().unwrap_or_default().to_string(),
+        chunk_index: row[3].as_i64().unwrap_or(0) as i32,
+        content: row[4].as_str().unwrap_or_default().to_string(),
+        embedding: vec![],
+        metadata,
+    })
+}
+
+fn vector_documents_table_sql(dimension: usize) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS vector_documents (
+                id VARCHAR(36) PRIMARY KEY,
+                project_id VARCHAR(36) NOT NULL,
+                document_id VARCHAR(36) NOT NULL,
+                chunk_index INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                embedding vector({}),
+                metadata TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE(document_id, chunk_index),
+                VECTOR INDEX idx_embedding(embedding) WITH (distance={}, type=hnsw, lib=vsag),
+                FULLTEXT idx_content(content)
+            )",
+        dimension,
+        INDEXED_DISTANCE_METRIC.index_option()
+    )
+}
+
+/// Extract N from the `embedding vector(N)` column definition of a CREATE TABLE statement
+fn parse_vector_dimension(create_table_sql: &str) -> Option<usize> {
+    let re = regex::Regex::new(r"(?i)`?embedding`?\s+vector\s*\(\s*(\d+)\s*\)").unwrap();
+    re.captures(create_table_sql)?.get(1)?.as_str().parse().ok()
+}
+
+/// Where the reported embedding dimension came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DimensionSource {
+    /// Length of a vector actually stored in the table
+    StoredVector,
+    /// `vector(N)` declared by the live table definition
+    TableSchema,
+    /// The dimension this version creates tables with (table empty and definition unreadable)
+    Default,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingDimension {
+    pub dimension: usize,
+    pub source: DimensionSource,
+}
+
+/// Prefer a stored vector's length, then the declared schema, then the configured default
+fn resolve_embedding_dimension(stored: Option<usize>, declared: Option<usize>, default: usize) -> EmbeddingDimension {
+    match (stored, declared) {
+        (Some(dimension), _) => EmbeddingDimension { dimension, source: DimensionSource::StoredVector },
+        (None, Some(dimension)) => EmbeddingDimension { dimension, source: DimensionSource::TableSchema },
+        (None, None) => EmbeddingDimension { dimension: default, source: DimensionSource::Default },
+    }
+}
+
+/// Reject vectors whose length doesn't match the `vector(N)` column before they reach SeekDB,
+/// which otherwise fails with an opaque error in the middle of the insert
+fn check_embedding_dimensions(docs: &[VectorDocument], column_dimension: usize) -> Result<()> {
+    match docs.iter().find(|doc| doc.embedding.len() != column_dimension) {
+        Some(doc) => Err(anyhow!(
+            "Embedding dimension mismatch: chunk {} of document {} has {} values but vector_documents.embedding \
+             is vector({}); set embedding.dimension to the embedding model's dimension or use a database created with it",
+            doc.chunk_index,
+            doc.document_id,
+            doc.embedding.len(),
+            column_dimension
+        )),
+        None => Ok(()),
+    }
+}
+
 /// Vector document structure (same as before)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VectorDocument {
@@ -26,12 +439,50 @@ pub struct SearchResult {
     pub similarity: f64,
 }
 
+/// Stored embedding of a project's name + description (used for query routing)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectEmbedding {
+    pub project_id: String,
+    pub source_text: String,
+    pub embedding: Vec<f64>,
+}
+
+/// Per-project chunk storage statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStorageStats {
+    pub project_id: String,
+    pub chunk_count: i64,
+    pub content_bytes: i64,
+}
+
+/// Token usage summed over one (project, kind, model) group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotal {
+    pub project_id: String,
+    /// `embedding` or `chat`
+    pub kind: String,
+    pub model: String,
+    pub tokens: i64,
+    /// Number of recorded API calls
+    pub requests: i64,
+}
+
+/// Timestamps in `usage_events` are stored as UTC `YYYY-MM-DD HH:MM:SS` so range filters compare correctly
+fn usage_timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
 /// SeekDB adapter - manages database operations through Python subprocess
 #[derive(Clone, Debug)]
 pub struct SeekDbAdapter {
     subprocess: Arc<Mutex<PythonSubprocess>>,
     db_path: String,
     db_name: String,
+    /// Dimension of the `vector_documents.embedding` column
+    embedding_dimension: usize,
+    /// Whether the running SeekDB build accepts `ORDER BY ... LIMIT`; older ObLite builds
+    /// reject it, in which case results are sorted (and truncated) in memory
+    supports_order_by: bool,
 }
 
 impl SeekDbAdapter {
@@ -42,6 +493,24 @@ impl SeekDbAdapter {
     
     /// Create new SeekDB adapter instance with custom Python executable
     pub fn new_with_python<P: AsRef<Path>>(db_path: P, python_executable: &str) -> Result<Self> {
+        Self::new_with_dimension(db_path, python_executable, SCHEMA_EMBEDDING_DIMENSION, DEFAULT_SCHEMA_LOCK_TIMEOUT)
+    }
+
+    /// Create new SeekDB adapter instance whose vector column is created with `embedding_dimension`.
+    /// An existing table keeps the dimension it was created with. `schema_lock_timeout` bounds how
+    /// long schema initialization waits for another initializer of the same database.
+    ///
+    /// This blocks on the Python bridge and the schema lock; call it from `spawn_blocking` in async code.
+    pub fn new_with_dimension<P: AsRef<Path>>(
+        db_path: P,
+        python_executable: &str,
+        embedding_dimension: usize,
+        schema_lock_timeout: Duration,
+    ) -> Result<Self> {
+        if embedding_dimension == 0 {
+            return Err(anyhow!("Embedding dimension must be greater than 0"));
+        }
+
         let db_path_str = db_path.as_ref().display().to_string();
         log::info!("🔗 [NEW-DB] Opening SeekDB: {}", db_path_str);
         
@@ -108,22 +577,75 @@ impl SeekDbAdapter {
         // Initialize database - use the actual db_path passed to the function
         subprocess.init_db(&db_path_str, &db_name)?;
         
-        let adapter = Self {
+        let mut adapter = Self {
             subprocess: Arc::new(Mutex::new(subprocess)),
             db_path: db_path_str.clone(),
             db_name: db_name.clone(),
+            embedding_dimension,
+            supports_order_by: false,
         };
         
         // Initialize schema
-        adapter.initialize_schema()?;
+        adapter.initialize_schema(schema_lock_timeout)?;
+
+        adapter.supports_order_by = adapter.probe_order_by_support();
+        log::info!("🔗 [NEW-DB] ORDER BY ... LIMIT supported: {}", adapter.supports_order_by);
+
+        // A table created earlier keeps its declared dimension
+        if let Some(declared) = adapter.declared_embedding_dimension() {
+            if declared != embedding_dimension {
+                log::warn!(
+                    "⚠️ vector_documents.embedding is vector({}) but the configured embedding dimension is {}; \
+                     inserting vectors of the configured size will fail",
+                    declared,
+                    embedding_dimension
+                );
+            }
+            adapter.embedding_dimension = declared;
+        }
         
         log::info!("🔗 [NEW-DB] SeekDB adapter initialized successfully");
         
         Ok(adapter)
     }
     
+    /// Command protocol version reported by the Python bridge
+    pub fn bridge_protocol_version(&self) -> u32 {
+        self.subprocess.lock().unwrap().protocol_version()
+    }
+
+    /// Installed seekdb package version, queried through the bridge
+    pub fn seekdb_version(&self) -> Result<Option<String>> {
+        self.subprocess.lock().unwrap().seekdb_version()
+    }
+
+    /// Whether queries can push sorting and limits down to SeekDB
+    pub fn supports_order_by(&self) -> bool {
+        self.supports_order_by
+    }
+
+    /// Run a tiny `ORDER BY ... LIMIT` query against the (already created) messages table
+    fn probe_order_by_support(&self) -> bool {
+        let subprocess = self.subprocess.lock().unwrap();
+        match subprocess.query("SELECT id FROM messages ORDER BY created_at DESC, sequence DESC LIMIT 1", vec![]) {
+            Ok(_) => true,
+            Err(e) => {
+                log::info!("ORDER BY ... LIMIT not supported by this SeekDB build, sorting in memory: {}", e);
+                false
+            }
+        }
+    }
+    
     /// Initialize database schema
-    fn initialize_schema(&self) -> Result<()> {
+    ///
+    /// All statements are idempotent (`IF NOT EXISTS`), and the whole run is
+    /// serialized through a cross-instance lock so that concurrent initializers
+    /// against the same database don't race on DDL.
+    fn initialize_schema(&self, lock_timeout: Duration) -> Result<()> {
+        with_schema_lock(&self.db_path, lock_timeout, || self.create_schema())
+    }
+    
+    fn create_schema(&self) -> Result<()> {
         log::info!("📋 Initializing database schema...");
         
         let subprocess = self.subprocess.lock().unwrap();
@@ -143,22 +665,7 @@ impl SeekDbAdapter {
         )?;
         
         // Create vector_documents table with vector index and fulltext index for hybrid search
-        subprocess.execute(
-            "CREATE TABLE IF NOT EXISTS vector_documents (
-                id VARCHAR(36) PRIMARY KEY,
-                project_id VARCHAR(36) NOT NULL,
-                document_id VARCHAR(36) NOT NULL,
-                chunk_index INTEGER NOT NULL,
-                content TEXT NOT NULL,
-                embedding vector(1536),
-                metadata TEXT NOT NULL,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                UNIQUE(document_id, chunk_index),
-                VECTOR INDEX idx_embedding(embedding) WITH (distance=l2, type=hnsw, lib=vsag),
-                FULLTEXT idx_content(content)
-            )",
-            vec![],
-        )?;
+        subprocess.execute(&vector_documents_table_sql(self.embedding_dimension), vec![])?;
         
         // Create regular indexes
         subprocess.execute(
@@ -180,6 +687,7 @@ impl SeekDbAdapter {
                 created_at DATETIME NOT NULL,
                 updated_at DATETIME NOT NULL,
                 message_count INTEGER DEFAULT 0,
+                model TEXT,
                 FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
             )",
             vec![],
@@ -194,6 +702,9 @@ impl SeekDbAdapter {
                 content TEXT NOT NULL,
                 created_at DATETIME NOT NULL,
                 sources TEXT,
+                sequence BIGINT DEFAULT 0,
+                token_count INTEGER,
+                processing_time REAL,
                 FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
             )",
             vec![],
@@ -210,6 +721,57 @@ impl SeekDbAdapter {
             vec![],
         )?;
         
+        // Add the per-conversation model override column to databases created before it existed
+        if subprocess.query("SELECT model FROM conversations LIMIT 1", vec![]).is_err() {
+            log::info!("Adding model column to conversations table");
+            subprocess.execute("ALTER TABLE conversations ADD COLUMN model TEXT", vec![])?;
+        }
+
+        // Add the message sequence column (ordering tiebreaker for same-second messages)
+        if subprocess.query("SELECT sequence FROM messages LIMIT 1", vec![]).is_err() {
+            log::info!("Adding sequence column to messages table");
+            subprocess.execute("ALTER TABLE messages ADD COLUMN sequence BIGINT DEFAULT 0", vec![])?;
+        }
+
+        // Add the per-message usage columns (token count and generation latency). Each column is
+        // probed on its own so a database where only one of them was added still gets the other.
+        for (column, column_type) in [("token_count", "INTEGER"), ("processing_time", "REAL")] {
+            if subprocess.query(&format!("SELECT {} FROM messages LIMIT 1", column), vec![]).is_err() {
+                log::info!("Adding {} column to messages table", column);
+                subprocess.execute(&format!("ALTER TABLE messages ADD COLUMN {} {}", column, column_type), vec![])?;
+            }
+        }
+
+        // Create project description embeddings table (embedding stored as JSON text,
+        // ranking is done in memory since the number of projects is small)
+        subprocess.execute(
+            "CREATE TABLE IF NOT EXISTS project_embeddings (
+                project_id VARCHAR(36) PRIMARY KEY,
+                source_text TEXT NOT NULL,
+                embedding TEXT NOT NULL,
+                updated_at DATETIME NOT NULL
+            )",
+            vec![],
+        )?;
+        
+        // Create the token usage log (one row per embedding batch / chat response) for cost reports
+        subprocess.execute(
+            "CREATE TABLE IF NOT EXISTS usage_events (
+                id BIGINT AUTO_INCREMENT PRIMARY KEY,
+                project_id VARCHAR(36) NOT NULL,
+                kind VARCHAR(16) NOT NULL,
+                tokens BIGINT NOT NULL,
+                model VARCHAR(128) NOT NULL,
+                created_at DATETIME NOT NULL
+            )",
+            vec![],
+        )?;
+
+        subprocess.execute(
+            "CREATE INDEX IF NOT EXISTS idx_usage_created_at ON usage_events(created_at)",
+            vec![],
+        )?;
+        
         // Commit schema changes
         subprocess.commit()?;
         
@@ -219,6 +781,7 @@ impl SeekDbAdapter {
     
     /// Add a single vector document
     pub fn add_document(&mut self, doc: VectorDocument) -> Result<()> {
+        check_embedding_dimensions(std::slice::from_ref(&doc), self.embedding_dimension)?;
         let subprocess = self.subprocess.lock().unwrap();
         
         let metadata_json = serde_json::to_string(&doc.metadata)?;
@@ -255,47 +818,98 @@ impl SeekDbAdapter {
     
     /// Add multiple vector documents in a transaction
     pub fn add_documents(&mut self, docs: Vec<VectorDocument>) -> Result<()> {
+        check_embedding_dimensions(&docs, self.embedding_dimension)?;
         let subprocess = self.subprocess.lock().unwrap();
         
         for doc in docs {
-            let metadata_json = serde_json::to_string(&doc.metadata)?;
-            let embedding_str = format!("[{}]", 
-                doc.embedding.iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<_>>()
-                    .join(",")
-            );
-            
-            subprocess.execute(
-                "INSERT INTO vector_documents 
-                 (id, project_id, document_id, chunk_index, content, embedding, metadata, created_at)
-                 VALUES (?, ?, ?, ?, ?, ?, ?, NOW())
-                 ON DUPLICATE KEY UPDATE 
-                    content = VALUES(content),
-                    embedding = VALUES(embedding),
-                    metadata = VALUES(metadata)",
-                vec![
-                    Value::String(doc.id),
-                    Value::String(doc.project_id),
-                    Value::String(doc.document_id),
-                    Value::Number(doc.chunk_index.into()),
-                    Value::String(doc.content),
-                    Value::String(embedding_str),
-                    Value::String(metadata_json),
-                ],
-            )?;
+            Self::insert_vector_document(&subprocess, doc)?;
         }
         
         subprocess.commit()?;
         Ok(())
     }
     
+    /// Atomically replace all chunks of a document with a new set of chunks.
+    /// Returns (deleted, inserted); rolls back if any statement fails.
+    pub fn replace_document_chunks(
+        &mut self,
+        document_id: &str,
+        docs: Vec<VectorDocument>,
+    ) -> Result<(usize, usize)> {
+        check_embedding_dimensions(&docs, self.embedding_dimension)?;
+        let subprocess = self.subprocess.lock().unwrap();
+        let inserted = docs.len();
+        
+        let result = (|| -> Result<usize> {
+            let deleted = subprocess.execute(
+                "DELETE FROM vector_documents WHERE document_id = ?",
+                vec![Value::String(document_id.to_string())],
+            )?;
+            for doc in docs {
+                Self::insert_vector_document(&subprocess, doc)?;
+            }
+            Ok(deleted as usize)
+        })();
+        
+        match result {
+            Ok(deleted) => {
+                subprocess.commit()?;
+                Ok((deleted, inserted))
+            }
+            Err(e) => {
+                log::error!("❌ 替换文档块失败，回滚: document_id={}, error={}", document_id, e);
+                if let Err(rollback_err) = subprocess.rollback() {
+                    log::error!("❌ 回滚失败: {}", rollback_err);
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Insert (or upsert) a single vector document without committing
+    fn insert_vector_document(subprocess: &PythonSubprocess, doc: VectorDocument) -> Result<()> {
+        let metadata_json = serde_json::to_string(&doc.metadata)?;
+        let embedding_str = format!("[{}]", 
+            doc.embedding.iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        
+        subprocess.execute(
+            "INSERT INTO vector_documents 
+             (id, project_id, document_id, chunk_index, content, embedding, metadata, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, NOW())
+             ON DUPLICATE KEY UPDATE 
+                content = VALUES(content),
+                embedding = VALUES(embedding),
+                metadata = VALUES(metadata)",
+            vec![
+                Value::String(doc.id),
+                Value::String(doc.project_id),
+                Value::String(doc.document_id),
+                Value::Number(doc.chunk_index.into()),
+                Value::String(doc.content),
+                Value::String(embedding_str),
+                Value::String(metadata_json),
+            ],
+        )?;
+        
+        Ok(())
+    }
+    
     /// Hybrid search using SeekDB's native hybrid search (vector + fulltext)
+    ///
+    /// `metadata_filters` keeps only chunks whose metadata has exactly the given
+    /// values (e.g. `{"filename": "report.pdf"}`). The matching chunk ids are
+    /// resolved first and passed in the knn filter, so `limit` still applies to
+    /// matching chunks only.
     pub fn hybrid_search(
         &self,
         query_text: &str,
         query_embedding: &[f64],
         project_id: Option<&str>,
+        metadata_filters: Option<&HashMap<String, String>>,
         limit: usize,
         semantic_boost: f64,
     ) -> Result<Vec<SearchResult>> {
@@ -303,74 +917,43 @@ impl SeekDbAdapter {
         log::info!("   查询文本: {}", query_text);
         log::info!("   向量维度: {}", query_embedding.len());
         log::info!("   项目ID: {:?}", project_id);
+        log::info!("   元数据过滤: {:?}", metadata_filters);
         log::info!("   返回数量: {}", limit);
         log::info!("   语义权重: {}", semantic_boost);
         
+        let metadata_values = metadata_filter_values(metadata_filters)?;
         let subprocess = self.subprocess.lock().unwrap();
-        
-        // Convert query embedding to JSON array
-        let embedding_json = format!("[{}]", 
-            query_embedding.iter()
-                .map(|v| v.to_string())
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-        
-        // Build hybrid search query using dbms_hybrid_search.search()
-        // Reference: docs/seekdb.md section 3.3
-        let search_param = if let Some(pid) = project_id {
-            format!(r#"{{
-                "query": {{
-                    "bool": {{
-                        "must": [
-                            {{"match": {{"content": "{}"}}}}
-                        ]
-                    }}
-                }},
-                "knn": {{
-                    "field": "embedding",
-                    "k": {},
-                    "num_candidates": {},
-                    "query_vector": {},
-                    "boost": {}
-                }},
-                "filter": {{
-                    "term": {{"project_id": "{}"}}
-                }},
-                "_source": ["id", "project_id", "document_id", "chunk_index", "content", "metadata", "_keyword_score", "_semantic_score"]
-            }}"#, 
-                query_text.replace('"', "\\\""),
-                limit,
-                limit * 2,
-                embedding_json,
-                semantic_boost,
-                pid
-            )
+
+        let chunk_ids = if metadata_values.is_empty() {
+            None
         } else {
-            format!(r#"{{
-                "query": {{
-                    "bool": {{
-                        "must": [
-                            {{"match": {{"content": "{}"}}}}
-                        ]
-                    }}
-                }},
-                "knn": {{
-                    "field": "embedding",
-                    "k": {},
-                    "num_candidates": {},
-                    "query_vector": {},
-                    "boost": {}
-                }},
-                "_source": ["id", "project_id", "document_id", "chunk_index", "content", "metadata", "_keyword_score", "_semantic_score"]
-            }}"#,
-                query_text.replace('"', "\\\""),
-                limit,
-                limit * 2,
-                embedding_json,
-                semantic_boost
-            )
+            let metadata_filter_count = metadata_values.len() / 2;
+            let mut values = Vec::with_capacity(metadata_values.len() + 1);
+            if let Some(pid) = project_id {
+                values.push(Value::String(pid.to_string()));
+            }
+            values.extend(metadata_values);
+
+            let rows = subprocess.query(&filtered_chunk_ids_sql(project_id.is_some(), metadata_filter_count), values)?;
+            let ids: Vec<String> = rows
+                .iter()
+                .filter_map(|row| row.first().and_then(|v| v.as_str()).map(str::to_string))
+                .collect();
+            log::info!("   元数据过滤匹配 {} 个文档块", ids.len());
+            if ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            Some(ids)
         };
+
+        let search_param = hybrid_search_param(
+            query_text,
+            query_embedding,
+            project_id,
+            chunk_ids.as_deref(),
+            limit,
+            semantic_boost,
+        );
         
         log::debug!("混合搜索参数: {}", search_param);
         
@@ -445,14 +1028,21 @@ impl SeekDbAdapter {
         Ok(results)
     }
     
-    /// Vector similarity search using SeekDB's native L2 distance
+    /// Vector similarity search using SeekDB's native distance functions.
+    ///
+    /// Only `INDEXED_DISTANCE_METRIC` is served by the HNSW index; other metrics
+    /// scan every row in scope. `metadata_filters` adds equality predicates on
+    /// chunk metadata (e.g. `{"filename": "report.pdf"}`) to the WHERE clause.
     pub fn similarity_search(
         &self,
         query_embedding: &[f64],
         project_id: Option<&str>,
+        metadata_filters: Option<&HashMap<String, String>>,
         limit: usize,
         threshold: f64,
+        metric: DistanceMetric,
     ) -> Result<Vec<SearchResult>> {
+        let metadata_values = metadata_filter_values(metadata_filters)?;
         let subprocess = self.subprocess.lock().unwrap();
         
         // Convert query embedding to SeekDB format
@@ -463,35 +1053,26 @@ impl SeekDbAdapter {
                 .join(",")
         );
         
+        if !metric.is_indexed() {
+            log::warn!(
+                "Vector search with {:?} is not covered by idx_embedding ({:?}), falling back to a full scan",
+                metric,
+                INDEXED_DISTANCE_METRIC
+            );
+        }
+
         // Build SQL query with SeekDB's native vector search
         // Note: We don't SELECT the embedding field because SeekDB doesn't support
-        // fetching vector columns when using vector functions (l2_distance) with APPROXIMATE
-        let sql = if project_id.is_some() {
-            format!(
-                "SELECT id, project_id, document_id, chunk_index, content, metadata,
-                        l2_distance(embedding, '{}') as distance
-                 FROM vector_documents
-                 WHERE project_id = ?
-                 ORDER BY l2_distance(embedding, '{}') APPROXIMATE
-                 LIMIT {}",
-                embedding_str, embedding_str, limit * 2 // Get more to filter by threshold
-            )
-        } else {
-            format!(
-                "SELECT id, project_id, document_id, chunk_index, content, metadata,
-                        l2_distance(embedding, '{}') as distance
-                 FROM vector_documents
-                 ORDER BY l2_distance(embedding, '{}') APPROXIMATE
-                 LIMIT {}",
-                embedding_str, embedding_str, limit * 2
-            )
-        };
+        // fetching vector columns when using vector functions with APPROXIMATE
+        // Get more rows than needed so the threshold filter still leaves enough results
+        let sql = similarity_search_sql(&embedding_str, metric, project_id.is_some(), metadata_values.len() / 2, limit * 2);
         
-        let values = if project_id.is_some() {
+        let mut values = if project_id.is_some() {
             vec![Value::String(project_id.unwrap().to_string())]
         } else {
             vec![]
         };
+        values.extend(metadata_values);
         
         let rows = subprocess.query(&sql, values)?;
         
@@ -511,17 +1092,9 @@ impl SeekDbAdapter {
             let metadata_str = row[5].as_str().unwrap_or("{}");
             let metadata: HashMap<String, String> = serde_json::from_str(metadata_str).unwrap_or_default();
             
-            // Get distance (L2) and convert to similarity (inverse)
+            // Get distance and convert it to a similarity for the chosen metric
             let distance = row[6].as_f64().unwrap_or(f64::MAX);
-            
-            // Convert L2 distance to cosine similarity approximation
-            // For normalized vectors, cosine similarity ≈ 1 - (L2_distance^2 / 2)
-            // But since we don't know if vectors are normalized, we'll use a simple inverse
-            let similarity = if distance > 0.0 {
-                1.0 / (1.0 + distance)
-            } else {
-                1.0
-            };
+            let similarity = metric.to_similarity(distance);
             
             // Filter by threshold
             if similarity >= threshold {
@@ -539,63 +1112,304 @@ impl SeekDbAdapter {
                 });
             }
         }
-        
-        // Limit results
-        results.truncate(limit);
-        
-        Ok(results)
+        
+        // Limit results
+        results.truncate(limit);
+        
+        Ok(results)
+    }
+
+    /// Keyword-only search over the full-text index on `content`.
+    /// `similarity` holds the keyword relevance score, which is not comparable
+    /// to vector similarities, so no threshold is applied.
+    pub fn fulltext_search(
+        &self,
+        query_text: &str,
+        project_id: Option<&str>,
+        metadata_filters: Option<&HashMap<String, String>>,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let query_text = query_text.trim();
+        if query_text.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let metadata_values = metadata_filter_values(metadata_filters)?;
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let sql = fulltext_search_sql(self.supports_order_by, project_id.is_some(), metadata_values.len() / 2, limit);
+        let mut values = vec![
+            Value::String(query_text.to_string()),
+            Value::String(query_text.to_string()),
+        ];
+        if let Some(project_id) = project_id {
+            values.push(Value::String(project_id.to_string()));
+        }
+        values.extend(metadata_values);
+
+        let rows = subprocess.query(&sql, values)?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            if row.len() < 7 {
+                continue;
+            }
+
+            let metadata_str = row[5].as_str().unwrap_or("{}");
+            let metadata: HashMap<String, String> = serde_json::from_str(metadata_str).unwrap_or_default();
+
+            results.push(SearchResult {
+                document: VectorDocument {
+                    id: row[0].as_str().unwrap_or_default().to_string(),
+                    project_id: row[1].as_str().unwrap_or_default().to_string(),
+                    document_id: row[2].as_str().unwrap_or_default().to_string(),
+                    chunk_index: row[3].as_i64().unwrap_or(0) as i32,
+                    content: row[4].as_str().unwrap_or_default().to_string(),
+                    embedding: vec![],
+                    metadata,
+                },
+                similarity: row[6].as_f64().unwrap_or(0.0),
+            });
+        }
+
+        if !self.supports_order_by {
+            results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+            results.truncate(limit);
+        }
+
+        log::debug!("Full-text search for {:?} returned {} chunks", query_text, results.len());
+
+        Ok(results)
+    }
+    
+    /// Get all documents for a project
+    pub fn get_project_documents(&self, project_id: &str) -> Result<Vec<VectorDocument>> {
+        let subprocess = self.subprocess.lock().unwrap();
+        
+        // Note: SeekDB may not support selecting vector columns in all contexts
+        // We query without embedding field and use empty vectors
+        let rows = subprocess.query(
+            "SELECT id, project_id, document_id, chunk_index, content, metadata
+             FROM vector_documents
+             WHERE project_id = ?",
+            vec![Value::String(project_id.to_string())],
+        )?;
+        
+        let mut documents: Vec<VectorDocument> = rows.iter().filter_map(|row| chunk_from_row(row)).collect();
+        
+        // Sort documents by document_id and chunk_index in memory
+        documents.sort_by(|a, b| {
+            match a.document_id.cmp(&b.document_id) {
+                std::cmp::Ordering::Equal => a.chunk_index.cmp(&b.chunk_index),
+                other => other,
+            }
+        });
+        
+        Ok(documents)
+    }
+    
+    /// Get all chunks of a project including content and embeddings, for full-project export.
+    /// Chunks whose embedding is NULL or unreadable are returned with an empty embedding.
+    pub fn get_project_chunks_with_embeddings(&self, project_id: &str) -> Result<Vec<VectorDocument>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let rows = subprocess.query(
+            "SELECT id, project_id, document_id, chunk_index, content, metadata, embedding
+             FROM vector_documents
+             WHERE project_id = ?",
+            vec![Value::String(project_id.to_string())],
+        )?;
+
+        let mut chunks: Vec<VectorDocument> = rows
+            .iter()
+            .filter_map(|row| {
+                let mut chunk = chunk_from_row(row)?;
+                chunk.embedding = row.get(6).and_then(parse_embedding_value).unwrap_or_default();
+                Some(chunk)
+            })
+            .collect();
+        chunks.sort_by(|a, b| a.document_id.cmp(&b.document_id).then(a.chunk_index.cmp(&b.chunk_index)));
+
+        Ok(chunks)
+    }
+
+    /// Get all chunks of a single document, ordered by chunk_index (without embeddings)
+    pub fn get_document_chunks(&self, document_id: &str) -> Result<Vec<VectorDocument>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let rows = subprocess.query(
+            "SELECT id, project_id, document_id, chunk_index, content, metadata
+             FROM vector_documents
+             WHERE document_id = ?",
+            vec![Value::String(document_id.to_string())],
+        )?;
+
+        let mut chunks: Vec<VectorDocument> = rows.iter().filter_map(|row| chunk_from_row(row)).collect();
+        chunks.sort_by_key(|chunk| chunk.chunk_index);
+
+        Ok(chunks)
+    }
+
+    /// Get the chunks of a project whose embedding is NULL (left behind by partially failed ingestion)
+    pub fn get_chunks_missing_embeddings(&self, project_id: &str) -> Result<Vec<VectorDocument>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let rows = subprocess.query(
+            "SELECT id, project_id, document_id, chunk_index, content, metadata
+             FROM vector_documents
+             WHERE project_id = ? AND embedding IS NULL",
+            vec![Value::String(project_id.to_string())],
+        )?;
+
+        Ok(rows.iter().filter_map(|row| chunk_from_row(row)).collect())
+    }
+
+    /// Set the embedding of existing chunks that still have none, in one transaction.
+    /// Chunks that already have an embedding are left untouched. Returns the number of rows updated.
+    pub fn fill_chunk_embeddings(&mut self, chunks: &[VectorDocument]) -> Result<usize> {
+        check_embedding_dimensions(chunks, self.embedding_dimension)?;
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let result = (|| -> Result<usize> {
+            let mut updated = 0;
+            for chunk in chunks {
+                let embedding_str = format!("[{}]",
+                    chunk.embedding.iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+                updated += subprocess.execute(
+                    "UPDATE vector_documents SET embedding = ? WHERE id = ? AND embedding IS NULL",
+                    vec![Value::String(embedding_str), Value::String(chunk.id.clone())],
+                )? as usize;
+            }
+            Ok(updated)
+        })();
+
+        match result {
+            Ok(updated) => {
+                subprocess.commit()?;
+                Ok(updated)
+            }
+            Err(e) => {
+                log::error!("❌ 补全文档块向量失败，回滚: {}", e);
+                if let Err(rollback_err) = subprocess.rollback() {
+                    log::error!("❌ 回滚失败: {}", rollback_err);
+                }
+                Err(e)
+            }
+        }
     }
-    
-    /// Get all documents for a project
-    pub fn get_project_documents(&self, project_id: &str) -> Result<Vec<VectorDocument>> {
+
+    /// Insert a chunk with a NULL embedding (as left behind by a partially failed ingestion, or
+    /// imported from a bundle whose embedding dimension does not match); fill it in later with
+    /// `fill_chunk_embeddings`
+    pub(crate) fn insert_chunk_without_embedding(&mut self, doc: &VectorDocument) -> Result<()> {
         let subprocess = self.subprocess.lock().unwrap();
-        
-        // Note: SeekDB may not support selecting vector columns in all contexts
-        // We query without embedding field and use empty vectors
+        subprocess.execute(
+            "INSERT INTO vector_documents (id, project_id, document_id, chunk_index, content, metadata, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, NOW())",
+            vec![
+                Value::String(doc.id.clone()),
+                Value::String(doc.project_id.clone()),
+                Value::String(doc.document_id.clone()),
+                Value::Number(doc.chunk_index.into()),
+                Value::String(doc.content.clone()),
+                Value::String(serde_json::to_string(&doc.metadata)?),
+            ],
+        )?;
+        subprocess.commit()?;
+        Ok(())
+    }
+
+    /// Get the project a document's chunks belong to
+    pub fn get_document_project_id(&self, document_id: &str) -> Result<Option<String>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let row = subprocess.query_one(
+            "SELECT project_id FROM vector_documents WHERE document_id = ? LIMIT 1",
+            vec![Value::String(document_id.to_string())],
+        )?;
+
+        Ok(row.and_then(|row| row.first().and_then(|v| v.as_str()).map(str::to_string)))
+    }
+
+    /// Detect the embedding dimension of this database: sample a stored vector,
+    /// falling back to the dimension declared in the table definition
+    pub fn get_embedding_dimension(&self) -> Result<EmbeddingDimension> {
+        let stored = self
+            .subprocess
+            .lock()
+            .unwrap()
+            .query("SELECT embedding FROM vector_documents LIMIT 1", vec![])
+            .ok()
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|row| row.first().and_then(parse_embedding_value))
+            .map(|embedding| embedding.len())
+            .filter(|&len| len > 0);
+
+        let declared = if stored.is_none() { self.declared_embedding_dimension() } else { None };
+
+        let dimension = resolve_embedding_dimension(stored, declared, self.embedding_dimension);
+        log::info!("Embedding dimension: {} ({:?})", dimension.dimension, dimension.source);
+        Ok(dimension)
+    }
+
+    /// Dimension declared by the live `vector_documents` table definition
+    fn declared_embedding_dimension(&self) -> Option<usize> {
+        self.subprocess
+            .lock()
+            .unwrap()
+            .query("SHOW CREATE TABLE vector_documents", vec![])
+            .ok()
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|row| row.get(1).and_then(|v| v.as_str()).and_then(parse_vector_dimension))
+    }
+
+    /// Get all chunks of a project including their embeddings.
+    /// Plain SELECT of the vector column works as long as no vector function is used in the same query.
+    pub fn get_project_chunk_embeddings(&self, project_id: &str) -> Result<Vec<VectorDocument>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
         let rows = subprocess.query(
-            "SELECT id, project_id, document_id, chunk_index, content, metadata
+            "SELECT id, project_id, document_id, chunk_index, metadata, embedding
              FROM vector_documents
              WHERE project_id = ?",
             vec![Value::String(project_id.to_string())],
         )?;
-        
+
         let mut documents = Vec::new();
         for row in rows {
             if row.len() < 6 {
                 continue;
             }
-            
-            let id = row[0].as_str().unwrap_or_default().to_string();
-            let project_id = row[1].as_str().unwrap_or_default().to_string();
-            let document_id = row[2].as_str().unwrap_or_default().to_string();
-            let chunk_index = row[3].as_i64().unwrap_or(0) as i32;
-            let content = row[4].as_str().unwrap_or_default().to_string();
-            
-            let metadata_str = row[5].as_str().unwrap_or("{}");
+
+            let embedding = match parse_embedding_value(&row[5]) {
+                Some(embedding) if !embedding.is_empty() => embedding,
+                _ => {
+                    log::warn!("Skipping chunk {:?} with unreadable embedding", row[0].as_str());
+                    continue;
+                }
+            };
+
+            let metadata_str = row[4].as_str().unwrap_or("{}");
             let metadata: HashMap<String, String> = serde_json::from_str(metadata_str).unwrap_or_default();
-            
+
             documents.push(VectorDocument {
-                id,
-                project_id,
-                document_id,
-                chunk_index,
-                content,
-                embedding: vec![], // Empty vector - not needed for this query
+                id: row[0].as_str().unwrap_or_default().to_string(),
+                project_id: row[1].as_str().unwrap_or_default().to_string(),
+                document_id: row[2].as_str().unwrap_or_default().to_string(),
+                chunk_index: row[3].as_i64().unwrap_or(0) as i32,
+                content: String::new(), // Content not needed for embedding-only queries
+                embedding,
                 metadata,
             });
         }
-        
-        // Sort documents by document_id and chunk_index in memory
-        documents.sort_by(|a, b| {
-            match a.document_id.cmp(&b.document_id) {
-                std::cmp::Ordering::Equal => a.chunk_index.cmp(&b.chunk_index),
-                other => other,
-            }
-        });
-        
+
         Ok(documents)
     }
-    
+
     /// Delete all documents for a project
     pub fn delete_project_documents(&mut self, project_id: &str) -> Result<usize> {
         let subprocess = self.subprocess.lock().unwrap();
@@ -647,6 +1461,38 @@ impl SeekDbAdapter {
         Ok(stats)
     }
     
+    /// Get chunk count and approximate content bytes grouped by project
+    pub fn get_project_storage_stats(&self) -> Result<Vec<ProjectStorageStats>> {
+        let subprocess = self.subprocess.lock().unwrap();
+        
+        let rows = subprocess.query(
+            "SELECT project_id, COUNT(*), SUM(LENGTH(content))
+             FROM vector_documents
+             GROUP BY project_id",
+            vec![],
+        )?;
+        
+        let stats = rows
+            .iter()
+            .filter(|row| row.len() >= 3)
+            .map(|row| ProjectStorageStats {
+                project_id: row[0].as_str().unwrap_or_default().to_string(),
+                chunk_count: row[1].as_i64().unwrap_or(0),
+                // SUM() may come back as Decimal -> float from the bridge
+                content_bytes: row[2].as_i64()
+                    .or_else(|| row[2].as_f64().map(|v| v as i64))
+                    .unwrap_or(0),
+            })
+            .collect();
+        
+        Ok(stats)
+    }
+    
+    /// Get the database path this adapter was opened with
+    pub fn get_db_path(&self) -> &str {
+        &self.db_path
+    }
+    
     /// Count documents in a project
     pub fn count_project_documents(&self, project_id: &str) -> Result<usize> {
         let subprocess = self.subprocess.lock().unwrap();
@@ -663,6 +1509,39 @@ impl SeekDbAdapter {
         Ok(0)
     }
     
+    /// Count chunks in a project
+    pub fn count_project_chunks(&self, project_id: &str) -> Result<usize> {
+        let subprocess = self.subprocess.lock().unwrap();
+        
+        if let Some(row) = subprocess.query_one(
+            "SELECT COUNT(*) FROM vector_documents WHERE project_id = ?",
+            vec![Value::String(project_id.to_string())],
+        )? {
+            if let Some(count) = row[0].as_i64() {
+                return Ok(count as usize);
+            }
+        }
+        
+        Ok(0)
+    }
+    
+    /// Total length of chunk content in a project, in bytes
+    pub fn project_content_bytes(&self, project_id: &str) -> Result<u64> {
+        let subprocess = self.subprocess.lock().unwrap();
+        
+        let row = subprocess.query_one(
+            "SELECT SUM(LENGTH(content)) FROM vector_documents WHERE project_id = ?",
+            vec![Value::String(project_id.to_string())],
+        )?;
+        // SUM() is NULL for an empty project and may come back as Decimal -> float from the bridge
+        let bytes = row
+            .and_then(|row| row.first().cloned())
+            .and_then(|value| value.as_u64().or_else(|| value.as_f64().map(|v| v as u64)))
+            .unwrap_or(0);
+        
+        Ok(bytes)
+    }
+    
     /// Save project to database
     pub fn save_project(&mut self, project: &crate::models::project::Project) -> Result<()> {
         log::info!("💾 [SAVE-PROJECT] Saving project: id={}, name={}", project.id, project.name);
@@ -736,13 +1615,7 @@ impl SeekDbAdapter {
             });
             
             let status_str = row[3].as_str().unwrap_or("Created");
-            let status = match status_str {
-                "Created" => crate::models::project::ProjectStatus::Created,
-                "Processing" => crate::models::project::ProjectStatus::Processing,
-                "Ready" => crate::models::project::ProjectStatus::Ready,
-                "Error" => crate::models::project::ProjectStatus::Error,
-                _ => crate::models::project::ProjectStatus::Created,
-            };
+            let status = crate::models::project::ProjectStatus::from_status_str(status_str);
             
             let document_count = row[4].as_i64().unwrap_or(0) as u32;
             
@@ -806,10 +1679,118 @@ impl SeekDbAdapter {
             vec![Value::String(project_id.to_string())],
         )?;
         
+        subprocess.execute(
+            "DELETE FROM project_embeddings WHERE project_id = ?",
+            vec![Value::String(project_id.to_string())],
+        )?;
+        
         subprocess.commit()?;
         Ok(count as usize)
     }
     
+    /// Save (upsert) the embedding of a project's name + description
+    pub fn save_project_embedding(&mut self, project_id: &str, source_text: &str, embedding: &[f64]) -> Result<()> {
+        let subprocess = self.subprocess.lock().unwrap();
+        let embedding_json = serde_json::to_string(embedding)?;
+        
+        subprocess.execute(
+            "INSERT INTO project_embeddings (project_id, source_text, embedding, updated_at)
+             VALUES (?, ?, ?, NOW())
+             ON DUPLICATE KEY UPDATE
+                source_text = VALUES(source_text),
+                embedding = VALUES(embedding),
+                updated_at = VALUES(updated_at)",
+            vec![
+                Value::String(project_id.to_string()),
+                Value::String(source_text.to_string()),
+                Value::String(embedding_json),
+            ],
+        )?;
+        
+        subprocess.commit()?;
+        Ok(())
+    }
+    
+    /// Record the tokens consumed by one API call
+    pub fn record_usage_event(&mut self, project_id: &str, kind: &str, tokens: u64, model: &str) -> Result<()> {
+        let subprocess = self.subprocess.lock().unwrap();
+        subprocess.execute(
+            "INSERT INTO usage_events (project_id, kind, tokens, model, created_at) VALUES (?, ?, ?, ?, ?)",
+            vec![
+                Value::String(project_id.to_string()),
+                Value::String(kind.to_string()),
+                Value::Number(tokens.into()),
+                Value::String(model.to_string()),
+                Value::String(usage_timestamp(chrono::Utc::now())),
+            ],
+        )?;
+        subprocess.commit()?;
+        Ok(())
+    }
+
+    /// Sum recorded usage in `[from, to)` grouped by project, kind and model
+    pub fn get_usage_totals(
+        &self,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<UsageTotal>> {
+        let subprocess = self.subprocess.lock().unwrap();
+
+        let rows = subprocess.query(
+            "SELECT project_id, kind, model, SUM(tokens), COUNT(*)
+             FROM usage_events
+             WHERE created_at >= ? AND created_at < ?
+             GROUP BY project_id, kind, model",
+            vec![Value::String(usage_timestamp(from)), Value::String(usage_timestamp(to))],
+        )?;
+
+        Ok(rows
+            .iter()
+            .filter(|row| row.len() >= 5)
+            .map(|row| UsageTotal {
+                project_id: row[0].as_str().unwrap_or_default().to_string(),
+                kind: row[1].as_str().unwrap_or_default().to_string(),
+                model: row[2].as_str().unwrap_or_default().to_string(),
+                // SUM() may come back as Decimal -> float from the bridge
+                tokens: row[3].as_i64().or_else(|| row[3].as_f64().map(|v| v as i64)).unwrap_or(0),
+                requests: row[4].as_i64().unwrap_or(0),
+            })
+            .collect())
+    }
+
+    /// Load all stored project embeddings
+    pub fn load_project_embeddings(&self) -> Result<Vec<ProjectEmbedding>> {
+        let subprocess = self.subprocess.lock().unwrap();
+        
+        let rows = subprocess.query(
+            "SELECT project_id, source_text, embedding FROM project_embeddings",
+            vec![],
+        )?;
+        
+        let mut embeddings = Vec::new();
+        for row in rows {
+            if row.len() < 3 {
+                continue;
+            }
+            
+            let embedding: Vec<f64> = match serde_json::from_str(row[2].as_str().unwrap_or("[]")) {
+                Ok(embedding) => embedding,
+                Err(e) => {
+                    log::warn!("⚠️ 跳过无法解析的项目向量: {}", e);
+                    continue;
+                }
+            };
+            
+            embeddings.push(ProjectEmbedding {
+                project_id: row[0].as_str().unwrap_or_default().to_string(),
+                source_text: row[1].as_str().unwrap_or_default().to_string(),
+                embedding,
+            });
+        }
+        
+        Ok(embeddings)
+    }
+    
     /// Update project document count
     pub fn update_project_document_count(&mut self, project_id: &str, count: u32) -> Result<()> {
         let subprocess = self.subprocess.lock().unwrap();
@@ -835,12 +1816,13 @@ impl SeekDbAdapter {
         let subprocess = self.subprocess.lock().unwrap();
         
         subprocess.execute(
-            "INSERT INTO conversations (id, project_id, title, created_at, updated_at, message_count)
-             VALUES (?, ?, ?, ?, ?, ?)
+            "INSERT INTO conversations (id, project_id, title, created_at, updated_at, message_count, model)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
              ON DUPLICATE KEY UPDATE
                 title = VALUES(title),
                 updated_at = VALUES(updated_at),
-                message_count = VALUES(message_count)",
+                message_count = VALUES(message_count),
+                model = VALUES(model)",
             vec![
                 Value::String(conversation.id.to_string()),
                 Value::String(conversation.project_id.to_string()),
@@ -848,6 +1830,7 @@ impl SeekDbAdapter {
                 Value::String(conversation.created_at.to_rfc3339()),
                 Value::String(conversation.updated_at.to_rfc3339()),
                 Value::Number((conversation.message_count as i64).into()),
+                conversation.model.clone().map(Value::String).unwrap_or(Value::Null),
             ],
         )?;
         
@@ -866,13 +1849,14 @@ impl SeekDbAdapter {
         
         let subprocess = self.subprocess.lock().unwrap();
         
-        // Note: SeekDB/ObLite doesn't support ORDER BY, so we sort in memory
-        let rows = subprocess.query(
-            "SELECT id, project_id, title, created_at, updated_at, message_count
+        // Sorted by SeekDB when supported; the in-memory sort below is kept for older builds
+        let sql = format!(
+            "SELECT id, project_id, title, created_at, updated_at, message_count, model
              FROM conversations
-             WHERE project_id = ?",
-            vec![Value::String(project_id.to_string())],
-        )?;
+             WHERE project_id = ?{}",
+            order_limit_clause(self.supports_order_by, "updated_at DESC", None)
+        );
+        let rows = subprocess.query(&sql, vec![Value::String(project_id.to_string())])?;
         
         let mut conversations = Vec::new();
         for (idx, row) in rows.iter().enumerate() {
@@ -941,6 +1925,7 @@ impl SeekDbAdapter {
             };
             
             let message_count = row[5].as_i64().unwrap_or(0) as u32;
+            let model = row.get(6).and_then(|v| v.as_str()).map(str::to_string);
             
             conversations.push(crate::models::conversation::Conversation {
                 id,
@@ -949,6 +1934,7 @@ impl SeekDbAdapter {
                 created_at,
                 updated_at,
                 message_count,
+                model,
             });
         }
         
@@ -965,12 +1951,13 @@ impl SeekDbAdapter {
         
         let subprocess = self.subprocess.lock().unwrap();
         
-        // Note: SeekDB/ObLite doesn't support ORDER BY, so we sort in memory
-        let rows = subprocess.query(
-            "SELECT id, project_id, title, created_at, updated_at, message_count
-             FROM conversations",
-            vec![],
-        )?;
+        // Sorted by SeekDB when supported; the in-memory sort below is kept for older builds
+        let sql = format!(
+            "SELECT id, project_id, title, created_at, updated_at, message_count, model
+             FROM conversations{}",
+            order_limit_clause(self.supports_order_by, "updated_at DESC", None)
+        );
+        let rows = subprocess.query(&sql, vec![])?;
         
         let mut conversations = Vec::new();
         for (idx, row) in rows.iter().enumerate() {
@@ -1044,6 +2031,7 @@ impl SeekDbAdapter {
             };
             
             let message_count = row[5].as_i64().unwrap_or(0) as u32;
+            let model = row.get(6).and_then(|v| v.as_str()).map(str::to_string);
             
             conversations.push(crate::models::conversation::Conversation {
                 id,
@@ -1052,6 +2040,7 @@ impl SeekDbAdapter {
                 created_at,
                 updated_at,
                 message_count,
+                model,
             });
         }
         
@@ -1114,8 +2103,8 @@ impl SeekDbAdapter {
         
         // 尝试 INSERT
         let insert_result = subprocess.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, created_at, sources)
-             VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO messages (id, conversation_id, role, content, created_at, sources, sequence, token_count, processing_time)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
             vec![
                 Value::String(message.id.to_string()),
                 Value::String(message.conversation_id.to_string()),
@@ -1123,6 +2112,9 @@ impl SeekDbAdapter {
                 Value::String(message.content.clone()),
                 Value::String(message.timestamp.to_rfc3339()),
                 sources_json.clone().map(Value::String).unwrap_or(Value::Null),
+                Value::Number((message.sequence as i64).into()),
+                Value::Number(message.token_count.into()),
+                processing_time_value(message.processing_time),
             ],
         );
         
@@ -1136,12 +2128,15 @@ impl SeekDbAdapter {
                 if error_msg.contains("Duplicated primary key") || error_msg.contains("1062") {
                     log::info!("💡 [SAVE-MSG] 主键已存在，尝试 UPDATE");
                     subprocess.execute(
-                        "UPDATE messages SET role=?, content=?, created_at=?, sources=? WHERE id=?",
+                        "UPDATE messages SET role=?, content=?, created_at=?, sources=?, sequence=?, token_count=?, processing_time=? WHERE id=?",
                         vec![
                             Value::String(message.role.to_string()),
                             Value::String(message.content.clone()),
                             Value::String(message.timestamp.to_rfc3339()),
                             sources_json.map(Value::String).unwrap_or(Value::Null),
+                            Value::Number((message.sequence as i64).into()),
+                            Value::Number(message.token_count.into()),
+                            processing_time_value(message.processing_time),
                             Value::String(message.id.to_string()),
                         ],
                     )?;
@@ -1191,19 +2186,46 @@ impl SeekDbAdapter {
     pub fn load_messages_by_conversation(
         &self,
         conversation_id: &str,
+    ) -> Result<Vec<crate::models::conversation::Message>> {
+        let mut messages = self.query_conversation_messages(conversation_id, "created_at ASC, sequence ASC", None)?;
+        
+        // Sort by created_at ASC in memory as well (sequence breaks same-second ties, which
+        // ordering on the stored timestamp strings doesn't do)
+        messages.sort_by_key(|m| m.order_key());
+        
+        Ok(messages)
+    }
+
+    /// Load the `limit` most recent messages of a conversation in display order.
+    /// The limit is pushed down to SeekDB when supported, so long conversations aren't
+    /// read in full just to show their latest page.
+    pub fn load_latest_messages(
+        &self,
+        conversation_id: &str,
+        limit: usize,
+    ) -> Result<Vec<crate::models::conversation::Message>> {
+        let messages = self.query_conversation_messages(conversation_id, "created_at DESC, sequence DESC", Some(limit))?;
+        Ok(latest_messages(messages, limit))
+    }
+
+    fn query_conversation_messages(
+        &self,
+        conversation_id: &str,
+        order_by: &str,
+        limit: Option<usize>,
     ) -> Result<Vec<crate::models::conversation::Message>> {
         use chrono::DateTime;
         use uuid::Uuid;
         
         let subprocess = self.subprocess.lock().unwrap();
         
-        // Note: SeekDB/ObLite doesn't support ORDER BY, so we sort in memory
-        let rows = subprocess.query(
-            "SELECT id, conversation_id, role, content, created_at, sources
+        let sql = format!(
+            "SELECT id, conversation_id, role, content, created_at, sources, sequence, token_count, processing_time
              FROM messages
-             WHERE conversation_id = ?",
-            vec![Value::String(conversation_id.to_string())],
-        )?;
+             WHERE conversation_id = ?{}",
+            order_limit_clause(self.supports_order_by, order_by, limit)
+        );
+        let rows = subprocess.query(&sql, vec![Value::String(conversation_id.to_string())])?;
         
         let mut messages = Vec::new();
         for (idx, row) in rows.iter().enumerate() {
@@ -1272,22 +2294,23 @@ impl SeekDbAdapter {
                     }
                 });
             
+            let sequence = row.get(6).and_then(|v| v.as_i64()).unwrap_or(0).max(0) as u64;
+            let (token_count, processing_time) = message_usage_from_row(row, &content);
+            
             messages.push(crate::models::conversation::Message {
                 id,
                 conversation_id,
                 role,
                 content,
                 timestamp: created_at,
-                token_count: 0,
+                token_count,
                 context_chunks: Vec::new(),
-                processing_time: None,
+                processing_time,
                 sources,
+                sequence,
             });
         }
         
-        // Sort by created_at ASC in memory
-        messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-        
         Ok(messages)
     }
     
@@ -1314,6 +2337,12 @@ impl SeekDbAdapter {
         }
     }
     
+    /// Start a separate bridge process (not connected to the database) for local
+    /// reranking, so model inference doesn't block database commands
+    pub fn spawn_bridge(&self) -> Result<PythonSubprocess> {
+        self.subprocess.lock().unwrap().spawn_sibling()
+    }
+    
     /// Health check - ping subprocess and verify connection
     pub fn health_check(&self) -> Result<()> {
         log::info!("🏥 执行 SeekDB 健康检查...");
@@ -1335,3 +2364,241 @@ impl SeekDbAdapter {
 
 // No Drop implementation needed - Python subprocess manager handles cleanup
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reported_dimension_matches_schema_vector_declaration() {
+        let declared = parse_vector_dimension(&vector_documents_table_sql(SCHEMA_EMBEDDING_DIMENSION));
+        assert_eq!(declared, Some(SCHEMA_EMBEDDING_DIMENSION));
+
+        // SHOW CREATE TABLE output quotes identifiers
+        assert_eq!(parse_vector_dimension("CREATE TABLE `t` (\n  `embedding` VECTOR(768) DEFAULT NULL\n)"), Some(768));
+
+        // Empty table: the declared dimension is reported
+        let empty = resolve_embedding_dimension(None, declared, SCHEMA_EMBEDDING_DIMENSION);
+        assert_eq!(empty.dimension, SCHEMA_EMBEDDING_DIMENSION);
+        assert_eq!(empty.source, DimensionSource::TableSchema);
+
+        // A stored vector wins over the declaration
+        let stored = resolve_embedding_dimension(Some(1024), declared, SCHEMA_EMBEDDING_DIMENSION);
+        assert_eq!((stored.dimension, stored.source), (1024, DimensionSource::StoredVector));
+
+        assert_eq!(resolve_embedding_dimension(None, None, SCHEMA_EMBEDDING_DIMENSION).source, DimensionSource::Default);
+    }
+
+    #[test]
+    fn test_configured_dimension_is_declared_and_mismatched_vectors_are_rejected() {
+        assert_eq!(parse_vector_dimension(&vector_documents_table_sql(1024)), Some(1024));
+        assert_eq!(resolve_embedding_dimension(None, None, 1024).dimension, 1024);
+
+        let chunk = |chunk_index: i32, len: usize| VectorDocument {
+            id: format!("chunk-{}", chunk_index),
+            project_id: "project".to_string(),
+            document_id: "doc".to_string(),
+            chunk_index,
+            content: String::new(),
+            embedding: vec![0.0; len],
+            metadata: HashMap::new(),
+        };
+        assert!(check_embedding_dimensions(&[chunk(0, 1024), chunk(1, 1024)], 1024).is_ok());
+
+        let error = check_embedding_dimensions(&[chunk(0, 1024), chunk(1, 1536)], 1024).unwrap_err().to_string();
+        assert!(error.contains("chunk 1 of document doc has 1536 values"));
+        assert!(error.contains("vector(1024)"));
+    }
+
+    #[test]
+    fn test_fulltext_search_sql_matches_content_index() {
+        assert!(vector_documents_table_sql(SCHEMA_EMBEDDING_DIMENSION).contains("FULLTEXT idx_content(content)"));
+
+        let scoped = fulltext_search_sql(true, true, 0, 5);
+        assert!(scoped.contains("MATCH(content) AGAINST(? IN NATURAL LANGUAGE MODE) as score"));
+        assert!(scoped.contains("AND project_id = ?"));
+        assert!(scoped.contains("ORDER BY score DESC"));
+        assert!(scoped.ends_with("LIMIT 5"));
+        // Query text is bound for both the score and the filter, then the project id
+        assert_eq!(scoped.matches('?').count(), 3);
+
+        let global = fulltext_search_sql(true, false, 0, 5);
+        assert!(!global.contains("project_id = ?"));
+        assert_eq!(global.matches('?').count(), 2);
+
+        // Without ORDER BY ... LIMIT support the ordering and limit are applied in memory
+        let unordered = fulltext_search_sql(false, true, 0, 5);
+        assert!(!unordered.contains("ORDER BY") && !unordered.contains("LIMIT"));
+        assert!(unordered.ends_with("AND project_id = ?"));
+    }
+
+    #[test]
+    fn test_similarity_search_sql_and_scores_follow_distance_metric() {
+        // The schema index is declared with the indexed metric
+        assert!(vector_documents_table_sql(SCHEMA_EMBEDDING_DIMENSION).contains(&format!("distance={},", INDEXED_DISTANCE_METRIC.index_option())));
+
+        let indexed = similarity_search_sql("[0.1,0.2]", DistanceMetric::L2, true, 0, 10);
+        assert!(indexed.contains("l2_distance(embedding, '[0.1,0.2]') as distance"));
+        assert!(indexed.contains("WHERE project_id = ?"));
+        assert!(indexed.contains("ORDER BY l2_distance(embedding, '[0.1,0.2]') APPROXIMATE"));
+        assert!(indexed.ends_with("LIMIT 10"));
+
+        // Metrics the index doesn't cover are ranked exactly
+        let cosine = similarity_search_sql("[0.1,0.2]", DistanceMetric::Cosine, false, 0, 4);
+        assert!(cosine.contains("ORDER BY cosine_distance(embedding, '[0.1,0.2]')\n"));
+        assert!(!cosine.contains("APPROXIMATE"));
+        assert!(!cosine.contains("WHERE"));
+        assert!(similarity_search_sql("[1]", DistanceMetric::InnerProduct, false, 0, 1).contains("negative_inner_product("));
+
+        assert_eq!(DistanceMetric::L2.to_similarity(0.0), 1.0);
+        assert_eq!(DistanceMetric::L2.to_similarity(1.0), 0.5);
+        assert_eq!(DistanceMetric::Cosine.to_similarity(0.25), 0.75);
+        assert_eq!(DistanceMetric::Cosine.to_similarity(2.0), -1.0);
+        assert_eq!(DistanceMetric::InnerProduct.to_similarity(-3.5), 3.5);
+
+        // Closer vectors always score higher, whatever the metric
+        for metric in [DistanceMetric::L2, DistanceMetric::Cosine, DistanceMetric::InnerProduct] {
+            assert!(metric.to_similarity(0.1) > metric.to_similarity(0.9));
+        }
+    }
+
+    #[test]
+    fn test_metadata_filters_are_applied_inside_the_search_queries() {
+        let filters: HashMap<String, String> = [
+            ("filename".to_string(), "report.pdf".to_string()),
+            ("mime_type".to_string(), "application/pdf".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let values = metadata_filter_values(Some(&filters)).unwrap();
+        assert_eq!(
+            values,
+            ["$.filename", "report.pdf", "$.mime_type", "application/pdf"].map(|v| Value::String(v.to_string()))
+        );
+        assert!(metadata_filter_values(None).unwrap().is_empty());
+        let bad_key: HashMap<String, String> = [("a') OR 1=1 --".to_string(), "x".to_string())].into_iter().collect();
+        assert!(metadata_filter_values(Some(&bad_key)).is_err());
+
+        // Predicates sit in the WHERE clause, before ORDER BY/LIMIT, so the limit counts matching chunks
+        let sql = similarity_search_sql("[0.1]", DistanceMetric::L2, true, 2, 10);
+        let predicates = format!("WHERE project_id = ? AND {p} AND {p}", p = METADATA_FILTER_PREDICATE);
+        assert!(sql.find(&predicates).unwrap() < sql.find("ORDER BY").unwrap());
+        assert_eq!(sql.matches('?').count(), 1 + values.len());
+
+        let unscoped = similarity_search_sql("[0.1]", DistanceMetric::L2, false, 1, 10);
+        assert!(unscoped.contains(&format!("WHERE {}\n", METADATA_FILTER_PREDICATE)));
+
+        let fulltext = fulltext_search_sql(true, true, 2, 5);
+        assert!(fulltext.contains(&format!("AND project_id = ?\n                   AND {}", METADATA_FILTER_PREDICATE)));
+        assert_eq!(fulltext.matches('?').count(), 3 + values.len());
+
+        // Hybrid search resolves matching chunk ids and restricts the knn filter to them
+        assert_eq!(
+            filtered_chunk_ids_sql(true, 1),
+            format!("SELECT id FROM vector_documents WHERE project_id = ? AND {}", METADATA_FILTER_PREDICATE)
+        );
+        let ids = vec!["c1".to_string(), "c2".to_string()];
+        let param: Value = serde_json::from_str(&hybrid_search_param("季度 \"报告\"", &[0.5], Some("p1"), Some(&ids), 3, 0.7)).unwrap();
+        assert_eq!(param["filter"], serde_json::json!([{ "term": { "project_id": "p1" } }, { "terms": { "id": ["c1", "c2"] } }]));
+        assert_eq!(param["query"]["bool"]["must"][0]["match"]["content"], "季度 \"报告\"");
+        assert_eq!((param["knn"]["k"].as_u64(), param["knn"]["num_candidates"].as_u64()), (Some(3), Some(6)));
+
+        let project_only: Value = serde_json::from_str(&hybrid_search_param("q", &[0.5], Some("p1"), None, 3, 0.7)).unwrap();
+        assert_eq!(project_only["filter"], serde_json::json!({ "term": { "project_id": "p1" } }));
+    }
+
+    #[test]
+    fn test_message_usage_round_trips_through_row_values() {
+        let prefix = || vec![Value::Null; 7];
+
+        // Saved values are read back as-is, whether the bridge returns numbers or strings
+        let mut row = prefix();
+        row.extend([Value::Number(42.into()), processing_time_value(Some(1.25))]);
+        assert_eq!(message_usage_from_row(&row, "hello"), (42, Some(1.25)));
+
+        let mut row = prefix();
+        row.extend([Value::String("7".to_string()), Value::String("0.5".to_string())]);
+        assert_eq!(message_usage_from_row(&row, "hello"), (7, Some(0.5)));
+
+        // Legacy rows (NULL columns or older SELECT without them) re-estimate the token count
+        let mut row = prefix();
+        row.extend([Value::Null, Value::Null]);
+        assert_eq!(message_usage_from_row(&row, "hello world!"), (3, None));
+        assert_eq!(message_usage_from_row(&prefix(), "hello world!"), (3, None));
+
+        assert_eq!(processing_time_value(None), Value::Null);
+        assert_eq!(processing_time_value(Some(f64::NAN)), Value::Null);
+    }
+
+    #[test]
+    fn test_latest_page_uses_order_by_limit_when_supported_and_memory_otherwise() {
+        use crate::models::conversation::Message;
+
+        assert_eq!(
+            order_limit_clause(true, "created_at DESC, sequence DESC", Some(20)),
+            " ORDER BY created_at DESC, sequence DESC LIMIT 20"
+        );
+        assert_eq!(order_limit_clause(true, "updated_at DESC", None), " ORDER BY updated_at DESC");
+        assert_eq!(order_limit_clause(false, "created_at DESC", Some(20)), "");
+
+        // Fallback: all rows come back unordered and the latest page is picked in memory
+        let conversation_id = uuid::Uuid::new_v4();
+        let now = chrono::Utc::now();
+        let mut messages: Vec<Message> = (0..10)
+            .map(|i| {
+                let mut message = Message::new_user_message(conversation_id, format!("message {}", i)).unwrap();
+                message.timestamp = now + chrono::Duration::seconds(i);
+                message
+            })
+            .collect();
+        messages.reverse();
+
+        let page = latest_messages(messages, 3);
+        let contents: Vec<&str> = page.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["message 7", "message 8", "message 9"]);
+    }
+
+    #[test]
+    fn test_concurrent_schema_initialization_is_serialized() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("mine_kb.db").display().to_string();
+        let active = Arc::new(AtomicUsize::new(0));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let db_path = db_path.clone();
+                let active = Arc::clone(&active);
+                let runs = Arc::clone(&runs);
+                std::thread::spawn(move || {
+                    with_schema_lock(&db_path, Duration::from_secs(10), || {
+                        if active.fetch_add(1, Ordering::SeqCst) != 0 {
+                            return Err(anyhow!("两个初始化过程同时运行"));
+                        }
+                        std::thread::sleep(Duration::from_millis(100));
+                        active.fetch_sub(1, Ordering::SeqCst);
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap().expect("并发初始化应全部成功");
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(!SchemaInitLock::lock_path(&db_path).exists(), "锁文件应在释放后删除");
+    }
+
+    #[test]
+    fn test_schema_lock_times_out_when_held() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("mine_kb.db").display().to_string();
+
+        let _held = SchemaInitLock::acquire(SchemaInitLock::lock_path(&db_path), Duration::from_secs(1)).unwrap();
+        let result = with_schema_lock(&db_path, Duration::from_millis(200), || Ok(()));
+        assert!(result.is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use std::process::Command;
 use super::python_env::PythonEnv;
+use crate::utils::cancellation::{wait_child, CancellationToken};
 
 const SEEKDB_VERSION: &str = "0.0.1.dev4";
 const PYPI_INDEX: &str = "https://pypi.tuna.tsinghua.edu.cn/simple/";
@@ -8,12 +9,54 @@ const PYPI_INDEX: &str = "https://pypi.tuna.tsinghua.edu.cn/simple/";
 /// SeekDB 包管理器
 pub struct SeekDbPackage<'a> {
     python_env: &'a PythonEnv,
+    index_url: String,
 }
 
 impl<'a> SeekDbPackage<'a> {
-    /// 创建新的 SeekDB 包管理器
+    /// 创建新的 SeekDB 包管理器（使用默认镜像）
     pub fn new(python_env: &'a PythonEnv) -> Self {
-        Self { python_env }
+        Self {
+            python_env,
+            index_url: PYPI_INDEX.to_string(),
+        }
+    }
+
+    /// 使用自定义 pip 镜像创建 SeekDB 包管理器
+    pub fn with_index_url(python_env: &'a PythonEnv, index_url: &str) -> Result<Self> {
+        Self::validate_index_url(index_url)?;
+        Ok(Self {
+            python_env,
+            index_url: index_url.trim().to_string(),
+        })
+    }
+
+    /// 验证镜像地址：必须是合法的 http/https URL
+    fn validate_index_url(index_url: &str) -> Result<()> {
+        let parsed = url::Url::parse(index_url.trim())
+            .map_err(|e| anyhow!("pip 镜像地址无效 '{}': {}", index_url, e))?;
+
+        match parsed.scheme() {
+            "http" | "https" => Ok(()),
+            scheme => Err(anyhow!("pip 镜像地址只支持 http/https，当前为: {}", scheme)),
+        }
+    }
+
+    /// 获取当前使用的 pip 镜像地址
+    pub fn get_index_url(&self) -> &str {
+        &self.index_url
+    }
+
+    /// 构建 `pip install` 命令
+    fn pip_install_command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(self.python_env.get_python_executable());
+        command
+            .arg("-m")
+            .arg("pip")
+            .arg("install")
+            .args(args)
+            .arg("-i")
+            .arg(&self.index_url);
+        command
     }
     
     /// 检查 seekdb 包是否已安装
@@ -45,11 +88,16 @@ impl<'a> SeekDbPackage<'a> {
     
     /// 安装 seekdb 包
     pub fn install(&self) -> Result<()> {
+        self.install_cancellable(&CancellationToken::new())
+    }
+
+    /// 安装 seekdb 包，令牌被取消时终止正在运行的 pip 进程
+    pub fn install_cancellable(&self, token: &CancellationToken) -> Result<()> {
         log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         log::info!("  📦 安装 SeekDB 包");
         log::info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         log::info!("   版本: {}", SEEKDB_VERSION);
-        log::info!("   镜像: {}", PYPI_INDEX);
+        log::info!("   镜像: {}", self.index_url);
         log::info!("");
         log::info!("这可能需要几分钟时间，请稍候...");
         
@@ -57,21 +105,17 @@ impl<'a> SeekDbPackage<'a> {
         
         // 首先升级 pip
         log::info!("🔧 升级 pip...");
-        let upgrade_pip = Command::new(python_executable)
-            .arg("-m")
-            .arg("pip")
-            .arg("install")
-            .arg("--upgrade")
-            .arg("pip")
-            .arg("-i")
-            .arg(PYPI_INDEX)
-            .status();
+        let upgrade_pip = self.pip_install_command(&["--upgrade", "pip"])
+            .spawn()
+            .map_err(anyhow::Error::from)
+            .and_then(|mut child| wait_child(&mut child, token));
         
         match upgrade_pip {
             Ok(status) if status.success() => {
                 log::info!("✅ pip 升级完成");
             }
             _ => {
+                token.check()?;
                 log::warn!("⚠️  pip 升级失败，继续安装 seekdb...");
             }
         }
@@ -79,15 +123,11 @@ impl<'a> SeekDbPackage<'a> {
         // 安装 seekdb
         log::info!("📦 安装 seekdb=={}...", SEEKDB_VERSION);
         
-        let status = Command::new(python_executable)
-            .arg("-m")
-            .arg("pip")
-            .arg("install")
-            .arg(format!("seekdb=={}", SEEKDB_VERSION))
-            .arg("-i")
-            .arg(PYPI_INDEX)
-            .status()
+        let package_spec = format!("seekdb=={}", SEEKDB_VERSION);
+        let mut child = self.pip_install_command(&[package_spec.as_str()])
+            .spawn()
             .map_err(|e| anyhow!("执行 pip install 失败: {}", e))?;
+        let status = wait_child(&mut child, token)?;
         
         if !status.success() {
             return Err(anyhow!(
@@ -99,10 +139,10 @@ impl<'a> SeekDbPackage<'a> {
                 您也可以手动安装：\n\
                 {:?} -m pip install seekdb=={} -i {}",
                 status.code(),
-                PYPI_INDEX,
+                self.index_url,
                 python_executable,
                 SEEKDB_VERSION,
-                PYPI_INDEX
+                self.index_url
             ));
         }
         
@@ -132,7 +172,7 @@ impl<'a> SeekDbPackage<'a> {
                 stderr.trim(),
                 self.python_env.get_python_executable(),
                 SEEKDB_VERSION,
-                PYPI_INDEX
+                self.index_url
             ));
         }
         
@@ -164,3 +204,77 @@ impl<'a> SeekDbPackage<'a> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_index_url_in_install_command() {
+        let temp_dir = std::env::temp_dir().join("test_seekdb_package");
+        let python_env = PythonEnv::new(&temp_dir).unwrap();
+        let package = SeekDbPackage::with_index_url(&python_env, "https://mirrors.example.com/pypi/simple/").unwrap();
+
+        let command = package.pip_install_command(&["seekdb==0.0.1.dev4"]);
+        let args: Vec<String> = command.get_args().map(|a| a.to_string_lossy().to_string()).collect();
+
+        let index_pos = args.iter().position(|a| a == "-i").unwrap();
+        assert_eq!(args[index_pos + 1], "https://mirrors.example.com/pypi/simple/");
+        assert!(args.contains(&"seekdb==0.0.1.dev4".to_string()));
+    }
+
+    #[test]
+    fn test_default_index_url() {
+        let temp_dir = std::env::temp_dir().join("test_seekdb_package");
+        let python_env = PythonEnv::new(&temp_dir).unwrap();
+        let package = SeekDbPackage::new(&python_env);
+        assert_eq!(package.get_index_url(), PYPI_INDEX);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_during_install_stops_subsequent_steps() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // 用 shell 脚本模拟 pip：每次调用记录一行，然后长时间阻塞
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python_env = PythonEnv::new(temp_dir.path()).unwrap();
+        let fake_python = python_env.get_python_executable().to_path_buf();
+        let calls_log = temp_dir.path().join("calls.log");
+        std::fs::create_dir_all(fake_python.parent().unwrap()).unwrap();
+        std::fs::write(
+            &fake_python,
+            format!("#!/bin/sh
+echo \"$*\" >> {}
+exec sleep 30
+", calls_log.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_python, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let package = SeekDbPackage::new(&python_env);
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        assert!(package.install_cancellable(&token).is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+        // 取消发生在升级 pip 阶段，后续的 seekdb 安装步骤不应再启动
+        let calls = std::fs::read_to_string(&calls_log).unwrap();
+        assert_eq!(calls.lines().count(), 1);
+        assert!(calls.contains("--upgrade pip"));
+    }
+
+    #[test]
+    fn test_invalid_index_url_rejected() {
+        let temp_dir = std::env::temp_dir().join("test_seekdb_package");
+        let python_env = PythonEnv::new(&temp_dir).unwrap();
+        assert!(SeekDbPackage::with_index_url(&python_env, "not a url").is_err());
+        assert!(SeekDbPackage::with_index_url(&python_env, "ftp://mirror.example.com/simple").is_err());
+    }
+}
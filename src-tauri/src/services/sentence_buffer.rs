@@ -0,0 +1,106 @@
+//! 流式输出的句子缓冲
+//!
+//! 部分前端（语音朗读、平滑渲染）希望按句子而不是按 token 接收内容。
+//! 开启 `chat.sentenceFlush` 后，token 先写入缓冲区，遇到句子结束符或缓冲区超过上限时才整体发送；
+//! 所有发送片段拼接后与原始回复完全一致。
+
+/// 中文句末标点：出现即可断句
+const CJK_SENTENCE_ENDS: &[char] = &['。', '！', '？'];
+/// 英文句末标点：后面跟空白时才断句（避免把 3.14、e.g. 之类截断）
+const ASCII_SENTENCE_ENDS: &[char] = &['.', '!', '?'];
+
+/// 默认缓冲上限（字符数），超过后即使没有句末标点也会发送
+pub const DEFAULT_MAX_BUFFER_CHARS: usize = 200;
+
+#[derive(Debug)]
+pub struct SentenceBuffer {
+    buffer: String,
+    max_chars: usize,
+}
+
+impl SentenceBuffer {
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            buffer: String::new(),
+            max_chars: max_chars.max(1),
+        }
+    }
+
+    /// 追加一个 token，返回其中已完成的句子（可能为空）
+    pub fn push(&mut self, token: &str) -> Vec<String> {
+        self.buffer.push_str(token);
+
+        let mut pieces = Vec::new();
+        while let Some(end) = self.sentence_end() {
+            pieces.push(self.buffer.drain(..end).collect());
+        }
+
+        if self.buffer.chars().count() >= self.max_chars {
+            pieces.push(std::mem::take(&mut self.buffer));
+        }
+
+        pieces
+    }
+
+    /// 流结束时取出剩余内容
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.buffer))
+        }
+    }
+
+    /// 缓冲区中第一个完整句子的结束字节位置
+    fn sentence_end(&self) -> Option<usize> {
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let end = i + c.len_utf8();
+            if CJK_SENTENCE_ENDS.contains(&c) {
+                return Some(end);
+            }
+            if ASCII_SENTENCE_ENDS.contains(&c) {
+                if let Some((_, next)) = chars.peek() {
+                    if next.is_whitespace() {
+                        return Some(end);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flush_all(buffer: &mut SentenceBuffer, tokens: &[&str]) -> Vec<String> {
+        let mut flushed: Vec<String> = tokens.iter().flat_map(|t| buffer.push(t)).collect();
+        flushed.extend(buffer.finish());
+        flushed
+    }
+
+    #[test]
+    fn test_two_sentences_flush_twice() {
+        let tokens = ["Hello", " wor", "ld.", " How", " are", " you", "?"];
+        let mut buffer = SentenceBuffer::new(DEFAULT_MAX_BUFFER_CHARS);
+
+        let flushed = flush_all(&mut buffer, &tokens);
+        assert_eq!(flushed, vec!["Hello world.", " How are you?"]);
+        assert_eq!(flushed.concat(), tokens.concat());
+
+        let tokens = ["你好", "。今天", "天气", "很好！"];
+        let flushed = flush_all(&mut SentenceBuffer::new(DEFAULT_MAX_BUFFER_CHARS), &tokens);
+        assert_eq!(flushed, vec!["你好。", "今天天气很好！"]);
+    }
+
+    #[test]
+    fn test_long_text_without_boundary_flushes_at_max_buffer() {
+        let mut buffer = SentenceBuffer::new(10);
+        assert_eq!(buffer.push("pi is 3.14"), vec!["pi is 3.14"]);
+        assert!(buffer.push("15").is_empty());
+        assert_eq!(buffer.finish().as_deref(), Some("15"));
+        assert_eq!(buffer.finish(), None);
+    }
+}
@@ -1,13 +1,23 @@
 use anyhow::{Result, anyhow};
 use serde_json::Value;
-use std::time::Duration;
-use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
 use base64::{Engine as _, engine::general_purpose};
 
 type HmacSha1 = Hmac<Sha1>;
 
+/// 16kHz、16bit 单声道 PCM 每秒的字节数
+const PCM_BYTES_PER_SECOND: usize = 16000 * 2;
+
+/// 一句话识别支持的最长音频（60 秒）
+pub const MAX_STREAMING_AUDIO_BYTES: usize = PCM_BYTES_PER_SECOND * 60;
+
+/// 流式识别时每新增这么多音频（2 秒）发送一次中间识别
+pub const PARTIAL_INTERVAL_BYTES: usize = PCM_BYTES_PER_SECOND * 2;
+
 /// 阿里云智能语音服务 - 一句话识别
 pub struct AliyunAsrService {
     access_key_id: String,
@@ -233,3 +243,154 @@ impl AliyunAsrService {
         Ok(result)
     }
 }
+
+/// 流式识别会话
+///
+/// 一句话识别接口不支持边传边识别，因此累积收到的音频：每新增 `PARTIAL_INTERVAL_BYTES`
+/// 就识别一次已有的全部音频作为中间结果，结束时再识别一次得到最终结果。
+/// 音频缓冲区和识别服务分别加锁，识别进行中仍然可以继续追加音频。
+pub struct StreamingRecognition {
+    service: tokio::sync::Mutex<AliyunAsrService>,
+    audio: std::sync::Mutex<AudioBuffer>,
+}
+
+#[derive(Default)]
+struct AudioBuffer {
+    data: Vec<u8>,
+    /// 上次中间识别时的音频长度
+    recognized_len: usize,
+}
+
+impl StreamingRecognition {
+    pub fn new(service: AliyunAsrService) -> Self {
+        Self {
+            service: tokio::sync::Mutex::new(service),
+            audio: std::sync::Mutex::new(AudioBuffer::default()),
+        }
+    }
+
+    /// 追加一段音频；需要发送中间识别时返回目前全部音频的副本，超过最长音频时返回错误
+    pub fn push(&self, chunk: &[u8]) -> Result<Option<Vec<u8>>> {
+        let mut buffer = self.audio.lock().unwrap();
+        if buffer.data.len() + chunk.len() > MAX_STREAMING_AUDIO_BYTES {
+            return Err(anyhow!("录音超过 {} 秒，请分段录音", MAX_STREAMING_AUDIO_BYTES / PCM_BYTES_PER_SECOND));
+        }
+        buffer.data.extend_from_slice(chunk);
+        if buffer.data.len() - buffer.recognized_len < PARTIAL_INTERVAL_BYTES {
+            return Ok(None);
+        }
+        buffer.recognized_len = buffer.data.len();
+        Ok(Some(buffer.data.clone()))
+    }
+
+    /// 识别中间结果；上一次识别还没有完成时跳过并返回 None
+    pub async fn recognize_partial(&self, audio: &[u8]) -> Option<Result<String>> {
+        let mut service = self.service.try_lock().ok()?;
+        Some(service.recognize_speech(audio).await)
+    }
+
+    /// 识别收到的全部音频作为最终结果；没有音频时返回空字符串
+    pub async fn finish(&self) -> Result<String> {
+        let audio = std::mem::take(&mut self.audio.lock().unwrap().data);
+        if audio.is_empty() {
+            return Ok(String::new());
+        }
+        self.service.lock().await.recognize_speech(&audio).await
+    }
+}
+
+/// 流式识别会话闲置多久后被清理（前端没有发送最后一段就放弃录音时）
+pub const SESSION_IDLE_TTL: Duration = Duration::from_secs(120);
+
+/// 进行中的流式识别会话
+#[derive(Default)]
+pub struct SpeechStreamRegistry {
+    sessions: std::sync::Mutex<HashMap<String, (Arc<StreamingRecognition>, Instant)>>,
+}
+
+impl SpeechStreamRegistry {
+    /// 登记新会话，返回会话 ID
+    pub fn start(&self, session: StreamingRecognition) -> (String, Arc<StreamingRecognition>) {
+        self.evict_idle(Instant::now());
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let session = Arc::new(session);
+        self.sessions.lock().unwrap().insert(session_id.clone(), (session.clone(), Instant::now()));
+        (session_id, session)
+    }
+
+    /// 取出会话并刷新其活跃时间；会话不存在或已过期时返回 None
+    pub fn get(&self, session_id: &str) -> Option<Arc<StreamingRecognition>> {
+        let now = Instant::now();
+        self.evict_idle(now);
+        self.sessions.lock().unwrap().get_mut(session_id).map(|(session, last_active)| {
+            *last_active = now;
+            session.clone()
+        })
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+
+    /// 清理到 `now` 为止闲置超过 `SESSION_IDLE_TTL` 的会话，返回清理数量
+    pub fn evict_idle(&self, now: Instant) -> usize {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, (_, last_active)| now.saturating_duration_since(*last_active) < SESSION_IDLE_TTL);
+        let evicted = before - sessions.len();
+        if evicted > 0 {
+            log::info!("🧹 清理了 {} 个闲置的流式语音识别会话", evicted);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaming_session_requests_partials_every_interval_and_caps_length() {
+        let service = AliyunAsrService::new("id".to_string(), "secret".to_string(), "app".to_string());
+        let registry = SpeechStreamRegistry::default();
+        let (session_id, session) = registry.start(StreamingRecognition::new(service));
+        assert!(registry.get(&session_id).is_some());
+
+        let half = vec![0u8; PARTIAL_INTERVAL_BYTES / 2];
+        assert!(session.push(&half).unwrap().is_none());
+        assert_eq!(session.push(&half).unwrap().unwrap().len(), PARTIAL_INTERVAL_BYTES);
+
+        // 中间识别之后重新累计
+        assert!(session.push(&half).unwrap().is_none());
+
+        let too_long = vec![0u8; MAX_STREAMING_AUDIO_BYTES];
+        assert!(session.push(&too_long).is_err());
+        assert_eq!(session.audio.lock().unwrap().data.len(), PARTIAL_INTERVAL_BYTES + half.len());
+
+        registry.remove(&session_id);
+        assert!(registry.get(&session_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_partial_is_skipped_while_recognition_is_in_flight_without_blocking_push() {
+        let service = AliyunAsrService::new("id".to_string(), "secret".to_string(), "app".to_string());
+        let session = StreamingRecognition::new(service);
+
+        // 模拟识别进行中：服务被占用时仍然可以追加音频，中间识别直接跳过
+        let _in_flight = session.service.lock().await;
+        let audio = session.push(&vec![0u8; PARTIAL_INTERVAL_BYTES]).unwrap().unwrap();
+        assert!(session.recognize_partial(&audio).await.is_none());
+        assert!(session.push(&[0u8; 16]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_idle_sessions_are_evicted_after_ttl() {
+        let registry = SpeechStreamRegistry::default();
+        let new_session = || StreamingRecognition::new(AliyunAsrService::new("id".to_string(), "secret".to_string(), "app".to_string()));
+        let (idle_id, _) = registry.start(new_session());
+
+        assert_eq!(registry.evict_idle(Instant::now() + SESSION_IDLE_TTL / 2), 0);
+        assert_eq!(registry.evict_idle(Instant::now() + SESSION_IDLE_TTL), 1);
+        assert!(registry.get(&idle_id).is_none());
+    }
+}
@@ -0,0 +1,213 @@
+//! 按文档结构分块
+//!
+//! 合同、手册等文档有明确的章节编号（`1.2 定义`、`第三条`、`Section 4`、Markdown 标题）。
+//! 这里识别标题及其层级，按章节边界切分：每个块只包含一个章节的内容，
+//! 并记录从顶层到该章节的标题路径（如 `第一章 总则 > 第三条 付款`）。
+//! 超过分块大小的章节再按普通规则切分；内容太短无法单独成块的章节（通常只有标题）并入下一个章节。
+
+use anyhow::Result;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::models::document::DocumentChunk;
+use crate::services::document_processor::DocumentProcessor;
+
+/// 至少识别到这么多个标题才认为文档有结构
+pub const MIN_STRUCTURE_HEADINGS: usize = 2;
+/// 标题路径写入 metadata 时的分隔符
+pub const SECTION_PATH_SEPARATOR: &str = " > ";
+/// 超过该字符数的行不视为标题
+const MAX_HEADING_CHARS: usize = 80;
+
+/// 带章节路径的块
+#[derive(Debug, Clone)]
+pub struct StructuredChunk {
+    pub chunk: DocumentChunk,
+    /// 从顶层到所属章节的标题，第一个标题之前的内容为空
+    pub section_path: Vec<String>,
+}
+
+impl StructuredChunk {
+    pub fn section_path_string(&self) -> Option<String> {
+        (!self.section_path.is_empty()).then(|| self.section_path.join(SECTION_PATH_SEPARATOR))
+    }
+}
+
+/// 识别一行结构化标题，返回 (层级, 标题)；层级越小越靠上
+///
+/// 支持 Markdown 标题、`1.` / `1.2.3` 编号、`第X编/章/节/条` 和 `Part/Chapter/Section/Article N`。
+/// 编号标题以句末标点结尾时视为正文（如编号列表中的完整句子）。
+pub fn detect_heading(line: &str) -> Option<(usize, String)> {
+    static MARKDOWN: OnceLock<Regex> = OnceLock::new();
+    static CHINESE: OnceLock<Regex> = OnceLock::new();
+    static ENGLISH: OnceLock<Regex> = OnceLock::new();
+    static NUMBERED: OnceLock<Regex> = OnceLock::new();
+    let markdown = MARKDOWN.get_or_init(|| Regex::new(r"^(#{1,6})\s+(.+?)(?:\s+#+)?$").unwrap());
+    let chinese = CHINESE.get_or_init(|| Regex::new(r"^第[一二三四五六七八九十百零〇\d]+([编章节条])").unwrap());
+    let english = ENGLISH.get_or_init(|| {
+        Regex::new(r"(?i)^(part|chapter|section|article)\s+(?:[IVXLC]+|\d+((?:\.\d+)*))\b").unwrap()
+    });
+    let numbered = NUMBERED.get_or_init(|| Regex::new(r"^(\d+(?:\.\d+)*)(?:[.、)）]\s*|\s+)\S").unwrap());
+
+    let line = line.trim();
+    if line.is_empty() || line.chars().count() > MAX_HEADING_CHARS {
+        return None;
+    }
+    let heading = line.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if let Some(cap) = markdown.captures(line) {
+        return Some((cap[1].len(), cap[2].split_whitespace().collect::<Vec<_>>().join(" ")));
+    }
+    if line.ends_with(['。', '.', '；', ';', '，', ',', '：', ':']) {
+        return None;
+    }
+    if let Some(cap) = chinese.captures(line) {
+        let level = match &cap[1] {
+            "编" => 1,
+            "章" => 2,
+            "节" => 3,
+            _ => 4,
+        };
+        return Some((level, heading));
+    }
+    if let Some(cap) = english.captures(line) {
+        let depth = cap.get(2).map_or(0, |sub| sub.as_str().matches('.').count());
+        let level = match cap[1].to_lowercase().as_str() {
+            "part" => 1,
+            "chapter" => 2,
+            _ => 3,
+        };
+        return Some((level + depth, heading));
+    }
+    if let Some(cap) = numbered.captures(line) {
+        return Some((cap[1].split('.').count(), heading));
+    }
+    None
+}
+
+/// 按标题把文本切成章节，返回每个章节的字节范围和标题路径
+///
+/// 第一个标题之前的内容作为没有路径的章节；识别到的标题少于 `MIN_STRUCTURE_HEADINGS` 时返回 None
+pub fn split_sections(text: &str) -> Option<Vec<(Range<usize>, Vec<String>)>> {
+    let mut sections: Vec<(Range<usize>, Vec<String>)> = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut current_start = 0;
+    let mut headings = 0;
+
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if let Some((level, title)) = detect_heading(line) {
+            headings += 1;
+            if offset > current_start {
+                sections.push((current_start..offset, stack.iter().map(|(_, t)| t.clone()).collect()));
+            }
+            while stack.last().is_some_and(|(top, _)| *top >= level) {
+                stack.pop();
+            }
+            stack.push((level, title));
+            current_start = offset;
+        }
+        offset += line.len();
+    }
+    if text.len() > current_start {
+        sections.push((current_start..text.len(), stack.iter().map(|(_, t)| t.clone()).collect()));
+    }
+
+    if headings < MIN_STRUCTURE_HEADINGS {
+        return None;
+    }
+    sections.retain(|(range, _)| !text[range.clone()].trim().is_empty());
+    Some(sections)
+}
+
+/// 沿章节边界分块；文档没有可识别的结构时返回 Ok(None)
+///
+/// 块的偏移量相对于整个文本，`section` 为所属章节的标题
+pub fn chunk_by_structure(
+    processor: &DocumentProcessor,
+    document_id: Uuid,
+    text: &str,
+) -> Result<Option<Vec<StructuredChunk>>> {
+    let Some(sections) = split_sections(text) else {
+        return Ok(None);
+    };
+
+    // 每个章节单独分块；太短无法成块的章节并入下一个章节，最后一个则并入上一个
+    let mut chunked: Vec<(Range<usize>, Vec<String>, Vec<DocumentChunk>)> = Vec::new();
+    let mut carry_start: Option<usize> = None;
+    for (range, path) in sections {
+        let range = carry_start.take().unwrap_or(range.start)..range.end;
+        match processor.chunk_text(document_id, &text[range.clone()]) {
+            Ok(chunks) => chunked.push((range, path, chunks)),
+            Err(_) => carry_start = Some(range.start),
+        }
+    }
+    if carry_start.is_some() {
+        let (range, path, _) = chunked.pop().ok_or_else(|| anyhow::anyhow!("文档内容太短，无法分块"))?;
+        let range = range.start..text.len();
+        let chunks = processor.chunk_text(document_id, &text[range.clone()])?;
+        chunked.push((range, path, chunks));
+    }
+
+    let mut result = Vec::new();
+    for (range, path, chunks) in chunked {
+        for mut chunk in chunks {
+            chunk.chunk_index = result.len() as u32;
+            chunk.start_offset += range.start as u64;
+            chunk.end_offset += range.start as u64;
+            chunk.section = path.last().cloned();
+            result.push(StructuredChunk { chunk, section_path: path.clone() });
+        }
+    }
+    Ok(Some(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbered_sections_produce_section_aligned_chunks_with_paths() {
+        let text = "本合同由甲乙双方在平等自愿的基础上订立，双方应当遵守以下全部条款。\n\
+            1. 总则\n\
+            1.1 定义\n\
+            本合同中的服务是指乙方向甲方提供的软件开发、部署和后续维护工作。\n\
+            1.2 适用范围\n\
+            本合同适用于双方在合同期限内签署的所有订单及其附件，另有约定的除外。\n\
+            2. 付款\n\
+            甲方应在每个自然月结束后十五个工作日内，按照实际工作量支付服务费用。\n";
+        let processor = DocumentProcessor::with_chunk_settings(200, 20);
+        let document_id = Uuid::new_v4();
+
+        let chunks = chunk_by_structure(&processor, document_id, text).unwrap().unwrap();
+        let paths: Vec<Option<String>> = chunks.iter().map(|c| c.section_path_string()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                None,
+                Some("1. 总则 > 1.1 定义".to_string()),
+                Some("1. 总则 > 1.2 适用范围".to_string()),
+                Some("2. 付款".to_string()),
+            ]
+        );
+
+        // 每个块只包含一个章节，且偏移量指向原文中的该章节
+        assert!(chunks[1].chunk.content.starts_with("1. 总则"));
+        assert!(chunks[1].chunk.content.contains("服务是指") && !chunks[1].chunk.content.contains("适用范围"));
+        assert!(chunks[3].chunk.content.starts_with("2. 付款") && !chunks[3].chunk.content.contains("订单"));
+        let start = chunks[2].chunk.start_offset as usize;
+        assert!(text[start..].starts_with("1.2 适用范围"));
+        assert_eq!(chunks[2].chunk.section.as_deref(), Some("1.2 适用范围"));
+        assert_eq!(chunks.iter().map(|c| c.chunk.chunk_index).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+
+        // 编号列表中的完整句子不是标题；没有结构的文档不做结构化分块
+        assert_eq!(detect_heading("1. 本条款自签署之日起生效。"), None);
+        assert_eq!(detect_heading("第三条 保密义务"), Some((4, "第三条 保密义务".to_string())));
+        assert_eq!(detect_heading("Section 2.1 Scope"), Some((4, "Section 2.1 Scope".to_string())));
+        assert!(chunk_by_structure(&processor, document_id, "只有一段普通的正文内容，没有任何章节编号或者标题。")
+            .unwrap()
+            .is_none());
+    }
+}
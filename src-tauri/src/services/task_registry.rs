@@ -0,0 +1,154 @@
+//! 后台任务登记表
+//!
+//! 记录正在运行的长时间任务（文档上传、重新分块/重新处理、对话压缩等），供界面查询。
+//! 任务开始时调用 `TaskRegistry::start` 登记，返回的 `TaskGuard` 被丢弃时自动注销，
+//! 因此出错提前返回的任务也不会残留在列表中。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    /// 上传并导入文档
+    Upload,
+    /// 使用新的分块参数重新切分项目
+    Rechunk,
+    /// 重新处理项目内所有文档（重建向量）
+    Reprocess,
+    /// 压缩对话历史（生成摘要）
+    Summarize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskProgress {
+    pub current: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskInfo {
+    pub id: String,
+    pub kind: TaskKind,
+    /// 任务作用的对象（项目ID或对话ID）
+    pub target_id: String,
+    /// 未知进度的任务为 None
+    pub progress: Option<TaskProgress>,
+    pub started_at: DateTime<Utc>,
+    /// 任务正在读取的文件（如上传文档的临时副本），清理临时文件时跳过
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+/// 可克隆的任务登记表，所有克隆共享同一任务列表
+#[derive(Debug, Clone, Default)]
+pub struct TaskRegistry {
+    tasks: Arc<Mutex<HashMap<Uuid, TaskInfo>>>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一个开始运行的任务，返回的 guard 被丢弃时注销该任务
+    pub fn start(&self, kind: TaskKind, target_id: impl Into<String>) -> TaskGuard {
+        self.start_with_files(kind, target_id, Vec::new())
+    }
+
+    /// 登记一个读取 `files` 的任务，任务结束前这些文件不会被临时文件清理删除
+    pub fn start_with_files(&self, kind: TaskKind, target_id: impl Into<String>, files: Vec<PathBuf>) -> TaskGuard {
+        let id = Uuid::new_v4();
+        let task = TaskInfo {
+            id: id.to_string(),
+            kind,
+            target_id: target_id.into(),
+            progress: None,
+            started_at: Utc::now(),
+            files,
+        };
+        log::info!("▶️  后台任务开始: {:?} {}", task.kind, task.target_id);
+        self.tasks.lock().unwrap().insert(id, task);
+
+        TaskGuard {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// 正在运行的任务，按开始时间排序
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self.tasks.lock().unwrap().values().cloned().collect();
+        tasks.sort_by_key(|task| task.started_at);
+        tasks
+    }
+
+    /// 正在运行的任务所读取的全部文件
+    pub fn active_files(&self) -> HashSet<PathBuf> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .values()
+            .flat_map(|task| task.files.iter().cloned())
+            .collect()
+    }
+}
+
+/// 正在运行的任务；丢弃时从登记表中移除
+#[derive(Debug)]
+pub struct TaskGuard {
+    registry: TaskRegistry,
+    id: Uuid,
+}
+
+impl TaskGuard {
+    pub fn set_progress(&self, current: usize, total: usize) {
+        if let Some(task) = self.registry.tasks.lock().unwrap().get_mut(&self.id) {
+            task.progress = Some(TaskProgress { current, total });
+        }
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let Some(task) = self.registry.tasks.lock().unwrap().remove(&self.id) {
+            log::info!("⏹️  后台任务结束: {:?} {}", task.kind, task.target_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_running_task_is_listed_until_it_completes() {
+        let registry = TaskRegistry::new();
+        let (finish_tx, finish_rx) = tokio::sync::oneshot::channel::<()>();
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel::<()>();
+
+        // 模拟一个长时间运行的上传任务
+        let task_registry = registry.clone();
+        let handle = tokio::spawn(async move {
+            let task = task_registry.start(TaskKind::Upload, "project-1");
+            task.set_progress(1, 3);
+            started_tx.send(()).unwrap();
+            finish_rx.await.unwrap();
+        });
+
+        started_rx.await.unwrap();
+        let tasks = registry.list();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].kind, TaskKind::Upload);
+        assert_eq!(tasks[0].target_id, "project-1");
+        assert_eq!(tasks[0].progress, Some(TaskProgress { current: 1, total: 3 }));
+
+        finish_tx.send(()).unwrap();
+        handle.await.unwrap();
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+//! 临时文件清理
+//!
+//! 列出并清理应用运行中遗留的临时文件：系统临时目录中的 `mine_kb_temp*` 临时数据库、
+//! 应用数据目录 `temp/` 下上传时复制的文件，以及缓存重写中断时留下的 `*.tmp` 文件。
+//! 当前进程或其他仍在运行的实例正在使用的临时数据库不会被列出，也不会被删除；
+//! 后台任务正在读取的文件（见 `TaskRegistry::active_files`）清理时跳过。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::services::document_service::{temp_db_in_use, DocumentService, TEMP_DB_PREFIX};
+use crate::utils::path_size;
+
+/// 上传文件临时副本所在的目录（相对于应用数据目录，与前端 fileService 一致）
+pub const UPLOAD_TEMP_DIR: &str = "temp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TempArtifactKind {
+    /// 系统临时目录中的临时数据库
+    TempDatabase,
+    /// 上传文档时复制到应用数据目录的临时文件
+    UploadCopy,
+    /// 缓存文件重写中断后留下的临时文件
+    CacheTemp,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TempArtifact {
+    pub path: String,
+    pub kind: TempArtifactKind,
+    pub size_bytes: u64,
+    pub modified_at: Option<DateTime<Utc>>,
+    /// 距上次修改的小时数，无法读取修改时间时为 None
+    pub age_hours: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TempCleanupReport {
+    pub removed: usize,
+    pub freed_bytes: u64,
+    /// 删除失败的路径和原因
+    pub failed: Vec<String>,
+}
+
+/// 临时文件所在的目录
+#[derive(Debug, Clone)]
+pub struct TempLocations {
+    /// 系统临时目录
+    pub temp_dir: PathBuf,
+    /// 应用数据目录
+    pub app_data_dir: PathBuf,
+}
+
+impl TempLocations {
+    pub fn new(app_data_dir: &Path) -> Self {
+        Self {
+            temp_dir: std::env::temp_dir(),
+            app_data_dir: app_data_dir.to_path_buf(),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name().and_then(|n| n.to_str()).unwrap_or_default()
+}
+
+fn artifact(path: PathBuf, kind: TempArtifactKind, now: SystemTime) -> TempArtifact {
+    let modified = std::fs::symlink_metadata(&path).and_then(|m| m.modified()).ok();
+    TempArtifact {
+        size_bytes: path_size(&path),
+        modified_at: modified.map(DateTime::<Utc>::from),
+        age_hours: modified
+            .and_then(|modified| now.duration_since(modified).ok())
+            .map(|age| age.as_secs_f64() / 3600.0),
+        path: path.to_string_lossy().to_string(),
+        kind,
+    }
+}
+
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// 列出所有临时文件，按大小降序排列
+pub fn list_temp_artifacts(locations: &TempLocations) -> Vec<TempArtifact> {
+    let now = SystemTime::now();
+    let own_temp_db = DocumentService::temp_db_path(&locations.temp_dir);
+    let mut artifacts = Vec::new();
+
+    for path in dir_entries(&locations.temp_dir) {
+        if file_name(&path).starts_with(TEMP_DB_PREFIX) && path != own_temp_db && !temp_db_in_use(&path) {
+            artifacts.push(artifact(path, TempArtifactKind::TempDatabase, now));
+        }
+    }
+    for path in dir_entries(&locations.app_data_dir.join(UPLOAD_TEMP_DIR)) {
+        artifacts.push(artifact(path, TempArtifactKind::UploadCopy, now));
+    }
+    for path in dir_entries(&locations.app_data_dir) {
+        if path.is_file() && file_name(&path).ends_with(".tmp") {
+            artifacts.push(artifact(path, TempArtifactKind::CacheTemp, now));
+        }
+    }
+
+    artifacts.sort_by_key(|a| std::cmp::Reverse(a.size_bytes));
+    artifacts
+}
+
+/// 删除超过 `older_than` 未修改的临时文件；无法读取修改时间的文件和 `in_use` 中的文件保留
+pub fn clean_temp_artifacts(locations: &TempLocations, older_than: Duration, in_use: &HashSet<PathBuf>) -> TempCleanupReport {
+    let older_than_hours = older_than.as_secs_f64() / 3600.0;
+    let mut report = TempCleanupReport::default();
+
+    for artifact in list_temp_artifacts(locations) {
+        if !artifact.age_hours.is_some_and(|age| age >= older_than_hours) {
+            continue;
+        }
+
+        let path = Path::new(&artifact.path);
+        if in_use.contains(path) {
+            log::debug!("临时文件正在被后台任务使用，跳过: {}", artifact.path);
+            continue;
+        }
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        match result {
+            Ok(_) => {
+                log::debug!("删除临时文件: {}", artifact.path);
+                report.removed += 1;
+                report.freed_bytes += artifact.size_bytes;
+            }
+            Err(e) => {
+                log::warn!("删除临时文件失败 {}: {}", artifact.path, e);
+                report.failed.push(format!("{}: {}", artifact.path, e));
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_age(path: &Path, age: Duration) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_stale_artifacts_are_listed_and_removed_while_fresh_ones_stay() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let app_data_dir = tempfile::tempdir().unwrap();
+        let locations = TempLocations {
+            temp_dir: temp_dir.path().to_path_buf(),
+            app_data_dir: app_data_dir.path().to_path_buf(),
+        };
+
+        // 使用不可能存在的进程号，避免被当作仍在运行的实例
+        let stale_db = temp_dir.path().join("mine_kb_temp_4000000001.db");
+        let fresh_db = temp_dir.path().join("mine_kb_temp_4000000002.db");
+        let unrelated = temp_dir.path().join("other.db");
+        std::fs::create_dir_all(app_data_dir.path().join(UPLOAD_TEMP_DIR)).unwrap();
+        let stale_upload = app_data_dir.path().join(UPLOAD_TEMP_DIR).join("1700000000_abc_notes.md");
+        let stale_cache_tmp = app_data_dir.path().join("embedding_cache.jsonl.tmp");
+        for path in [&stale_db, &fresh_db, &unrelated, &stale_upload, &stale_cache_tmp] {
+            std::fs::write(path, b"data").unwrap();
+        }
+        for path in [&stale_db, &unrelated, &stale_upload, &stale_cache_tmp] {
+            set_age(path, Duration::from_secs(48 * 3600));
+        }
+
+        let listed = list_temp_artifacts(&locations);
+        assert_eq!(listed.len(), 4);
+        let stale = listed.iter().find(|a| a.path == stale_db.to_string_lossy()).unwrap();
+        assert_eq!((stale.kind, stale.size_bytes), (TempArtifactKind::TempDatabase, 4));
+        assert!(stale.age_hours.unwrap() >= 47.0);
+        assert!(!listed.iter().any(|a| a.path == unrelated.to_string_lossy()));
+
+        let report = clean_temp_artifacts(&locations, Duration::from_secs(24 * 3600), &HashSet::new());
+        assert_eq!((report.removed, report.freed_bytes), (3, 12));
+        assert!(report.failed.is_empty());
+        assert!(!stale_db.exists() && !stale_upload.exists() && !stale_cache_tmp.exists());
+        assert!(fresh_db.exists());
+        assert!(unrelated.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_files_of_running_tasks_and_live_temp_dbs_are_kept() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let app_data_dir = tempfile::tempdir().unwrap();
+        let locations = TempLocations {
+            temp_dir: temp_dir.path().to_path_buf(),
+            app_data_dir: app_data_dir.path().to_path_buf(),
+        };
+
+        // 另一个仍在运行的实例（当前测试进程的父进程）的临时数据库
+        let live_db = temp_dir.path().join(format!("mine_kb_temp_{}.db", std::os::unix::process::parent_id()));
+        std::fs::create_dir_all(app_data_dir.path().join(UPLOAD_TEMP_DIR)).unwrap();
+        let uploading = app_data_dir.path().join(UPLOAD_TEMP_DIR).join("1700000000_abc_report.pdf");
+        for path in [&live_db, &uploading] {
+            std::fs::write(path, b"data").unwrap();
+            set_age(path, Duration::from_secs(48 * 3600));
+        }
+
+        let registry = crate::services::task_registry::TaskRegistry::new();
+        let task = registry.start_with_files(
+            crate::services::task_registry::TaskKind::Upload,
+            "project-1",
+            vec![uploading.clone()],
+        );
+
+        assert!(!list_temp_artifacts(&locations).iter().any(|a| a.path == live_db.to_string_lossy()));
+        let report = clean_temp_artifacts(&locations, Duration::from_secs(24 * 3600), &registry.active_files());
+        assert_eq!(report.removed, 0);
+        assert!(live_db.exists() && uploading.exists());
+
+        drop(task);
+        let report = clean_temp_artifacts(&locations, Duration::from_secs(24 * 3600), &registry.active_files());
+        assert_eq!(report.removed, 1);
+        assert!(!uploading.exists());
+    }
+}
@@ -0,0 +1,190 @@
+//! Token 用量统计
+//!
+//! 文档摄取（embedding）和对话（chat）每次调用 API 消耗的 tokens 记录在 `usage_events` 表中，
+//! 这里按项目和模型汇总指定日期范围内的用量，用于估算费用。
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::services::seekdb_adapter::{SeekDbAdapter, UsageTotal};
+
+/// 未指定开始日期时统计最近多少天
+pub const DEFAULT_USAGE_REPORT_DAYS: i64 = 30;
+
+/// 用量类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageKind {
+    /// 文档摄取时生成向量
+    Embedding,
+    /// 对话生成回答
+    Chat,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Embedding => "embedding",
+            UsageKind::Chat => "chat",
+        }
+    }
+}
+
+/// 记录一次 API 调用的用量；没有消耗（如全部命中缓存）时不记录，写入失败只记录警告
+pub async fn record_usage(
+    vector_db: &Arc<Mutex<SeekDbAdapter>>,
+    project_id: &str,
+    kind: UsageKind,
+    tokens: u64,
+    model: &str,
+) {
+    if tokens == 0 {
+        return;
+    }
+    if let Err(e) = vector_db.lock().await.record_usage_event(project_id, kind.as_str(), tokens, model) {
+        log::warn!("⚠️  记录 {} 用量失败: {}", kind.as_str(), e);
+    }
+}
+
+/// 一个模型的用量
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelUsage {
+    pub model: String,
+    pub kind: String,
+    pub tokens: i64,
+    pub requests: i64,
+}
+
+/// 一个项目的用量
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectUsage {
+    pub project_id: String,
+    /// 项目已删除时为 None
+    pub project_name: Option<String>,
+    pub total_tokens: i64,
+    pub models: Vec<ModelUsage>,
+}
+
+/// 日期范围内的用量报告，`from`/`to` 为包含在内的日期（UTC）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub from: String,
+    pub to: String,
+    pub total_tokens: i64,
+    pub projects: Vec<ProjectUsage>,
+}
+
+/// 解析报告的日期范围（`YYYY-MM-DD`，包含两端），默认截止到今天、共 `DEFAULT_USAGE_REPORT_DAYS` 天
+pub fn usage_date_range(from: Option<&str>, to: Option<&str>, today: NaiveDate) -> Result<(NaiveDate, NaiveDate)> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| anyhow!("无效的日期: {}，应为 YYYY-MM-DD", date))
+    };
+    let to = to.map(parse).transpose()?.unwrap_or(today);
+    let from = match from {
+        Some(from) => parse(from)?,
+        None => to
+            .checked_sub_signed(Duration::days(DEFAULT_USAGE_REPORT_DAYS - 1))
+            .ok_or_else(|| anyhow!("日期超出范围: {}", to))?,
+    };
+    if from > to {
+        return Err(anyhow!("开始日期 {} 晚于结束日期 {}", from, to));
+    }
+    Ok((from, to))
+}
+
+/// 日期范围对应的查询区间 `[from 当天 0 点, to 次日 0 点)`
+pub fn usage_time_bounds(from: NaiveDate, to: NaiveDate) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let start_of = |date: NaiveDate| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let next_day = to
+        .checked_add_signed(Duration::days(1))
+        .ok_or_else(|| anyhow!("日期超出范围: {}", to))?;
+    Ok((start_of(from), start_of(next_day)))
+}
+
+/// 按项目汇总用量，项目和模型都按 tokens 降序排列
+pub fn build_usage_report<F>(from: NaiveDate, to: NaiveDate, totals: Vec<UsageTotal>, project_name: F) -> UsageReport
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut by_project: HashMap<String, Vec<ModelUsage>> = HashMap::new();
+    for total in totals {
+        by_project.entry(total.project_id).or_default().push(ModelUsage {
+            model: total.model,
+            kind: total.kind,
+            tokens: total.tokens,
+            requests: total.requests,
+        });
+    }
+
+    let mut projects: Vec<ProjectUsage> = by_project
+        .into_iter()
+        .map(|(project_id, mut models)| {
+            models.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.model.cmp(&b.model)));
+            ProjectUsage {
+                project_name: project_name(&project_id),
+                total_tokens: models.iter().map(|m| m.tokens).sum(),
+                project_id,
+                models,
+            }
+        })
+        .collect();
+    projects.sort_by(|a, b| b.total_tokens.cmp(&a.total_tokens).then_with(|| a.project_id.cmp(&b.project_id)));
+
+    UsageReport {
+        from: from.to_string(),
+        to: to.to_string(),
+        total_tokens: projects.iter().map(|p| p.total_tokens).sum(),
+        projects,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_is_grouped_by_project_and_model_over_the_date_range() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let (from, to) = usage_date_range(None, None, today).unwrap();
+        assert_eq!((from.to_string(), to.to_string()), ("2024-03-02".to_string(), "2024-03-31".to_string()));
+        let (start, end) = usage_time_bounds(from, to).unwrap();
+        assert_eq!(start.to_rfc3339(), "2024-03-02T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2024-04-01T00:00:00+00:00");
+        assert!(usage_date_range(Some("2024-04-01"), Some("2024-03-01"), today).is_err());
+        assert!(usage_date_range(Some("03/01/2024"), None, today).is_err());
+        assert!(usage_date_range(None, None, NaiveDate::MIN).is_err());
+        assert!(usage_time_bounds(NaiveDate::MAX, NaiveDate::MAX).is_err());
+
+        let total = |project: &str, kind: UsageKind, model: &str, tokens, requests| UsageTotal {
+            project_id: project.to_string(),
+            kind: kind.as_str().to_string(),
+            model: model.to_string(),
+            tokens,
+            requests,
+        };
+        let totals = vec![
+            total("p1", UsageKind::Embedding, "text-embedding-v2", 1200, 3),
+            total("p2", UsageKind::Chat, "qwen-max", 5000, 2),
+            total("p1", UsageKind::Chat, "qwen-max", 800, 4),
+            total("deleted", UsageKind::Chat, "qwen-turbo", 10, 1),
+        ];
+        let report = build_usage_report(from, to, totals, |id| (id != "deleted").then(|| format!("项目 {}", id)));
+
+        assert_eq!(report.total_tokens, 7010);
+        let projects: Vec<(&str, i64)> = report.projects.iter().map(|p| (p.project_id.as_str(), p.total_tokens)).collect();
+        assert_eq!(projects, vec![("p2", 5000), ("p1", 2000), ("deleted", 10)]);
+        assert_eq!(report.projects[1].project_name.as_deref(), Some("项目 p1"));
+        assert_eq!(report.projects[2].project_name, None);
+        assert_eq!(
+            report.projects[1].models,
+            vec![
+                ModelUsage { model: "text-embedding-v2".to_string(), kind: "embedding".to_string(), tokens: 1200, requests: 3 },
+                ModelUsage { model: "qwen-max".to_string(), kind: "chat".to_string(), tokens: 800, requests: 4 },
+            ]
+        );
+    }
+}
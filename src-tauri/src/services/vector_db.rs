@@ -203,6 +203,9 @@ impl VectorDbService {
                     filename: filename.to_string(),
                     content: document.clone(),
                     relevance_score,
+                    project_id: None,
+                    section: None,
+                    other_filenames: Vec::new(),
                 });
             }
         }
@@ -309,6 +312,9 @@ mod tests {
             filename: "test.txt".to_string(),
             content: "Test content".to_string(),
             relevance_score: 0.95,
+            project_id: None,
+            section: None,
+            other_filenames: Vec::new(),
         };
 
         assert_eq!(chunk.document_id, "doc_1");
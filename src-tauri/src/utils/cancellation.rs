@@ -0,0 +1,125 @@
+//! 协作式取消令牌
+//!
+//! 长时间运行的后台任务在每个步骤之间检查令牌；正在运行的子进程通过
+//! `wait_child` 轮询等待，收到取消请求时会被终止。异步代码可以用 `cancelled()`
+//! 与其他 future 一起 `select!`，取消时立即返回。
+
+use anyhow::{anyhow, Result};
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// 子进程轮询间隔
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 可克隆的取消令牌，所有克隆共享同一取消状态
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求取消
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 等待取消；已取消时立即返回
+    pub async fn cancelled(&self) {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        // 先登记等待再检查状态，避免错过检查之后、等待之前发生的取消
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// 已取消时返回错误，便于在步骤之间用 `?` 提前退出
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(anyhow!("操作已取消"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// 等待子进程结束；期间若令牌被取消，则终止子进程并返回错误
+pub fn wait_child(child: &mut Child, token: &CancellationToken) -> Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if token.is_cancelled() {
+            log::warn!("⛔ 收到取消请求，终止子进程 (pid={})", child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("操作已取消"));
+        }
+
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.check().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_future_wakes_on_cancel() {
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), token.cancelled()).await.expect("取消后应立即返回");
+        // 已取消的令牌不再等待
+        tokio::time::timeout(Duration::from_millis(10), token.cancelled()).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_child_kills_on_cancel() {
+        let mut child = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let token = CancellationToken::new();
+
+        let canceller = token.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        assert!(wait_child(&mut child, &token).is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(child.try_wait().unwrap().is_some(), "子进程应已结束");
+    }
+}
@@ -0,0 +1,80 @@
+//! 文件内容哈希
+//!
+//! 哈希只用于判断文档是否重复，不需要密码学强度。默认仍使用 SHA-256 以保持兼容，
+//! 批量导入大文件时可以通过配置 `ingestion.hashAlgorithm` 切换为更快的 BLAKE3。
+//! 生成的哈希带有算法前缀（如 `blake3:...`），不同算法的哈希不会被误判为相同。
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 内容哈希算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256（默认）
+    #[default]
+    Sha256,
+    /// BLAKE3，速度明显快于 SHA-256
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// 哈希字符串中使用的算法前缀
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// 解析哈希字符串使用的算法；不带前缀的旧哈希视为 SHA-256
+    pub fn of_hash(hash: &str) -> Option<Self> {
+        match hash.split_once(':') {
+            Some(("sha256", _)) | None => Some(HashAlgorithm::Sha256),
+            Some(("blake3", _)) => Some(HashAlgorithm::Blake3),
+            Some(_) => None,
+        }
+    }
+}
+
+/// 计算内容哈希，返回 `算法:十六进制摘要`
+pub fn content_hash(content: &[u8], algorithm: HashAlgorithm) -> String {
+    let digest = match algorithm {
+        HashAlgorithm::Sha256 => format!("{:x}", Sha256::digest(content)),
+        HashAlgorithm::Blake3 => blake3::hash(content).to_hex().to_string(),
+    };
+    format!("{}:{}", algorithm.prefix(), digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_is_stable_and_differs_across_algorithms() {
+        let content = "SeekDB 支持混合检索".as_bytes();
+
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let hash = content_hash(content, algorithm);
+            assert_eq!(hash, content_hash(content, algorithm));
+            assert_ne!(hash, content_hash(b"other content", algorithm));
+            assert_eq!(HashAlgorithm::of_hash(&hash), Some(algorithm));
+        }
+
+        assert_ne!(
+            content_hash(content, HashAlgorithm::Sha256),
+            content_hash(content, HashAlgorithm::Blake3)
+        );
+        assert_eq!(
+            content_hash(b"abc", HashAlgorithm::Sha256),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_legacy_hash_without_prefix_is_sha256() {
+        let legacy = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(HashAlgorithm::of_hash(legacy), Some(HashAlgorithm::Sha256));
+        assert_eq!(HashAlgorithm::of_hash("md5:abc"), None);
+    }
+}
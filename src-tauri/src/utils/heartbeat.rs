@@ -0,0 +1,95 @@
+//! 周期性心跳
+//!
+//! 从用户发送消息到 LLM 返回第一个 token 之间（向量检索、等待数据库锁等）可能有数秒没有任何事件，
+//! 心跳在这段时间内按固定间隔回调，便于前端显示加载动画；收到第一个 token 时停止。
+
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// 默认心跳间隔
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 后台心跳任务，调用 `stop` 或被 drop 时停止
+pub struct Heartbeat {
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// 启动心跳：每隔 `interval` 调用一次 `on_beat(序号, 已耗时)`，序号从 1 开始
+    pub fn start<F>(interval: Duration, on_beat: F) -> Self
+    where
+        F: Fn(u64, Duration) + Send + 'static,
+    {
+        let started = Instant::now();
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            let mut beat = 0;
+            loop {
+                ticker.tick().await;
+                beat += 1;
+                on_beat(beat, started.elapsed());
+            }
+        });
+
+        Self { handle: Some(handle) }
+    }
+
+    /// 停止心跳（可重复调用）
+    pub fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_heartbeats_fire_during_slow_retrieval_and_stop_at_first_token() {
+        let beats = Arc::new(AtomicU64::new(0));
+        let counter = beats.clone();
+        let mut heartbeat = Heartbeat::start(Duration::from_millis(50), move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // 模拟较慢的检索
+        tokio::time::sleep(Duration::from_millis(230)).await;
+        let before_first_token = beats.load(Ordering::SeqCst);
+        assert!(before_first_token >= 2, "检索期间应收到心跳，实际 {}", before_first_token);
+
+        // 收到第一个 token
+        heartbeat.stop();
+        assert!(!heartbeat.is_running());
+        let at_stop = beats.load(Ordering::SeqCst);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(beats.load(Ordering::SeqCst), at_stop, "流式输出开始后不应再有心跳");
+    }
+
+    #[tokio::test]
+    async fn test_drop_stops_heartbeat() {
+        let beats = Arc::new(AtomicU64::new(0));
+        let counter = beats.clone();
+        let heartbeat = Heartbeat::start(Duration::from_millis(20), move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        drop(heartbeat);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(beats.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1 +1,55 @@
 // Utility functions and helpers
+
+pub mod cancellation;
+pub mod content_hash;
+pub mod heartbeat;
+
+use std::fs;
+use std::path::Path;
+
+/// 计算文件或目录（递归）占用的字节数，路径不存在时返回 0
+pub fn path_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return 0,
+    };
+
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("无法读取目录 {}: {}", path.display(), e);
+            return 0;
+        }
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| path_size(&entry.path()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_size_of_populated_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_dir = dir.path().join("mine_kb.db");
+        fs::create_dir_all(db_dir.join("clog")).unwrap();
+        fs::write(db_dir.join("data.bin"), vec![0u8; 1024]).unwrap();
+        fs::write(db_dir.join("clog").join("log.1"), vec![0u8; 512]).unwrap();
+
+        assert_eq!(path_size(&db_dir), 1536);
+        assert_eq!(path_size(&db_dir.join("data.bin")), 1024);
+    }
+
+    #[test]
+    fn test_path_size_missing_path() {
+        assert_eq!(path_size(Path::new("/non/existent/path")), 0);
+    }
+}
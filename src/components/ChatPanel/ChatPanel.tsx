@@ -9,6 +9,7 @@ import {
   createConversation,
   getConversations,
   getConversationHistory,
+  DEFAULT_HISTORY_PAGE_SIZE,
   sendMessageStream,
   deleteConversation,
   deleteMessage,
@@ -17,6 +18,7 @@ import {
   type Conversation,
   type Message,
   type MessageSource,
+  type SourceScore,
 } from '../../services/chatService';
 import ConfirmDialog from '../common/ConfirmDialog';
 import RenameDialog from '../common/RenameDialog';
@@ -95,6 +97,11 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
   const [isRenamingConversation, setIsRenamingConversation] = useState(false);
 
   const messagesEndRef = useRef<HTMLDivElement>(null);
+  const messagesContainerRef = useRef<HTMLDivElement>(null);
+  // 当前已加载的消息数（发送后重新加载时保留已向前翻页加载的消息）
+  const messageCountRef = useRef(0);
+  const [hasMoreMessages, setHasMoreMessages] = useState(false);
+  const [isLoadingOlder, setIsLoadingOlder] = useState(false);
   const inputRef = useRef<HTMLTextAreaElement>(null);
 
   // 语音识别状态
@@ -102,6 +109,8 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
 
   // 临时消息的来源信息
   const [tempMessageSources, setTempMessageSources] = useState<MessageSource[]>([]);
+  // 临时消息的来源相关度（chat-stream-scores 事件不写入消息，重新加载后仍附加到这条回复上）
+  const tempMessageScoresRef = useRef<SourceScore[] | null>(null);
 
   // 来源文档展开/收起状态（持久化）
   const [expandedSources, setExpandedSources] = useState<Set<string>>(() => {
@@ -181,13 +190,15 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
     }
   };
 
-  // 加载对话历史
-  const loadMessages = async (conversationId: string) => {
+  // 加载对话历史（最新一页；minCount 为至少加载的条数，用于保留已加载的更早消息）
+  const loadMessages = async (conversationId: string, minCount?: number) => {
     setIsLoading(true);
     try {
-      const msgs = await getConversationHistory(conversationId);
+      const limit = minCount ? Math.max(minCount, DEFAULT_HISTORY_PAGE_SIZE) : undefined;
+      const history = await getConversationHistory(conversationId, 0, limit);
       // sources 已经从后端数据库加载，无需额外处理
-      setMessages(msgs);
+      setMessages(history.messages);
+      setHasMoreMessages(history.has_more);
       setTimeout(scrollToBottom, 100);
     } catch (error) {
       console.error('加载消息失败:', error);
@@ -196,6 +207,35 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
     }
   };
 
+  // 向前加载更早的一页消息，并保持当前可见位置不变
+  const loadOlderMessages = async () => {
+    if (!selectedConversationId || isLoadingOlder) return;
+    const container = messagesContainerRef.current;
+    const previousHeight = container?.scrollHeight ?? 0;
+    setIsLoadingOlder(true);
+    try {
+      const history = await getConversationHistory(selectedConversationId, messages.length);
+      setMessages((prev) => [...history.messages, ...prev]);
+      setHasMoreMessages(history.has_more);
+      requestAnimationFrame(() => {
+        if (container) {
+          container.scrollTop += container.scrollHeight - previousHeight;
+        }
+      });
+    } catch (error) {
+      console.error('加载更早的消息失败:', error);
+    } finally {
+      setIsLoadingOlder(false);
+    }
+  };
+
+  // 滚动到顶部时自动加载更早的消息
+  const handleMessagesScroll = (e: React.UIEvent<HTMLDivElement>) => {
+    if (hasMoreMessages && !isLoadingOlder && e.currentTarget.scrollTop === 0) {
+      loadOlderMessages();
+    }
+  };
+
   // 创建新对话
   const handleCreateConversation = async () => {
     if (!projectId) return;
@@ -420,6 +460,7 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
 
     // 创建一个临时的 AI 消息用于流式更新
     const tempAiMsgId = `temp-ai-${Date.now()}`;
+    tempMessageScoresRef.current = null;
     const tempAiMsg: Message = {
       id: tempAiMsgId,
       conversation_id: currentConversationId,
@@ -468,6 +509,16 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
             )
           );
         },
+        onScores: (scores: SourceScore[]) => {
+          tempMessageScoresRef.current = scores;
+          setMessages((prev) =>
+            prev.map((msg) =>
+              msg.id === tempAiMsgId
+                ? { ...msg, scores }
+                : msg
+            )
+          );
+        },
         onEnd: (fullContent: string) => {
           console.log('流式响应结束，完整内容长度:', fullContent?.length);
 
@@ -492,7 +543,7 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
           });
 
           // 重新加载消息列表以获取真实的消息ID
-          loadMessages(currentConversationId).then(() => {
+          loadMessages(currentConversationId, messageCountRef.current).then(() => {
             // 使用 setTimeout 确保消息加载完成后再处理
             setTimeout(() => {
               setMessages((prevMessages) => {
@@ -513,6 +564,12 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
                     });
                   });
                 }
+
+                const scores = tempMessageScoresRef.current;
+                tempMessageScoresRef.current = null;
+                if (lastAssistantMsg && scores && !lastAssistantMsg.scores) {
+                  return prevMessages.map((m) => (m.id === lastAssistantMsg.id ? { ...m, scores } : m));
+                }
                 return prevMessages;
               });
 
@@ -674,6 +731,7 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
 
   // 选中对话改变时加载消息
   useEffect(() => {
+    setHasMoreMessages(false);
     if (selectedConversationId) {
       loadMessages(selectedConversationId);
       // 不再清空展开状态，保持持久化的状态
@@ -682,6 +740,10 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
     }
   }, [selectedConversationId]);
 
+  useEffect(() => {
+    messageCountRef.current = messages.length;
+  }, [messages]);
+
   if (!projectId) {
     return (
       <div className="flex-1 flex items-center justify-center text-muted-foreground">
@@ -854,7 +916,11 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
         </div>
 
         {/* 消息列表 */}
-        <div className="flex-1 overflow-y-auto p-4 bg-secondary">
+        <div
+          ref={messagesContainerRef}
+          onScroll={handleMessagesScroll}
+          className="flex-1 overflow-y-auto p-4 bg-secondary"
+        >
           {!selectedConversationId ? (
             <div className="h-full flex items-center justify-center text-muted-foreground">
               <div className="text-center">
@@ -884,6 +950,20 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
             </div>
           ) : (
             <div className="space-y-4">
+              {hasMoreMessages && (
+                <div className="flex justify-center">
+                  <Button
+                    variant="ghost"
+                    size="sm"
+                    onClick={loadOlderMessages}
+                    disabled={isLoadingOlder}
+                    className="text-muted-foreground"
+                  >
+                    {isLoadingOlder && <Loader2 size={14} className="mr-1.5 animate-spin" />}
+                    加载更早的消息
+                  </Button>
+                </div>
+              )}
               {messages.map((msg) => (
                 <div
                   key={msg.id}
@@ -987,7 +1067,7 @@ const ChatPanel: React.FC<ChatPanelProps> = ({ projectId, projectName }) => {
                                   </span>
                                 </div>
                                 <span className="text-muted-foreground ml-2 flex-shrink-0 font-medium">
-                                  {(source?.relevance_score * 100)?.toFixed(0)}%
+                                  {((msg?.scores?.[idx]?.score ?? source?.relevance_score) * 100)?.toFixed(0)}%
                                 </span>
                               </div>
                             ))}
@@ -5,7 +5,14 @@ import AddDocumentModal from '../ProjectPanel/AddDocumentModal';
 import ChatPanel from '../ChatPanel';
 import { handleCreateProject } from '../ProjectPanel/projectHandlers';
 import { handleAddDocuments } from '../ProjectPanel/documentHandlers';
-import { getProjects, ProjectResponse, deleteProject, renameProject } from '../../services/projectService';
+import {
+  getProjects,
+  getProjectDetails,
+  ProjectResponse,
+  ProjectDetailsResponse,
+  deleteProject,
+  renameProject,
+} from '../../services/projectService';
 import { Theme } from '../../hooks/useTheme';
 import ConfirmDialog from '../common/ConfirmDialog';
 import RenameDialog from '../common/RenameDialog';
@@ -33,6 +40,8 @@ const Layout: React.FC<LayoutProps> = ({ selectedProjectId, onProjectSelect, the
   const [selectedProjectForAdd, setSelectedProjectForAdd] = useState<ProjectResponse | null>(null);
   const [projects, setProjects] = useState<ProjectResponse[]>([]);
   const [isLoadingProjects, setIsLoadingProjects] = useState(false);
+  // 选中项目的详细统计（对话数、文本块数、存储大小）
+  const [projectDetails, setProjectDetails] = useState<ProjectDetailsResponse | null>(null);
 
   // 删除相关状态
   const [isDeleteDialogOpen, setIsDeleteDialogOpen] = useState(false);
@@ -64,6 +73,28 @@ const Layout: React.FC<LayoutProps> = ({ selectedProjectId, onProjectSelect, the
     loadProjects();
   }, []);
 
+  // 选中项目变化或项目列表刷新后重新加载项目详情
+  useEffect(() => {
+    if (!selectedProjectId) {
+      setProjectDetails(null);
+      return;
+    }
+
+    let cancelled = false;
+    getProjectDetails(selectedProjectId)
+      .then((details) => {
+        if (!cancelled) setProjectDetails(details);
+      })
+      .catch((error) => {
+        console.error('加载项目详情失败:', error);
+        if (!cancelled) setProjectDetails(null);
+      });
+
+    return () => {
+      cancelled = true;
+    };
+  }, [selectedProjectId, projects]);
+
   // 拖拽处理函数
   const handleMouseDown = () => {
     setIsResizing(true);
@@ -146,6 +177,15 @@ const Layout: React.FC<LayoutProps> = ({ selectedProjectId, onProjectSelect, the
     }
   };
 
+  // 格式化存储大小
+  const formatFileSize = (bytes: number): string => {
+    if (bytes === 0) return '0 Bytes';
+    const k = 1024;
+    const sizes = ['Bytes', 'KB', 'MB', 'GB'];
+    const i = Math.floor(Math.log(bytes) / Math.log(k));
+    return parseFloat((bytes / Math.pow(k, i)).toFixed(2)) + ' ' + sizes[i];
+  };
+
   // 打开删除确认对话框
   const handleOpenDeleteDialog = (project: ProjectResponse, e: React.MouseEvent) => {
     e.stopPropagation();
@@ -320,7 +360,7 @@ const Layout: React.FC<LayoutProps> = ({ selectedProjectId, onProjectSelect, the
                   <div className="flex items-center justify-between">
                     <div className="flex items-center gap-2">
                       <Badge variant="outline" className="text-xs bg-accent/50 dark:border-border/30">
-                        {project?.document_count || 0} 文档
+                        {(projectDetails?.id === project?.id ? projectDetails?.document_count : project?.document_count) || 0} 文档
                       </Badge>
                     </div>
                     <div className="flex items-center gap-1 text-xs text-muted-foreground">
@@ -328,6 +368,12 @@ const Layout: React.FC<LayoutProps> = ({ selectedProjectId, onProjectSelect, the
                       <span>{formatDate(project?.updated_at)}</span>
                     </div>
                   </div>
+                  {selectedProjectId === project?.id && projectDetails?.id === project?.id && (
+                    <div className="mt-2 text-xs text-muted-foreground">
+                      {projectDetails.conversation_count} 对话 · {projectDetails.total_chunks} 文本块 ·{' '}
+                      {formatFileSize(projectDetails.storage_size)}
+                    </div>
+                  )}
                 </Card>
               ))}
             </div>
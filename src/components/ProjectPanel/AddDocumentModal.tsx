@@ -122,7 +122,7 @@ const AddDocumentModal: React.FC<AddDocumentModalProps> = ({
   };
 
   const addFiles = (newFiles: File[]) => {
-    const allowedTypes = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf'];
+    const allowedTypes = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf', '.html', '.htm', '.epub'];
     const validFiles = newFiles.filter(file => {
       const extension = '.' + file.name.split('.').pop()?.toLowerCase();
       return allowedTypes.includes(extension);
@@ -140,7 +140,7 @@ const AddDocumentModal: React.FC<AddDocumentModalProps> = ({
     }
 
     if (validFiles.length < newFiles.length) {
-      alert('部分文件格式不支持，仅支持 .txt, .md, .pdf, .doc, .docx, .rtf 格式');
+      alert('部分文件格式不支持，仅支持 .txt, .md, .pdf, .doc, .docx, .rtf, .html, .epub 格式');
     }
   };
 
@@ -448,6 +448,8 @@ const AddDocumentModal: React.FC<AddDocumentModalProps> = ({
       request: {
         project_id: projectId,
         file_paths: [filePath],
+        // 失败信息由下方逐个文件处理，不使用严格模式
+        strict: false,
       },
     }) as {
       successful: Array<any>;
@@ -510,7 +512,7 @@ const AddDocumentModal: React.FC<AddDocumentModalProps> = ({
           multiple
           onChange={handleFileSelect}
           className="hidden"
-          accept=".txt,.md,.pdf,.doc,.docx,.rtf"
+          accept=".txt,.md,.pdf,.doc,.docx,.rtf,.html,.htm,.epub"
           disabled={isSubmitting}
         />
         <div
@@ -536,7 +538,7 @@ const AddDocumentModal: React.FC<AddDocumentModalProps> = ({
                 {isDragOver ? '释放文件以上传' : '拖拽文件到此处或点击选择'}
               </p>
               <p className="text-sm text-muted-foreground">
-                支持 .txt, .md, .pdf, .doc, .docx, .rtf 格式
+                支持 .txt, .md, .pdf, .doc, .docx, .rtf, .html, .epub 格式
               </p>
             </div>
           </div>
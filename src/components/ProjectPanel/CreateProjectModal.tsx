@@ -189,7 +189,7 @@ const CreateProjectModal: React.FC<CreateProjectModalProps> = ({
   };
 
   const addFiles = (newFiles: File[]) => {
-    const allowedTypes = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf'];
+    const allowedTypes = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf', '.html', '.htm', '.epub'];
     const validFiles = newFiles.filter(file => {
       const extension = '.' + file.name.split('.').pop()?.toLowerCase();
       return allowedTypes.includes(extension);
@@ -207,7 +207,7 @@ const CreateProjectModal: React.FC<CreateProjectModalProps> = ({
     }
 
     if (validFiles.length < newFiles.length) {
-      alert('部分文件格式不支持，仅支持 .txt, .md, .pdf, .doc, .docx, .rtf 格式');
+      alert('部分文件格式不支持，仅支持 .txt, .md, .pdf, .doc, .docx, .rtf, .html, .epub 格式');
     }
   };
 
@@ -410,6 +410,8 @@ const CreateProjectModal: React.FC<CreateProjectModalProps> = ({
       request: {
         project_id: projectId,
         file_paths: [filePath],
+        // 失败信息由下方逐个文件处理，不使用严格模式
+        strict: false,
       },
     }) as {
       successful: Array<any>;
@@ -483,7 +485,7 @@ const CreateProjectModal: React.FC<CreateProjectModalProps> = ({
           multiple
           onChange={handleFileSelect}
           className="hidden"
-          accept=".txt,.md,.pdf,.doc,.docx,.rtf"
+          accept=".txt,.md,.pdf,.doc,.docx,.rtf,.html,.htm,.epub"
           disabled={isSubmitting}
         />
         <div
@@ -509,7 +511,7 @@ const CreateProjectModal: React.FC<CreateProjectModalProps> = ({
                 {isDragOver ? '释放文件以上传' : '拖拽文件到此处或点击选择'}
               </p>
               <p className="text-sm text-muted-foreground">
-                支持 .txt, .md, .pdf, .doc, .docx, .rtf 格式
+                支持 .txt, .md, .pdf, .doc, .docx, .rtf, .html, .epub 格式
               </p>
             </div>
           </div>
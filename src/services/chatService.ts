@@ -10,11 +10,31 @@ export interface Conversation {
   created_at: string;
   updated_at?: string;  // 添加 updated_at 字段用于排序
   message_count: number;
+  model?: string | null;  // 对话级别的模型覆盖，null 表示使用默认模型
+  last_message_preview?: string | null;  // 最后一条消息预览
+  last_message_role?: 'user' | 'assistant' | 'system' | null;
 }
 
 export interface MessageSource {
   filename: string;
   relevance_score: number;
+  project_id?: string | null;  // 来源文档所在项目（跨项目检索时用于区分）
+  section?: string | null;  // 来源块所属章节（文档中最近的标题）
+  other_filenames?: string[];  // 包含相同内容的其他文档（按内容去重合并）
+}
+
+/** chat-stream-scores 事件中单个来源的归一化相关度（0~1） */
+export interface SourceScore {
+  index: number;
+  filename: string;
+  document_id: string;
+  score: number;
+}
+
+export interface ProjectWeight {
+  project_id: string;
+  /** 分数乘数，对话所属项目默认为 1.0 */
+  weight: number;
 }
 
 export interface Message {
@@ -24,6 +44,8 @@ export interface Message {
   content: string;
   created_at: string;
   sources?: MessageSource[];
+  /** 各来源的归一化相关度（来自消息末尾的页脚或 chat-stream-scores 事件），顺序与 sources 一致 */
+  scores?: SourceScore[];
 }
 
 export interface CreateConversationRequest {
@@ -32,7 +54,28 @@ export interface CreateConversationRequest {
 }
 
 export interface SendMessageRequest {
+  /** 不指定时在 project_id 下自动创建对话（需开启配置 chat.autoCreateConversation） */
+  conversation_id?: string;
+  project_id?: string;
+  content: string;
+  /** 按句子接收 chat-stream-token（不指定时使用配置 chat.sentenceFlush） */
+  sentence_flush?: boolean;
+  /** 检索完成后逐个接收 chat-stream-source（不指定时使用配置 chat.incrementalSources） */
+  incremental_sources?: boolean;
+  /** 同时检索的其他项目及权重 */
+  extra_projects?: ProjectWeight[];
+  /** 本次回复的 temperature（0.0-2.0，不指定时使用配置） */
+  temperature?: number;
+  /** 本次回复的最大 token 数（不指定时使用配置） */
+  max_tokens?: number;
+  /** 只检索元数据匹配的文档块，如 { filename: 'report.pdf' } 只针对一个文档提问 */
+  metadata_filters?: Record<string, string>;
+}
+
+export interface SendMessageResponse {
   conversation_id: string;
+  /** 本次请求是否自动创建了对话 */
+  conversation_created: boolean;
   content: string;
 }
 
@@ -50,11 +93,28 @@ export interface RenameConversationRequest {
   new_title: string;
 }
 
+export interface SetConversationModelRequest {
+  conversation_id: string;
+  model: string | null;
+}
+
 export interface StreamCallbacks {
   onStart?: () => void;
+  /** 检索阶段（第一个 token 之前）的心跳，elapsedMs 为已等待的毫秒数 */
+  onHeartbeat?: (elapsedMs: number) => void;
+  /** embedding 服务不可用，本次检索降级为关键词检索 */
+  onRetrievalDegraded?: (message: string) => void;
+  /** 最相关的来源也低于 chat.lowConfidenceFloor，知识库可能没有涵盖该问题；没有块达到相似度阈值时 topScore 为 null */
+  onLowConfidence?: (topScore: number | null, floor: number) => void;
   onToken: (token: string) => void;
+  /** 单个来源（开启 incremental_sources 时在第一个 token 之前逐个到达），total 为来源总数 */
+  onSource?: (source: MessageSource, index: number, total: number) => void;
   onContext?: (sources: MessageSource[]) => void;
+  /** 各来源的相关度（配置 chat.scoreFooter 为 eventOnly 时在结束事件之前到达） */
+  onScores?: (scores: SourceScore[]) => void;
   onEnd?: (fullContent: string) => void;
+  /** 生成被 cancelGeneration 停止，partialContent 为已保存的部分回复 */
+  onCancelled?: (partialContent: string) => void;
   onError?: (error: string) => void;
 }
 
@@ -93,19 +153,98 @@ export async function getConversations(projectId: string): Promise<Conversation[
   }
 }
 
+/** 助手消息末尾相关度页脚（chat.scoreFooter 为 inline 时写入）的前缀 */
+const SCORE_FOOTER_PREFIX = '<!-- mine-kb-scores: ';
+
+/**
+ * 拆出消息末尾的相关度页脚：返回去掉页脚的内容和页脚中的各来源相关度
+ */
+export function splitScoreFooter(content: string): { content: string; scores?: SourceScore[] } {
+  const start = content.lastIndexOf(SCORE_FOOTER_PREFIX);
+  const trimmed = content.trimEnd();
+  if (start < 0 || !trimmed.endsWith('-->')) {
+    return { content };
+  }
+  try {
+    const scores = JSON.parse(trimmed.slice(start + SCORE_FOOTER_PREFIX.length, -'-->'.length)) as SourceScore[];
+    return { content: content.slice(0, start).trimEnd(), scores };
+  } catch {
+    return { content };
+  }
+}
+
+/** 去掉消息内容中的相关度页脚，页脚中的相关度放到 scores 中 */
+function withoutScoreFooter(message: Message): Message {
+  const { content, scores } = splitScoreFooter(message.content);
+  return scores ? { ...message, content, scores } : message;
+}
+
+/** get_conversation_history 未指定 limit 时每页的消息数 */
+export const DEFAULT_HISTORY_PAGE_SIZE = 50;
+
+export interface ConversationHistory {
+  /** 本页消息，按时间升序 */
+  messages: Message[];
+  /** 对话的消息总数 */
+  total: number;
+  /** 是否还有更早的消息 */
+  has_more: boolean;
+}
+
 /**
- * 获取对话的历史消息
+ * 获取对话的历史消息：默认返回最新的 50 条，offset 为已加载的消息数，用于向前加载更早的消息
  */
-export async function getConversationHistory(conversationId: string): Promise<Message[]> {
+export async function getConversationHistory(
+  conversationId: string,
+  offset?: number,
+  limit?: number
+): Promise<ConversationHistory> {
   try {
-    const messages = await invoke<Message[]>('get_conversation_history', { conversationId });
-    return messages;
+    const history = await invoke<ConversationHistory>('get_conversation_history', { conversationId, offset, limit });
+    return { ...history, messages: history.messages.map(withoutScoreFooter) };
   } catch (error) {
     console.error('获取对话历史失败:', error);
     throw new Error(`获取对话历史失败: ${error}`);
   }
 }
 
+/**
+ * 使用调整后的参数重新生成某条助手消息（原地替换，消息 ID 不变）
+ */
+export async function regenerateMessage(
+  conversationId: string,
+  messageId: string,
+  overrides?: { temperature?: number; top_k?: number }
+): Promise<Message> {
+  try {
+    const message = await invoke<Message>('regenerate_message', {
+      request: { conversation_id: conversationId, message_id: messageId, overrides },
+    });
+    return withoutScoreFooter(message);
+  } catch (error) {
+    console.error('重新生成消息失败:', error);
+    throw new Error(`重新生成消息失败: ${error}`);
+  }
+}
+
+/**
+ * 编辑已发送的用户消息：删除其后的所有消息并重新生成回复，返回新助手消息的 ID
+ */
+export async function editMessage(
+  conversationId: string,
+  messageId: string,
+  newContent: string
+): Promise<string> {
+  try {
+    return await invoke<string>('edit_message', {
+      request: { conversation_id: conversationId, message_id: messageId, new_content: newContent },
+    });
+  } catch (error) {
+    console.error('编辑消息失败:', error);
+    throw new Error(`编辑消息失败: ${error}`);
+  }
+}
+
 /**
  * 发送消息（流式版本）
  */
@@ -125,6 +264,39 @@ export async function sendMessageStream(
     });
     unlistenFns.push(unlistenStart);
 
+    // 监听检索阶段的心跳事件
+    const unlistenHeartbeat = await listen<{ conversation_id: string; beat: number; elapsed_ms: number }>(
+      'chat-heartbeat',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onHeartbeat?.(event?.payload?.elapsed_ms || 0);
+        }
+      }
+    );
+    unlistenFns.push(unlistenHeartbeat);
+
+    // 监听检索降级事件（embedding 服务不可用时改用关键词检索）
+    const unlistenDegraded = await listen<{ conversation_id: string; mode: string; message: string }>(
+      'chat-retrieval-degraded',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onRetrievalDegraded?.(event?.payload?.message || '');
+        }
+      }
+    );
+    unlistenFns.push(unlistenDegraded);
+
+    // 监听低置信度事件（检索结果的相关度都很低）
+    const unlistenLowConfidence = await listen<{ conversation_id: string; top_score: number | null; floor: number }>(
+      'chat-low-confidence',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onLowConfidence?.(event?.payload?.top_score ?? null, event?.payload?.floor || 0);
+        }
+      }
+    );
+    unlistenFns.push(unlistenLowConfidence);
+
     // 监听流式 token 事件
     const unlistenToken = await listen<{ conversation_id: string; token: string }>(
       'chat-stream-token',
@@ -136,6 +308,17 @@ export async function sendMessageStream(
     );
     unlistenFns.push(unlistenToken);
 
+    // 监听逐个到达的来源事件
+    const unlistenSource = await listen<{ conversation_id: string; index: number; total: number; source: MessageSource }>(
+      'chat-stream-source',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onSource?.(event.payload.source, event.payload.index, event.payload.total);
+        }
+      }
+    );
+    unlistenFns.push(unlistenSource);
+
     // 监听来源文档事件
     const unlistenContext = await listen<{ conversation_id: string; sources: MessageSource[] }>(
       'chat-stream-context',
@@ -147,12 +330,23 @@ export async function sendMessageStream(
     );
     unlistenFns.push(unlistenContext);
 
+    // 监听来源相关度事件
+    const unlistenScores = await listen<{ conversation_id: string; scores: SourceScore[] }>(
+      'chat-stream-scores',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onScores?.(event?.payload?.scores || []);
+        }
+      }
+    );
+    unlistenFns.push(unlistenScores);
+
     // 监听流式结束事件
     const unlistenEnd = await listen<{ conversation_id: string; content: string }>(
       'chat-stream-end',
       (event) => {
         if (event?.payload?.conversation_id === conversationId) {
-          callbacks?.onEnd?.(event?.payload?.content || '');
+          callbacks?.onEnd?.(splitScoreFooter(event?.payload?.content || '').content);
           // 清理监听器
           unlistenFns.forEach((fn) => fn());
         }
@@ -160,6 +354,19 @@ export async function sendMessageStream(
     );
     unlistenFns.push(unlistenEnd);
 
+    // 监听取消事件（content 为已保存的部分回复，可能为空）
+    const unlistenCancelled = await listen<{ conversation_id: string; content: string }>(
+      'chat-stream-cancelled',
+      (event) => {
+        if (event?.payload?.conversation_id === conversationId) {
+          callbacks?.onCancelled?.(event?.payload?.content || '');
+          // 清理监听器
+          unlistenFns.forEach((fn) => fn());
+        }
+      }
+    );
+    unlistenFns.push(unlistenCancelled);
+
     // 监听错误事件
     const unlistenError = await listen<{ conversation_id: string; error: string }>(
       'chat-stream-error',
@@ -178,7 +385,7 @@ export async function sendMessageStream(
       conversation_id: conversationId,
       content,
     };
-    await invoke<string>('send_message', { request });
+    await invoke<SendMessageResponse>('send_message', { request });
   } catch (error) {
     console.error('发送消息失败:', error);
     // 清理监听器
@@ -188,19 +395,31 @@ export async function sendMessageStream(
   }
 }
 
+/**
+ * 停止对话正在生成的回复（已生成的部分会被保存），返回是否有正在进行的生成
+ */
+export async function cancelGeneration(conversationId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('cancel_generation', { conversationId });
+  } catch (error) {
+    console.error('取消生成失败:', error);
+    throw new Error(`取消生成失败: ${error}`);
+  }
+}
+
 /**
  * 发送消息（同步版本，等待完整响应）- 保留用于兼容性
  */
 export async function sendMessage(
   conversationId: string,
   content: string
-): Promise<string> {
+): Promise<SendMessageResponse> {
   try {
     const request: SendMessageRequest = {
       conversation_id: conversationId,
       content,
     };
-    const response = await invoke<string>('send_message', { request });
+    const response = await invoke<SendMessageResponse>('send_message', { request });
     return response;
   } catch (error) {
     console.error('发送消息失败:', error);
@@ -208,6 +427,26 @@ export async function sendMessage(
   }
 }
 
+/**
+ * 不先创建对话直接发送第一条消息：后端在项目下自动创建对话并返回其 ID
+ * （流式事件中的 conversation_id 可通过 chat-conversation-created 事件提前获知）
+ */
+export async function startConversation(
+  projectId: string,
+  content: string
+): Promise<SendMessageResponse> {
+  try {
+    const request: SendMessageRequest = {
+      project_id: projectId,
+      content,
+    };
+    return await invoke<SendMessageResponse>('send_message', { request });
+  } catch (error) {
+    console.error('发送消息失败:', error);
+    throw new Error(`发送消息失败: ${error}`);
+  }
+}
+
 /**
  * 删除对话
  */
@@ -260,6 +499,49 @@ export async function clearMessages(conversationId: string): Promise<boolean> {
   }
 }
 
+/**
+ * 只保留对话中最近的 keepLastN 条消息，返回删除的消息数
+ */
+export async function trimConversation(conversationId: string, keepLastN: number): Promise<number> {
+  try {
+    const request = {
+      conversation_id: conversationId,
+      keep_last_n: keepLastN,
+    };
+    return await invoke<number>('trim_conversation', { request });
+  } catch (error) {
+    console.error('裁剪对话失败:', error);
+    throw new Error(`裁剪对话失败: ${error}`);
+  }
+}
+
+export interface MessageSearchHit {
+  message_id: string;
+  /** 消息在对话中的位置（按显示顺序，从 0 开始） */
+  position: number;
+  role: string;
+  content: string;
+  timestamp: string;
+  /** 匹配在 content 中的字符区间 [start, end) */
+  highlights: { start: number; end: number }[];
+}
+
+/**
+ * 在单个对话的消息中搜索，返回命中的消息和高亮区间
+ */
+export async function searchConversationMessages(conversationId: string, query: string): Promise<MessageSearchHit[]> {
+  try {
+    const request = {
+      conversation_id: conversationId,
+      query,
+    };
+    return await invoke<MessageSearchHit[]>('search_conversation_messages', { request });
+  } catch (error) {
+    console.error('搜索对话消息失败:', error);
+    throw new Error(`搜索对话消息失败: ${error}`);
+  }
+}
+
 /**
  * 重命名对话
  */
@@ -280,6 +562,87 @@ export async function renameConversation(
   }
 }
 
+/**
+ * 设置对话使用的模型（传 null 恢复默认模型）
+ */
+export async function setConversationModel(
+  conversationId: string,
+  model: string | null
+): Promise<boolean> {
+  try {
+    const request: SetConversationModelRequest = {
+      conversation_id: conversationId,
+      model,
+    };
+    return await invoke<boolean>('set_conversation_model', { request });
+  } catch (error) {
+    console.error('设置对话模型失败:', error);
+    throw new Error(`设置对话模型失败: ${error}`);
+  }
+}
+
+/**
+ * 修复对话的消息顺序（同一秒内创建的消息显示错乱时使用），返回更新的消息数
+ */
+export async function repairMessageOrdering(conversationId: string): Promise<number> {
+  try {
+    return await invoke<number>('repair_message_ordering', { conversationId });
+  } catch (error) {
+    console.error('修复消息顺序失败:', error);
+    throw new Error(`修复消息顺序失败: ${error}`);
+  }
+}
+
+export interface CompressConversationResult {
+  compressed_messages: number;
+  remaining_messages: number;
+}
+
+/**
+ * 压缩对话历史：把最近 keepTurns 轮之前的消息总结为一条摘要消息（不指定时保留最近 3 轮）
+ */
+export async function compressConversation(
+  conversationId: string,
+  keepTurns?: number
+): Promise<CompressConversationResult> {
+  try {
+    return await invoke<CompressConversationResult>('compress_conversation', { conversationId, keepTurns });
+  } catch (error) {
+    console.error('压缩对话失败:', error);
+    throw new Error(`压缩对话失败: ${error}`);
+  }
+}
+
+/**
+ * 根据第一轮对话自动生成标题（已有自定义标题时不修改），返回对话当前标题
+ */
+export async function generateConversationTitle(conversationId: string): Promise<string> {
+  try {
+    return await invoke<string>('generate_conversation_title', { conversationId });
+  } catch (error) {
+    console.error('生成对话标题失败:', error);
+    throw new Error(`生成对话标题失败: ${error}`);
+  }
+}
+
+export interface ExportConversationRequest {
+  conversation_id: string;
+  output_path: string;
+  format?: 'markdown' | 'jsonl';
+}
+
+/**
+ * 流式导出对话到文件（进度通过 conversation-export-progress 事件通知），返回导出的消息数
+ */
+export async function exportConversation(request: ExportConversationRequest): Promise<number> {
+  try {
+    return await invoke<number>('export_conversation', { request });
+  } catch (error) {
+    console.error('导出对话失败:', error);
+    throw new Error(`导出对话失败: ${error}`);
+  }
+}
+
 // ==================== 辅助函数 ====================
 
 /**
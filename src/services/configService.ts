@@ -0,0 +1,249 @@
+import { invoke } from '@tauri-apps/api/tauri';
+
+export interface ConfigureLLMRequest {
+  api_key?: string;
+  model: string;
+  base_url?: string;
+}
+
+export interface SaveConfigResponse {
+  config_path: string;
+  initialization_started: boolean;
+}
+
+/**
+ * 保存完整配置（与 config.json 结构一致）并继续初始化
+ */
+export async function saveConfig(config: Record<string, unknown>): Promise<SaveConfigResponse> {
+  try {
+    return await invoke<SaveConfigResponse>('save_config', { config });
+  } catch (error) {
+    console.error('保存配置失败:', error);
+    throw new Error(`保存配置失败: ${error}`);
+  }
+}
+
+/**
+ * 配置 LLM 服务（首次运行缺少配置时使用），返回是否已开始初始化
+ */
+export async function configureLLMService(request: ConfigureLLMRequest): Promise<boolean> {
+  try {
+    return await invoke<boolean>('configure_llm_service', { request });
+  } catch (error) {
+    console.error('配置 LLM 服务失败:', error);
+    throw new Error(`配置 LLM 服务失败: ${error}`);
+  }
+}
+
+export interface DbEmbeddingDimension {
+  dimension: number;
+  source: 'stored_vector' | 'table_schema' | 'default';
+  model_dimension: number;
+  matches: boolean;
+}
+
+/**
+ * 检测当前数据库的向量维度（用于配置匹配的 embedding 模型）
+ */
+export async function getDbEmbeddingDimension(): Promise<DbEmbeddingDimension> {
+  try {
+    return await invoke<DbEmbeddingDimension>('get_db_embedding_dimension');
+  } catch (error) {
+    console.error('检测向量维度失败:', error);
+    throw new Error(`检测向量维度失败: ${error}`);
+  }
+}
+
+export interface ReloadFromDatabaseResult {
+  projects: number;
+  conversations: number;
+  messages: number;
+  documents: number;
+}
+
+/**
+ * 重新从数据库加载项目、对话和文档（数据库被外部导入、修复或迁移后使用）
+ */
+export async function reloadFromDatabase(): Promise<ReloadFromDatabaseResult> {
+  try {
+    return await invoke<ReloadFromDatabaseResult>('reload_from_database');
+  } catch (error) {
+    console.error('重新加载数据失败:', error);
+    throw new Error(`重新加载数据失败: ${error}`);
+  }
+}
+
+export interface ActiveTask {
+  id: string;
+  kind: 'upload' | 'rechunk' | 'reprocess' | 'summarize';
+  /** 任务作用的项目ID或对话ID */
+  target_id: string;
+  progress: { current: number; total: number } | null;
+  started_at: string;
+}
+
+/**
+ * 列出正在运行的后台任务
+ */
+export async function listActiveTasks(): Promise<ActiveTask[]> {
+  try {
+    return await invoke<ActiveTask[]>('list_active_tasks');
+  } catch (error) {
+    console.error('获取后台任务失败:', error);
+    throw new Error(`获取后台任务失败: ${error}`);
+  }
+}
+
+export interface ComponentHealth {
+  healthy: boolean;
+  latency_ms: number;
+  error: string | null;
+}
+
+export interface SystemHealth {
+  database: ComponentHealth;
+  llm: ComponentHealth;
+  embedding: ComponentHealth;
+  checked_at: string;
+  /** 是否为缓存的结果 */
+  cached: boolean;
+}
+
+export interface VersionInfo {
+  app_version: string;
+  expected_protocol_version: number;
+  bridge_protocol_version: number;
+  /** 已安装的 seekdb 包版本，无法获取时为 null */
+  seekdb_version: string | null;
+  embedding_model: string;
+  llm_model: string;
+}
+
+/**
+ * 获取应用版本、桥接协议版本、SeekDB 版本和使用中的模型（用于问题反馈）
+ */
+export async function getVersionInfo(): Promise<VersionInfo> {
+  try {
+    return await invoke<VersionInfo>('get_version_info');
+  } catch (error) {
+    console.error('获取版本信息失败:', error);
+    throw new Error(`获取版本信息失败: ${error}`);
+  }
+}
+
+/**
+ * 清空持久化 embedding 缓存，返回删除的条目数（persistentCache 设为 false 时为 0）
+ */
+export async function clearEmbeddingCache(): Promise<number> {
+  try {
+    return await invoke<number>('clear_embedding_cache');
+  } catch (error) {
+    console.error('清空 embedding 缓存失败:', error);
+    throw new Error(`清空 embedding 缓存失败: ${error}`);
+  }
+}
+
+export interface ModelUsage {
+  model: string;
+  kind: 'embedding' | 'chat';
+  tokens: number;
+  requests: number;
+}
+
+export interface ProjectUsage {
+  project_id: string;
+  /** 项目已删除时为 null */
+  project_name: string | null;
+  total_tokens: number;
+  models: ModelUsage[];
+}
+
+export interface UsageReport {
+  from: string;
+  to: string;
+  total_tokens: number;
+  projects: ProjectUsage[];
+}
+
+/**
+ * 按项目和模型汇总 token 用量，日期格式 YYYY-MM-DD（包含两端），默认最近 30 天
+ */
+export async function getUsageReport(from?: string, to?: string): Promise<UsageReport> {
+  try {
+    return await invoke<UsageReport>('get_usage_report', { from, to });
+  } catch (error) {
+    console.error('获取用量报告失败:', error);
+    throw new Error(`获取用量报告失败: ${error}`);
+  }
+}
+
+export interface TempArtifact {
+  path: string;
+  kind: 'temp_database' | 'upload_copy' | 'cache_temp';
+  size_bytes: number;
+  modified_at: string | null;
+  age_hours: number | null;
+}
+
+export interface TempCleanupReport {
+  removed: number;
+  freed_bytes: number;
+  failed: string[];
+}
+
+/**
+ * 列出遗留的临时数据库、上传临时副本和缓存临时文件
+ */
+export async function listTempArtifacts(): Promise<TempArtifact[]> {
+  try {
+    return await invoke<TempArtifact[]>('list_temp_artifacts');
+  } catch (error) {
+    console.error('获取临时文件失败:', error);
+    throw new Error(`获取临时文件失败: ${error}`);
+  }
+}
+
+/**
+ * 删除超过指定小时数未修改的临时文件
+ */
+export async function cleanTempArtifacts(olderThanHours: number): Promise<TempCleanupReport> {
+  try {
+    return await invoke<TempCleanupReport>('clean_temp_artifacts', { olderThanHours });
+  } catch (error) {
+    console.error('清理临时文件失败:', error);
+    throw new Error(`清理临时文件失败: ${error}`);
+  }
+}
+
+/**
+ * 检查系统健康状态（结果短时间内缓存，force 为 true 时重新检查）
+ */
+export async function getSystemHealth(force = false): Promise<SystemHealth> {
+  try {
+    return await invoke<SystemHealth>('get_system_health', { force });
+  } catch (error) {
+    console.error('健康检查失败:', error);
+    throw new Error(`健康检查失败: ${error}`);
+  }
+}
+
+export interface AppStatus {
+  db_ok: boolean;
+  python_ok: boolean;
+  llm_ok: boolean;
+  embedding_ok: boolean;
+  /** 每个子系统一条说明，失败时包含错误信息 */
+  details: string[];
+}
+
+/**
+ * 获取 SeekDB、Python、LLM 和 embedding 服务的连通性（用于设置页诊断面板）
+ */
+export async function getAppStatus(force = false): Promise<AppStatus> {
+  try {
+    return await invoke<AppStatus>('get_app_status', { force });
+  } catch (error) {
+    console.error('获取应用状态失败:', error);
+    throw new Error(`获取应用状态失败: ${error}`);
+  }
+}
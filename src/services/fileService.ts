@@ -1,10 +1,20 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import { writeBinaryFile, createDir } from '@tauri-apps/api/fs';
 import { appDataDir, join } from '@tauri-apps/api/path';
 
 export interface UploadDocumentsRequest {
   project_id: string;
   file_paths: string[];
+  /** 严格模式：所有文件均失败时 reject（UploadAllFailedError），未指定时使用配置 ingestion.failOnAllFailed */
+  strict?: boolean;
+}
+
+/** 严格模式下整批失败时 upload_documents 返回的错误 */
+export interface UploadAllFailedError {
+  kind: 'allFailed';
+  message: string;
+  failed: FailedDocumentInfo[];
 }
 
 export interface DocumentResponse {
@@ -22,6 +32,18 @@ export interface FailedDocumentInfo {
   error_stage: string;
 }
 
+/** 每处理完一个文件发送一次的上传进度，全部完成后再发送一次 stage 为 'done' 的事件 */
+export interface DocumentUploadProgress {
+  project_id: string;
+  /** 刚处理完的文件，最终事件为 null */
+  filename: string | null;
+  /** 已处理的文件数（从 1 开始） */
+  index: number;
+  total: number;
+  /** 成功时为处理状态（如 Indexed），失败时为错误阶段（如 embedding），全部完成时为 done */
+  stage: string;
+}
+
 export interface UploadDocumentsResponse {
   successful: DocumentResponse[];
   failed: FailedDocumentInfo[];
@@ -97,9 +119,20 @@ export async function getFilePathsForUpload(files: File[]): Promise<string[]> {
 
 /**
  * 上传文档到项目
- * 返回详细的上传结果，包括成功和失败的文件
+ * 返回详细的上传结果，包括成功和失败的文件；onProgress 在每个文件处理完后调用
  */
-export async function uploadDocuments(request: UploadDocumentsRequest): Promise<UploadDocumentsResponse> {
+export async function uploadDocuments(
+  request: UploadDocumentsRequest,
+  onProgress?: (progress: DocumentUploadProgress) => void
+): Promise<UploadDocumentsResponse> {
+  const unlisten = onProgress
+    ? await listen<DocumentUploadProgress>('document-upload-progress', (event) => {
+        if (event.payload.project_id === request.project_id) {
+          onProgress(event.payload);
+        }
+      })
+    : undefined;
+
   try {
     const response = await invoke<UploadDocumentsResponse>('upload_documents', { request });
 
@@ -111,16 +144,48 @@ export async function uploadDocuments(request: UploadDocumentsRequest): Promise<
     return response;
   } catch (error) {
     console.error('上传文档失败:', error);
-    throw new Error(`上传文档失败: ${error}`);
+    const message = (error as UploadAllFailedError)?.kind === 'allFailed'
+      ? (error as UploadAllFailedError).message
+      : String(error);
+    throw new Error(`上传文档失败: ${message}`);
+  } finally {
+    unlisten?.();
   }
 }
 
+export interface DocumentContent {
+  document_id: string;
+  filename?: string | null;
+  content: string;
+  chunk_count: number;
+}
+
+export interface RestructureReport {
+  document_id: string;
+  /** 识别出的章节数 */
+  sections: number;
+  chunks_before: number;
+  chunks_after: number;
+}
+
 /**
- * 获取文档内容
+ * 按文档结构（编号章节、标题层级）重新分块，章节路径保存在块 metadata 的 section_path 中
  */
-export async function getDocumentContent(documentId: string): Promise<string> {
+export async function restructureDocument(documentId: string): Promise<RestructureReport> {
   try {
-    const content = await invoke<string>('get_document_content', { documentId });
+    return await invoke<RestructureReport>('restructure_document', { documentId });
+  } catch (error) {
+    console.error('按结构重新分块失败:', error);
+    throw new Error(`按结构重新分块失败: ${error}`);
+  }
+}
+
+/**
+ * 获取文档内容（由文档块拼接还原，用于查看引用来源）
+ */
+export async function getDocumentContent(documentId: string): Promise<DocumentContent> {
+  try {
+    const content = await invoke<DocumentContent>('get_document_content', { documentId });
     return content;
   } catch (error) {
     console.error('获取文档内容失败:', error);
@@ -128,6 +193,200 @@ export async function getDocumentContent(documentId: string): Promise<string> {
   }
 }
 
+/**
+ * 删除文档（同时删除其向量数据，不会再被检索到）
+ */
+export async function deleteDocument(documentId: string): Promise<void> {
+  try {
+    await invoke('delete_document', { documentId });
+  } catch (error) {
+    console.error('删除文档失败:', error);
+    throw new Error(`删除文档失败: ${error}`);
+  }
+}
+
+export interface ReprocessFailure {
+  document_id: string;
+  filename: string;
+  error: string;
+}
+
+export interface ReprocessReport {
+  document_count: number;
+  deleted_chunks: number;
+  reprocessed: number;
+  failed_documents: ReprocessFailure[];
+}
+
+/**
+ * 重新处理项目内所有文档（更换 embedding 模型后重建向量）
+ * onProgress 在每个文档处理完后调用
+ */
+export async function reprocessProject(
+  projectId: string,
+  onProgress?: (progress: { current: number; total: number; filename: string }) => void
+): Promise<ReprocessReport> {
+  const unlisten = onProgress
+    ? await listen<{ project_id: string; current: number; total: number; filename: string }>(
+        'reprocess-progress',
+        (event) => {
+          if (event.payload.project_id === projectId) {
+            onProgress(event.payload);
+          }
+        }
+      )
+    : undefined;
+
+  try {
+    return await invoke<ReprocessReport>('reprocess_project', { projectId });
+  } catch (error) {
+    console.error('重新处理项目文档失败:', error);
+    throw new Error(`重新处理项目文档失败: ${error}`);
+  } finally {
+    unlisten?.();
+  }
+}
+
+export interface PartialCleanupReport {
+  failed_documents: number;
+  cleaned_documents: number;
+  removed_chunks: number;
+}
+
+/**
+ * 清理项目中处理失败的文档残留的部分向量数据
+ */
+export async function cleanupPartialDocuments(projectId: string): Promise<PartialCleanupReport> {
+  try {
+    return await invoke<PartialCleanupReport>('cleanup_partial_documents', { projectId });
+  } catch (error) {
+    console.error('清理残留文档块失败:', error);
+    throw new Error(`清理残留文档块失败: ${error}`);
+  }
+}
+
+export interface EmbeddingFillReport {
+  missing_chunks: number;
+  filled_chunks: number;
+}
+
+/**
+ * 为项目中缺少向量的文档块重新生成向量（比整体重建便宜）
+ */
+export async function fillMissingEmbeddings(projectId: string): Promise<EmbeddingFillReport> {
+  try {
+    return await invoke<EmbeddingFillReport>('fill_missing_embeddings', { projectId });
+  } catch (error) {
+    console.error('补全文档块向量失败:', error);
+    throw new Error(`补全文档块向量失败: ${error}`);
+  }
+}
+
+export interface SimilarDocument {
+  document_id: string;
+  filename: string | null;
+  score: number;
+}
+
+/**
+ * 查找与指定文档内容相近的同项目文档
+ */
+export async function findSimilarDocuments(documentId: string, topK?: number): Promise<SimilarDocument[]> {
+  try {
+    return await invoke<SimilarDocument[]>('find_similar_documents', { documentId, topK });
+  } catch (error) {
+    console.error('查找相似文档失败:', error);
+    throw new Error(`查找相似文档失败: ${error}`);
+  }
+}
+
+export interface MetadataValueCount {
+  value: string;
+  document_count: number;
+  chunk_count: number;
+}
+
+/**
+ * 获取项目中某个元数据键（如 category）的所有取值及数量
+ */
+export async function getDocumentMetadataValues(projectId: string, key: string): Promise<MetadataValueCount[]> {
+  try {
+    return await invoke<MetadataValueCount[]>('get_document_metadata_values', { projectId, key });
+  } catch (error) {
+    console.error('获取元数据取值失败:', error);
+    throw new Error(`获取元数据取值失败: ${error}`);
+  }
+}
+
+export interface LatencyStats {
+  samples: number;
+  p50_ms: number;
+  p95_ms: number;
+  p99_ms: number;
+  max_ms: number;
+}
+
+export interface RetrievalBenchmark {
+  query_count: number;
+  iterations: number;
+  embedding: LatencyStats;
+  search: LatencyStats;
+  result_counts: number[];
+}
+
+/**
+ * 检索延迟基准测试：分别统计生成查询向量和向量检索的耗时
+ */
+export async function benchmarkRetrieval(projectId: string, queries: string[], iterations?: number): Promise<RetrievalBenchmark> {
+  try {
+    return await invoke<RetrievalBenchmark>('benchmark_retrieval', { projectId, queries, iterations });
+  } catch (error) {
+    console.error('检索基准测试失败:', error);
+    throw new Error(`检索基准测试失败: ${error}`);
+  }
+}
+
+export interface ChunkSizeTestQuery {
+  query: string;
+  expected_text: string;
+}
+
+export interface ChunkSizeScore {
+  chunk_size: number;
+  chunk_overlap: number;
+  chunk_count: number;
+  hits: number;
+  hit_rate: number;
+  mrr: number;
+}
+
+export interface ChunkSizeRecommendation {
+  recommended_chunk_size: number;
+  top_k: number;
+  scores: ChunkSizeScore[];
+}
+
+/**
+ * 分块大小调优：用项目文档或样本文件试验各候选分块大小，返回推荐值
+ */
+export async function optimizeChunkSize(
+  source: { projectId?: string; filePath?: string },
+  candidateSizes: number[],
+  testQueries: ChunkSizeTestQuery[]
+): Promise<ChunkSizeRecommendation> {
+  try {
+    return await invoke<ChunkSizeRecommendation>('optimize_chunk_size', {
+      projectId: source.projectId,
+      filePath: source.filePath,
+      candidateSizes,
+      testQueries,
+    });
+  } catch (error) {
+    console.error('分块大小调优失败:', error);
+    throw new Error(`分块大小调优失败: ${error}`);
+  }
+}
+
 /**
  * 验证文件类型
  */
@@ -138,10 +397,12 @@ export function validateFileType(file: File): boolean {
     'application/pdf',
     'application/msword',
     'application/vnd.openxmlformats-officedocument.wordprocessingml.document',
-    'application/rtf'
+    'application/rtf',
+    'text/html',
+    'application/epub+zip'
   ];
 
-  const allowedExtensions = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf'];
+  const allowedExtensions = ['.txt', '.md', '.pdf', '.doc', '.docx', '.rtf', '.html', '.htm', '.epub'];
 
   return allowedTypes.includes(file.type) ||
          allowedExtensions.some(ext => file.name.toLowerCase().endsWith(ext));
@@ -15,6 +15,21 @@ export interface ProjectResponse {
   document_count: number;
 }
 
+export interface ProjectQuotaUsage {
+  usage: { document_count: number; total_bytes: number };
+  /** null 表示不限制 */
+  quota: { max_documents: number | null; max_total_bytes: number | null };
+}
+
+export interface ProjectDetailsResponse extends ProjectResponse {
+  conversation_count: number;
+  total_chunks: number;
+  /** 文档块内容的总字节数 */
+  storage_size: number;
+  /** 当前用量与配额 */
+  quota: ProjectQuotaUsage;
+}
+
 export interface CreateProjectResponse {
   project: ProjectResponse;
 }
@@ -42,11 +57,11 @@ export async function createProject(request: CreateProjectRequest): Promise<Crea
 }
 
 /**
- * 获取所有项目列表
+ * 获取所有项目列表（默认不包含已归档的项目）
  */
-export async function getProjects(): Promise<ProjectResponse[]> {
+export async function getProjects(includeArchived = false): Promise<ProjectResponse[]> {
   try {
-    const projects = await invoke<ProjectResponse[]>('get_projects');
+    const projects = await invoke<ProjectResponse[]>('get_projects', { includeArchived });
     return projects;
   } catch (error) {
     console.error('获取项目列表失败:', error);
@@ -57,9 +72,9 @@ export async function getProjects(): Promise<ProjectResponse[]> {
 /**
  * 获取项目详情
  */
-export async function getProjectDetails(projectId: string): Promise<ProjectResponse> {
+export async function getProjectDetails(projectId: string): Promise<ProjectDetailsResponse> {
   try {
-    const project = await invoke<ProjectResponse>('get_project_details', { projectId });
+    const project = await invoke<ProjectDetailsResponse>('get_project_details', { projectId });
     return project;
   } catch (error) {
     console.error('获取项目详情失败:', error);
@@ -80,6 +95,49 @@ export async function deleteProject(projectId: string): Promise<boolean> {
   }
 }
 
+/**
+ * 归档项目（从默认列表中隐藏，保留对话和向量数据）
+ */
+export async function archiveProject(projectId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('archive_project', { projectId });
+  } catch (error) {
+    console.error('归档项目失败:', error);
+    throw new Error(`归档项目失败: ${error}`);
+  }
+}
+
+/**
+ * 恢复已归档的项目
+ */
+export async function restoreProject(projectId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('restore_project', { projectId });
+  } catch (error) {
+    console.error('恢复项目失败:', error);
+    throw new Error(`恢复项目失败: ${error}`);
+  }
+}
+
+export interface DocumentCountCorrection {
+  project_id: string;
+  project_name: string;
+  previous_count: number;
+  actual_count: number;
+}
+
+/**
+ * 按数据库重新计算所有项目的文档数，返回被修正的项目
+ */
+export async function recomputeDocumentCounts(): Promise<DocumentCountCorrection[]> {
+  try {
+    return await invoke<DocumentCountCorrection[]>('recompute_document_counts');
+  } catch (error) {
+    console.error('重新计算文档数失败:', error);
+    throw new Error(`重新计算文档数失败: ${error}`);
+  }
+}
+
 export interface RenameProjectRequest {
   project_id: string;
   new_name: string;
@@ -101,3 +159,74 @@ export async function renameProject(projectId: string, newName: string): Promise
     throw new Error(`重命名项目失败: ${error}`);
   }
 }
+
+export interface ProjectImportReport {
+  project_id: string;
+  project_name: string;
+  chunks: number;
+  conversations: number;
+  messages: number;
+  /** 向量维度与当前配置不一致时的提示，此时需要补全向量后才能语义检索 */
+  warning: string | null;
+}
+
+export interface BundleCounts {
+  documents: number;
+  chunks: number;
+  conversations: number;
+  messages: number;
+}
+
+export interface BundleInspection {
+  format_version: number;
+  app_version: string;
+  exported_at: string;
+  project_name: string;
+  embedding_model: string;
+  embedding_dimension: number | null;
+  /** 当前数据库向量列的维度 */
+  database_dimension: number;
+  counts: BundleCounts;
+  /** 包内数据解压后的字节数，用于估算导入后增加的存储 */
+  uncompressed_bytes: number;
+  /** 向量维度与数据库不一致时的提示 */
+  warning: string | null;
+}
+
+/**
+ * 将项目导出为 .mkb 文件，返回实际写入的路径
+ */
+export async function exportProject(projectId: string, outputPath: string): Promise<string> {
+  try {
+    return await invoke<string>('export_project', {
+      request: { project_id: projectId, output_path: outputPath },
+    });
+  } catch (error) {
+    console.error('导出项目失败:', error);
+    throw new Error(`导出项目失败: ${error}`);
+  }
+}
+
+/**
+ * 从 .mkb 文件导入项目（分配新的项目 ID）
+ */
+export async function importProject(bundlePath: string): Promise<ProjectImportReport> {
+  try {
+    return await invoke<ProjectImportReport>('import_project', { bundlePath });
+  } catch (error) {
+    console.error('导入项目失败:', error);
+    throw new Error(`导入项目失败: ${error}`);
+  }
+}
+
+/**
+ * 导入前检查 .mkb 文件（记录数量、大小和向量维度），不导入任何数据
+ */
+export async function inspectProjectArchive(path: string): Promise<BundleInspection> {
+  try {
+    return await invoke<BundleInspection>('inspect_project_archive', { path });
+  } catch (error) {
+    console.error('检查导出文件失败:', error);
+    throw new Error(`检查导出文件失败: ${error}`);
+  }
+}
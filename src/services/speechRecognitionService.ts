@@ -53,6 +53,36 @@ export async function recognizeSpeech(audioBlob: Blob): Promise<string> {
   }
 }
 
+export interface StreamingRecognitionResult {
+  session_id: string;
+  /** 最终识别结果，只在 isFinal 时返回 */
+  text?: string;
+}
+
+/**
+ * 流式语音识别：依次传入录音片段（16kHz 16bit 单声道 PCM）
+ *
+ * 第一段不传 sessionId，之后使用返回的 session_id；最后一段设置 isFinal。
+ * 中间结果通过 speech-partial 事件返回，最终结果通过 speech-final 事件返回，
+ * 事件内容为 { session_id, text }。
+ * 一句话识别接口不支持原生流式识别（streaming_supported 为 false），中间结果是累积音频后重新识别得到的。
+ * 会话闲置超过 2 分钟会被清理。
+ */
+export async function startStreamingRecognition(
+  audioChunk: Blob,
+  sessionId?: string,
+  isFinal = false
+): Promise<StreamingRecognitionResult> {
+  const base64Audio = await blobToBase64(audioChunk);
+  return await invoke<StreamingRecognitionResult>('start_streaming_recognition', {
+    request: {
+      session_id: sessionId ?? null,
+      audio_chunk: base64Audio,
+      is_final: isFinal,
+    },
+  });
+}
+
 /**
  * 检查语音识别服务配置
  */
@@ -60,17 +90,22 @@ export async function checkSpeechConfig(): Promise<{
   configured: boolean;
   provider?: string;
   message?: string;
+  streaming_supported?: boolean;
 }> {
   try {
-    const result = await invoke<{ configured: boolean; provider?: string; message?: string }>(
-      'check_speech_config'
-    );
+    const result = await invoke<{
+      configured: boolean;
+      provider?: string;
+      message?: string;
+      streaming_supported?: boolean;
+    }>('check_speech_config');
     return result;
   } catch (error) {
     console.error('检查语音配置失败:', error);
     return {
       configured: false,
       message: '无法检查配置',
+      streaming_supported: false,
     };
   }
 }